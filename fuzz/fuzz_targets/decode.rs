@@ -0,0 +1,33 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Decodes arbitrary bytes as each of the chain structures received from the
+//! network. Decoding must never panic, and whatever decodes must encode to
+//! bytes decoding to the same value.
+
+#![no_main]
+
+use std::fmt::Debug;
+
+use forest_filecoin::fuzzing_private::{BlockHeader, Message, Receipt, SignedMessage};
+use fvm_ipld_encoding::{from_slice, to_vec};
+use libfuzzer_sys::fuzz_target;
+use serde::{de::DeserializeOwned, Serialize};
+
+fn round_trip<T>(data: &[u8])
+where
+    T: Serialize + DeserializeOwned + PartialEq + Debug,
+{
+    if let Ok(decoded) = from_slice::<T>(data) {
+        let encoded = to_vec(&decoded).expect("a decoded value must encode");
+        let decoded_again = from_slice::<T>(&encoded).expect("an encoded value must decode");
+        assert_eq!(decoded, decoded_again);
+    }
+}
+
+fuzz_target!(|data: &[u8]| {
+    round_trip::<BlockHeader>(data);
+    round_trip::<Message>(data);
+    round_trip::<SignedMessage>(data);
+    round_trip::<Receipt>(data);
+});
@@ -4,6 +4,7 @@ data_dir = "/forest_data"
 
 [network]
 kademlia = false
+mdns = true
 target_peer_count = 1
 
 # Note that this has to come last. The actual TOML file will have
@@ -27,13 +27,16 @@ RPC_ENDPOINTS=("WalletList" "WalletBalance" "WalletDefaultAddress" "WalletExport
 RPC_ENDPOINTS+=("WalletSetDefault" "WalletSign" "WalletVerify")
 
 # Sync
-RPC_ENDPOINTS+=("SyncCheckBad" "SyncMarkBad" "SyncState" "SyncSubmitBlock")
+RPC_ENDPOINTS+=("SyncCheckBad" "SyncMarkBad" "SyncUnmarkBad" "SyncUnmarkAllBad" "SyncState" "SyncSubmitBlock" "SyncCheckpoint")
 
 # Message Pool
 RPC_ENDPOINTS+=("MpoolEstimateGasPrice" "MpoolGetNonce" "MpoolPending" "MpoolPush" "MpoolPushMessage" "MpoolSelect")
 
+# Miner
+RPC_ENDPOINTS+=("MinerCreateBlock")
+
 # Chain
-RPC_ENDPOINTS+=("ChainGetMessage" "ChainReadObj" "ChainHasObj" "ChainGetBlockMessages" "ChainGetTipsetByHeight" "ChainGetGenesis")
+RPC_ENDPOINTS+=("ChainGetMessage" "ChainReadObj" "ChainHasObj" "ChainGetBlockMessages" "ChainGetParentMessages" "ChainGetParentReceipts" "ChainGetTipsetByHeight" "ChainGetGenesis")
 RPC_ENDPOINTS+=("ChainHead" "ChainHeadSubscription" "ChainNotify" "ChainTipSetWeight" "ChainGetBlock" "ChainGetTipSet")
 RPC_ENDPOINTS+=("ChainGetRandomnessFromTickets" "ChainGetRandomnessFromBeacon")
 
@@ -41,7 +44,13 @@ RPC_ENDPOINTS+=("ChainGetRandomnessFromTickets" "ChainGetRandomnessFromBeacon")
 RPC_ENDPOINTS+=("AuthNew" "AuthVerify")
 
 # Net
-RPC_ENDPOINTS+=("NetAddrsListen" "NetPeers" "NetConnect" "NetDisconnect")
+RPC_ENDPOINTS+=("NetAddrsListen" "NetPeers" "NetInfo" "NetAutoNatStatus" "NetConnect" "NetDisconnect" "NetAddPeer" "NetRemovePeer" "NetBandwidthStats" "NetBandwidthStatsByPeer" "NetBandwidthStatsByProtocol")
+
+# Eth
+RPC_ENDPOINTS+=("FilecoinAddressToEthAddress" "EthAddressToFilecoinAddress")
+
+# F3
+RPC_ENDPOINTS+=("F3GetCertificate" "F3GetLatestCertificate")
 
 # Common
 RPC_ENDPOINTS+=("Version")
@@ -55,7 +64,8 @@ RPC_ENDPOINTS+=("StateSectorGetInfo" "StateMinerProvingDeadline" "StateMinerFaul
 RPC_ENDPOINTS+=("StateMinerRecoveries" "StateMinerPartitions" "StateReplay" "StateNetworkName" "StateNetworkVersion")
 RPC_ENDPOINTS+=("StateGetActor" "StateAccountKey" "StateLookupId" "StateMarketBalance" "StateMarketDeals")
 RPC_ENDPOINTS+=("StateGetReceipt" "StateWaitMsg" "MinerCreateBlock" "StateMinerSectorAllocated" "StateMinerPreCommitDepositForPower")
-RPC_ENDPOINTS+=("StateMinerInitialPledgeCollateral" "MinerGetBaseInfo")
+RPC_ENDPOINTS+=("StateMinerInitialPledgeCollateral" "MinerGetBaseInfo" "StateExecutionTrace" "StateListExecutionTraces")
+RPC_ENDPOINTS+=("StateDecodeParams" "StateEncodeParams")
 
 
 # send requests programmatically
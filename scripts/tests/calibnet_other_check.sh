@@ -17,6 +17,9 @@ forest_check_db_stats
 $FOREST_CLI_PATH db gc
 forest_check_db_stats
 
+echo "Measuring the space reclaimed by pruning"
+$FOREST_CLI_PATH chain prune --dry-run
+
 echo "Testing js console"
 $FOREST_CLI_PATH attach --exec 'showPeers()'
 
@@ -8,6 +8,7 @@ pub mod gossip_block;
 pub mod header;
 pub mod persistence;
 pub mod ticket;
+pub mod timing;
 pub mod tipset;
 
 pub use block::*;
@@ -32,6 +33,7 @@ impl quickcheck::Arbitrary for ArbitraryCid {
 }
 #[cfg(test)]
 mod tests {
+    mod codec_vectors;
     mod header_json_test;
     mod serialization_vectors;
     mod ticket_test;
@@ -0,0 +1,112 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Golden vectors of the CBOR encoding of the chain structures, as produced
+//! by Lotus. The headers and messages are taken from the calibnet chain, the
+//! receipts cover the encodings before and after the events were introduced.
+
+use crate::blocks::{header, BlockHeader};
+use crate::json::{message, message_receipt::json::ReceiptJson};
+use crate::shim::{executor::Receipt, message::Message};
+use cid::{
+    multihash::{Code::Blake2b256, MultihashDigest},
+    Cid,
+};
+use fvm_ipld_encoding::{from_slice, to_vec, Cbor, DAG_CBOR};
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+struct Vectors {
+    block_headers: Vec<BlockCase>,
+    messages: Vec<BlockCase>,
+    receipts: Vec<ReceiptCase>,
+}
+
+#[derive(Deserialize)]
+struct BlockCase {
+    cid: String,
+    cbor_hex: String,
+}
+
+#[derive(Deserialize)]
+struct ReceiptCase {
+    description: String,
+    cbor_hex: String,
+}
+
+fn vectors() -> Vectors {
+    serde_json::from_str(include_str!("codec_vectors.json"))
+        .expect("Test vector deserialization failed")
+}
+
+#[test]
+fn header_vectors_round_trip() {
+    for BlockCase { cid, cbor_hex } in vectors().block_headers {
+        let bytes = hex::decode(&cbor_hex).unwrap();
+        let cid: Cid = cid.parse().unwrap();
+        assert_eq!(Cid::new_v1(DAG_CBOR, Blake2b256.digest(&bytes)), cid);
+
+        let decoded: BlockHeader = from_slice(&bytes).unwrap();
+        assert_eq!(hex::encode(to_vec(&decoded).unwrap()), cbor_hex);
+        assert_eq!(decoded.cid(), &cid);
+
+        let json = serde_json::to_string(&header::json::BlockHeaderJsonRef(&decoded)).unwrap();
+        let header::json::BlockHeaderJson(from_json) = serde_json::from_str(&json).unwrap();
+        assert_eq!(hex::encode(to_vec(&from_json).unwrap()), cbor_hex);
+    }
+}
+
+#[test]
+fn message_vectors_round_trip() {
+    for BlockCase { cid, cbor_hex } in vectors().messages {
+        let bytes = hex::decode(&cbor_hex).unwrap();
+        let cid: Cid = cid.parse().unwrap();
+
+        let decoded: Message = from_slice(&bytes).unwrap();
+        assert_eq!(hex::encode(to_vec(&decoded).unwrap()), cbor_hex);
+        assert_eq!(decoded.cid().unwrap(), cid);
+
+        let json = serde_json::to_string(&message::json::MessageJsonRef(&decoded)).unwrap();
+        let message::json::MessageJson(from_json) = serde_json::from_str(&json).unwrap();
+        assert_eq!(hex::encode(to_vec(&from_json).unwrap()), cbor_hex);
+    }
+}
+
+#[test]
+fn receipt_vectors_round_trip() {
+    for ReceiptCase {
+        description,
+        cbor_hex,
+    } in vectors().receipts
+    {
+        let bytes = hex::decode(&cbor_hex).unwrap();
+        let decoded: Receipt = from_slice(&bytes).unwrap();
+        assert_eq!(
+            hex::encode(to_vec(&decoded).unwrap()),
+            cbor_hex,
+            "{description}"
+        );
+
+        // The JSON encoding doesn't tell the versions of the receipts apart
+        let json = serde_json::to_string(&ReceiptJson(decoded.clone())).unwrap();
+        let ReceiptJson(from_json) = serde_json::from_str(&json).unwrap();
+        assert_eq!(from_json.exit_code(), decoded.exit_code(), "{description}");
+        assert_eq!(
+            from_json.return_data(),
+            decoded.return_data(),
+            "{description}"
+        );
+        assert_eq!(from_json.gas_used(), decoded.gas_used(), "{description}");
+        assert_eq!(
+            from_json.events_root(),
+            decoded.events_root(),
+            "{description}"
+        );
+    }
+}
+
+#[test]
+fn receipts_of_3_or_4_elements_only() {
+    assert!(from_slice::<Receipt>(&hex::decode("820040").unwrap()).is_err());
+    assert!(from_slice::<Receipt>(&hex::decode("8500401903e8f600").unwrap()).is_err());
+}
@@ -0,0 +1,168 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Rules on the timestamps of the blocks. The timestamp of a block isn't
+//! chosen by its miner: it follows from the earliest timestamp of its parents
+//! and the number of epochs since, so that a miner can't grind on parents or
+//! timestamps to improve its chances. Blocks are still accepted when the clock
+//! of the node lags a little behind the ones of the miners, within the
+//! allowed drift.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::{BlockHeader, Tipset};
+use crate::networks::ChainConfig;
+use crate::shim::clock::ChainEpoch;
+
+/// Drift allowed between the clocks of the miners and of the node, the same as
+/// in Lotus
+pub const DEFAULT_ALLOWABLE_CLOCK_DRIFT_SECS: u64 = fvm_shared::ALLOWABLE_CLOCK_DRIFT;
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum TimingError {
+    #[error("Block had the wrong timestamp: {got} != {expected}")]
+    UnexpectedTimestamp { got: u64, expected: u64 },
+    #[error("Block epoch {epoch} is not after the one of its parents {parent_epoch}")]
+    EpochNotAfterParents {
+        epoch: ChainEpoch,
+        parent_epoch: ChainEpoch,
+    },
+    #[error("Block received from the future: now = {now}, block = {timestamp}")]
+    FromFuture { now: u64, timestamp: u64 },
+}
+
+/// Timing parameters of a network, see [`ChainConfig`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlockTiming {
+    /// Duration of an epoch
+    pub block_delay_secs: u64,
+    /// Time the blocks of an epoch are expected to take to reach the network
+    pub propagation_delay_secs: u64,
+    /// Drift allowed between the clocks of the miners and of the node
+    pub allowable_clock_drift_secs: u64,
+}
+
+impl From<&ChainConfig> for BlockTiming {
+    fn from(config: &ChainConfig) -> Self {
+        Self {
+            block_delay_secs: config.block_delay_secs,
+            propagation_delay_secs: config.propagation_delay_secs,
+            allowable_clock_drift_secs: config.allowable_clock_drift_secs,
+        }
+    }
+}
+
+impl BlockTiming {
+    /// Timestamp of a block of the given epoch on top of `parent`, the null
+    /// rounds in between included.
+    pub fn timestamp(&self, parent: &Tipset, epoch: ChainEpoch) -> Result<u64, TimingError> {
+        if epoch <= parent.epoch() {
+            return Err(TimingError::EpochNotAfterParents {
+                epoch,
+                parent_epoch: parent.epoch(),
+            });
+        }
+        Ok(parent.min_timestamp() + self.block_delay_secs * (epoch - parent.epoch()) as u64)
+    }
+
+    /// Checks that the timestamp of the block is exactly the one its epoch
+    /// gives on top of its parents.
+    pub fn check_timestamp(
+        &self,
+        header: &BlockHeader,
+        parent: &Tipset,
+    ) -> Result<(), TimingError> {
+        let expected = self.timestamp(parent, header.epoch())?;
+        if header.timestamp() != expected {
+            return Err(TimingError::UnexpectedTimestamp {
+                got: header.timestamp(),
+                expected,
+            });
+        }
+        Ok(())
+    }
+
+    /// Checks that the timestamp isn't ahead of `now` by more than the allowed
+    /// clock drift.
+    pub fn check_clock_drift(&self, timestamp: u64, now: u64) -> Result<(), TimingError> {
+        if timestamp > now + self.allowable_clock_drift_secs {
+            return Err(TimingError::FromFuture { now, timestamp });
+        }
+        Ok(())
+    }
+
+    /// Returns true if a block received at `now` arrived later than the
+    /// propagation delay after its timestamp.
+    pub fn is_late(&self, timestamp: u64, now: u64) -> bool {
+        now > timestamp + self.propagation_delay_secs
+    }
+}
+
+/// Current UNIX time in seconds, to compare with the timestamps of the blocks
+pub fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Retrieved system time before UNIX epoch")
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shim::address::Address;
+
+    fn timing() -> BlockTiming {
+        BlockTiming {
+            block_delay_secs: 30,
+            propagation_delay_secs: 6,
+            allowable_clock_drift_secs: 1,
+        }
+    }
+
+    fn header(epoch: ChainEpoch, timestamp: u64) -> BlockHeader {
+        BlockHeader::builder()
+            .miner_address(Address::new_id(0))
+            .epoch(epoch)
+            .timestamp(timestamp)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn timestamp_follows_parents() {
+        let parent = Tipset::from(header(10, 1000));
+        assert_eq!(timing().timestamp(&parent, 11), Ok(1030));
+        // Two null rounds
+        assert_eq!(timing().timestamp(&parent, 13), Ok(1090));
+        assert!(timing().check_timestamp(&header(13, 1090), &parent).is_ok());
+        assert_eq!(
+            timing().check_timestamp(&header(13, 1089), &parent),
+            Err(TimingError::UnexpectedTimestamp {
+                got: 1089,
+                expected: 1090
+            })
+        );
+        assert!(matches!(
+            timing().check_timestamp(&header(10, 1000), &parent),
+            Err(TimingError::EpochNotAfterParents { .. })
+        ));
+    }
+
+    #[test]
+    fn clock_drift_is_tolerated() {
+        assert!(timing().check_clock_drift(1001, 1000).is_ok());
+        assert_eq!(
+            timing().check_clock_drift(1002, 1000),
+            Err(TimingError::FromFuture {
+                now: 1000,
+                timestamp: 1002
+            })
+        );
+    }
+
+    #[test]
+    fn late_blocks() {
+        assert!(!timing().is_late(1000, 1006));
+        assert!(timing().is_late(1000, 1007));
+    }
+}
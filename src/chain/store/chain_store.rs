@@ -1,7 +1,14 @@
 // Copyright 2019-2023 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
-use std::{num::NonZeroUsize, ops::DerefMut, path::Path, sync::Arc, time::SystemTime};
+use std::{
+    collections::VecDeque,
+    num::NonZeroUsize,
+    ops::DerefMut,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::SystemTime,
+};
 
 use crate::beacon::{BeaconEntry, IGNORE_DRAND_VAR};
 use crate::blocks::{Block, BlockHeader, FullTipset, Tipset, TipsetKeys, TxMeta};
@@ -9,15 +16,12 @@ use crate::interpreter::BlockMessages;
 use crate::ipld::{walk_snapshot, WALK_SNAPSHOT_PROGRESS_EXPORT};
 use crate::libp2p_bitswap::{BitswapStoreRead, BitswapStoreReadWrite};
 use crate::message::{ChainMessage, Message as MessageTrait, SignedMessage};
+use crate::message_pool::split_block_messages;
 use crate::metrics;
 use crate::networks::{ChainConfig, NetworkChain};
 use crate::shim::clock::ChainEpoch;
 use crate::shim::{
-    address::Address,
-    crypto::{Signature, SignatureType},
-    econ::TokenAmount,
-    executor::Receipt,
-    message::Message,
+    address::Address, crypto::Signature, econ::TokenAmount, executor::Receipt, message::Message,
     state_tree::StateTree,
 };
 use crate::utils::{
@@ -31,7 +35,6 @@ use crate::utils::{
 use ahash::{HashMap, HashMapExt, HashSet};
 use anyhow::{Context, Result};
 use async_compression::futures::write::ZstdEncoder;
-use bls_signatures::Serialize as SerializeBls;
 use cid::Cid;
 use digest::Digest;
 use futures::{io::BufWriter, AsyncWrite};
@@ -51,24 +54,28 @@ use tokio::sync::{
 };
 
 use super::{
+    forks::{ForkHead, ForkTracker},
+    head_changes::{reorg_ops, HeadChange, HeadChangeSubscriber},
     index::{checkpoint_tipsets, ChainIndex},
     tipset_tracker::TipsetTracker,
     Error,
 };
-use crate::chain::Scale;
+use crate::chain::{Scale, Weight};
 
 // A cap on the size of the future_sink
 const SINK_CAP: usize = 200;
 
+/// Head changes over more epochs, e.g. while catching up with the network, are
+/// published as the new head applied only, rather than every tipset
+const MAX_HEAD_CHANGE_EPOCHS: ChainEpoch = SINK_CAP as ChainEpoch / 2;
+
 const DEFAULT_TIPSET_CACHE_SIZE: NonZeroUsize = nonzero!(8192usize);
 
-/// `Enum` for `pubsub` channel that defines message type variant and data
-/// contained in message type.
-#[derive(Clone, Debug)]
-pub enum HeadChange {
-    Current(Arc<Tipset>),
-    Apply(Arc<Tipset>),
-    Revert(Arc<Tipset>),
+/// A tipset that can no longer be reverted, e.g. a checkpoint
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FinalizedTipset {
+    pub epoch: ChainEpoch,
+    pub key: TipsetKeys,
 }
 
 /// Stores chain data such as heaviest tipset and cached tipset info at each
@@ -90,6 +97,9 @@ pub struct ChainStore<DB> {
     /// Tracks blocks for the purpose of forming tipsets.
     tipset_tracker: TipsetTracker<DB>,
 
+    /// Tracks the heads of the forks and the weights of the recent tipsets.
+    forks: Mutex<ForkTracker>,
+
     /// File backed genesis block CID
     file_backed_genesis: Mutex<FileBacked<Cid>>,
 
@@ -101,6 +111,12 @@ pub struct ChainStore<DB> {
 
     /// File backed chain metadata
     file_backed_chain_meta: Arc<Mutex<FileBacked<ChainMeta>>>,
+
+    /// Latest finalized tipset, the chain is never reorganized below it
+    finalized_tipset: Mutex<Option<FinalizedTipset>>,
+
+    /// File the checkpoint set at runtime is persisted to
+    checkpoint_path: PathBuf,
 }
 
 impl<DB> BitswapStoreRead for ChainStore<DB>
@@ -160,6 +176,13 @@ where
             head_store
         });
         let validated_blocks = Mutex::new(HashSet::default());
+        let latest_checkpoint =
+            chain_config
+                .latest_checkpoint()
+                .map(|checkpoint| FinalizedTipset {
+                    epoch: checkpoint.epoch,
+                    key: TipsetKeys::new(checkpoint.tipset.clone()),
+                });
         let file_backed_chain_meta = Arc::new(Mutex::new(FileBacked::load_from_file_or_create(
             chain_data_root.join("meta.yaml"),
             ChainMeta::default,
@@ -169,6 +192,7 @@ where
         let cs = Self {
             publisher,
             chain_index: ChainIndex::new(ts_cache.clone(), db.clone()),
+            forks: Mutex::new(ForkTracker::new(chain_config.policy.chain_finality)),
             tipset_tracker: TipsetTracker::new(db.clone(), chain_config),
             db,
             ts_cache,
@@ -176,9 +200,15 @@ where
             file_backed_heaviest_tipset_keys,
             validated_blocks,
             file_backed_chain_meta,
+            finalized_tipset: Default::default(),
+            checkpoint_path: chain_data_root.join("CHECKPOINT"),
         };
 
         cs.set_genesis(genesis_block_header)?;
+        if let Some(checkpoint) = latest_checkpoint {
+            cs.set_finalized_tipset(checkpoint);
+        }
+        cs.load_checkpoint()?;
 
         Ok(cs)
     }
@@ -189,17 +219,156 @@ where
     }
 
     /// Sets heaviest tipset within `ChainStore` and store its tipset keys in
-    /// `{crate::chain_store}/HEAD`
+    /// `{crate::chain_store}/HEAD`. The tipsets reverted and applied are
+    /// published in order, see [`reorg_ops`].
     pub fn set_heaviest_tipset(&self, ts: Arc<Tipset>) -> Result<(), Error> {
-        self.file_backed_heaviest_tipset_keys
-            .lock()
-            .set_inner(ts.key().clone())?;
-        if self.publisher.send(HeadChange::Apply(ts)).is_err() {
-            debug!("did not publish head change, no active receivers");
+        // The head stays locked until the changes are published, so that
+        // concurrent head changes don't interleave
+        let mut head = self.file_backed_heaviest_tipset_keys.lock();
+        let changes = match tipset_from_keys(&self.ts_cache, &self.db, head.inner()) {
+            Ok(current)
+                if current.epoch().abs_diff(ts.epoch()) <= MAX_HEAD_CHANGE_EPOCHS as u64 =>
+            {
+                reorg_ops(
+                    |tsk| tipset_from_keys(&self.ts_cache, &self.db, tsk),
+                    current,
+                    ts.clone(),
+                )
+                .unwrap_or_else(|e| {
+                    warn!("Failed to find the tipsets reverted by the head change: {e}");
+                    vec![HeadChange::Apply(ts.clone())]
+                })
+            }
+            _ => vec![HeadChange::Apply(ts.clone())],
+        };
+        head.set_inner(ts.key().clone())?;
+        for change in changes {
+            if self.publisher.send(change).is_err() {
+                debug!("did not publish head change, no active receivers");
+                break;
+            }
         }
         Ok(())
     }
 
+    /// Subscribes to the head changes, replaying first the tipsets applied on
+    /// the current chain since `epoch`, up to `MAX_HEAD_CHANGE_EPOCHS` ago.
+    pub fn subscribe_head_changes_from(
+        &self,
+        epoch: ChainEpoch,
+    ) -> Result<HeadChangeSubscriber, Error> {
+        let head = self.file_backed_heaviest_tipset_keys.lock();
+        let receiver = self.publisher.subscribe();
+        let mut ts = tipset_from_keys(&self.ts_cache, &self.db, head.inner())?;
+        if ts.epoch() - epoch > MAX_HEAD_CHANGE_EPOCHS {
+            return Err(Error::Other(format!(
+                "Cannot replay the head changes from epoch {epoch}, the head is at {}",
+                ts.epoch()
+            )));
+        }
+        let mut replay = VecDeque::new();
+        while ts.epoch() >= epoch {
+            replay.push_front(HeadChange::Apply(ts.clone()));
+            if ts.epoch() == 0 {
+                break;
+            }
+            ts = tipset_from_keys(&self.ts_cache, &self.db, ts.parents())?;
+        }
+        Ok(HeadChangeSubscriber::new(replay, receiver))
+    }
+
+    /// Returns the latest finalized tipset, if any.
+    pub fn finalized_tipset(&self) -> Option<FinalizedTipset> {
+        self.finalized_tipset.lock().clone()
+    }
+
+    /// Sets the latest finalized tipset. Finality only moves forward, older
+    /// tipsets are ignored.
+    pub fn set_finalized_tipset(&self, finalized: FinalizedTipset) {
+        let mut current = self.finalized_tipset.lock();
+        if current
+            .as_ref()
+            .map_or(true, |current| current.epoch < finalized.epoch)
+        {
+            info!(
+                "New finalized tipset {} (EPOCH = {})",
+                finalized.key, finalized.epoch
+            );
+            metrics::FINALIZED_EPOCH.set(finalized.epoch as u64);
+            *current = Some(finalized);
+        }
+    }
+
+    /// Pins the chain to the given tipset, which becomes the finalized tipset
+    /// and is persisted across restarts. The head is reset to the checkpoint
+    /// when it is on a different chain.
+    pub fn set_checkpoint(&self, ts: Arc<Tipset>) -> Result<(), Error> {
+        if let Some(finalized) = self.finalized_tipset() {
+            if ts.epoch() <= finalized.epoch {
+                return Err(Error::Other(format!(
+                    "Checkpoint at epoch {} is not above the finalized epoch {}",
+                    ts.epoch(),
+                    finalized.epoch
+                )));
+            }
+            if !self.is_consistent_with_finality(ts.clone())? {
+                return Err(Error::Other(format!(
+                    "Checkpoint {} reverts the finalized tipset",
+                    ts.key()
+                )));
+            }
+        }
+        FileBacked::new(ts.key().clone(), self.checkpoint_path.clone()).sync()?;
+        self.set_finalized_tipset(FinalizedTipset {
+            epoch: ts.epoch(),
+            key: ts.key().clone(),
+        });
+        if !self.is_consistent_with_finality(self.heaviest_tipset())? {
+            warn!(
+                "Head is not on the chain of checkpoint {}, resetting it",
+                ts.key()
+            );
+            self.set_heaviest_tipset(ts)?;
+        }
+        Ok(())
+    }
+
+    /// Restores the checkpoint persisted by [`ChainStore::set_checkpoint`]
+    fn load_checkpoint(&self) -> Result<(), Error> {
+        if !self.checkpoint_path.is_file() {
+            return Ok(());
+        }
+        let keys: TipsetKeys = FileBacked::load_from_file_or_create(
+            self.checkpoint_path.clone(),
+            || TipsetKeys::new(vec![]),
+            None,
+        )?
+        .inner()
+        .clone();
+        match self.tipset_from_keys(&keys) {
+            Ok(ts) => self.set_finalized_tipset(FinalizedTipset {
+                epoch: ts.epoch(),
+                key: keys,
+            }),
+            Err(e) => warn!("Ignoring checkpoint {keys}: {e}"),
+        }
+        Ok(())
+    }
+
+    /// Checks whether the chain ending at the given tipset agrees with the
+    /// finalized tipset. Chains that have not reached the finalized epoch yet
+    /// are accepted.
+    pub fn is_consistent_with_finality(&self, ts: Arc<Tipset>) -> Result<bool, Error> {
+        let Some(finalized) = self.finalized_tipset() else {
+            return Ok(true);
+        };
+        if ts.epoch() < finalized.epoch {
+            return Ok(true);
+        }
+        let ancestor = self.tipset_by_height(finalized.epoch, ts, true)?;
+        Ok(ancestor.key() == &finalized.key)
+    }
+
     /// Writes genesis to `blockstore`.
     pub fn set_genesis(&self, header: &BlockHeader) -> Result<Cid, Error> {
         self.file_backed_genesis.lock().set_inner(*header.cid())?;
@@ -219,7 +388,7 @@ where
     /// with other compatible tracked headers.
     pub fn put_tipset<S>(&self, ts: &Tipset) -> Result<(), Error>
     where
-        S: Scale,
+        S: Scale + 'static,
     {
         // TODO: we could add the blocks of `ts` to the tipset tracker from here,
         // making `add_to_tipset_tracker` redundant and decreasing the number of
@@ -278,22 +447,50 @@ where
     /// tipset
     fn update_heaviest<S>(&self, ts: Arc<Tipset>) -> Result<(), Error>
     where
-        S: Scale,
+        S: Scale + 'static,
     {
-        // Calculate heaviest weight before matching to avoid deadlock with mutex
-        let heaviest_weight = S::weight(self.blockstore(), &self.heaviest_tipset())?;
-
-        let new_weight = S::weight(self.blockstore(), ts.as_ref())?;
-        let curr_weight = heaviest_weight;
+        if !self.is_consistent_with_finality(ts.clone())? {
+            warn!(
+                "Ignoring tipset {} (EPOCH = {}) that reverts the finalized tipset",
+                ts.key(),
+                ts.epoch()
+            );
+            return Ok(());
+        }
+        let heaviest = self.heaviest_tipset();
+        let curr_weight = self.weight::<S>(&heaviest)?;
+        let new_weight = self.weight::<S>(&ts)?;
+        self.forks.lock().add(ts.clone(), new_weight.clone());
 
         if new_weight > curr_weight {
-            // TODO potentially need to deal with re-orgs here
             info!("New heaviest tipset! {} (EPOCH = {})", ts.key(), ts.epoch());
             self.set_heaviest_tipset(ts)?;
         }
         Ok(())
     }
 
+    /// Returns the weight of the tipset, cached for the recent tipsets.
+    pub fn weight<S>(&self, ts: &Tipset) -> Result<Weight, Error>
+    where
+        S: Scale + 'static,
+    {
+        if let Some(weight) = self.forks.lock().cached_weight::<S>(ts.key()) {
+            return Ok(weight);
+        }
+        // The lock isn't held while loading the power table
+        let weight = S::weight(self.blockstore(), ts)?;
+        self.forks
+            .lock()
+            .cache_weight::<S>(ts.key().clone(), weight.clone());
+        Ok(weight)
+    }
+
+    /// Returns the heads of the forks put in the store within the chain
+    /// finality, from the heaviest one.
+    pub fn fork_heads(&self) -> Vec<ForkHead> {
+        self.forks.lock().heads()
+    }
+
     /// Checks metadata file if block has already been validated.
     pub fn is_block_validated(&self, cid: &Cid) -> bool {
         let validated = self.validated_blocks.lock().contains(cid);
@@ -638,7 +835,7 @@ where
     }
 }
 
-pub(in crate::chain) type TipsetCache = Mutex<LruCache<TipsetKeys, Arc<Tipset>>>;
+pub(crate) type TipsetCache = Mutex<LruCache<TipsetKeys, Arc<Tipset>>>;
 
 /// Loads a tipset from memory given the tipset keys and cache.
 pub(in crate::chain) fn tipset_from_keys<BS>(
@@ -895,25 +1092,22 @@ pub struct PersistedBlockMessages {
 /// Partition the messages into SECP and BLS variants, store them individually
 /// in the IPLD store, and the corresponding `TxMeta` as well, returning its CID
 /// so that it can be put in a block header. Also return the aggregated BLS
-/// signature of all BLS messages.
+/// signature of all BLS messages, as computed by [`split_block_messages`].
 pub fn persist_block_messages<DB: Blockstore>(
     db: &DB,
     messages: Vec<&SignedMessage>,
 ) -> anyhow::Result<PersistedBlockMessages> {
-    let mut bls_cids = Vec::new();
-    let mut secp_cids = Vec::new();
-
-    let mut bls_sigs = Vec::new();
-    for msg in messages {
-        if msg.signature().signature_type() == SignatureType::BLS {
-            let c = db.put_cbor_default(&msg.message)?;
-            bls_cids.push(c);
-            bls_sigs.push(&msg.signature);
-        } else {
-            let c = db.put_cbor_default(&msg)?;
-            secp_cids.push(c);
-        }
-    }
+    let split = split_block_messages(messages)?;
+    let bls_cids = split
+        .bls
+        .into_iter()
+        .map(|msg| db.put_cbor_default(msg))
+        .collect::<Result<Vec<_>, _>>()?;
+    let secp_cids = split
+        .secp
+        .into_iter()
+        .map(|msg| db.put_cbor_default(msg))
+        .collect::<Result<Vec<_>, _>>()?;
 
     let bls_msg_root = Amt::new_from_iter(db, bls_cids.iter().copied())?;
     let secp_msg_root = Amt::new_from_iter(db, secp_cids.iter().copied())?;
@@ -923,33 +1117,18 @@ pub fn persist_block_messages<DB: Blockstore>(
         secp_message_root: secp_msg_root,
     })?;
 
-    let bls_agg = if bls_sigs.is_empty() {
-        Signature::new_bls(vec![])
-    } else {
-        Signature::new_bls(
-            bls_signatures::aggregate(
-                &bls_sigs
-                    .iter()
-                    .map(|s| s.bytes())
-                    .map(bls_signatures::Signature::from_bytes)
-                    .collect::<Result<Vec<_>, _>>()?,
-            )
-            .unwrap()
-            .as_bytes(),
-        )
-    };
-
     Ok(PersistedBlockMessages {
         msg_cid: mmcid,
         secp_cids,
         bls_cids,
-        bls_agg,
+        bls_agg: split.bls_aggregate,
     })
 }
 
 #[cfg(test)]
 mod tests {
     use crate::shim::address::Address;
+    use crate::test_utils::ChainGenerator;
     use cid::{
         multihash::{
             Code::{Blake2b256, Identity},
@@ -1000,4 +1179,153 @@ mod tests {
         cs.mark_block_as_validated(&cid);
         assert!(cs.is_block_validated(&cid));
     }
+
+    #[test]
+    fn checkpoint_is_persisted() {
+        let db = crate::db::MemoryDB::default();
+        let chain_config = Arc::new(ChainConfig::default());
+        let gen_block = BlockHeader::builder()
+            .miner_address(Address::new_id(0))
+            .build()
+            .unwrap();
+        let block = BlockHeader::builder()
+            .miner_address(Address::new_id(0))
+            .parents(TipsetKeys::new(vec![*gen_block.cid()]))
+            .epoch(1)
+            .build()
+            .unwrap();
+
+        let chain_data_root = TempDir::new().unwrap();
+        let cs = ChainStore::new(
+            db.clone(),
+            chain_config.clone(),
+            &gen_block,
+            chain_data_root.path(),
+        )
+        .unwrap();
+        persist_objects(cs.blockstore(), &[&block]).unwrap();
+        let ts = cs
+            .tipset_from_keys(&TipsetKeys::new(vec![*block.cid()]))
+            .unwrap();
+        cs.set_checkpoint(ts.clone()).unwrap();
+        // Finality only moves forward
+        let genesis = cs
+            .tipset_from_keys(&TipsetKeys::new(vec![*gen_block.cid()]))
+            .unwrap();
+        assert!(cs.set_checkpoint(genesis).is_err());
+
+        let cs = ChainStore::new(db, chain_config, &gen_block, chain_data_root.path()).unwrap();
+        assert_eq!(
+            cs.finalized_tipset(),
+            Some(FinalizedTipset {
+                epoch: 1,
+                key: ts.key().clone(),
+            })
+        );
+    }
+
+    #[test]
+    fn tipset_by_height_over_null_rounds() {
+        let db = crate::db::MemoryDB::default();
+        let mut generator = ChainGenerator::new();
+        let genesis = generator.genesis().clone();
+        let chain_data_root = TempDir::new().unwrap();
+        let cs = ChainStore::new(
+            db,
+            Arc::new(ChainConfig::default()),
+            genesis.min_ticket_block(),
+            chain_data_root.path(),
+        )
+        .unwrap();
+
+        let before = generator.mine(&genesis, &[1000], 0);
+        let after = generator.mine(&before, &[1000, 1001], 3);
+        let head = generator.mine(&after, &[1001], 0);
+        for tipset in [&before, &after, &head] {
+            persist_objects(cs.blockstore(), tipset.blocks()).unwrap();
+        }
+        assert_eq!(after.epoch(), 5);
+
+        for height in 2..5 {
+            assert_eq!(
+                cs.tipset_by_height(height, head.clone(), true).unwrap(),
+                before
+            );
+            assert_eq!(
+                cs.tipset_by_height(height, head.clone(), false).unwrap(),
+                after
+            );
+        }
+        assert_eq!(cs.tipset_by_height(1, head.clone(), false).unwrap(), before);
+        assert_eq!(cs.tipset_by_height(5, head.clone(), true).unwrap(), after);
+        assert_eq!(cs.tipset_by_height(0, head.clone(), true).unwrap(), genesis);
+        assert!(cs.tipset_by_height(7, head, true).is_err());
+    }
+
+    #[test]
+    fn head_changes_are_published_in_order() {
+        let db = crate::db::MemoryDB::default();
+        let mut generator = ChainGenerator::new();
+        let genesis = generator.genesis().clone();
+        let chain_data_root = TempDir::new().unwrap();
+        let cs = ChainStore::new(
+            db,
+            Arc::new(ChainConfig::default()),
+            genesis.min_ticket_block(),
+            chain_data_root.path(),
+        )
+        .unwrap();
+        let main = generator.extend(&genesis, &[1000], 4);
+        let fork = generator.extend(&main[1], &[1001], 3);
+        for tipset in main.iter().chain(&fork) {
+            persist_objects(cs.blockstore(), tipset.blocks()).unwrap();
+        }
+
+        let mut head_changes = cs.publisher().subscribe();
+        cs.set_heaviest_tipset(main[3].clone()).unwrap();
+        cs.set_heaviest_tipset(fork[2].clone()).unwrap();
+        let mut changes = Vec::new();
+        while let Ok(change) = head_changes.try_recv() {
+            changes.push(match change {
+                HeadChange::Revert(ts) => -ts.epoch(),
+                HeadChange::Apply(ts) => ts.epoch(),
+                HeadChange::Current(_) => unreachable!(),
+            });
+        }
+        assert_eq!(changes, vec![1, 2, 3, 4, -4, -3, 3, 4, 5]);
+    }
+
+    #[tokio::test]
+    async fn head_changes_are_replayed() {
+        let db = crate::db::MemoryDB::default();
+        let mut generator = ChainGenerator::new();
+        let genesis = generator.genesis().clone();
+        let chain_data_root = TempDir::new().unwrap();
+        let cs = ChainStore::new(
+            db,
+            Arc::new(ChainConfig::default()),
+            genesis.min_ticket_block(),
+            chain_data_root.path(),
+        )
+        .unwrap();
+        let main = generator.extend(&genesis, &[1000], 4);
+        for tipset in &main {
+            persist_objects(cs.blockstore(), tipset.blocks()).unwrap();
+        }
+        cs.set_heaviest_tipset(main[3].clone()).unwrap();
+
+        let mut subscriber = cs.subscribe_head_changes_from(3).unwrap();
+        let next = generator.mine(&main[3], &[1000], 0);
+        persist_objects(cs.blockstore(), next.blocks()).unwrap();
+        cs.set_heaviest_tipset(next.clone()).unwrap();
+        for expected in [&main[2], &main[3], &next] {
+            assert!(matches!(
+                subscriber.recv().await,
+                Ok(HeadChange::Apply(ts)) if &ts == expected
+            ));
+        }
+
+        assert!(cs.subscribe_head_changes_from(-1000).is_err());
+        assert!(cs.subscribe_head_changes_from(0).is_ok());
+    }
 }
@@ -0,0 +1,244 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Base fees and gas premiums paid over the last tipsets of the chain, for fee
+//! estimators. The premiums are percentiles of the effective premiums of the
+//! messages of each tipset, weighted by the gas they used, as in the
+//! `eth_feeHistory` of Lotus.
+
+use std::sync::Arc;
+
+use crate::blocks::Tipset;
+use crate::message::Message;
+use crate::shim::{clock::ChainEpoch, econ::TokenAmount, executor::Receipt};
+use cid::Cid;
+use fvm_ipld_amt::Amtv0 as Amt;
+use fvm_ipld_blockstore::Blockstore;
+use num_traits::Zero;
+use serde::{Deserialize, Serialize};
+
+use super::{compute_base_fee, ChainStore, Error};
+
+/// Maximum number of tipsets a fee history covers
+pub const MAX_FEE_HISTORY_TIPSETS: u64 = 1024;
+
+/// Maximum number of percentiles a fee history reports, as in Lotus
+pub const MAX_FEE_HISTORY_PERCENTILES: usize = 100;
+
+/// Fees of the messages of a tipset
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct TipsetFees {
+    pub epoch: ChainEpoch,
+    /// Base fee paid by the messages of the tipset
+    #[serde(with = "crate::json::token_amount::json")]
+    pub base_fee: TokenAmount,
+    /// Gas used by the messages of the tipset over the gas limit of a block,
+    /// as Lotus reports it, so above 1 for some tipsets of several blocks
+    pub gas_used_ratio: f64,
+    /// Effective premiums at the requested percentiles
+    #[serde(with = "crate::json::token_amount::json::vec")]
+    pub premiums: Vec<TokenAmount>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct FeeHistory {
+    /// Fees of the tipsets, oldest first
+    pub tipsets: Vec<TipsetFees>,
+    /// Base fee of the messages of the tipset following the newest one
+    #[serde(with = "crate::json::token_amount::json")]
+    pub next_base_fee: TokenAmount,
+}
+
+/// Returns the fees of `count` tipsets, up to `newest` included, with their
+/// premiums at the given percentiles. The history stops at genesis. The gas
+/// used by the messages of a tipset is read from the receipts of its
+/// execution, `newest_receipts` for `newest` and the parent receipts of the
+/// next tipset for the others, and reported over `block_gas_limit`.
+pub fn fee_history<DB>(
+    chain_store: &ChainStore<DB>,
+    newest: Arc<Tipset>,
+    newest_receipts: Cid,
+    count: u64,
+    percentiles: &[f64],
+    smoke_height: ChainEpoch,
+    block_gas_limit: u64,
+) -> Result<FeeHistory, Error>
+where
+    DB: Blockstore,
+{
+    if count == 0 || count > MAX_FEE_HISTORY_TIPSETS {
+        return Err(Error::Other(format!(
+            "the number of tipsets must be between 1 and {MAX_FEE_HISTORY_TIPSETS}"
+        )));
+    }
+    if percentiles.len() > MAX_FEE_HISTORY_PERCENTILES {
+        return Err(Error::Other(format!(
+            "at most {MAX_FEE_HISTORY_PERCENTILES} percentiles can be requested"
+        )));
+    }
+    if percentiles.iter().any(|p| !(0. ..=100.).contains(p))
+        || percentiles.windows(2).any(|w| w[0] > w[1])
+    {
+        return Err(Error::Other(
+            "percentiles must be increasing values between 0 and 100".into(),
+        ));
+    }
+
+    let db = chain_store.blockstore();
+    let next_base_fee = compute_base_fee(db, &newest, smoke_height)?;
+    let mut tipsets = Vec::with_capacity(count as usize);
+    let mut ts = newest;
+    let mut receipts = newest_receipts;
+    loop {
+        tipsets.push(tipset_fees(
+            db,
+            &ts,
+            &receipts,
+            percentiles,
+            block_gas_limit,
+        )?);
+        if tipsets.len() as u64 == count || ts.epoch() == 0 {
+            break;
+        }
+        receipts = *ts.blocks()[0].message_receipts();
+        ts = chain_store.tipset_from_keys(ts.parents())?;
+    }
+    tipsets.reverse();
+    Ok(FeeHistory {
+        tipsets,
+        next_base_fee,
+    })
+}
+
+/// Returns the fees of the messages of `ts`, whose execution produced the
+/// receipts at `receipts`, one per message in execution order.
+fn tipset_fees<DB>(
+    db: &DB,
+    ts: &Tipset,
+    receipts: &Cid,
+    percentiles: &[f64],
+    block_gas_limit: u64,
+) -> Result<TipsetFees, Error>
+where
+    DB: Blockstore,
+{
+    let base_fee = ts.blocks()[0].parent_base_fee().clone();
+    let messages = crate::chain::messages_for_tipset(db, ts)?;
+    let mut gas_used = Vec::with_capacity(messages.len());
+    Amt::<Receipt, _>::load(receipts, db)?.for_each(|_, receipt| {
+        gas_used.push(receipt.gas_used());
+        Ok(())
+    })?;
+    if gas_used.len() != messages.len() {
+        return Err(Error::Other(format!(
+            "tipset at epoch {} has {} messages but {} receipts",
+            ts.epoch(),
+            messages.len(),
+            gas_used.len()
+        )));
+    }
+    let premiums: Vec<(TokenAmount, u64)> = messages
+        .iter()
+        .zip(gas_used)
+        .map(|(msg, gas_used)| {
+            let premium = msg
+                .gas_premium()
+                .min(msg.gas_fee_cap() - &base_fee)
+                .max(TokenAmount::zero());
+            (premium, gas_used)
+        })
+        .collect();
+    let total_gas_used: u64 = premiums.iter().map(|(_, gas_used)| gas_used).sum();
+    Ok(TipsetFees {
+        epoch: ts.epoch(),
+        gas_used_ratio: total_gas_used as f64 / block_gas_limit as f64,
+        premiums: premium_percentiles(premiums, percentiles),
+        base_fee,
+    })
+}
+
+/// Premiums at the given percentiles of the gas used by the messages, zero
+/// for a tipset without messages.
+fn premium_percentiles(
+    mut premiums: Vec<(TokenAmount, u64)>,
+    percentiles: &[f64],
+) -> Vec<TokenAmount> {
+    premiums.sort_by(|(a, _), (b, _)| a.cmp(b));
+    let total: u64 = premiums.iter().map(|(_, gas_used)| gas_used).sum();
+    let mut result = Vec::with_capacity(percentiles.len());
+    let mut index = 0;
+    let mut cumulated = 0;
+    for percentile in percentiles {
+        let Some(last) = premiums.last() else {
+            result.push(TokenAmount::zero());
+            continue;
+        };
+        let threshold = (total as f64 * percentile / 100.) as u64;
+        while index < premiums.len() && cumulated + premiums[index].1 < threshold {
+            cumulated += premiums[index].1;
+            index += 1;
+        }
+        result.push(premiums.get(index).unwrap_or(last).0.clone());
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::MemoryDB;
+    use crate::networks::ChainConfig;
+    use crate::test_utils::ChainGenerator;
+    use tempfile::TempDir;
+
+    fn atto(amounts: &[i64]) -> Vec<TokenAmount> {
+        amounts.iter().map(|a| TokenAmount::from_atto(*a)).collect()
+    }
+
+    #[test]
+    fn percentiles_are_weighted_by_gas() {
+        let premiums = vec![
+            (TokenAmount::from_atto(300), 10),
+            (TokenAmount::from_atto(100), 70),
+            (TokenAmount::from_atto(200), 20),
+        ];
+        assert_eq!(
+            premium_percentiles(premiums, &[0., 50., 70., 80., 95., 100.]),
+            atto(&[100, 100, 100, 200, 300, 300])
+        );
+    }
+
+    #[test]
+    fn percentiles_are_capped() {
+        let generator = ChainGenerator::new();
+        let chain_data_root = TempDir::new().unwrap();
+        let chain_config = Arc::new(ChainConfig::default());
+        let chain_store = ChainStore::new(
+            MemoryDB::default(),
+            chain_config.clone(),
+            generator.genesis().min_ticket_block(),
+            chain_data_root.path(),
+        )
+        .unwrap();
+        let receipts = *generator.genesis().blocks()[0].message_receipts();
+        let percentiles = vec![50.; MAX_FEE_HISTORY_PERCENTILES + 1];
+        let result = fee_history(
+            &chain_store,
+            generator.genesis().clone(),
+            receipts,
+            1,
+            &percentiles,
+            0,
+            chain_config.block_gas_limit,
+        );
+        assert!(matches!(result, Err(Error::Other(e)) if e.contains("at most 100 percentiles")));
+    }
+
+    #[test]
+    fn percentiles_without_messages() {
+        assert_eq!(premium_percentiles(vec![], &[10., 90.]), atto(&[0, 0]));
+        assert!(premium_percentiles(vec![], &[]).is_empty());
+    }
+}
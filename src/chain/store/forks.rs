@@ -0,0 +1,191 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Tracking of the competing heads of the chain. The tipsets put in the
+//! [`ChainStore`](super::ChainStore) are validated, those without a known
+//! child are the heads of the forks, indexed here by weight. The weights of
+//! the recent tipsets are cached per [`Scale`], as computing them requires
+//! loading the power table of their state.
+
+use std::{any::TypeId, collections::BTreeMap, num::NonZeroUsize, sync::Arc};
+
+use crate::blocks::{Tipset, TipsetKeys};
+use crate::chain::{Scale, Weight};
+use crate::shim::clock::ChainEpoch;
+use lru::LruCache;
+use nonzero_ext::nonzero;
+
+const DEFAULT_WEIGHT_CACHE_SIZE: NonZeroUsize = nonzero!(4096usize);
+
+/// Head of a fork, with its weight
+#[derive(Clone, Debug)]
+pub struct ForkHead {
+    pub tipset: Arc<Tipset>,
+    pub weight: Weight,
+}
+
+pub(in crate::chain) struct ForkTracker {
+    /// Heads of the forks by weight
+    heads: BTreeMap<Weight, Vec<Arc<Tipset>>>,
+    /// Weights of the recent tipsets, by scale
+    weights: LruCache<(TypeId, TipsetKeys), Weight>,
+    /// Heads more than this number of epochs behind the latest tipset added
+    /// are dropped
+    chain_finality: ChainEpoch,
+}
+
+impl ForkTracker {
+    pub fn new(chain_finality: ChainEpoch) -> Self {
+        Self {
+            heads: BTreeMap::new(),
+            weights: LruCache::new(DEFAULT_WEIGHT_CACHE_SIZE),
+            chain_finality,
+        }
+    }
+
+    /// Returns the cached weight of the tipset with the given scale
+    pub fn cached_weight<S: Scale + 'static>(&mut self, key: &TipsetKeys) -> Option<Weight> {
+        self.weights.get(&(TypeId::of::<S>(), key.clone())).cloned()
+    }
+
+    /// Caches the weight of the tipset with the given scale
+    pub fn cache_weight<S: Scale + 'static>(&mut self, key: TipsetKeys, weight: Weight) {
+        self.weights.put((TypeId::of::<S>(), key), weight);
+    }
+
+    /// Adds the tipset, of the given weight, as the head of its fork,
+    /// replacing its parent.
+    pub fn add(&mut self, ts: Arc<Tipset>, weight: Weight) {
+        let cut_off_epoch = ts.epoch() - self.chain_finality;
+        self.heads.retain(|_, heads| {
+            heads.retain(|head| {
+                head.key() != ts.parents()
+                    && head.key() != ts.key()
+                    && head.epoch() >= cut_off_epoch
+            });
+            !heads.is_empty()
+        });
+        self.heads.entry(weight).or_default().push(ts);
+    }
+
+    /// Returns the heads of the forks, from the heaviest one
+    pub fn heads(&self) -> Vec<ForkHead> {
+        self.heads
+            .iter()
+            .rev()
+            .flat_map(|(weight, heads)| {
+                heads.iter().map(|tipset| ForkHead {
+                    tipset: tipset.clone(),
+                    weight: weight.clone(),
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::ChainGenerator;
+    use fvm_ipld_blockstore::Blockstore;
+
+    use super::*;
+
+    /// Weight of the tipsets as claimed by their headers
+    struct ClaimedWeight;
+
+    impl Scale for ClaimedWeight {
+        fn weight<DB>(_: &DB, ts: &Tipset) -> anyhow::Result<Weight>
+        where
+            DB: Blockstore,
+        {
+            Ok(ts.weight().clone())
+        }
+    }
+
+    /// Number of blocks of the tipsets
+    struct BlockCount;
+
+    impl Scale for BlockCount {
+        fn weight<DB>(_: &DB, ts: &Tipset) -> anyhow::Result<Weight>
+        where
+            DB: Blockstore,
+        {
+            Ok(ts.blocks().len().into())
+        }
+    }
+
+    fn add(forks: &mut ForkTracker, ts: Arc<Tipset>) {
+        let weight = ts.weight().clone();
+        forks.add(ts, weight);
+    }
+
+    fn heaviest(forks: &ForkTracker) -> Arc<Tipset> {
+        forks.heads()[0].tipset.clone()
+    }
+
+    #[test]
+    fn tracks_the_heads_of_the_forks() {
+        let mut forks = ForkTracker::new(900);
+        let mut generator = ChainGenerator::new();
+        let genesis = generator.genesis().clone();
+        let main = generator.extend(&genesis, &[1000], 5);
+        let fork = generator.extend(&main[1], &[1001, 1002], 3);
+        for tipset in main.iter().chain(&fork) {
+            add(&mut forks, tipset.clone());
+        }
+
+        let heads: Vec<_> = forks.heads().into_iter().map(|head| head.tipset).collect();
+        assert_eq!(heads, vec![fork[2].clone(), main[4].clone()]);
+        assert_eq!(heaviest(&forks), fork[2]);
+        assert_eq!(&forks.heads()[0].weight, fork[2].weight());
+
+        // Extending the lighter fork replaces its head, until it's the
+        // heaviest one
+        let next = generator.extend(&main[4], &[1000], 3);
+        add(&mut forks, next[0].clone());
+        assert_eq!(forks.heads().len(), 2);
+        assert_eq!(heaviest(&forks), fork[2]);
+        for tipset in &next[1..] {
+            add(&mut forks, tipset.clone());
+        }
+        assert_eq!(forks.heads().len(), 2);
+        assert_eq!(heaviest(&forks), next[2]);
+    }
+
+    #[test]
+    fn drops_the_heads_behind_finality() {
+        let mut forks = ForkTracker::new(3);
+        let mut generator = ChainGenerator::new();
+        let genesis = generator.genesis().clone();
+        let stale = generator.mine(&genesis, &[1001], 0);
+        add(&mut forks, stale.clone());
+        for tipset in generator.extend(&genesis, &[1000], 5) {
+            add(&mut forks, tipset);
+        }
+        assert_eq!(forks.heads().len(), 1);
+        assert_ne!(forks.heads()[0].tipset, stale);
+    }
+
+    #[test]
+    fn weights_are_cached_per_scale() {
+        let mut forks = ForkTracker::new(900);
+        let mut generator = ChainGenerator::new();
+        let genesis = generator.genesis().clone();
+        let tipset = generator.mine(&genesis, &[1000, 1001], 2);
+        forks.cache_weight::<ClaimedWeight>(tipset.key().clone(), tipset.weight().clone());
+        assert_eq!(
+            forks.cached_weight::<ClaimedWeight>(tipset.key()).as_ref(),
+            Some(tipset.weight())
+        );
+        assert_eq!(forks.cached_weight::<BlockCount>(tipset.key()), None);
+        forks.cache_weight::<BlockCount>(tipset.key().clone(), 2.into());
+        assert_eq!(
+            forks.cached_weight::<ClaimedWeight>(tipset.key()).as_ref(),
+            Some(tipset.weight())
+        );
+        assert_eq!(
+            forks.cached_weight::<BlockCount>(tipset.key()),
+            Some(2.into())
+        );
+    }
+}
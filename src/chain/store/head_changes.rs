@@ -0,0 +1,123 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Head changes published by the [`ChainStore`](super::ChainStore). When the
+//! head moves, the tipsets of the previous chain are reverted from the old
+//! head down to the common ancestor, then those of the new chain are applied
+//! from the common ancestor up to the new head. A head change is published
+//! entirely before the next one starts, so that subscribers see a consistent
+//! sequence of tipsets.
+
+use std::{collections::VecDeque, sync::Arc};
+
+use crate::blocks::{Tipset, TipsetKeys};
+use tokio::sync::broadcast::{error::RecvError, Receiver};
+
+/// `Enum` for `pubsub` channel that defines message type variant and data
+/// contained in message type.
+#[derive(Clone, Debug)]
+pub enum HeadChange {
+    Current(Arc<Tipset>),
+    Apply(Arc<Tipset>),
+    Revert(Arc<Tipset>),
+}
+
+/// Subscription to the head changes, replaying the tipsets applied before it
+/// was created first.
+pub struct HeadChangeSubscriber {
+    replay: VecDeque<HeadChange>,
+    receiver: Receiver<HeadChange>,
+}
+
+impl HeadChangeSubscriber {
+    pub(in crate::chain) fn new(
+        replay: VecDeque<HeadChange>,
+        receiver: Receiver<HeadChange>,
+    ) -> Self {
+        Self { replay, receiver }
+    }
+
+    /// Receives the next head change. Subscribers too slow to keep up with
+    /// the buffered head changes get [`RecvError::Lagged`], they can
+    /// subscribe again from the last epoch they applied.
+    pub async fn recv(&mut self) -> Result<HeadChange, RecvError> {
+        match self.replay.pop_front() {
+            Some(change) => Ok(change),
+            None => self.receiver.recv().await,
+        }
+    }
+}
+
+/// Returns the head changes moving the head from `from` to `to`: the reverted
+/// tipsets from `from`, followed by the applied tipsets up to `to`.
+pub fn reorg_ops<F, E>(
+    mut load_tipset: F,
+    from: Arc<Tipset>,
+    to: Arc<Tipset>,
+) -> Result<Vec<HeadChange>, E>
+where
+    F: FnMut(&TipsetKeys) -> Result<Arc<Tipset>, E>,
+{
+    let mut left = from;
+    let mut right = to;
+    let mut reverted = Vec::new();
+    let mut applied = Vec::new();
+    while left != right {
+        if left.epoch() > right.epoch() {
+            let parent = load_tipset(left.parents())?;
+            reverted.push(HeadChange::Revert(left));
+            left = parent;
+        } else {
+            let parent = load_tipset(right.parents())?;
+            applied.push(HeadChange::Apply(right));
+            right = parent;
+        }
+    }
+    reverted.extend(applied.into_iter().rev());
+    Ok(reverted)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::ChainGenerator;
+    use ahash::HashMap;
+
+    use super::*;
+
+    #[test]
+    fn reorg_ops_over_null_rounds() {
+        let mut generator = ChainGenerator::new();
+        let genesis = generator.genesis().clone();
+        let main = generator.extend(&genesis, &[1000], 5);
+        let mut fork = vec![generator.mine(&main[1], &[1001], 2)];
+        fork.extend(generator.extend(&fork[0], &[1001], 2));
+        let tipsets: HashMap<_, _> = [&genesis]
+            .into_iter()
+            .chain(&main)
+            .chain(&fork)
+            .map(|ts| (ts.key().clone(), ts.clone()))
+            .collect();
+        let load = |tsk: &TipsetKeys| tipsets.get(tsk).cloned().ok_or(());
+
+        let changes = reorg_ops(load, main[4].clone(), fork[2].clone()).unwrap();
+        let epochs: Vec<_> = changes
+            .iter()
+            .map(|change| match change {
+                HeadChange::Revert(ts) => -ts.epoch(),
+                HeadChange::Apply(ts) => ts.epoch(),
+                HeadChange::Current(_) => unreachable!(),
+            })
+            .collect();
+        assert_eq!(epochs, vec![-5, -4, -3, 5, 6, 7]);
+
+        assert!(reorg_ops(load, main[4].clone(), main[4].clone())
+            .unwrap()
+            .is_empty());
+        assert_eq!(
+            reorg_ops(load, main[2].clone(), main[4].clone())
+                .unwrap()
+                .len(),
+            2
+        );
+    }
+}
@@ -0,0 +1,272 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Inclusion proofs of the messages of a block and of the receipts of a
+//! tipset, for light clients to check them against a block header they trust
+//! without syncing the chain. A proof is made of the IPLD blocks loaded to
+//! find the value from the trusted root. On verification, these blocks are
+//! addressed by their hash again, so that a tampered block can't be found.
+
+use std::cell::RefCell;
+
+use crate::blocks::{BlockHeader, TxMeta};
+use crate::db::MemoryDB;
+use crate::shim::executor::Receipt;
+use anyhow::bail;
+use cid::{
+    multihash::{Code::Blake2b256, MultihashDigest},
+    Cid,
+};
+use fvm_ipld_amt::Amtv0 as Amt;
+use fvm_ipld_blockstore::Blockstore;
+use fvm_ipld_encoding::{CborStore, DAG_CBOR};
+use serde::{Deserialize, Serialize};
+use serde_with::{base64::Base64, serde_as};
+
+use super::Error;
+
+/// Proof that a value is stored at an index of an AMT
+#[serde_as]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct InclusionProof {
+    pub index: u64,
+    /// Blocks loaded from the root to the value, in order
+    #[serde_as(as = "Vec<Base64>")]
+    pub blocks: Vec<Vec<u8>>,
+}
+
+/// Proof that a message is included in a block
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct MessageInclusionProof {
+    /// Whether the message is in the array of the SECP messages, rather than
+    /// of the BLS ones
+    pub secp: bool,
+    /// Proof from the messages root of the block header, starting with its
+    /// [`TxMeta`]
+    pub proof: InclusionProof,
+}
+
+/// Blockstore recording the blocks read from the underlying one
+struct RecordingBlockstore<'a, DB> {
+    db: &'a DB,
+    blocks: RefCell<Vec<Vec<u8>>>,
+}
+
+impl<'a, DB> RecordingBlockstore<'a, DB> {
+    fn new(db: &'a DB) -> Self {
+        Self {
+            db,
+            blocks: RefCell::default(),
+        }
+    }
+
+    fn into_proof(self, index: u64) -> InclusionProof {
+        InclusionProof {
+            index,
+            blocks: self.blocks.into_inner(),
+        }
+    }
+}
+
+impl<DB: Blockstore> Blockstore for RecordingBlockstore<'_, DB> {
+    fn get(&self, k: &Cid) -> anyhow::Result<Option<Vec<u8>>> {
+        let block = self.db.get(k)?;
+        if let Some(block) = &block {
+            self.blocks.borrow_mut().push(block.clone());
+        }
+        Ok(block)
+    }
+
+    fn put_keyed(&self, _k: &Cid, _block: &[u8]) -> anyhow::Result<()> {
+        bail!("proofs are built from a read-only store")
+    }
+}
+
+/// Returns a store of the blocks of the proof, keyed by their hash
+fn proof_store(proof: &InclusionProof) -> Result<MemoryDB, Error> {
+    let db = MemoryDB::default();
+    for block in &proof.blocks {
+        let cid = Cid::new_v1(DAG_CBOR, Blake2b256.digest(block));
+        db.put_keyed(&cid, block)?;
+    }
+    Ok(db)
+}
+
+/// Proves that the message is included in the block
+pub fn message_inclusion_proof<DB>(
+    db: &DB,
+    header: &BlockHeader,
+    message: &Cid,
+) -> Result<MessageInclusionProof, Error>
+where
+    DB: Blockstore,
+{
+    let meta: TxMeta = db
+        .get_cbor(header.messages())?
+        .ok_or_else(|| Error::NotFound(format!("messages of block {}", header.cid())))?;
+    for (secp, root) in [
+        (false, meta.bls_message_root),
+        (true, meta.secp_message_root),
+    ] {
+        let index = match find_cid(db, &root, message)? {
+            Some(index) => index,
+            None => continue,
+        };
+        let store = RecordingBlockstore::new(db);
+        store.get(header.messages())?;
+        Amt::<Cid, _>::load(&root, &store)?.get(index)?;
+        return Ok(MessageInclusionProof {
+            secp,
+            proof: store.into_proof(index),
+        });
+    }
+    Err(Error::NotFound(format!(
+        "message {message} in block {}",
+        header.cid()
+    )))
+}
+
+/// Checks the proof that the message is included in the block whose
+/// messages root is `messages_root`
+pub fn verify_message_inclusion(
+    messages_root: &Cid,
+    message: &Cid,
+    proof: &MessageInclusionProof,
+) -> Result<(), Error> {
+    let db = proof_store(&proof.proof)?;
+    let meta: TxMeta = db
+        .get_cbor(messages_root)?
+        .ok_or_else(|| Error::Other("invalid proof: missing messages root".into()))?;
+    let root = match proof.secp {
+        true => meta.secp_message_root,
+        false => meta.bls_message_root,
+    };
+    match Amt::<Cid, _>::load(&root, &db)?.get(proof.proof.index)? {
+        Some(cid) if cid == message => Ok(()),
+        _ => Err(Error::Other(format!(
+            "invalid proof: message {message} isn't at index {}",
+            proof.proof.index
+        ))),
+    }
+}
+
+/// Proves that the receipt at `index` is included in the receipts root, the
+/// `message_receipts` of the headers of the next tipset
+pub fn receipt_inclusion_proof<DB>(
+    db: &DB,
+    receipts_root: &Cid,
+    index: u64,
+) -> Result<(Receipt, InclusionProof), Error>
+where
+    DB: Blockstore,
+{
+    let store = RecordingBlockstore::new(db);
+    let receipt = Amt::<Receipt, _>::load(receipts_root, &store)?
+        .get(index)?
+        .cloned()
+        .ok_or_else(|| Error::NotFound(format!("receipt {index} in {receipts_root}")))?;
+    Ok((receipt, store.into_proof(index)))
+}
+
+/// Checks the proof that the receipt is included in the receipts root
+pub fn verify_receipt_inclusion(
+    receipts_root: &Cid,
+    receipt: &Receipt,
+    proof: &InclusionProof,
+) -> Result<(), Error> {
+    let db = proof_store(proof)?;
+    // Compared encoded, as the receipts are decoded with the oldest version
+    match Amt::<Receipt, _>::load(receipts_root, &db)?.get(proof.index)? {
+        Some(included)
+            if fvm_ipld_encoding::to_vec(included)? == fvm_ipld_encoding::to_vec(receipt)? =>
+        {
+            Ok(())
+        }
+        _ => Err(Error::Other(format!(
+            "invalid proof: the receipt isn't at index {}",
+            proof.index
+        ))),
+    }
+}
+
+/// Returns the index of the CID in the AMT, if any
+fn find_cid<DB>(db: &DB, root: &Cid, cid: &Cid) -> Result<Option<u64>, Error>
+where
+    DB: Blockstore,
+{
+    let mut index = None;
+    Amt::<Cid, _>::load(root, db)?.for_each_while(|i, c| {
+        if c == cid {
+            index = Some(i);
+        }
+        Ok(index.is_none())
+    })?;
+    Ok(index)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::shim::address::Address;
+    use crate::utils::db::CborStoreExt;
+    use fvm_ipld_encoding::RawBytes;
+    use fvm_shared::{error::ExitCode, receipt::Receipt as Receipt_v2};
+
+    use super::*;
+
+    fn message_cid(i: u64) -> Cid {
+        Cid::new_v1(DAG_CBOR, Blake2b256.digest(&i.to_be_bytes()))
+    }
+
+    fn receipt(gas_used: i64) -> Receipt {
+        Receipt::V2(Receipt_v2 {
+            exit_code: ExitCode::OK,
+            return_data: RawBytes::default(),
+            gas_used,
+        })
+    }
+
+    #[test]
+    fn message_proof_roundtrip() {
+        let db = MemoryDB::default();
+        let meta = TxMeta {
+            bls_message_root: Amt::new_from_iter(&db, (0..100).map(message_cid)).unwrap(),
+            secp_message_root: Amt::new_from_iter(&db, (1000..1100).map(message_cid)).unwrap(),
+        };
+        let messages_root = db.put_cbor_default(&meta).unwrap();
+        let header = BlockHeader::builder()
+            .messages(messages_root)
+            .miner_address(Address::new_id(0))
+            .build()
+            .unwrap();
+
+        let message = message_cid(1042);
+        let proof = message_inclusion_proof(&db, &header, &message).unwrap();
+        assert!(proof.secp);
+        assert_eq!(proof.proof.index, 42);
+        verify_message_inclusion(&messages_root, &message, &proof).unwrap();
+        assert!(verify_message_inclusion(&messages_root, &message_cid(42), &proof).is_err());
+
+        let mut tampered = proof.clone();
+        tampered.secp = false;
+        assert!(verify_message_inclusion(&messages_root, &message, &tampered).is_err());
+
+        assert!(message_inclusion_proof(&db, &header, &message_cid(5000)).is_err());
+    }
+
+    #[test]
+    fn receipt_proof_roundtrip() {
+        let db = MemoryDB::default();
+        let receipts_root = Amt::new_from_iter(&db, (0..100).map(receipt)).unwrap();
+
+        let (included, proof) = receipt_inclusion_proof(&db, &receipts_root, 7).unwrap();
+        assert_eq!(included.gas_used(), 7);
+        verify_receipt_inclusion(&receipts_root, &receipt(7), &proof).unwrap();
+        assert!(verify_receipt_inclusion(&receipts_root, &receipt(8), &proof).is_err());
+
+        let mut tampered = proof.clone();
+        tampered.blocks.pop();
+        assert!(verify_receipt_inclusion(&receipts_root, &receipt(7), &tampered).is_err());
+    }
+}
@@ -72,7 +72,8 @@ pub(super) mod checkpoint_tipsets {
         match network {
             NetworkChain::Mainnet => Some(KNOWN_CHECKPOINTS.mainnet.clone()),
             NetworkChain::Calibnet => Some(KNOWN_CHECKPOINTS.calibnet.clone()),
-            NetworkChain::Devnet(_) => None, // skip and pass through if an unsupported network found
+            // skip and pass through if an unsupported network found
+            NetworkChain::Butterflynet | NetworkChain::Devnet(_) => None,
         }
     }
 
@@ -85,7 +86,8 @@ pub(super) mod checkpoint_tipsets {
             NetworkChain::Calibnet => {
                 ts.min_ticket_block().cid().to_string() == crate::networks::calibnet::GENESIS_CID
             }
-            NetworkChain::Devnet(_) => true, // skip and pass through if an unsupported network found
+            // skip and pass through if an unsupported network found
+            NetworkChain::Butterflynet | NetworkChain::Devnet(_) => true,
         }
     }
 
@@ -108,7 +110,7 @@ pub(in crate::chain) struct LookbackEntry {
 
 /// Keeps look-back tipsets in cache at a given interval `skip_length` and can
 /// be used to look-back at the chain to retrieve an old tipset.
-pub(in crate::chain) struct ChainIndex<BS> {
+pub(crate) struct ChainIndex<BS> {
     /// Cache of look-back entries to speed up lookup.
     skip_cache: Mutex<LruCache<TipsetKeys, Arc<LookbackEntry>>>,
 
@@ -120,7 +122,7 @@ pub(in crate::chain) struct ChainIndex<BS> {
 }
 
 impl<BS: Blockstore> ChainIndex<BS> {
-    pub(in crate::chain) fn new(ts_cache: Arc<TipsetCache>, db: BS) -> Self {
+    pub(crate) fn new(ts_cache: Arc<TipsetCache>, db: BS) -> Self {
         Self {
             skip_cache: Mutex::new(LruCache::new(DEFAULT_CHAIN_INDEX_CACHE_SIZE)),
             ts_cache,
@@ -134,7 +136,7 @@ impl<BS: Blockstore> ChainIndex<BS> {
 
     /// Loads tipset at `to` [`ChainEpoch`], loading from sparse cache and/or
     /// loading parents from the `blockstore`.
-    pub(in crate::chain) fn get_tipset_by_height(
+    pub(crate) fn get_tipset_by_height(
         &self,
         from: Arc<Tipset>,
         to: ChainEpoch,
@@ -4,7 +4,16 @@
 pub mod base_fee;
 mod chain_store;
 mod errors;
+mod fee_history;
+mod forks;
+mod head_changes;
+mod inclusion_proof;
 mod index;
+mod roots;
 mod tipset_tracker;
 
-pub use self::{base_fee::*, chain_store::*, errors::*};
+pub(crate) use self::index::ChainIndex;
+pub use self::{
+    base_fee::*, chain_store::*, errors::*, fee_history::*, forks::ForkHead, head_changes::*,
+    inclusion_proof::*, roots::*,
+};
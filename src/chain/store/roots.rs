@@ -0,0 +1,86 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Roots that block headers commit to: the one of the messages of a block and
+//! the one of the receipts of the messages of the parent tipset. Block
+//! builders and tests outside of Forest compute them the same way to check
+//! them against those of Lotus.
+
+use crate::blocks::TxMeta;
+use crate::message::SignedMessage;
+use crate::shim::{executor::Receipt, message::Message};
+use crate::utils::db::CborStoreExt;
+use cid::Cid;
+use fvm_ipld_amt::Amtv0 as Amt;
+use fvm_ipld_blockstore::Blockstore;
+use fvm_ipld_encoding::Cbor;
+
+/// Computes the root of the messages of a block: the CID of its [`TxMeta`],
+/// made of the roots of the AMTs of the CIDs of its BLS messages, unsigned,
+/// and of its other messages, signed. The AMTs and the [`TxMeta`] are put in
+/// `db`, the messages aren't.
+pub fn compute_message_root(
+    db: &impl Blockstore,
+    bls_messages: &[Message],
+    secp_messages: &[SignedMessage],
+) -> anyhow::Result<Cid> {
+    let bls_cids = bls_messages
+        .iter()
+        .map(Cbor::cid)
+        .collect::<Result<Vec<_>, _>>()?;
+    let secp_cids = secp_messages
+        .iter()
+        .map(Cbor::cid)
+        .collect::<Result<Vec<_>, _>>()?;
+    db.put_cbor_default(&TxMeta {
+        bls_message_root: Amt::new_from_iter(db, bls_cids)?,
+        secp_message_root: Amt::new_from_iter(db, secp_cids)?,
+    })
+}
+
+/// Computes the root of the AMT of the receipts of the messages of a tipset,
+/// in the order the messages are executed. The AMT is put in `db`.
+pub fn compute_receipts_root(db: &impl Blockstore, receipts: &[Receipt]) -> anyhow::Result<Cid> {
+    Ok(Amt::new_from_iter(db, receipts.iter().cloned())?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::MemoryDB;
+    use crate::shim::executor::Receipt_v3;
+    use fvm_shared3::error::ExitCode;
+
+    #[test]
+    fn empty_message_root_matches_lotus() {
+        let root = compute_message_root(&MemoryDB::default(), &[], &[]).unwrap();
+        assert_eq!(
+            root.to_string(),
+            "bafy2bzacecmda75ovposbdateg7eyhwij65zklgyijgcjwynlklmqazpwlhba"
+        );
+    }
+
+    #[test]
+    fn receipts_are_stored_in_order() {
+        let db = MemoryDB::default();
+        let receipts = (0..10)
+            .map(|i| {
+                Receipt::V3(Receipt_v3 {
+                    exit_code: ExitCode::new(i),
+                    return_data: Default::default(),
+                    gas_used: 100 * i as u64,
+                    events_root: None,
+                })
+            })
+            .collect::<Vec<_>>();
+        let root = compute_receipts_root(&db, &receipts).unwrap();
+        let amt = Amt::<Receipt, _>::load(&root, &db).unwrap();
+        let mut stored = vec![];
+        amt.for_each(|_, receipt| {
+            stored.push(receipt.clone());
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(stored, receipts);
+    }
+}
@@ -108,7 +108,13 @@ impl<DB: Blockstore> TipsetTracker<DB> {
                         Error::Other(format!("failed to load block ({cid}) for tipset expansion"))
                     })?;
 
-                if h.parents() == headers[0].parents() {
+                // Equivocating blocks of the same miner can't be in the same
+                // tipset, the first one tracked is kept
+                if h.parents() == headers[0].parents()
+                    && headers
+                        .iter()
+                        .all(|header| header.miner_address() != h.miner_address())
+                {
                     headers.push(h);
                 }
             }
@@ -121,7 +127,9 @@ impl<DB: Blockstore> TipsetTracker<DB> {
 
 #[cfg(test)]
 mod test {
+    use crate::chain::persist_objects;
     use crate::db::MemoryDB;
+    use crate::test_utils::ChainGenerator;
 
     use super::*;
 
@@ -163,4 +171,38 @@ mod test {
             ]
         );
     }
+
+    #[test]
+    fn expand_skips_equivocating_blocks() {
+        let db = MemoryDB::default();
+        let tipset_tracker = TipsetTracker::new(db.clone(), Arc::new(ChainConfig::default()));
+        let mut generator = ChainGenerator::new();
+        let genesis = generator.genesis().clone();
+        let tipset = generator.mine(&genesis, &[1000, 1001], 0);
+        let equivocated = generator.equivocate(tipset.min_ticket_block(), &genesis);
+        let sibling = generator.mine(&genesis, &[1002], 0);
+        let headers: Vec<_> = tipset
+            .blocks()
+            .iter()
+            .chain([&equivocated])
+            .chain(sibling.blocks())
+            .cloned()
+            .collect();
+        persist_objects(&db, &headers).unwrap();
+        for header in &headers {
+            tipset_tracker.add(header);
+        }
+
+        let expanded = tipset_tracker
+            .expand(tipset.min_ticket_block().clone())
+            .unwrap();
+        assert_eq!(expanded.blocks().len(), 3);
+        assert!(!expanded.cids().contains(equivocated.cid()));
+
+        // The equivocating block still forms a tipset with the blocks of the
+        // other miners
+        let expanded = tipset_tracker.expand(equivocated.clone()).unwrap();
+        assert_eq!(expanded.blocks().len(), 3);
+        assert!(expanded.cids().contains(equivocated.cid()));
+    }
 }
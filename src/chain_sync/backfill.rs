@@ -0,0 +1,190 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Background execution of historical tipsets, to store the message receipts
+//! and events missing from the epochs imported from a snapshot. A tipset is
+//! executed when its parent state is available and the receipts recorded by
+//! its child are missing. As executing a tipset also stores its state, the
+//! job rolls forward from the earliest state available.
+//!
+//! The job only runs while the node follows the chain, and waits between
+//! tipsets, so that the head sync isn't slowed down.
+
+use std::{sync::Arc, time::Duration};
+
+use crate::blocks::Tipset;
+use crate::message::ChainMessage;
+use crate::shim::{clock::ChainEpoch, executor::ApplyRet};
+use crate::state_manager::{ExecutionLane, StateManager};
+use crate::utils::operations::{self, OperationCancelled, OperationKind};
+use anyhow::Context;
+use cid::Cid;
+use fvm_ipld_blockstore::Blockstore;
+use log::{debug, info, warn};
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use serde_with::{serde_as, DurationMilliSeconds};
+
+use super::{SyncStage, SyncState};
+
+/// Interval of the checks of the sync stage while the node is catching up
+const SYNC_POLL_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Number of epochs between the progress logs
+const PROGRESS_LOG_INTERVAL: ChainEpoch = 1000;
+
+#[serde_as]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct BackfillConfig {
+    /// Runs the backfill job when the daemon starts
+    pub enabled: bool,
+    /// First epoch checked for missing receipts
+    pub from_epoch: ChainEpoch,
+    /// Pause after each tipset executed, in milliseconds
+    #[serde_as(as = "DurationMilliSeconds<u64>")]
+    pub interval: Duration,
+}
+
+impl Default for BackfillConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            from_epoch: 0,
+            interval: Duration::from_millis(500),
+        }
+    }
+}
+
+/// Executes the tipsets from [`BackfillConfig::from_epoch`] to the head at
+/// the time the job starts. Failures are logged, they don't stop the daemon,
+/// and neither does cancelling the job.
+pub async fn backfill_receipts<DB>(
+    state_manager: Arc<StateManager<DB>>,
+    sync_state: Arc<RwLock<SyncState>>,
+    config: BackfillConfig,
+) -> anyhow::Result<()>
+where
+    DB: Blockstore + Clone + Send + Sync + 'static,
+{
+    wait_for_sync(&sync_state).await;
+    let head = state_manager.chain_store().heaviest_tipset();
+    info!(
+        "Backfilling receipts from epoch {} to {}",
+        config.from_epoch,
+        head.epoch()
+    );
+
+    let from_epoch = config.from_epoch.max(0);
+    let result = operations::run(
+        OperationKind::ReceiptBackfill,
+        format!(
+            "receipt backfill from epoch {from_epoch} to {}",
+            head.epoch()
+        ),
+        None,
+        |operation| async move {
+            let mut executed = 0;
+            let mut epoch = from_epoch;
+            while epoch < head.epoch() {
+                let tipset = state_manager
+                    .chain_store()
+                    .tipset_by_height(epoch, head.clone(), false)
+                    .with_context(|| format!("failed to load the tipset at epoch {epoch}"))?;
+                if tipset.epoch() >= head.epoch() {
+                    break;
+                }
+                operation.set_progress(
+                    (tipset.epoch() - from_epoch) as u64,
+                    (head.epoch() - from_epoch) as u64,
+                );
+                if tipset.epoch() % PROGRESS_LOG_INTERVAL == 0 {
+                    info!(
+                        "Backfill at epoch {}, {executed} tipsets executed",
+                        tipset.epoch()
+                    );
+                }
+                if backfill_tipset(&state_manager, &sync_state, &tipset, &head)
+                    .await
+                    .with_context(|| format!("failed at epoch {}", tipset.epoch()))?
+                {
+                    executed += 1;
+                    tokio::time::sleep(config.interval).await;
+                }
+                // Null rounds are skipped
+                epoch = tipset.epoch() + 1;
+            }
+            Ok(executed)
+        },
+    )
+    .await;
+    match result {
+        Ok(executed) => info!("Backfill finished, {executed} tipsets executed"),
+        Err(e) if e.is::<OperationCancelled>() => info!("Backfill cancelled"),
+        Err(e) => warn!("Backfill stopped: {e:#}"),
+    }
+    Ok(())
+}
+
+/// Executes the tipset if its parent state is available and its receipts
+/// are missing. Returns whether it was executed.
+async fn backfill_tipset<DB>(
+    state_manager: &Arc<StateManager<DB>>,
+    sync_state: &RwLock<SyncState>,
+    tipset: &Arc<Tipset>,
+    head: &Arc<Tipset>,
+) -> anyhow::Result<bool>
+where
+    DB: Blockstore + Clone + Send + Sync + 'static,
+{
+    let db = state_manager.blockstore();
+    let child =
+        state_manager
+            .chain_store()
+            .tipset_by_height(tipset.epoch() + 1, head.clone(), false)?;
+    let receipts = *child
+        .blocks()
+        .first()
+        .context("empty tipset")?
+        .message_receipts();
+    if db.has(&receipts)? && db.has(child.parent_state())? {
+        return Ok(false);
+    }
+    if !db.has(tipset.parent_state())? {
+        debug!(
+            "Skipping epoch {}, its parent state is missing",
+            tipset.epoch()
+        );
+        return Ok(false);
+    }
+
+    wait_for_sync(sync_state).await;
+    match state_manager
+        .compute_tipset_state(
+            tipset.clone(),
+            None::<fn(&Cid, &ChainMessage, &ApplyRet) -> anyhow::Result<()>>,
+            ExecutionLane::Backfill,
+        )
+        .await
+    {
+        Ok((state_root, receipt_root)) => {
+            if &state_root != child.parent_state() || receipt_root != receipts {
+                warn!(
+                    "Backfill mismatch at epoch {}: state {state_root} and receipts {receipt_root}, expected {} and {receipts}",
+                    tipset.epoch(),
+                    child.parent_state()
+                );
+            }
+        }
+        // The next tipsets may still be executed if the state is available
+        Err(e) => warn!("Backfill failed to execute epoch {}: {e}", tipset.epoch()),
+    }
+    Ok(true)
+}
+
+/// Waits for the node to follow the chain
+async fn wait_for_sync(sync_state: &RwLock<SyncState>) {
+    while sync_state.read().stage() != SyncStage::Complete {
+        tokio::time::sleep(SYNC_POLL_INTERVAL).await;
+    }
+}
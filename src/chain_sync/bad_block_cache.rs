@@ -1,12 +1,31 @@
 // Copyright 2019-2023 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
-use std::num::NonZeroUsize;
+use std::{
+    num::NonZeroUsize,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
 
+use crate::utils::db::file_backed_obj::{FileBacked, FileBackedObject};
 use cid::Cid;
+use log::warn;
 use lru::LruCache;
 use nonzero_ext::nonzero;
 use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use serde_with::{serde_as, DisplayFromStr};
+
+/// Name of the file in the chain data directory the bad blocks are persisted
+/// to
+pub const BAD_BLOCKS_FILE_NAME: &str = "bad_blocks.json";
+
+/// Interval between the writes of the changed cache to its file
+const PERSIST_INTERVAL: Duration = Duration::from_secs(60);
 
 /// Thread-safe cache for tracking bad blocks.
 /// This cache is checked before validating a block, to ensure no duplicate
@@ -14,6 +33,10 @@ use parking_lot::Mutex;
 #[derive(Debug)]
 pub struct BadBlockCache {
     cache: Mutex<LruCache<Cid, String>>,
+    /// File the cache is persisted to, if any
+    path: Option<PathBuf>,
+    /// Whether the cache changed since it was last persisted
+    dirty: AtomicBool,
 }
 
 impl Default for BadBlockCache {
@@ -26,12 +49,52 @@ impl BadBlockCache {
     pub fn new(cap: NonZeroUsize) -> Self {
         Self {
             cache: Mutex::new(LruCache::new(cap)),
+            path: None,
+            dirty: AtomicBool::new(false),
+        }
+    }
+
+    /// Loads the cache persisted to the given file, or creates an empty one.
+    /// The changes are written to the file by [`BadBlockCache::persist`], on
+    /// an interval and on shutdown, so that known-invalid chains are not
+    /// validated again after a restart.
+    pub fn load_or_create(path: PathBuf) -> anyhow::Result<Self> {
+        let bad_blocks =
+            FileBacked::load_from_file_or_create(path.clone(), BadBlocks::default, None)?;
+        let cache = Self {
+            path: Some(path),
+            ..Default::default()
+        };
+        {
+            let mut lru = cache.cache.lock();
+            for entry in &bad_blocks.inner().0 {
+                lru.put(entry.cid, entry.reason.clone());
+            }
         }
+        Ok(cache)
     }
 
     /// Puts a bad block `Cid` in the cache with a given reason.
     pub fn put(&self, c: Cid, reason: String) -> Option<String> {
-        self.cache.lock().put(c, reason)
+        let previous = self.cache.lock().put(c, reason);
+        self.dirty.store(true, Ordering::Relaxed);
+        previous
+    }
+
+    /// Removes a block `Cid` from the cache, returns the reason it was marked
+    /// bad for.
+    pub fn remove(&self, c: &Cid) -> Option<String> {
+        let reason = self.cache.lock().pop(c);
+        if reason.is_some() {
+            self.dirty.store(true, Ordering::Relaxed);
+        }
+        reason
+    }
+
+    /// Removes all the blocks from the cache.
+    pub fn clear(&self) {
+        self.cache.lock().clear();
+        self.dirty.store(true, Ordering::Relaxed);
     }
 
     /// Returns `Some` with the reason if the block CID is in bad block cache.
@@ -45,4 +108,100 @@ impl BadBlockCache {
     pub fn peek(&self, c: &Cid) -> Option<String> {
         self.cache.lock().peek(c).cloned()
     }
+
+    /// Writes the cache to its file if it changed since it was last
+    /// persisted. The file is written outside of the cache lock.
+    pub fn persist(&self) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        if !self.dirty.swap(false, Ordering::Relaxed) {
+            return;
+        }
+        // Least recently used first, so that loading restores the order
+        let bad_blocks = BadBlocks(
+            self.cache
+                .lock()
+                .iter()
+                .rev()
+                .map(|(cid, reason)| BadBlock {
+                    cid: *cid,
+                    reason: reason.clone(),
+                })
+                .collect(),
+        );
+        if let Err(e) = FileBacked::new(bad_blocks, path.clone()).sync() {
+            warn!("Failed to persist the bad blocks: {e}");
+            self.dirty.store(true, Ordering::Relaxed);
+        }
+    }
+
+    /// Persists the changes of the cache on an interval.
+    pub async fn persist_loop(self: Arc<Self>) -> anyhow::Result<()> {
+        let mut interval = tokio::time::interval(PERSIST_INTERVAL);
+        loop {
+            interval.tick().await;
+            let cache = self.clone();
+            tokio::task::spawn_blocking(move || cache.persist()).await?;
+        }
+    }
+}
+
+#[serde_as]
+#[derive(Debug, Serialize, Deserialize)]
+struct BadBlock {
+    #[serde_as(as = "DisplayFromStr")]
+    cid: Cid,
+    reason: String,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+struct BadBlocks(Vec<BadBlock>);
+
+impl FileBackedObject for BadBlocks {
+    fn serialize(&self) -> anyhow::Result<Vec<u8>> {
+        Ok(serde_json::to_vec(self)?)
+    }
+
+    fn deserialize(bytes: &[u8]) -> anyhow::Result<Self> {
+        Ok(serde_json::from_slice(bytes)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cid::multihash::{Code::Blake2b256, MultihashDigest};
+    use fvm_ipld_encoding::DAG_CBOR;
+    use tempfile::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn persisted_across_restarts() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join(BAD_BLOCKS_FILE_NAME);
+        let bad = Cid::new_v1(DAG_CBOR, Blake2b256.digest(&[1]));
+        let unmarked = Cid::new_v1(DAG_CBOR, Blake2b256.digest(&[2]));
+
+        let cache = BadBlockCache::load_or_create(path.clone()).unwrap();
+        cache.put(bad, "invalid state root".into());
+        cache.put(unmarked, "invalid signature".into());
+        assert_eq!(cache.remove(&unmarked), Some("invalid signature".into()));
+        // Changes are only written when persisted
+        assert!(BadBlockCache::load_or_create(path.clone())
+            .unwrap()
+            .peek(&bad)
+            .is_none());
+        cache.persist();
+
+        let cache = BadBlockCache::load_or_create(path.clone()).unwrap();
+        assert_eq!(cache.peek(&bad), Some("invalid state root".into()));
+        assert_eq!(cache.peek(&unmarked), None);
+
+        cache.clear();
+        cache.persist();
+        let cache = BadBlockCache::load_or_create(path).unwrap();
+        assert_eq!(cache.peek(&bad), None);
+    }
 }
@@ -9,11 +9,12 @@ use std::{
 };
 
 use crate::blocks::{
-    Block, Error as ForestBlockError, FullTipset, GossipBlock, Tipset, TipsetKeys,
+    Block, BlockHeader, Error as ForestBlockError, FullTipset, GossipBlock, Tipset, TipsetKeys,
 };
 use crate::chain::{ChainStore, Error as ChainStoreError};
 use crate::libp2p::{
-    hello::HelloRequest, NetworkEvent, NetworkMessage, PeerId, PeerManager, PubsubMessage,
+    hello::HelloRequest, ClockSample, NetworkEvent, NetworkMessage, PeerId, PeerManager,
+    PubsubMessage,
 };
 use crate::message::SignedMessage;
 use crate::message_pool::{MessagePool, Provider};
@@ -79,6 +80,16 @@ pub struct SyncConfig {
     /// Sample size of tipsets to acquire before determining what the network
     /// head is
     pub tipset_sample_size: usize,
+    /// Syncs and validates the block headers only, without executing the
+    /// messages. Light clients following the chain for its finality and
+    /// randomness keep no state but the genesis one.
+    #[serde(default)]
+    pub headers_only: bool,
+    /// Computes the state of each new head in the background, instead of on
+    /// the first request needing it, which then reads the parent state of the
+    /// head until it is computed
+    #[serde(default)]
+    pub precompute_head_state: bool,
 }
 
 impl Default for SyncConfig {
@@ -86,6 +97,8 @@ impl Default for SyncConfig {
         Self {
             req_window: 200,
             tipset_sample_size: 5,
+            headers_only: false,
+            precompute_head_state: false,
         }
     }
 }
@@ -152,6 +165,10 @@ pub struct ChainMuxer<DB, M, C: Consensus> {
     /// Tipset channel receiver
     tipset_receiver: flume::Receiver<Arc<Tipset>>,
 
+    /// Receives the headers of the valid blocks received through GossipSub,
+    /// to detect consensus faults
+    observed_headers: Option<flume::Sender<BlockHeader>>,
+
     /// Syncing configurations
     sync_config: SyncConfig,
 }
@@ -171,8 +188,10 @@ where
         network_send: flume::Sender<NetworkMessage>,
         network_rx: flume::Receiver<NetworkEvent>,
         genesis: Arc<Tipset>,
+        bad_blocks: Arc<BadBlockCache>,
         tipset_sender: flume::Sender<Arc<Tipset>>,
         tipset_receiver: flume::Receiver<Arc<Tipset>>,
+        observed_headers: Option<flume::Sender<BlockHeader>>,
         cfg: SyncConfig,
     ) -> Result<Self, ChainMuxerError<C>> {
         let network = SyncNetworkContext::new(
@@ -188,11 +207,12 @@ where
             genesis,
             consensus,
             state_manager,
-            bad_blocks: Arc::new(BadBlockCache::default()),
+            bad_blocks,
             net_handler: network_rx,
             mpool,
             tipset_sender,
             tipset_receiver,
+            observed_headers,
             sync_config: cfg,
         })
     }
@@ -273,14 +293,20 @@ where
                         return;
                     }
                 };
-            let dur = SystemTime::now()
+            let moment_received = SystemTime::now();
+            let dur = moment_received
                 .duration_since(moment_sent)
                 .unwrap_or_default();
 
             // Update the peer metadata based on the response
             match response {
-                Some(_) => {
+                Some(response) => {
                     network.peer_manager().log_success(peer_id, dur).await;
+                    let sample = ClockSample::from_hello(moment_sent, moment_received, &response);
+                    network
+                        .peer_manager()
+                        .log_clock_sample(peer_id, sample)
+                        .await;
                 }
                 None => {
                     network.peer_manager().log_failure(peer_id, dur).await;
@@ -291,6 +317,8 @@ where
 
     async fn handle_peer_disconnected_event(network: SyncNetworkContext<DB>, peer_id: PeerId) {
         network.peer_manager().remove_peer(&peer_id).await;
+        // The head reported by the peer is stale from now on
+        let _ = metrics::PEER_TIPSET_EPOCH.remove_label_values(&[peer_id.to_string().as_str()]);
     }
 
     async fn gossipsub_block_to_full_tipset(
@@ -358,12 +386,17 @@ where
         genesis: Arc<Tipset>,
         message_processing_strategy: PubsubMessageProcessingStrategy,
         block_delay: u64,
+        observed_headers: Option<flume::Sender<BlockHeader>>,
     ) -> Result<Option<(FullTipset, PeerId)>, ChainMuxerError<C>> {
         let (tipset, source) = match event {
             NetworkEvent::HelloRequestInbound { source, request } => {
                 metrics::LIBP2P_MESSAGE_TOTAL
                     .with_label_values(&[metrics::values::HELLO_REQUEST_INBOUND])
                     .inc();
+                debug!(
+                    "Peer {} reported heaviest tipset {:?} at epoch {}",
+                    source, request.heaviest_tip_set, request.heaviest_tipset_height
+                );
                 metrics::PEER_TIPSET_EPOCH
                     .with_label_values(&[source.to_string().as_str()])
                     .set(request.heaviest_tipset_height);
@@ -497,6 +530,19 @@ where
             return Err(why.into());
         }
 
+        if let Some(observed_headers) = observed_headers {
+            for block in tipset.blocks() {
+                // Dropping headers is better than stalling the sync when the
+                // detector lags behind
+                if observed_headers.try_send(block.header().clone()).is_err() {
+                    debug!(
+                        "Dropping header {} for consensus fault detection",
+                        block.cid()
+                    );
+                }
+            }
+        }
+
         // Store block messages in the block store
         for block in tipset.blocks() {
             crate::chain::persist_objects(&chain_store.db, &[block.header()])?;
@@ -526,6 +572,7 @@ where
         let mem_pool = self.mpool.clone();
         let tipset_sample_size = self.sync_config.tipset_sample_size;
         let block_delay = self.state_manager.chain_config().block_delay_secs;
+        let observed_headers = self.observed_headers.clone();
 
         let evaluator = async move {
             let mut tipsets = vec![];
@@ -547,6 +594,7 @@ where
                     genesis.clone(),
                     PubsubMessageProcessingStrategy::Process,
                     block_delay,
+                    observed_headers.clone(),
                 )
                 .await
                 {
@@ -608,6 +656,7 @@ where
         let trs_network = self.network.clone();
         let trs_tracker = self.worker_state.clone();
         let trs_genesis = self.genesis.clone();
+        let trs_headers_only = self.sync_config.headers_only;
         let tipset_range_syncer: ChainMuxerFuture<(), ChainMuxerError<C>> = Box::pin(async move {
             let network_head_epoch = network_head.epoch();
             let tipset_range_syncer = match TipsetRangeSyncer::new(
@@ -620,6 +669,7 @@ where
                 trs_chain_store,
                 trs_bad_block_cache,
                 trs_genesis,
+                trs_headers_only,
             ) {
                 Ok(tipset_range_syncer) => tipset_range_syncer,
                 Err(why) => {
@@ -645,6 +695,7 @@ where
         let bad_block_cache = self.bad_blocks.clone();
         let mem_pool = self.mpool.clone();
         let block_delay = self.state_manager.chain_config().block_delay_secs;
+        let observed_headers = self.observed_headers.clone();
         let stream_processor: ChainMuxerFuture<(), ChainMuxerError<C>> = Box::pin(async move {
             loop {
                 let event = match p2p_messages.recv_async().await {
@@ -664,6 +715,7 @@ where
                     genesis.clone(),
                     PubsubMessageProcessingStrategy::DoNotProcess,
                     block_delay,
+                    observed_headers.clone(),
                 )
                 .await
                 {
@@ -707,6 +759,7 @@ where
         let tp_tipset_receiver = self.tipset_receiver.clone();
         let tp_tracker = self.worker_state.clone();
         let tp_genesis = self.genesis.clone();
+        let tp_headers_only = self.sync_config.headers_only;
         enum UnexpectedReturnKind {
             TipsetProcessor,
         }
@@ -721,6 +774,7 @@ where
                     tp_chain_store,
                     tp_bad_block_cache,
                     tp_genesis,
+                    tp_headers_only,
                 )
                 .await
                 .map_err(ChainMuxerError::TipsetProcessor)?;
@@ -738,6 +792,7 @@ where
         let mem_pool = self.mpool.clone();
         let tipset_sender = self.tipset_sender.clone();
         let block_delay = self.state_manager.chain_config().block_delay_secs;
+        let observed_headers = self.observed_headers.clone();
         let stream_processor: ChainMuxerFuture<UnexpectedReturnKind, ChainMuxerError<C>> = Box::pin(
             async move {
                 // If a tipset has been provided, pass it to the tipset processor
@@ -768,6 +823,7 @@ where
                         genesis.clone(),
                         PubsubMessageProcessingStrategy::Process,
                         block_delay,
+                        observed_headers.clone(),
                     )
                     .await
                     {
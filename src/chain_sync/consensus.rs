@@ -7,7 +7,7 @@ use std::{
     sync::Arc,
 };
 
-use crate::blocks::{Block, GossipBlock, Tipset};
+use crate::blocks::{Block, BlockHeader, GossipBlock, Tipset};
 use crate::chain::Scale;
 use crate::libp2p::{NetworkMessage, Topic, PUBSUB_BLOCK_STR};
 use crate::message::SignedMessage;
@@ -46,6 +46,17 @@ pub trait Consensus: Scale + Debug + Send + Sync + Unpin + 'static {
     ) -> Result<(), NonEmpty<Self::Error>>
     where
         DB: Blockstore + Clone + Sync + Send + 'static;
+
+    /// Perform the validations of the block header for the nodes syncing the
+    /// headers only, which don't execute the messages. The parts of the state
+    /// the rules read are fetched by the caller beforehand.
+    async fn validate_block_header<DB>(
+        &self,
+        state_manager: Arc<StateManager<DB>>,
+        header: Arc<BlockHeader>,
+    ) -> Result<(), NonEmpty<Self::Error>>
+    where
+        DB: Blockstore + Clone + Sync + Send + 'static;
 }
 
 /// Helper function to collect errors from async validations.
@@ -0,0 +1,415 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Detection of the consensus faults defined in the [specification], from the
+//! block headers received by the node, and reporting of the faulty miners
+//! through the `ReportConsensusFault` method of their miner actor.
+//!
+//! [specification]: https://spec.filecoin.io/#section-glossary.consensus-fault
+
+use std::{fmt, sync::Arc};
+
+use crate::blocks::{BlockHeader, TipsetKeys};
+use crate::key_management::KeyStore;
+use crate::message::SignedMessage;
+use crate::message_pool::{MessagePool, Provider};
+use crate::shim::{
+    address::Address,
+    clock::ChainEpoch,
+    econ::TokenAmount,
+    message::{Message, Message_v3},
+};
+use crate::state_manager::{ExecutionLane, StateManager};
+use crate::utils::proofs_api::verifier::{self, kinds};
+use ahash::HashMap;
+use cid::Cid;
+use fvm_ipld_blockstore::Blockstore;
+use fvm_ipld_encoding::Cbor;
+use fvm_ipld_encoding3::RawBytes;
+use log::{debug, error, info, warn};
+use serde_tuple::{self, Deserialize_tuple, Serialize_tuple};
+use tokio::sync::RwLock;
+
+use crate::chain_sync::{consensus::Consensus, metrics};
+
+/// Method number of `ReportConsensusFault` in the miner actor
+const REPORT_CONSENSUS_FAULT_METHOD: u64 = 15;
+
+/// The gas used by `ReportConsensusFault` is dominated by the verification of
+/// the header signatures, this leaves a comfortable margin.
+const REPORT_CONSENSUS_FAULT_GAS_LIMIT: u64 = 100_000_000;
+
+/// Same as the minimum premium used for gas estimation
+const REPORT_CONSENSUS_FAULT_GAS_PREMIUM: u64 = 100_000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConsensusFaultType {
+    /// Two blocks mined by the same miner at the same epoch
+    DoubleForkMining,
+    /// Two blocks mined by the same miner on top of the same parents
+    TimeOffsetMining,
+    /// A miner ignored its own block of the previous epoch when mining
+    ParentGrinding,
+}
+
+impl ConsensusFaultType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::DoubleForkMining => "double_fork_mining",
+            Self::TimeOffsetMining => "time_offset_mining",
+            Self::ParentGrinding => "parent_grinding",
+        }
+    }
+}
+
+impl fmt::Display for ConsensusFaultType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A consensus fault, with the headers proving it
+#[derive(Clone, Debug)]
+pub struct ConsensusFault {
+    pub fault_type: ConsensusFaultType,
+    pub block1: BlockHeader,
+    pub block2: BlockHeader,
+    /// The parent of `block2` sibling of `block1`, for parent grinding only
+    pub block_extra: Option<BlockHeader>,
+}
+
+#[derive(Serialize_tuple, Deserialize_tuple)]
+struct ReportConsensusFaultParams {
+    #[serde(with = "cs_serde_bytes")]
+    header1: Vec<u8>,
+    #[serde(with = "cs_serde_bytes")]
+    header2: Vec<u8>,
+    #[serde(with = "cs_serde_bytes")]
+    header_extra: Vec<u8>,
+}
+
+impl ConsensusFault {
+    pub fn miner(&self) -> &Address {
+        self.block1.miner_address()
+    }
+
+    /// Builds the unsigned `ReportConsensusFault` message, sent by `from` to
+    /// the actor of the faulty miner.
+    pub fn report_message(
+        &self,
+        from: Address,
+        sequence: u64,
+        base_fee: &TokenAmount,
+    ) -> anyhow::Result<Message> {
+        let params = RawBytes::serialize(ReportConsensusFaultParams {
+            header1: self.block1.marshal_cbor()?,
+            header2: self.block2.marshal_cbor()?,
+            header_extra: match &self.block_extra {
+                Some(header) => header.marshal_cbor()?,
+                None => vec![],
+            },
+        })?;
+        let gas_premium = TokenAmount::from_atto(REPORT_CONSENSUS_FAULT_GAS_PREMIUM);
+        Ok(Message_v3 {
+            version: 0,
+            from: from.into(),
+            to: (*self.miner()).into(),
+            sequence,
+            value: Default::default(),
+            method_num: REPORT_CONSENSUS_FAULT_METHOD,
+            params,
+            gas_limit: REPORT_CONSENSUS_FAULT_GAS_LIMIT,
+            // Leaves room for the base fee to double before inclusion
+            gas_fee_cap: (base_fee * 2_u64 + &gas_premium).into(),
+            gas_premium: gas_premium.into(),
+        }
+        .into())
+    }
+}
+
+/// Keeps track of the recent block headers of each miner to detect
+/// consensus faults.
+pub struct ConsensusFaultDetector {
+    /// Faults older than this number of epochs can't be reported anymore
+    lookback: ChainEpoch,
+    latest_epoch: ChainEpoch,
+    headers: HashMap<Cid, BlockHeader>,
+    by_epoch: HashMap<(Address, ChainEpoch), Cid>,
+    by_parents: HashMap<(Address, TipsetKeys), Cid>,
+}
+
+impl ConsensusFaultDetector {
+    pub fn new(lookback: ChainEpoch) -> Self {
+        Self {
+            lookback,
+            latest_epoch: 0,
+            headers: Default::default(),
+            by_epoch: Default::default(),
+            by_parents: Default::default(),
+        }
+    }
+
+    /// Records a block header, returns the faults it proves together with the
+    /// previously observed headers.
+    pub fn observe(&mut self, header: BlockHeader) -> Vec<ConsensusFault> {
+        let cid = *header.cid();
+        if self.headers.contains_key(&cid) || header.epoch() + self.lookback < self.latest_epoch {
+            return vec![];
+        }
+        let miner = *header.miner_address();
+        let mut faults = vec![];
+
+        let same_epoch = self.by_epoch.entry((miner, header.epoch())).or_insert(cid);
+        if *same_epoch != cid {
+            faults.push(ConsensusFault {
+                fault_type: ConsensusFaultType::DoubleForkMining,
+                block1: self.headers[same_epoch].clone(),
+                block2: header.clone(),
+                block_extra: None,
+            });
+        }
+
+        let same_parents = self
+            .by_parents
+            .entry((miner, header.parents().clone()))
+            .or_insert(cid);
+        if *same_parents != cid && self.headers[same_parents].epoch() != header.epoch() {
+            faults.push(ConsensusFault {
+                fault_type: ConsensusFaultType::TimeOffsetMining,
+                block1: self.headers[same_parents].clone(),
+                block2: header.clone(),
+                block_extra: None,
+            });
+        }
+
+        // The miner mined a block on top of a tipset of the epoch of one of its
+        // own blocks, which should then have been included as a parent.
+        let parent_grinding = header.parents().cids().iter().find_map(|parent| {
+            let extra = self.headers.get(parent)?;
+            let own = self.by_epoch.get(&(miner, extra.epoch()))?;
+            let own = &self.headers[own];
+            (own.parents() == extra.parents() && !header.parents().cids().contains(own.cid()))
+                .then(|| (own.clone(), extra.clone()))
+        });
+        if let Some((own, extra)) = parent_grinding {
+            faults.push(ConsensusFault {
+                fault_type: ConsensusFaultType::ParentGrinding,
+                block1: own,
+                block2: header.clone(),
+                block_extra: Some(extra),
+            });
+        }
+
+        let epoch = header.epoch();
+        self.headers.insert(cid, header);
+        if epoch > self.latest_epoch {
+            self.latest_epoch = epoch;
+            self.prune();
+        }
+        faults
+    }
+
+    fn prune(&mut self) {
+        let oldest = self.latest_epoch - self.lookback;
+        self.headers.retain(|_, header| header.epoch() >= oldest);
+        self.by_epoch.retain(|(_, epoch), _| *epoch >= oldest);
+        let headers = &self.headers;
+        self.by_parents.retain(|_, cid| headers.contains_key(cid));
+    }
+}
+
+/// Detects consensus faults in the block headers received by the node, and
+/// reports them from the `reporter` wallet address. Only the headers whose
+/// signature, ticket and election are valid are considered, so that forged
+/// headers can't get an honest miner reported.
+pub async fn consensus_fault_reporter<DB, M, C>(
+    headers: flume::Receiver<BlockHeader>,
+    consensus: Arc<C>,
+    state_manager: Arc<StateManager<DB>>,
+    mpool: Arc<MessagePool<M>>,
+    keystore: Arc<RwLock<KeyStore>>,
+    reporter: Address,
+) -> anyhow::Result<()>
+where
+    DB: Blockstore + Clone + Send + Sync + 'static,
+    M: Provider + Send + Sync + 'static,
+    C: Consensus,
+{
+    let mut detector =
+        ConsensusFaultDetector::new(state_manager.chain_config().policy.chain_finality);
+    while let Ok(header) = headers.recv_async().await {
+        let header = Arc::new(header);
+        if let Err(e) = validate_header(consensus.as_ref(), &state_manager, &header).await {
+            debug!(
+                "Ignoring header {} for consensus fault detection: {e}",
+                header.cid()
+            );
+            continue;
+        }
+        for fault in detector.observe(header.as_ref().clone()) {
+            metrics::CONSENSUS_FAULTS_TOTAL
+                .with_label_values(&[fault.fault_type.as_str()])
+                .inc();
+            warn!(
+                "Miner {} committed a {} consensus fault with blocks {} and {}",
+                fault.miner(),
+                fault.fault_type,
+                fault.block1.cid(),
+                fault.block2.cid()
+            );
+            match report(&fault, &state_manager, &mpool, &keystore, reporter).await {
+                Ok(cid) => info!("Reported the consensus fault in message {cid}"),
+                Err(e) => error!("Failed to report the consensus fault: {e}"),
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Checks the signature of the header against the worker key of its miner in
+/// the lookback state, and the consensus rules of the header, including the
+/// ticket and the election.
+async fn validate_header<DB, C>(
+    consensus: &C,
+    state_manager: &Arc<StateManager<DB>>,
+    header: &Arc<BlockHeader>,
+) -> anyhow::Result<()>
+where
+    DB: Blockstore + Clone + Send + Sync + 'static,
+    C: Consensus,
+{
+    let base_tipset = state_manager
+        .chain_store()
+        .tipset_from_keys(header.parents())?;
+    let (_, lookback_state) =
+        state_manager.get_lookback_tipset_for_round(base_tipset, header.epoch())?;
+    let work_addr = state_manager.get_miner_work_addr(lookback_state, header.miner_address())?;
+    let v_header = header.clone();
+    verifier::verify(kinds::BLOCK_SIGNATURE, move || {
+        v_header.check_block_signature(&work_addr)
+    })
+    .await??;
+    consensus
+        .validate_block_header(state_manager.clone(), header.clone())
+        .await
+        .map_err(|errs| {
+            anyhow::anyhow!(errs
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(", "))
+        })
+}
+
+async fn report<DB, M>(
+    fault: &ConsensusFault,
+    state_manager: &Arc<StateManager<DB>>,
+    mpool: &MessagePool<M>,
+    keystore: &RwLock<KeyStore>,
+    reporter: Address,
+) -> anyhow::Result<Cid>
+where
+    DB: Blockstore + Clone + Send + Sync + 'static,
+    M: Provider + Send + Sync + 'static,
+{
+    let heaviest = state_manager.chain_store().heaviest_tipset();
+    let key_addr = state_manager
+        .resolve_to_key_addr(&reporter, &heaviest, ExecutionLane::Validation)
+        .await?;
+    let message = fault.report_message(
+        key_addr,
+        mpool.get_sequence(&key_addr)?,
+        heaviest.blocks()[0].parent_base_fee(),
+    )?;
+    let key = crate::key_management::find_key(&key_addr, &*keystore.read().await)?;
+    let signature = crate::key_management::sign(
+        *key.key_info.key_type(),
+        key.key_info.private_key(),
+        message.cid()?.to_bytes().as_slice(),
+    )?;
+    Ok(mpool
+        .push(SignedMessage::new_from_parts(message, signature)?)
+        .await?)
+}
+
+#[cfg(test)]
+mod tests {
+    use cid::multihash::{Code, MultihashDigest};
+
+    use super::*;
+
+    fn cid(data: &[u8]) -> Cid {
+        Cid::new_v1(fvm_ipld_encoding::DAG_CBOR, Code::Blake2b256.digest(data))
+    }
+
+    fn header(miner: u64, epoch: ChainEpoch, parents: &[Cid], timestamp: u64) -> BlockHeader {
+        BlockHeader::builder()
+            .miner_address(Address::new_id(miner))
+            .epoch(epoch)
+            .parents(TipsetKeys::new(parents.to_vec()))
+            .timestamp(timestamp)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn detects_double_fork_mining() {
+        let mut detector = ConsensusFaultDetector::new(900);
+        let parents = [cid(b"parent")];
+        assert!(detector.observe(header(1000, 10, &parents, 0)).is_empty());
+        // Another miner at the same epoch is fine
+        assert!(detector.observe(header(1001, 10, &parents, 0)).is_empty());
+        let faults = detector.observe(header(1000, 10, &[cid(b"other")], 1));
+        assert_eq!(faults.len(), 1);
+        assert_eq!(faults[0].fault_type, ConsensusFaultType::DoubleForkMining);
+        // Observing the same header again doesn't report it twice
+        assert!(detector
+            .observe(header(1000, 10, &[cid(b"other")], 1))
+            .is_empty());
+    }
+
+    #[test]
+    fn detects_time_offset_mining() {
+        let mut detector = ConsensusFaultDetector::new(900);
+        let parents = [cid(b"parent")];
+        assert!(detector.observe(header(1000, 10, &parents, 0)).is_empty());
+        let faults = detector.observe(header(1000, 11, &parents, 0));
+        assert_eq!(faults.len(), 1);
+        assert_eq!(faults[0].fault_type, ConsensusFaultType::TimeOffsetMining);
+    }
+
+    #[test]
+    fn detects_parent_grinding() {
+        let mut detector = ConsensusFaultDetector::new(900);
+        let parents = [cid(b"parent")];
+        let own = header(1000, 10, &parents, 0);
+        let extra = header(1001, 10, &parents, 0);
+        assert!(detector.observe(own.clone()).is_empty());
+        assert!(detector.observe(extra.clone()).is_empty());
+        // Mining on top of both blocks is fine
+        assert!(detector
+            .observe(header(1000, 11, &[*own.cid(), *extra.cid()], 0))
+            .is_empty());
+        let faults = detector.observe(header(1000, 11, &[*extra.cid()], 1));
+        assert!(faults.iter().any(
+            |fault| fault.fault_type == ConsensusFaultType::ParentGrinding
+                && fault.block_extra.as_ref() == Some(&extra)
+        ));
+    }
+
+    #[test]
+    fn report_message_targets_the_miner() {
+        let mut detector = ConsensusFaultDetector::new(900);
+        detector.observe(header(1000, 10, &[cid(b"a")], 0));
+        let fault = detector
+            .observe(header(1000, 10, &[cid(b"b")], 0))
+            .pop()
+            .unwrap();
+        let message = fault
+            .report_message(Address::new_id(1), 3, &TokenAmount::from_atto(100))
+            .unwrap();
+        assert_eq!(message.to, Address::new_id(1000).into());
+        assert_eq!(message.method_num, REPORT_CONSENSUS_FAULT_METHOD);
+        assert_eq!(message.sequence, 3);
+    }
+}
@@ -197,10 +197,85 @@ lazy_static! {
             );
         follow_network_errors
     };
+    pub static ref CONSENSUS_FAULTS_TOTAL: Box<GenericCounterVec<AtomicU64>> = {
+        let consensus_faults_total = Box::new(
+            GenericCounterVec::<AtomicU64>::new(
+                Opts::new(
+                    "consensus_faults_total",
+                    "Total number of consensus faults detected by type",
+                ),
+                &[labels::CONSENSUS_FAULT_TYPE],
+            )
+            .expect("Defining the consensus_faults_total metric must succeed"),
+        );
+        prometheus::default_registry()
+            .register(consensus_faults_total.clone())
+            .expect(
+                "Registering the consensus_faults_total metric with the metrics registry must succeed",
+            );
+        consensus_faults_total
+    };
+    pub static ref TIPSET_VALIDATION_TIME: Box<HistogramVec> = {
+        let tipset_validation_time = Box::new(
+            HistogramVec::new(
+                HistogramOpts {
+                    common_opts: Opts::new(
+                        "tipset_validation_time",
+                        "Duration of the validation of the blocks of tipsets by result",
+                    ),
+                    buckets: vec![],
+                },
+                &[labels::VALIDATION_RESULT],
+            )
+            .expect("Defining the tipset_validation_time metric must succeed"),
+        );
+        prometheus::default_registry()
+            .register(tipset_validation_time.clone())
+            .expect(
+                "Registering the tipset_validation_time metric with the metrics registry must succeed",
+            );
+        tipset_validation_time
+    };
+    pub static ref SYNC_EPOCHS_BEHIND: Box<GenericGauge<AtomicI64>> = {
+        let sync_epochs_behind = Box::new(
+            GenericGauge::<AtomicI64>::new(
+                "sync_epochs_behind",
+                "Number of epochs between the last validated tipset and the sync target",
+            )
+            .expect("Defining the sync_epochs_behind metric must succeed"),
+        );
+        prometheus::default_registry()
+            .register(sync_epochs_behind.clone())
+            .expect(
+                "Registering the sync_epochs_behind metric with the metrics registry must succeed",
+            );
+        sync_epochs_behind
+    };
+    pub static ref CHAIN_WEIGHT_AUDIT_MISMATCHES: Box<GenericCounterVec<AtomicU64>> = {
+        let chain_weight_audit_mismatches = Box::new(
+            GenericCounterVec::<AtomicU64>::new(
+                Opts::new(
+                    "chain_weight_audit_mismatches",
+                    "Number of parent weights that differ from the recomputed one, by source",
+                ),
+                &[labels::WEIGHT_SOURCE],
+            )
+            .expect("Defining the chain_weight_audit_mismatches metric must succeed"),
+        );
+        prometheus::default_registry()
+            .register(chain_weight_audit_mismatches.clone())
+            .expect(
+                "Registering the chain_weight_audit_mismatches metric with the metrics registry must succeed",
+            );
+        chain_weight_audit_mismatches
+    };
 }
 
 pub mod labels {
     pub const GOSSIPSUB_MESSAGE_KIND: &str = "libp2p_message_kind";
+    pub const CONSENSUS_FAULT_TYPE: &str = "type";
+    pub const VALIDATION_RESULT: &str = "result";
+    pub const WEIGHT_SOURCE: &str = "source";
 }
 
 pub mod values {
@@ -222,6 +297,16 @@ pub mod values {
     pub const BASE_FEE_CHECK: &str = "base_fee_check";
     pub const PARENT_WEIGHT_CAL: &str = "parent_weight_check";
     pub const BLOCK_SIGNATURE_CHECK: &str = "block_signature_check";
+
+    // tipset_validation_time
+    pub const VALID: &str = "valid";
+    pub const INVALID: &str = "invalid";
+
+    // chain_weight_audit_mismatches
+    /// Weight claimed by the header of a child
+    pub const HEADER_WEIGHT: &str = "header";
+    /// Weight cached by the chain store
+    pub const CACHED_WEIGHT: &str = "cache";
 }
 
 #[cfg(test)]
@@ -253,5 +338,9 @@ mod tests {
         test_counter!(BOOTSTRAP_ERRORS);
         test_counter!(FOLLOW_NETWORK_INTERRUPTIONS);
         test_counter!(FOLLOW_NETWORK_ERRORS);
+        test_counter_vec!(CONSENSUS_FAULTS_TOTAL);
+        test_counter_vec!(TIPSET_VALIDATION_TIME);
+        test_counter!(SYNC_EPOCHS_BEHIND);
+        test_counter_vec!(CHAIN_WEIGHT_AUDIT_MISMATCHES);
     }
 }
@@ -1,9 +1,11 @@
 // Copyright 2019-2023 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
+mod backfill;
 mod bad_block_cache;
 mod chain_muxer;
 pub mod consensus;
+mod consensus_fault;
 mod metrics;
 mod network_context;
 mod sync_state;
@@ -11,9 +13,14 @@ mod tipset_syncer;
 mod validation;
 
 pub use self::{
-    bad_block_cache::BadBlockCache,
+    backfill::{backfill_receipts, BackfillConfig},
+    bad_block_cache::{BadBlockCache, BAD_BLOCKS_FILE_NAME},
     chain_muxer::{ChainMuxer, SyncConfig},
     consensus::{collect_errs, Consensus},
+    consensus_fault::{
+        consensus_fault_reporter, ConsensusFault, ConsensusFaultDetector, ConsensusFaultType,
+    },
+    network_context::SyncNetworkContext,
     sync_state::{SyncStage, SyncState},
     validation::TipsetValidator,
 };
@@ -41,6 +41,39 @@ const CHAIN_EXCHANGE_TIMEOUT: Duration = Duration::from_secs(5);
 /// network.
 const MAX_CONCURRENT_CHAIN_EXCHANGE_REQUESTS: usize = 2;
 
+/// Store reading the database, and fetching the blocks it misses over
+/// `Bitswap`. Reads block the thread until the block arrives or times out, so
+/// it's only to be used on the blocking thread pool.
+pub(in crate::chain_sync) struct BitswapFallbackStore<DB> {
+    network_send: flume::Sender<NetworkMessage>,
+    db: DB,
+    epoch: ChainEpoch,
+}
+
+impl<DB: Blockstore> Blockstore for BitswapFallbackStore<DB> {
+    fn get(&self, k: &Cid) -> anyhow::Result<Option<Vec<u8>>> {
+        if let Some(block) = self.db.get(k)? {
+            return Ok(Some(block));
+        }
+        let (tx, rx) = flume::bounded(1);
+        self.network_send
+            .send(NetworkMessage::BitswapRequest {
+                epoch: self.epoch,
+                cid: *k,
+                response_channel: tx,
+            })
+            .context("failed to send bitswap request, network receiver dropped")?;
+        // The fetched block is written to the database by the `Bitswap`
+        // request manager
+        let _ = rx.recv_timeout(BITSWAP_TIMEOUT);
+        self.db.get(k)
+    }
+
+    fn put_keyed(&self, k: &Cid, block: &[u8]) -> anyhow::Result<()> {
+        self.db.put_keyed(k, block)
+    }
+}
+
 /// Context used in chain sync to handle network requests.
 /// This contains the peer manager, P2P service interface, and [`Blockstore`]
 /// required to make network requests.
@@ -128,6 +161,19 @@ where
         self.peer_manager.as_ref()
     }
 
+    /// Returns a store that fetches the blocks missing in the database over
+    /// `Bitswap`, to read the state on nodes syncing the headers only.
+    pub fn bitswap_fallback_store(&self, epoch: ChainEpoch) -> BitswapFallbackStore<DB>
+    where
+        DB: Clone,
+    {
+        BitswapFallbackStore {
+            network_send: self.network_send.clone(),
+            db: self.db.as_ref().clone(),
+            epoch,
+        }
+    }
+
     /// Send a `chain_exchange` request for only block headers (ignore
     /// messages). If `peer_id` is `None`, requests will be sent to a set of
     /// shuffled peers.
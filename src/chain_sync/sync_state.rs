@@ -22,8 +22,10 @@ pub enum SyncStage {
     Headers,
     /// Persisting headers on chain from heaviest to genesis.
     PersistHeaders,
-    /// Syncing messages and performing state transitions.
+    /// Syncing messages, waiting for them to be fetched from the network.
     Messages,
+    /// Executing messages and validating the fetched tipsets.
+    Execution,
     /// `ChainSync` completed and is following chain.
     Complete,
     /// Error has occurred while syncing.
@@ -44,6 +46,7 @@ impl quickcheck::Arbitrary for SyncStage {
             SyncStage::Headers,
             SyncStage::PersistHeaders,
             SyncStage::Messages,
+            SyncStage::Execution,
             SyncStage::Complete,
         ])
         .unwrap()
@@ -57,6 +60,7 @@ impl fmt::Display for SyncStage {
             SyncStage::Headers => write!(f, "header sync"),
             SyncStage::PersistHeaders => write!(f, "persisting headers"),
             SyncStage::Messages => write!(f, "message sync"),
+            SyncStage::Execution => write!(f, "execution"),
             SyncStage::Complete => write!(f, "complete"),
             SyncStage::Error => write!(f, "error"),
         }
@@ -84,6 +88,7 @@ impl<'de> Deserialize<'de> for SyncStage {
             "header sync" => SyncStage::Headers,
             "persisting headers" => SyncStage::PersistHeaders,
             "message sync" => SyncStage::Messages,
+            "execution" => SyncStage::Execution,
             "complete" => SyncStage::Complete,
             _ => SyncStage::Error,
         };
@@ -101,6 +106,12 @@ pub struct SyncState {
 
     stage: SyncStage,
     epoch: ChainEpoch,
+    /// Latest epoch whose messages have been fetched
+    fetched_epoch: ChainEpoch,
+    /// Number of tipsets being validated concurrently
+    active_workers: usize,
+    /// Average number of tipsets validated per second
+    validation_rate: f64,
 
     start: Option<DateTime<Utc>>,
     end: Option<DateTime<Utc>>,
@@ -115,6 +126,10 @@ impl quickcheck::Arbitrary for SyncState {
             target: Option::arbitrary(g),
             stage: SyncStage::arbitrary(g),
             epoch: ChainEpoch::arbitrary(g),
+            fetched_epoch: ChainEpoch::arbitrary(g),
+            active_workers: u8::arbitrary(g) as usize,
+            // Arbitrary floats include NaN which never equals itself
+            validation_rate: u16::arbitrary(g) as f64 / 8.0,
             start: if bool::arbitrary(g) {
                 None
             } else {
@@ -162,6 +177,29 @@ impl SyncState {
         self.epoch
     }
 
+    /// Returns the latest epoch whose messages have been fetched
+    pub fn fetched_epoch(&self) -> ChainEpoch {
+        self.fetched_epoch
+    }
+
+    /// Returns the number of tipsets being validated concurrently
+    pub fn active_workers(&self) -> usize {
+        self.active_workers
+    }
+
+    /// Returns the average number of tipsets validated per second
+    pub fn validation_rate(&self) -> f64 {
+        self.validation_rate
+    }
+
+    /// Returns the number of epochs between the current epoch and the target,
+    /// if any.
+    pub fn epochs_behind(&self) -> Option<ChainEpoch> {
+        self.target
+            .as_ref()
+            .map(|target| (target.epoch() - self.epoch).max(0))
+    }
+
     /// Get the elapsed time of the current syncing process.
     /// Returns `None` if syncing has not started
     pub fn get_elapsed_time(&self) -> Option<Duration> {
@@ -187,6 +225,21 @@ impl SyncState {
         self.epoch = epoch;
     }
 
+    /// Sets the latest epoch whose messages have been fetched.
+    pub fn set_fetched_epoch(&mut self, epoch: ChainEpoch) {
+        self.fetched_epoch = epoch;
+    }
+
+    /// Sets the number of tipsets being validated concurrently.
+    pub fn set_active_workers(&mut self, active_workers: usize) {
+        self.active_workers = active_workers;
+    }
+
+    /// Sets the average number of tipsets validated per second.
+    pub fn set_validation_rate(&mut self, validation_rate: f64) {
+        self.validation_rate = validation_rate;
+    }
+
     /// Sets error for the sync.
     pub fn error(&mut self, err: String) {
         self.message = err;
@@ -208,6 +261,9 @@ impl Serialize for SyncState {
 
             stage: SyncStage,
             epoch: ChainEpoch,
+            fetched_epoch: ChainEpoch,
+            active_workers: usize,
+            validation_rate: f64,
 
             start: &'a Option<DateTime<Utc>>,
             end: &'a Option<DateTime<Utc>>,
@@ -219,6 +275,9 @@ impl Serialize for SyncState {
             target: self.target.as_ref().map(|ts| TipsetJsonRef(ts.as_ref())),
             stage: self.stage,
             epoch: self.epoch,
+            fetched_epoch: self.fetched_epoch,
+            active_workers: self.active_workers,
+            validation_rate: self.validation_rate,
             start: &self.start,
             end: &self.end,
             message: &self.message,
@@ -241,6 +300,12 @@ impl<'de> Deserialize<'de> for SyncState {
             #[serde(with = "super::SyncStage")]
             stage: SyncStage,
             epoch: ChainEpoch,
+            #[serde(default)]
+            fetched_epoch: ChainEpoch,
+            #[serde(default)]
+            active_workers: usize,
+            #[serde(default)]
+            validation_rate: f64,
 
             start: Option<DateTime<Utc>>,
             end: Option<DateTime<Utc>>,
@@ -252,6 +317,9 @@ impl<'de> Deserialize<'de> for SyncState {
             target,
             stage,
             epoch,
+            fetched_epoch,
+            active_workers,
+            validation_rate,
             start,
             end,
             message,
@@ -261,6 +329,9 @@ impl<'de> Deserialize<'de> for SyncState {
             target: target.map(Into::into),
             stage,
             epoch,
+            fetched_epoch,
+            active_workers,
+            validation_rate,
             start,
             end,
             message,
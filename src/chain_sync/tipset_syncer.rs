@@ -8,29 +8,37 @@ use std::{
     pin::Pin,
     sync::Arc,
     task::{Context, Poll},
-    time::{SystemTime, UNIX_EPOCH},
+    time::Instant,
 };
 
 use crate::blocks::{
+    timing::{self, BlockTiming},
     Block, BlockHeader, Error as ForestBlockError, FullTipset, Tipset, TipsetKeys,
 };
-use crate::chain::{persist_objects, ChainStore, Error as ChainStoreError};
+use crate::chain::{persist_objects, ChainStore, Error as ChainStoreError, Scale, Weight};
 use crate::libp2p::chain_exchange::TipsetBundle;
-use crate::message::{valid_for_block_inclusion, Message as MessageTrait};
+use crate::message::{signature_cache, valid_for_block_inclusion, Message as MessageTrait};
+use crate::message_pool::{verify_bls_messages_aggregate, BlockMessagesValidator};
 use crate::networks::Height;
 use crate::shim::{
-    address::Address, clock::ChainEpoch, crypto::verify_bls_aggregate,
-    gas::price_list_by_network_version, message::Message, state_tree::StateTree,
+    address::Address, clock::ChainEpoch, gas::price_list_by_network_version, message::Message,
+    state_tree::StateTree,
+};
+use crate::state_manager::{
+    is_valid_for_sending, miner_work_addr, Error as StateManagerError, ExecutionLane, StateManager,
 };
-use crate::state_manager::{is_valid_for_sending, Error as StateManagerError, StateManager};
 use crate::utils::io::ProgressBar;
+use crate::utils::proofs_api::verifier::{self, kinds, VerificationPanic};
 use ahash::{HashMap, HashMapExt, HashSet};
 use cid::Cid;
-use futures::{stream::FuturesUnordered, Stream, StreamExt, TryFutureExt};
+use fil_actor_interface::power;
+use fil_actors_shared::v10::runtime::Policy;
+use futures::{
+    stream::{FuturesOrdered, FuturesUnordered},
+    Stream, StreamExt, TryFutureExt,
+};
 use fvm_ipld_blockstore::Blockstore;
 use fvm_ipld_encoding::Cbor;
-use fvm_shared::ALLOWABLE_CLOCK_DRIFT;
-use fvm_shared3::BLOCK_GAS_LIMIT;
 use log::{debug, error, info, trace, warn};
 use nonempty::NonEmpty;
 use num::BigInt;
@@ -47,6 +55,15 @@ use crate::chain_sync::{
 
 const MAX_TIPSETS_TO_REQUEST: u64 = 100;
 
+/// Upper bound of the tipsets being validated at the same time while syncing
+/// a range of tipsets.
+const MAX_CONCURRENT_VALIDATIONS: usize = 8;
+
+/// Environment variable that, set to `1`, makes the validation of each block
+/// recompute the weight of its parent regardless of the cache of the chain
+/// store, and log and count the mismatches.
+pub const AUDIT_CHAIN_WEIGHT_VAR: &str = "FOREST_AUDIT_CHAIN_WEIGHT";
+
 #[derive(Debug, Error)]
 pub enum TipsetProcessorError<C: Consensus> {
     #[error("TipsetRangeSyncer error: {0}")]
@@ -87,6 +104,8 @@ pub enum TipsetRangeSyncerError<C: Consensus> {
     ChainForkLengthExceedsMaximum,
     #[error("Chain fork length exceeds finality threshold")]
     ChainForkLengthExceedsFinalityThreshold,
+    #[error("Chain reverts the finalized tipset at epoch {0}")]
+    RevertsFinalizedTipset(ChainEpoch),
     #[error("Chain for block forked from local chain at genesis, refusing to sync block: {0}")]
     ForkAtGenesisBlock(String),
     #[error("Querying tipsets from the network failed: {0}")]
@@ -117,6 +136,12 @@ impl<C: Consensus, T> From<flume::SendError<T>> for TipsetRangeSyncerError<C> {
     }
 }
 
+impl<C: Consensus> From<VerificationPanic> for TipsetRangeSyncerError<C> {
+    fn from(err: VerificationPanic) -> Self {
+        TipsetRangeSyncerError::Validation(err.to_string())
+    }
+}
+
 impl<C: Consensus> From<tokio::task::JoinError> for TipsetRangeSyncerError<C> {
     fn from(err: tokio::task::JoinError) -> Self {
         TipsetRangeSyncerError::NetworkTipsetQueryFailed(format!("{err}"))
@@ -256,6 +281,7 @@ pub(in crate::chain_sync) struct TipsetProcessor<DB, C: Consensus> {
     chain_store: Arc<ChainStore<DB>>,
     bad_block_cache: Arc<BadBlockCache>,
     genesis: Arc<Tipset>,
+    headers_only: bool,
 }
 
 impl<DB, C> TipsetProcessor<DB, C>
@@ -273,6 +299,7 @@ where
         chain_store: Arc<ChainStore<DB>>,
         bad_block_cache: Arc<BadBlockCache>,
         genesis: Arc<Tipset>,
+        headers_only: bool,
     ) -> Self {
         Self {
             state: TipsetProcessorState::Idle,
@@ -284,6 +311,7 @@ where
             chain_store,
             bad_block_cache,
             genesis,
+            headers_only,
         }
     }
 
@@ -298,6 +326,7 @@ where
         let bad_block_cache = self.bad_block_cache.clone();
         let tracker = self.tracker.clone();
         let genesis = self.genesis.clone();
+        let headers_only = self.headers_only;
         Box::pin(async move {
             // Define the low end of the range
             // Unwrapping is safe here because the store always has at least one tipset
@@ -320,6 +349,7 @@ where
                 chain_store,
                 bad_block_cache,
                 genesis,
+                headers_only,
             )?;
             for tipset in tipset_group.tipsets() {
                 tipset_range_syncer.add_tipset(tipset)?;
@@ -632,6 +662,7 @@ pub(in crate::chain_sync) struct TipsetRangeSyncer<DB, C: Consensus> {
     bad_block_cache: Arc<BadBlockCache>,
     genesis: Arc<Tipset>,
     consensus: Arc<C>,
+    headers_only: bool,
 }
 
 impl<DB, C> TipsetRangeSyncer<DB, C>
@@ -650,6 +681,7 @@ where
         chain_store: Arc<ChainStore<DB>>,
         bad_block_cache: Arc<BadBlockCache>,
         genesis: Arc<Tipset>,
+        headers_only: bool,
     ) -> Result<Self, TipsetRangeSyncerError<C>> {
         let tipset_tasks = Box::pin(FuturesUnordered::new());
         let tipset_range_length = proposed_head.epoch() - current_head.epoch();
@@ -672,6 +704,7 @@ where
             network.clone(),
             bad_block_cache.clone(),
             genesis.clone(),
+            headers_only,
         ));
 
         let tipsets_included = HashSet::from_iter([proposed_head.key().clone()]);
@@ -686,6 +719,7 @@ where
             chain_store,
             bad_block_cache,
             genesis,
+            headers_only,
         })
     }
 
@@ -723,6 +757,7 @@ where
             self.network.clone(),
             self.bad_block_cache.clone(),
             self.genesis.clone(),
+            self.headers_only,
         ));
         Ok(true)
     }
@@ -771,6 +806,7 @@ fn sync_tipset_range<DB: Blockstore + Clone + Sync + Send + 'static, C: Consensu
     network: SyncNetworkContext<DB>,
     bad_block_cache: Arc<BadBlockCache>,
     genesis: Arc<Tipset>,
+    headers_only: bool,
 ) -> TipsetRangeSyncerFuture<C> {
     Box::pin(async move {
         tracker
@@ -796,13 +832,52 @@ fn sync_tipset_range<DB: Blockstore + Clone + Sync + Send + 'static, C: Consensu
         };
 
         // Persist the blocks from the synced Tipsets into the store
-        tracker.write().set_stage(SyncStage::Headers);
+        tracker.write().set_stage(SyncStage::PersistHeaders);
         let headers: Vec<&BlockHeader> = parent_tipsets.iter().flat_map(|t| t.blocks()).collect();
         if let Err(why) = persist_objects(chain_store.blockstore(), &headers) {
             tracker.write().error(why.to_string());
             return Err(why.into());
         };
 
+        // Refuse to reorganize the chain below the finalized tipset
+        match chain_store.is_consistent_with_finality(proposed_head.clone()) {
+            Ok(true) => {}
+            Ok(false) => {
+                let finalized_epoch = chain_store
+                    .finalized_tipset()
+                    .map(|finalized| finalized.epoch)
+                    .unwrap_or_default();
+                let why = TipsetRangeSyncerError::RevertsFinalizedTipset(finalized_epoch);
+                tracker.write().error(why.to_string());
+                return Err(why);
+            }
+            Err(why) => {
+                tracker.write().error(why.to_string());
+                return Err(why.into());
+            }
+        }
+
+        if headers_only {
+            if let Err(why) = validate_headers(
+                consensus,
+                state_manager,
+                &network,
+                &bad_block_cache,
+                &parent_tipsets,
+                &genesis,
+                InvalidBlockStrategy::Strict,
+            )
+            .await
+            {
+                error!("Header validation failed for tipset range");
+                tracker.write().error(why.to_string());
+                return Err(why);
+            }
+            tracker.write().set_stage(SyncStage::Complete);
+            prefetch_weight_state::<_, C>(&network, proposed_head.clone()).await?;
+            return put_validated_headers::<_, C, C>(&chain_store, &proposed_head);
+        }
+
         //  Sync and validate messages from the tipsets
         tracker.write().set_stage(SyncStage::Messages);
         if let Err(why) = sync_messages_check_state(
@@ -902,6 +977,10 @@ async fn sync_headers_in_reverse<DB: Blockstore + Clone + Sync + Send + 'static,
                 break 'sync;
             }
             validate_tipset_against_cache(bad_block_cache, tipset.key(), &parent_blocks)?;
+            // Unwrapping is safe here because the tipset vector always
+            // has at least one element
+            let child_epoch = parent_tipsets.last().unwrap().epoch();
+            validate_tipset_against_finality(chain_store, child_epoch, &tipset)?;
             parent_blocks.extend_from_slice(tipset.cids());
             tracker.write().set_epoch(tipset.epoch());
             parent_tipsets.push(tipset);
@@ -965,6 +1044,28 @@ async fn sync_headers_in_reverse<DB: Blockstore + Clone + Sync + Send + 'static,
     Ok(parent_tipsets)
 }
 
+/// Rejects a chain as soon as the headers synced in reverse cross the epoch of
+/// the finalized tipset without going through it, instead of downloading the
+/// rest of the fork first.
+fn validate_tipset_against_finality<DB: Blockstore + Send + Sync, C: Consensus>(
+    chain_store: &ChainStore<DB>,
+    child_epoch: ChainEpoch,
+    tipset: &Tipset,
+) -> Result<(), TipsetRangeSyncerError<C>> {
+    let Some(finalized) = chain_store.finalized_tipset() else {
+        return Ok(());
+    };
+    if child_epoch > finalized.epoch
+        && tipset.epoch() <= finalized.epoch
+        && tipset.key() != &finalized.key
+    {
+        return Err(TipsetRangeSyncerError::RevertsFinalizedTipset(
+            finalized.epoch,
+        ));
+    }
+    Ok(())
+}
+
 #[allow(clippy::too_many_arguments)]
 fn sync_tipset<DB: Blockstore + Clone + Sync + Send + 'static, C: Consensus>(
     proposed_head: Arc<Tipset>,
@@ -974,12 +1075,32 @@ fn sync_tipset<DB: Blockstore + Clone + Sync + Send + 'static, C: Consensus>(
     network: SyncNetworkContext<DB>,
     bad_block_cache: Arc<BadBlockCache>,
     genesis: Arc<Tipset>,
+    headers_only: bool,
 ) -> TipsetRangeSyncerFuture<C> {
     Box::pin(async move {
         // Persist the blocks from the proposed tipsets into the store
         let headers: Vec<&BlockHeader> = proposed_head.blocks().iter().collect();
         persist_objects(chain_store.blockstore(), &headers)?;
 
+        if headers_only {
+            if let Err(e) = validate_headers(
+                consensus,
+                state_manager,
+                &network,
+                &bad_block_cache,
+                &[proposed_head.clone()],
+                &genesis,
+                InvalidBlockStrategy::Forgiving,
+            )
+            .await
+            {
+                warn!("Header validation failed for single tipset");
+                return Err(e);
+            }
+            prefetch_weight_state::<_, C>(&network, proposed_head.clone()).await?;
+            return put_validated_headers::<_, C, C>(&chain_store, &proposed_head);
+        }
+
         // Sync and validate messages from the tipsets
         if let Err(e) = sync_messages_check_state(
             // Include a dummy WorkerState
@@ -1015,6 +1136,179 @@ fn sync_tipset<DB: Blockstore + Clone + Sync + Send + 'static, C: Consensus>(
     })
 }
 
+/// Fetches the parts of the parent state of the tipset its weight depends on,
+/// i.e. the power table, for the nodes syncing the headers only.
+async fn prefetch_weight_state<DB, C>(
+    network: &SyncNetworkContext<DB>,
+    tipset: Arc<Tipset>,
+) -> Result<(), TipsetRangeSyncerError<C>>
+where
+    DB: Blockstore + Clone + Send + Sync + 'static,
+    C: Consensus,
+{
+    let store = network.bitswap_fallback_store(tipset.epoch());
+    tokio::task::spawn_blocking(move || C::weight(&store, &tipset))
+        .await?
+        .map_err(|e| {
+            TipsetRangeSyncerError::Calculation(format!("Error calculating weight: {e}"))
+        })?;
+    Ok(())
+}
+
+/// Sets the tipset as the heaviest one, if heavier than the current head by
+/// the scale `S`
+fn put_validated_headers<DB: Blockstore, C: Consensus, S: Scale + 'static>(
+    chain_store: &ChainStore<DB>,
+    proposed_head: &Tipset,
+) -> Result<(), TipsetRangeSyncerError<C>> {
+    if let Err(why) = chain_store.put_tipset::<S>(proposed_head) {
+        error!(
+            "Putting tipset [EPOCH = {}, KEYS = {:?}] in the store failed: {}",
+            proposed_head.epoch(),
+            proposed_head.key(),
+            why
+        );
+        return Err(why.into());
+    }
+    Ok(())
+}
+
+/// Validates the headers of the tipsets, given from the most recent one,
+/// without downloading their messages nor executing them. The sanity checks,
+/// the clock drift, the parent weight, the block signature and the consensus
+/// specific header rules are checked. The parts of the state they depend on
+/// are fetched over `Bitswap`.
+async fn validate_headers<DB: Blockstore + Clone + Send + Sync + 'static, C: Consensus>(
+    consensus: Arc<C>,
+    state_manager: Arc<StateManager<DB>>,
+    network: &SyncNetworkContext<DB>,
+    bad_block_cache: &BadBlockCache,
+    tipsets: &[Arc<Tipset>],
+    genesis: &Tipset,
+    invalid_block_strategy: InvalidBlockStrategy,
+) -> Result<(), TipsetRangeSyncerError<C>> {
+    let chain_store = state_manager.chain_store();
+    // Visit tipsets in chronological order
+    for tipset in tipsets.iter().rev() {
+        if tipset.key() == genesis.key() {
+            continue;
+        }
+        let parent = chain_store
+            .tipset_from_keys(tipset.parents())
+            .map_err(TipsetRangeSyncerError::TipsetParentNotFound)?;
+        for header in tipset.blocks() {
+            if chain_store.is_block_validated(header.cid()) {
+                chain_store.add_to_tipset_tracker(header);
+                continue;
+            }
+            if let Err(why) =
+                validate_header(&consensus, &state_manager, network, header.clone(), &parent).await
+            {
+                warn!(
+                    "Validating block header [CID = {}] in EPOCH = {} failed: {}",
+                    header.cid(),
+                    header.epoch(),
+                    why
+                );
+                if let InvalidBlockStrategy::Strict = invalid_block_strategy {
+                    if !matches!(why, TipsetRangeSyncerError::TimeTravellingBlock(_, _)) {
+                        bad_block_cache.put(*header.cid(), why.to_string());
+                    }
+                }
+                return Err(why);
+            }
+            chain_store.mark_block_as_validated(header.cid());
+            chain_store.add_to_tipset_tracker(header);
+        }
+    }
+    Ok(())
+}
+
+async fn validate_header<DB: Blockstore + Clone + Send + Sync + 'static, C: Consensus>(
+    consensus: &Arc<C>,
+    state_manager: &Arc<StateManager<DB>>,
+    network: &SyncNetworkContext<DB>,
+    header: BlockHeader,
+    parent: &Arc<Tipset>,
+) -> Result<(), TipsetRangeSyncerError<C>> {
+    block_sanity_checks::<C>(&header)?;
+    block_timestamp_checks::<C>(
+        &header,
+        &BlockTiming::from(state_manager.chain_config().as_ref()),
+    )?;
+
+    let (_, lookback_state) =
+        state_manager.get_lookback_tipset_for_round(parent.clone(), header.epoch())?;
+
+    // Read the state from the database, fetching what it misses over Bitswap,
+    // so that the consensus rules find it in the database too
+    let store = network.bitswap_fallback_store(header.epoch());
+    let policy = state_manager.chain_config().policy.clone();
+    let v_parent = parent.clone();
+    let miner = *header.miner_address();
+    let (work_addr, parent_weight) = tokio::task::spawn_blocking(move || {
+        let work_addr = miner_work_addr(&store, lookback_state, &miner)?;
+        prefetch_miner_state(
+            &store,
+            &policy,
+            *v_parent.parent_state(),
+            lookback_state,
+            &miner,
+        );
+        let parent_weight = C::weight(&store, &v_parent)?;
+        anyhow::Ok((work_addr, parent_weight))
+    })
+    .await?
+    .map_err(|e| TipsetRangeSyncerError::Calculation(format!("Error reading the state: {e}")))?;
+
+    if header.weight() != &parent_weight {
+        return Err(TipsetRangeSyncerError::Validation(format!(
+            "Parent weight doesn't match: {} (header), {parent_weight} (computed)",
+            header.weight()
+        )));
+    }
+
+    let header = Arc::new(header);
+    let v_header = header.clone();
+    verifier::verify(kinds::BLOCK_SIGNATURE, move || {
+        v_header.check_block_signature(&work_addr)
+    })
+    .await??;
+
+    consensus
+        .validate_block_header(state_manager.clone(), header)
+        .await
+        .map_err(|errs| {
+            TipsetRangeSyncerError::concat(errs.map(TipsetRangeSyncerError::ConsensusError))
+        })
+}
+
+/// Reads the ID of the miner in the parent state, and its power and the total
+/// power in the lookback state, for the consensus rules to find them in the
+/// database. Failures are reported by the rules themselves.
+fn prefetch_miner_state<BS: Blockstore>(
+    store: &BS,
+    policy: &Policy,
+    parent_state: Cid,
+    lookback_state: Cid,
+    miner: &Address,
+) {
+    let prefetch = || -> anyhow::Result<()> {
+        StateTree::new_from_root(store, &parent_state)?.lookup_id(miner)?;
+        let state = StateTree::new_from_root(store, &lookback_state)?;
+        let actor = state
+            .get_actor(&Address::POWER_ACTOR)?
+            .ok_or_else(|| anyhow::anyhow!("power actor not found"))?;
+        let power_state = power::State::load(store, actor.code, actor.state)?;
+        power_state.miner_power(store, &miner.into())?;
+        power_state.miner_nominal_power_meets_consensus_minimum(policy, store, &miner.into())?;
+        Ok(())
+    };
+    if let Err(e) = prefetch() {
+        debug!("Fetching the power of miner {miner} failed: {e}");
+    }
+}
+
 async fn fetch_batch<DB: Blockstore + Clone + Send + Sync + 'static, C: Consensus>(
     batch: &[Arc<Tipset>],
     network: &SyncNetworkContext<DB>,
@@ -1107,27 +1401,118 @@ async fn sync_messages_check_state<DB: Blockstore + Clone + Send + Sync + 'stati
         Ok(())
     });
 
-    // Validation loop
-    while let Ok(full_tipset) = r.recv_async().await {
-        let current_epoch = full_tipset.epoch();
+    // Execution task. The messages of consecutive tipsets have to be executed
+    // one after the other, so the state of the parent of every received
+    // tipset is computed here ahead of the validation of the tipset itself.
+    // `executed` counts the tipsets whose parent state has been computed.
+    let (exec_s, exec_r) = flume::bounded::<TipsetKeys>(MAX_CONCURRENT_VALIDATIONS);
+    let (executed_tx, executed_rx) = tokio::sync::watch::channel(0_usize);
+    let exec_state_manager = state_manager.clone();
+    let exec_chainstore = chainstore.clone();
+    let genesis_key = genesis.key().clone();
+    let executor = tokio::task::spawn(async move {
+        let mut executed = 0;
+        while let Ok(key) = exec_r.recv_async().await {
+            if key != genesis_key {
+                let tipset = exec_chainstore.tipset_from_keys(&key)?;
+                let parent = exec_chainstore.tipset_from_keys(tipset.parents())?;
+                exec_state_manager
+                    .tipset_state(&parent)
+                    .await
+                    .map_err(|e| {
+                        TipsetRangeSyncerError::<C>::Calculation(format!(
+                            "Failed to calculate state: {e}"
+                        ))
+                    })?;
+            }
+            executed += 1;
+            // Nobody is waiting anymore if the validation failed
+            let _ = executed_tx.send(executed);
+        }
+        Ok::<_, TipsetRangeSyncerError<C>>(())
+    });
+
+    // Validation loop. A tipset only depends on the state of its grandparent
+    // (the parent state of its parent), so its validation starts as soon as
+    // the state of the previous tipset's parent is computed, while the
+    // execution of its own parent is still in progress. Validations run
+    // concurrently up to `MAX_CONCURRENT_VALIDATIONS` and are committed in
+    // order.
+    let mut validations = FuturesOrdered::new();
+    let mut index = 0_usize;
+    let mut receiving = true;
+    let validation_start = Instant::now();
+    let mut validated = 0_u32;
+    loop {
         {
-            let _timer = metrics::TIPSET_PROCESSING_TIME.start_timer();
-            validate_tipset::<_, C>(
-                consensus.clone(),
-                state_manager.clone(),
-                &chainstore,
-                bad_block_cache,
-                full_tipset.clone(),
-                genesis,
-                invalid_block_strategy,
-            )
-            .await?;
+            // Waiting for messages from the network when nothing is being
+            // validated, bound by execution otherwise
+            let mut tracker = tracker.write();
+            tracker.set_stage(if validations.is_empty() {
+                SyncStage::Messages
+            } else {
+                SyncStage::Execution
+            });
+            tracker.set_active_workers(validations.len());
+        }
+        let can_receive = receiving && validations.len() < MAX_CONCURRENT_VALIDATIONS;
+        tokio::select! {
+            full_tipset = r.recv_async(), if can_receive => {
+                let Ok(full_tipset) = full_tipset else {
+                    receiving = false;
+                    continue;
+                };
+                tracker.write().set_fetched_epoch(full_tipset.epoch());
+                if exec_s.send_async(full_tipset.key().clone()).await.is_err() {
+                    // The executor failed, its error is returned below
+                    break;
+                }
+                let mut executed = executed_rx.clone();
+                let (consensus, state_manager) = (consensus.clone(), state_manager.clone());
+                let chainstore = &chainstore;
+                validations.push_back(async move {
+                    // The executor only stops early when it fails
+                    if executed.wait_for(|executed| *executed >= index).await.is_err() {
+                        return Ok(None);
+                    }
+                    let _timer = metrics::TIPSET_PROCESSING_TIME.start_timer();
+                    validate_tipset::<_, C>(
+                        consensus,
+                        state_manager,
+                        chainstore,
+                        bad_block_cache,
+                        full_tipset.clone(),
+                        genesis,
+                        invalid_block_strategy,
+                    )
+                    .await?;
+                    Ok::<_, TipsetRangeSyncerError<C>>(Some(full_tipset))
+                });
+                index += 1;
+            }
+            Some(result) = validations.next() => {
+                let Some(full_tipset) = result? else {
+                    // The executor failed, its error is returned below
+                    break;
+                };
+                let current_epoch = full_tipset.epoch();
+                chainstore.set_heaviest_tipset(Arc::new(full_tipset.into_tipset()))?;
+                validated += 1;
+                let mut tracker = tracker.write();
+                tracker.set_epoch(current_epoch);
+                tracker.set_validation_rate(
+                    validated as f64 / validation_start.elapsed().as_secs_f64(),
+                );
+                metrics::LAST_VALIDATED_TIPSET_EPOCH.set(current_epoch as u64);
+                metrics::SYNC_EPOCHS_BEHIND.set(tracker.epochs_behind().unwrap_or_default());
+            }
+            else => break,
         }
-        chainstore.set_heaviest_tipset(Arc::new(full_tipset.into_tipset()))?;
-        tracker.write().set_epoch(current_epoch);
-        metrics::LAST_VALIDATED_TIPSET_EPOCH.set(current_epoch as u64);
     }
+    tracker.write().set_active_workers(0);
+    drop(exec_s);
 
+    executor.await??;
     handle.await?
 }
 
@@ -1135,6 +1520,7 @@ async fn sync_messages_check_state<DB: Blockstore + Clone + Send + Sync + 'stati
 /// executed), adding the successful ones to the tipset tracker, and the failed
 /// ones to the bad block cache, depending on strategy. Any bad block fails
 /// validation.
+#[tracing::instrument(skip_all, fields(epoch = full_tipset.epoch()))]
 async fn validate_tipset<DB: Blockstore + Clone + Send + Sync + 'static, C: Consensus>(
     consensus: Arc<C>,
     state_manager: Arc<StateManager<DB>>,
@@ -1152,6 +1538,7 @@ async fn validate_tipset<DB: Blockstore + Clone + Send + Sync + 'static, C: Cons
     let epoch = full_tipset.epoch();
     let full_tipset_key = full_tipset.key().clone();
 
+    let validation_start = Instant::now();
     let mut validations = FuturesUnordered::new();
     let blocks = full_tipset.into_blocks();
 
@@ -1193,10 +1580,16 @@ async fn validate_tipset<DB: Blockstore + Clone + Send + Sync + 'static, C: Cons
                         }
                     }
                 }
+                metrics::TIPSET_VALIDATION_TIME
+                    .with_label_values(&[metrics::values::INVALID])
+                    .observe(validation_start.elapsed().as_secs_f64());
                 return Err(why);
             }
         }
     }
+    metrics::TIPSET_VALIDATION_TIME
+        .with_label_values(&[metrics::values::VALID])
+        .observe(validation_start.elapsed().as_secs_f64());
     Ok(())
 }
 
@@ -1245,7 +1638,11 @@ async fn validate_block<DB: Blockstore + Clone + Sync + Send + 'static, C: Conse
 
     // Check to ensure all optional values exist
     block_sanity_checks(header).map_err(|e| (*block_cid, e))?;
-    block_timestamp_checks(header).map_err(|e| (*block_cid, e))?;
+    block_timestamp_checks(
+        header,
+        &BlockTiming::from(state_manager.chain_config().as_ref()),
+    )
+    .map_err(|e| (*block_cid, e))?;
 
     let base_tipset = chain_store
         .tipset_from_keys(header.parents())
@@ -1305,17 +1702,28 @@ async fn validate_block<DB: Blockstore + Clone + Sync + Send + 'static, C: Conse
         Ok(())
     }));
 
-    // Parent weight calculation check
-    let v_block_store = state_manager.blockstore().clone();
+    // Parent weight calculation check, the weight of the parent is cached for
+    // its other children
+    let v_chain_store = state_manager.chain_store().clone();
     let v_base_tipset = Arc::clone(&base_tipset);
     let weight = header.weight().clone();
     validations.push(tokio::task::spawn_blocking(move || {
         let _timer = metrics::BLOCK_VALIDATION_TASKS_TIME
             .with_label_values(&[metrics::values::PARENT_WEIGHT_CAL])
             .start_timer();
-        let calc_weight = C::weight(&v_block_store, &v_base_tipset).map_err(|e| {
+        let calc_weight = v_chain_store.weight::<C>(&v_base_tipset).map_err(|e| {
             TipsetRangeSyncerError::Calculation(format!("Error calculating weight: {e}"))
         })?;
+        if std::env::var(AUDIT_CHAIN_WEIGHT_VAR) == Ok("1".to_owned()) {
+            if let Err(e) = audit_parent_weight::<C, _>(
+                v_chain_store.blockstore(),
+                &v_base_tipset,
+                &weight,
+                &calc_weight,
+            ) {
+                warn!("Chain weight audit failed: {e}");
+            }
+        }
         if weight != calc_weight {
             return Err(TipsetRangeSyncerError::<C>::Validation(format!(
                 "Parent weight doesn't match: {weight} (header), {calc_weight} (computed)"
@@ -1357,11 +1765,14 @@ async fn validate_block<DB: Blockstore + Clone + Sync + Send + 'static, C: Conse
 
     // Block signature check
     let v_block = block.clone();
-    validations.push(tokio::task::spawn_blocking(move || {
+    validations.push(tokio::task::spawn(async move {
         let _timer = metrics::BLOCK_VALIDATION_TASKS_TIME
             .with_label_values(&[metrics::values::BLOCK_SIGNATURE_CHECK])
             .start_timer();
-        v_block.header().check_block_signature(&work_addr)?;
+        verifier::verify(kinds::BLOCK_SIGNATURE, move || {
+            v_block.header().check_block_signature(&work_addr)
+        })
+        .await??;
         Ok(())
     }));
 
@@ -1425,19 +1836,13 @@ async fn check_block_messages<DB: Blockstore + Clone + Send + Sync + 'static, C:
         cids.push(m.cid().unwrap().to_bytes());
     }
 
-    if let Some(sig) = block.header().bls_aggregate() {
-        if !verify_bls_aggregate(
-            cids.iter()
-                .map(|x| x.as_slice())
-                .collect::<Vec<&[u8]>>()
-                .as_slice(),
-            pub_keys
-                .iter()
-                .map(|x| &x[..])
-                .collect::<Vec<&[u8]>>()
-                .as_slice(),
-            sig,
-        ) {
+    if let Some(sig) = block.header().bls_aggregate().clone() {
+        let (valid, sig, cids) = verifier::verify(kinds::BLS_AGGREGATE, move || {
+            let valid = verify_bls_messages_aggregate(&cids, &pub_keys, &sig);
+            (valid, sig, cids)
+        })
+        .await?;
+        if !valid {
             return Err(TipsetRangeSyncerError::BlsAggregateSignatureInvalid(
                 format!("{sig:?}"),
                 format!("{cids:?}"),
@@ -1448,55 +1853,29 @@ async fn check_block_messages<DB: Blockstore + Clone + Send + Sync + 'static, C:
     }
 
     let price_list = price_list_by_network_version(network_version);
-    let mut sum_gas_limit = 0;
+    let block_gas_limit = state_manager.chain_config().block_gas_limit;
+    let mut validator = BlockMessagesValidator::new(block_gas_limit);
 
     // Check messages for validity
-    let mut check_msg = |msg: &Message,
-                         account_sequences: &mut HashMap<Address, u64>,
-                         tree: &StateTree<&DB>|
-     -> Result<(), anyhow::Error> {
+    let mut check_msg = |msg: &Message, tree: &StateTree<&DB>| -> Result<(), anyhow::Error> {
         // Phase 1: Syntactic validation
         let min_gas = price_list.on_chain_message(msg.marshal_cbor().unwrap().len());
-        valid_for_block_inclusion(msg, min_gas.total(), network_version)
+        valid_for_block_inclusion(msg, min_gas.total(), network_version, block_gas_limit)
             .map_err(|e| anyhow::anyhow!("{}", e))?;
-        sum_gas_limit += msg.gas_limit;
-        if sum_gas_limit > BLOCK_GAS_LIMIT {
-            anyhow::bail!("block gas limit exceeded");
-        }
 
         // Phase 2: (Partial) Semantic validation
         // Send exists and is an account actor, and sequence is correct
-        let sequence: u64 = match account_sequences.get(&msg.from()) {
-            Some(sequence) => *sequence,
-            None => {
-                let actor = tree.get_actor(&msg.from.into())?.ok_or_else(|| {
-                    anyhow::anyhow!(
-                        "Failed to retrieve nonce for addr: Actor does not exist in state"
-                    )
-                })?;
-                let network_version = state_manager
-                    .chain_config()
-                    .network_version(block.header.epoch());
-                if !is_valid_for_sending(network_version, &actor) {
-                    anyhow::bail!("not valid for sending!");
-                }
-                actor.sequence
+        validator.include(msg, |from| {
+            let actor = tree.get_actor(from)?.ok_or_else(|| {
+                anyhow::anyhow!("Failed to retrieve nonce for addr: Actor does not exist in state")
+            })?;
+            if !is_valid_for_sending(network_version, &actor) {
+                anyhow::bail!("not valid for sending!");
             }
-        };
-
-        // Sequence equality check
-        if sequence != msg.sequence {
-            anyhow::bail!(
-                "Message has incorrect sequence (exp: {} got: {})",
-                sequence,
-                msg.sequence
-            );
-        }
-        account_sequences.insert(msg.from(), sequence + 1);
-        Ok(())
+            Ok(actor.sequence)
+        })
     };
 
-    let mut account_sequences: HashMap<Address, u64> = HashMap::default();
     let block_store = state_manager.blockstore();
     let (state_root, _) = state_manager
         .tipset_state(&base_tipset)
@@ -1510,7 +1889,7 @@ async fn check_block_messages<DB: Blockstore + Clone + Send + Sync + 'static, C:
 
     // Check validity for BLS messages
     for (i, msg) in block.bls_msgs().iter().enumerate() {
-        check_msg(msg, &mut account_sequences, &tree).map_err(|e| {
+        check_msg(msg, &tree).map_err(|e| {
             TipsetRangeSyncerError::<C>::Validation(format!(
                 "Block had invalid BLS message at index {i}: {e}"
             ))
@@ -1518,21 +1897,27 @@ async fn check_block_messages<DB: Blockstore + Clone + Send + Sync + 'static, C:
     }
 
     // Check validity for SECP messages
+    let mut signature_checks = Vec::with_capacity(block.secp_msgs().len());
     for (i, msg) in block.secp_msgs().iter().enumerate() {
-        check_msg(msg.message(), &mut account_sequences, &tree).map_err(|e| {
+        check_msg(msg.message(), &tree).map_err(|e| {
             TipsetRangeSyncerError::<C>::Validation(format!(
                 "block had an invalid secp message at index {i}: {e}"
             ))
         })?;
         // Resolve key address for signature verification
         let key_addr = state_manager
-            .resolve_to_key_addr(&msg.from(), &base_tipset)
+            .resolve_to_key_addr(&msg.from(), &base_tipset, ExecutionLane::Validation)
             .await
             .map_err(|e| TipsetRangeSyncerError::ResolvingAddressFromMessage(e.to_string()))?;
-        // SecP256K1 Signature validation
-        msg.signature
-            .verify(&msg.message().cid().unwrap().to_bytes(), &key_addr)
-            .map_err(TipsetRangeSyncerError::MessageSignatureInvalid)?;
+        let signature = msg.signature.clone();
+        let cid = msg.message().cid().unwrap();
+        signature_checks
+            .push(move || signature_cache::verify_signature(&cid, &signature, &key_addr));
+    }
+    // SecP256K1 Signature validation, batched, skipping the messages already
+    // verified by the message pool
+    for result in verifier::verify_batch(kinds::SECP_SIGNATURES, signature_checks).await {
+        result?.map_err(TipsetRangeSyncerError::MessageSignatureInvalid)?;
     }
 
     // Validate message root from header matches message root
@@ -1567,13 +1952,14 @@ fn block_sanity_checks<C: Consensus>(
 /// Check the clock drift.
 fn block_timestamp_checks<C: Consensus>(
     header: &BlockHeader,
+    timing: &BlockTiming,
 ) -> Result<(), TipsetRangeSyncerError<C>> {
     // TODO: Time should come from a component we control, for testing.
-    let time_now = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .expect("Retrieved system time before UNIX epoch")
-        .as_secs();
-    if header.timestamp() > time_now + ALLOWABLE_CLOCK_DRIFT {
+    let time_now = timing::now_secs();
+    if timing
+        .check_clock_drift(header.timestamp(), time_now)
+        .is_err()
+    {
         return Err(TipsetRangeSyncerError::TimeTravellingBlock(
             time_now,
             header.timestamp(),
@@ -1608,13 +1994,50 @@ fn validate_tipset_against_cache<C: Consensus>(
     Ok(())
 }
 
+/// Recomputes the weight of the tipset `parent` from its state and compares
+/// it with the weight claimed by the header of a child and with the one cached
+/// by the chain store. Mismatches are logged and counted, the validation of
+/// the child checking the claimed weight on its own. Returns the sources of
+/// the weights that don't match.
+fn audit_parent_weight<S: Scale, DB: Blockstore>(
+    db: &DB,
+    parent: &Tipset,
+    claimed: &Weight,
+    cached: &Weight,
+) -> anyhow::Result<Vec<&'static str>> {
+    let recomputed = S::weight(db, parent)?;
+    let mut mismatches = vec![];
+    for (source, weight) in [
+        (metrics::values::HEADER_WEIGHT, claimed),
+        (metrics::values::CACHED_WEIGHT, cached),
+    ] {
+        if weight != &recomputed {
+            warn!(
+                "Weight of the tipset at epoch {} doesn't match: {weight} ({source}), {recomputed} (recomputed), key = {}",
+                parent.epoch(),
+                parent.key()
+            );
+            metrics::CHAIN_WEIGHT_AUDIT_MISMATCHES
+                .with_label_values(&[source])
+                .inc();
+            mismatches.push(source);
+        }
+    }
+    Ok(mismatches)
+}
+
 #[cfg(test)]
 mod test {
     use crate::blocks::{BlockHeader, ElectionProof, Ticket, Tipset};
+    use crate::chain::HeadChange;
+    use crate::db::MemoryDB;
+    use crate::deleg_cns::DelegatedConsensus;
     use crate::json::vrf::VRFProof;
-    use crate::shim::address::Address;
+    use crate::networks::ChainConfig;
+    use crate::test_utils::ChainGenerator;
     use cid::Cid;
     use num_bigint::BigInt;
+    use tempfile::TempDir;
 
     use super::*;
 
@@ -1663,4 +2086,164 @@ mod test {
         assert_eq!(index, 2);
         assert_eq!(weight, &BigInt::from(10));
     }
+
+    fn chain_store(generator: &ChainGenerator, dir: &TempDir) -> ChainStore<MemoryDB> {
+        ChainStore::new(
+            MemoryDB::default(),
+            Arc::new(ChainConfig::default()),
+            generator.genesis().min_ticket_block(),
+            dir.path(),
+        )
+        .unwrap()
+    }
+
+    /// Weight of the tipsets claimed by their headers, i.e. the weight of
+    /// their parents, which the chain generator sets
+    struct ClaimedWeight;
+
+    impl Scale for ClaimedWeight {
+        fn weight<DB>(_: &DB, ts: &Tipset) -> anyhow::Result<Weight>
+        where
+            DB: Blockstore,
+        {
+            Ok(ts.weight().clone())
+        }
+    }
+
+    fn put_headers(chain_store: &ChainStore<MemoryDB>, tipset: &Tipset) {
+        for header in tipset.blocks() {
+            chain_store.add_to_tipset_tracker(header);
+        }
+        put_validated_headers::<_, DelegatedConsensus, ClaimedWeight>(chain_store, tipset).unwrap();
+    }
+
+    #[test]
+    fn tipset_group_keeps_equivocating_tipsets() {
+        let mut generator = ChainGenerator::new();
+        let genesis = generator.genesis().clone();
+        let tipset = generator.mine(&genesis, &[1000, 1001], 0);
+        let equivocated = Arc::new(Tipset::from(
+            generator.equivocate(tipset.min_ticket_block(), &genesis),
+        ));
+
+        let mut group = TipsetGroup::new(tipset.clone());
+        assert!(group.try_add_tipset(equivocated.clone()).is_none());
+        assert!(group.try_add_tipset(tipset.clone()).is_some());
+        // Both claim the weight of the same parent, the tie is broken by the
+        // tickets
+        let (index, _) = group.heaviest_weight();
+        let expected = if tipset.break_weight_tie(&equivocated) {
+            &tipset
+        } else {
+            &equivocated
+        };
+        assert_eq!(&group.tipsets()[index], expected);
+    }
+
+    #[test]
+    fn deep_reorg_to_heavier_fork() {
+        let dir = TempDir::new().unwrap();
+        let mut generator = ChainGenerator::new();
+        let genesis = generator.genesis().clone();
+        let chain_store = chain_store(&generator, &dir);
+        let main = generator.extend(&genesis, &[1000], 20);
+        for tipset in &main {
+            put_headers(&chain_store, tipset);
+        }
+        assert_eq!(chain_store.heaviest_tipset(), main[19]);
+
+        // A fork of two miners from epoch 5, after null rounds, overtakes the
+        // main chain once it claims more weight
+        let mut head_changes = chain_store.publisher().subscribe();
+        let mut fork = vec![generator.mine(&main[4], &[1001, 1002], 2)];
+        fork.extend(generator.extend(&fork[0], &[1001, 1002], 11));
+        for tipset in &fork {
+            put_headers(&chain_store, tipset);
+            let expected = if tipset.weight() > main[19].weight() {
+                tipset
+            } else {
+                &main[19]
+            };
+            assert_eq!(&chain_store.heaviest_tipset(), expected);
+        }
+        let head = chain_store.heaviest_tipset();
+        assert_eq!(&head, fork.last().unwrap());
+        // The main chain is reverted down to the fork, then the fork applied
+        let mut changes = Vec::new();
+        while let Ok(change) = head_changes.try_recv() {
+            changes.push(change);
+        }
+        assert_eq!(changes.len(), 15 + fork.len());
+        for (change, tipset) in changes.iter().zip(main[5..].iter().rev()) {
+            assert!(matches!(change, HeadChange::Revert(ts) if ts == tipset));
+        }
+        for (change, tipset) in changes[15..].iter().zip(&fork) {
+            assert!(matches!(change, HeadChange::Apply(ts) if ts == tipset));
+        }
+
+        // The null rounds of the fork are resolved on the new chain
+        assert_eq!(
+            chain_store.tipset_by_height(6, head.clone(), true).unwrap(),
+            main[4]
+        );
+        assert_eq!(
+            chain_store
+                .tipset_by_height(6, head.clone(), false)
+                .unwrap(),
+            fork[0]
+        );
+
+        // Extending the reverted chain doesn't bring it back while it's
+        // lighter
+        let main_next = generator.mine(&main[19], &[1000], 0);
+        put_headers(&chain_store, &main_next);
+        assert_eq!(chain_store.heaviest_tipset(), head);
+    }
+
+    #[test]
+    fn deep_reorg_below_checkpoint_is_ignored() {
+        let dir = TempDir::new().unwrap();
+        let mut generator = ChainGenerator::new();
+        let genesis = generator.genesis().clone();
+        let chain_store = chain_store(&generator, &dir);
+        let main = generator.extend(&genesis, &[1000], 10);
+        for tipset in &main {
+            put_headers(&chain_store, tipset);
+        }
+        chain_store.set_checkpoint(main[7].clone()).unwrap();
+
+        let fork = generator.extend(&main[4], &[1001, 1002, 1003], 10);
+        for tipset in &fork {
+            put_headers(&chain_store, tipset);
+        }
+        assert!(fork.last().unwrap().weight() > main[9].weight());
+        assert_eq!(chain_store.heaviest_tipset(), main[9]);
+    }
+
+    #[test]
+    fn weight_audit_reports_mismatching_sources() {
+        let db = MemoryDB::default();
+        let parent = Tipset::from(mock_block(1234561, 10, 2));
+        // `ClaimedWeight` recomputes the weight claimed by the parent itself
+        let recomputed = parent.weight().clone();
+
+        let mismatches =
+            audit_parent_weight::<ClaimedWeight, _>(&db, &parent, &recomputed, &recomputed)
+                .unwrap();
+        assert!(mismatches.is_empty());
+
+        let before = metrics::CHAIN_WEIGHT_AUDIT_MISMATCHES
+            .with_label_values(&[metrics::values::HEADER_WEIGHT])
+            .get();
+        let mismatches =
+            audit_parent_weight::<ClaimedWeight, _>(&db, &parent, &BigInt::from(11), &recomputed)
+                .unwrap();
+        assert_eq!(mismatches, vec![metrics::values::HEADER_WEIGHT]);
+        assert!(
+            metrics::CHAIN_WEIGHT_AUDIT_MISMATCHES
+                .with_label_values(&[metrics::values::HEADER_WEIGHT])
+                .get()
+                > before
+        );
+    }
 }
@@ -6,15 +6,14 @@ use std::{
     time::{SystemTime, UNIX_EPOCH},
 };
 
-use crate::blocks::{Block, FullTipset, Tipset, TxMeta};
-use crate::chain::ChainStore;
+use crate::blocks::{Block, FullTipset, Tipset};
+use crate::chain::{compute_message_root, ChainStore};
 use crate::message::SignedMessage;
 use crate::shim::message::Message;
-use crate::utils::db::CborStoreExt;
 use cid::Cid;
-use fvm_ipld_amt::{Amtv0 as Amt, Error as IpldAmtError};
+use fvm_ipld_amt::Error as IpldAmtError;
 use fvm_ipld_blockstore::Blockstore;
-use fvm_ipld_encoding::{Cbor, Error as EncodingError};
+use fvm_ipld_encoding::Error as EncodingError;
 use thiserror::Error;
 
 use crate::chain_sync::bad_block_cache::BadBlockCache;
@@ -124,27 +123,7 @@ impl<'a> TipsetValidator<'a> {
         bls_msgs: &[Message],
         secp_msgs: &[SignedMessage],
     ) -> Result<Cid, Box<TipsetValidationError>> {
-        // Generate message CIDs
-        let bls_cids = bls_msgs
-            .iter()
-            .map(Cbor::cid)
-            .collect::<Result<Vec<Cid>, fvm_ipld_encoding::Error>>()?;
-        let secp_cids = secp_msgs
-            .iter()
-            .map(Cbor::cid)
-            .collect::<Result<Vec<Cid>, fvm_ipld_encoding::Error>>()?;
-
-        // Generate Amt and batch set message values
-        let bls_message_root = Amt::new_from_iter(blockstore, bls_cids)?;
-        let secp_message_root = Amt::new_from_iter(blockstore, secp_cids)?;
-        let meta = TxMeta {
-            bls_message_root,
-            secp_message_root,
-        };
-
-        // Store message roots and receive meta_root CID
-        blockstore
-            .put_cbor_default(&meta)
-            .map_err(|e| Box::new(TipsetValidationError::Blockstore(e.to_string())))
+        compute_message_root(blockstore, bls_msgs, secp_msgs)
+            .map_err(|e| Box::new(TipsetValidationError::IpldAmt(e.to_string())))
     }
 }
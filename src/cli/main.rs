@@ -4,8 +4,11 @@
 use std::ffi::OsString;
 use std::sync::Arc;
 
-use crate::cli_shared::{cli::LogConfig, logger};
-use crate::networks::ChainConfig;
+use crate::cli_shared::{
+    cli::{DaemonConfig, LogConfig},
+    logger,
+};
+use crate::networks::{ChainConfig, NetworkChain};
 use crate::shim::address::{CurrentNetwork, Network};
 use crate::utils::io::ProgressBar;
 use crate::{
@@ -30,7 +33,7 @@ where
         .block_on(async {
             match opts.to_config() {
                 Ok((mut config, _)) => {
-                    logger::setup_logger(&config.log, &opts);
+                    logger::setup_logger(&config.log, &config.daemon, &opts);
                     ProgressBar::set_progress_bars_visibility(config.client.show_progress_bars);
                     if opts.dry_run {
                         return Ok(());
@@ -38,10 +41,8 @@ where
                     let opts = &opts;
                     if opts.chain.is_none() {
                         if let Ok(name) = chain_get_name((), &config.client.rpc_token).await {
-                            if name == "calibnet" {
-                                config.chain = Arc::new(ChainConfig::calibnet());
-                            } else if name == "devnet" {
-                                config.chain = Arc::new(ChainConfig::devnet());
+                            if let Ok(chain) = name.parse::<NetworkChain>() {
+                                config.chain = Arc::new(ChainConfig::from_chain(&chain));
                             }
                         }
                     }
@@ -56,19 +57,22 @@ where
                         Subcommand::Net(cmd) => cmd.run(config).await,
                         Subcommand::Wallet(cmd) => cmd.run(config).await,
                         Subcommand::Sync(cmd) => cmd.run(config).await,
-                        Subcommand::Mpool(cmd) => cmd.run(config),
+                        Subcommand::Mpool(cmd) => cmd.run(config).await,
                         Subcommand::State(cmd) => cmd.run(config).await,
-                        Subcommand::Config(cmd) => cmd.run(&config, &mut std::io::stdout()),
+                        Subcommand::Config(cmd) => cmd.run(&config, &mut std::io::stdout()).await,
                         Subcommand::Send(cmd) => cmd.run(config).await,
                         Subcommand::Info(cmd) => cmd.run(config, opts).await,
                         Subcommand::DB(cmd) => cmd.run(&config).await,
+                        Subcommand::Operation(cmd) => cmd.run(config).await,
+                        Subcommand::Watch(cmd) => cmd.run(config).await,
+                        Subcommand::Devnet(cmd) => cmd.run().await,
                         Subcommand::Snapshot(cmd) => cmd.run(config).await,
                         Subcommand::Attach(cmd) => cmd.run(config),
                         Subcommand::Shutdown(cmd) => cmd.run(config).await,
                     }
                 }
                 Err(e) => {
-                    logger::setup_logger(&LogConfig::default(), &opts);
+                    logger::setup_logger(&LogConfig::default(), &DaemonConfig::default(), &opts);
                     cli_error_and_die(format!("Error parsing config: {e}"), 1);
                 }
             }
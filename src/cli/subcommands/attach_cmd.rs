@@ -8,11 +8,17 @@ use std::{
 };
 
 use crate::chain_sync::SyncStage;
+use crate::cli::humantoken::TokenAmountPretty;
 use crate::json::message::json::MessageJson;
-use crate::rpc_api::mpool_api::MpoolPushMessageResult;
+use crate::rpc_api::{chain_api::ChainHeadResult, mpool_api::MpoolPushMessageResult};
 use crate::rpc_client::node_ops::node_status;
 use crate::rpc_client::*;
-use crate::shim::{address::Address, clock::ChainEpoch, message::Message_v3};
+use crate::shim::{
+    address::{Address, CurrentNetwork, Network},
+    clock::ChainEpoch,
+    econ::TokenAmount,
+    message::Message_v3,
+};
 use boa_engine::{
     object::{FunctionBuilder, JsArray},
     prelude::JsObject,
@@ -23,6 +29,7 @@ use boa_engine::{
 use convert_case::{Case, Casing};
 use directories::BaseDirs;
 use fvm_shared::METHOD_SEND;
+use num::BigInt;
 
 use rustyline::{config::Config as RustyLineConfig, EditMode, Editor};
 use serde::Serialize;
@@ -240,6 +247,75 @@ async fn send_message(
     mpool_push_message((json_message, None), auth_token).await
 }
 
+type ChainHeadParams = ();
+
+async fn chain_head(
+    _: ChainHeadParams,
+    auth_token: &Option<String>,
+) -> Result<ChainHeadResult, jsonrpc_v2::Error> {
+    crate::rpc_client::chain_head(auth_token).await
+}
+
+type ToAttoParams = (String,);
+type ToAttoResult = String;
+
+/// Converts an amount such as `1.5 FIL` or `100 nanoFIL` to `attoFIL`, the
+/// unit the API uses. The default unit is FIL.
+async fn to_atto(
+    params: ToAttoParams,
+    _auth_token: &Option<String>,
+) -> Result<ToAttoResult, jsonrpc_v2::Error> {
+    let amount = humantoken::parse(&params.0)?;
+    Ok(amount.atto().to_string())
+}
+
+type FormatFilParams = (String,);
+type FormatFilResult = String;
+
+/// Formats an amount in `attoFIL`, as returned by the API, in FIL.
+async fn format_fil(
+    params: FormatFilParams,
+    _auth_token: &Option<String>,
+) -> Result<FormatFilResult, jsonrpc_v2::Error> {
+    let amount = TokenAmount::from_atto(params.0.parse::<BigInt>()?);
+    Ok(format!("{:#}", amount.pretty()))
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct AddressInfo {
+    address: String,
+    network: String,
+    protocol: String,
+    id: Option<u64>,
+}
+
+type ParseAddressParams = (String,);
+type ParseAddressResult = AddressInfo;
+
+/// Breaks an address down into its network, protocol and, for ID addresses,
+/// actor ID.
+async fn parse_address(
+    params: ParseAddressParams,
+    _auth_token: &Option<String>,
+) -> Result<ParseAddressResult, jsonrpc_v2::Error> {
+    let (network, address) = match Network::Mainnet.parse_address(&params.0) {
+        Ok(address) => (Network::Mainnet, address),
+        Err(_) => (Network::Testnet, Network::Testnet.parse_address(&params.0)?),
+    };
+    let address = Address::from(address);
+    Ok(AddressInfo {
+        address: CurrentNetwork::with(network, || address.to_string()),
+        network: match network {
+            Network::Mainnet => "mainnet",
+            Network::Testnet => "testnet",
+        }
+        .into(),
+        protocol: format!("{:?}", address.protocol()),
+        id: address.id().ok(),
+    })
+}
+
 type SleepParams = (u64,);
 type SleepResult = ();
 
@@ -298,6 +374,12 @@ impl AttachCommand {
 
         // Chain API
         bind_func!(context, token, chain_get_name);
+        bind_func!(context, token, chain_head);
+        bind_func!(context, token, chain_get_block);
+        bind_func!(context, token, chain_get_tipset);
+        bind_func!(context, token, chain_get_tipset_by_height);
+        bind_func!(context, token, chain_get_message);
+        bind_func!(context, token, chain_read_obj);
 
         // Net API
         bind_func!(context, token, net_addrs_listen);
@@ -335,6 +417,11 @@ impl AttachCommand {
         bind_func!(context, token, send_message);
         bind_func!(context, token, sleep);
         bind_func!(context, token, sleep_tipsets);
+
+        // Unit conversion and address helpers
+        bind_func!(context, token, to_atto);
+        bind_func!(context, token, format_fil);
+        bind_func!(context, token, parse_address);
     }
 
     fn import_prelude(&self, context: &mut Context) -> anyhow::Result<()> {
@@ -347,6 +434,7 @@ impl AttachCommand {
             if (Prelude.showWallet) { showWallet = Prelude.showWallet; }
             if (Prelude.showSyncStatus) { showSyncStatus = Prelude.showSyncStatus; }
             if (Prelude.sendFIL) { sendFIL = Prelude.sendFIL; }
+            if (Prelude.showHead) { showHead = Prelude.showHead; }
         ";
         let result = context.eval(INIT);
         if let Err(err) = result {
@@ -1,14 +1,20 @@
 // Copyright 2019-2023 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
-use crate::blocks::TipsetKeys;
+use std::path::PathBuf;
+
+use crate::blocks::{tipset_keys_json::TipsetKeysJson, TipsetKeys};
+use crate::cli_shared::snapshot::{self, TrustedVendor};
 use crate::json::cid::CidJson;
+use crate::rpc_api::chain_api::{ChainExportParams, ChainPruneParams};
 use crate::rpc_client::chain_ops::*;
 use crate::shim::clock::ChainEpoch;
 use anyhow::bail;
+use chrono::Utc;
 use cid::Cid;
 use clap::Subcommand;
 use futures::TryFutureExt;
+use human_repr::HumanCount;
 
 use super::*;
 
@@ -62,6 +68,29 @@ pub enum ChainCommands {
         #[arg(short, long, aliases = ["yes", "no-confirm"], short_alias = 'y')]
         force: bool,
     },
+
+    /// Prunes the database down to the data reachable from the head: the
+    /// headers of the chain and the recent state roots. A snapshot of this
+    /// data is exported first, to recover from if the pruning goes wrong.
+    Prune {
+        /// Number of recent state roots to keep. Defaults to
+        /// `chain.recent_state_roots` of the configuration.
+        #[arg(long)]
+        recent_stateroots: Option<i64>,
+        /// Safety snapshot filename or directory. Defaults to
+        /// `./forest_snapshot_{chain}_{year}-{month}-{day}_height_{epoch}.car.zst`.
+        #[arg(short, default_value = ".", verbatim_doc_comment)]
+        output_path: PathBuf,
+        /// Don't export the safety snapshot.
+        #[arg(long)]
+        skip_snapshot: bool,
+        /// Only report the space that would be reclaimed.
+        #[arg(long)]
+        dry_run: bool,
+        /// Skip confirmation dialogue.
+        #[arg(short, long, aliases = ["yes", "no-confirm"], short_alias = 'y')]
+        force: bool,
+    },
 }
 
 impl ChainCommands {
@@ -75,8 +104,6 @@ impl ChainCommands {
             }
             Self::Head => print_rpc_res_cids(chain_head(&config.client.rpc_token).await),
             Self::TipsetHash { epoch } => {
-                use crate::blocks::tipset_keys_json::TipsetKeysJson;
-
                 let TipsetJson(head) = chain_head(&config.client.rpc_token)
                     .await
                     .map_err(handle_rpc_err)?;
@@ -138,13 +165,95 @@ impl ChainCommands {
                 .await
                 .map_err(handle_rpc_err)
             }
+            Self::Prune {
+                recent_stateroots,
+                output_path,
+                skip_snapshot,
+                dry_run,
+                force: no_confirm,
+            } => {
+                let recent_roots = recent_stateroots.unwrap_or(config.chain.recent_state_roots);
+                if *dry_run {
+                    let report = chain_prune(
+                        ChainPruneParams {
+                            recent_roots,
+                            dry_run: true,
+                        },
+                        &config.client.rpc_token,
+                    )
+                    .await
+                    .map_err(handle_rpc_err)?;
+                    println!("Database size:  {}", report.total_bytes.human_count_bytes());
+                    println!(
+                        "Reachable data: {}",
+                        report.reachable_bytes.human_count_bytes()
+                    );
+                    println!(
+                        "Reclaimable:    {}",
+                        report.reclaimed_bytes().human_count_bytes()
+                    );
+                    return Ok(());
+                }
+                maybe_confirm(*no_confirm, PRUNE_CONFIRMATION_MESSAGE)?;
+
+                if !skip_snapshot {
+                    let TipsetJson(head) = chain_head(&config.client.rpc_token)
+                        .await
+                        .map_err(handle_rpc_err)?;
+                    let output_path = match output_path.is_dir() {
+                        true => {
+                            let chain_name = chain_get_name((), &config.client.rpc_token)
+                                .await
+                                .map_err(handle_rpc_err)?;
+                            output_path.join(snapshot::filename(
+                                TrustedVendor::Forest,
+                                chain_name,
+                                Utc::now().date_naive(),
+                                head.epoch(),
+                            ))
+                        }
+                        false => output_path.clone(),
+                    };
+                    let params = ChainExportParams {
+                        epoch: head.epoch(),
+                        recent_roots,
+                        output_path,
+                        tipset_keys: TipsetKeysJson(head.key().clone()),
+                        skip_checksum: false,
+                        dry_run: false,
+                        car_v2: false,
+                        forest_car: false,
+                    };
+                    let out = chain_export(params, &config.client.rpc_token)
+                        .await
+                        .map_err(handle_rpc_err)?;
+                    println!("Safety snapshot located at {}", out.display());
+                }
+
+                let report = chain_prune(
+                    ChainPruneParams {
+                        recent_roots,
+                        dry_run: false,
+                    },
+                    &config.client.rpc_token,
+                )
+                .await
+                .map_err(handle_rpc_err)?;
+                println!(
+                    "Pruning completed. Database size: {} -> {}, reclaimed: {}",
+                    report.total_bytes.human_count_bytes(),
+                    report.remaining_bytes.human_count_bytes(),
+                    report.reclaimed_bytes().human_count_bytes()
+                );
+                Ok(())
+            }
         }
     }
 }
 
 /// If `epoch_or_offset` is negative, get the tipset that many blocks before the
 /// current head. Else treat `epoch_or_offset` as an epoch, and get that tipset.
-async fn tipset_by_epoch_or_offset(
+pub(super) async fn tipset_by_epoch_or_offset(
     epoch_or_offset: i64,
     auth_token: &Option<String>,
 ) -> Result<TipsetJson, JsonRpcError> {
@@ -161,6 +270,9 @@ async fn tipset_by_epoch_or_offset(
 const SET_HEAD_CONFIRMATION_MESSAGE: &str =
     "Manually setting head is an unsafe operation that could brick the node! Continue?";
 
+const PRUNE_CONFIRMATION_MESSAGE: &str =
+    "Pruning deletes the data unreachable from the head, it can't be undone! Continue?";
+
 fn maybe_confirm(no_confirm: bool, prompt: impl Into<String>) -> anyhow::Result<()> {
     if no_confirm {
         return Ok(());
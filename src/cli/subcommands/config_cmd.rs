@@ -6,16 +6,31 @@ use std::io::Write;
 use anyhow::Context;
 use clap::Subcommand;
 
-use crate::cli::subcommands::Config;
+use crate::cli::subcommands::{handle_rpc_err, Config};
+use crate::rpc_client::config_ops::{config_reload, config_set};
 
 #[derive(Debug, Subcommand)]
 pub enum ConfigCommands {
     /// Dump current configuration to standard output
     Dump,
+    /// Change the configuration of the running node and save it to its
+    /// configuration file
+    Set {
+        /// One of `log.filters`, `network.target_peer_count`,
+        /// `network.max_peer_count`, `client.gc_interval`,
+        /// `mpool.size_limit_high`, `mpool.size_limit_low`,
+        /// `mpool.randomize_chain_ties` or `mpool.greedy_selection_threshold`
+        key: String,
+        /// Value in TOML syntax, e.g. `50` or `[{ module = "forest", level =
+        /// "debug" }]`
+        value: String,
+    },
+    /// Reload the configuration file of the running node, as on `SIGHUP`
+    Reload,
 }
 
 impl ConfigCommands {
-    pub fn run<W: Write + Unpin>(&self, config: &Config, sink: &mut W) -> anyhow::Result<()> {
+    pub async fn run<W: Write + Unpin>(&self, config: &Config, sink: &mut W) -> anyhow::Result<()> {
         match self {
             Self::Dump => writeln!(
                 sink,
@@ -24,6 +39,18 @@ impl ConfigCommands {
                     .context("Could not convert configuration to TOML format")?
             )
             .context("Failed to write the configuration"),
+            Self::Set { key, value } => {
+                config_set((key.clone(), value.clone()), &config.client.rpc_token)
+                    .await
+                    .map_err(handle_rpc_err)?;
+                writeln!(sink, "Set {key} to {value}").context("Failed to write the result")
+            }
+            Self::Reload => {
+                config_reload((), &config.client.rpc_token)
+                    .await
+                    .map_err(handle_rpc_err)?;
+                writeln!(sink, "Configuration reloaded").context("Failed to write the result")
+            }
         }
     }
 }
@@ -39,6 +66,7 @@ mod tests {
 
         ConfigCommands::Dump
             .run(&expected_config, &mut sink)
+            .await
             .unwrap();
 
         let actual_config: Config = toml::from_str(std::str::from_utf8(sink.buffer()).unwrap())
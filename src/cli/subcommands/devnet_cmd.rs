@@ -0,0 +1,315 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use std::{
+    path::{Path, PathBuf},
+    process::Stdio,
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crate::cli::humantoken;
+use crate::cli_shared::cli::Config;
+use crate::daemon::bundle::get_actors_bundle;
+use crate::db::MemoryDB;
+use crate::genesis::{
+    forest_load_car, generate_genesis, write_genesis_car, GenesisTemplate, GENESIS_MINER_ID,
+};
+use crate::key_management::{KeyStore, KeyStoreConfig, Wallet};
+use crate::libp2p::Multiaddr;
+use crate::networks::{ChainSpec, Height, NetworkChain, UpgradeSpec};
+use crate::rpc_api::chain_api::{ChainHeadResult, CHAIN_HEAD};
+use crate::rpc_client::call_api;
+use crate::shim::{
+    address::{Address, CurrentNetwork, Network},
+    crypto::SignatureType,
+    econ::TokenAmount,
+};
+use anyhow::{bail, ensure, Context};
+use clap::Subcommand;
+use tokio::process::{Child, Command};
+use tokio_util::compat::TokioAsyncReadCompatExt;
+
+/// Name of the chain of the devnets, also the name of their data directory
+const DEVNET_NAME: &str = "devnet";
+
+/// Time the node has to start and serve RPC requests
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(120);
+
+#[derive(Debug, Subcommand)]
+pub enum DevnetCommands {
+    /// Start a local single-node network that mines a block every
+    /// `--block-delay` seconds, with test accounts funded at genesis. The
+    /// node must be built with the `deleg_cns` feature.
+    Up {
+        /// Directory of the node data, a temporary one by default. The
+        /// genesis, the keys and the chain are kept in between runs.
+        #[arg(long)]
+        data_dir: Option<PathBuf>,
+        /// Duration of an epoch, in seconds
+        #[arg(long, default_value_t = 4)]
+        block_delay: u64,
+        /// Number of test accounts to create and fund, on the first run only
+        #[arg(long, default_value_t = 5)]
+        accounts: usize,
+        /// Balance of each test account, on the first run only
+        #[arg(long, value_parser = humantoken::parse, default_value = "1000 FIL")]
+        balance: TokenAmount,
+        /// Port of the RPC endpoint
+        #[arg(long, default_value_t = 1234)]
+        rpc_port: u16,
+        /// Forest daemon binary to run
+        #[arg(long, default_value = "forest")]
+        forest_bin: PathBuf,
+    },
+}
+
+impl DevnetCommands {
+    pub async fn run(self) -> anyhow::Result<()> {
+        match self {
+            Self::Up {
+                data_dir,
+                block_delay,
+                accounts,
+                balance,
+                rpc_port,
+                forest_bin,
+            } => {
+                // The keys of the wallet are named after the testnet
+                // addresses on a devnet, as the node does
+                CurrentNetwork::set_global(Network::Testnet);
+                let data_dir =
+                    data_dir.unwrap_or_else(|| std::env::temp_dir().join("forest-devnet"));
+                std::fs::create_dir_all(&data_dir)?;
+
+                let spec = chain_spec(block_delay);
+                let chain_spec_path = data_dir.join("devnet.toml");
+                std::fs::write(&chain_spec_path, toml::to_string(&spec)?)?;
+                let config = node_config(&data_dir)?;
+                let config_path = data_dir.join("config.toml");
+                std::fs::write(&config_path, toml::to_string(&config)?)?;
+
+                let (worker, accounts) = init_wallet(&data_dir, accounts)?;
+                let genesis = data_dir.join("genesis.car");
+                // The genesis of a previous run is kept, along with the chain
+                // built on top of it
+                if !genesis.exists() {
+                    let config = Config {
+                        chain: Arc::new(spec.into_config()?),
+                        ..config
+                    };
+                    write_genesis(&config, &genesis, worker, &accounts, &balance).await?;
+                }
+
+                let token_path = data_dir.join("token.jwt");
+                // A token left by a previous run would be taken for the one of
+                // the new node
+                if token_path.exists() {
+                    std::fs::remove_file(&token_path)?;
+                }
+                let mut node = Command::new(&forest_bin)
+                    .arg("--config")
+                    .arg(&config_path)
+                    .arg("--chain")
+                    .arg(&chain_spec_path)
+                    .arg("--genesis")
+                    .arg(&genesis)
+                    .arg("--rpc-address")
+                    .arg(format!("127.0.0.1:{rpc_port}"))
+                    .arg("--save-token")
+                    .arg(&token_path)
+                    .stdout(Stdio::null())
+                    .stderr(Stdio::inherit())
+                    .kill_on_drop(true)
+                    .spawn()
+                    .with_context(|| format!("failed to start {}", forest_bin.display()))?;
+
+                let multiaddr: Multiaddr = format!("/ip4/127.0.0.1/tcp/{rpc_port}/http").parse()?;
+                let token = wait_for_node(&mut node, &token_path, &multiaddr).await?;
+
+                println!();
+                println!("Devnet running, a block every {block_delay}s");
+                println!("RPC endpoint: http://127.0.0.1:{rpc_port}/rpc/v0");
+                println!("FULLNODE_API_INFO={token}:{multiaddr}");
+                println!("Miner: t0{GENESIS_MINER_ID}, worker {worker}");
+                println!(
+                    "Accounts funded at genesis, also in the keystore of {}:",
+                    data_dir.display()
+                );
+                for account in &accounts {
+                    println!("  {account}");
+                }
+                println!();
+                println!("Press Ctrl-C to stop");
+
+                tokio::select! {
+                    status = node.wait() => bail!("the node stopped: {}", status?),
+                    _ = tokio::signal::ctrl_c() => {
+                        node.kill().await?;
+                        Ok(())
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Returns the chain spec of the devnet, with a fast block time. The genesis
+/// already runs the latest network version, so that the devnet has the
+/// actors of the current networks from its first block.
+fn chain_spec(block_delay: u64) -> ChainSpec {
+    let upgrades = [Height::Lightning, Height::Thunder]
+        .into_iter()
+        .map(|height| UpgradeSpec {
+            height,
+            epoch: -1,
+            bundle: None,
+        })
+        .collect();
+    ChainSpec {
+        name: DEVNET_NAME.into(),
+        base: Some(NetworkChain::Devnet(DEVNET_NAME.into())),
+        genesis_cid: None,
+        bootstrap_peers: Some(vec![]),
+        block_delay_secs: Some(block_delay),
+        propagation_delay_secs: None,
+        allowable_clock_drift_secs: None,
+        eth_chain_id: None,
+        block_gas_limit: None,
+        block_message_limit: None,
+        max_tipset_blocks: None,
+        upgrades,
+    }
+}
+
+/// Returns the configuration of a node that is alone on its network, so that
+/// it neither looks for peers nor waits for them to tell the head of the
+/// chain.
+fn node_config(data_dir: &Path) -> anyhow::Result<Config> {
+    let mut config = Config::default();
+    config.client.data_dir = data_dir.to_owned();
+    config.client.encrypt_keystore = false;
+    config.network.listening_multiaddrs = vec!["/ip4/127.0.0.1/tcp/0".parse()?];
+    config.network.kademlia = false;
+    config.network.mdns = false;
+    config.network.target_peer_count = 1;
+    config.sync.tipset_sample_size = 0;
+    Ok(config)
+}
+
+/// Creates the worker key of the miner, as the default one of the keystore of
+/// the node since the delegated proposer starts if it finds the key there,
+/// and the test accounts. The keys of a previous run are kept, along with the
+/// genesis that funds them. Returns the address of the worker and the ones of
+/// the accounts.
+fn init_wallet(data_dir: &Path, accounts: usize) -> anyhow::Result<(Address, Vec<Address>)> {
+    let keystore = KeyStore::new(KeyStoreConfig::Persistent(data_dir.to_owned()))?;
+    let mut wallet = Wallet::new(keystore);
+    if let Ok(worker) = wallet.get_default() {
+        let accounts = wallet
+            .list_addrs()?
+            .into_iter()
+            .filter(|a| *a != worker)
+            .collect();
+        return Ok((worker, accounts));
+    }
+    let worker = wallet.generate_addr(SignatureType::Bls)?;
+    wallet.set_default(worker)?;
+    let accounts = (0..accounts)
+        .map(|_| wallet.generate_addr(SignatureType::Secp256k1))
+        .collect::<anyhow::Result<_>>()?;
+    Ok((worker, accounts))
+}
+
+/// Generates the genesis of the devnet, with the miner that delegated
+/// consensus elects and the funded accounts, and writes it to `path` along
+/// with the actors bundle.
+async fn write_genesis(
+    config: &Config,
+    path: &Path,
+    worker: Address,
+    accounts: &[Address],
+    balance: &TokenAmount,
+) -> anyhow::Result<()> {
+    let db = MemoryDB::default();
+    let bundle = get_actors_bundle(config, Height::Lightning).await?;
+    let (roots, _) = forest_load_car(db.clone(), bundle.compat(), &Default::default()).await?;
+    ensure!(roots.len() == 1, "expected one root in the actors bundle");
+    let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+    let template = GenesisTemplate {
+        network_name: DEVNET_NAME.into(),
+        timestamp,
+        manifest: roots[0],
+        worker,
+        accounts: accounts.to_vec(),
+        balance: balance.clone(),
+    };
+    let genesis = generate_genesis(&db, &template)?;
+    // Written aside first, so that an interrupted run doesn't leave a
+    // truncated genesis behind
+    let partial = path.with_extension("car.partial");
+    write_genesis_car(&db, &genesis, &partial).await?;
+    std::fs::rename(&partial, path)?;
+    println!("Generated the genesis {}", genesis.cid());
+    Ok(())
+}
+
+/// Waits for the node to write its admin token and to serve RPC requests,
+/// returning the token.
+async fn wait_for_node(
+    node: &mut Child,
+    token_path: &Path,
+    multiaddr: &Multiaddr,
+) -> anyhow::Result<String> {
+    let start = tokio::time::Instant::now();
+    loop {
+        if let Some(status) = node.try_wait()? {
+            bail!("the node stopped: {status}");
+        }
+        if start.elapsed() > STARTUP_TIMEOUT {
+            bail!(
+                "the node did not start within {}s",
+                STARTUP_TIMEOUT.as_secs()
+            );
+        }
+        if let Ok(token) = std::fs::read_to_string(token_path) {
+            let token = Some(token.trim().to_owned());
+            let head: Result<ChainHeadResult, _> =
+                call_api(multiaddr, &token, CHAIN_HEAD, ()).await;
+            if head.is_ok() {
+                return Ok(token.unwrap_or_default());
+            }
+        }
+        tokio::time::sleep(Duration::from_millis(500)).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::networks::ChainConfig;
+    use crate::shim::version::NetworkVersion;
+
+    #[test]
+    fn genesis_runs_the_latest_actors() {
+        let config: ChainConfig = chain_spec(4).into_config().unwrap();
+        assert_eq!(config.network_version(0), NetworkVersion::V20);
+        assert!(config.height_infos[Height::Lightning as usize]
+            .bundle
+            .is_some());
+    }
+
+    #[test]
+    fn wallet_is_kept_between_runs() {
+        let dir = tempfile::tempdir().unwrap();
+        let (worker, accounts) = init_wallet(dir.path(), 3).unwrap();
+        assert_eq!(accounts.len(), 3);
+        assert!(!accounts.contains(&worker));
+        let (worker_again, mut accounts_again) = init_wallet(dir.path(), 5).unwrap();
+        assert_eq!(worker_again, worker);
+        let mut accounts = accounts;
+        accounts.sort_by_key(|a| a.to_string());
+        accounts_again.sort_by_key(|a| a.to_string());
+        assert_eq!(accounts_again, accounts);
+    }
+}
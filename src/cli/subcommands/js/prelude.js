@@ -2,7 +2,7 @@
 // SPDX-License-Identifier: Apache-2.0, MIT
 
 /* global netPeers, netDisconnect, walletList, walletDefaultAddress, walletBalance, syncStatus */
-/* global sendMessage */
+/* global sendMessage, chainHead, formatFil */
 
 module.exports = {
   greet: function () {
@@ -43,7 +43,7 @@ module.exports = {
     let buffer = "Address                                         Balance\n";
     for (var i = 0; i < addrs.length; i++) {
       const addr = addrs[i];
-      const line = `${addr}       ${walletBalance(addr)} attoFIL\n`;
+      const line = `${addr}       ${formatFil(walletBalance(addr))}\n`;
       if (addr == defaultAddr) {
         buffer = buffer.concat("\033[1m", line, "\033[0m");
       } else {
@@ -56,6 +56,14 @@ module.exports = {
     let from = walletDefaultAddress();
     return sendMessage(from, to, amount.toString());
   },
+  showHead: function () {
+    let head = chainHead();
+    let buffer = `Epoch:  ${head.Height}\nBlocks:\n`;
+    for (var i = 0; i < head.Cids.length; i++) {
+      buffer += `${i}:\t${head.Cids[i]["/"]}\n`;
+    }
+    console.log(buffer);
+  },
   showSyncStatus: function () {
     let stage = syncStatus().ActiveSyncs[0].Stage;
     let height = syncStatus().ActiveSyncs[0].Epoch;
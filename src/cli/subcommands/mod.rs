@@ -11,16 +11,19 @@ mod auth_cmd;
 mod chain_cmd;
 mod config_cmd;
 mod db_cmd;
+mod devnet_cmd;
 mod fetch_params_cmd;
 mod info_cmd;
 mod mpool_cmd;
 mod net_cmd;
+mod operation_cmd;
 pub mod send_cmd;
 mod shutdown_cmd;
 mod snapshot_cmd;
 mod state_cmd;
 mod sync_cmd;
 mod wallet_cmd;
+mod watch_cmd;
 
 use std::io::{self, Write};
 
@@ -36,10 +39,11 @@ use serde::Serialize;
 
 pub(super) use self::{
     attach_cmd::AttachCommand, auth_cmd::AuthCommands, chain_cmd::ChainCommands,
-    config_cmd::ConfigCommands, db_cmd::DBCommands, fetch_params_cmd::FetchCommands,
-    mpool_cmd::MpoolCommands, net_cmd::NetCommands, send_cmd::SendCommand,
-    shutdown_cmd::ShutdownCommand, snapshot_cmd::SnapshotCommands, state_cmd::StateCommands,
-    sync_cmd::SyncCommands, wallet_cmd::WalletCommands,
+    config_cmd::ConfigCommands, db_cmd::DBCommands, devnet_cmd::DevnetCommands,
+    fetch_params_cmd::FetchCommands, mpool_cmd::MpoolCommands, net_cmd::NetCommands,
+    operation_cmd::OperationCommands, send_cmd::SendCommand, shutdown_cmd::ShutdownCommand,
+    snapshot_cmd::SnapshotCommands, state_cmd::StateCommands, sync_cmd::SyncCommands,
+    wallet_cmd::WalletCommands, watch_cmd::WatchCommands,
 };
 use crate::cli::subcommands::info_cmd::InfoCommand;
 
@@ -108,6 +112,18 @@ pub enum Subcommand {
     #[command(subcommand)]
     DB(DBCommands),
 
+    /// Monitor and cancel long-running operations
+    #[command(subcommand)]
+    Operation(OperationCommands),
+
+    /// Watch the messages of addresses
+    #[command(subcommand)]
+    Watch(WatchCommands),
+
+    /// Run a local single-node network for development
+    #[command(subcommand)]
+    Devnet(DevnetCommands),
+
     /// Attach to daemon via a JavaScript console
     Attach(AttachCommand),
 
@@ -116,7 +132,7 @@ pub enum Subcommand {
 }
 
 /// Pretty-print a JSON-RPC error and exit
-pub(super) fn handle_rpc_err(e: JsonRpcError) -> anyhow::Error {
+pub(crate) fn handle_rpc_err(e: JsonRpcError) -> anyhow::Error {
     match serde_json::to_string(&e) {
         Ok(err_msg) => anyhow::Error::msg(err_msg),
         Err(err) => err.into(),
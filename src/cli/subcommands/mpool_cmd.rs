@@ -1,16 +1,408 @@
 // Copyright 2019-2023 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
+use crate::blocks::tipset_keys_json::TipsetKeysJson;
+use crate::json::{
+    address::json::AddressJson,
+    cid::{vec::CidJsonVec, CidJson},
+    message::json::MessageJson,
+    signature::json::SignatureJson,
+    signed_message::json::SignedMessageJson,
+};
+use crate::message::{Message as _, SignedMessage};
+use crate::message_pool::min_rbf_premium;
+use crate::rpc_client::{
+    chain_head, gas_ops::gas_estimate_message_gas, mpool_batch_push, mpool_clear, mpool_pending,
+    mpool_push, mpool_sequence_gaps, mpool_stat, wallet_list, wallet_sign, wallet_sign_batch,
+};
+use crate::shim::{
+    address::{Address, StrictAddress},
+    econ::TokenAmount,
+    message::{Message, Message_v3},
+};
+use ahash::HashSet;
+use anyhow::Context;
+use base64::{prelude::BASE64_STANDARD, Engine};
 use clap::Subcommand;
+use fvm_ipld_encoding::Cbor;
+use fvm_shared3::METHOD_SEND;
+use num::Zero as _;
 
-use super::Config;
+use super::{handle_rpc_err, Config};
+use crate::cli::humantoken::{self, TokenAmountPretty as _};
 
 #[derive(Debug, Subcommand)]
-pub enum MpoolCommands {}
+pub enum MpoolCommands {
+    /// Print the pending messages
+    Pending {
+        /// Only print the messages sent from the addresses of the wallet
+        #[arg(long)]
+        local: bool,
+        /// Only print the CIDs of the messages
+        #[arg(long)]
+        cids: bool,
+        /// Only print the messages sent to this address
+        #[arg(long)]
+        to: Option<StrictAddress>,
+        /// Only print the messages sent from this address
+        #[arg(long)]
+        from: Option<StrictAddress>,
+    },
+    /// Print the number of pending messages of each sender
+    Stat {
+        /// Only print the senders of the wallet
+        #[arg(long)]
+        local: bool,
+    },
+    /// Replace a pending message with one paying a higher gas premium
+    Replace {
+        /// Sender of the message to replace
+        from: StrictAddress,
+        /// Sequence of the message to replace
+        nonce: u64,
+        /// Gas premium of the new message, the lowest one accepted by the
+        /// pool by default
+        #[arg(long, value_parser = humantoken::parse)]
+        gas_premium: Option<TokenAmount>,
+        /// Gas fee cap of the new message, that of the replaced message by
+        /// default
+        #[arg(long, value_parser = humantoken::parse)]
+        gas_feecap: Option<TokenAmount>,
+        /// Gas limit of the new message, that of the replaced message by
+        /// default
+        #[arg(long)]
+        gas_limit: Option<u64>,
+        /// Estimate the gas fee cap and premium of the new message
+        #[arg(long, conflicts_with_all = ["gas_premium", "gas_feecap"])]
+        auto: bool,
+    },
+    /// Fill the gaps in the nonces of the pending messages of a sender with
+    /// messages sending nothing to the sender itself, so that the messages
+    /// after the gaps can be included
+    FixNonce {
+        /// Sender of the pending messages
+        from: StrictAddress,
+        /// Also cancel the messages after the first gap, by replacing them
+        /// with messages sending nothing
+        #[arg(long)]
+        cancel: bool,
+        /// Only print the gaps
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Remove all the pending messages
+    Clear {
+        /// Also remove the messages sent from the addresses of the wallet
+        #[arg(long)]
+        local: bool,
+        /// Confirm the removal of the messages
+        #[arg(long)]
+        really_do_it: bool,
+    },
+}
 
 impl MpoolCommands {
-    pub fn run(&self, _config: Config) -> anyhow::Result<()> {
-        // match self {}
-        Ok(())
+    pub async fn run(&self, config: Config) -> anyhow::Result<()> {
+        let token = &config.client.rpc_token;
+        match self {
+            Self::Pending {
+                local,
+                cids,
+                to,
+                from,
+            } => {
+                let local_addrs = if *local {
+                    Some(wallet_addresses(token).await?)
+                } else {
+                    None
+                };
+                let head = chain_head(token).await.map_err(handle_rpc_err)?;
+                let pending = mpool_pending((CidJsonVec(head.0.cids().to_vec()),), token)
+                    .await
+                    .map_err(handle_rpc_err)?;
+                for message in pending {
+                    if local_addrs
+                        .as_ref()
+                        .map_or(false, |addrs| !addrs.contains(&message.from()))
+                        || to.map_or(false, |to| message.to() != to.0)
+                        || from.map_or(false, |from| message.from() != from.0)
+                    {
+                        continue;
+                    }
+                    if *cids {
+                        println!("{}", message.cid()?);
+                    } else {
+                        println!(
+                            "{}",
+                            serde_json::to_string_pretty(&SignedMessageJson(message))?
+                        );
+                    }
+                }
+                Ok(())
+            }
+            Self::Stat { local } => {
+                let local_addrs = if *local {
+                    Some(wallet_addresses(token).await?)
+                } else {
+                    None
+                };
+                let stats = mpool_stat((), token).await.map_err(handle_rpc_err)?;
+                let (mut past, mut current, mut future, mut below_base_fee) = (0, 0, 0, 0);
+                for stat in stats {
+                    if local_addrs
+                        .as_ref()
+                        .map_or(false, |addrs| !addrs.contains(&stat.address))
+                    {
+                        continue;
+                    }
+                    println!(
+                        "{}: nonce {}, past: {}, cur: {}, future: {}; below base fee: {}",
+                        stat.address,
+                        stat.state_sequence,
+                        stat.past,
+                        stat.current,
+                        stat.future,
+                        stat.below_base_fee
+                    );
+                    past += stat.past;
+                    current += stat.current;
+                    future += stat.future;
+                    below_base_fee += stat.below_base_fee;
+                }
+                println!("-----");
+                println!(
+                    "total: past: {past}, cur: {current}, future: {future}; below base fee: {below_base_fee}"
+                );
+                Ok(())
+            }
+            Self::Replace {
+                from: StrictAddress(from),
+                nonce,
+                gas_premium,
+                gas_feecap,
+                gas_limit,
+                auto,
+            } => {
+                let head = chain_head(token).await.map_err(handle_rpc_err)?;
+                let pending = mpool_pending((CidJsonVec(head.0.cids().to_vec()),), token)
+                    .await
+                    .map_err(handle_rpc_err)?;
+                let mut message = pending
+                    .into_iter()
+                    .find(|m| m.from() == *from && m.sequence() == *nonce)
+                    .with_context(|| format!("No pending message from {from} with nonce {nonce}"))?
+                    .into_message();
+
+                let min_premium = min_rbf_premium(&message.gas_premium());
+                if let Some(gas_limit) = gas_limit {
+                    message.set_gas_limit(*gas_limit);
+                }
+                if *auto {
+                    message.set_gas_premium(TokenAmount::zero());
+                    message.set_gas_fee_cap(TokenAmount::zero());
+                    let MessageJson(estimated) = gas_estimate_message_gas(
+                        (
+                            MessageJson(message),
+                            None,
+                            TipsetKeysJson(head.0.key().clone()),
+                        ),
+                        token,
+                    )
+                    .await
+                    .map_err(handle_rpc_err)?;
+                    message = estimated;
+                    if message.gas_premium() < min_premium {
+                        message.set_gas_premium(min_premium);
+                    }
+                } else {
+                    let gas_premium = gas_premium.clone().unwrap_or(min_premium.clone());
+                    anyhow::ensure!(
+                        gas_premium >= min_premium,
+                        "The gas premium must be at least {} to replace the message",
+                        min_premium.pretty()
+                    );
+                    message.set_gas_premium(gas_premium);
+                    if let Some(gas_feecap) = gas_feecap {
+                        message.set_gas_fee_cap(gas_feecap.clone());
+                    }
+                }
+                if message.gas_fee_cap() < message.gas_premium() {
+                    message.set_gas_fee_cap(message.gas_premium());
+                }
+
+                let to_sign = BASE64_STANDARD.encode(message.cid()?.to_bytes());
+                let SignatureJson(signature) =
+                    wallet_sign((AddressJson(*from), to_sign.into_bytes()), token)
+                        .await
+                        .map_err(handle_rpc_err)?;
+                let signed = SignedMessage::new_from_parts(message, signature)?;
+                let CidJson(cid) = mpool_push((SignedMessageJson(signed),), token)
+                    .await
+                    .map_err(handle_rpc_err)?;
+                println!("new message cid: {cid}");
+                Ok(())
+            }
+            Self::FixNonce {
+                from: StrictAddress(from),
+                cancel,
+                dry_run,
+            } => {
+                let gaps = mpool_sequence_gaps((AddressJson(*from),), token)
+                    .await
+                    .map_err(handle_rpc_err)?;
+                println!(
+                    "{from}: nonce {}, gaps: {:?}, stranded: {:?}",
+                    gaps.state_sequence, gaps.gaps, gaps.stranded
+                );
+                if gaps.gaps.is_empty() {
+                    println!("No nonce gap to fill");
+                    return Ok(());
+                }
+                if *dry_run {
+                    return Ok(());
+                }
+
+                let mut sequences = gaps.gaps;
+                if *cancel {
+                    sequences.extend(gaps.stranded);
+                }
+                let head = chain_head(token).await.map_err(handle_rpc_err)?;
+                let pending = mpool_pending((CidJsonVec(head.0.cids().to_vec()),), token)
+                    .await
+                    .map_err(handle_rpc_err)?;
+                let mut messages = Vec::with_capacity(sequences.len());
+                for &sequence in &sequences {
+                    let message = Message_v3 {
+                        from: (*from).into(),
+                        to: (*from).into(),
+                        sequence,
+                        method_num: METHOD_SEND,
+                        ..Default::default()
+                    };
+                    let MessageJson(mut message) = gas_estimate_message_gas(
+                        (
+                            MessageJson(message.into()),
+                            None,
+                            TipsetKeysJson(head.0.key().clone()),
+                        ),
+                        token,
+                    )
+                    .await
+                    .map_err(handle_rpc_err)?;
+                    if let Some(replaced) = pending
+                        .iter()
+                        .find(|m| m.from() == *from && m.sequence() == sequence)
+                    {
+                        outbid(&mut message, &replaced.gas_premium());
+                    }
+                    messages.push(message);
+                }
+
+                let to_sign = messages
+                    .iter()
+                    .map(|message| {
+                        let to_sign = BASE64_STANDARD.encode(message.cid()?.to_bytes());
+                        Ok((AddressJson(*from), to_sign.into_bytes()))
+                    })
+                    .collect::<anyhow::Result<_>>()?;
+                let signatures = wallet_sign_batch((to_sign,), token)
+                    .await
+                    .map_err(handle_rpc_err)?;
+                let mut signed = Vec::with_capacity(messages.len());
+                for (message, signature) in messages.into_iter().zip(signatures) {
+                    let SignatureJson(signature) = signature.result.with_context(|| {
+                        format!(
+                            "Failed to sign the message with nonce {}: {}",
+                            message.sequence(),
+                            signature.error.unwrap_or_default()
+                        )
+                    })?;
+                    signed.push(SignedMessageJson(SignedMessage::new_from_parts(
+                        message, signature,
+                    )?));
+                }
+
+                let results = mpool_batch_push((signed,), token)
+                    .await
+                    .map_err(handle_rpc_err)?;
+                let mut pushed = true;
+                for (sequence, result) in sequences.iter().zip(results) {
+                    match (result.result, result.error) {
+                        (Some(CidJson(cid)), _) => println!("nonce {sequence}: {cid}"),
+                        (None, error) => {
+                            pushed = false;
+                            println!("nonce {sequence}: {}", error.unwrap_or_default());
+                        }
+                    }
+                }
+                // The batch is pushed as a whole, the pending messages are
+                // kept if it is rejected
+                anyhow::ensure!(
+                    pushed,
+                    "None of the messages were pushed, the pending ones are kept"
+                );
+                Ok(())
+            }
+            Self::Clear {
+                local,
+                really_do_it,
+            } => {
+                anyhow::ensure!(
+                    *really_do_it,
+                    "The pending messages would be lost, pass --really-do-it to clear them"
+                );
+                mpool_clear((*local,), token)
+                    .await
+                    .map_err(handle_rpc_err)?;
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Raises the gas premium of a message to the lowest one replacing a pending
+/// message paying `replaced_premium`, and its fee cap to cover the premium.
+fn outbid(message: &mut Message, replaced_premium: &TokenAmount) {
+    let min_premium = min_rbf_premium(replaced_premium);
+    if message.gas_premium() < min_premium {
+        message.set_gas_premium(min_premium);
+    }
+    if message.gas_fee_cap() < message.gas_premium() {
+        message.set_gas_fee_cap(message.gas_premium());
+    }
+}
+
+/// Returns the addresses of the wallet of the node
+async fn wallet_addresses(token: &Option<String>) -> anyhow::Result<HashSet<Address>> {
+    let addresses = wallet_list((), token).await.map_err(handle_rpc_err)?;
+    Ok(addresses.into_iter().map(|AddressJson(a)| a).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(gas_premium: u64, gas_fee_cap: u64) -> Message {
+        Message_v3 {
+            gas_premium: TokenAmount::from_atto(gas_premium).into(),
+            gas_fee_cap: TokenAmount::from_atto(gas_fee_cap).into(),
+            ..Default::default()
+        }
+        .into()
+    }
+
+    #[test]
+    fn outbid_replaced_messages() {
+        // 100 * 1.25 + 2
+        let mut replacement = message(100, 110);
+        outbid(&mut replacement, &TokenAmount::from_atto(100));
+        assert_eq!(replacement.gas_premium(), TokenAmount::from_atto(127));
+        assert_eq!(replacement.gas_fee_cap(), TokenAmount::from_atto(127));
+
+        // The estimated premium and fee cap are kept when they are enough
+        let mut replacement = message(200, 300);
+        outbid(&mut replacement, &TokenAmount::from_atto(100));
+        assert_eq!(replacement.gas_premium(), TokenAmount::from_atto(200));
+        assert_eq!(replacement.gas_fee_cap(), TokenAmount::from_atto(300));
     }
 }
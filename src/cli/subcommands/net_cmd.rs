@@ -6,6 +6,7 @@ use crate::rpc_api::data_types::AddrInfo;
 use crate::rpc_client::net_ops::*;
 use ahash::HashSet;
 use clap::Subcommand;
+use human_repr::{HumanCount, HumanThroughput};
 
 use super::{handle_rpc_err, print_stdout, Config};
 use crate::cli::subcommands::cli_error_and_die;
@@ -16,6 +17,19 @@ pub enum NetCommands {
     Listen,
     /// Lists `libp2p` swarm peers
     Peers,
+    /// Prints peer counts and the scores of the peers
+    Info,
+    /// Prints whether the node is reachable from the public internet
+    Reachability,
+    /// Prints the bandwidth used by the node
+    Bandwidth {
+        /// Break the bandwidth down by peer
+        #[arg(long, conflicts_with = "by_protocol")]
+        by_peer: bool,
+        /// Break the bandwidth down by protocol
+        #[arg(long)]
+        by_protocol: bool,
+    },
     /// Connects to a peer by its peer ID and multi-addresses
     Connect {
         /// Multi-address (with `/p2p/` protocol)
@@ -26,6 +40,17 @@ pub enum NetCommands {
         /// Peer ID to disconnect from
         id: String,
     },
+    /// Adds a bootstrap peer that is persisted across restarts and connects to
+    /// it
+    AddPeer {
+        /// Multi-address (with `/p2p/` protocol)
+        address: String,
+    },
+    /// Removes a bootstrap peer that was added with `add-peer`
+    RemovePeer {
+        /// Peer ID to remove
+        id: String,
+    },
 }
 
 impl NetCommands {
@@ -65,12 +90,88 @@ impl NetCommands {
                         if addresses.is_empty() {
                             return None;
                         }
-                        Some(format!("{}, [{}]", info.id, addresses.join(", ")))
+                        let score = info
+                            .score
+                            .map(|score| format!(", score: {score:.3}"))
+                            .unwrap_or_default();
+                        Some(format!("{}, [{}]{score}", info.id, addresses.join(", ")))
                     })
                     .collect();
                 print_stdout(output.join("\n"));
                 Ok(())
             }
+            Self::Info => {
+                let info = net_info((), &config.client.rpc_token)
+                    .await
+                    .map_err(handle_rpc_err)?;
+                println!("connected peers: {}", info.num_connected);
+                println!("full peers: {}", info.num_full_peers);
+                println!("bad peers: {}", info.num_bad_peers);
+                println!("protected peers: {}", info.num_protected);
+                for peer in info.peers {
+                    println!(
+                        "{}, score: {:.3}, successes: {}, failures: {}, latency: {}ms{}",
+                        peer.id,
+                        peer.score,
+                        peer.successes,
+                        peer.failures,
+                        peer.average_time_ms,
+                        if peer.protected { ", protected" } else { "" }
+                    );
+                }
+                Ok(())
+            }
+            Self::Reachability => {
+                let status = net_auto_nat_status((), &config.client.rpc_token)
+                    .await
+                    .map_err(handle_rpc_err)?;
+                let reachability = match status.reachability {
+                    1 => "public",
+                    2 => "private",
+                    _ => "unknown",
+                };
+                println!("reachability: {reachability}");
+                if !status.public_addr.is_empty() {
+                    println!("public address: {}", status.public_addr);
+                }
+                Ok(())
+            }
+            Self::Bandwidth {
+                by_peer,
+                by_protocol,
+            } => {
+                let mut stats: Vec<_> = if *by_peer {
+                    net_bandwidth_stats_by_peer((), &config.client.rpc_token)
+                        .await
+                        .map_err(handle_rpc_err)?
+                        .into_iter()
+                        .collect()
+                } else if *by_protocol {
+                    net_bandwidth_stats_by_protocol((), &config.client.rpc_token)
+                        .await
+                        .map_err(handle_rpc_err)?
+                        .into_iter()
+                        .collect()
+                } else {
+                    let total = net_bandwidth_stats((), &config.client.rpc_token)
+                        .await
+                        .map_err(handle_rpc_err)?;
+                    vec![("total".to_owned(), total)]
+                };
+                stats.sort_by(|(_, a), (_, b)| {
+                    (b.total_in + b.total_out).cmp(&(a.total_in + a.total_out))
+                });
+                for (segment, stats) in stats {
+                    println!(
+                        "{segment}, in: {} ({}), out: {} ({})",
+                        stats.total_in.human_count_bytes(),
+                        stats.rate_in.human_throughput_bytes(),
+                        stats.total_out.human_count_bytes(),
+                        stats.rate_out.human_throughput_bytes(),
+                    );
+                }
+                Ok(())
+            }
             Self::Connect { address } => {
                 let addr: Multiaddr = address
                     .parse()
@@ -96,6 +197,7 @@ impl NetCommands {
                 let addr_info = AddrInfo {
                     id: id.clone(),
                     addrs,
+                    score: None,
                 };
 
                 net_connect((addr_info,), &config.client.rpc_token)
@@ -111,6 +213,24 @@ impl NetCommands {
                 println!("disconnect {id}: success");
                 Ok(())
             }
+            Self::AddPeer { address } => {
+                net_add_peer((address.to_owned(),), &config.client.rpc_token)
+                    .await
+                    .map_err(handle_rpc_err)?;
+                println!("add peer {address}: success");
+                Ok(())
+            }
+            Self::RemovePeer { id } => {
+                let removed = net_remove_peer((id.to_owned(),), &config.client.rpc_token)
+                    .await
+                    .map_err(handle_rpc_err)?;
+                if removed {
+                    println!("remove peer {id}: success");
+                } else {
+                    println!("{id} is not a bootstrap peer");
+                }
+                Ok(())
+            }
         }
     }
 }
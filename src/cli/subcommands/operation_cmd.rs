@@ -0,0 +1,97 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use crate::rpc_api::progress_api::OperationStatus;
+use crate::rpc_client::progress_ops::{operation_cancel, operation_status};
+use chrono::Utc;
+use clap::Subcommand;
+
+use super::{handle_rpc_err, Config};
+
+#[derive(Debug, Subcommand)]
+pub enum OperationCommands {
+    /// List the long-running operations of the node, such as snapshot exports
+    /// and garbage collections, in progress or recently ended
+    List,
+    /// Cancel an operation in progress
+    Cancel {
+        /// ID of the operation, as listed
+        id: u64,
+    },
+}
+
+impl OperationCommands {
+    pub async fn run(&self, config: Config) -> anyhow::Result<()> {
+        match self {
+            Self::List => {
+                let statuses = operation_status((), &config.client.rpc_token)
+                    .await
+                    .map_err(handle_rpc_err)?;
+                if statuses.is_empty() {
+                    println!("No operations");
+                }
+                for status in &statuses {
+                    println!("{}", format_status(status, Utc::now().timestamp()));
+                }
+                Ok(())
+            }
+            Self::Cancel { id } => {
+                operation_cancel((*id,), &config.client.rpc_token)
+                    .await
+                    .map_err(handle_rpc_err)?;
+                println!("Cancelling operation {id}");
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Formats an operation as its ID, state, progress and duration, then its
+/// description and error if any
+fn format_status(status: &OperationStatus, now: i64) -> String {
+    let progress = match status.percent() {
+        Some(percent) => format!("{percent:.1}%"),
+        None if status.current > 0 => status.current.to_string(),
+        None => "-".into(),
+    };
+    let duration = status.ended.unwrap_or(now) - status.started;
+    let mut line = format!(
+        "{:>4}  {:<26} {:<10} {:>8} {:>7}s  {}",
+        status.id,
+        status.kind.to_string(),
+        status.state.to_string(),
+        progress,
+        duration,
+        status.description,
+    );
+    if let Some(error) = &status.error {
+        line.push_str(&format!(": {error}"));
+    }
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rpc_api::progress_api::{OperationKind, OperationState};
+
+    #[test]
+    fn format_running_operation() {
+        let status = OperationStatus {
+            id: 3,
+            kind: OperationKind::SnapshotExport,
+            description: "snapshot export".into(),
+            state: OperationState::Running,
+            started: 100,
+            ended: None,
+            current: 1,
+            total: 8,
+            cancellable: true,
+            error: None,
+        };
+        assert_eq!(
+            format_status(&status, 160),
+            "   3  SnapshotExport             Running       12.5%      60s  snapshot export"
+        );
+    }
+}
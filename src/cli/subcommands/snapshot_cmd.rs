@@ -37,6 +37,15 @@ pub enum SnapshotCommands {
         /// Don't write the archive.
         #[arg(long)]
         dry_run: bool,
+        /// Write an uncompressed CARv2 archive, with an index of its blocks
+        /// for random access. Defaults to a `.car` file name.
+        #[arg(long)]
+        car_v2: bool,
+        /// Write a `.forest.car.zst` archive, compressed in independent zstd
+        /// frames and ending with an index of its blocks for random access.
+        /// It remains readable as a plain `.car.zst` archive.
+        #[arg(long, conflicts_with = "car_v2")]
+        forest_car: bool,
     },
 
     /// Fetches the most recent snapshot from a trusted, pre-defined location.
@@ -68,6 +77,8 @@ impl SnapshotCommands {
                 output_path,
                 skip_checksum,
                 dry_run,
+                car_v2,
+                forest_car,
             } => {
                 let chain_head = match chain_head(&config.client.rpc_token).await {
                     Ok(head) => head.0,
@@ -81,12 +92,21 @@ impl SnapshotCommands {
                     .map_err(handle_rpc_err)?;
 
                 let output_path = match output_path.is_dir() {
-                    true => output_path.join(snapshot::filename(
-                        TrustedVendor::Forest,
-                        chain_name,
-                        Utc::now().date_naive(),
-                        chain_head.epoch(),
-                    )),
+                    true => {
+                        let filename = snapshot::filename(
+                            TrustedVendor::Forest,
+                            chain_name,
+                            Utc::now().date_naive(),
+                            chain_head.epoch(),
+                        );
+                        if *car_v2 {
+                            output_path.join(filename.trim_end_matches(".zst"))
+                        } else if *forest_car {
+                            output_path.join(filename.replace(".car.zst", ".forest.car.zst"))
+                        } else {
+                            output_path.join(filename)
+                        }
+                    }
                     false => output_path.clone(),
                 };
 
@@ -97,6 +117,8 @@ impl SnapshotCommands {
                     tipset_keys: TipsetKeysJson(chain_head.key().clone()),
                     skip_checksum: *skip_checksum,
                     dry_run: *dry_run,
+                    car_v2: *car_v2,
+                    forest_car: *forest_car,
                 };
 
                 let bar = Arc::new(tokio::sync::Mutex::new({
@@ -196,7 +218,12 @@ async fn validate(
 
         let (cids, _n_records) = {
             let reader = get_fetch_progress_from_file(&snapshot).await?;
-            forest_load_car(chain_store.blockstore().clone(), reader).await?
+            forest_load_car(
+                chain_store.blockstore().clone(),
+                reader,
+                &Default::default(),
+            )
+            .await?
         };
 
         let ts = chain_store.tipset_from_keys(&TipsetKeys::new(cids))?;
@@ -286,3 +313,26 @@ where
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use crate::cli::subcommands::Cli;
+
+    #[test]
+    fn export_formats_conflict() {
+        assert!(Cli::try_parse_from(["forest-cli", "snapshot", "export", "--car-v2"]).is_ok());
+        assert!(Cli::try_parse_from(["forest-cli", "snapshot", "export", "--forest-car"]).is_ok());
+        let err = Cli::try_parse_from([
+            "forest-cli",
+            "snapshot",
+            "export",
+            "--car-v2",
+            "--forest-car",
+        ])
+        .err()
+        .unwrap();
+        assert_eq!(err.kind(), clap::error::ErrorKind::ArgumentConflict);
+    }
+}
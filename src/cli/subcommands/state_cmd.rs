@@ -3,10 +3,25 @@
 
 use crate::db::db_engine::db_root;
 use crate::db::db_engine::open_proxy_db;
-use crate::json::cid::CidJson;
-use crate::rpc_client::state_ops::state_fetch_root;
-use crate::shim::clock::ChainEpoch;
+use std::{
+    io::{BufWriter, Write},
+    path::PathBuf,
+    str::FromStr,
+};
+
+use crate::json::{address::json::AddressJson, cid::CidJson};
+use crate::rpc_api::data_types::ComputedState;
+use crate::rpc_client::{
+    chain_ops::chain_head,
+    state_ops::{
+        state_balance_history, state_balance_history_recompute, state_compute,
+        state_execution_trace, state_fetch_root, state_list_execution_traces,
+    },
+};
+use crate::shim::{address::Address, clock::ChainEpoch};
+use crate::state_manager::MAX_BALANCE_HISTORY_EPOCHS;
 use crate::statediff::print_state_diff;
+use crate::utils::io::ProgressBar;
 use cid::Cid;
 use clap::Subcommand;
 use fvm_shared::econ::TokenAmount;
@@ -40,6 +55,59 @@ pub enum StateCommands {
         #[arg(short, long)]
         depth: Option<u64>,
     },
+    /// Print the execution trace of a message recently applied by the node
+    Trace {
+        /// CID of the message
+        message: Cid,
+    },
+    /// Print the recent execution traces involving an actor
+    Traces {
+        /// Address of the actor
+        actor: String,
+        /// Maximum number of traces
+        #[arg(short, long, default_value_t = 10)]
+        limit: usize,
+    },
+    /// Execute the tipset at an epoch again and check the resulting state
+    /// against the one of the chain
+    Compute {
+        epoch: ChainEpoch,
+    },
+    /// Compute the states of a range of epochs, in order, from the earliest
+    /// state available. Meant for archival nodes, started with `--archival`
+    /// so that the states computed are kept.
+    Backfill {
+        /// First epoch to compute, whose parent state must be available
+        #[arg(long)]
+        from: ChainEpoch,
+        /// Last epoch to compute, the head of the chain by default
+        #[arg(long)]
+        to: Option<ChainEpoch>,
+    },
+    /// Export the balance of an actor over a range of epochs, as CSV with the
+    /// epoch, the state root and the balance in attoFIL. The balance at an
+    /// epoch is the one at the start of the epoch.
+    BalanceHistory {
+        /// Address of the actor
+        address: String,
+        /// First epoch
+        #[arg(long)]
+        from: ChainEpoch,
+        /// Last epoch, the head of the chain by default
+        #[arg(long)]
+        to: Option<ChainEpoch>,
+        /// Number of epochs between two balances
+        #[arg(long, default_value_t = 1)]
+        step: u64,
+        /// Compute again the states pruned from the store, from the nearest
+        /// earlier state available, at most 120 epochs before. Not needed on
+        /// archival nodes, needs a token with the write permission.
+        #[arg(long)]
+        recompute: bool,
+        /// File to write the CSV to, the standard output by default
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
 }
 
 impl StateCommands {
@@ -64,7 +132,124 @@ impl StateCommands {
                     eprintln!("Failed to print state diff: {err}");
                 }
             }
+            Self::Trace { message } => {
+                let trace = state_execution_trace((CidJson(message),), &config.client.rpc_token)
+                    .await
+                    .map_err(handle_rpc_err)?;
+                println!("{}", serde_json::to_string_pretty(&trace)?);
+            }
+            Self::Traces { actor, limit } => {
+                let actor = Address::from_str(&actor)?;
+                let traces = state_list_execution_traces(
+                    (AddressJson(actor), limit),
+                    &config.client.rpc_token,
+                )
+                .await
+                .map_err(handle_rpc_err)?;
+                println!("{}", serde_json::to_string_pretty(&traces)?);
+            }
+            Self::Compute { epoch } => {
+                let computed = state_compute((epoch,), &config.client.rpc_token)
+                    .await
+                    .map_err(handle_rpc_err)?;
+                print_computed_state(&computed);
+                if !computed_state_matches(&computed) {
+                    anyhow::bail!("State mismatch at epoch {}", computed.epoch);
+                }
+            }
+            Self::Backfill { from, to } => {
+                let to = match to {
+                    Some(to) => to,
+                    None => chain_head(&config.client.rpc_token)
+                        .await
+                        .map_err(handle_rpc_err)?
+                        .0
+                        .epoch(),
+                };
+                let bar = ProgressBar::new((to - from + 1).max(0) as u64);
+                bar.message("Computing states | epochs ");
+                let mut epoch = from;
+                while epoch <= to {
+                    let computed = state_compute((epoch,), &config.client.rpc_token)
+                        .await
+                        .map_err(handle_rpc_err)?;
+                    if !computed_state_matches(&computed) {
+                        bar.finish();
+                        print_computed_state(&computed);
+                        anyhow::bail!("State mismatch at epoch {}", computed.epoch);
+                    }
+                    // Null rounds are computed with the tipset before them
+                    bar.set((epoch - from + 1) as u64);
+                    epoch += 1;
+                }
+                bar.finish_println(&format!("Computed the states of epochs {from} to {to}"));
+            }
+            Self::BalanceHistory {
+                address,
+                from,
+                to,
+                step,
+                recompute,
+                output,
+            } => {
+                let address = Address::from_str(&address)?;
+                let to = match to {
+                    Some(to) => to,
+                    None => chain_head(&config.client.rpc_token)
+                        .await
+                        .map_err(handle_rpc_err)?
+                        .0
+                        .epoch(),
+                };
+                anyhow::ensure!(step > 0, "the step must be positive");
+                let mut writer: Box<dyn Write> = match output {
+                    Some(path) => Box::new(BufWriter::new(std::fs::File::create(path)?)),
+                    None => Box::new(std::io::stdout().lock()),
+                };
+                writeln!(writer, "epoch,state_root,balance")?;
+                // Requested in pages of the maximum number of epochs per call
+                let page = (MAX_BALANCE_HISTORY_EPOCHS * step) as ChainEpoch;
+                let mut first = from;
+                while first <= to {
+                    let last = to.min(first + page - 1);
+                    let params = (AddressJson(address), first, last, step);
+                    let history = if recompute {
+                        state_balance_history_recompute(params, &config.client.rpc_token).await
+                    } else {
+                        state_balance_history(params, &config.client.rpc_token).await
+                    }
+                    .map_err(handle_rpc_err)?;
+                    for entry in history {
+                        writeln!(
+                            writer,
+                            "{},{},{}",
+                            entry.epoch,
+                            entry.state_root,
+                            entry.balance.atto()
+                        )?;
+                    }
+                    first += page;
+                }
+                writer.flush()?;
+            }
         }
         Ok(())
     }
 }
+
+fn computed_state_matches(computed: &ComputedState) -> bool {
+    match &computed.expected_state_root {
+        Some(expected) => expected.0 == computed.state_root.0,
+        None => true,
+    }
+}
+
+fn print_computed_state(computed: &ComputedState) {
+    println!("Epoch:               {}", computed.epoch);
+    println!("State root:          {}", computed.state_root.0);
+    println!("Receipt root:        {}", computed.receipt_root.0);
+    match &computed.expected_state_root {
+        Some(expected) => println!("Expected state root: {}", expected.0),
+        None => println!("Expected state root: unknown, the epoch is the head of the chain"),
+    }
+}
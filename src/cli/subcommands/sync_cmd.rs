@@ -6,6 +6,7 @@ use std::{
     time::Duration,
 };
 
+use crate::blocks::{tipset_keys_json::TipsetKeysJson, TipsetKeys};
 use crate::chain_sync::SyncStage;
 use crate::json::cid::CidJson;
 use crate::rpc_client::*;
@@ -14,7 +15,9 @@ use clap::Subcommand;
 use ticker::Ticker;
 
 use super::Config;
-use crate::cli::subcommands::{format_vec_pretty, handle_rpc_err};
+use crate::cli::subcommands::{
+    chain_cmd::tipset_by_epoch_or_offset, format_vec_pretty, handle_rpc_err,
+};
 
 #[derive(Debug, Subcommand)]
 pub enum SyncCommands {
@@ -38,6 +41,26 @@ pub enum SyncCommands {
         #[arg(short)]
         cid: String,
     },
+    /// Unmark a given block as bad, so that it is validated again
+    UnmarkBad {
+        /// The block CID to unmark
+        #[arg(short, required_unless_present = "all")]
+        cid: Option<String>,
+        /// Unmark all the bad blocks
+        #[arg(long, conflicts_with = "cid")]
+        all: bool,
+    },
+    /// Mark a given tipset as checkpointed, the chain will never be
+    /// reorganized below it
+    Checkpoint {
+        /// The block CIDs of the tipset
+        #[arg(num_args = 1.., required = true)]
+        cids: Vec<Cid>,
+        /// Use the tipset from this height of the current chain instead.
+        /// Negative numbers specify decrements from the current head.
+        #[arg(long, conflicts_with = "cids", allow_hyphen_values = true)]
+        height: Option<i64>,
+    },
 }
 
 impl SyncCommands {
@@ -79,8 +102,14 @@ impl SyncCommands {
                         state.epoch(),
                         target_height - state.epoch()
                     );
+                    println!(
+                        "Fetched Epoch: {}; Active Workers: {}; Rate: {:.2} tipsets/s",
+                        state.fetched_epoch(),
+                        state.active_workers(),
+                        state.validation_rate()
+                    );
 
-                    for _ in 0..2 {
+                    for _ in 0..3 {
                         write!(
                             stdout,
                             "\r{}{}",
@@ -128,6 +157,12 @@ impl SyncCommands {
                 println!("Height diff:\t{}", height_diff.abs());
                 println!("Stage:\t{}", state.stage());
                 println!("Height:\t{}", state.epoch());
+                if let Some(epochs_behind) = state.epochs_behind() {
+                    println!("Behind:\t{epochs_behind} epochs");
+                }
+                println!("Fetched:\t{}", state.fetched_epoch());
+                println!("Workers:\t{}", state.active_workers());
+                println!("Rate:\t{:.2} tipsets/s", state.validation_rate());
 
                 if let Some(duration) = elapsed_time {
                     println!("Elapsed time:\t{}s", duration.num_seconds());
@@ -143,7 +178,7 @@ impl SyncCommands {
                 if response.is_empty() {
                     println!("Block \"{cid}\" is not marked as a bad block");
                 } else {
-                    println!("response");
+                    println!("Block \"{cid}\" is marked as a bad block: {response}");
                 }
                 Ok(())
             }
@@ -155,6 +190,37 @@ impl SyncCommands {
                 println!("OK");
                 Ok(())
             }
+            Self::UnmarkBad { cid, all } => {
+                match cid {
+                    Some(cid) if !all => {
+                        let cid: Cid = cid.parse()?;
+                        sync_unmark_bad((CidJson(cid),), &config.client.rpc_token)
+                            .await
+                            .map_err(handle_rpc_err)?;
+                    }
+                    _ => sync_unmark_all_bad((), &config.client.rpc_token)
+                        .await
+                        .map_err(handle_rpc_err)?,
+                }
+                println!("OK");
+                Ok(())
+            }
+            Self::Checkpoint { cids, height } => {
+                let tsk = match height {
+                    Some(height) => tipset_by_epoch_or_offset(*height, &config.client.rpc_token)
+                        .await
+                        .map_err(handle_rpc_err)?
+                        .0
+                        .key()
+                        .clone(),
+                    None => TipsetKeys::new(cids.clone()),
+                };
+                sync_checkpoint((TipsetKeysJson(tsk.clone()),), &config.client.rpc_token)
+                    .await
+                    .map_err(handle_rpc_err)?;
+                println!("Checkpointed tipset {tsk}");
+                Ok(())
+            }
         }
     }
 }
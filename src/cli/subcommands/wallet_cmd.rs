@@ -10,7 +10,7 @@ use crate::json::{
     address::json::AddressJson,
     signature::json::{signature_type::SignatureTypeJson, SignatureJson},
 };
-use crate::key_management::json::KeyInfoJson;
+use crate::key_management::{json::KeyInfoJson, FOREST_KEYSTORE_PHRASE_ENV};
 use crate::rpc_client::wallet_ops::*;
 use crate::shim::{
     address::{Address, Protocol, StrictAddress},
@@ -96,6 +96,10 @@ pub enum WalletCommands {
         #[arg(short)]
         signature: String,
     },
+    /// Unlock the encrypted keystore of a node started without its
+    /// passphrase. The passphrase is read from `FOREST_KEYSTORE_PHRASE` if
+    /// set, otherwise prompted for
+    Unlock,
 }
 
 impl WalletCommands {
@@ -274,6 +278,24 @@ impl WalletCommands {
                 println!("{response}");
                 Ok(())
             }
+            Self::Unlock => {
+                let passphrase = match std::env::var(FOREST_KEYSTORE_PHRASE_ENV) {
+                    Ok(passphrase) => passphrase,
+                    Err(_) => {
+                        tokio::task::spawn_blocking(|| {
+                            Password::with_theme(&ColorfulTheme::default())
+                                .with_prompt("Enter the password for Forest's keystore")
+                                .interact()
+                        })
+                        .await??
+                    }
+                };
+                wallet_unlock((passphrase,), &config.client.rpc_token)
+                    .await
+                    .map_err(handle_rpc_err)?;
+                println!("Keystore unlocked");
+                Ok(())
+            }
         }
     }
 }
@@ -0,0 +1,79 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use std::str::FromStr;
+
+use crate::json::address::json::AddressJson;
+use crate::rpc_client::watch_ops::{unwatch_address, watch_address, watch_events, watch_list};
+use crate::shim::address::Address;
+use clap::Subcommand;
+
+use super::{handle_rpc_err, Config};
+
+#[derive(Debug, Subcommand)]
+pub enum WatchCommands {
+    /// Watch the messages sent from or to an address, as they enter the
+    /// message pool and land on chain
+    Add {
+        address: String,
+        /// URL the events are posted to, as JSON
+        #[arg(long)]
+        webhook: Option<String>,
+    },
+    /// Stop watching an address
+    Remove { address: String },
+    /// List the watched addresses
+    List,
+    /// Print the recent events of the watched addresses
+    Events {
+        /// Print the events following the one of this sequence number only
+        #[arg(long, default_value_t = 0)]
+        after: u64,
+        /// Maximum number of events
+        #[arg(short, long, default_value_t = 100)]
+        limit: usize,
+    },
+}
+
+impl WatchCommands {
+    pub async fn run(self, config: Config) -> anyhow::Result<()> {
+        match self {
+            Self::Add { address, webhook } => {
+                let address = Address::from_str(&address)?;
+                watch_address((AddressJson(address), webhook), &config.client.rpc_token)
+                    .await
+                    .map_err(handle_rpc_err)?;
+                println!("Watching {address}");
+            }
+            Self::Remove { address } => {
+                let address = Address::from_str(&address)?;
+                let watched = unwatch_address((AddressJson(address),), &config.client.rpc_token)
+                    .await
+                    .map_err(handle_rpc_err)?;
+                if !watched {
+                    anyhow::bail!("{address} is not watched");
+                }
+                println!("Stopped watching {address}");
+            }
+            Self::List => {
+                let watches = watch_list((), &config.client.rpc_token)
+                    .await
+                    .map_err(handle_rpc_err)?;
+                for watch in watches {
+                    println!(
+                        "{}  {}",
+                        watch.address,
+                        watch.webhook.as_deref().unwrap_or("-")
+                    );
+                }
+            }
+            Self::Events { after, limit } => {
+                let events = watch_events((after, limit), &config.client.rpc_token)
+                    .await
+                    .map_err(handle_rpc_err)?;
+                println!("{}", serde_json::to_string_pretty(&events)?);
+            }
+        }
+        Ok(())
+    }
+}
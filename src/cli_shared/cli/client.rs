@@ -9,6 +9,7 @@ use std::{
 
 use crate::rpc_client::DEFAULT_PORT;
 use crate::utils::io::ProgressBarVisibility;
+use anyhow::Context as _;
 use chrono::Duration;
 use directories::ProjectDirs;
 use serde::{Deserialize, Serialize};
@@ -30,16 +31,55 @@ pub struct Client {
     /// Skips loading import CAR file and assumes it's already been loaded.
     /// Will use the CIDs in the header of the file to index the chain.
     pub skip_load: bool,
+    /// Memory in bytes the records of an imported snapshot take at most
+    /// before they are written to the database. Lower it on machines with
+    /// little RAM.
+    pub import_buffer_size: usize,
+    /// Interval at which the buffered records of an import are written to
+    /// the database regardless of their size, 0 to only write them when the
+    /// buffer is full
+    #[serde_as(as = "DurationSeconds<i64>")]
+    pub import_flush_interval: Duration,
+    /// Journals the buffered records of an import to disk, so that the ones
+    /// of an import that crashes are written on the next start
+    pub import_journal: bool,
     pub encrypt_keystore: bool,
     /// Metrics bind, e.g. 127.0.0.1:6116
     pub metrics_address: SocketAddr,
     /// RPC bind, e.g. 127.0.0.1:1234
     pub rpc_address: SocketAddr,
+    /// Health check endpoints bind, e.g. 0.0.0.0:2346
+    pub healthcheck_address: SocketAddr,
     // Period of validity for JWT in seconds. Defaults to 60 days.
     #[serde_as(as = "DurationSeconds<i64>")]
     pub token_exp: Duration,
     /// Display progress bars mode. Auto will display if TTY.
     pub show_progress_bars: ProgressBarVisibility,
+    /// Storage miner actor to produce blocks for, on devnets only
+    pub miner_address: Option<String>,
+    /// Wallet address reporting the consensus faults detected in the
+    /// validated blocks received by the node. Faults are neither detected nor
+    /// reported if unset, the default
+    pub consensus_fault_reporter: Option<String>,
+    /// Number of message execution traces kept in memory for the trace API,
+    /// 0 disables tracing
+    pub execution_traces: usize,
+    /// Traces the tipsets at the epochs multiple of it only, 1 tracing all of
+    /// them and 0 none
+    pub execution_trace_interval: u64,
+    /// Addresses whose messages are traced in every tipset, whether they are
+    /// sent to their key or their ID address
+    pub execution_trace_addresses: Vec<String>,
+    /// Memory in bytes used at most by the cache of the states computed per
+    /// tipset, 0 disables it
+    pub tipset_state_cache_size: usize,
+    /// Interval between the checks of the database size that trigger the
+    /// garbage collection
+    #[serde_as(as = "DurationSeconds<i64>")]
+    pub gc_interval: Duration,
+    /// Keeps every state computed by the node, disabling the garbage
+    /// collection, for archival and explorer nodes
+    pub archival: bool,
 }
 
 impl Default for Client {
@@ -54,11 +94,39 @@ impl Default for Client {
             snapshot: false,
             snapshot_height: None,
             skip_load: false,
+            import_buffer_size: 1024 * 1024 * 1024,
+            import_flush_interval: Duration::seconds(30),
+            import_journal: false,
             encrypt_keystore: true,
             metrics_address: FromStr::from_str("0.0.0.0:6116").unwrap(),
             rpc_address: SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), DEFAULT_PORT),
+            healthcheck_address: FromStr::from_str("0.0.0.0:2346").unwrap(),
             token_exp: Duration::seconds(5184000), // 60 Days = 5184000 Seconds
             show_progress_bars: Default::default(),
+            miner_address: None,
+            consensus_fault_reporter: None,
+            execution_traces: 0,
+            execution_trace_interval: 1,
+            execution_trace_addresses: vec![],
+            tipset_state_cache_size: crate::state_manager::DEFAULT_TIPSET_STATE_CACHE_SIZE,
+            gc_interval: Duration::minutes(10),
+            archival: false,
         }
     }
 }
+
+impl Client {
+    /// Returns the interval of the garbage collection checks, rejecting the
+    /// intervals under a second, which would make the checks busy-loop.
+    pub fn gc_interval(&self) -> anyhow::Result<std::time::Duration> {
+        let interval = self
+            .gc_interval
+            .to_std()
+            .context("client.gc_interval must be positive")?;
+        anyhow::ensure!(
+            interval.as_secs() > 0,
+            "client.gc_interval must be at least one second"
+        );
+        Ok(interval)
+    }
+}
@@ -70,6 +70,15 @@ pub struct DaemonConfig {
     pub stderr: PathBuf,
     pub work_dir: PathBuf,
     pub pid_file: Option<PathBuf>,
+    /// File the logs are written to instead of `stdout` when detached
+    #[serde(default = "default_daemon_log_file")]
+    pub log_file: PathBuf,
+    #[serde(default)]
+    pub log_rotation: LogRotation,
+}
+
+fn default_daemon_log_file() -> PathBuf {
+    "forest.log".into()
 }
 
 impl Default for DaemonConfig {
@@ -82,6 +91,30 @@ impl Default for DaemonConfig {
             stderr: "forest.err".into(),
             work_dir: ".".into(),
             pid_file: None,
+            log_file: default_daemon_log_file(),
+            log_rotation: Default::default(),
+        }
+    }
+}
+
+/// Rotation of the log file of the detached daemon
+#[derive(Deserialize, Serialize, PartialEq, Eq, Debug, Clone)]
+#[serde(default)]
+pub struct LogRotation {
+    /// Size in bytes above which the file is rotated, 0 disables it
+    pub max_size: u64,
+    /// Age in seconds above which the file is rotated, 0 disables it
+    pub max_age: u64,
+    /// Number of rotated files kept
+    pub max_files: usize,
+}
+
+impl Default for LogRotation {
+    fn default() -> Self {
+        Self {
+            max_size: 100 * 1024 * 1024,
+            max_age: 24 * 60 * 60,
+            max_files: 7,
         }
     }
 }
@@ -106,12 +139,98 @@ pub struct Config {
     pub daemon: DaemonConfig,
     pub log: LogConfig,
     pub tokio: TokioConfig,
+    pub f3: crate::f3::F3Config,
+    pub snapshot_server: crate::rpc::SnapshotServerConfig,
+    pub rpc_limits: crate::rpc::RpcLimitsConfig,
+    pub backfill: crate::chain_sync::BackfillConfig,
+    pub execution: crate::state_manager::ExecutionConfig,
+    pub events: crate::events::EventsConfig,
+    pub miner_monitor: crate::miner_monitor::MinerMonitorConfig,
 }
 
+/// Configuration keys that can be changed while the node is running, with
+/// `forest-cli config set` or by reloading the configuration file.
+pub const RELOADABLE_KEYS: &[&str] = &[
+    "log.filters",
+    "network.target_peer_count",
+    "network.max_peer_count",
+    "client.gc_interval",
+];
+
 impl Config {
     pub fn db_config(&self) -> &DbConfig {
         &self.parity_db
     }
+
+    /// Sets one of the [`RELOADABLE_KEYS`] to a value in TOML syntax, e.g.
+    /// `50` or `[{ module = "forest", level = "debug" }]`.
+    pub fn set(&mut self, key: &str, value: &str) -> anyhow::Result<()> {
+        anyhow::ensure!(
+            RELOADABLE_KEYS.contains(&key),
+            "{key} cannot be changed at runtime, supported keys: {}",
+            RELOADABLE_KEYS.join(", ")
+        );
+        let mut document = toml::Value::try_from(&*self)?;
+        set_toml_value(&mut document, key, parse_toml_value(value))?;
+        *self = document
+            .try_into()
+            .map_err(|e| anyhow::anyhow!("invalid value for {key}: {e}"))?;
+        Ok(())
+    }
+
+    /// Returns `true` if the configurations only differ by their
+    /// [`RELOADABLE_KEYS`].
+    pub fn reloadable_from(&self, other: &Config) -> bool {
+        let mut other = other.clone();
+        other.log = self.log.clone();
+        other.network.target_peer_count = self.network.target_peer_count;
+        other.network.max_peer_count = self.network.max_peer_count;
+        other.client.gc_interval = self.client.gc_interval;
+        *self == other
+    }
+}
+
+/// Events handled by the daemon to change its configuration at runtime
+#[derive(Debug)]
+pub enum ConfigEvent {
+    /// Reloads the configuration file
+    Reload,
+    /// Sets a key, see [`Config::set`], and persists it to the configuration
+    /// file
+    Set { key: String, value: String },
+}
+
+/// Parses a value in TOML syntax, falling back to a bare string.
+pub fn parse_toml_value(value: &str) -> toml::Value {
+    toml::from_str::<toml::Table>(&format!("value = {value}"))
+        .ok()
+        .and_then(|mut table| table.remove("value"))
+        .unwrap_or_else(|| toml::Value::String(value.into()))
+}
+
+/// Sets a dotted key, e.g. `network.target_peer_count`, in a TOML document,
+/// creating the missing tables.
+pub fn set_toml_value(
+    document: &mut toml::Value,
+    key: &str,
+    value: toml::Value,
+) -> anyhow::Result<()> {
+    let mut path = key.split('.').peekable();
+    let mut table = document
+        .as_table_mut()
+        .ok_or_else(|| anyhow::anyhow!("the configuration is not a table"))?;
+    while let Some(name) = path.next() {
+        if path.peek().is_none() {
+            table.insert(name.into(), value);
+            return Ok(());
+        }
+        table = table
+            .entry(name)
+            .or_insert_with(|| toml::Value::Table(Default::default()))
+            .as_table_mut()
+            .ok_or_else(|| anyhow::anyhow!("{name} is not a table in {key}"))?;
+    }
+    anyhow::bail!("empty configuration key")
 }
 
 #[cfg(test)]
@@ -151,6 +270,13 @@ mod test {
                 daemon: DaemonConfig::default(),
                 log: Default::default(),
                 tokio: Default::default(),
+                f3: Default::default(),
+                snapshot_server: Default::default(),
+                rpc_limits: Default::default(),
+                backfill: Default::default(),
+                execution: Default::default(),
+                events: Default::default(),
+                miner_monitor: Default::default(),
             }
         }
     }
@@ -167,26 +293,56 @@ mod test {
                     snapshot_height: Option::arbitrary(g),
                     snapshot_path: Option::arbitrary(g),
                     skip_load: bool::arbitrary(g),
+                    import_buffer_size: usize::arbitrary(g),
+                    import_flush_interval: Duration::milliseconds(i64::arbitrary(g)),
+                    import_journal: bool::arbitrary(g),
                     encrypt_keystore: bool::arbitrary(g),
                     metrics_address: SocketAddr::arbitrary(g),
                     rpc_address: SocketAddr::arbitrary(g),
+                    healthcheck_address: SocketAddr::arbitrary(g),
                     token_exp: Duration::milliseconds(i64::arbitrary(g)),
                     show_progress_bars: ProgressBarVisibility::arbitrary(g),
+                    miner_address: Option::arbitrary(g),
+                    consensus_fault_reporter: Option::arbitrary(g),
+                    execution_traces: usize::arbitrary(g),
+                    execution_trace_interval: u64::arbitrary(g),
+                    execution_trace_addresses: Vec::arbitrary(g),
+                    tipset_state_cache_size: usize::arbitrary(g),
+                    gc_interval: Duration::milliseconds(i64::arbitrary(g)),
+                    archival: bool::arbitrary(g),
                 },
                 parity_db: crate::db::parity_db_config::ParityDbConfig {
                     enable_statistics: bool::arbitrary(g),
                     compression_type: String::arbitrary(g),
+                    block_cache_size: u32::arbitrary(g) as _,
                 },
                 network: Libp2pConfig {
                     listening_multiaddrs: vec![Ipv4Addr::arbitrary(g).into()],
+                    announce_multiaddrs: vec![
+                        Ipv4Addr::arbitrary(g).into();
+                        u8::arbitrary(g) as usize
+                    ],
                     bootstrap_peers: vec![Ipv4Addr::arbitrary(g).into(); u8::arbitrary(g) as usize],
                     mdns: bool::arbitrary(g),
                     kademlia: bool::arbitrary(g),
                     target_peer_count: u32::arbitrary(g),
+                    max_peer_count: u32::arbitrary(g),
+                    protected_peers: Vec::arbitrary(g),
+                    serve_bitswap: bool::arbitrary(g),
+                    chain_exchange_compression: Vec::arbitrary(g),
+                    publish_dedup_window_secs: u64::arbitrary(g),
+                    publish_rate_limit: u32::arbitrary(g),
+                    pre_shared_key_path: Option::<String>::arbitrary(g).map(Into::into),
+                    quic: bool::arbitrary(g),
+                    relays: vec![Ipv4Addr::arbitrary(g).into(); u8::arbitrary(g) as usize],
+                    hole_punching: bool::arbitrary(g),
+                    port_mapping: bool::arbitrary(g),
                 },
                 sync: SyncConfig {
                     req_window: i64::arbitrary(g),
                     tipset_sample_size: u32::arbitrary(g) as _,
+                    headers_only: bool::arbitrary(g),
+                    precompute_head_state: bool::arbitrary(g),
                 },
             }
         }
@@ -207,6 +363,39 @@ mod test {
         )
     }
 
+    #[test]
+    fn test_set_reloadable_keys() {
+        let mut config = Config::default();
+        config.set("network.target_peer_count", "42").unwrap();
+        assert_eq!(config.network.target_peer_count, 42);
+        config
+            .set("log.filters", r#"[{ module = "forest", level = "debug" }]"#)
+            .unwrap();
+        assert_eq!(
+            config.log.filters,
+            vec![LogValue::new("forest", LevelFilter::Debug)]
+        );
+        assert!(config.reloadable_from(&Config::default()));
+
+        assert!(config.set("network.target_peer_count", "many").is_err());
+        assert!(config.set("client.rpc_address", "127.0.0.1:1234").is_err());
+    }
+
+    #[test]
+    fn test_set_toml_value_creates_tables() {
+        let mut document = toml::Value::Table(Default::default());
+        set_toml_value(
+            &mut document,
+            "network.max_peer_count",
+            parse_toml_value("10"),
+        )
+        .unwrap();
+        assert_eq!(
+            document["network"]["max_peer_count"],
+            toml::Value::Integer(10)
+        );
+    }
+
     #[test]
     fn test_default_log_filters() {
         let config = LogConfig::default();
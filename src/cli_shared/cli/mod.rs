@@ -10,7 +10,7 @@ use std::{
     sync::Arc,
 };
 
-use crate::networks::{ChainConfig, NetworkChain};
+use crate::networks::{ChainConfig, UpgradeOverride};
 use crate::utils::{
     io::{read_file_to_string, read_toml, ProgressBarVisibility},
     misc::LoggingColor,
@@ -40,7 +40,7 @@ OPTIONS:
 ";
 
 /// CLI options
-#[derive(Default, Debug, Parser)]
+#[derive(Default, Debug, Clone, Parser)]
 pub struct CliOpts {
     /// A TOML file containing relevant configurations
     #[arg(short, long)]
@@ -61,6 +61,10 @@ pub struct CliOpts {
     /// Address used for RPC. By defaults binds on localhost on port 1234.
     #[arg(long)]
     pub rpc_address: Option<SocketAddr>,
+    /// Address used for the `/livez`, `/readyz` and `/healthz` health check
+    /// endpoints. By defaults binds on port 2346.
+    #[arg(long)]
+    pub healthcheck_address: Option<SocketAddr>,
     /// Allow Kademlia (default: true)
     #[arg(short, long)]
     pub kademlia: Option<bool>,
@@ -71,15 +75,23 @@ pub struct CliOpts {
     /// the last N EPOCH(s)
     #[arg(long)]
     pub height: Option<i64>,
-    /// Import a snapshot from a local CAR file or URL
+    /// Import a snapshot from a local CAR file or URL, including `s3://` and
+    /// `gs://` ones. A directory or a pattern such as `shard-*.car.zst`
+    /// imports a snapshot sharded into several files.
     #[arg(long)]
     pub import_snapshot: Option<String>,
     /// Halt with exit code 0 after successfully importing a snapshot
     #[arg(long)]
     pub halt_after_import: bool,
-    /// Import a chain from a local CAR file or URL
+    /// Import a chain from a local CAR file or URL, or from the shards in a
+    /// directory or matching a pattern
     #[arg(long)]
     pub import_chain: Option<String>,
+    /// Estimate the number of records and the disk space the snapshot given
+    /// to `--import-snapshot` or `--import-chain` takes, and check that the
+    /// space is available, then exit without importing anything
+    #[arg(long)]
+    pub dry_run: bool,
     /// Skips loading CAR file and uses header to index chain. Assumes a
     /// pre-loaded database
     #[arg(long)]
@@ -97,9 +109,15 @@ pub struct CliOpts {
     /// Encrypt the key-store (default: true)
     #[arg(long)]
     pub encrypt_keystore: Option<bool>,
-    /// Choose network chain to sync to
+    /// Choose network chain to sync to: `mainnet`, `calibnet` (or
+    /// `calibration`), `butterflynet`, `devnet`, or the path of a chain spec
+    /// TOML file defining a custom chain
+    #[arg(long)]
+    pub chain: Option<String>,
+    /// Move a network upgrade of a devnet or custom chain to another epoch,
+    /// e.g. `nv18=10` or `Hygge=10`. Can be repeated.
     #[arg(long)]
-    pub chain: Option<NetworkChain>,
+    pub override_upgrade: Vec<UpgradeOverride>,
     /// Daemonize Forest process
     #[arg(long)]
     pub detach: bool,
@@ -123,6 +141,13 @@ pub struct CliOpts {
     /// Endpoint of `grafana loki`
     #[arg(long, default_value = "http://127.0.0.1:3100")]
     pub loki_endpoint: String,
+    /// Export tracing spans to the given OpenTelemetry (OTLP/gRPC) collector,
+    /// e.g. `http://127.0.0.1:4317`
+    #[arg(long)]
+    pub otlp_endpoint: Option<String>,
+    /// Fraction of the traces exported to the OpenTelemetry collector
+    #[arg(long, default_value_t = 1.0)]
+    pub otlp_sampling_ratio: f64,
     /// Specify a directory into which rolling log files should be appended
     #[arg(long)]
     pub log_dir: Option<PathBuf>,
@@ -141,6 +166,28 @@ pub struct CliOpts {
     /// Check your command-line options and configuration file if one is used
     #[arg(long)]
     pub dry_run: bool,
+    /// Produce blocks for the given storage miner actor, using the key of its
+    /// worker from the keystore. Only supported on devnets, by builds with the
+    /// `insecure_post` feature.
+    #[arg(long)]
+    pub miner: Option<String>,
+    /// Detect the consensus faults in the validated blocks received by the
+    /// node and report them, sending the messages from the given wallet
+    /// address. Disabled by default
+    #[arg(long)]
+    pub consensus_fault_reporter: Option<String>,
+    /// Record the execution traces of the applied messages, keeping the given
+    /// number of most recent ones
+    #[arg(long)]
+    pub execution_traces: Option<usize>,
+    /// Keep every state computed by the node, with the garbage collection
+    /// disabled
+    #[arg(long)]
+    pub archival: bool,
+    /// Sync and validate the block headers only, without executing the
+    /// messages
+    #[arg(long, conflicts_with = "archival")]
+    pub headers_only: bool,
 }
 
 impl CliOpts {
@@ -156,14 +203,18 @@ impl CliOpts {
             None => Config::default(),
         };
 
-        if let Some(chain) = &self.chain {
+        let mut chain = if let Some(chain) = &self.chain {
             // override the chain configuration
-            cfg.chain = Arc::new(ChainConfig::from_chain(chain));
+            ChainConfig::from_chain_arg(chain)?
         } else {
             // override any custom changes to the chain configuration based on the used
             // network.
-            cfg.chain = Arc::new(ChainConfig::from_chain(&cfg.chain.network));
+            ChainConfig::from_chain(&cfg.chain.network)
+        };
+        for upgrade in &self.override_upgrade {
+            chain.override_upgrade(*upgrade)?;
         }
+        cfg.chain = Arc::new(chain);
 
         if let Some(genesis_file) = &self.genesis {
             cfg.client.genesis_file = Some(genesis_file.to_owned());
@@ -183,6 +234,9 @@ impl CliOpts {
         if let Some(metrics_address) = self.metrics_address {
             cfg.client.metrics_address = metrics_address;
         }
+        if let Some(healthcheck_address) = self.healthcheck_address {
+            cfg.client.healthcheck_address = healthcheck_address;
+        }
         if self.import_snapshot.is_some() && self.import_chain.is_some() {
             anyhow::bail!("Can't set import_snapshot and import_chain at the same time!")
         }
@@ -222,6 +276,21 @@ impl CliOpts {
         if let Some(encrypt_keystore) = self.encrypt_keystore {
             cfg.client.encrypt_keystore = encrypt_keystore;
         }
+        if let Some(miner) = &self.miner {
+            cfg.client.miner_address = Some(miner.to_owned());
+        }
+        if let Some(reporter) = &self.consensus_fault_reporter {
+            cfg.client.consensus_fault_reporter = Some(reporter.to_owned());
+        }
+        if let Some(execution_traces) = self.execution_traces {
+            cfg.client.execution_traces = execution_traces;
+        }
+        if self.headers_only {
+            cfg.sync.headers_only = true;
+        }
+        if self.archival {
+            cfg.client.archival = true;
+        }
 
         Ok((cfg, path))
     }
@@ -243,7 +312,7 @@ impl ConfigPath {
     }
 }
 
-fn find_config_path(opts: &CliOpts) -> Option<ConfigPath> {
+pub fn find_config_path(opts: &CliOpts) -> Option<ConfigPath> {
     if let Some(s) = &opts.config {
         return Some(ConfigPath::Cli(PathBuf::from(s)));
     }
@@ -453,4 +522,21 @@ mod tests {
         };
         assert!(options.to_config().is_ok());
     }
+
+    #[test]
+    fn upgrades_are_overridden_on_devnets_only() {
+        let options = CliOpts {
+            chain: Some("devnet".into()),
+            override_upgrade: vec!["nv18=10".parse().unwrap()],
+            ..Default::default()
+        };
+        let (config, _) = options.to_config().unwrap();
+        assert_eq!(config.chain.epoch(crate::networks::Height::Hygge), 10);
+
+        let options = CliOpts {
+            chain: Some("mainnet".into()),
+            ..options
+        };
+        assert!(options.to_config().is_err());
+    }
 }
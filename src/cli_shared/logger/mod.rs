@@ -1,18 +1,61 @@
 // Copyright 2019-2023 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
+use std::time::Duration;
+
+use once_cell::sync::OnceCell;
+use opentelemetry::{
+    sdk::{
+        trace::{self, Sampler},
+        Resource,
+    },
+    KeyValue,
+};
+use tracing::{Level, Subscriber};
 use tracing_subscriber::{
-    filter::{EnvFilter, LevelFilter},
+    filter::{EnvFilter, LevelFilter, Targets},
     prelude::*,
+    registry::LookupSpan,
+    reload,
 };
 
-use crate::cli_shared::cli::{CliOpts, LogConfig};
+use crate::cli_shared::cli::{CliOpts, DaemonConfig, LogConfig};
+use crate::utils::io::RotatingFile;
+
+/// Keeps the exporter of the OpenTelemetry spans running, and flushes the
+/// pending spans when dropped.
+pub struct OtlpExporter {
+    // The batch exporter needs a runtime, the logger is set up before the
+    // runtime of the daemon is built and outlives it
+    _runtime: tokio::runtime::Runtime,
+}
+
+impl Drop for OtlpExporter {
+    fn drop(&mut self) {
+        opentelemetry::global::shutdown_tracer_provider();
+    }
+}
+
+type FilterReloader = Box<dyn Fn(EnvFilter) -> anyhow::Result<()> + Send + Sync>;
+
+/// Swap the filters of the console and file outputs
+static FILTER_RELOADERS: OnceCell<Vec<FilterReloader>> = OnceCell::new();
+
+/// Replaces the log filters set up by [`setup_logger`].
+pub fn reload_log_filters(log_config: &LogConfig) -> anyhow::Result<()> {
+    for reload in FILTER_RELOADERS.get().into_iter().flatten() {
+        reload(build_env_filter(log_config))?;
+    }
+    Ok(())
+}
 
 pub fn setup_logger(
     log_config: &LogConfig,
+    daemon_config: &DaemonConfig,
     opts: &CliOpts,
-) -> (Option<tracing_loki::BackgroundTask>,) {
+) -> (Option<tracing_loki::BackgroundTask>, Option<OtlpExporter>) {
     let mut loki_task = None;
+    let mut otlp_exporter = None;
     let tracing_tokio_console = if opts.tokio_console {
         Some(
             console_subscriber::ConsoleLayer::builder()
@@ -45,29 +88,109 @@ pub fn setup_logger(
     } else {
         None
     };
+    let tracing_otlp = if let Some(endpoint) = &opts.otlp_endpoint {
+        let (layer, exporter) = otlp_layer(endpoint, opts.otlp_sampling_ratio)
+            .map_err(|e| format!("Unable to create OpenTelemetry layer: {e}"))
+            .unwrap();
+        otlp_exporter = Some(exporter);
+        // Only the spans of Forest, the dependencies produce too many
+        Some(layer.with_filter(Targets::new().with_target(env!("CARGO_CRATE_NAME"), Level::DEBUG)))
+    } else {
+        None
+    };
+    let mut filter_reloaders: Vec<FilterReloader> = vec![];
     let tracing_rolling_file = if let Some(log_dir) = &opts.log_dir {
         let file_appender = tracing_appender::rolling::hourly(log_dir, "forest.log");
+        let (filter, handle) = reload::Layer::new(build_env_filter(log_config));
+        filter_reloaders.push(Box::new(move |filter| Ok(handle.reload(filter)?)));
         Some(
             tracing_subscriber::fmt::Layer::new()
                 .with_ansi(false)
                 .with_writer(file_appender)
-                .with_filter(build_env_filter(log_config)),
+                .with_filter(filter),
         )
     } else {
         None
     };
+    let (console_filter, console_handle) = reload::Layer::new(build_env_filter(log_config));
+    filter_reloaders.push(Box::new(move |filter| Ok(console_handle.reload(filter)?)));
+    // Detached, the standard output is redirected to a file that is never
+    // rotated, the logs go to a file of their own
+    let tracing_console = if opts.detach {
+        let rotation = &daemon_config.log_rotation;
+        let log_file = RotatingFile::open(
+            &daemon_config.log_file,
+            (rotation.max_size > 0).then_some(rotation.max_size),
+            (rotation.max_age > 0).then(|| Duration::from_secs(rotation.max_age)),
+            rotation.max_files,
+        )
+        .map_err(|e| {
+            format!(
+                "Unable to open log file {}: {e}",
+                daemon_config.log_file.display()
+            )
+        })
+        .unwrap();
+        tracing_subscriber::fmt::Layer::new()
+            .with_ansi(false)
+            .with_writer(log_file)
+            .with_filter(console_filter)
+            .boxed()
+    } else {
+        tracing_subscriber::fmt::Layer::new()
+            .with_ansi(opts.color.coloring_enabled())
+            .with_filter(console_filter)
+            .boxed()
+    };
 
     tracing_subscriber::registry()
         .with(tracing_tokio_console)
         .with(tracing_loki)
+        .with(tracing_otlp)
         .with(tracing_rolling_file)
-        .with(
-            tracing_subscriber::fmt::Layer::new()
-                .with_ansi(opts.color.coloring_enabled())
-                .with_filter(build_env_filter(log_config)),
-        )
+        .with(tracing_console)
         .init();
-    (loki_task,)
+    let _ = FILTER_RELOADERS.set(filter_reloaders);
+    (loki_task, otlp_exporter)
+}
+
+fn otlp_layer<S>(
+    endpoint: &str,
+    sampling_ratio: f64,
+) -> anyhow::Result<(
+    tracing_opentelemetry::OpenTelemetryLayer<S, trace::Tracer>,
+    OtlpExporter,
+)>
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+{
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(1)
+        .thread_name("otlp-exporter")
+        .enable_all()
+        .build()?;
+    let tracer = {
+        let _guard = runtime.enter();
+        opentelemetry_otlp::new_pipeline()
+            .tracing()
+            .with_exporter(
+                opentelemetry_otlp::new_exporter()
+                    .tonic()
+                    .with_endpoint(endpoint),
+            )
+            .with_trace_config(
+                trace::config()
+                    .with_sampler(Sampler::ParentBased(Box::new(Sampler::TraceIdRatioBased(
+                        sampling_ratio,
+                    ))))
+                    .with_resource(Resource::new([KeyValue::new("service.name", "forest")])),
+            )
+            .install_batch(opentelemetry::runtime::Tokio)?
+    };
+    Ok((
+        tracing_opentelemetry::layer().with_tracer(tracer),
+        OtlpExporter { _runtime: runtime },
+    ))
 }
 
 fn build_env_filter(log_config: &LogConfig) -> EnvFilter {
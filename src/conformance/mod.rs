@@ -0,0 +1,423 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Runner of the Filecoin conformance test vectors, executing them on the VM
+//! and reporting where the resulting state roots and receipts differ from the
+//! expected ones. Only the `message` and `tipset` classes are supported, at
+//! the network versions executed by the FVM.
+
+mod rand;
+pub mod vector;
+
+use std::sync::Arc;
+
+use crate::cli_shared::cli::Config;
+use crate::daemon::bundle::load_bundles;
+use crate::db::MemoryDB;
+use crate::interpreter::{BlockMessages, RewardActorMessageCalc, VMTrace, VM};
+use crate::message::{ChainMessage, SignedMessage};
+use crate::networks::ChainConfig;
+use crate::shim::{
+    address::Protocol,
+    clock::ChainEpoch,
+    crypto::Signature,
+    econ::TokenAmount,
+    executor::{ApplyRet, Receipt},
+    machine::MultiEngine,
+    message::Message,
+    version::NetworkVersion,
+};
+use async_compression::futures::bufread::GzipDecoder;
+use cid::Cid;
+use fvm_ipld_amt::Amtv0 as Amt;
+use fvm_ipld_blockstore::Blockstore;
+use fvm_ipld_car::load_car;
+use fvm_ipld_encoding::from_slice;
+use fvm_shared3::version::NetworkVersion as NetworkVersion_v3;
+
+use self::rand::ReplayingRand;
+use self::vector::{PostConditions, TestVector, Variant, VectorClass};
+
+/// Base fee of the vectors not setting one, as in Lotus
+const DEFAULT_BASE_FEE: u128 = 100;
+/// Circulating supply of the vectors not setting one, the total supply of FIL
+const DEFAULT_CIRC_SUPPLY: u128 = 2_000_000_000 * 10u128.pow(18);
+
+/// Outcome of a vector at one of its variants
+pub struct Outcome {
+    pub variant: String,
+    pub result: VariantResult,
+}
+
+pub enum VariantResult {
+    Passed,
+    /// Differences with the postconditions
+    Failed(Vec<String>),
+    Skipped(String),
+}
+
+pub struct Runner {
+    /// Actor bundles of all the network versions
+    bundles: MemoryDB,
+    engine: MultiEngine,
+}
+
+impl Runner {
+    /// Loads the actor bundles, downloading them if they aren't already
+    pub async fn new() -> anyhow::Result<Self> {
+        let bundles = MemoryDB::default();
+        let config = Config {
+            chain: Arc::new(ChainConfig::mainnet()),
+            ..Default::default()
+        };
+        load_bundles(0, &config, bundles.clone()).await?;
+        Ok(Self {
+            bundles,
+            engine: MultiEngine::default(),
+        })
+    }
+
+    /// Executes the vector at each of its variants
+    pub async fn run(&self, vector: &TestVector) -> anyhow::Result<Vec<Outcome>> {
+        if vector.class == VectorClass::Unsupported {
+            return Ok(vec![Outcome {
+                variant: String::new(),
+                result: VariantResult::Skipped("unsupported class".into()),
+            }]);
+        }
+        let car = MemoryDB::default();
+        load_car(
+            &car,
+            GzipDecoder::new(futures::io::Cursor::new(vector.car.as_slice())),
+        )
+        .await?;
+
+        let mut outcomes = Vec::new();
+        for variant in &vector.preconditions.variants {
+            let result = if variant.nv < 16 {
+                VariantResult::Skipped(format!("network version {} predates the FVM", variant.nv))
+            } else {
+                let store = VectorStore {
+                    bundles: self.bundles.clone(),
+                    car: car.clone(),
+                    written: MemoryDB::default(),
+                };
+                match self.run_variant(vector, variant, &store) {
+                    Ok(mismatches) if mismatches.is_empty() => VariantResult::Passed,
+                    Ok(mismatches) => VariantResult::Failed(mismatches),
+                    Err(e) => VariantResult::Failed(vec![format!("execution failed: {e}")]),
+                }
+            };
+            outcomes.push(Outcome {
+                variant: variant.id.clone(),
+                result,
+            });
+        }
+        Ok(outcomes)
+    }
+
+    /// Returns the differences with the postconditions
+    fn run_variant(
+        &self,
+        vector: &TestVector,
+        variant: &Variant,
+        store: &VectorStore,
+    ) -> anyhow::Result<Vec<String>> {
+        let env = Environment {
+            engine: &self.engine,
+            store,
+            chain_config: Arc::new(chain_config_at(variant.nv)),
+            rand: ReplayingRand::new(&vector.randomness)?,
+            circ_supply: TokenAmount::from_atto(
+                vector
+                    .preconditions
+                    .circ_supply
+                    .unwrap_or(DEFAULT_CIRC_SUPPLY),
+            ),
+        };
+        let mut root = vector.preconditions.state_tree.root_cid;
+        let mut receipts = Vec::new();
+        let mut receipts_roots = Vec::new();
+        match vector.class {
+            VectorClass::Message => {
+                let base_fee = TokenAmount::from_atto(
+                    vector.preconditions.basefee.unwrap_or(DEFAULT_BASE_FEE),
+                );
+                // Each message is applied by a VM of its own
+                for message in &vector.apply_messages {
+                    let epoch = variant.epoch + message.epoch_offset.unwrap_or(0);
+                    let mut vm = env.create_vm(root, epoch, base_fee.clone())?;
+                    let ret = vm.apply_message(&to_chain_message(from_slice(&message.bytes)?))?;
+                    receipts.push(ret.msg_receipt());
+                    root = vm.flush()?;
+                }
+            }
+            VectorClass::Tipset => {
+                let mut parent_epoch = variant.epoch;
+                for tipset in &vector.apply_tipsets {
+                    let epoch = variant.epoch + tipset.epoch_offset;
+                    let base_fee = TokenAmount::from_atto(tipset.basefee);
+                    for null_round in parent_epoch + 1..epoch {
+                        let mut vm = env.create_vm(root, null_round, base_fee.clone())?;
+                        vm.run_cron(
+                            null_round,
+                            None::<&mut fn(&Cid, &ChainMessage, &ApplyRet) -> anyhow::Result<()>>,
+                        )?;
+                        root = vm.flush()?;
+                    }
+
+                    let blocks = tipset
+                        .blocks
+                        .iter()
+                        .map(|block| {
+                            Ok(BlockMessages {
+                                miner: block.miner_addr,
+                                messages: block_messages(&block.messages)?,
+                                win_count: block.win_count,
+                            })
+                        })
+                        .collect::<anyhow::Result<Vec<_>>>()?;
+                    let mut vm = env.create_vm(root, epoch, base_fee)?;
+                    let tipset_receipts = vm.apply_block_messages(
+                        &blocks,
+                        epoch,
+                        None::<fn(&Cid, &ChainMessage, &ApplyRet) -> anyhow::Result<()>>,
+                    )?;
+                    root = vm.flush()?;
+                    receipts_roots.push(Amt::new_from_iter(store, tipset_receipts.clone())?);
+                    receipts.extend(tipset_receipts);
+                    parent_epoch = epoch;
+                }
+            }
+            VectorClass::Unsupported => unreachable!("unsupported vectors are skipped"),
+        }
+        Ok(compare(
+            &vector.postconditions,
+            root,
+            &receipts,
+            &receipts_roots,
+        ))
+    }
+}
+
+/// What the VMs of a variant are created with
+struct Environment<'a> {
+    engine: &'a MultiEngine,
+    store: &'a VectorStore,
+    chain_config: Arc<ChainConfig>,
+    rand: ReplayingRand,
+    circ_supply: TokenAmount,
+}
+
+impl Environment<'_> {
+    fn create_vm(
+        &self,
+        root: Cid,
+        epoch: ChainEpoch,
+        base_fee: TokenAmount,
+    ) -> anyhow::Result<VM<VectorStore>> {
+        VM::new(
+            root,
+            self.store.clone(),
+            epoch,
+            self.rand.clone(),
+            base_fee,
+            self.circ_supply.clone(),
+            Arc::new(RewardActorMessageCalc),
+            // Vectors don't have the states of the previous epochs
+            Box::new(move |_| Ok(root)),
+            Box::new(|_| anyhow::bail!("test vectors don't have the tipsets of a chain")),
+            self.engine,
+            self.chain_config.clone(),
+            0,
+            VMTrace::NotTraced,
+        )
+    }
+}
+
+/// Blockstore reading the blocks of a vector, then the actor bundles, and
+/// keeping the blocks written apart
+#[derive(Clone)]
+struct VectorStore {
+    bundles: MemoryDB,
+    car: MemoryDB,
+    written: MemoryDB,
+}
+
+impl Blockstore for VectorStore {
+    fn get(&self, k: &Cid) -> anyhow::Result<Option<Vec<u8>>> {
+        for store in [&self.written, &self.car, &self.bundles] {
+            if let Some(block) = store.get(k)? {
+                return Ok(Some(block));
+            }
+        }
+        Ok(None)
+    }
+
+    fn has(&self, k: &Cid) -> anyhow::Result<bool> {
+        Ok(self.written.has(k)? || self.car.has(k)? || self.bundles.has(k)?)
+    }
+
+    fn put_keyed(&self, k: &Cid, block: &[u8]) -> anyhow::Result<()> {
+        self.written.put_keyed(k, block)
+    }
+}
+
+/// Returns a chain configuration whose network version is `nv` at any epoch
+fn chain_config_at(nv: u32) -> ChainConfig {
+    let nv = NetworkVersion::from(NetworkVersion_v3::new(nv));
+    let mut config = ChainConfig::mainnet();
+    for (i, info) in config.height_infos.iter_mut().enumerate() {
+        info.epoch = if NetworkVersion::from(info.height) <= nv {
+            ChainEpoch::MIN + i as ChainEpoch
+        } else {
+            ChainEpoch::MAX
+        };
+    }
+    config
+}
+
+/// Wraps SECP messages with an empty signature, as their size is charged
+fn to_chain_message(message: Message) -> ChainMessage {
+    if message.from.protocol() == Protocol::Secp256k1 {
+        ChainMessage::Signed(SignedMessage::new_unchecked(
+            message,
+            Signature::new_secp256k1(vec![0; 65]),
+        ))
+    } else {
+        ChainMessage::Unsigned(message)
+    }
+}
+
+/// Returns the messages of a block, the BLS ones first. Messages from other
+/// protocols are included as both BLS and SECP ones, as done by Lotus.
+fn block_messages(messages: &[Vec<u8>]) -> anyhow::Result<Vec<ChainMessage>> {
+    let mut bls = Vec::new();
+    let mut secp = Vec::new();
+    for bytes in messages {
+        let message: Message = from_slice(bytes)?;
+        match message.from.protocol() {
+            Protocol::BLS => bls.push(ChainMessage::Unsigned(message)),
+            Protocol::Secp256k1 => secp.push(to_chain_message(message)),
+            _ => {
+                bls.push(ChainMessage::Unsigned(message.clone()));
+                secp.push(ChainMessage::Signed(SignedMessage::new_unchecked(
+                    message,
+                    Signature::new_secp256k1(vec![0; 65]),
+                )));
+            }
+        }
+    }
+    bls.extend(secp);
+    Ok(bls)
+}
+
+fn compare(
+    expected: &PostConditions,
+    root: Cid,
+    receipts: &[Receipt],
+    receipts_roots: &[Cid],
+) -> Vec<String> {
+    let mut mismatches = Vec::new();
+    if root != expected.state_tree.root_cid {
+        mismatches.push(format!(
+            "state root {root}, expected {}",
+            expected.state_tree.root_cid
+        ));
+    }
+    if receipts.len() != expected.receipts.len() {
+        mismatches.push(format!(
+            "{} receipts, expected {}",
+            receipts.len(),
+            expected.receipts.len()
+        ));
+    }
+    for (i, (actual, expected)) in receipts.iter().zip(&expected.receipts).enumerate() {
+        if actual.exit_code().value() != expected.exit_code {
+            mismatches.push(format!(
+                "receipt {i}: exit code {}, expected {}",
+                actual.exit_code().value(),
+                expected.exit_code
+            ));
+        }
+        if actual.return_data().bytes() != expected.return_data.as_slice() {
+            mismatches.push(format!(
+                "receipt {i}: return data {}, expected {}",
+                hex::encode(actual.return_data().bytes()),
+                hex::encode(&expected.return_data)
+            ));
+        }
+        if actual.gas_used() != expected.gas_used {
+            mismatches.push(format!(
+                "receipt {i}: gas used {}, expected {}",
+                actual.gas_used(),
+                expected.gas_used
+            ));
+        }
+    }
+    for (i, (actual, expected)) in receipts_roots
+        .iter()
+        .zip(&expected.receipts_roots)
+        .enumerate()
+    {
+        if actual != expected {
+            mismatches.push(format!(
+                "tipset {i}: receipts root {actual}, expected {expected}"
+            ));
+        }
+    }
+    mismatches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chain_config_at_network_version() {
+        for nv in [16, 18, 20] {
+            let config = chain_config_at(nv);
+            let nv = NetworkVersion::from(NetworkVersion_v3::new(nv));
+            for epoch in [-1, 0, 1_000_000] {
+                assert_eq!(config.network_version(epoch), nv);
+            }
+        }
+    }
+
+    #[test]
+    fn vectors_deserialize() {
+        let vector: TestVector = serde_json::from_str(
+            r#"{
+                "class": "message",
+                "_meta": { "id": "test" },
+                "car": "",
+                "preconditions": {
+                    "variants": [{ "id": "nv18", "epoch": 10, "nv": 18 }],
+                    "state_tree": { "root_cid": { "/": "bafy2bzacea3wsdh6y3a36tb3skempjoxqpuyompjbmfeyf34fi3uy6uue42v4" } },
+                    "basefee": 100,
+                    "circ_supply": 2000000000000000000000000000
+                },
+                "apply_messages": [{ "bytes": "AAE=", "epoch_offset": 1 }],
+                "postconditions": {
+                    "state_tree": { "root_cid": { "/": "bafy2bzacea3wsdh6y3a36tb3skempjoxqpuyompjbmfeyf34fi3uy6uue42v4" } },
+                    "receipts": [{ "exit_code": 0, "return": "", "gas_used": 1000 }]
+                }
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(vector.class, VectorClass::Message);
+        assert_eq!(vector.preconditions.circ_supply, Some(DEFAULT_CIRC_SUPPLY));
+        assert_eq!(vector.apply_messages[0].bytes, vec![0, 1]);
+
+        let vector: TestVector = serde_json::from_str(
+            r#"{
+                "class": "blockseq",
+                "car": "",
+                "preconditions": { "variants": [], "state_tree": { "root_cid": { "/": "bafy2bzacea3wsdh6y3a36tb3skempjoxqpuyompjbmfeyf34fi3uy6uue42v4" } } },
+                "postconditions": { "state_tree": { "root_cid": { "/": "bafy2bzacea3wsdh6y3a36tb3skempjoxqpuyompjbmfeyf34fi3uy6uue42v4" } } }
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(vector.class, VectorClass::Unsupported);
+    }
+}
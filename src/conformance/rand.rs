@@ -0,0 +1,69 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use crate::shim::{clock::ChainEpoch, externs::Rand};
+
+use super::vector::{RandomnessKind, RandomnessMatch, RandomnessRule};
+
+/// Randomness returned when a vector didn't record the one drawn, as by Lotus
+const FIXED_RANDOMNESS: [u8; 32] = *b"i_am_random_____i_am_random_____";
+
+/// Replays the randomness recorded in a vector
+#[derive(Clone)]
+pub struct ReplayingRand {
+    recorded: Vec<(RandomnessRule, [u8; 32])>,
+}
+
+impl ReplayingRand {
+    pub fn new(recorded: &[RandomnessMatch]) -> anyhow::Result<Self> {
+        let recorded = recorded
+            .iter()
+            .map(|RandomnessMatch { on, ret }| {
+                let ret = <[u8; 32]>::try_from(ret.as_slice())
+                    .map_err(|_| anyhow::anyhow!("recorded randomness must be 32 bytes"))?;
+                Ok((on.clone(), ret))
+            })
+            .collect::<anyhow::Result<_>>()?;
+        Ok(Self { recorded })
+    }
+
+    fn replay(
+        &self,
+        kind: RandomnessKind,
+        dst: i64,
+        epoch: ChainEpoch,
+        entropy: &[u8],
+    ) -> [u8; 32] {
+        let rule = RandomnessRule {
+            kind,
+            dst,
+            epoch,
+            entropy: entropy.to_vec(),
+        };
+        self.recorded
+            .iter()
+            .find(|(recorded, _)| recorded == &rule)
+            .map(|(_, ret)| *ret)
+            .unwrap_or(FIXED_RANDOMNESS)
+    }
+}
+
+impl Rand for ReplayingRand {
+    fn get_chain_randomness(
+        &self,
+        pers: i64,
+        round: ChainEpoch,
+        entropy: &[u8],
+    ) -> anyhow::Result<[u8; 32]> {
+        Ok(self.replay(RandomnessKind::Chain, pers, round, entropy))
+    }
+
+    fn get_beacon_randomness(
+        &self,
+        pers: i64,
+        round: ChainEpoch,
+        entropy: &[u8],
+    ) -> anyhow::Result<[u8; 32]> {
+        Ok(self.replay(RandomnessKind::Beacon, pers, round, entropy))
+    }
+}
@@ -0,0 +1,149 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Schema of the test vectors, see
+//! <https://github.com/filecoin-project/test-vectors/tree/master/schema>.
+
+use crate::shim::{address::Address, clock::ChainEpoch};
+use cid::Cid;
+use serde::Deserialize;
+use serde_with::{base64::Base64, serde_as, DisplayFromStr};
+
+// The classes share most of their fields. They aren't an internally tagged
+// enum as the base fees and circulating supplies, above `u64::MAX`, would be
+// buffered as floats.
+#[serde_as]
+#[derive(Debug, Deserialize)]
+pub struct TestVector {
+    pub class: VectorClass,
+    #[serde(rename = "_meta")]
+    pub meta: Option<MetaData>,
+    /// Gzipped CAR of the blocks of the states
+    #[serde_as(as = "Base64")]
+    pub car: Vec<u8>,
+    pub preconditions: PreConditions,
+    /// Messages of `message` vectors
+    #[serde(default)]
+    pub apply_messages: Vec<ApplyMessage>,
+    /// Tipsets of `tipset` vectors
+    #[serde(default)]
+    pub apply_tipsets: Vec<TipsetCase>,
+    pub postconditions: PostConditions,
+    #[serde(default)]
+    pub randomness: Vec<RandomnessMatch>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VectorClass {
+    /// Messages applied one after the other on top of a state
+    Message,
+    /// Tipsets applied one after the other on top of a state, with the
+    /// rewards of their blocks and the cron of their epochs
+    Tipset,
+    /// Classes not supported by the runner, e.g. `blockseq`
+    #[serde(other)]
+    Unsupported,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MetaData {
+    pub id: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PreConditions {
+    pub variants: Vec<Variant>,
+    pub state_tree: StateTree,
+    /// Base fee of the messages, in attoFIL
+    pub basefee: Option<u128>,
+    /// Circulating supply of the messages, in attoFIL
+    pub circ_supply: Option<u128>,
+}
+
+/// Epoch and network version the vector is executed at
+#[derive(Debug, Deserialize)]
+pub struct Variant {
+    pub id: String,
+    pub epoch: ChainEpoch,
+    pub nv: u32,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct StateTree {
+    #[serde(with = "crate::json::cid")]
+    pub root_cid: Cid,
+}
+
+#[serde_as]
+#[derive(Debug, Deserialize)]
+pub struct ApplyMessage {
+    /// CBOR of the unsigned message
+    #[serde_as(as = "Base64")]
+    pub bytes: Vec<u8>,
+    pub epoch_offset: Option<ChainEpoch>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TipsetCase {
+    pub epoch_offset: ChainEpoch,
+    pub basefee: u128,
+    pub blocks: Vec<BlockCase>,
+}
+
+#[serde_as]
+#[derive(Debug, Deserialize)]
+pub struct BlockCase {
+    #[serde_as(as = "DisplayFromStr")]
+    pub miner_addr: Address,
+    pub win_count: i64,
+    /// CBOR of the unsigned messages
+    #[serde_as(as = "Vec<Base64>")]
+    pub messages: Vec<Vec<u8>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PostConditions {
+    pub state_tree: StateTree,
+    #[serde(default)]
+    pub receipts: Vec<ReceiptCase>,
+    /// Receipts roots of the tipsets applied, for tipset vectors
+    #[serde(default, with = "crate::json::cid::vec")]
+    pub receipts_roots: Vec<Cid>,
+}
+
+#[serde_as]
+#[derive(Debug, Deserialize)]
+pub struct ReceiptCase {
+    pub exit_code: u32,
+    #[serde(rename = "return")]
+    #[serde_as(as = "Base64")]
+    pub return_data: Vec<u8>,
+    pub gas_used: u64,
+}
+
+/// Randomness drawn while the vector was generated
+#[serde_as]
+#[derive(Debug, Deserialize)]
+pub struct RandomnessMatch {
+    pub on: RandomnessRule,
+    #[serde_as(as = "Base64")]
+    pub ret: Vec<u8>,
+}
+
+#[serde_as]
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+pub struct RandomnessRule {
+    pub kind: RandomnessKind,
+    pub dst: i64,
+    pub epoch: ChainEpoch,
+    #[serde_as(as = "Base64")]
+    pub entropy: Vec<u8>,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum RandomnessKind {
+    Chain,
+    Beacon,
+}
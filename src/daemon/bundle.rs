@@ -27,7 +27,7 @@ where
     }
 
     for bundle in bundles {
-        let (result, _) = forest_load_car(db.clone(), bundle.compat()).await?;
+        let (result, _) = forest_load_car(db.clone(), bundle.compat(), &Default::default()).await?;
         assert_eq!(
             result.len(),
             1,
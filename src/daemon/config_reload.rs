@@ -0,0 +1,179 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Changes of the configuration while the node is running, on `SIGHUP` or
+//! through the `Filecoin.ConfigReload` and `Filecoin.ConfigSet` RPC methods.
+//! Only the [`RELOADABLE_KEYS`] and the message pool limits are applied, the
+//! other changes are picked up on restart.
+
+use std::sync::Arc;
+
+use crate::blocks::Tipset;
+use crate::cli_shared::{
+    cli::{
+        find_config_path, parse_toml_value, set_toml_value, CliOpts, Config, ConfigEvent,
+        RELOADABLE_KEYS,
+    },
+    logger::reload_log_filters,
+};
+use crate::db::{rolling::DbGarbageCollector, Store};
+use crate::libp2p::PeerManager;
+use crate::message_pool::{MessagePool, Provider};
+use crate::shim::clock::ChainEpoch;
+use anyhow::Context;
+use log::{info, warn};
+use tokio::signal::unix::{signal, SignalKind};
+
+type ConfigEventReceiver = flume::Receiver<(ConfigEvent, flume::Sender<anyhow::Result<()>>)>;
+
+pub(super) struct ConfigReloader<DB, T, F, G>
+where
+    F: Fn() -> Tipset + Send + Sync + 'static,
+    G: Fn() -> Option<ChainEpoch> + Send + Sync + 'static,
+{
+    pub opts: CliOpts,
+    /// The configuration the node was started with, and the reloadable
+    /// changes applied since
+    pub config: Config,
+    pub db: DB,
+    pub peer_manager: Arc<PeerManager>,
+    pub mpool: Arc<MessagePool<T>>,
+    pub db_garbage_collector: Arc<DbGarbageCollector<F, G>>,
+}
+
+impl<DB, T, F, G> ConfigReloader<DB, T, F, G>
+where
+    DB: Store,
+    T: Provider + Send + Sync + 'static,
+    F: Fn() -> Tipset + Send + Sync + 'static,
+    G: Fn() -> Option<ChainEpoch> + Send + Sync + 'static,
+{
+    pub async fn run(mut self, config_event_rx: ConfigEventReceiver) -> anyhow::Result<()> {
+        let mut hangup = signal(SignalKind::hangup())?;
+        loop {
+            let (event, reply) = tokio::select! {
+                _ = hangup.recv() => {
+                    info!("Received SIGHUP, reloading the configuration");
+                    (ConfigEvent::Reload, None)
+                }
+                event = config_event_rx.recv_async() => {
+                    let (event, reply) = event?;
+                    (event, Some(reply))
+                }
+            };
+            let result = match &event {
+                ConfigEvent::Reload => self.reload(),
+                ConfigEvent::Set { key, value } => self.set(key, value),
+            };
+            if let Err(e) = &result {
+                warn!("Failed to apply {event:?}: {e}");
+            }
+            if let Some(reply) = reply {
+                // The requester is gone if the RPC call was cancelled
+                let _ = reply.send_async(result).await;
+            }
+        }
+    }
+
+    fn reload(&mut self) -> anyhow::Result<()> {
+        let (config, _) = self.opts.to_config()?;
+        self.apply(config)
+    }
+
+    fn set(&mut self, key: &str, value: &str) -> anyhow::Result<()> {
+        if let Some(name) = key.strip_prefix("mpool.") {
+            return self.set_mpool_config(name, value);
+        }
+        let mut config = self.config.clone();
+        config.set(key, value)?;
+        self.apply(config)?;
+
+        let Some(path) = find_config_path(&self.opts) else {
+            warn!("No configuration file, {key} is reset on restart");
+            return Ok(());
+        };
+        let path = path.to_path_buf();
+        let mut document = if path.exists() {
+            toml::from_str(&std::fs::read_to_string(path)?)?
+        } else {
+            toml::Value::Table(Default::default())
+        };
+        set_toml_value(&mut document, key, parse_toml_value(value))?;
+        std::fs::write(path, toml::to_string(&document)?)
+            .with_context(|| format!("could not write {}", path.display()))?;
+        info!("Saved {key} to {}", path.display());
+        Ok(())
+    }
+
+    /// The message pool configuration is kept in the database, not in the
+    /// configuration file.
+    fn set_mpool_config(&self, name: &str, value: &str) -> anyhow::Result<()> {
+        let mut mpool_config = self.mpool.get_config();
+        let invalid = || format!("invalid value for mpool.{name}");
+        match name {
+            "size_limit_high" => {
+                mpool_config.size_limit_high = value.parse().with_context(invalid)?
+            }
+            "size_limit_low" => {
+                mpool_config.size_limit_low = value.parse().with_context(invalid)?
+            }
+            "randomize_chain_ties" => {
+                mpool_config.randomize_chain_ties = value.parse().with_context(invalid)?
+            }
+            "greedy_selection_threshold" => {
+                let threshold: f64 = value.parse().with_context(invalid)?;
+                anyhow::ensure!(
+                    (0.0..=1.0).contains(&threshold),
+                    "mpool.greedy_selection_threshold must be between 0 and 1"
+                );
+                mpool_config.greedy_selection_threshold = threshold
+            }
+            _ => anyhow::bail!(
+                "mpool.{name} cannot be changed at runtime, supported keys: mpool.size_limit_high, mpool.size_limit_low, mpool.randomize_chain_ties, mpool.greedy_selection_threshold"
+            ),
+        }
+        self.mpool.set_config(&self.db, mpool_config)?;
+        info!("Set mpool.{name} to {value}");
+        Ok(())
+    }
+
+    fn apply(&mut self, config: Config) -> anyhow::Result<()> {
+        if !config.reloadable_from(&self.config) {
+            warn!(
+                "Some configuration changes require a restart, only {} are applied",
+                RELOADABLE_KEYS.join(", ")
+            );
+        }
+        let gc_interval = config.client.gc_interval()?;
+
+        if config.log != self.config.log {
+            reload_log_filters(&config.log)?;
+            self.config.log = config.log;
+            info!("Reloaded the log filters");
+        }
+        let network = &config.network;
+        if (network.target_peer_count, network.max_peer_count)
+            != (
+                self.config.network.target_peer_count,
+                self.config.network.max_peer_count,
+            )
+        {
+            self.peer_manager.set_peer_count_limits(
+                network.target_peer_count as usize,
+                network.max_peer_count as usize,
+            );
+            self.config.network.target_peer_count = network.target_peer_count;
+            self.config.network.max_peer_count = network.max_peer_count;
+            info!(
+                "Set the peer count limits to {} and {}",
+                network.target_peer_count, network.max_peer_count
+            );
+        }
+        if config.client.gc_interval != self.config.client.gc_interval {
+            self.db_garbage_collector.set_interval(gc_interval);
+            self.config.client.gc_interval = config.client.gc_interval;
+            info!("Set the garbage collection interval to {gc_interval:?}");
+        }
+        Ok(())
+    }
+}
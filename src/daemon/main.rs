@@ -12,7 +12,7 @@ use crate::utils::version::FOREST_VERSION_STRING;
 use anyhow::Context;
 use clap::Parser;
 use daemonize_me::{Daemon, Group, User};
-use log::info;
+use log::{info, warn};
 use raw_sync::{
     events::{Event, EventInit},
     Timeout,
@@ -91,7 +91,7 @@ where
     // Run forest as a daemon if no other subcommands are used. Otherwise, run the
     // subcommand.
 
-    let (loki_task,) = logger::setup_logger(&cfg.log, &opts);
+    let (loki_task, _otlp_exporter) = logger::setup_logger(&cfg.log, &cfg.daemon, &opts);
     ProgressBar::set_progress_bars_visibility(cfg.client.show_progress_bars);
 
     if let Some(path) = &path {
@@ -152,9 +152,17 @@ where
             if let Some(loki_task) = loki_task {
                 rt.spawn(loki_task);
             }
+            let detach = opts.detach;
+            let pid_file = cfg.daemon.pid_file.clone();
             let ret = rt.block_on(super::start_interruptable(opts, cfg));
             info!("Shutting down tokio...");
             rt.shutdown_timeout(Duration::from_secs_f32(0.5));
+            if let (true, Some(pid_file)) = (detach, pid_file) {
+                // A stale PID file would be mistaken for a running daemon
+                if let Err(e) = std::fs::remove_file(&pid_file) {
+                    warn!("Failed to remove PID file {}: {e}", pid_file.display());
+                }
+            }
             info!("Forest finish shutdown");
             ret
         }
@@ -2,12 +2,17 @@
 // SPDX-License-Identifier: Apache-2.0, MIT
 
 pub mod bundle;
+mod config_reload;
 pub mod main;
+mod shutdown;
 
 use crate::auth::{create_token, generate_priv_key, ADMIN, JWT_IDENTIFIER};
-use crate::blocks::Tipset;
+use crate::blocks::{timing::BlockTiming, Tipset};
 use crate::chain::ChainStore;
-use crate::chain_sync::{consensus::SyncGossipSubmitter, ChainMuxer};
+use crate::chain_sync::{
+    backfill_receipts, consensus::SyncGossipSubmitter, consensus_fault_reporter, BadBlockCache,
+    ChainMuxer, BAD_BLOCKS_FILE_NAME,
+};
 use crate::cli_shared::{
     chain_path,
     cli::{CliOpts, Config},
@@ -18,39 +23,58 @@ use crate::db::{
     rolling::DbGarbageCollector,
     Store,
 };
+use crate::f3::F3Client;
 use crate::genesis::{
-    get_network_name_from_genesis, import_chain, read_genesis_header, validate_chain,
+    available_space, estimate_import, fetch_genesis, get_network_name_from_genesis, import_chain,
+    read_genesis_header, snapshot_files, validate_chain,
 };
+use crate::health::HealthCheckState;
+use crate::interpreter::TraceSampling;
 use crate::key_management::{
     KeyStore, KeyStoreConfig, ENCRYPTED_KEYSTORE_NAME, FOREST_KEYSTORE_PHRASE_ENV,
 };
-use crate::libp2p::{get_keypair, Libp2pConfig, Libp2pService, PeerId, PeerManager};
+use crate::libp2p::{
+    get_keypair, read_pre_shared_key, Libp2pConfig, Libp2pService, PeerId, PeerManager, Protocol,
+    BOOTSTRAP_PEERS_FILE_NAME,
+};
 use crate::message_pool::{MessagePool, MpoolConfig, MpoolRpcProvider};
-use crate::rpc::start_rpc;
+use crate::miner_monitor::MinerMonitor;
+use crate::rpc::{start_rpc, SnapshotServer};
 use crate::rpc_api::data_types::RPCState;
 use crate::shim::{
-    address::{CurrentNetwork, Network},
+    address::{Address, CurrentNetwork, Network},
     clock::ChainEpoch,
     version::NetworkVersion,
 };
 use crate::state_manager::StateManager;
 use crate::utils::{
-    io::write_to_file, monitoring::MemStatsTracker,
-    proofs_api::paramfetch::ensure_params_downloaded, retry, version::FOREST_VERSION_STRING,
+    db::{replay_journal, BufferedWriteConfig},
+    io::write_to_file,
+    monitoring::MemStatsTracker,
+    net::is_object_store_url,
+    proofs_api::paramfetch::ensure_params_downloaded,
+    retry,
+    version::FOREST_VERSION_STRING,
     RetryArgs,
 };
+use crate::watch::{AddressWatcher, WATCHES_FILE_NAME};
 use anyhow::{bail, Context};
 use bundle::load_bundles;
+use config_reload::ConfigReloader;
 use dialoguer::{console::Term, theme::ColorfulTheme};
 use futures::{select, Future, FutureExt};
+use human_repr::HumanCount;
 use lazy_static::lazy_static;
 use log::{debug, info, warn};
 use raw_sync::events::{Event, EventInit as _, EventState};
 use shared_memory::ShmemConf;
+use shutdown::ShutdownCoordinator;
 use std::{
     cell::RefCell,
     net::TcpListener,
+    num::NonZeroUsize,
     path::{Path, PathBuf},
+    str::FromStr,
     sync::Arc,
     time,
     time::Duration,
@@ -65,6 +89,9 @@ use tokio::{
     task::JoinSet,
 };
 
+/// Journal of the records of an import, in the chain data directory
+const IMPORT_JOURNAL_FILE_NAME: &str = "import.journal";
+
 lazy_static! {
     static ref IPC_PATH: TempPath = Builder::new()
         .prefix("forest-ipc")
@@ -109,12 +136,14 @@ fn unblock_parent_process() -> anyhow::Result<()> {
 }
 
 // Start the daemon and abort if we're interrupted by ctrl-c, SIGTERM, or `forest-cli shutdown`.
+// The services are stopped before the state they leave behind is flushed.
 pub async fn start_interruptable(opts: CliOpts, config: Config) -> anyhow::Result<()> {
     let mut terminate = signal(SignalKind::terminate())?;
     let (shutdown_send, mut shutdown_recv) = mpsc::channel(1);
+    let shutdown = ShutdownCoordinator::default();
 
     let result = tokio::select! {
-        ret = start(opts, config, shutdown_send) => ret,
+        ret = start(opts, config, shutdown_send, &shutdown) => ret,
         _ = ctrl_c() => {
             info!("Keyboard interrupt.");
             Ok(())
@@ -128,6 +157,7 @@ pub async fn start_interruptable(opts: CliOpts, config: Config) -> anyhow::Resul
             Ok(())
         },
     };
+    shutdown.shutdown().await;
     crate::utils::io::terminal_cleanup();
     result
 }
@@ -137,11 +167,19 @@ pub(super) async fn start(
     opts: CliOpts,
     config: Config,
     shutdown_send: mpsc::Sender<()>,
+    shutdown: &ShutdownCoordinator,
 ) -> anyhow::Result<()> {
     if config.chain.is_testnet() {
         CurrentNetwork::set_global(Network::Testnet);
     }
 
+    if opts.dry_run {
+        let Some(path) = &config.client.snapshot_path else {
+            bail!("--dry-run needs --import-snapshot or --import-chain");
+        };
+        return preflight_import(&config, path, true).await;
+    }
+
     info!(
         "Starting Forest daemon, version {}",
         FOREST_VERSION_STRING.as_str()
@@ -187,6 +225,8 @@ pub(super) async fn start(
 
     let chain_data_path = chain_path(&config);
     let db = open_proxy_db(db_root(&chain_data_path), config.db_config().clone())?;
+    shutdown.set_db(db.clone());
+    replay_journal(&db, &chain_data_path.join(IMPORT_JOURNAL_FILE_NAME))?;
 
     let mut services = JoinSet::new();
 
@@ -219,12 +259,12 @@ pub(super) async fn start(
     // Read Genesis file
     // * When snapshot command implemented, this genesis does not need to be
     //   initialized
-    let genesis_header = read_genesis_header(
-        config.client.genesis_file.as_ref(),
-        config.chain.genesis_bytes(),
-        &db,
-    )
-    .await?;
+    let genesis_file = match &config.client.genesis_file {
+        Some(genesis_file) => Some(genesis_file.clone()),
+        None => fetch_genesis(&config.chain, &chain_data_path).await?,
+    };
+    let genesis_header =
+        read_genesis_header(genesis_file.as_ref(), config.chain.genesis_bytes(), &db).await?;
 
     // Initialize ChainStore
     let chain_store = Arc::new(ChainStore::new(
@@ -238,27 +278,87 @@ pub(super) async fn start(
     let db_garbage_collector = {
         let db = db.clone();
         let file_backed_chain_meta = chain_store.file_backed_chain_meta().clone();
-        let chain_store = chain_store.clone();
-        let get_tipset = move || chain_store.heaviest_tipset().as_ref().clone();
+        let get_tipset = {
+            let chain_store = chain_store.clone();
+            move || chain_store.heaviest_tipset().as_ref().clone()
+        };
+        let get_finalized_epoch = {
+            let chain_store = chain_store.clone();
+            move || {
+                chain_store
+                    .finalized_tipset()
+                    .map(|finalized| finalized.epoch)
+            }
+        };
+        // Nodes syncing the headers only keep the header chain, and the
+        // genesis state
+        let recent_state_roots = match config.sync.headers_only {
+            true => 0,
+            false => config.chain.recent_state_roots,
+        };
         Arc::new(DbGarbageCollector::new(
             db,
             file_backed_chain_meta,
             config.chain.policy.chain_finality,
-            config.chain.recent_state_roots,
+            recent_state_roots,
             get_tipset,
+            get_finalized_epoch,
+            config.client.gc_interval()?,
         ))
     };
 
-    if !opts.no_gc {
+    if config.client.archival {
+        // Every state computed is kept, the database only grows
+        info!("Archival mode: garbage collection disabled, all the states are kept");
+        services.spawn({
+            let db_garbage_collector = db_garbage_collector.clone();
+            async move { db_garbage_collector.reject_loop_event().await }
+        });
+    } else {
+        if !opts.no_gc {
+            services.spawn({
+                let db_garbage_collector = db_garbage_collector.clone();
+                async move { db_garbage_collector.collect_loop_passive().await }
+            });
+        }
         services.spawn({
             let db_garbage_collector = db_garbage_collector.clone();
-            async move { db_garbage_collector.collect_loop_passive().await }
+            async move { db_garbage_collector.collect_loop_event().await }
+        });
+    }
+
+    let f3 = Arc::new(F3Client::default());
+    if config.f3.certificate_source.is_some() {
+        services.spawn(
+            f3.clone()
+                .certificate_poll_loop(chain_store.clone(), config.f3.clone()),
+        );
+    }
+
+    {
+        // Start the health check server
+        let healthcheck_listener = TcpListener::bind(config.client.healthcheck_address).context(
+            format!("could not bind to {}", config.client.healthcheck_address),
+        )?;
+        info!(
+            "Health check server started at {}",
+            config.client.healthcheck_address
+        );
+        let state = HealthCheckState::new(
+            chain_store.clone(),
+            db.clone(),
+            config
+                .client
+                .enable_rpc
+                .then_some(config.client.rpc_address),
+            config.chain.block_delay_secs,
+        )?;
+        services.spawn(async {
+            crate::health::init_healthcheck_server(healthcheck_listener, state)
+                .await
+                .context("Failed to initiate health check server")
         });
     }
-    services.spawn({
-        let db_garbage_collector = db_garbage_collector.clone();
-        async move { db_garbage_collector.collect_loop_event().await }
-    });
 
     let publisher = chain_store.publisher();
 
@@ -269,11 +369,37 @@ pub(super) async fn start(
     let reward_calc = cns::reward_calc();
 
     // Initialize StateManager
-    let sm = StateManager::new(
+    let mut sm = StateManager::new(
         Arc::clone(&chain_store),
         Arc::clone(&config.chain),
         reward_calc,
-    )?;
+    )?
+    .with_execution_lanes(&config.execution)
+    .with_tipset_state_cache_size(config.client.tipset_state_cache_size);
+    let precompute_head_state = config.sync.precompute_head_state && !config.sync.headers_only;
+    if precompute_head_state {
+        sm = sm.with_precomputed_head_states();
+    }
+    if let Some(capacity) = NonZeroUsize::new(config.client.execution_traces) {
+        let sampling = TraceSampling {
+            interval: config.client.execution_trace_interval,
+            watched: config
+                .client
+                .execution_trace_addresses
+                .iter()
+                .map(|address| {
+                    Address::from_str(address)
+                        .with_context(|| format!("invalid traced address {address}"))
+                })
+                .collect::<anyhow::Result<_>>()?,
+        };
+        info!(
+            "Recording the execution traces of the last {capacity} messages, in one tipset every {} and of {} watched addresses",
+            sampling.interval,
+            sampling.watched.len()
+        );
+        sm = sm.with_execution_traces(capacity, sampling);
+    }
 
     let state_manager = Arc::new(sm);
 
@@ -311,33 +437,74 @@ pub(super) async fn start(
     load_bundles(epoch, &config, db.clone()).await?;
 
     let peer_manager = Arc::new(PeerManager::default());
+    for peer_id in protected_peers(&config.network)? {
+        peer_manager.protect_peer(peer_id).await;
+    }
     services.spawn(peer_manager.clone().peer_operation_event_loop_task());
+    peer_manager.set_peer_count_limits(
+        config.network.target_peer_count as usize,
+        config.network.max_peer_count as usize,
+    );
+    services.spawn(peer_manager.clone().peer_pruning_loop_task());
     let genesis_cid = *genesis_header.cid();
+    let pre_shared_key = match &config.network.pre_shared_key_path {
+        // A private network needs a chain of its own, its gossip topics and
+        // genesis would otherwise be those of the public one
+        Some(_) if !config.chain.network.is_devnet() => {
+            bail!(
+                "A private network can't use the {} chain",
+                config.chain.network
+            )
+        }
+        Some(path) => Some(read_pre_shared_key(path)?),
+        None => None,
+    };
     // Libp2p service setup
     let p2p_service = Libp2pService::new(
         config.network.clone(),
         Arc::clone(&chain_store),
         peer_manager.clone(),
         net_keypair,
+        pre_shared_key,
         &network_name,
         genesis_cid,
-    );
+    )
+    .with_bootstrap_peers_file(chain_data_path.join(BOOTSTRAP_PEERS_FILE_NAME))?
+    .with_block_timing(BlockTiming::from(config.chain.as_ref()));
 
     let network_rx = p2p_service.network_receiver();
     let network_send = p2p_service.network_sender();
+    shutdown.set_peer_store(p2p_service.peer_store());
 
     // Initialize mpool
     let provider = MpoolRpcProvider::new(publisher.clone(), Arc::clone(&state_manager));
+    let mut mpool_config = MpoolConfig::load_config(&db)?;
+    mpool_config.headers_only = config.sync.headers_only;
     let mpool = MessagePool::new(
         provider,
         network_name.clone(),
         network_send.clone(),
-        MpoolConfig::load_config(&db)?,
+        mpool_config,
         Arc::clone(state_manager.chain_config()),
         &mut services,
     )?;
 
+    mpool.restore_local(&db)?;
     let mpool = Arc::new(mpool);
+    shutdown.set_mpool(mpool.clone());
+
+    let (config_event_tx, config_event_rx) = flume::unbounded();
+    services.spawn(
+        ConfigReloader {
+            opts: opts.clone(),
+            config: config.clone(),
+            db: db.clone(),
+            peer_manager: peer_manager.clone(),
+            mpool: mpool.clone(),
+            db_garbage_collector: db_garbage_collector.clone(),
+        }
+        .run(config_event_rx),
+    );
 
     // For consensus types that do mining, create a component to submit their
     // proposals.
@@ -348,27 +515,94 @@ pub(super) async fn start(
     );
 
     // Initialize Consensus. Mining may or may not happen, depending on type.
-    let consensus =
-        cns::consensus(&state_manager, &keystore, &mpool, submitter, &mut services).await?;
+    let miner = config
+        .client
+        .miner_address
+        .as_deref()
+        .map(Address::from_str)
+        .transpose()
+        .context("invalid miner address")?;
+    let consensus = cns::consensus(
+        &state_manager,
+        &keystore,
+        &mpool,
+        miner,
+        submitter,
+        &mut services,
+    )
+    .await?;
+    let consensus = Arc::new(consensus);
+
+    let fault_reporter = config
+        .client
+        .consensus_fault_reporter
+        .as_deref()
+        .map(Address::from_str)
+        .transpose()
+        .context("invalid consensus fault reporter address")?;
+    let observed_headers = fault_reporter.map(|reporter| {
+        let (headers_tx, headers_rx) = flume::bounded(1024);
+        services.spawn(consensus_fault_reporter(
+            headers_rx,
+            Arc::clone(&consensus),
+            Arc::clone(&state_manager),
+            Arc::clone(&mpool),
+            Arc::clone(&keystore),
+            reporter,
+        ));
+        headers_tx
+    });
+
+    let bad_block_cache = Arc::new(BadBlockCache::load_or_create(
+        chain_data_path.join(BAD_BLOCKS_FILE_NAME),
+    )?);
+    shutdown.set_bad_blocks(bad_block_cache.clone());
+    services.spawn(bad_block_cache.clone().persist_loop());
 
     // Initialize ChainMuxer
     let chain_muxer_tipset_sink = tipset_sink.clone();
     let chain_muxer = ChainMuxer::new(
-        Arc::new(consensus),
+        consensus,
         Arc::clone(&state_manager),
-        peer_manager,
+        peer_manager.clone(),
         mpool.clone(),
         network_send.clone(),
         network_rx,
         Arc::new(Tipset::from(genesis_header)),
+        bad_block_cache,
         chain_muxer_tipset_sink,
         tipset_stream,
+        observed_headers,
         config.sync.clone(),
     )?;
     let bad_blocks = chain_muxer.bad_blocks_cloned();
     let sync_state = chain_muxer.sync_state_cloned();
     services.spawn(async { Err(anyhow::anyhow!("{}", chain_muxer.await)) });
 
+    if precompute_head_state {
+        services.spawn(Arc::clone(&state_manager).compute_head_states());
+    }
+
+    services.spawn(crate::events::event_loop(
+        config.events.clone(),
+        chain_store.clone(),
+    ));
+
+    let miner_monitor = Arc::new(MinerMonitor::new(&config.miner_monitor)?);
+    services.spawn(
+        miner_monitor
+            .clone()
+            .monitor_loop(Arc::clone(&state_manager)),
+    );
+
+    if config.backfill.enabled {
+        services.spawn(backfill_receipts(
+            Arc::clone(&state_manager),
+            sync_state.clone(),
+            config.backfill.clone(),
+        ));
+    }
+
     // Start services
     if config.client.enable_rpc {
         let keystore_rpc = Arc::clone(&keystore);
@@ -378,10 +612,27 @@ pub(super) async fn start(
                 config.client.rpc_address
             ))?;
 
+        let address_watcher = Arc::new(AddressWatcher::load_or_create(
+            chain_data_path.join(WATCHES_FILE_NAME),
+        )?);
+        services.spawn(
+            address_watcher
+                .clone()
+                .notify_loop(chain_store.clone(), mpool.subscribe_added()),
+        );
+
         let rpc_state_manager = Arc::clone(&state_manager);
         let rpc_chain_store = Arc::clone(&chain_store);
+        let rpc_peer_manager = Arc::clone(&peer_manager);
 
         let gc_event_tx = db_garbage_collector.get_tx();
+        let snapshot_server = config.snapshot_server.enabled.then(|| {
+            SnapshotServer::new(
+                config.snapshot_server.clone(),
+                config.client.data_dir.join("snapshots"),
+            )
+        });
+        let rpc_limits = config.rpc_limits.clone();
         services.spawn(async move {
             info!("JSON-RPC endpoint started at {}", config.client.rpc_address);
             // XXX: The JSON error message are a nightmare to print.
@@ -398,12 +649,19 @@ pub(super) async fn start(
                     // TODO: the RPCState can fetch this itself from the StateManager
                     beacon: rpc_state_manager.beacon_schedule(),
                     chain_store: rpc_chain_store,
+                    peer_manager: rpc_peer_manager,
                     new_mined_block_tx: tipset_sink,
                     gc_event_tx,
+                    config_event_tx,
+                    f3,
+                    address_watcher,
+                    miner_monitor,
+                    rpc_limits,
                 }),
                 rpc_listen,
                 FOREST_VERSION_STRING.as_str(),
                 shutdown_send,
+                snapshot_server,
             )
             .await
             .map_err(|err| anyhow::anyhow!("{:?}", serde_json::to_string(&err)))
@@ -427,11 +685,17 @@ pub(super) async fn start(
     fetch_snapshot_if_required(&mut config, epoch, opts.auto_download_snapshot).await?;
 
     if let Some(path) = &config.client.snapshot_path {
+        if !config.client.skip_load && !is_url(path) {
+            if let Err(e) = preflight_import(&config, path, false).await {
+                warn!("{e:#}");
+            }
+        }
         let stopwatch = time::Instant::now();
         import_chain::<_>(
             &state_manager,
             &path.display().to_string(),
             config.client.skip_load,
+            &import_write_config(&config),
         )
         .await
         .context("Failed miserably while importing chain from snapshot")?;
@@ -452,7 +716,10 @@ pub(super) async fn start(
         return Ok(());
     }
 
-    ensure_params_downloaded().await?;
+    // The proofs are only verified when executing the messages
+    if !config.sync.headers_only {
+        ensure_params_downloaded().await?;
+    }
     services.spawn(p2p_service.run());
 
     // blocking until any of the services returns an error,
@@ -462,6 +729,67 @@ pub(super) async fn start(
         .map(|_| {})
 }
 
+/// Settings of the writes of the imported records to the database
+fn import_write_config(config: &Config) -> BufferedWriteConfig {
+    let flush_interval = config.client.import_flush_interval;
+    BufferedWriteConfig {
+        capacity_bytes: config.client.import_buffer_size,
+        flush_interval: flush_interval.to_std().ok().filter(|d| !d.is_zero()),
+        journal: config
+            .client
+            .import_journal
+            .then(|| chain_path(config).join(IMPORT_JOURNAL_FILE_NAME)),
+    }
+}
+
+/// Estimates the number of records and the disk space the import of the
+/// snapshot at `path` takes, failing if the space isn't available. The report
+/// is printed on a dry run, and logged otherwise.
+async fn preflight_import(config: &Config, path: &Path, dry_run: bool) -> anyhow::Result<()> {
+    if is_url(path) {
+        bail!("only local snapshots can be checked, download the snapshot first");
+    }
+    let (mut records, mut db_size) = (0, 0);
+    for file in snapshot_files(&path.to_string_lossy())? {
+        let estimate = estimate_import(&file)
+            .await
+            .with_context(|| format!("failed to read the snapshot {}", file.display()))?;
+        if dry_run {
+            println!("{}:\n{estimate}\n", file.display());
+        }
+        records += estimate.records;
+        db_size += estimate.db_size;
+    }
+    let db_path = db_root(&chain_path(config));
+    let available = available_space(&db_path)?;
+    if dry_run {
+        println!(
+            "Available space in {}: {}",
+            db_path.display(),
+            available.human_count_bytes()
+        );
+    } else {
+        info!(
+            "Importing {records} records, about {} once in the database",
+            db_size.human_count_bytes()
+        );
+    }
+    if db_size > available {
+        bail!(
+            "the import needs about {} but only {} are available in {}",
+            db_size.human_count_bytes(),
+            available.human_count_bytes(),
+            db_path.display()
+        );
+    }
+    Ok(())
+}
+
+fn is_url(path: &Path) -> bool {
+    let path = path.to_string_lossy();
+    path.starts_with("http://") || path.starts_with("https://") || is_object_store_url(&path)
+}
+
 /// If our current chain is below a supported height, we need a snapshot to bring it up
 /// to a supported height. If we've not been given a snapshot by the user, get one.
 ///
@@ -569,8 +897,10 @@ async fn propagate_error(
     while !services.is_empty() {
         select! {
             option = services.join_next().fuse() => {
-                if let Some(Ok(Err(error_message))) = option {
-                    return Err(error_message)
+                match option {
+                    Some(Ok(Err(error_message))) => return Err(error_message),
+                    Some(Err(e)) if e.is_panic() => anyhow::bail!("service panicked: {e}"),
+                    _ => {}
                 }
             },
         }
@@ -578,6 +908,27 @@ async fn propagate_error(
     std::future::pending().await
 }
 
+/// Peers that are protected from pruning: the bootstrap peers and the
+/// explicitly configured ones.
+fn protected_peers(config: &Libp2pConfig) -> anyhow::Result<Vec<PeerId>> {
+    let mut peers: Vec<PeerId> = config
+        .bootstrap_peers
+        .iter()
+        .flat_map(|addr| addr.iter())
+        .filter_map(|protocol| match protocol {
+            Protocol::P2p(hash) => PeerId::from_multihash(hash).ok(),
+            _ => None,
+        })
+        .collect();
+    for peer in config.protected_peers.iter() {
+        peers.push(
+            peer.parse()
+                .with_context(|| format!("invalid protected peer id: {peer}"))?,
+        );
+    }
+    Ok(peers)
+}
+
 fn get_actual_chain_name(internal_network_name: &str) -> &str {
     match internal_network_name {
         "testnetnet" => "mainnet",
@@ -590,6 +941,9 @@ fn get_actual_chain_name(internal_network_name: &str) -> &str {
 /// - create a [`KeyStore`]
 /// - load a [`KeyStore`]
 /// - ask a user for password input
+/// - return a locked [`KeyStore`], if the passphrase is wrong or can't be
+///   prompted for. The node syncs but can't sign until `forest-cli wallet
+///   unlock`.
 async fn load_or_create_keystore(config: &Config) -> anyhow::Result<KeyStore> {
     use std::env::VarError;
 
@@ -616,11 +970,20 @@ async fn load_or_create_keystore(config: &Config) -> anyhow::Result<KeyStore> {
         }
 
         // need encryption, the user has provided the password through env
-        (true, Ok(passphrase)) => KeyStore::new(KeyStoreConfig::Encrypted(
+        (true, Ok(passphrase)) => match KeyStore::new(KeyStoreConfig::Encrypted(
             config.client.data_dir.clone(),
             passphrase,
-        ))
-        .map_err(anyhow::Error::new),
+        )) {
+            Ok(keystore) => Ok(keystore),
+            Err(e) if keystore_already_exists => {
+                warn!(
+                    "Couldn't unlock the keystore with the passphrase provided in {}: {e}",
+                    FOREST_KEYSTORE_PHRASE_ENV
+                );
+                locked_keystore(config)
+            }
+            Err(e) => Err(e).context("Couldn't create keystore"),
+        },
 
         // need encryption, we've not been given a password
         (true, Err(error)) => {
@@ -634,6 +997,14 @@ async fn load_or_create_keystore(config: &Config) -> anyhow::Result<KeyStore> {
                 )
             }
 
+            if !Term::stderr().is_term() {
+                warn!(
+                    "Cannot prompt for the keystore password from non-terminal, set {} to unlock the keystore on start",
+                    FOREST_KEYSTORE_PHRASE_ENV
+                );
+                return locked_keystore(config);
+            }
+
             let data_dir = config.client.data_dir.clone();
 
             match keystore_already_exists {
@@ -652,6 +1023,14 @@ async fn load_or_create_keystore(config: &Config) -> anyhow::Result<KeyStore> {
     }
 }
 
+fn locked_keystore(config: &Config) -> anyhow::Result<KeyStore> {
+    warn!(
+        "The keystore is locked, signing is disabled until `forest-cli wallet unlock`. The admin token is replaced by the one of the keystore on unlock."
+    );
+    KeyStore::new(KeyStoreConfig::Locked(config.client.data_dir.clone()))
+        .map_err(anyhow::Error::new)
+}
+
 /// Run the closure on a thread where blocking is allowed
 ///
 /// # Panics
@@ -728,7 +1107,9 @@ mod test {
     use crate::db::MemoryDB;
     use crate::networks::ChainConfig;
     use crate::shim::address::Address;
+    use fvm_ipld_blockstore::Blockstore;
     use tempfile::TempDir;
+    use tokio_util::compat::TokioAsyncReadCompatExt;
 
     use super::*;
 
@@ -746,6 +1127,44 @@ mod test {
             .unwrap()
     }
 
+    #[tokio::test]
+    async fn import_sharded_snapshot() {
+        let dir = TempDir::new().unwrap();
+        for file in ["chain4.car", "chain4.car.zst"] {
+            std::fs::copy(
+                Path::new("test-snapshots").join(file),
+                dir.path().join(file),
+            )
+            .unwrap();
+        }
+        import_snapshot_from_file(&dir.path().display().to_string())
+            .await
+            .unwrap();
+        import_snapshot_from_file(&dir.path().join("chain4.*").display().to_string())
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn import_shards_of_different_snapshots() {
+        let dir = TempDir::new().unwrap();
+        std::fs::copy("test-snapshots/chain4.car", dir.path().join("a.car")).unwrap();
+        std::fs::write(dir.path().join("b.car"), crate::genesis::EXPORT_SR_40).unwrap();
+        let db = MemoryDB::default();
+        import_snapshot_into(db.clone(), &dir.path().display().to_string())
+            .await
+            .unwrap_err();
+
+        // Nothing of the first shard is loaded
+        let file = tokio::fs::File::open(dir.path().join("a.car"))
+            .await
+            .unwrap();
+        let car = fvm_ipld_car::CarReader::new(file.compat()).await.unwrap();
+        for root in car.header.roots {
+            assert!(!db.has(&root).unwrap());
+        }
+    }
+
     #[tokio::test]
     async fn import_snapshot_from_file_invalid() {
         import_snapshot_from_file("Cargo.toml").await.unwrap_err();
@@ -764,7 +1183,10 @@ mod test {
     }
 
     async fn import_snapshot_from_file(file_path: &str) -> anyhow::Result<()> {
-        let db = MemoryDB::default();
+        import_snapshot_into(MemoryDB::default(), file_path).await
+    }
+
+    async fn import_snapshot_into(db: MemoryDB, file_path: &str) -> anyhow::Result<()> {
         let chain_config = Arc::new(ChainConfig::default());
 
         let genesis_header = BlockHeader::builder()
@@ -784,7 +1206,7 @@ mod test {
             chain_config,
             Arc::new(crate::interpreter::RewardActorMessageCalc),
         )?);
-        import_chain::<_>(&sm, file_path, false).await?;
+        import_chain::<_>(&sm, file_path, false, &Default::default()).await?;
         Ok(())
     }
 
@@ -809,7 +1231,7 @@ mod test {
             chain_config,
             Arc::new(crate::interpreter::RewardActorMessageCalc),
         )?);
-        import_chain::<_>(&sm, "test-snapshots/chain4.car", false)
+        import_chain::<_>(&sm, "test-snapshots/chain4.car", false, &Default::default())
             .await
             .context("Failed to import chain")?;
 
@@ -0,0 +1,107 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Orderly shutdown of the daemon, on `ctrl-c`, `SIGTERM` or the `Shutdown`
+//! RPC method. The services, the RPC server included, are stopped first. Then
+//! the state that only lives in memory is flushed and the database is closed,
+//! so that the next start doesn't have to recover it.
+
+use std::{sync::Arc, time::Duration};
+
+use crate::chain_sync::BadBlockCache;
+use crate::db::rolling::RollingDB;
+use crate::libp2p::PeerStore;
+use crate::message_pool::{MessagePool, MpoolRpcProvider};
+use crate::utils::db::wait_for_buffered_writes;
+use chrono::Utc;
+use log::{info, warn};
+use parking_lot::Mutex;
+
+/// Time given to the shutdown, after which the process exits anyway
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(20);
+
+/// Interval of the checks for the database handles still in use
+const DB_HANDLES_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Resources flushed on shutdown, registered as the daemon starts
+#[derive(Default)]
+pub struct ShutdownCoordinator {
+    db: Mutex<Option<RollingDB>>,
+    mpool: Mutex<Option<Arc<MessagePool<MpoolRpcProvider<RollingDB>>>>>,
+    bad_blocks: Mutex<Option<Arc<BadBlockCache>>>,
+    peer_store: Mutex<Option<Arc<Mutex<PeerStore>>>>,
+}
+
+impl ShutdownCoordinator {
+    pub fn set_db(&self, db: RollingDB) {
+        *self.db.lock() = Some(db);
+    }
+
+    pub fn set_mpool(&self, mpool: Arc<MessagePool<MpoolRpcProvider<RollingDB>>>) {
+        *self.mpool.lock() = Some(mpool);
+    }
+
+    pub fn set_bad_blocks(&self, bad_blocks: Arc<BadBlockCache>) {
+        *self.bad_blocks.lock() = Some(bad_blocks);
+    }
+
+    pub fn set_peer_store(&self, peer_store: Arc<Mutex<PeerStore>>) {
+        *self.peer_store.lock() = Some(peer_store);
+    }
+
+    /// Flushes the registered resources, once the services have been
+    /// stopped.
+    pub async fn shutdown(&self) {
+        info!("Flushing state before shutdown...");
+        match tokio::time::timeout(SHUTDOWN_TIMEOUT, self.flush()).await {
+            Ok(()) => info!("State flushed"),
+            Err(_) => warn!(
+                "State not flushed after {}s, the next start may have to recover the database",
+                SHUTDOWN_TIMEOUT.as_secs()
+            ),
+        }
+    }
+
+    async fn flush(&self) {
+        let db = self.db.lock().take();
+        let mpool = self.mpool.lock().take();
+        if let (Some(db), Some(mpool)) = (&db, mpool) {
+            match mpool.save_local(db) {
+                Ok(()) => info!("Saved the local messages of the message pool"),
+                Err(e) => warn!("Failed to save the local messages of the message pool: {e}"),
+            }
+        }
+
+        if let Some(bad_blocks) = self.bad_blocks.lock().take() {
+            bad_blocks.persist();
+        }
+
+        // The peers seen since the last periodic save
+        if let (Some(db), Some(peer_store)) = (&db, self.peer_store.lock().take()) {
+            match peer_store.lock().save(db, Utc::now().timestamp()) {
+                Ok(()) => info!("Saved the peer store"),
+                Err(e) => warn!("Failed to save the peer store: {e:#}"),
+            }
+        }
+
+        // The snapshot imports in progress lost their senders when the
+        // services were stopped
+        wait_for_buffered_writes().await;
+
+        if let Some(db) = db {
+            // The handles held by the stopped services, including the copies
+            // of the database spaces such as the one of the writer of a
+            // garbage collection, are released as the runtime drops their
+            // tasks, until this one is the last
+            while db.other_handle_count() > 0 {
+                tokio::time::sleep(DB_HANDLES_POLL_INTERVAL).await;
+            }
+            // Closing the database waits for its background writes
+            if let Err(e) = tokio::task::spawn_blocking(move || drop(db)).await {
+                warn!("Failed to close the database: {e}");
+            } else {
+                info!("Database closed");
+            }
+        }
+    }
+}
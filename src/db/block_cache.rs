@@ -0,0 +1,155 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use cid::Cid;
+use lru::LruCache;
+use parking_lot::Mutex;
+
+use crate::db::metrics::BLOCK_CACHE_SIZE_BYTES;
+use crate::metrics;
+
+/// A size-bounded, in-memory LRU cache of raw blocks, meant to sit in front of
+/// a persistent blockstore. Repeated reads of hot state-tree nodes (HAMT and
+/// AMT nodes in particular) during tipset validation are served from memory
+/// instead of hitting the disk.
+///
+/// The limit is expressed in bytes of cached block data. A limit of `0`
+/// disables the cache entirely.
+pub struct BlockCache {
+    max_size_bytes: usize,
+    inner: Mutex<BlockCacheInner>,
+}
+
+struct BlockCacheInner {
+    cache: LruCache<Cid, Vec<u8>>,
+    size_bytes: usize,
+}
+
+impl BlockCache {
+    pub fn new(max_size_bytes: usize) -> Self {
+        Self {
+            max_size_bytes,
+            inner: Mutex::new(BlockCacheInner {
+                cache: LruCache::unbounded(),
+                size_bytes: 0,
+            }),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.max_size_bytes > 0
+    }
+
+    /// Returns a copy of the cached block, recording a cache hit or miss.
+    pub fn get(&self, k: &Cid) -> Option<Vec<u8>> {
+        if !self.is_enabled() {
+            return None;
+        }
+        let value = self.inner.lock().cache.get(k).cloned();
+        let counter = if value.is_some() {
+            &metrics::LRU_CACHE_HIT
+        } else {
+            &metrics::LRU_CACHE_MISS
+        };
+        counter
+            .with_label_values(&[metrics::values::BLOCKSTORE])
+            .inc();
+        value
+    }
+
+    /// Returns `true` if the block is cached, without affecting its recency.
+    pub fn contains(&self, k: &Cid) -> bool {
+        self.is_enabled() && self.inner.lock().cache.contains(k)
+    }
+
+    /// Caches a block, evicting the least recently used blocks until the cache
+    /// fits into its size limit. Blocks larger than the limit are not cached.
+    pub fn put(&self, k: Cid, block: &[u8]) {
+        if !self.is_enabled() || block.len() > self.max_size_bytes {
+            return;
+        }
+        let mut inner = self.inner.lock();
+        if let Some(old) = inner.cache.put(k, block.to_vec()) {
+            inner.size_bytes -= old.len();
+        }
+        inner.size_bytes += block.len();
+        while inner.size_bytes > self.max_size_bytes {
+            match inner.cache.pop_lru() {
+                Some((_, evicted)) => inner.size_bytes -= evicted.len(),
+                None => break,
+            }
+        }
+        BLOCK_CACHE_SIZE_BYTES.set(inner.size_bytes as u64);
+    }
+
+    /// Drops all cached blocks.
+    pub fn clear(&self) {
+        let mut inner = self.inner.lock();
+        inner.cache.clear();
+        inner.size_bytes = 0;
+        BLOCK_CACHE_SIZE_BYTES.set(0);
+    }
+
+    pub fn size_in_bytes(&self) -> usize {
+        self.inner.lock().size_bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cid::multihash::{Code, MultihashDigest};
+
+    use super::*;
+
+    fn block(n: u8, len: usize) -> (Cid, Vec<u8>) {
+        let bytes = vec![n; len];
+        let cid = Cid::new_v1(fvm_ipld_encoding::DAG_CBOR, Code::Blake2b256.digest(&bytes));
+        (cid, bytes)
+    }
+
+    #[test]
+    fn get_after_put() {
+        let cache = BlockCache::new(1024);
+        let (cid, bytes) = block(0, 100);
+        assert_eq!(cache.get(&cid), None);
+        cache.put(cid, &bytes);
+        assert_eq!(cache.get(&cid), Some(bytes));
+        assert_eq!(cache.size_in_bytes(), 100);
+    }
+
+    #[test]
+    fn evicts_least_recently_used_within_size_limit() {
+        let cache = BlockCache::new(250);
+        let (a, a_bytes) = block(0, 100);
+        let (b, b_bytes) = block(1, 100);
+        let (c, c_bytes) = block(2, 100);
+        cache.put(a, &a_bytes);
+        cache.put(b, &b_bytes);
+        // Touch `a` so that `b` becomes the least recently used block.
+        assert!(cache.get(&a).is_some());
+        cache.put(c, &c_bytes);
+
+        assert!(cache.contains(&a));
+        assert!(!cache.contains(&b));
+        assert!(cache.contains(&c));
+        assert_eq!(cache.size_in_bytes(), 200);
+    }
+
+    #[test]
+    fn oversized_blocks_are_not_cached() {
+        let cache = BlockCache::new(50);
+        let (cid, bytes) = block(0, 100);
+        cache.put(cid, &bytes);
+        assert!(!cache.contains(&cid));
+        assert_eq!(cache.size_in_bytes(), 0);
+    }
+
+    #[test]
+    fn zero_size_disables_cache() {
+        let cache = BlockCache::new(0);
+        let (cid, bytes) = block(0, 0);
+        cache.put(cid, &bytes);
+        assert!(!cache.is_enabled());
+        assert_eq!(cache.get(&cid), None);
+    }
+}
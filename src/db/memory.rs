@@ -18,6 +18,17 @@ pub struct MemoryDB {
     db: Arc<RwLock<HashMap<Vec<u8>, Vec<u8>>>>,
 }
 
+impl MemoryDB {
+    /// Returns all the blocks of the store, in no particular order
+    pub fn blocks(&self) -> Result<Vec<(Cid, Vec<u8>)>> {
+        self.db
+            .read()
+            .iter()
+            .map(|(key, block)| Ok((Cid::try_from(key.as_slice())?, block.clone())))
+            .collect()
+    }
+}
+
 impl Store for MemoryDB {
     fn write<K, V>(&self, key: K, value: V) -> Result<(), Error>
     where
@@ -55,6 +66,40 @@ impl Blockstore for MemoryDB {
     }
 }
 
+/// Blockstore reading from an underlying store and keeping the blocks written
+/// in memory, for the states computed without being persisted.
+#[derive(Clone)]
+pub struct MemoryOverlay<DB> {
+    base: DB,
+    written: MemoryDB,
+}
+
+impl<DB> MemoryOverlay<DB> {
+    pub fn new(base: DB) -> Self {
+        Self {
+            base,
+            written: MemoryDB::default(),
+        }
+    }
+}
+
+impl<DB: Blockstore> Blockstore for MemoryOverlay<DB> {
+    fn get(&self, k: &Cid) -> Result<Option<Vec<u8>>> {
+        match self.written.get(k)? {
+            Some(block) => Ok(Some(block)),
+            None => self.base.get(k),
+        }
+    }
+
+    fn has(&self, k: &Cid) -> Result<bool> {
+        Ok(self.written.has(k)? || self.base.has(k)?)
+    }
+
+    fn put_keyed(&self, k: &Cid, block: &[u8]) -> Result<()> {
+        self.written.put_keyed(k, block)
+    }
+}
+
 impl BitswapStoreRead for MemoryDB {
     fn contains(&self, cid: &Cid) -> Result<bool> {
         Ok(self.exists(cid.to_bytes())?)
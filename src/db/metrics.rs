@@ -2,7 +2,10 @@
 // SPDX-License-Identifier: Apache-2.0, MIT
 
 use lazy_static::lazy_static;
-use prometheus::{Histogram, HistogramOpts};
+use prometheus::{
+    core::{AtomicU64, GenericGauge},
+    Histogram, HistogramOpts,
+};
 
 lazy_static! {
     pub static ref BLOCK_SIZE_BYTES: Box<Histogram> = {
@@ -21,4 +24,17 @@ lazy_static! {
             .expect("Registering the block_size_bytes metric with the metrics registry must succeed");
         block_size
     };
+    pub static ref BLOCK_CACHE_SIZE_BYTES: Box<GenericGauge<AtomicU64>> = {
+        let block_cache_size = Box::new(
+            GenericGauge::<AtomicU64>::new(
+                "block_cache_size_bytes",
+                "Total size of the blocks held in the in-memory blockstore cache",
+            )
+            .expect("Defining the block_cache_size_bytes metric must succeed"),
+        );
+        prometheus::default_registry()
+            .register(block_cache_size.clone())
+            .expect("Registering the block_cache_size_bytes metric with the metrics registry must succeed");
+        block_cache_size
+    };
 }
@@ -1,13 +1,14 @@
 // Copyright 2019-2023 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
+pub mod block_cache;
 mod errors;
 mod memory;
 mod metrics;
 pub mod parity_db;
 pub mod parity_db_config;
 pub use errors::Error;
-pub use memory::MemoryDB;
+pub use memory::{MemoryDB, MemoryOverlay};
 
 pub mod rolling;
 
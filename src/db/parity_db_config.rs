@@ -9,6 +9,9 @@ use serde::{Deserialize, Serialize};
 pub struct ParityDbConfig {
     pub enable_statistics: bool,
     pub compression_type: String,
+    /// Maximum size, in bytes, of the in-memory cache of hot blocks kept in
+    /// front of the database. Set to `0` to disable the cache.
+    pub block_cache_size: usize,
 }
 
 impl Default for ParityDbConfig {
@@ -16,6 +19,7 @@ impl Default for ParityDbConfig {
         Self {
             enable_statistics: false,
             compression_type: "lz4".into(),
+            block_cache_size: 256 * 1024 * 1024,
         }
     }
 }
@@ -40,8 +40,10 @@
 //!
 //! ## Scheduling
 //! 1. GC is triggered automatically when total DB size is greater than `2x` of
-//! the last reachable data size
-//! 2. GC can be triggered manually by `forest-cli db gc` command
+//! the last reachable data size, checked every `client.gc_interval`
+//! 2. GC can be triggered manually by `forest-cli db gc` command, or by
+//! `forest-cli chain prune` which keeps a given number of recent state roots
+//! and can measure the reclaimable space without deleting anything
 //! 3. There's a global GC lock to ensure at most one GC job is running
 //!
 //! ## Performance
@@ -74,7 +76,11 @@ use std::{
 
 use crate::blocks::Tipset;
 use crate::ipld::util::*;
-use crate::utils::db::{file_backed_obj::ChainMeta, BlockstoreBufferedWriteExt, DB_KEY_BYTES};
+use crate::shim::clock::ChainEpoch;
+use crate::utils::db::{
+    file_backed_obj::ChainMeta, BlockstoreBufferedWriteExt, BufferedWriteConfig, DB_KEY_BYTES,
+};
+use crate::utils::operations::{self, OperationKind};
 use chrono::Utc;
 use fvm_ipld_blockstore::Blockstore;
 use human_repr::HumanCount;
@@ -82,24 +88,59 @@ use tokio::sync::Mutex;
 
 use super::*;
 
-pub struct DbGarbageCollector<F>
+/// Request to run a garbage collection, answered on `responder`
+pub struct GcRequest {
+    /// Number of recent state roots to keep, the configured one if unset
+    pub recent_state_roots: Option<i64>,
+    /// Only measure the reachable data, without deleting anything
+    pub dry_run: bool,
+    pub responder: flume::Sender<anyhow::Result<GcReport>>,
+}
+
+/// Sizes measured by a garbage collection
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct GcReport {
+    /// Size of the database before the collection
+    pub total_bytes: u64,
+    /// Size of the data reachable from the head, which is kept
+    pub reachable_bytes: u64,
+    /// Size of the database after the collection, measured once the old
+    /// database space is deleted. On a dry run, the size of the current
+    /// database space and of the reachable data it lacks, which would be
+    /// copied to it.
+    pub remaining_bytes: u64,
+}
+
+impl GcReport {
+    /// Space freed by the collection, or that would be on a dry run
+    pub fn reclaimed_bytes(&self) -> u64 {
+        self.total_bytes.saturating_sub(self.remaining_bytes)
+    }
+}
+
+pub struct DbGarbageCollector<F, G>
 where
     F: Fn() -> Tipset + Send + Sync + 'static,
+    G: Fn() -> Option<ChainEpoch> + Send + Sync + 'static,
 {
     db: RollingDB,
     file_backed_chain_meta: Arc<parking_lot::Mutex<FileBacked<ChainMeta>>>,
     get_tipset: F,
+    get_finalized_epoch: G,
     chain_finality: i64,
     recent_state_roots: i64,
     lock: Mutex<()>,
-    gc_tx: flume::Sender<flume::Sender<anyhow::Result<()>>>,
-    gc_rx: flume::Receiver<flume::Sender<anyhow::Result<()>>>,
+    gc_tx: flume::Sender<GcRequest>,
+    gc_rx: flume::Receiver<GcRequest>,
     last_reachable_bytes: AtomicU64,
+    interval_secs: AtomicU64,
 }
 
-impl<F> DbGarbageCollector<F>
+impl<F, G> DbGarbageCollector<F, G>
 where
     F: Fn() -> Tipset + Send + Sync + 'static,
+    G: Fn() -> Option<ChainEpoch> + Send + Sync + 'static,
 {
     pub fn new(
         db: RollingDB,
@@ -107,6 +148,8 @@ where
         chain_finality: i64,
         recent_state_roots: i64,
         get_tipset: F,
+        get_finalized_epoch: G,
+        interval: Duration,
     ) -> Self {
         let (gc_tx, gc_rx) = flume::unbounded();
 
@@ -114,16 +157,24 @@ where
             db,
             file_backed_chain_meta,
             get_tipset,
+            get_finalized_epoch,
             chain_finality,
             recent_state_roots,
             lock: Default::default(),
             gc_tx,
             gc_rx,
             last_reachable_bytes: AtomicU64::new(0),
+            interval_secs: AtomicU64::new(interval.as_secs()),
         }
     }
 
-    pub fn get_tx(&self) -> flume::Sender<flume::Sender<anyhow::Result<()>>> {
+    /// Sets the interval of the size checks, effective after the pending one.
+    pub fn set_interval(&self, interval: Duration) {
+        self.interval_secs
+            .store(interval.as_secs(), atomic::Ordering::Relaxed);
+    }
+
+    pub fn get_tx(&self) -> flume::Sender<GcRequest> {
         self.gc_tx.clone()
     }
 
@@ -132,8 +183,10 @@ where
     pub async fn collect_loop_passive(&self) -> anyhow::Result<()> {
         info!("Running automatic database garbage collection task");
         loop {
-            // Check every 10 mins
-            tokio::time::sleep(Duration::from_secs(10 * 60)).await;
+            tokio::time::sleep(Duration::from_secs(
+                self.interval_secs.load(atomic::Ordering::Relaxed),
+            ))
+            .await;
 
             // Bypass size checking during import
             let tipset = (self.get_tipset)();
@@ -161,7 +214,7 @@ where
                 };
 
                 if should_collect {
-                    if let Err(err) = self.collect_once().await {
+                    if let Err(err) = self.collect_once(self.recent_state_roots, false).await {
                         warn!("Garbage collection failed: {err}");
                     }
                 }
@@ -169,14 +222,20 @@ where
         }
     }
 
-    /// This loop listens on events emitted by `forest-cli db gc` and triggers
-    /// `collect_once`
+    /// This loop listens on events emitted by `forest-cli db gc` and
+    /// `forest-cli chain prune` and triggers `collect_once`
     pub async fn collect_loop_event(self: &Arc<Self>) -> anyhow::Result<()> {
         info!("Listening on database garbage collection events");
-        while let Ok(responder) = self.gc_rx.recv_async().await {
+        while let Ok(GcRequest {
+            recent_state_roots,
+            dry_run,
+            responder,
+        }) = self.gc_rx.recv_async().await
+        {
             let this = self.clone();
             tokio::spawn(async move {
-                let result = this.collect_once().await;
+                let recent_state_roots = recent_state_roots.unwrap_or(this.recent_state_roots);
+                let result = this.collect_once(recent_state_roots, dry_run).await;
                 if let Err(e) = responder.send(result) {
                     warn!("{e}");
                 }
@@ -186,6 +245,22 @@ where
         Ok(())
     }
 
+    /// Answers the events emitted by `forest-cli db gc` and
+    /// `forest-cli chain prune` with an error, for archival nodes which keep
+    /// all their data
+    pub async fn reject_loop_event(&self) -> anyhow::Result<()> {
+        while let Ok(GcRequest { responder, .. }) = self.gc_rx.recv_async().await {
+            let result = Err(anyhow::anyhow!(
+                "Garbage collection is disabled on archival nodes"
+            ));
+            if let Err(e) = responder.send(result) {
+                warn!("{e}");
+            }
+        }
+
+        Ok(())
+    }
+
     /// ## GC workflow
     /// 1. Walk back from the current heaviest tipset to the genesis block,
     /// collect all the blocks that are reachable from the snapshot
@@ -204,11 +279,38 @@ where
     /// the old database space that will be deleted at the end of garbage
     /// collection only contains immutable or finalized part of the chain,
     /// from which all block data that is marked as unreachable will not
-    /// become reachable because of the chain being mutated later.
-    async fn collect_once(&self) -> anyhow::Result<()> {
+    /// become reachable because of the chain being mutated later. A
+    /// checkpoint makes the chain below it immutable right away.
+    ///
+    /// On a dry run, the reachable data is only measured, whether the old
+    /// database space can be deleted yet or not.
+    async fn collect_once(
+        &self,
+        recent_state_roots: i64,
+        dry_run: bool,
+    ) -> anyhow::Result<GcReport> {
+        // Cancelling drops the collection before the old database space is
+        // deleted, leaving the blocks already written to the current one.
+        operations::run(
+            OperationKind::DatabaseGarbageCollection,
+            if dry_run {
+                "database garbage collection (dry run)"
+            } else {
+                "database garbage collection"
+            },
+            Some(WALK_SNAPSHOT_PROGRESS_DB_GC.clone()),
+            |_| self.collect(recent_state_roots, dry_run),
+        )
+        .await
+    }
+
+    async fn collect(&self, recent_state_roots: i64, dry_run: bool) -> anyhow::Result<GcReport> {
         let tipset = (self.get_tipset)();
 
-        if self.db.current_creation_epoch() + self.chain_finality >= tipset.epoch() {
+        let creation_epoch = self.db.current_creation_epoch();
+        let checkpointed = (self.get_finalized_epoch)()
+            .map_or(false, |finalized_epoch| creation_epoch < finalized_epoch);
+        if !dry_run && !checkpointed && creation_epoch + self.chain_finality >= tipset.epoch() {
             anyhow::bail!("Cancelling GC: the old DB space contains unfinalized chain parts");
         }
 
@@ -218,16 +320,30 @@ where
         }
 
         let start = Utc::now();
+        let total_bytes = self.db.total_size_in_bytes()?;
+        let current_bytes = self.db.current_size_in_bytes()?;
         let reachable_bytes = Arc::new(AtomicUsize::new(0));
+        // Reachable data missing from the current database space
+        let copied_bytes = Arc::new(AtomicUsize::new(0));
 
-        info!("Garbage collection started at epoch {}", tipset.epoch());
+        info!(
+            "Garbage collection started at epoch {}{}",
+            tipset.epoch(),
+            if dry_run { " (dry run)" } else { "" }
+        );
         let db = &self.db;
         // 128MB
         const BUFFER_CAPCITY_BYTES: usize = 128 * 1024 * 1024;
         let (tx, rx) = flume::bounded(100);
-        let write_task = tokio::spawn({
+        let write_task = (!dry_run).then(|| {
             let db = db.current();
-            async move { db.buffered_write(rx, BUFFER_CAPCITY_BYTES).await }
+            tokio::spawn(async move {
+                db.buffered_write(
+                    rx,
+                    &BufferedWriteConfig::with_capacity(BUFFER_CAPCITY_BYTES),
+                )
+                .await
+            })
         });
         let estimated_reachable_records = Some(
             self.file_backed_chain_meta
@@ -237,21 +353,24 @@ where
         );
         let n_records = walk_snapshot(
             &tipset,
-            self.recent_state_roots,
+            recent_state_roots,
             |cid| {
                 let db = db.clone();
                 let tx = tx.clone();
                 let reachable_bytes = reachable_bytes.clone();
+                let copied_bytes = copied_bytes.clone();
                 async move {
                     let block = db
                         .get(&cid)?
                         .ok_or_else(|| anyhow::anyhow!("Cid {cid} not found in blockstore"))?;
 
-                    let pair = (cid, block.clone());
-                    reachable_bytes
-                        .fetch_add(DB_KEY_BYTES + pair.1.len(), atomic::Ordering::Relaxed);
+                    let record_bytes = DB_KEY_BYTES + block.len();
+                    reachable_bytes.fetch_add(record_bytes, atomic::Ordering::Relaxed);
                     if !db.current().has(&cid)? {
-                        tx.send_async(pair).await?;
+                        copied_bytes.fetch_add(record_bytes, atomic::Ordering::Relaxed);
+                        if !dry_run {
+                            tx.send_async((cid, block.clone())).await?;
+                        }
                     }
 
                     Ok(block)
@@ -264,6 +383,22 @@ where
         .await?;
         drop(tx);
 
+        let mut report = GcReport {
+            total_bytes,
+            reachable_bytes: reachable_bytes.load(atomic::Ordering::Relaxed) as _,
+            remaining_bytes: current_bytes + copied_bytes.load(atomic::Ordering::Relaxed) as u64,
+        };
+        let Some(write_task) = write_task else {
+            info!(
+                "Garbage collection dry run finished at epoch {}, took {}s, reachable data size: {}, reclaimable: {}",
+                tipset.epoch(),
+                (Utc::now() - start).num_seconds(),
+                report.reachable_bytes.human_count_bytes(),
+                report.reclaimed_bytes().human_count_bytes(),
+            );
+            return Ok(report);
+        };
+
         {
             let mut meta = self.file_backed_chain_meta.lock();
             meta.inner_mut().estimated_reachable_records = n_records;
@@ -272,20 +407,20 @@ where
 
         write_task.await??;
 
-        let reachable_bytes = reachable_bytes.load(atomic::Ordering::Relaxed);
         self.last_reachable_bytes
-            .store(reachable_bytes as _, atomic::Ordering::Relaxed);
+            .store(report.reachable_bytes, atomic::Ordering::Relaxed);
         info!(
             "Garbage collection finished at epoch {}, took {}s, reachable data size: {}",
             tipset.epoch(),
             (Utc::now() - start).num_seconds(),
-            reachable_bytes.human_count_bytes(),
+            report.reachable_bytes.human_count_bytes(),
         );
 
         // Use the latest head here
         self.db.next_current((self.get_tipset)().epoch())?;
+        report.remaining_bytes = self.db.total_size_in_bytes()?;
 
-        Ok(())
+        Ok(report)
     }
 }
 
@@ -14,6 +14,9 @@ use crate::db::*;
 
 impl Blockstore for RollingDB {
     fn has(&self, k: &Cid) -> anyhow::Result<bool> {
+        if self.cache.contains(k) {
+            return Ok(true);
+        }
         for db in self.db_queue().iter() {
             if Blockstore::has(db, k)? {
                 return Ok(true);
@@ -24,8 +27,15 @@ impl Blockstore for RollingDB {
     }
 
     fn get(&self, k: &Cid) -> anyhow::Result<Option<Vec<u8>>> {
-        for db in self.db_queue().iter() {
+        if let Some(v) = self.cache.get(k) {
+            return Ok(Some(v));
+        }
+        // The old space is locked until the block is cached, so that a
+        // rotation only clears the cache once the block is in it
+        let old = self.old.read();
+        for db in [&self.current(), &*old] {
             if let Some(v) = Blockstore::get(db, k)? {
+                self.cache.put(*k, &v);
                 return Ok(Some(v));
             }
         }
@@ -163,6 +173,7 @@ impl RollingDB {
             std::fs::create_dir_all(db_root.as_path())?;
         }
         let (db_index, current, old) = load_dbs(&db_root, &db_config)?;
+        let cache = BlockCache::new(db_config.block_cache_size);
 
         Ok(Self {
             db_root: db_root.into(),
@@ -170,6 +181,7 @@ impl RollingDB {
             db_index: RwLock::new(db_index).into(),
             current: RwLock::new(current).into(),
             old: RwLock::new(old).into(),
+            cache: cache.into(),
         })
     }
 
@@ -179,8 +191,10 @@ impl RollingDB {
         let new_db_name = Uuid::new_v4().simple().to_string();
         info!("Setting {new_db_name} as current db");
         let db = open_db(&self.db_root.join(&new_db_name), &self.db_config)?;
-        *self.old.write() = self.current.read().clone();
-        *self.current.write() = db;
+        // The old space is locked, before the current one as everywhere else,
+        // until the cache is cleared
+        let mut old = self.old.write();
+        *old = std::mem::replace(&mut *self.current.write(), db);
         let mut db_index = self.db_index.write();
         let db_index_inner_mut = db_index.inner_mut();
         let old_db_path = self.db_root.join(&db_index_inner_mut.old);
@@ -189,6 +203,10 @@ impl RollingDB {
         db_index_inner_mut.current_creation_epoch = current_epoch;
         db_index.sync()?;
         delete_db(&old_db_path);
+        // Blocks that only lived in the deleted DB must not be served from
+        // memory. The readers that found them have cached them by now.
+        self.cache.clear();
+        drop(old);
 
         Ok(())
     }
@@ -209,12 +227,22 @@ impl RollingDB {
         )?)
     }
 
+    /// Number of the other handles to the database, this one excluded: the
+    /// clones of the rolling database, and the copies of its spaces taken
+    /// through [`RollingDB::current`], which keep them open on their own. The
+    /// database is closed when the last handle is dropped.
+    pub fn other_handle_count(&self) -> usize {
+        let space_copies = |space: &RwLock<Db>| Arc::strong_count(&space.read().db) - 1;
+        Arc::strong_count(&self.current) - 1 + space_copies(&self.current) + space_copies(&self.old)
+    }
+
     pub fn current(&self) -> Db {
         self.current.read().clone()
     }
 
     fn db_queue(&self) -> [Db; 2] {
-        [self.current.read().clone(), self.old.read().clone()]
+        let old = self.old.read().clone();
+        [self.current(), old]
     }
 }
 
@@ -326,4 +354,36 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn deleted_blocks_are_not_cached() -> Result<()> {
+        let db_root = TempDir::new()?;
+        let rolling_db = RollingDB::load_or_create(db_root.path().into(), Default::default())?;
+        let block = b"block".to_vec();
+        let cid = Cid::new_v0(cid::multihash::Code::Sha2_256.digest(&block))?;
+        rolling_db.put_keyed(&cid, &block)?;
+        rolling_db.next_current(0)?;
+        // Cached from the old space
+        ensure!(Blockstore::get(&rolling_db, &cid)? == Some(block));
+        rolling_db.next_current(0)?;
+        ensure!(!Blockstore::has(&rolling_db, &cid)?);
+        ensure!(Blockstore::get(&rolling_db, &cid)?.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn other_handles_are_counted() -> Result<()> {
+        let db_root = TempDir::new()?;
+        let rolling_db = RollingDB::load_or_create(db_root.path().into(), Default::default())?;
+        ensure!(rolling_db.other_handle_count() == 0);
+        let other = rolling_db.clone();
+        ensure!(rolling_db.other_handle_count() == 1);
+        drop(other);
+        ensure!(rolling_db.other_handle_count() == 0);
+        let current = rolling_db.current();
+        ensure!(rolling_db.other_handle_count() == 1);
+        drop(current);
+        ensure!(rolling_db.other_handle_count() == 0);
+        Ok(())
+    }
 }
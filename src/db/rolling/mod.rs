@@ -25,7 +25,10 @@ use log::{info, warn};
 use parking_lot::RwLock;
 use serde::{Deserialize, Serialize};
 
-use crate::db::db_engine::{open_db, Db, DbConfig};
+use crate::db::{
+    block_cache::BlockCache,
+    db_engine::{open_db, Db, DbConfig},
+};
 
 /// This DB wrapper is specially designed for supporting the concurrent,
 /// semi-space GC algorithm that is implemented in [`DbGarbageCollector`],
@@ -41,6 +44,8 @@ pub struct RollingDB {
     current: Arc<RwLock<Db>>,
     /// The old writable DB
     old: Arc<RwLock<Db>>,
+    /// In-memory cache of recently read blocks, shared by both DB spaces
+    cache: Arc<BlockCache>,
 }
 
 #[derive(Debug, Default, Serialize, Deserialize)]
@@ -34,3 +34,18 @@ fn mem_db_bulk_write() {
     let db = MemoryDB::default();
     subtests::bulk_write(&db);
 }
+
+#[test]
+fn mem_overlay_keeps_writes_in_memory() {
+    use crate::db::MemoryOverlay;
+    use crate::utils::db::CborStoreExt;
+    use fvm_ipld_blockstore::Blockstore;
+
+    let base = MemoryDB::default();
+    let read = base.put_cbor_default(&"base").unwrap();
+    let overlay = MemoryOverlay::new(base.clone());
+    let written = overlay.put_cbor_default(&"overlay").unwrap();
+    assert!(overlay.has(&read).unwrap());
+    assert!(overlay.has(&written).unwrap());
+    assert!(!base.has(&written).unwrap());
+}
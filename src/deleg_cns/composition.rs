@@ -4,8 +4,10 @@ use std::sync::Arc;
 
 use crate::chain_sync::consensus::{MessagePoolApi, Proposer, SyncGossipSubmitter};
 use crate::key_management::KeyStore;
+use crate::shim::address::Address;
 use crate::shim::econ::TokenAmount;
 use crate::state_manager::StateManager;
+use anyhow::ensure;
 use fvm_ipld_blockstore::Blockstore;
 use log::info;
 use tokio::{sync::RwLock, task::JoinSet};
@@ -27,6 +29,7 @@ pub async fn consensus<DB, MP>(
     state_manager: &Arc<StateManager<DB>>,
     keystore: &Arc<RwLock<KeyStore>>,
     mpool: &Arc<MP>,
+    miner: Option<Address>,
     submitter: SyncGossipSubmitter,
     services: &mut JoinSet<anyhow::Result<()>>,
 ) -> anyhow::Result<FullConsensus>
@@ -35,7 +38,19 @@ where
     MP: MessagePoolApi + Send + Sync + 'static,
 {
     let consensus = DelegatedConsensus::default();
-    if let Some(proposer) = consensus.proposer(keystore, state_manager).await.unwrap() {
+    let proposer = consensus.proposer(keystore, state_manager).await?;
+    if let Some(miner) = miner {
+        ensure!(
+            &miner == consensus.chosen_one(),
+            "only the chosen miner {} produces blocks with delegated consensus",
+            consensus.chosen_one()
+        );
+        ensure!(
+            proposer.is_some(),
+            "the key of the worker of miner {miner} is not in the keystore"
+        );
+    }
+    if let Some(proposer) = proposer {
         info!("Starting the delegated consensus proposer...");
         let sm = state_manager.clone();
         let mp = mpool.clone();
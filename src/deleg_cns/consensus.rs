@@ -2,7 +2,7 @@
 // SPDX-License-Identifier: Apache-2.0, MIT
 use std::{fmt::Debug, str::FromStr, sync::Arc};
 
-use crate::blocks::{Block, Tipset};
+use crate::blocks::{timing::TimingError, Block, BlockHeader, Tipset};
 use crate::chain::{Error as ChainStoreError, Scale, Weight};
 use crate::chain_sync::consensus::Consensus;
 use crate::key_management::KeyStore;
@@ -26,8 +26,8 @@ pub enum DelegatedConsensusError {
     BlockWithElectionProof,
     #[error("Block must not have a ticket")]
     BlockWithTicket,
-    #[error("Block had the wrong timestamp: {0} != {1}")]
-    UnequalBlockTimestamps(u64, u64),
+    #[error(transparent)]
+    BlockTiming(#[from] TimingError),
     #[error("Miner isn't eligible to mine: expected {0}; found {1}")]
     MinerNotEligibleToMine(Address, Address),
     #[error("Unknown miner: {0}")]
@@ -85,6 +85,11 @@ impl DelegatedConsensus {
         Self { chosen_one }
     }
 
+    /// Address of the only miner eligible to propose blocks
+    pub fn chosen_one(&self) -> &Address {
+        &self.chosen_one
+    }
+
     /// Create an instance of the proposer on the node
     /// which has the private key to sign blocks.
     ///
@@ -148,4 +153,20 @@ impl Consensus for DelegatedConsensus {
             .await
             .map_err(NonEmpty::new)
     }
+
+    async fn validate_block_header<DB>(
+        &self,
+        state_manager: Arc<StateManager<DB>>,
+        header: Arc<BlockHeader>,
+    ) -> Result<(), NonEmpty<Self::Error>>
+    where
+        DB: Blockstore + Clone + Sync + Send + 'static,
+    {
+        crate::deleg_cns::validation::validate_block_header(
+            &self.chosen_one,
+            state_manager,
+            &header,
+        )
+        .map_err(NonEmpty::new)
+    }
 }
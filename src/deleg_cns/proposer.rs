@@ -4,7 +4,7 @@
 use core::time::Duration;
 use std::sync::Arc;
 
-use crate::blocks::{BlockHeader, GossipBlock, Tipset};
+use crate::blocks::{timing::BlockTiming, BlockHeader, GossipBlock, Tipset};
 use crate::chain::Scale;
 use crate::chain_sync::consensus::{MessagePoolApi, Proposer, SyncGossipSubmitter};
 use crate::key_management::Key;
@@ -53,7 +53,7 @@ impl DelegatedProposer {
     where
         DB: Blockstore + Clone + Sync + Send + 'static,
     {
-        let block_delay = state_manager.chain_config().block_delay_secs;
+        let timing = BlockTiming::from(state_manager.chain_config().as_ref());
         let smoke_height = state_manager.chain_config().epoch(Height::Smoke);
 
         let (parent_state_root, parent_receipts) = state_manager.tipset_state(base).await?;
@@ -73,7 +73,7 @@ impl DelegatedProposer {
             .parent_base_fee(parent_base_fee)
             .parents(base.key().clone())
             .epoch(base.epoch() + 1)
-            .timestamp(base.min_timestamp() + block_delay)
+            .timestamp(timing.timestamp(base, base.epoch() + 1)?)
             .state_root(parent_state_root)
             .message_receipts(parent_receipts)
             .build()?;
@@ -3,7 +3,7 @@
 
 use std::sync::Arc;
 
-use crate::blocks::{Block, BlockHeader, Tipset};
+use crate::blocks::{timing::BlockTiming, Block, BlockHeader, Tipset};
 use crate::networks::ChainConfig;
 use crate::shim::address::Address;
 use crate::state_manager::StateManager;
@@ -49,6 +49,37 @@ pub(in crate::deleg_cns) async fn validate_block<DB: Blockstore + Clone + Sync +
     Ok(())
 }
 
+/// Validates the block header for the nodes syncing the headers only: the
+/// sanity checks, the timestamps and the miner. The parts of the parent state
+/// resolving the miner address are expected to be fetched by the caller.
+pub(in crate::deleg_cns) fn validate_block_header<DB>(
+    chosen_one: &Address,
+    state_manager: Arc<StateManager<DB>>,
+    header: &BlockHeader,
+) -> Result<(), Box<DelegatedConsensusError>>
+where
+    DB: Blockstore + Clone + Sync + Send + 'static,
+{
+    block_sanity_checks(header)?;
+
+    let base_tipset = state_manager
+        .chain_store()
+        .tipset_from_keys(header.parents())?;
+
+    block_timestamp_checks(
+        header,
+        base_tipset.as_ref(),
+        state_manager.chain_config().as_ref(),
+    )?;
+
+    validate_miner(
+        header,
+        base_tipset.as_ref(),
+        state_manager.as_ref(),
+        chosen_one,
+    )
+}
+
 /// Checks optional values in header.
 ///
 /// In particular it looks for an election proof and a ticket,
@@ -72,17 +103,9 @@ fn block_timestamp_checks(
     base_tipset: &Tipset,
     chain_config: &ChainConfig,
 ) -> Result<(), Box<DelegatedConsensusError>> {
-    // Timestamp checks
-    let block_delay = chain_config.block_delay_secs;
-    let nulls = (header.epoch() - (base_tipset.epoch() + 1)) as u64;
-    let target_timestamp = base_tipset.min_timestamp() + block_delay * (nulls + 1);
-    if target_timestamp != header.timestamp() {
-        return Err(Box::new(DelegatedConsensusError::UnequalBlockTimestamps(
-            header.timestamp(),
-            target_timestamp,
-        )));
-    }
-    Ok(())
+    BlockTiming::from(chain_config)
+        .check_timestamp(header, base_tipset)
+        .map_err(|e| Box::new(e.into()))
 }
 
 /// Check that the miner who produced the block is the one we delegated to.
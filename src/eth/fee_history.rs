@@ -0,0 +1,223 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Fee history of the chain in the format of `eth_feeHistory`, where blocks
+//! are tipsets and amounts are hexadecimal quantities of attoFIL.
+
+use std::{fmt, str::FromStr};
+
+use crate::chain::FeeHistory;
+use crate::shim::clock::ChainEpoch;
+use crate::shim::econ::TokenAmount;
+use anyhow::{bail, Context};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// Block number, or tag standing for one, as accepted by the Ethereum methods
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EthBlockNumber {
+    Earliest,
+    /// The head of the chain. `pending`, `safe` and `finalized` stand for it
+    /// too.
+    Latest,
+    Number(ChainEpoch),
+}
+
+impl FromStr for EthBlockNumber {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "earliest" => Ok(Self::Earliest),
+            "latest" | "pending" | "safe" | "finalized" => Ok(Self::Latest),
+            _ => Ok(Self::Number(parse_hex(s)?)),
+        }
+    }
+}
+
+impl fmt::Display for EthBlockNumber {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Earliest => write!(f, "earliest"),
+            Self::Latest => write!(f, "latest"),
+            Self::Number(n) => write!(f, "{n:#x}"),
+        }
+    }
+}
+
+impl Serialize for EthBlockNumber {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.to_string().serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for EthBlockNumber {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// Unsigned quantity, formatted in hexadecimal. Plain numbers are accepted as
+/// well.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct EthUint64(pub u64);
+
+impl fmt::Display for EthUint64 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:#x}", self.0)
+    }
+}
+
+impl Serialize for EthUint64 {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.to_string().serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for EthUint64 {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Quantity {
+            Number(u64),
+            Hex(String),
+        }
+        match Quantity::deserialize(deserializer)? {
+            Quantity::Number(n) => Ok(Self(n)),
+            Quantity::Hex(s) => parse_hex(&s).map(Self).map_err(serde::de::Error::custom),
+        }
+    }
+}
+
+fn parse_hex<T: num_traits::Num>(s: &str) -> anyhow::Result<T> {
+    let digits = s
+        .strip_prefix("0x")
+        .with_context(|| format!("{s} is missing the 0x prefix"))?;
+    match T::from_str_radix(digits, 16) {
+        Ok(n) => Ok(n),
+        Err(_) => bail!("{s} is not a hexadecimal quantity"),
+    }
+}
+
+fn hex_amount(amount: &TokenAmount) -> String {
+    format!("{:#x}", amount.atto())
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EthFeeHistory {
+    pub oldest_block: EthUint64,
+    /// Base fees of the blocks, followed by the one of the next block
+    pub base_fee_per_gas: Vec<String>,
+    pub gas_used_ratio: Vec<f64>,
+    /// Premiums at the requested percentiles, absent if none were
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reward: Option<Vec<Vec<String>>>,
+}
+
+impl EthFeeHistory {
+    pub fn new(history: &FeeHistory, with_rewards: bool) -> Self {
+        let oldest_block = history.tipsets.first().map_or(0, |fees| fees.epoch);
+        Self {
+            oldest_block: EthUint64(oldest_block as u64),
+            base_fee_per_gas: history
+                .tipsets
+                .iter()
+                .map(|fees| &fees.base_fee)
+                .chain(std::iter::once(&history.next_base_fee))
+                .map(hex_amount)
+                .collect(),
+            gas_used_ratio: history
+                .tipsets
+                .iter()
+                .map(|fees| fees.gas_used_ratio)
+                .collect(),
+            reward: with_rewards.then(|| {
+                history
+                    .tipsets
+                    .iter()
+                    .map(|fees| fees.premiums.iter().map(hex_amount).collect())
+                    .collect()
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chain::TipsetFees;
+
+    #[test]
+    fn parse_block_numbers() {
+        assert_eq!(
+            "latest".parse::<EthBlockNumber>().unwrap(),
+            EthBlockNumber::Latest
+        );
+        assert_eq!(
+            "safe".parse::<EthBlockNumber>().unwrap(),
+            EthBlockNumber::Latest
+        );
+        assert_eq!(
+            "earliest".parse::<EthBlockNumber>().unwrap(),
+            EthBlockNumber::Earliest
+        );
+        assert_eq!(
+            "0x1f".parse::<EthBlockNumber>().unwrap(),
+            EthBlockNumber::Number(31)
+        );
+        assert_eq!(EthBlockNumber::Number(31).to_string(), "0x1f");
+        assert!("31".parse::<EthBlockNumber>().is_err());
+        assert!("0xzz".parse::<EthBlockNumber>().is_err());
+    }
+
+    #[test]
+    fn quantities() {
+        assert_eq!(serde_json::to_string(&EthUint64(255)).unwrap(), "\"0xff\"");
+        assert_eq!(
+            serde_json::from_str::<EthUint64>("\"0xff\"").unwrap(),
+            EthUint64(255)
+        );
+        assert_eq!(
+            serde_json::from_str::<EthUint64>("4").unwrap(),
+            EthUint64(4)
+        );
+    }
+
+    #[test]
+    fn fee_history_format() {
+        let history = FeeHistory {
+            tipsets: vec![TipsetFees {
+                epoch: 16,
+                base_fee: TokenAmount::from_atto(100),
+                gas_used_ratio: 0.5,
+                premiums: vec![TokenAmount::from_atto(10), TokenAmount::from_atto(20)],
+            }],
+            next_base_fee: TokenAmount::from_atto(101),
+        };
+        let json = serde_json::to_value(EthFeeHistory::new(&history, true)).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "oldestBlock": "0x10",
+                "baseFeePerGas": ["0x64", "0x65"],
+                "gasUsedRatio": [0.5],
+                "reward": [["0xa", "0x14"]],
+            })
+        );
+        let json = serde_json::to_value(EthFeeHistory::new(&history, false)).unwrap();
+        assert!(json.get("reward").is_none());
+    }
+}
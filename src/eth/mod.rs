@@ -0,0 +1,189 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Ethereum addresses of Filecoin actors. An actor is known to Ethereum tools
+//! either by its delegated `f410` address, whose sub-address in the namespace
+//! of the Ethereum Address Manager is the Ethereum address itself, or by its
+//! actor ID masked into an Ethereum address.
+
+mod fee_history;
+
+use std::{fmt, str::FromStr};
+
+use crate::shim::address::{Address, Payload};
+use anyhow::{bail, ensure, Context};
+use fvm_shared::ActorID;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+pub use self::fee_history::*;
+
+/// Length of an Ethereum address in bytes
+pub const ETH_ADDRESS_LENGTH: usize = 20;
+
+/// Prefix of the Ethereum addresses masking actor IDs, followed by the ID in
+/// big endian
+const MASKED_ID_PREFIX: [u8; 12] = [0xff, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct EthAddress(pub [u8; ETH_ADDRESS_LENGTH]);
+
+impl EthAddress {
+    /// Ethereum address masking the given actor ID
+    pub fn from_actor_id(id: ActorID) -> Self {
+        let mut bytes = [0; ETH_ADDRESS_LENGTH];
+        bytes[..MASKED_ID_PREFIX.len()].copy_from_slice(&MASKED_ID_PREFIX);
+        bytes[MASKED_ID_PREFIX.len()..].copy_from_slice(&id.to_be_bytes());
+        Self(bytes)
+    }
+
+    /// Actor ID masked by the address, if it is one
+    pub fn as_actor_id(&self) -> Option<ActorID> {
+        let (prefix, id) = self.0.split_at(MASKED_ID_PREFIX.len());
+        (prefix == MASKED_ID_PREFIX).then(|| {
+            ActorID::from_be_bytes(id.try_into().expect("Infallible: the ID is 8 bytes long"))
+        })
+    }
+
+    /// Converts an ID or `f410` address to its Ethereum equivalent. Other
+    /// addresses need to be resolved to one of those first.
+    pub fn from_filecoin_address(address: &Address) -> anyhow::Result<Self> {
+        match address.payload() {
+            Payload::ID(id) => Ok(Self::from_actor_id(*id)),
+            Payload::Delegated(delegated)
+                if delegated.namespace() == Address::ETHEREUM_ACCOUNT_MANAGER_ACTOR.id()? =>
+            {
+                let bytes: [u8; ETH_ADDRESS_LENGTH] = delegated
+                    .subaddress()
+                    .try_into()
+                    .with_context(|| format!("{address} has a sub-address of the wrong length"))?;
+                let eth_address = Self(bytes);
+                // Otherwise the address would stand for another actor
+                ensure!(
+                    eth_address.as_actor_id().is_none(),
+                    "{address} has a sub-address masking an actor ID"
+                );
+                Ok(eth_address)
+            }
+            _ => bail!("{address} has no Ethereum equivalent, it needs to be resolved first"),
+        }
+    }
+
+    /// Converts to the ID address it masks or to a `f410` address.
+    pub fn to_filecoin_address(&self) -> anyhow::Result<Address> {
+        match self.as_actor_id() {
+            Some(id) => Ok(Address::new_id(id)),
+            None => Ok(Address::new_delegated(
+                Address::ETHEREUM_ACCOUNT_MANAGER_ACTOR.id()?,
+                &self.0,
+            )?),
+        }
+    }
+}
+
+impl fmt::Display for EthAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "0x{}", hex::encode(self.0))
+    }
+}
+
+impl FromStr for EthAddress {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let hex_str = s
+            .strip_prefix("0x")
+            .with_context(|| format!("{s} is missing the 0x prefix"))?;
+        let bytes = hex::decode(hex_str)?;
+        Ok(Self(bytes.try_into().map_err(|bytes: Vec<u8>| {
+            anyhow::anyhow!(
+                "{s} is {} bytes long instead of {ETH_ADDRESS_LENGTH}",
+                bytes.len()
+            )
+        })?))
+    }
+}
+
+impl Serialize for EthAddress {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.to_string().serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for EthAddress {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn masked_id_round_trip() {
+        let eth_address = EthAddress::from_filecoin_address(&Address::new_id(1234)).unwrap();
+        assert_eq!(
+            eth_address.to_string(),
+            "0xff000000000000000000000000000000000004d2"
+        );
+        assert_eq!(eth_address.as_actor_id(), Some(1234));
+        assert_eq!(
+            eth_address.to_filecoin_address().unwrap(),
+            Address::new_id(1234)
+        );
+    }
+
+    #[test]
+    fn delegated_round_trip() {
+        let eth_address: EthAddress = "0xd4c5fb16488aa48081296299d54b0c648c9333da"
+            .parse()
+            .unwrap();
+        assert_eq!(eth_address.as_actor_id(), None);
+        let address = eth_address.to_filecoin_address().unwrap();
+        assert_eq!(address, Address::new_delegated(10, &eth_address.0).unwrap());
+        assert_eq!(
+            EthAddress::from_filecoin_address(&address).unwrap(),
+            eth_address
+        );
+    }
+
+    #[test]
+    fn unsupported_addresses() {
+        // Secp256k1, BLS and actor addresses have to be resolved to IDs first
+        assert!(EthAddress::from_filecoin_address(&Address::new_actor(b"actor")).is_err());
+        // Delegated addresses outside of the EAM namespace
+        let address = Address::new_delegated(32, &[1; ETH_ADDRESS_LENGTH]).unwrap();
+        assert!(EthAddress::from_filecoin_address(&address).is_err());
+        // Sub-addresses masking IDs
+        let masked = EthAddress::from_actor_id(1);
+        let address = Address::new_delegated(10, &masked.0).unwrap();
+        assert!(EthAddress::from_filecoin_address(&address).is_err());
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert!("d4c5fb16488aa48081296299d54b0c648c9333da"
+            .parse::<EthAddress>()
+            .is_err());
+        assert!("0xd4c5".parse::<EthAddress>().is_err());
+        assert!("0xzz".parse::<EthAddress>().is_err());
+    }
+
+    #[test]
+    fn serde_round_trip() {
+        let eth_address = EthAddress::from_actor_id(42);
+        let json = serde_json::to_string(&eth_address).unwrap();
+        assert_eq!(json, "\"0xff0000000000000000000000000000000000002a\"");
+        assert_eq!(
+            serde_json::from_str::<EthAddress>(&json).unwrap(),
+            eth_address
+        );
+    }
+}
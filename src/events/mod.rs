@@ -0,0 +1,322 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Bus of the notable events of the node, such as new heads, reorgs and
+//! completed migrations. The subsystems [`publish`] their events, which are
+//! delivered to the sinks configured in the `[events]` section, so that
+//! operators can alert on them without scraping the logs.
+
+mod sinks;
+
+use std::{fmt, sync::Arc, time::Duration};
+
+use crate::blocks::Tipset;
+use crate::chain::{ChainStore, HeadChange};
+use crate::shim::{address::Address, clock::ChainEpoch};
+use cid::Cid;
+use fvm_ipld_blockstore::Blockstore;
+use log::warn;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use serde_with::{serde_as, DurationSeconds};
+use tokio::sync::broadcast::{self, error::RecvError};
+
+pub use self::sinks::*;
+
+/// Number of events buffered for slow sinks
+const BUS_CAPACITY: usize = 256;
+
+/// Largest interval between the checks of the age of the head
+const STALL_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+static BUS: Lazy<broadcast::Sender<ChainEvent>> = Lazy::new(|| broadcast::channel(BUS_CAPACITY).0);
+
+/// An event of the node
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "Type")]
+pub enum ChainEvent {
+    /// A tipset became the head
+    #[serde(rename_all = "PascalCase")]
+    NewHead {
+        epoch: ChainEpoch,
+        #[serde(with = "crate::json::cid::vec")]
+        tipset: Vec<Cid>,
+    },
+    /// Tipsets were reverted before the new head at `epoch` was applied
+    #[serde(rename_all = "PascalCase")]
+    Reorg { epoch: ChainEpoch, reverted: usize },
+    /// A message of a watched address was included in a tipset
+    #[serde(rename_all = "PascalCase")]
+    MessageLanded {
+        #[serde(with = "crate::json::cid")]
+        message: Cid,
+        epoch: ChainEpoch,
+    },
+    /// The state was migrated to a new network version
+    #[serde(rename_all = "PascalCase")]
+    MigrationCompleted { epoch: ChainEpoch, upgrade: String },
+    /// No new head was received for a while
+    #[serde(rename_all = "PascalCase")]
+    SyncStalled {
+        epoch: ChainEpoch,
+        head_age_secs: u64,
+    },
+    /// More sectors of a monitored miner are faulty
+    #[serde(rename_all = "PascalCase")]
+    MinerFaults {
+        #[serde(with = "crate::json::address::json")]
+        miner: Address,
+        epoch: ChainEpoch,
+        faulty_sectors: u64,
+    },
+    /// More sectors of a monitored miner expire before epoch `before`
+    #[serde(rename_all = "PascalCase")]
+    SectorsExpiring {
+        #[serde(with = "crate::json::address::json")]
+        miner: Address,
+        epoch: ChainEpoch,
+        sectors: u64,
+        before: ChainEpoch,
+    },
+}
+
+/// Kinds of [`ChainEvent`], to select the events delivered to a sink
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, strum::Display)]
+pub enum ChainEventKind {
+    NewHead,
+    Reorg,
+    MessageLanded,
+    MigrationCompleted,
+    SyncStalled,
+    MinerFaults,
+    SectorsExpiring,
+}
+
+impl ChainEvent {
+    pub fn kind(&self) -> ChainEventKind {
+        match self {
+            Self::NewHead { .. } => ChainEventKind::NewHead,
+            Self::Reorg { .. } => ChainEventKind::Reorg,
+            Self::MessageLanded { .. } => ChainEventKind::MessageLanded,
+            Self::MigrationCompleted { .. } => ChainEventKind::MigrationCompleted,
+            Self::SyncStalled { .. } => ChainEventKind::SyncStalled,
+            Self::MinerFaults { .. } => ChainEventKind::MinerFaults,
+            Self::SectorsExpiring { .. } => ChainEventKind::SectorsExpiring,
+        }
+    }
+}
+
+impl fmt::Display for ChainEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NewHead { epoch, .. } => write!(f, "new head at epoch {epoch}"),
+            Self::Reorg { epoch, reverted } => {
+                write!(f, "reorg of {reverted} tipsets before epoch {epoch}")
+            }
+            Self::MessageLanded { message, epoch } => {
+                write!(f, "message {message} landed at epoch {epoch}")
+            }
+            Self::MigrationCompleted { epoch, upgrade } => {
+                write!(f, "{upgrade} state migration completed at epoch {epoch}")
+            }
+            Self::SyncStalled {
+                epoch,
+                head_age_secs,
+            } => write!(
+                f,
+                "sync stalled, head at epoch {epoch} is {head_age_secs}s old"
+            ),
+            Self::MinerFaults {
+                miner,
+                epoch,
+                faulty_sectors,
+            } => write!(
+                f,
+                "miner {miner} has {faulty_sectors} faulty sectors at epoch {epoch}"
+            ),
+            Self::SectorsExpiring {
+                miner,
+                sectors,
+                before,
+                ..
+            } => write!(
+                f,
+                "{sectors} sectors of miner {miner} expire before epoch {before}"
+            ),
+        }
+    }
+}
+
+/// Publishes an event to the configured sinks.
+pub fn publish(event: ChainEvent) {
+    // There is no subscriber when no sink is configured
+    let _ = BUS.send(event);
+}
+
+#[serde_as]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct EventsConfig {
+    pub sinks: Vec<EventSinkConfig>,
+    /// Age of the head, in seconds, above which the sync is reported as
+    /// stalled
+    #[serde_as(as = "DurationSeconds<u64>")]
+    pub sync_stall_threshold: Duration,
+}
+
+impl Default for EventsConfig {
+    fn default() -> Self {
+        Self {
+            sinks: vec![],
+            sync_stall_threshold: Duration::from_secs(300),
+        }
+    }
+}
+
+/// Publishes the head changes and sync stalls, and delivers the events of
+/// the bus to the configured sinks.
+pub async fn event_loop<DB>(
+    config: EventsConfig,
+    chain_store: Arc<ChainStore<DB>>,
+) -> anyhow::Result<()>
+where
+    DB: Blockstore + Send + Sync + 'static,
+{
+    let sinks = EventSinks::from_config(&config.sinks)?;
+    if sinks.is_empty() {
+        return Ok(());
+    }
+    let mut events = BUS.subscribe();
+    let mut head_changes = chain_store.publisher().subscribe();
+    let threshold = config.sync_stall_threshold;
+    let mut stall_check =
+        tokio::time::interval(threshold.clamp(Duration::from_secs(1), STALL_CHECK_INTERVAL));
+    let mut head_events = HeadEvents::default();
+    loop {
+        tokio::select! {
+            event = events.recv() => match event {
+                Ok(event) => sinks.deliver(&event),
+                Err(RecvError::Lagged(n)) => warn!("Event sinks skipped {n} events"),
+                Err(RecvError::Closed) => return Ok(()),
+            },
+            head_change = head_changes.recv() => match head_change {
+                Ok(change) => head_events.head_change(change).into_iter().for_each(publish),
+                Err(RecvError::Lagged(n)) => warn!("Event sinks skipped {n} head changes"),
+                Err(RecvError::Closed) => return Ok(()),
+            },
+            _ = stall_check.tick() => {
+                let head = chain_store.heaviest_tipset();
+                if let Some(event) = head_events.check_stall(&head, threshold) {
+                    publish(event);
+                }
+            },
+        }
+    }
+}
+
+/// Events derived from the head changes and the age of the head
+#[derive(Default)]
+struct HeadEvents {
+    /// Number of tipsets reverted since the last one applied
+    reverted: usize,
+    /// Epoch of the last head reported as stalled
+    stalled_at: Option<ChainEpoch>,
+}
+
+impl HeadEvents {
+    /// Returns the events of a head change: a new head, preceded by a reorg
+    /// when tipsets were reverted before it.
+    fn head_change(&mut self, change: HeadChange) -> Vec<ChainEvent> {
+        match change {
+            HeadChange::Revert(_) => {
+                self.reverted += 1;
+                vec![]
+            }
+            HeadChange::Apply(tipset) => {
+                let mut events = vec![];
+                if self.reverted > 0 {
+                    events.push(ChainEvent::Reorg {
+                        epoch: tipset.epoch(),
+                        reverted: std::mem::take(&mut self.reverted),
+                    });
+                }
+                events.push(ChainEvent::NewHead {
+                    epoch: tipset.epoch(),
+                    tipset: tipset.cids().to_vec(),
+                });
+                events
+            }
+            HeadChange::Current(_) => vec![],
+        }
+    }
+
+    /// Returns a stall event when the head is older than `threshold`, once
+    /// per head.
+    fn check_stall(&mut self, head: &Tipset, threshold: Duration) -> Option<ChainEvent> {
+        let age = head_age_secs(head);
+        if age > threshold.as_secs() && self.stalled_at != Some(head.epoch()) {
+            self.stalled_at = Some(head.epoch());
+            return Some(ChainEvent::SyncStalled {
+                epoch: head.epoch(),
+                head_age_secs: age,
+            });
+        }
+        None
+    }
+}
+
+fn head_age_secs(head: &Tipset) -> u64 {
+    (chrono::Utc::now().timestamp() as u64).saturating_sub(head.min_timestamp())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::ChainGenerator;
+
+    #[test]
+    fn reorgs_and_stalls_are_reported() {
+        let mut generator = ChainGenerator::new();
+        let genesis = generator.genesis().clone();
+        let main = generator.extend(&genesis, &[1000], 3);
+        let fork = generator.extend(&main[0], &[1001], 3);
+
+        let mut head_events = HeadEvents::default();
+        let events: Vec<_> = [
+            HeadChange::Apply(main[0].clone()),
+            HeadChange::Apply(main[1].clone()),
+            HeadChange::Apply(main[2].clone()),
+            HeadChange::Revert(main[2].clone()),
+            HeadChange::Revert(main[1].clone()),
+            HeadChange::Apply(fork[0].clone()),
+            HeadChange::Apply(fork[1].clone()),
+            HeadChange::Apply(fork[2].clone()),
+        ]
+        .into_iter()
+        .flat_map(|change| head_events.head_change(change))
+        .collect();
+        let reorgs: Vec<_> = events
+            .iter()
+            .filter(|event| event.kind() == ChainEventKind::Reorg)
+            .collect();
+        assert_eq!(
+            reorgs,
+            [&ChainEvent::Reorg {
+                epoch: 2,
+                reverted: 2
+            }]
+        );
+        assert_eq!(events.len(), 7);
+
+        // The generated heads are decades old
+        let threshold = Duration::from_secs(300);
+        assert!(matches!(
+            head_events.check_stall(&fork[2], threshold),
+            Some(ChainEvent::SyncStalled { epoch: 4, .. })
+        ));
+        assert!(head_events.check_stall(&fork[2], threshold).is_none());
+        assert!(HeadEvents::default()
+            .check_stall(&fork[2], Duration::MAX)
+            .is_none());
+    }
+}
@@ -0,0 +1,214 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Destinations of the events of the bus.
+
+use std::time::Duration;
+
+use super::{ChainEvent, ChainEventKind};
+use crate::metrics;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+
+/// A destination of the events
+pub trait EventSink: Send + Sync {
+    fn deliver(&self, event: &ChainEvent);
+}
+
+/// Logs the events.
+pub struct LogSink;
+
+impl EventSink for LogSink {
+    fn deliver(&self, event: &ChainEvent) {
+        info!("Chain event: {event}");
+    }
+}
+
+/// Number of the events waiting to be posted by a webhook sink, the events
+/// beyond it are dropped
+const WEBHOOK_QUEUE_CAPACITY: usize = 256;
+
+/// Time after which a webhook post is abandoned
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Posts the events as JSON to a URL, one at a time, from a task spawned
+/// with the sink and stopped with it.
+pub struct WebhookSink {
+    url: reqwest::Url,
+    queue: flume::Sender<Vec<u8>>,
+}
+
+impl WebhookSink {
+    pub fn new(url: &str) -> anyhow::Result<Self> {
+        let url = reqwest::Url::parse(url)?;
+        let client = reqwest::Client::builder()
+            .timeout(WEBHOOK_TIMEOUT)
+            .build()?;
+        let (queue, posts) = flume::bounded(WEBHOOK_QUEUE_CAPACITY);
+        tokio::spawn(post_loop(client, url.clone(), posts));
+        Ok(Self { url, queue })
+    }
+}
+
+impl EventSink for WebhookSink {
+    fn deliver(&self, event: &ChainEvent) {
+        let body = serde_json::to_vec(event).expect("serializing an event must succeed");
+        if self.queue.try_send(body).is_err() {
+            warn!("Too many chain events waiting to be posted to {}", self.url);
+        }
+    }
+}
+
+/// Posts the queued events until the sink is dropped.
+async fn post_loop(client: reqwest::Client, url: reqwest::Url, posts: flume::Receiver<Vec<u8>>) {
+    while let Ok(body) = posts.recv_async().await {
+        let result = client
+            .post(url.clone())
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(body)
+            .send()
+            .await
+            .and_then(|r| r.error_for_status());
+        if let Err(e) = result {
+            warn!("Failed to post a chain event to {url}: {e}");
+        }
+    }
+}
+
+/// Counts the events in the `chain_events` metric, per kind, for Prometheus
+/// alerting rules.
+pub struct PrometheusSink;
+
+impl EventSink for PrometheusSink {
+    fn deliver(&self, event: &ChainEvent) {
+        metrics::CHAIN_EVENTS
+            .with_label_values(&[&event.kind().to_string()])
+            .inc();
+    }
+}
+
+/// A sink in the `[events]` configuration section, e.g.
+/// `{ type = "webhook", url = "https://example.com/hook", events = ["Reorg"] }`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum EventSinkConfig {
+    Log {
+        /// Kinds of events delivered, all of them if empty
+        #[serde(default)]
+        events: Vec<ChainEventKind>,
+    },
+    Webhook {
+        url: String,
+        #[serde(default)]
+        events: Vec<ChainEventKind>,
+    },
+    Prometheus {
+        #[serde(default)]
+        events: Vec<ChainEventKind>,
+    },
+}
+
+/// The sinks events are delivered to, each with the kinds of events it
+/// accepts
+#[derive(Default)]
+pub struct EventSinks {
+    sinks: Vec<(Vec<ChainEventKind>, Box<dyn EventSink>)>,
+}
+
+impl EventSinks {
+    pub fn from_config(configs: &[EventSinkConfig]) -> anyhow::Result<Self> {
+        let mut sinks = Self::default();
+        for config in configs {
+            match config {
+                EventSinkConfig::Log { events } => sinks.add(events.clone(), Box::new(LogSink)),
+                EventSinkConfig::Webhook { url, events } => {
+                    sinks.add(events.clone(), Box::new(WebhookSink::new(url)?))
+                }
+                EventSinkConfig::Prometheus { events } => {
+                    sinks.add(events.clone(), Box::new(PrometheusSink))
+                }
+            }
+        }
+        Ok(sinks)
+    }
+
+    /// Adds a sink of the given kinds of events, all of them if empty.
+    pub fn add(&mut self, events: Vec<ChainEventKind>, sink: Box<dyn EventSink>) {
+        self.sinks.push((events, sink));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sinks.is_empty()
+    }
+
+    pub fn deliver(&self, event: &ChainEvent) {
+        let kind = event.kind();
+        for (events, sink) in &self.sinks {
+            if events.is_empty() || events.contains(&kind) {
+                sink.deliver(event);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parking_lot::Mutex;
+    use std::sync::Arc;
+
+    #[derive(Default, Clone)]
+    struct Collect(Arc<Mutex<Vec<ChainEvent>>>);
+
+    impl EventSink for Collect {
+        fn deliver(&self, event: &ChainEvent) {
+            self.0.lock().push(event.clone());
+        }
+    }
+
+    #[test]
+    fn sinks_get_the_kinds_of_events_they_accept() {
+        let (all, reorgs) = (Collect::default(), Collect::default());
+        let mut sinks = EventSinks::default();
+        sinks.add(vec![], Box::new(all.clone()));
+        sinks.add(vec![ChainEventKind::Reorg], Box::new(reorgs.clone()));
+
+        let reorg = ChainEvent::Reorg {
+            epoch: 10,
+            reverted: 2,
+        };
+        sinks.deliver(&ChainEvent::SyncStalled {
+            epoch: 9,
+            head_age_secs: 600,
+        });
+        sinks.deliver(&reorg);
+        assert_eq!(all.0.lock().len(), 2);
+        assert_eq!(*reorgs.0.lock(), [reorg]);
+    }
+
+    // The webhook sinks spawn their worker
+    #[tokio::test]
+    async fn sinks_config_from_toml() {
+        let config: crate::events::EventsConfig = toml::from_str(
+            r#"
+            sync_stall_threshold = 600
+            sinks = [
+                { type = "log" },
+                { type = "webhook", url = "https://example.com/hook", events = ["Reorg"] },
+            ]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.sinks[1],
+            EventSinkConfig::Webhook {
+                url: "https://example.com/hook".into(),
+                events: vec![ChainEventKind::Reorg],
+            }
+        );
+        assert_eq!(
+            EventSinks::from_config(&config.sinks).unwrap().sinks.len(),
+            2
+        );
+    }
+}
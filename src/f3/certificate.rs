@@ -0,0 +1,128 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use crate::blocks::{tipset_keys_json, TipsetKeys};
+use crate::chain::FinalizedTipset;
+use crate::shim::clock::ChainEpoch;
+use anyhow::{ensure, Context};
+use cid::Cid;
+use num_bigint::BigInt;
+use serde::{Deserialize, Serialize};
+
+/// Certificate of an F3 instance, proving that a power majority agreed on
+/// the chain it carries. The JSON representation matches the one of Lotus.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct FinalityCertificate {
+    #[serde(rename = "GPBFTInstance")]
+    pub gpbft_instance: u64,
+    /// Finalized chain, starting with the head of the previous instance
+    #[serde(rename = "ECChain")]
+    pub ec_chain: Vec<ECTipSet>,
+    pub supplemental_data: SupplementalData,
+    /// Run-length encoded indices of the signing participants
+    pub signers: Vec<u64>,
+    #[serde(with = "base64_bytes")]
+    pub signature: Vec<u8>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub power_table_delta: Vec<PowerTableDelta>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ECTipSet {
+    #[serde(with = "tipset_keys_json")]
+    pub key: TipsetKeys,
+    pub commitments: [u8; 32],
+    pub epoch: ChainEpoch,
+    #[serde(with = "crate::json::cid")]
+    pub power_table: Cid,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct SupplementalData {
+    pub commitments: [u8; 32],
+    #[serde(with = "crate::json::cid")]
+    pub power_table: Cid,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct PowerTableDelta {
+    #[serde(rename = "ParticipantID")]
+    pub participant_id: u64,
+    #[serde(with = "crate::json::bigint::json")]
+    pub power_delta: BigInt,
+    #[serde(with = "base64_bytes")]
+    pub signing_key: Vec<u8>,
+}
+
+impl FinalityCertificate {
+    /// The last tipset of the finalized chain
+    pub fn head(&self) -> Option<&ECTipSet> {
+        self.ec_chain.last()
+    }
+
+    /// The tipset the finalized chain builds upon
+    pub fn base(&self) -> Option<&ECTipSet> {
+        self.ec_chain.first()
+    }
+
+    pub fn finalized_tipset(&self) -> Option<FinalizedTipset> {
+        self.head().map(|head| FinalizedTipset {
+            epoch: head.epoch,
+            key: head.key.clone(),
+        })
+    }
+
+    /// Checks the structure of the certificate and that it extends the chain
+    /// finalized by the previous one, if any.
+    pub fn validate(&self, previous: Option<&FinalityCertificate>) -> anyhow::Result<()> {
+        let base = self.base().context("empty finalized chain")?;
+        ensure!(
+            self.ec_chain
+                .windows(2)
+                .all(|pair| pair[0].epoch < pair[1].epoch),
+            "finalized chain epochs are not increasing"
+        );
+        if let Some(previous) = previous {
+            ensure!(
+                self.gpbft_instance == previous.gpbft_instance + 1,
+                "expected instance {}, got {}",
+                previous.gpbft_instance + 1,
+                self.gpbft_instance
+            );
+            let previous_head = previous.head().context("empty finalized chain")?;
+            ensure!(
+                base.epoch == previous_head.epoch && base.key == previous_head.key,
+                "finalized chain does not build upon the previous instance"
+            );
+        }
+        Ok(())
+    }
+}
+
+mod base64_bytes {
+    use std::borrow::Cow;
+
+    use base64::{prelude::BASE64_STANDARD, Engine};
+    use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        BASE64_STANDARD.encode(bytes).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Vec<u8>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s: Cow<'de, str> = Deserialize::deserialize(deserializer)?;
+        BASE64_STANDARD
+            .decode(s.as_ref())
+            .map_err(de::Error::custom)
+    }
+}
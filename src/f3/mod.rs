@@ -0,0 +1,332 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Consumption of F3 (fast finality) certificates. Certificates are fetched
+//! from a node that participates in F3 and checked against the local chain.
+//!
+//! The aggregated BLS signatures of the certificates are not verified against
+//! the F3 power table yet, so the certificates are advisory by default: a
+//! certificate that disagrees with the local chain is reported, but it
+//! neither finalizes the tipsets it carries nor relaxes the garbage collection
+//! finality guard. Only when the source is explicitly trusted do the certified
+//! tipsets the local chain agrees with become the finalized tipset of the
+//! chain store, which both the syncer and the garbage collector honour.
+
+mod certificate;
+
+use std::{collections::BTreeMap, sync::Arc, time::Duration};
+
+use crate::chain::{ChainStore, FinalizedTipset};
+use crate::rpc_api::f3_api::{F3_GET_CERTIFICATE, F3_GET_LATEST_CERTIFICATE};
+use crate::rpc_client::{call_api, ApiInfo};
+use fvm_ipld_blockstore::Blockstore;
+use log::{debug, info, warn};
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use serde_with::{serde_as, DurationSeconds};
+
+pub use self::certificate::*;
+
+/// Number of the most recent certificates kept in memory
+const MAX_CERTIFICATES: usize = 4096;
+
+/// Maximum number of missing certificates fetched in one poll
+const MAX_CERTIFICATES_PER_POLL: u64 = 100;
+
+#[serde_as]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct F3Config {
+    /// Trusted node to fetch the certificates from, in the
+    /// `[<token>:]<multiaddr>` format of `FULLNODE_API_INFO`. F3 is disabled
+    /// when not set.
+    pub certificate_source: Option<String>,
+    /// Interval between polls of the certificate source, in seconds
+    #[serde_as(as = "DurationSeconds<u64>")]
+    pub poll_interval: Duration,
+    /// Finalize the tipsets certified by the source once the local chain
+    /// agrees with them. The signatures of the certificates are not verified,
+    /// so this trusts the source with the finality of the node.
+    pub trust_certificate_source: bool,
+}
+
+impl Default for F3Config {
+    fn default() -> Self {
+        Self {
+            certificate_source: None,
+            poll_interval: Duration::from_secs(30),
+            trust_certificate_source: false,
+        }
+    }
+}
+
+/// Keeps track of the latest F3 certificates
+#[derive(Debug, Default)]
+pub struct F3Client {
+    certificates: RwLock<BTreeMap<u64, FinalityCertificate>>,
+}
+
+impl F3Client {
+    pub fn latest_certificate(&self) -> Option<FinalityCertificate> {
+        self.certificates
+            .read()
+            .last_key_value()
+            .map(|(_, cert)| cert.clone())
+    }
+
+    pub fn certificate(&self, instance: u64) -> Option<FinalityCertificate> {
+        self.certificates.read().get(&instance).cloned()
+    }
+
+    /// Adds a certificate that extends the latest one. The first certificate
+    /// is accepted as is.
+    pub fn add_certificate(&self, cert: FinalityCertificate) -> anyhow::Result<()> {
+        let mut certificates = self.certificates.write();
+        cert.validate(certificates.last_key_value().map(|(_, cert)| cert))?;
+        certificates.insert(cert.gpbft_instance, cert);
+        while certificates.len() > MAX_CERTIFICATES {
+            certificates.pop_first();
+        }
+        Ok(())
+    }
+
+    /// Polls the certificate source and checks the head of the latest
+    /// certificate against the chain store, once the local chain reaches it.
+    /// The head is finalized when the local chain agrees with it and the
+    /// source is trusted.
+    pub async fn certificate_poll_loop<DB>(
+        self: Arc<Self>,
+        chain_store: Arc<ChainStore<DB>>,
+        config: F3Config,
+    ) -> anyhow::Result<()>
+    where
+        DB: Blockstore + Send + Sync + 'static,
+    {
+        let Some(source) = config.certificate_source else {
+            return Ok(());
+        };
+        let source: ApiInfo = source.parse()?;
+        info!("Fetching F3 certificates from {}", source.multiaddr);
+        if config.trust_certificate_source {
+            warn!("Finalizing the tipsets certified by F3 without verifying the certificates");
+        }
+        let mut interval = tokio::time::interval(config.poll_interval);
+        let mut checked = None;
+        loop {
+            interval.tick().await;
+            if let Err(e) = self.poll(&source).await {
+                warn!("Failed to fetch F3 certificates: {e}");
+            }
+            let Some(certified) = self
+                .latest_certificate()
+                .and_then(|cert| cert.finalized_tipset())
+            else {
+                continue;
+            };
+            if checked.as_ref() == Some(&certified) {
+                continue;
+            }
+            match check_finalized_tipset(&chain_store, &certified) {
+                Some(true) if config.trust_certificate_source => {
+                    chain_store.set_finalized_tipset(certified.clone());
+                }
+                Some(_) => {}
+                // Checked again once the local chain reaches it
+                None => continue,
+            }
+            checked = Some(certified);
+        }
+    }
+
+    /// Fetches the certificates that are missing locally, returns the latest
+    /// one that was added.
+    async fn poll(&self, source: &ApiInfo) -> anyhow::Result<Option<FinalityCertificate>> {
+        let latest: FinalityCertificate = call_api(
+            &source.multiaddr,
+            &source.token,
+            F3_GET_LATEST_CERTIFICATE,
+            (),
+        )
+        .await
+        .map_err(|e| anyhow::anyhow!("{e:?}"))?;
+        let next_instance = match self.latest_certificate() {
+            Some(local) if local.gpbft_instance >= latest.gpbft_instance => return Ok(None),
+            Some(local) => local.gpbft_instance + 1,
+            None => {
+                self.add_certificate(latest.clone())?;
+                return Ok(Some(latest));
+            }
+        };
+
+        let mut added = None;
+        let last_instance = latest
+            .gpbft_instance
+            .min(next_instance + MAX_CERTIFICATES_PER_POLL - 1);
+        for instance in next_instance..=last_instance {
+            let cert: FinalityCertificate = if instance == latest.gpbft_instance {
+                latest.clone()
+            } else {
+                call_api(
+                    &source.multiaddr,
+                    &source.token,
+                    F3_GET_CERTIFICATE,
+                    (instance,),
+                )
+                .await
+                .map_err(|e| anyhow::anyhow!("{e:?}"))?
+            };
+            debug!("Received F3 certificate for instance {instance}");
+            self.add_certificate(cert.clone())?;
+            added = Some(cert);
+        }
+        Ok(added)
+    }
+}
+
+/// Returns whether the local chain agrees with a tipset certified by F3,
+/// warning when it does not. Heads that have not reached the certified epoch
+/// yet are not checked, nor are tipsets that fail to load.
+fn check_finalized_tipset<DB>(
+    chain_store: &ChainStore<DB>,
+    certified: &FinalizedTipset,
+) -> Option<bool>
+where
+    DB: Blockstore + Send + Sync + 'static,
+{
+    let head = chain_store.heaviest_tipset();
+    if head.epoch() < certified.epoch {
+        return None;
+    }
+    match chain_store.tipset_by_height(certified.epoch, head, true) {
+        Ok(local) if local.key() == &certified.key => {
+            debug!("Local chain agrees with F3 at epoch {}", certified.epoch);
+            Some(true)
+        }
+        Ok(local) => {
+            warn!(
+                "Local chain has {} at epoch {}, F3 certified {}",
+                local.key(),
+                certified.epoch,
+                certified.key
+            );
+            Some(false)
+        }
+        Err(e) => {
+            warn!(
+                "Failed to check the F3 certified tipset {}: {e}",
+                certified.key
+            );
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::blocks::TipsetKeys;
+    use crate::db::MemoryDB;
+    use crate::networks::ChainConfig;
+    use crate::test_utils::ChainGenerator;
+    use cid::multihash::{Code, MultihashDigest};
+    use cid::Cid;
+    use tempfile::TempDir;
+
+    use super::*;
+
+    fn tipset(epoch: i64) -> ECTipSet {
+        let cid = Cid::new_v1(
+            fvm_ipld_encoding::DAG_CBOR,
+            Code::Blake2b256.digest(&epoch.to_be_bytes()),
+        );
+        ECTipSet {
+            key: TipsetKeys::new(vec![cid]),
+            commitments: [0; 32],
+            epoch,
+            power_table: cid,
+        }
+    }
+
+    fn certificate(instance: u64, epochs: &[i64]) -> FinalityCertificate {
+        FinalityCertificate {
+            gpbft_instance: instance,
+            ec_chain: epochs.iter().copied().map(tipset).collect(),
+            supplemental_data: SupplementalData {
+                commitments: [0; 32],
+                power_table: tipset(0).power_table,
+            },
+            signers: vec![0, 3],
+            signature: vec![1, 2, 3],
+            power_table_delta: vec![],
+        }
+    }
+
+    #[test]
+    fn accepts_consecutive_certificates() {
+        let client = F3Client::default();
+        client
+            .add_certificate(certificate(5, &[10, 11, 12]))
+            .unwrap();
+        client.add_certificate(certificate(6, &[12, 14])).unwrap();
+        assert_eq!(client.latest_certificate().unwrap().gpbft_instance, 6);
+        assert_eq!(
+            client
+                .latest_certificate()
+                .unwrap()
+                .finalized_tipset()
+                .unwrap()
+                .epoch,
+            14
+        );
+        assert!(client.certificate(5).is_some());
+    }
+
+    #[test]
+    fn rejects_gaps_and_forks() {
+        let client = F3Client::default();
+        client.add_certificate(certificate(5, &[10, 12])).unwrap();
+        // Instance gap
+        assert!(client.add_certificate(certificate(7, &[12, 14])).is_err());
+        // Does not build upon the previous head
+        assert!(client.add_certificate(certificate(6, &[11, 14])).is_err());
+        // Epochs are not increasing
+        assert!(client.add_certificate(certificate(6, &[12, 12])).is_err());
+        assert_eq!(client.latest_certificate().unwrap().gpbft_instance, 5);
+    }
+
+    #[test]
+    fn certified_tipsets_are_checked_once_reached() {
+        let generator = ChainGenerator::new();
+        let chain_data_root = TempDir::new().unwrap();
+        let chain_store = ChainStore::new(
+            MemoryDB::default(),
+            Arc::new(ChainConfig::default()),
+            generator.genesis().min_ticket_block(),
+            chain_data_root.path(),
+        )
+        .unwrap();
+        let genesis = FinalizedTipset {
+            epoch: 0,
+            key: generator.genesis().key().clone(),
+        };
+        assert_eq!(check_finalized_tipset(&chain_store, &genesis), Some(true));
+        let fork = FinalizedTipset {
+            key: tipset(0).key,
+            ..genesis.clone()
+        };
+        assert_eq!(check_finalized_tipset(&chain_store, &fork), Some(false));
+        let ahead = FinalizedTipset {
+            epoch: 1,
+            ..genesis
+        };
+        assert_eq!(check_finalized_tipset(&chain_store, &ahead), None);
+    }
+
+    #[test]
+    fn json_round_trip() {
+        let cert = certificate(1, &[1, 2]);
+        let json = serde_json::to_string(&cert).unwrap();
+        assert!(json.contains("\"GPBFTInstance\":1"));
+        let deserialized: FinalityCertificate = serde_json::from_str(&json).unwrap();
+        assert_eq!(cert, deserialized);
+    }
+}
@@ -3,13 +3,16 @@
 use std::sync::Arc;
 
 use crate::beacon::DrandBeacon;
-use crate::chain_sync::consensus::{MessagePoolApi, SyncGossipSubmitter};
+use crate::chain_sync::consensus::{MessagePoolApi, Proposer, SyncGossipSubmitter};
 use crate::key_management::KeyStore;
+use crate::shim::address::Address;
 use crate::state_manager::StateManager;
+use anyhow::ensure;
 use fvm_ipld_blockstore::Blockstore;
+use log::info;
 use tokio::{sync::RwLock, task::JoinSet};
 
-use crate::fil_cns::FilecoinConsensus;
+use crate::fil_cns::{FilecoinConsensus, FilecoinProposer};
 
 pub type FullConsensus = FilecoinConsensus<DrandBeacon>;
 
@@ -19,13 +22,13 @@ pub fn reward_calc() -> Arc<dyn crate::interpreter::RewardCalc> {
     Arc::new(crate::interpreter::RewardActorMessageCalc)
 }
 
-#[allow(clippy::unused_async)]
 pub async fn consensus<DB, MP>(
     state_manager: &Arc<StateManager<DB>>,
-    _keystore: &Arc<RwLock<KeyStore>>,
-    _mpool: &Arc<MP>,
-    _submitter: SyncGossipSubmitter,
-    _services: &mut JoinSet<anyhow::Result<()>>,
+    keystore: &Arc<RwLock<KeyStore>>,
+    mpool: &Arc<MP>,
+    miner: Option<Address>,
+    submitter: SyncGossipSubmitter,
+    services: &mut JoinSet<anyhow::Result<()>>,
 ) -> anyhow::Result<FullConsensus>
 where
     DB: Blockstore + Clone + Send + Sync + 'static,
@@ -33,5 +36,29 @@ where
 {
     let consensus = FilecoinConsensus::new(state_manager.beacon_schedule());
 
+    if let Some(miner) = miner {
+        ensure!(
+            state_manager.chain_config().network.is_devnet(),
+            "block production is only supported on devnets"
+        );
+        // The produced blocks carry a stub winning PoSt, which only the nodes
+        // built with the same feature accept
+        ensure!(
+            cfg!(feature = "insecure_post"),
+            "block production requires a build with the `insecure_post` feature"
+        );
+        let proposer = FilecoinProposer::new(
+            miner,
+            keystore,
+            state_manager,
+            state_manager.beacon_schedule(),
+        )
+        .await?;
+        info!("Starting the block producer for miner {miner}...");
+        proposer
+            .spawn(state_manager.clone(), mpool.clone(), submitter, services)
+            .await?;
+    }
+
     Ok(consensus)
 }
@@ -3,7 +3,7 @@
 use std::{fmt::Debug, sync::Arc};
 
 use crate::beacon::{Beacon, BeaconSchedule};
-use crate::blocks::{Block, Tipset};
+use crate::blocks::{timing::TimingError, Block, BlockHeader, Tipset};
 use crate::chain::{Error as ChainStoreError, Scale, Weight};
 use crate::chain_sync::Consensus;
 use crate::state_manager::{Error as StateManagerError, StateManager};
@@ -15,20 +15,23 @@ use nonempty::NonEmpty;
 use thiserror::Error;
 
 mod metrics;
+mod proposer;
 mod validation;
 mod weight;
 
 // Shim to work with daemon.rs
 pub mod composition;
 
+pub use proposer::FilecoinProposer;
+
 #[derive(Debug, Error)]
 pub enum FilecoinConsensusError {
     #[error("Block must have an election proof included in tipset")]
     BlockWithoutElectionProof,
     #[error("Block without ticket")]
     BlockWithoutTicket,
-    #[error("Block had the wrong timestamp: {0} != {1}")]
-    UnequalBlockTimestamps(u64, u64),
+    #[error(transparent)]
+    BlockTiming(#[from] TimingError),
     #[error("Tipset without ticket to verify")]
     TipsetWithoutTicket,
     #[error("Block is not claiming to be a winner")]
@@ -112,4 +115,15 @@ where
     {
         validation::validate_block::<_, _>(state_manager, self.beacon.clone(), block).await
     }
+
+    async fn validate_block_header<DB>(
+        &self,
+        state_manager: Arc<StateManager<DB>>,
+        header: Arc<BlockHeader>,
+    ) -> Result<(), NonEmpty<Self::Error>>
+    where
+        DB: Blockstore + Clone + Sync + Send + 'static,
+    {
+        validation::validate_block_header(state_manager, self.beacon.clone(), header)
+    }
 }
@@ -0,0 +1,300 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use std::{sync::Arc, time::Duration};
+
+use crate::beacon::{Beacon, BeaconEntry, BeaconSchedule};
+use crate::blocks::{timing::BlockTiming, BlockHeader, ElectionProof, GossipBlock, Ticket, Tipset};
+use crate::chain::Scale;
+use crate::chain_sync::consensus::{MessagePoolApi, Proposer, SyncGossipSubmitter};
+use crate::json::vrf::VRFProof;
+use crate::key_management::{Key, KeyStore};
+use crate::networks::Height;
+use crate::shim::{
+    address::Address,
+    clock::ChainEpoch,
+    crypto::{Signature, SignatureType},
+    sector::{PoStProof, RegisteredPoStProof},
+};
+use crate::state_manager::StateManager;
+use anyhow::{ensure, Context};
+use async_trait::async_trait;
+use cid::Cid;
+use fil_actors_shared::v10::runtime::DomainSeparationTag;
+use fvm_ipld_blockstore::Blockstore;
+use fvm_ipld_encoding::Cbor;
+use fvm_shared::TICKET_RANDOMNESS_LOOKBACK;
+use fvm_shared3::sector::RegisteredPoStProof as RegisteredPoStProofV3;
+use log::{debug, error, info};
+use tokio::{sync::RwLock, task::JoinSet};
+
+use crate::fil_cns::{validation::INSECURE_WINNING_POST_PROOF, FilecoinConsensus};
+
+/// `FilecoinProposer` produces blocks for a single storage miner on local
+/// devnets. Tickets and election proofs are computed as they would be by a
+/// real miner, but the winning PoSt is a stub, so the produced blocks only
+/// pass validation on nodes built with the `insecure_post` feature, which the
+/// proposer requires as well.
+pub struct FilecoinProposer<B> {
+    miner_addr: Address,
+    /// Key of the worker of the miner, which signs the blocks and produces
+    /// the VRF proofs
+    worker_key: Key,
+    beacon: Arc<BeaconSchedule<B>>,
+}
+
+impl<B> FilecoinProposer<B>
+where
+    B: Beacon,
+{
+    /// Creates a proposer for the given miner, looking up the key of its
+    /// worker in the `keystore`.
+    pub(in crate::fil_cns) async fn new<DB>(
+        miner_addr: Address,
+        keystore: &Arc<RwLock<KeyStore>>,
+        state_manager: &Arc<StateManager<DB>>,
+        beacon: Arc<BeaconSchedule<B>>,
+    ) -> anyhow::Result<Self>
+    where
+        DB: Blockstore + Clone + Sync + Send + 'static,
+    {
+        let heaviest = state_manager.chain_store().heaviest_tipset();
+        let work_addr = state_manager.get_miner_work_addr(*heaviest.parent_state(), &miner_addr)?;
+        info!("The work address of miner {miner_addr} is {work_addr}");
+
+        let worker_key = crate::key_management::find_key(&work_addr, &*keystore.read().await)
+            .with_context(|| format!("the key of worker {work_addr} is not in the keystore"))?;
+        ensure!(
+            *worker_key.key_info.key_type() == SignatureType::BLS,
+            "the worker {work_addr} must have a BLS key"
+        );
+
+        Ok(Self {
+            miner_addr,
+            worker_key,
+            beacon,
+        })
+    }
+
+    fn sign(&self, data: &[u8]) -> anyhow::Result<Signature> {
+        Ok(crate::key_management::sign(
+            *self.worker_key.key_info.key_type(),
+            self.worker_key.key_info.private_key(),
+            data,
+        )?)
+    }
+
+    fn vrf_proof(&self, vrf_base: &[u8]) -> anyhow::Result<VRFProof> {
+        Ok(VRFProof::new(self.sign(vrf_base)?.bytes().to_vec()))
+    }
+
+    /// Runs the election for the given round on top of `base`, and creates
+    /// the block if the miner won it.
+    async fn mine_one<DB>(
+        &self,
+        mpool: &impl MessagePoolApi,
+        state_manager: &Arc<StateManager<DB>>,
+        base: &Arc<Tipset>,
+        round: ChainEpoch,
+    ) -> anyhow::Result<Option<GossipBlock>>
+    where
+        DB: Blockstore + Clone + Sync + Send + 'static,
+    {
+        let chain_config = state_manager.chain_config();
+        let network_version = state_manager.get_network_version(base.epoch());
+
+        let prev_beacon = state_manager.chain_store().latest_beacon_entry(base)?;
+        let beacon_entries = self
+            .beacon
+            .beacon_entries_for_block(network_version, round, base.epoch(), &prev_beacon)
+            .await?;
+        let rand_base = beacon_entries.last().unwrap_or(&prev_beacon);
+
+        let (lookback_tipset, lookback_state) =
+            state_manager.get_lookback_tipset_for_round(base.clone(), round)?;
+        if !state_manager.eligible_to_mine(&self.miner_addr, base, &lookback_tipset)? {
+            debug!("Miner {} is not eligible to mine", self.miner_addr);
+            return Ok(None);
+        }
+
+        let Some(election_proof) =
+            self.election_proof(state_manager, &lookback_state, rand_base, round)?
+        else {
+            return Ok(None);
+        };
+        let ticket = self.ticket(base, rand_base, round, chain_config.epoch(Height::Smoke))?;
+
+        let (parent_state_root, parent_receipts) = state_manager.tipset_state(base).await?;
+        let parent_base_fee = crate::chain::compute_base_fee(
+            state_manager.blockstore(),
+            base,
+            chain_config.epoch(Height::Smoke),
+        )?;
+        let parent_weight = FilecoinConsensus::<B>::weight(state_manager.blockstore(), base)?;
+
+        let msgs = mpool.select_signed(state_manager, base)?;
+        let msgs = msgs.iter().map(|m| m.as_ref()).collect();
+        let persisted = crate::chain::persist_block_messages(state_manager.blockstore(), msgs)?;
+
+        let mut header = BlockHeader::builder()
+            .messages(persisted.msg_cid)
+            .bls_aggregate(Some(persisted.bls_agg))
+            .miner_address(self.miner_addr)
+            .weight(parent_weight)
+            .parent_base_fee(parent_base_fee)
+            .parents(base.key().clone())
+            .epoch(round)
+            .timestamp(BlockTiming::from(chain_config.as_ref()).timestamp(base, round)?)
+            .state_root(parent_state_root)
+            .message_receipts(parent_receipts)
+            .beacon_entries(beacon_entries)
+            .election_proof(Some(election_proof))
+            .ticket(Some(ticket))
+            .winning_post_proof(vec![PoStProof::new(
+                RegisteredPoStProof::from(RegisteredPoStProofV3::StackedDRGWinning2KiBV1),
+                INSECURE_WINNING_POST_PROOF.to_vec(),
+            )])
+            .build()?;
+
+        header.signature = Some(self.sign(&header.to_signing_bytes())?);
+
+        Ok(Some(GossipBlock {
+            header,
+            bls_messages: persisted.bls_cids,
+            secpk_messages: persisted.secp_cids,
+        }))
+    }
+
+    /// Computes the election proof of the miner, returns `None` if it did not
+    /// win any block in the round.
+    fn election_proof<DB>(
+        &self,
+        state_manager: &StateManager<DB>,
+        lookback_state: &Cid,
+        rand_base: &BeaconEntry,
+        round: ChainEpoch,
+    ) -> anyhow::Result<Option<ElectionProof>>
+    where
+        DB: Blockstore + Clone + Sync + Send + 'static,
+    {
+        let vrf_base = crate::state_manager::chain_rand::draw_randomness(
+            rand_base.data(),
+            DomainSeparationTag::ElectionProofProduction as i64,
+            round,
+            &self.miner_addr.marshal_cbor()?,
+        )?;
+        let mut election_proof = ElectionProof {
+            win_count: 0,
+            vrfproof: self.vrf_proof(&vrf_base)?,
+        };
+
+        let Some((miner_power, total_power)) =
+            state_manager.get_power(lookback_state, Some(&self.miner_addr))?
+        else {
+            debug!("Miner {} does not have the minimum power", self.miner_addr);
+            return Ok(None);
+        };
+        election_proof.win_count = election_proof.compute_win_count(
+            &miner_power.quality_adj_power,
+            &total_power.quality_adj_power,
+        );
+        Ok((election_proof.win_count > 0).then_some(election_proof))
+    }
+
+    fn ticket(
+        &self,
+        base: &Tipset,
+        rand_base: &BeaconEntry,
+        round: ChainEpoch,
+        smoke_height: ChainEpoch,
+    ) -> anyhow::Result<Ticket> {
+        let mut entropy = self.miner_addr.marshal_cbor()?;
+        if round > smoke_height {
+            let base_ticket = base.min_ticket().context("base tipset without ticket")?;
+            entropy.extend_from_slice(base_ticket.vrfproof.as_bytes());
+        }
+        let vrf_base = crate::state_manager::chain_rand::draw_randomness(
+            rand_base.data(),
+            DomainSeparationTag::TicketProduction as i64,
+            round - TICKET_RANDOMNESS_LOOKBACK,
+            &entropy,
+        )?;
+        Ok(Ticket::new(self.vrf_proof(&vrf_base)?))
+    }
+
+    async fn run<DB, MP>(
+        self,
+        state_manager: Arc<StateManager<DB>>,
+        mpool: &MP,
+        submitter: &SyncGossipSubmitter,
+    ) -> anyhow::Result<()>
+    where
+        DB: Blockstore + Clone + Sync + Send + 'static,
+        MP: MessagePoolApi + Send + Sync + 'static,
+    {
+        let chain_store = state_manager.chain_store();
+        let mut interval = tokio::time::interval(Duration::from_secs(
+            state_manager.chain_config().block_delay_secs,
+        ));
+
+        // Rounds without a block on top of the same base are null rounds
+        let mut last_base = None;
+        let mut null_rounds = 0;
+        loop {
+            interval.tick().await;
+            let base = chain_store.heaviest_tipset();
+            if last_base.as_ref() == Some(base.key()) {
+                null_rounds += 1;
+            } else {
+                last_base = Some(base.key().clone());
+                null_rounds = 0;
+            }
+            let round = base.epoch() + null_rounds + 1;
+
+            debug!(
+                "Running the election of round {round} on top of {} in epoch {}",
+                base.min_ticket_block().cid(),
+                base.epoch(),
+            );
+            match self.mine_one(mpool, &state_manager, &base, round).await {
+                Ok(Some(block)) => {
+                    let cid = *block.header.cid();
+                    let msg_cnt = block.secpk_messages.len() + block.bls_messages.len();
+                    match submitter.submit_block(block).await {
+                        Ok(()) => {
+                            info!("Mined block {cid} in epoch {round} with {msg_cnt} messages")
+                        }
+                        Err(e) => error!("Failed to submit block: {e}"),
+                    }
+                }
+                Ok(None) => debug!("Did not win the election of round {round}"),
+                Err(e) => error!("Failed to mine a block in epoch {round}: {e}"),
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl<B> Proposer for FilecoinProposer<B>
+where
+    B: Beacon + Send + Sync + 'static,
+{
+    async fn spawn<DB, MP>(
+        self,
+        state_manager: Arc<StateManager<DB>>,
+        mpool: Arc<MP>,
+        submitter: SyncGossipSubmitter,
+        services: &mut JoinSet<anyhow::Result<()>>,
+    ) -> anyhow::Result<()>
+    where
+        DB: Blockstore + Clone + Sync + Send + 'static,
+        MP: MessagePoolApi + Send + Sync + 'static,
+    {
+        services.spawn(async move {
+            self.run(state_manager, mpool.as_ref(), &submitter)
+                .await
+                .context("block production stopped")
+        });
+        Ok(())
+    }
+}
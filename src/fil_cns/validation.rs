@@ -4,7 +4,7 @@
 use std::{collections::BTreeMap, sync::Arc};
 
 use crate::beacon::{Beacon, BeaconEntry, BeaconSchedule, IGNORE_DRAND_VAR};
-use crate::blocks::{Block, BlockHeader, Tipset};
+use crate::blocks::{timing::BlockTiming, Block, BlockHeader, Tipset};
 use crate::chain_sync::collect_errs;
 use crate::networks::{ChainConfig, Height};
 use crate::shim::{
@@ -15,6 +15,7 @@ use crate::shim::{
 };
 use crate::state_manager::StateManager;
 use crate::utils::encoding::prover_id_from_u64;
+use crate::utils::proofs_api::verifier::{self, kinds};
 use cid::Cid;
 use fil_actor_interface::power;
 use fil_actors_shared::v10::runtime::DomainSeparationTag;
@@ -157,21 +158,106 @@ pub(in crate::fil_cns) async fn validate_block<
     // Winning PoSt proof validation
     let v_block = block.clone();
     let v_prev_beacon = Arc::clone(&prev_beacon);
-    validations.push(tokio::task::spawn_blocking(move || {
-        verify_winning_post_proof::<_>(
-            &state_manager,
-            win_p_nv,
-            v_block.header(),
-            &v_prev_beacon,
-            &lookback_state,
-        )?;
-        Ok(())
+    validations.push(tokio::task::spawn(async move {
+        verifier::verify(kinds::WINNING_POST, move || {
+            verify_winning_post_proof::<_>(
+                &state_manager,
+                win_p_nv,
+                v_block.header(),
+                &v_prev_beacon,
+                &lookback_state,
+            )
+        })
+        .await
+        .map_err(|e| FilecoinConsensusError::WinningPoStValidation(e.to_string()))?
     }));
 
     // Collect the errors from the async validations
     collect_errs(validations).await
 }
 
+/// Validates the block header for the nodes syncing the headers only, which
+/// don't execute the messages. The parts of the lookback state read here are
+/// expected to be fetched by the caller.
+///
+/// Validation includes:
+/// * Sanity checks
+/// * Timestamps
+/// * Beacon values
+/// * Ticket, against the worker key of the miner in the lookback state
+/// * Election proof and win count, against the power in the lookback state
+///
+/// The eligibility of the miner depends on the parent state, so it isn't
+/// validated.
+pub(in crate::fil_cns) fn validate_block_header<
+    DB: Blockstore + Clone + Sync + Send + 'static,
+    B: Beacon,
+>(
+    state_manager: Arc<StateManager<DB>>,
+    beacon_schedule: Arc<BeaconSchedule<B>>,
+    header: Arc<BlockHeader>,
+) -> Result<(), NonEmpty<FilecoinConsensusError>> {
+    let chain_store = state_manager.chain_store();
+
+    block_sanity_checks(&header).map_err(to_errs)?;
+
+    let base_tipset = chain_store
+        .tipset_from_keys(header.parents())
+        .map_err(to_errs)?;
+
+    block_timestamp_checks(
+        &header,
+        base_tipset.as_ref(),
+        state_manager.chain_config().as_ref(),
+    )
+    .map_err(to_errs)?;
+
+    // Safe to unwrap because checked to `Some` in sanity check
+    if header.election_proof().as_ref().unwrap().win_count < 1 {
+        return Err(to_errs(FilecoinConsensusError::NotClaimingWin));
+    }
+
+    let prev_beacon = chain_store
+        .latest_beacon_entry(&base_tipset)
+        .map_err(to_errs)?;
+
+    if std::env::var(IGNORE_DRAND_VAR) != Ok("1".to_owned()) {
+        header
+            .validate_block_drand(
+                state_manager.get_network_version(base_tipset.epoch()),
+                beacon_schedule.as_ref(),
+                base_tipset.epoch(),
+                &prev_beacon,
+            )
+            .map_err(|e| to_errs(FilecoinConsensusError::BeaconValidation(e.to_string())))?;
+    }
+
+    let (_, lookback_state) = state_manager
+        .get_lookback_tipset_for_round(base_tipset.clone(), header.epoch())
+        .map_err(to_errs)?;
+    let work_addr = state_manager
+        .get_miner_work_addr(lookback_state, header.miner_address())
+        .map_err(to_errs)?;
+
+    validate_ticket_election(
+        &header,
+        base_tipset.as_ref(),
+        &prev_beacon,
+        &work_addr,
+        state_manager.chain_config().as_ref(),
+    )
+    .map_err(to_errs)?;
+
+    validate_election_proof(
+        &header,
+        &lookback_state,
+        &prev_beacon,
+        &work_addr,
+        state_manager.as_ref(),
+    )
+    .map_err(to_errs)
+}
+
 /// Checks optional values in header.
 ///
 /// In particular it looks for an election proof and a ticket,
@@ -193,17 +279,7 @@ fn block_timestamp_checks(
     base_tipset: &Tipset,
     chain_config: &ChainConfig,
 ) -> Result<(), FilecoinConsensusError> {
-    // Timestamp checks
-    let block_delay = chain_config.block_delay_secs;
-    let nulls = (header.epoch() - (base_tipset.epoch() + 1)) as u64;
-    let target_timestamp = base_tipset.min_timestamp() + block_delay * (nulls + 1);
-    if target_timestamp != header.timestamp() {
-        return Err(FilecoinConsensusError::UnequalBlockTimestamps(
-            header.timestamp(),
-            target_timestamp,
-        ));
-    }
-    Ok(())
+    Ok(BlockTiming::from(chain_config).check_timestamp(header, base_tipset)?)
 }
 
 // Check that the miner power can be loaded.
@@ -256,6 +332,31 @@ fn validate_winner_election<DB: Blockstore + Clone + Sync + Send + 'static>(
         return Err(FilecoinConsensusError::MinerNotEligibleToMine);
     }
 
+    if state_manager.is_miner_slashed(header.miner_address(), base_tipset.parent_state())? {
+        return Err(FilecoinConsensusError::InvalidOrSlashedMiner);
+    }
+
+    validate_election_proof(
+        header,
+        lookback_state,
+        prev_beacon,
+        work_addr,
+        state_manager,
+    )
+}
+
+/// Checks the VRF of the election proof against the worker key of the miner,
+/// and the claimed win count against its power in the lookback state.
+fn validate_election_proof<DB: Blockstore + Clone + Sync + Send + 'static>(
+    header: &BlockHeader,
+    lookback_state: &Cid,
+    prev_beacon: &BeaconEntry,
+    work_addr: &Address,
+    state_manager: &StateManager<DB>,
+) -> Result<(), FilecoinConsensusError> {
+    // Safe to unwrap because checked to `Some` in sanity check
+    let election_proof = header.election_proof().as_ref().unwrap();
+
     let beacon = header.beacon_entries().last().unwrap_or(prev_beacon);
     let miner_address = header.miner_address();
     let miner_address_buf = miner_address.marshal_cbor()?;
@@ -270,10 +371,6 @@ fn validate_winner_election<DB: Blockstore + Clone + Sync + Send + 'static>(
 
     verify_election_post_vrf(work_addr, &vrf_base, election_proof.vrfproof.as_bytes())?;
 
-    if state_manager.is_miner_slashed(header.miner_address(), base_tipset.parent_state())? {
-        return Err(FilecoinConsensusError::InvalidOrSlashedMiner);
-    }
-
     let (mpow, tpow) = state_manager
         .get_power(lookback_state, Some(header.miner_address()))?
         .ok_or(FilecoinConsensusError::MinerPowerNotAvailable)?;
@@ -342,6 +439,10 @@ fn verify_election_post_vrf(
     verify_bls_sig(evrf, rand, &worker.into()).map_err(FilecoinConsensusError::VrfValidation)
 }
 
+/// Winning PoSt proof accepted when the node is built with the
+/// `insecure_post` feature, produced by the devnet block proposer
+pub(in crate::fil_cns) const INSECURE_WINNING_POST_PROOF: &[u8] = b"valid_proof";
+
 fn verify_winning_post_proof<DB: Blockstore + Clone + Send + Sync + 'static>(
     state_manager: &StateManager<DB>,
     network_version: NetworkVersion,
@@ -360,7 +461,7 @@ fn verify_winning_post_proof<DB: Blockstore + Clone + Send + Sync + 'static>(
                 String::from("No winning PoSt proof provided"),
             ));
         }
-        if wpp[0].proof_bytes == b"valid_proof" {
+        if wpp[0].proof_bytes == INSECURE_WINNING_POST_PROOF {
             return Ok(());
         }
         return Err(FilecoinConsensusError::InsecurePostValidation(
@@ -440,12 +541,51 @@ enum ProofType {
     // Window,
 }
 
+/// Size of a Groth16 proof, winning `PoSt` proofs have a single partition
+const WINNING_POST_PROOF_SIZE: usize = 192;
+
+/// Checks the shape of the winning `PoSt` proofs of a block before they reach
+/// the proofs library, which may panic on malformed inputs, as in Lotus.
+fn check_winning_post_inputs(
+    proofs: &[PoStProof],
+    challenge_sectors: &[SectorInfo],
+) -> Result<(), anyhow::Error> {
+    let [proof] = proofs else {
+        anyhow::bail!("expected 1 winning PoSt proof, got {}", proofs.len());
+    };
+    if proof.proof_bytes.len() != WINNING_POST_PROOF_SIZE {
+        anyhow::bail!(
+            "winning PoSt proof has {} bytes instead of {WINNING_POST_PROOF_SIZE}",
+            proof.proof_bytes.len()
+        );
+    }
+    if challenge_sectors.is_empty() {
+        anyhow::bail!("no sectors challenged for the winning PoSt");
+    }
+    for sector in challenge_sectors {
+        let registered = sector
+            .proof
+            .registered_winning_post_proof()
+            .map_err(|e| anyhow::anyhow!("{e}"))?;
+        if registered != proof.post_proof {
+            anyhow::bail!(
+                "winning PoSt proof type {:?} does not match the type {registered:?} of sector {}",
+                proof.post_proof,
+                sector.sector_number
+            );
+        }
+    }
+    Ok(())
+}
+
 fn verify_winning_post(
     mut rand: Randomness,
     proofs: &[PoStProof],
     challenge_sectors: &[SectorInfo],
     prover: u64,
 ) -> Result<(), anyhow::Error> {
+    check_winning_post_inputs(proofs, challenge_sectors)?;
+
     // Necessary to be valid bls12 381 element.
     rand.0[31] &= 0x3f;
 
@@ -468,3 +608,38 @@ fn verify_winning_post(
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cid::multihash::{Code, MultihashDigest};
+    use fvm_shared3::sector::{RegisteredPoStProof, RegisteredSealProof};
+
+    fn sector() -> SectorInfo {
+        let sealed_cid = Cid::new_v1(0x55, Code::Sha2_256.digest(b"sector"));
+        SectorInfo::new(RegisteredSealProof::StackedDRG32GiBV1P1, 1, sealed_cid)
+    }
+
+    fn proof(post_proof: RegisteredPoStProof, len: usize) -> PoStProof {
+        PoStProof::new(post_proof.into(), vec![0; len])
+    }
+
+    #[test]
+    fn malformed_winning_posts_are_rejected() {
+        let winning = RegisteredPoStProof::StackedDRGWinning32GiBV1;
+        let sectors = [sector()];
+        let valid = proof(winning, WINNING_POST_PROOF_SIZE);
+        assert!(check_winning_post_inputs(&[valid.clone()], &sectors).is_ok());
+
+        assert!(check_winning_post_inputs(&[], &sectors).is_err());
+        assert!(check_winning_post_inputs(&[valid.clone(), valid.clone()], &sectors).is_err());
+        assert!(check_winning_post_inputs(&[valid], &[]).is_err());
+        let short = proof(winning, WINNING_POST_PROOF_SIZE - 1);
+        assert!(check_winning_post_inputs(&[short], &sectors).is_err());
+        let other_size = proof(
+            RegisteredPoStProof::StackedDRGWinning64GiBV1,
+            WINNING_POST_PROOF_SIZE,
+        );
+        assert!(check_winning_post_inputs(&[other_size], &sectors).is_err());
+    }
+}
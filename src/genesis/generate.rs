@@ -0,0 +1,355 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Generation of the genesis of a local network, the way `lotus-seed` and
+//! `lotus genesis` make one: the singleton built-in actors in their initial
+//! state, a miner `t01000` whose worker and owner is a given key, and funded
+//! accounts. The miner has no sectors, so it can only mine blocks with the
+//! delegated consensus.
+
+use std::path::Path;
+
+use crate::blocks::{BlockHeader, Ticket};
+use crate::chain::{compute_message_root, compute_receipts_root, persist_objects};
+use crate::db::MemoryDB;
+use crate::json::vrf::VRFProof;
+use crate::shim::{
+    address::Address,
+    econ::TokenAmount,
+    machine::{
+        Manifest, ACCOUNT_ACTOR_NAME, CRON_ACTOR_NAME, DATACAP_ACTOR_NAME, EAM_ACTOR_NAME,
+        INIT_ACTOR_NAME, MARKET_ACTOR_NAME, MINER_ACTOR_NAME, POWER_ACTOR_NAME, REWARD_ACTOR_NAME,
+        SYSTEM_ACTOR_NAME, VERIFREG_ACTOR_NAME,
+    },
+    state_tree::{ActorState, StateTree, StateTreeVersion},
+};
+use crate::utils::db::CborStoreExt;
+use cid::Cid;
+use fil_actor_cron_state::v11 as cron;
+use fil_actor_market_state::v11 as market;
+use fil_actor_miner_state::v11 as miner;
+use fil_actor_power_state::v11 as power;
+use fil_actors_shared::v11::{builtin::HAMT_BIT_WIDTH, make_empty_map, runtime::Policy};
+use futures::AsyncWriteExt;
+use fvm_ipld_blockstore::Blockstore;
+use fvm_ipld_car::CarHeader;
+use fvm_shared3::{sector::RegisteredPoStProof, ActorID};
+use num::Zero;
+use tokio_util::compat::TokioAsyncWriteCompatExt;
+
+/// ID of the first account created at genesis, the worker of the miner
+const FIRST_ACCOUNT_ID: ActorID = 100;
+
+/// ID of the miner created at genesis, the one delegated consensus elects
+pub const GENESIS_MINER_ID: ActorID = 1000;
+
+/// Balance of the reward actor, the storage mining allocation of mainnet
+const REWARD_BALANCE_FIL: u64 = 1_100_000_000;
+
+/// Balance of the reserve actor, the reserve of mainnet
+const RESERVE_BALANCE_FIL: u64 = 300_000_000;
+
+/// What the generated genesis contains
+#[derive(Debug, Clone)]
+pub struct GenesisTemplate {
+    /// Name of the network, in the state of the init actor
+    pub network_name: String,
+    /// Timestamp of the genesis block, the time the chain starts at
+    pub timestamp: u64,
+    /// Manifest of the built-in actors, whose bundle is in the store
+    pub manifest: Cid,
+    /// Key of the owner and worker of the miner `t01000`, funded as the
+    /// accounts are
+    pub worker: Address,
+    /// Key addresses of the accounts to fund
+    pub accounts: Vec<Address>,
+    /// Balance of each account
+    pub balance: TokenAmount,
+}
+
+/// Generates the state and the block of the genesis into `db`, and returns
+/// the block.
+pub fn generate_genesis<DB: Blockstore + Clone>(
+    db: &DB,
+    template: &GenesisTemplate,
+) -> anyhow::Result<BlockHeader> {
+    let manifest = Manifest::load(db, &template.manifest)?;
+    let code = |name| manifest.code_by_name(name).copied();
+    let mut state_tree = StateTree::new(db.clone(), StateTreeVersion::V5)?;
+
+    // The init actor is set first, as the other actors are set by ID
+    let keys: Vec<_> = std::iter::once(&template.worker)
+        .chain(&template.accounts)
+        .collect();
+    let mut address_map = make_empty_map::<_, ActorID>(db, HAMT_BIT_WIDTH);
+    for (id, key) in (FIRST_ACCOUNT_ID..).zip(&keys) {
+        address_map.set(key.to_bytes().into(), id)?;
+    }
+    let init = fil_actor_init_state::v11::State {
+        address_map: address_map.flush()?,
+        next_id: GENESIS_MINER_ID + 1,
+        network_name: template.network_name.clone(),
+    };
+    set_actor(
+        &mut state_tree,
+        Address::INIT_ACTOR,
+        code(INIT_ACTOR_NAME)?,
+        &init,
+    )?;
+
+    let system = fil_actor_system_state::v11::State {
+        builtin_actors: manifest.actors_cid(),
+    };
+    set_actor(
+        &mut state_tree,
+        Address::SYSTEM_ACTOR,
+        code(SYSTEM_ACTOR_NAME)?,
+        &system,
+    )?;
+
+    let reward = fil_actor_reward_state::v11::State::new(Zero::zero());
+    state_tree.set_actor(
+        &Address::REWARD_ACTOR,
+        ActorState::new(
+            code(REWARD_ACTOR_NAME)?,
+            db.put_cbor_default(&reward)?,
+            TokenAmount::from_whole(REWARD_BALANCE_FIL),
+            0,
+            None,
+        ),
+    )?;
+
+    let cron = cron::State {
+        entries: vec![
+            cron::Entry {
+                receiver: Address::POWER_ACTOR.into(),
+                method_num: power::Method::OnEpochTickEnd as u64,
+            },
+            cron::Entry {
+                receiver: Address::MARKET_ACTOR.into(),
+                method_num: market::Method::CronTick as u64,
+            },
+        ],
+    };
+    set_actor(
+        &mut state_tree,
+        Address::CRON_ACTOR,
+        code(CRON_ACTOR_NAME)?,
+        &cron,
+    )?;
+
+    let power = power::State::new(db)?;
+    set_actor(
+        &mut state_tree,
+        Address::POWER_ACTOR,
+        code(POWER_ACTOR_NAME)?,
+        &power,
+    )?;
+
+    let market = market::State::new(db)?;
+    set_actor(
+        &mut state_tree,
+        Address::MARKET_ACTOR,
+        code(MARKET_ACTOR_NAME)?,
+        &market,
+    )?;
+
+    // The worker is the root key, so that it can add verifiers
+    let worker_id = Address::new_id(FIRST_ACCOUNT_ID);
+    let verifreg = fil_actor_verifreg_state::v11::State::new(db, worker_id.into())?;
+    set_actor(
+        &mut state_tree,
+        Address::VERIFIED_REGISTRY_ACTOR,
+        code(VERIFREG_ACTOR_NAME)?,
+        &verifreg,
+    )?;
+
+    let datacap =
+        fil_actor_datacap_state::v11::State::new(db, Address::VERIFIED_REGISTRY_ACTOR.into())?;
+    set_actor(
+        &mut state_tree,
+        Address::DATACAP_TOKEN_ACTOR,
+        code(DATACAP_ACTOR_NAME)?,
+        &datacap,
+    )?;
+
+    state_tree.set_actor(
+        &Address::ETHEREUM_ACCOUNT_MANAGER_ACTOR,
+        ActorState::new_empty(code(EAM_ACTOR_NAME)?, None),
+    )?;
+
+    let accounts = [
+        (
+            Address::BURNT_FUNDS_ACTOR,
+            Address::BURNT_FUNDS_ACTOR,
+            TokenAmount::zero(),
+        ),
+        (
+            Address::RESERVE_ACTOR,
+            Address::RESERVE_ACTOR,
+            TokenAmount::from_whole(RESERVE_BALANCE_FIL),
+        ),
+    ]
+    .into_iter()
+    .chain(
+        (FIRST_ACCOUNT_ID..)
+            .zip(keys)
+            .map(|(id, key)| (Address::new_id(id), *key, template.balance.clone())),
+    );
+    for (id, key, balance) in accounts {
+        let state = fil_actor_account_state::v11::State {
+            address: key.into(),
+        };
+        state_tree.set_actor(
+            &id,
+            ActorState::new(
+                code(ACCOUNT_ACTOR_NAME)?,
+                db.put_cbor_default(&state)?,
+                balance,
+                0,
+                None,
+            ),
+        )?;
+    }
+
+    let info = miner::MinerInfo::new(
+        FIRST_ACCOUNT_ID,
+        FIRST_ACCOUNT_ID,
+        vec![],
+        vec![],
+        vec![],
+        RegisteredPoStProof::StackedDRGWindow2KiBV1P1,
+    )?;
+    let miner = miner::State::new(&Policy::default(), db, db.put_cbor_default(&info)?, 0, 0)?;
+    set_actor(
+        &mut state_tree,
+        Address::new_id(GENESIS_MINER_ID),
+        code(MINER_ACTOR_NAME)?,
+        &miner,
+    )?;
+
+    let genesis = BlockHeader::builder()
+        .miner_address(Address::SYSTEM_ACTOR)
+        .ticket(Some(Ticket::new(VRFProof::new(b"genesis".to_vec()))))
+        .state_root(state_tree.flush()?)
+        .messages(compute_message_root(db, &[], &[])?)
+        .message_receipts(compute_receipts_root(db, &[])?)
+        .timestamp(template.timestamp)
+        .build()?;
+    persist_objects(db, &[genesis.clone()])?;
+    Ok(genesis)
+}
+
+/// Sets a built-in actor, without balance
+fn set_actor<DB: Blockstore + Clone>(
+    state_tree: &mut StateTree<DB>,
+    address: Address,
+    code: Cid,
+    state: &impl serde::Serialize,
+) -> anyhow::Result<()> {
+    let state = state_tree.store().put_cbor_default(state)?;
+    state_tree.set_actor(
+        &address,
+        ActorState::new(code, state, TokenAmount::zero(), 0, None),
+    )
+}
+
+/// Writes a CAR file of all the blocks of `db`, the actors bundle included,
+/// with the genesis block as its root.
+pub async fn write_genesis_car(
+    db: &MemoryDB,
+    genesis: &BlockHeader,
+    path: &Path,
+) -> anyhow::Result<()> {
+    let mut writer = tokio::fs::File::create(path).await?.compat_write();
+    let mut blocks = futures::stream::iter(db.blocks()?);
+    CarHeader::from(vec![*genesis.cid()])
+        .write_stream_async(&mut writer, &mut blocks)
+        .await?;
+    writer.close().await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shim::machine::ManifestActorsCbor;
+    use cid::multihash::{Code::Blake2b256, MultihashDigest};
+    use fvm_ipld_encoding::CborStore;
+    use fvm_shared::IPLD_RAW;
+
+    /// Stores a manifest with placeholder code CIDs, as the tests have no
+    /// actors bundle
+    fn placeholder_manifest(db: &MemoryDB) -> Cid {
+        let actors: ManifestActorsCbor = [
+            ACCOUNT_ACTOR_NAME,
+            CRON_ACTOR_NAME,
+            DATACAP_ACTOR_NAME,
+            EAM_ACTOR_NAME,
+            INIT_ACTOR_NAME,
+            MARKET_ACTOR_NAME,
+            MINER_ACTOR_NAME,
+            POWER_ACTOR_NAME,
+            REWARD_ACTOR_NAME,
+            SYSTEM_ACTOR_NAME,
+            VERIFREG_ACTOR_NAME,
+        ]
+        .into_iter()
+        .map(|name| {
+            let code = Cid::new_v1(IPLD_RAW, Blake2b256.digest(name.as_bytes()));
+            (name.to_owned(), code)
+        })
+        .collect();
+        let actors = db.put_cbor_default(&actors).unwrap();
+        db.put_cbor_default(&(1u32, actors)).unwrap()
+    }
+
+    #[tokio::test]
+    async fn genesis_round_trip() {
+        let db = MemoryDB::default();
+        let worker = Address::new_bls(&[1; 48]).unwrap();
+        let account = Address::new_secp256k1(&[2; 65]).unwrap();
+        let template = GenesisTemplate {
+            network_name: "localnet".into(),
+            timestamp: 1_700_000_000,
+            manifest: placeholder_manifest(&db),
+            worker,
+            accounts: vec![account],
+            balance: TokenAmount::from_whole(1000),
+        };
+        let genesis = generate_genesis(&db, &template).unwrap();
+        assert_eq!(genesis.epoch(), 0);
+
+        let state_tree = StateTree::new_from_root(db.clone(), genesis.state_root()).unwrap();
+        for key in [worker, account] {
+            let actor = state_tree.get_actor(&key).unwrap().unwrap();
+            assert_eq!(actor.balance, TokenAmount::from_whole(1000).into());
+        }
+        assert_eq!(
+            state_tree.lookup_id(&worker).unwrap(),
+            Some(FIRST_ACCOUNT_ID)
+        );
+        let miner = state_tree
+            .get_actor(&Address::new_id(GENESIS_MINER_ID))
+            .unwrap()
+            .unwrap();
+        let miner: miner::State = db.get_cbor(&miner.state).unwrap().unwrap();
+        let info: miner::MinerInfo = db.get_cbor(&miner.info).unwrap().unwrap();
+        assert_eq!(
+            Address::from(info.worker),
+            Address::new_id(FIRST_ACCOUNT_ID)
+        );
+
+        // The node reads back the same genesis from the CAR file
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("genesis.car");
+        write_genesis_car(&db, &genesis, &path).await.unwrap();
+        let imported = MemoryDB::default();
+        let header =
+            crate::genesis::read_genesis_header(Some(&path.display().to_string()), None, &imported)
+                .await
+                .unwrap();
+        assert_eq!(header, genesis);
+        assert!(imported.has(genesis.state_root()).unwrap());
+    }
+}
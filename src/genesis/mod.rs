@@ -1,15 +1,27 @@
 // Copyright 2019-2023 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
-use std::{sync::Arc, time};
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+    time,
+};
 
 use crate::blocks::{BlockHeader, Tipset, TipsetKeys};
+use crate::networks::ChainConfig;
 use crate::state_manager::StateManager;
 use crate::utils::{
-    db::BlockstoreBufferedWriteExt,
-    net::{get_fetch_progress_from_file, get_fetch_progress_from_url},
+    car::{
+        car_v1_payload,
+        forest::{ForestCarFooter, ForestCarReader},
+    },
+    db::{BlockstoreBufferedWriteExt, BufferedWriteConfig},
+    net::{
+        get_fetch_progress_from_file, get_fetch_progress_from_object_store,
+        get_fetch_progress_from_url, is_object_store_url, FetchProgress,
+    },
 };
-use anyhow::bail;
+use anyhow::{bail, Context};
 use cid::Cid;
 use futures::AsyncRead;
 use fvm_ipld_blockstore::Blockstore;
@@ -17,9 +29,14 @@ use fvm_ipld_car::{load_car, CarReader};
 use fvm_ipld_encoding::CborStore;
 use log::{debug, info};
 use tokio::{fs::File, io::BufReader};
-use tokio_util::compat::TokioAsyncReadCompatExt;
+use tokio_util::compat::{FuturesAsyncReadCompatExt, TokioAsyncReadCompatExt};
 use url::Url;
 
+mod generate;
+pub use generate::*;
+mod preflight;
+pub use preflight::*;
+
 #[cfg(test)]
 pub const EXPORT_SR_40: &[u8] = std::include_bytes!("export40.car");
 
@@ -52,6 +69,28 @@ where
     Ok(genesis)
 }
 
+/// Downloads the genesis of the networks which don't embed it into the chain
+/// data directory, unless it's there already. Returns its path, if any.
+pub async fn fetch_genesis(
+    chain_config: &ChainConfig,
+    chain_data_path: &Path,
+) -> anyhow::Result<Option<String>> {
+    let Some(url) = chain_config.genesis_url() else {
+        return Ok(None);
+    };
+    let genesis_path = chain_data_path.join("genesis.car");
+    if !genesis_path.exists() {
+        info!("Downloading the genesis from {url}...");
+        let reader = FetchProgress::fetch_from_url(&url).await?.inner;
+        let tmp_path = genesis_path.with_extension("car.tmp");
+        let mut file = File::create(&tmp_path).await?;
+        tokio::io::copy(&mut reader.compat(), &mut file).await?;
+        file.sync_all().await?;
+        tokio::fs::rename(&tmp_path, &genesis_path).await?;
+    }
+    Ok(Some(genesis_path.display().to_string()))
+}
+
 pub fn get_network_name_from_genesis<BS>(
     genesis_header: &BlockHeader,
     state_manager: &StateManager<BS>,
@@ -87,6 +126,7 @@ where
     BS: Blockstore + Send + Sync,
 {
     // Load genesis state into the database and get the Cid
+    let (_, reader) = car_v1_payload(reader).await?;
     let genesis_cids: Vec<Cid> = load_car(db, reader).await?;
     if genesis_cids.len() != 1 {
         panic!("Invalid Genesis. Genesis Tipset must have only 1 Block.");
@@ -101,28 +141,63 @@ where
 
 /// Import a chain from a CAR file. If the snapshot boolean is set, it will not
 /// verify the chain state and instead accept the largest height as genesis.
+///
+/// The path may also be a directory or a pattern matching the file names of
+/// a snapshot sharded into several CAR files, such as
+/// `/snapshots/shard-*.car.zst`. The contents of the files are unioned, and
+/// they must all declare the same roots, those of the head tipset.
+#[tracing::instrument(skip(sm))]
 pub async fn import_chain<DB>(
     sm: &Arc<StateManager<DB>>,
     path: &str,
     skip_load: bool,
+    write_config: &BufferedWriteConfig,
 ) -> anyhow::Result<()>
 where
     DB: Blockstore + Clone + Send + Sync + 'static,
 {
-    let is_remote_file: bool = path.starts_with("http://") || path.starts_with("https://");
+    let is_remote_file: bool =
+        path.starts_with("http://") || path.starts_with("https://") || is_object_store_url(path);
 
     info!("Importing chain from snapshot at: {path}");
     // start import
     let stopwatch = time::Instant::now();
     let (cids, n_records) = if is_remote_file {
-        info!("Downloading file...");
-        let url = Url::parse(path)?;
-        let reader = get_fetch_progress_from_url(&url).await?;
-        load_and_retrieve_header(sm.blockstore().clone(), reader, skip_load).await?
+        load_snapshot_file(sm, path, skip_load, write_config).await?
     } else {
-        info!("Reading file...");
-        let reader = get_fetch_progress_from_file(&path).await?;
-        load_and_retrieve_header(sm.blockstore().clone(), reader, skip_load).await?
+        let files = snapshot_files(path)?;
+        // The roots are read from the CAR headers of all the shards before
+        // loading any, so that mismatched shards leave nothing behind
+        let mut roots: Option<(Vec<Cid>, &Path)> = None;
+        // Known without loading for indexed files only
+        let mut indexed_records = Some(0);
+        for file in &files {
+            let (cids, n) =
+                load_snapshot_file(sm, &file.display().to_string(), true, write_config).await?;
+            let (expected, first) = roots.get_or_insert_with(|| (cids.clone(), file.as_path()));
+            if *expected != cids {
+                bail!(
+                    "{} and {} declare different roots, they aren't shards of the same snapshot",
+                    first.display(),
+                    file.display()
+                );
+            }
+            indexed_records = indexed_records.zip(n).map(|(total, n)| total + n);
+        }
+        let (cids, _) = roots.context("no snapshot file")?;
+        let n_records = if skip_load {
+            indexed_records
+        } else {
+            let mut total = 0;
+            for file in &files {
+                let (_, n) =
+                    load_snapshot_file(sm, &file.display().to_string(), false, write_config)
+                        .await?;
+                total += n.unwrap_or_default();
+            }
+            Some(total)
+        };
+        (cids, n_records)
     };
 
     info!(
@@ -136,6 +211,7 @@ where
         meta.sync()?;
     }
 
+    // Fails if the roots aren't the blocks of a tipset
     let ts = sm.chain_store().tipset_from_keys(&TipsetKeys::new(cids))?;
 
     if !skip_load {
@@ -179,38 +255,160 @@ where
     Ok(())
 }
 
-/// Loads car file into database, and returns the block header CIDs from the CAR
-/// header.
+/// Loads a CAR file, local or remote, into the store, returning its roots and
+/// number of records.
+async fn load_snapshot_file<DB>(
+    sm: &Arc<StateManager<DB>>,
+    path: &str,
+    skip_load: bool,
+    write_config: &BufferedWriteConfig,
+) -> anyhow::Result<(Vec<Cid>, Option<usize>)>
+where
+    DB: Blockstore + Clone + Send + Sync + 'static,
+{
+    let is_remote_file: bool = path.starts_with("http://") || path.starts_with("https://");
+    let result = if is_object_store_url(path) {
+        info!("Downloading object...");
+        let reader = get_fetch_progress_from_object_store(path).await?;
+        load_and_retrieve_header(sm.blockstore().clone(), reader, skip_load, write_config).await?
+    } else if is_remote_file {
+        info!("Downloading file...");
+        let url = Url::parse(path)?;
+        let reader = get_fetch_progress_from_url(&url).await?;
+        load_and_retrieve_header(sm.blockstore().clone(), reader, skip_load, write_config).await?
+    } else if let Some(footer) = ForestCarFooter::read(&std::fs::File::open(path)?)? {
+        info!("Reading indexed file...");
+        if skip_load {
+            // The roots and the number of blocks are read without
+            // decompressing the whole archive
+            let car = ForestCarReader::open(path)?;
+            (car.roots().to_vec(), Some(car.len() as usize))
+        } else {
+            let reader = get_fetch_progress_from_file(&path).await?;
+            let (cids, n_records) =
+                load_and_retrieve_header(sm.blockstore().clone(), reader, skip_load, write_config)
+                    .await?;
+            if n_records != Some(footer.entry_count as usize) {
+                bail!(
+                    "Loaded {} records while the snapshot index has {}",
+                    n_records.unwrap_or_default(),
+                    footer.entry_count
+                );
+            }
+            (cids, n_records)
+        }
+    } else {
+        info!("Reading file...");
+        let reader = get_fetch_progress_from_file(&path).await?;
+        load_and_retrieve_header(sm.blockstore().clone(), reader, skip_load, write_config).await?
+    };
+    Ok(result)
+}
+
+/// Returns the CAR files of a snapshot: the `.car` and `.car.zst` files of a
+/// directory, the files matching a pattern with `*` and `?` wildcards in its
+/// file name, or the given file.
+pub fn snapshot_files(path: &str) -> anyhow::Result<Vec<PathBuf>> {
+    let path = Path::new(path);
+    let (dir, pattern) = if path.is_dir() {
+        (path, None)
+    } else {
+        match path.file_name().and_then(|name| name.to_str()) {
+            Some(pattern) if pattern.contains(['*', '?']) => (
+                path.parent()
+                    .filter(|dir| !dir.as_os_str().is_empty())
+                    .unwrap_or(Path::new(".")),
+                Some(pattern),
+            ),
+            _ => return Ok(vec![path.to_owned()]),
+        }
+    };
+    let mut files = vec![];
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let Some(name) = entry.file_name().to_str().map(str::to_owned) else {
+            continue;
+        };
+        let matches = match pattern {
+            Some(pattern) => wildcard_match(pattern, &name),
+            None => name.ends_with(".car") || name.ends_with(".car.zst"),
+        };
+        if matches && entry.file_type()?.is_file() {
+            files.push(entry.path());
+        }
+    }
+    if files.is_empty() {
+        bail!("no snapshot file matches {}", path.display());
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Matches a file name against a pattern where `*` stands for any sequence of
+/// characters and `?` for any single character.
+fn wildcard_match(pattern: &str, name: &str) -> bool {
+    let (pattern, name): (Vec<_>, Vec<_>) = (pattern.chars().collect(), name.chars().collect());
+    // Where to resume after the last `*`: the position in the pattern after
+    // it, and the position in the name it matches up to
+    let (mut p, mut n, mut star) = (0, 0, None);
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p + 1, n));
+                p += 1;
+            }
+            Some(c) if *c == '?' || *c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                Some((star_p, star_n)) => {
+                    p = star_p;
+                    n = star_n + 1;
+                    star = Some((star_p, star_n + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+/// Loads car file, CARv1 or CARv2, into database, and returns the block
+/// header CIDs from the CAR header.
 async fn load_and_retrieve_header<DB, R>(
     store: DB,
-    mut reader: R,
+    reader: R,
     skip_load: bool,
+    write_config: &BufferedWriteConfig,
 ) -> anyhow::Result<(Vec<Cid>, Option<usize>)>
 where
     DB: Blockstore + Send + Sync + 'static,
     R: AsyncRead + Send + Unpin,
 {
+    let (_, mut reader) = car_v1_payload(reader).await?;
     let result = if skip_load {
         (CarReader::new(&mut reader).await?.header.roots, None)
     } else {
-        let (roots, n_records) = forest_load_car(store, &mut reader).await?;
+        let (roots, n_records) = forest_load_car(store, &mut reader, write_config).await?;
         (roots, Some(n_records))
     };
 
     Ok(result)
 }
 
-pub async fn forest_load_car<DB, R>(store: DB, reader: R) -> anyhow::Result<(Vec<Cid>, usize)>
+pub async fn forest_load_car<DB, R>(
+    store: DB,
+    reader: R,
+    write_config: &BufferedWriteConfig,
+) -> anyhow::Result<(Vec<Cid>, usize)>
 where
     R: futures::AsyncRead + Send + Unpin,
     DB: Blockstore + Send + Sync + 'static,
 {
-    // 1GB
-    const BUFFER_CAPCITY_BYTES: usize = 1024 * 1024 * 1024;
-
     let (tx, rx) = flume::bounded(100);
-    let write_task =
-        tokio::spawn(async move { store.buffered_write(rx, BUFFER_CAPCITY_BYTES).await });
+    let write_config = write_config.clone();
+    let write_task = tokio::spawn(async move { store.buffered_write(rx, &write_config).await });
     let mut car_reader = CarReader::new(reader).await?;
     let mut n_records = 0;
     while let Some(block) = car_reader.next_block().await? {
@@ -222,3 +420,36 @@ where
     write_task.await??;
     Ok((car_reader.header.roots, n_records))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wildcard_match_file_names() {
+        assert!(wildcard_match("shard-*.car.zst", "shard-0001.car.zst"));
+        assert!(wildcard_match("shard-*.car.zst", "shard-.car.zst"));
+        assert!(wildcard_match("shard-?.car", "shard-1.car"));
+        assert!(wildcard_match("*", "anything"));
+        assert!(wildcard_match("*a*b", "xxaxxab"));
+        assert!(!wildcard_match("shard-?.car", "shard-10.car"));
+        assert!(!wildcard_match("shard-*.car", "shard-1.car.zst"));
+        assert!(!wildcard_match("*a*b", "xxaxxa"));
+    }
+
+    #[test]
+    fn snapshot_files_of_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["b.car.zst", "a.car", "notes.txt"] {
+            std::fs::write(dir.path().join(name), []).unwrap();
+        }
+        let files = snapshot_files(&dir.path().display().to_string()).unwrap();
+        assert_eq!(
+            files,
+            vec![dir.path().join("a.car"), dir.path().join("b.car.zst")]
+        );
+        let files = snapshot_files(&dir.path().join("*.txt").display().to_string()).unwrap();
+        assert_eq!(files, vec![dir.path().join("notes.txt")]);
+        snapshot_files(&dir.path().join("*.json").display().to_string()).unwrap_err();
+    }
+}
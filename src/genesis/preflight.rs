@@ -0,0 +1,227 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Estimation of what importing a snapshot takes, before any of it is
+//! written. The records at the start of the snapshot are sampled to tell the
+//! average size of a record and how many of them fit in the file, so that a
+//! lack of disk space shows up before the import rather than hours into it.
+
+use std::{
+    ffi::CString,
+    fmt, io,
+    path::Path,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    task::{Context, Poll},
+};
+
+use crate::utils::car::{car_v1_payload, forest::ForestCarFooter};
+use async_compression::futures::bufread::ZstdDecoder;
+use cid::Cid;
+use futures::{io::BufReader, AsyncRead, AsyncReadExt, AsyncSeekExt};
+use fvm_ipld_car::CarReader;
+use human_repr::HumanCount;
+
+// https://github.com/facebook/zstd/blob/dev/doc/zstd_compression_format.md#zstandard-frames
+const ZSTD_MAGIC_HEADER: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+/// Uncompressed bytes of records sampled from the start of the snapshot
+const SAMPLE_BYTES: u64 = 64 * 1024 * 1024;
+
+/// Space used by the database for each record on top of its data: the key,
+/// which is the CID, and the index entries.
+const DB_OVERHEAD_PER_RECORD: u64 = 80;
+
+/// What importing a snapshot is expected to take
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImportEstimate {
+    pub roots: Vec<Cid>,
+    /// Version of the CAR format
+    pub car_version: u64,
+    pub compressed: bool,
+    /// Size of the snapshot file
+    pub file_size: u64,
+    /// Number of records sampled
+    pub sampled_records: u64,
+    /// Number of records in the snapshot
+    pub records: u64,
+    /// True if the number of records is exact rather than estimated, when the
+    /// whole snapshot was sampled or it is indexed
+    pub exact: bool,
+    /// Bytes the database is expected to grow by
+    pub db_size: u64,
+}
+
+impl fmt::Display for ImportEstimate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "CARv{} file of {}{}",
+            self.car_version,
+            self.file_size.human_count_bytes(),
+            if self.compressed {
+                ", zstd compressed"
+            } else {
+                ""
+            }
+        )?;
+        writeln!(
+            f,
+            "Roots: {}",
+            self.roots
+                .iter()
+                .map(Cid::to_string)
+                .collect::<Vec<_>>()
+                .join(", ")
+        )?;
+        writeln!(
+            f,
+            "Records: {}{}",
+            self.records,
+            if self.exact {
+                String::new()
+            } else {
+                format!(" (estimated from {} sampled)", self.sampled_records)
+            }
+        )?;
+        write!(
+            f,
+            "Expected database growth: {}",
+            self.db_size.human_count_bytes()
+        )
+    }
+}
+
+/// Samples the records at the start of the snapshot at `path` to estimate
+/// the number of records and the growth of the database once imported.
+pub async fn estimate_import(path: &Path) -> anyhow::Result<ImportEstimate> {
+    let file_size = std::fs::metadata(path)?.len();
+    let footer = ForestCarFooter::read(&std::fs::File::open(path)?)?;
+
+    let mut file = async_fs::File::open(path).await?;
+    let compressed = {
+        let mut header = [0; ZSTD_MAGIC_HEADER.len()];
+        file.read_exact(&mut header).await?;
+        file.seek(io::SeekFrom::Start(0)).await?;
+        header == ZSTD_MAGIC_HEADER
+    };
+    // Bytes read from the file, compressed or not
+    let file_bytes = Arc::new(AtomicU64::new(0));
+    let file = CountingReader {
+        inner: file,
+        count: file_bytes.clone(),
+    };
+    let reader: Box<dyn AsyncRead + Send + Unpin> = if compressed {
+        Box::new(ZstdDecoder::new(BufReader::new(file)))
+    } else {
+        Box::new(BufReader::new(file))
+    };
+
+    let (car_version, payload) = car_v1_payload(reader).await?;
+    let mut car = CarReader::new(payload).await?;
+    let roots = car.header.roots.clone();
+    let (mut sampled_records, mut sampled_bytes) = (0, 0);
+    let mut complete = true;
+    while let Some(block) = car.next_block().await? {
+        sampled_records += 1;
+        sampled_bytes += block.data.len() as u64;
+        if sampled_bytes >= SAMPLE_BYTES {
+            complete = false;
+            break;
+        }
+    }
+
+    let records = match (complete, &footer) {
+        (true, _) => sampled_records,
+        (false, Some(footer)) => footer.entry_count,
+        (false, None) => {
+            let sampled_file_bytes = file_bytes.load(Ordering::Relaxed).max(1);
+            (sampled_records as u128 * file_size as u128 / sampled_file_bytes as u128) as u64
+        }
+    };
+    let average_size = sampled_bytes / sampled_records.max(1);
+    Ok(ImportEstimate {
+        roots,
+        car_version,
+        compressed,
+        file_size,
+        sampled_records,
+        records,
+        exact: complete || footer.is_some(),
+        db_size: records * (average_size + DB_OVERHEAD_PER_RECORD),
+    })
+}
+
+/// Returns the space available to the user on the file system of `path`, or
+/// of its closest existing ancestor as the database may not exist yet.
+pub fn available_space(path: &Path) -> io::Result<u64> {
+    let existing = path
+        .ancestors()
+        .find(|p| p.exists())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no existing ancestor"))?;
+    let c_path = CString::new(existing.as_os_str().to_string_lossy().as_bytes())?;
+    let mut stat = std::mem::MaybeUninit::<libc::statvfs>::uninit();
+    // SAFETY: the path is a valid C string and `stat` is only read if the
+    // call succeeds, in which case it is initialized
+    let stat = unsafe {
+        if libc::statvfs(c_path.as_ptr(), stat.as_mut_ptr()) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        stat.assume_init()
+    };
+    #[allow(clippy::unnecessary_cast)]
+    Ok(stat.f_bavail as u64 * stat.f_frsize as u64)
+}
+
+/// Counts the bytes read from the inner reader
+struct CountingReader<R> {
+    inner: R,
+    count: Arc<AtomicU64>,
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for CountingReader<R> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let poll = Pin::new(&mut self.inner).poll_read(cx, buf);
+        if let Poll::Ready(Ok(n)) = &poll {
+            self.count.fetch_add(*n as u64, Ordering::Relaxed);
+        }
+        poll
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::genesis::EXPORT_SR_40;
+
+    #[tokio::test]
+    async fn small_snapshots_are_sampled_whole() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("export40.car");
+        std::fs::write(&path, EXPORT_SR_40).unwrap();
+
+        let estimate = estimate_import(&path).await.unwrap();
+        assert!(estimate.exact);
+        assert!(!estimate.compressed);
+        assert_eq!(estimate.car_version, 1);
+        assert_eq!(estimate.file_size, EXPORT_SR_40.len() as u64);
+        assert_eq!(estimate.records, estimate.sampled_records);
+        assert!(estimate.records > 0);
+        assert!(!estimate.roots.is_empty());
+        assert!(estimate.db_size > 0);
+    }
+
+    #[test]
+    fn available_space_of_missing_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        let space = available_space(&dir.path().join("not/created/yet")).unwrap();
+        assert_eq!(space, available_space(dir.path()).unwrap());
+    }
+}
@@ -0,0 +1,281 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Health endpoints for orchestrators such as Kubernetes:
+//! - `/livez`: the node is running and its chain is not stalled,
+//! - `/readyz`: the node is synced, its RPC server is up and its database
+//!   writable, so it can serve requests,
+//! - `/healthz`: both of the above.
+//!
+//! The endpoints answer with `200 OK` or `503 Service Unavailable`, and with
+//! the details of every check as JSON when the `verbose` query parameter is
+//! set.
+
+use std::{
+    net::{SocketAddr, TcpListener},
+    sync::Arc,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use crate::chain::ChainStore;
+use crate::db::Store;
+use crate::shim::clock::ChainEpoch;
+use ahash::HashMap;
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use fvm_ipld_blockstore::Blockstore;
+use parking_lot::Mutex;
+use serde::Serialize;
+
+/// The node is considered synced when its head is at most this number of
+/// epochs behind the epoch expected from the wall clock.
+const MAX_EPOCHS_BEHIND: ChainEpoch = 5;
+
+/// The chain is considered stalled when its head hasn't changed for this
+/// number of epochs.
+const MAX_STALLED_EPOCHS: u64 = 30;
+
+const RPC_CONNECT_TIMEOUT: Duration = Duration::from_secs(1);
+
+const HEALTHCHECK_DB_KEY: &str = "/health/check";
+
+pub struct HealthCheckState<DB> {
+    chain_store: Arc<ChainStore<DB>>,
+    db: DB,
+    /// `None` when the RPC server is disabled
+    rpc_address: Option<SocketAddr>,
+    genesis_timestamp: u64,
+    block_delay: u64,
+    /// Epoch of the head and the time it was first seen at, from the start
+    /// of the node
+    head_progress: Mutex<(ChainEpoch, Instant)>,
+}
+
+impl<DB> HealthCheckState<DB>
+where
+    DB: Blockstore + Store + Clone + Send + Sync + 'static,
+{
+    pub fn new(
+        chain_store: Arc<ChainStore<DB>>,
+        db: DB,
+        rpc_address: Option<SocketAddr>,
+        block_delay: u64,
+    ) -> anyhow::Result<Self> {
+        anyhow::ensure!(block_delay > 0, "the block delay must be positive");
+        let genesis_timestamp = chain_store.genesis()?.timestamp();
+        let head_epoch = chain_store.heaviest_tipset().epoch();
+        Ok(Self {
+            chain_store,
+            db,
+            rpc_address,
+            genesis_timestamp,
+            block_delay,
+            head_progress: Mutex::new((head_epoch, Instant::now())),
+        })
+    }
+
+    fn now(&self) -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs()
+    }
+
+    fn check_sync(&self) -> Check {
+        let head_epoch = self.chain_store.heaviest_tipset().epoch();
+        let expected_epoch =
+            (self.now().saturating_sub(self.genesis_timestamp) / self.block_delay) as ChainEpoch;
+        let behind = expected_epoch - head_epoch;
+        if behind <= MAX_EPOCHS_BEHIND {
+            Check::ok("sync", format!("head at epoch {head_epoch}"))
+        } else {
+            Check::failed(
+                "sync",
+                format!("head at epoch {head_epoch}, {behind} epochs behind"),
+            )
+        }
+    }
+
+    /// Fails when the head hasn't changed for a while, be the node catching
+    /// up with the network or synced
+    fn check_head_progress(&self) -> Check {
+        let head_epoch = self.chain_store.heaviest_tipset().epoch();
+        let mut progress = self.head_progress.lock();
+        if progress.0 != head_epoch {
+            *progress = (head_epoch, Instant::now());
+        }
+        let stalled = progress.1.elapsed().as_secs();
+        if stalled > MAX_STALLED_EPOCHS * self.block_delay {
+            Check::failed(
+                "chain_head",
+                format!("head at epoch {head_epoch} hasn't changed for {stalled}s"),
+            )
+        } else {
+            Check::ok("chain_head", format!("head at epoch {head_epoch}"))
+        }
+    }
+
+    async fn check_rpc(&self) -> Check {
+        let Some(rpc_address) = self.rpc_address else {
+            return Check::ok("rpc", "disabled".into());
+        };
+        match tokio::time::timeout(
+            RPC_CONNECT_TIMEOUT,
+            tokio::net::TcpStream::connect(rpc_address),
+        )
+        .await
+        {
+            Ok(Ok(_)) => Check::ok("rpc", format!("listening on {rpc_address}")),
+            Ok(Err(e)) => Check::failed("rpc", format!("cannot connect to {rpc_address}: {e}")),
+            Err(_) => Check::failed("rpc", format!("timed out connecting to {rpc_address}")),
+        }
+    }
+
+    async fn check_db(&self) -> Check {
+        let db = self.db.clone();
+        let value = self.now().to_be_bytes();
+        let result = tokio::task::spawn_blocking(move || {
+            db.write(HEALTHCHECK_DB_KEY, value)?;
+            anyhow::ensure!(
+                db.read(HEALTHCHECK_DB_KEY)?.as_deref() == Some(&value[..]),
+                "read back a different value"
+            );
+            Ok(())
+        })
+        .await;
+        match result {
+            Ok(Ok(())) => Check::ok("db", "writable".into()),
+            Ok(Err(e)) => Check::failed("db", format!("not writable: {e}")),
+            Err(e) => Check::failed("db", format!("check failed: {e}")),
+        }
+    }
+
+    fn liveness_checks(&self) -> Vec<Check> {
+        vec![self.check_head_progress()]
+    }
+
+    async fn readiness_checks(&self) -> Vec<Check> {
+        vec![
+            self.check_sync(),
+            self.check_rpc().await,
+            self.check_db().await,
+        ]
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct Check {
+    name: &'static str,
+    ok: bool,
+    message: String,
+}
+
+impl Check {
+    fn ok(name: &'static str, message: String) -> Self {
+        Self {
+            name,
+            ok: true,
+            message,
+        }
+    }
+
+    fn failed(name: &'static str, message: String) -> Self {
+        Self {
+            name,
+            ok: false,
+            message,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Report {
+    ok: bool,
+    checks: Vec<Check>,
+}
+
+fn respond(checks: Vec<Check>, params: &HashMap<String, String>) -> Response {
+    let ok = checks.iter().all(|check| check.ok);
+    let status = if ok {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    if params.contains_key("verbose") {
+        (status, Json(Report { ok, checks })).into_response()
+    } else {
+        let body = if ok { "OK" } else { "Service Unavailable" };
+        (status, body).into_response()
+    }
+}
+
+async fn livez<DB>(
+    State(state): State<Arc<HealthCheckState<DB>>>,
+    Query(params): Query<HashMap<String, String>>,
+) -> Response
+where
+    DB: Blockstore + Store + Clone + Send + Sync + 'static,
+{
+    respond(state.liveness_checks(), &params)
+}
+
+async fn readyz<DB>(
+    State(state): State<Arc<HealthCheckState<DB>>>,
+    Query(params): Query<HashMap<String, String>>,
+) -> Response
+where
+    DB: Blockstore + Store + Clone + Send + Sync + 'static,
+{
+    respond(state.readiness_checks().await, &params)
+}
+
+async fn healthz<DB>(
+    State(state): State<Arc<HealthCheckState<DB>>>,
+    Query(params): Query<HashMap<String, String>>,
+) -> Response
+where
+    DB: Blockstore + Store + Clone + Send + Sync + 'static,
+{
+    let mut checks = state.liveness_checks();
+    checks.extend(state.readiness_checks().await);
+    respond(checks, &params)
+}
+
+/// Serves the health endpoints until the listener fails.
+pub async fn init_healthcheck_server<DB>(
+    listener: TcpListener,
+    state: HealthCheckState<DB>,
+) -> anyhow::Result<()>
+where
+    DB: Blockstore + Store + Clone + Send + Sync + 'static,
+{
+    let app = Router::new()
+        .route("/livez", get(livez::<DB>))
+        .route("/readyz", get(readyz::<DB>))
+        .route("/healthz", get(healthz::<DB>))
+        .with_state(Arc::new(state));
+    let server = axum::Server::from_tcp(listener)?.serve(app.into_make_service());
+    Ok(server.await?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn failed_checks_are_unavailable() {
+        let params = HashMap::default();
+        let ok = respond(vec![Check::ok("a", "".into())], &params);
+        assert_eq!(ok.status(), StatusCode::OK);
+        let failed = respond(
+            vec![Check::ok("a", "".into()), Check::failed("b", "".into())],
+            &params,
+        );
+        assert_eq!(failed.status(), StatusCode::SERVICE_UNAVAILABLE);
+    }
+}
@@ -7,6 +7,7 @@ pub mod fvm3;
 mod instrumented_kernel;
 #[cfg(feature = "instrumented_kernel")]
 mod metrics;
+mod trace;
 mod vm;
 
 use crate::shim::{
@@ -16,6 +17,9 @@ use crate::shim::{
 use fil_actor_interface::account;
 use fvm_ipld_blockstore::Blockstore;
 
+pub use self::trace::{
+    CallTrace, ExecutionTrace, ExecutionTraceStore, GasTrace, TraceSampling, WatchedActors,
+};
 pub use self::vm::*;
 
 /// returns the public key type of address (`BLS`/`SECP256K1`) of an account
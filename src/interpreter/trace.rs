@@ -0,0 +1,499 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Execution traces of the messages applied by the VM. The flat event stream
+//! recorded by the FVM when tracing is enabled is turned into a tree of calls,
+//! each with the gas charged while it was running and how it returned.
+
+use std::num::NonZeroUsize;
+
+use crate::shim::{
+    address::Address, clock::ChainEpoch, econ::TokenAmount, executor::ApplyRet,
+    state_tree::StateTree,
+};
+use ahash::HashSet;
+use cid::Cid;
+use fvm_ipld_blockstore::Blockstore;
+use lru::LruCache;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+
+/// Execution trace of a single message
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ExecutionTrace {
+    #[serde(with = "crate::json::cid")]
+    pub msg_cid: Cid,
+    pub epoch: ChainEpoch,
+    /// Gas charged outside of the invocation, e.g. for the message inclusion
+    pub gas_charges: Vec<GasTrace>,
+    /// The invocation of the receiver, missing if the message failed before
+    /// it, e.g. because of an invalid sequence
+    pub invocation: Option<CallTrace>,
+}
+
+/// A call from one actor to another, and the calls it made in turn
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct CallTrace {
+    #[serde(with = "crate::json::address::json")]
+    pub from: Address,
+    #[serde(with = "crate::json::address::json")]
+    pub to: Address,
+    pub method: u64,
+    #[serde(with = "crate::json::token_amount::json")]
+    pub value: TokenAmount,
+    pub exit_code: u32,
+    /// Set when the call failed in a syscall, before reaching the callee
+    pub error: Option<String>,
+    pub gas_charges: Vec<GasTrace>,
+    pub subcalls: Vec<CallTrace>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct GasTrace {
+    pub name: String,
+    pub total_gas: u64,
+}
+
+/// Execution event, common to the supported FVM versions
+enum Event {
+    GasCharge(GasTrace),
+    Call {
+        from: Address,
+        to: Address,
+        method: u64,
+        value: TokenAmount,
+    },
+    Return {
+        exit_code: u32,
+        error: Option<String>,
+    },
+}
+
+impl ExecutionTrace {
+    /// Builds the trace from the result of a message applied with tracing
+    /// enabled, empty otherwise.
+    pub fn new(msg_cid: Cid, epoch: ChainEpoch, ret: &ApplyRet) -> Self {
+        let events: Vec<Event> = match ret {
+            ApplyRet::V2(ret) => ret
+                .exec_trace
+                .iter()
+                .filter_map(|event| {
+                    use fvm::trace::ExecutionEvent;
+                    Some(match event {
+                        ExecutionEvent::GasCharge(charge) => Event::GasCharge(GasTrace {
+                            name: charge.name.to_string(),
+                            total_gas: charge.total().round_up() as u64,
+                        }),
+                        ExecutionEvent::Call {
+                            from,
+                            to,
+                            method,
+                            value,
+                            ..
+                        } => Event::Call {
+                            from: Address::new_id(*from),
+                            to: Address::from(*to),
+                            method: *method,
+                            value: TokenAmount::from(value),
+                        },
+                        ExecutionEvent::CallReturn(_) => Event::Return {
+                            exit_code: 0,
+                            error: None,
+                        },
+                        ExecutionEvent::CallAbort(exit_code) => Event::Return {
+                            exit_code: exit_code.value(),
+                            error: None,
+                        },
+                        ExecutionEvent::CallError(error) => Event::Return {
+                            exit_code: 0,
+                            error: Some(error.to_string()),
+                        },
+                        #[allow(unreachable_patterns)]
+                        _ => return None,
+                    })
+                })
+                .collect(),
+            ApplyRet::V3(ret) => ret
+                .exec_trace
+                .iter()
+                .filter_map(|event| {
+                    use fvm3::trace::ExecutionEvent;
+                    Some(match event {
+                        ExecutionEvent::GasCharge(charge) => Event::GasCharge(GasTrace {
+                            name: charge.name.to_string(),
+                            total_gas: charge.total().round_up(),
+                        }),
+                        ExecutionEvent::Call {
+                            from,
+                            to,
+                            method,
+                            value,
+                            ..
+                        } => Event::Call {
+                            from: Address::new_id(*from),
+                            to: Address::from(*to),
+                            method: *method,
+                            value: TokenAmount::from(value),
+                        },
+                        ExecutionEvent::CallReturn(exit_code, _) => Event::Return {
+                            exit_code: exit_code.value(),
+                            error: None,
+                        },
+                        ExecutionEvent::CallError(error) => Event::Return {
+                            exit_code: 0,
+                            error: Some(error.to_string()),
+                        },
+                        _ => return None,
+                    })
+                })
+                .collect(),
+        };
+        Self::from_events(msg_cid, epoch, events)
+    }
+
+    fn from_events(msg_cid: Cid, epoch: ChainEpoch, events: Vec<Event>) -> Self {
+        let mut trace = Self {
+            msg_cid,
+            epoch,
+            gas_charges: vec![],
+            invocation: None,
+        };
+        // Calls that have not returned yet, the innermost last
+        let mut stack: Vec<CallTrace> = vec![];
+        for event in events {
+            match event {
+                Event::GasCharge(charge) => match stack.last_mut() {
+                    Some(call) => call.gas_charges.push(charge),
+                    None => trace.gas_charges.push(charge),
+                },
+                Event::Call {
+                    from,
+                    to,
+                    method,
+                    value,
+                } => stack.push(CallTrace {
+                    from,
+                    to,
+                    method,
+                    value,
+                    exit_code: 0,
+                    error: None,
+                    gas_charges: vec![],
+                    subcalls: vec![],
+                }),
+                Event::Return { exit_code, error } => {
+                    let Some(mut call) = stack.pop() else {
+                        continue;
+                    };
+                    call.exit_code = exit_code;
+                    call.error = error;
+                    match stack.last_mut() {
+                        Some(caller) => caller.subcalls.push(call),
+                        None => trace.invocation = Some(call),
+                    }
+                }
+            }
+        }
+        trace
+    }
+
+    /// Returns `true` if the actor sent or received any of the calls.
+    pub fn involves(&self, actor: &Address) -> bool {
+        fn visit(call: &CallTrace, actor: &Address) -> bool {
+            call.from == *actor
+                || call.to == *actor
+                || call.subcalls.iter().any(|call| visit(call, actor))
+        }
+        self.invocation
+            .as_ref()
+            .map(|call| visit(call, actor))
+            .unwrap_or_default()
+    }
+}
+
+/// Which of the executed messages are traced. Tracing every message slows the
+/// execution down, so production nodes trace a sample of the tipsets, and the
+/// messages of the actors of interest.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TraceSampling {
+    /// Traces the tipsets at the epochs multiple of it, `1` tracing all of
+    /// them and `0` none
+    pub interval: u64,
+    /// Traces the messages sent from or to these addresses, by their key or
+    /// their ID address, in any tipset. The calls reaching them from other
+    /// messages are known once these are executed, so they are caught only in
+    /// the tipsets traced anyway.
+    pub watched: Vec<Address>,
+}
+
+impl Default for TraceSampling {
+    fn default() -> Self {
+        Self {
+            interval: 1,
+            watched: vec![],
+        }
+    }
+}
+
+impl TraceSampling {
+    /// Returns `true` if all the messages of the tipset at `epoch` are traced.
+    pub fn samples_epoch(&self, epoch: ChainEpoch) -> bool {
+        self.interval > 0 && epoch.rem_euclid(self.interval as ChainEpoch) == 0
+    }
+
+    /// Returns the watched actors with both their key and ID addresses in the
+    /// state `state_root`, so that the messages and the calls reaching them
+    /// by either are caught. The actors missing from the state are known by
+    /// the configured address only.
+    pub fn resolve_watched<DB: Blockstore + Clone>(
+        &self,
+        store: &DB,
+        state_root: &Cid,
+    ) -> anyhow::Result<WatchedActors> {
+        let mut addresses: HashSet<Address> = self.watched.iter().copied().collect();
+        if self.watched.is_empty() {
+            return Ok(WatchedActors(addresses));
+        }
+        let state = StateTree::new_from_root(store.clone(), state_root)?;
+        for address in &self.watched {
+            if let Some(id) = state.lookup_id(address)? {
+                addresses.insert(Address::new_id(id));
+            }
+            // Only the accounts and the actors with a delegated address have
+            // a key address
+            if let Ok(key) = super::resolve_to_key_addr(&state, store, address) {
+                addresses.insert(key);
+            }
+        }
+        Ok(WatchedActors(addresses))
+    }
+}
+
+/// Addresses of the watched actors in a given state, see
+/// [`TraceSampling::resolve_watched`]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct WatchedActors(HashSet<Address>);
+
+impl WatchedActors {
+    /// Returns `true` if the message is traced in any tipset.
+    pub fn watches_message(&self, from: &Address, to: &Address) -> bool {
+        self.0.contains(from) || self.0.contains(to)
+    }
+
+    /// Returns `true` if the trace involves a watched actor.
+    pub fn watches_trace(&self, trace: &ExecutionTrace) -> bool {
+        self.0.iter().any(|actor| trace.involves(actor))
+    }
+}
+
+/// Bounded store of the most recent execution traces
+pub struct ExecutionTraceStore {
+    traces: Mutex<LruCache<Cid, ExecutionTrace>>,
+    sampling: TraceSampling,
+}
+
+impl ExecutionTraceStore {
+    pub fn new(capacity: NonZeroUsize) -> Self {
+        Self {
+            traces: Mutex::new(LruCache::new(capacity)),
+            sampling: TraceSampling::default(),
+        }
+    }
+
+    /// Records only the traces selected by `sampling`, instead of all of them.
+    pub fn with_sampling(mut self, sampling: TraceSampling) -> Self {
+        self.sampling = sampling;
+        self
+    }
+
+    pub fn sampling(&self) -> &TraceSampling {
+        &self.sampling
+    }
+
+    pub fn insert(&self, trace: ExecutionTrace) {
+        self.traces.lock().put(trace.msg_cid, trace);
+    }
+
+    pub fn get(&self, msg_cid: &Cid) -> Option<ExecutionTrace> {
+        self.traces.lock().peek(msg_cid).cloned()
+    }
+
+    /// Returns up to `limit` traces involving the actor, most recent first.
+    pub fn involving(&self, actor: &Address, limit: usize) -> Vec<ExecutionTrace> {
+        self.traces
+            .lock()
+            .iter()
+            .map(|(_, trace)| trace)
+            .filter(|trace| trace.involves(actor))
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cid::multihash::{Code, MultihashDigest};
+    use nonzero_ext::nonzero;
+
+    use super::*;
+    use crate::db::MemoryDB;
+    use crate::shim::state_tree::{ActorState, StateTreeVersion};
+    use crate::utils::db::CborStoreExt;
+    use fil_actors_shared::v11::{builtin::HAMT_BIT_WIDTH, make_empty_map};
+    use fvm_shared3::ActorID;
+
+    fn cid(data: &[u8]) -> Cid {
+        Cid::new_v1(fvm_ipld_encoding::DAG_CBOR, Code::Blake2b256.digest(data))
+    }
+
+    fn empty_state() -> Cid {
+        StateTree::new(MemoryDB::default(), StateTreeVersion::V5)
+            .unwrap()
+            .flush()
+            .unwrap()
+    }
+
+    fn charge(name: &str) -> Event {
+        Event::GasCharge(GasTrace {
+            name: name.into(),
+            total_gas: 1,
+        })
+    }
+
+    fn call(from: u64, to: u64) -> Event {
+        Event::Call {
+            from: Address::new_id(from),
+            to: Address::new_id(to),
+            method: 2,
+            value: TokenAmount::default(),
+        }
+    }
+
+    fn ret(exit_code: u32) -> Event {
+        Event::Return {
+            exit_code,
+            error: None,
+        }
+    }
+
+    #[test]
+    fn events_are_nested() {
+        let trace = ExecutionTrace::from_events(
+            cid(b"msg"),
+            10,
+            vec![
+                charge("OnChainMessage"),
+                call(100, 1000),
+                charge("OnMethodInvocation"),
+                call(1000, 1001),
+                charge("OnBlockRead"),
+                ret(16),
+                call(1000, 1002),
+                ret(0),
+                ret(0),
+            ],
+        );
+        assert_eq!(trace.gas_charges.len(), 1);
+        let invocation = trace.invocation.as_ref().unwrap();
+        assert_eq!(invocation.to, Address::new_id(1000));
+        assert_eq!(invocation.gas_charges.len(), 1);
+        assert_eq!(invocation.subcalls.len(), 2);
+        assert_eq!(invocation.subcalls[0].exit_code, 16);
+        assert_eq!(invocation.subcalls[0].gas_charges[0].name, "OnBlockRead");
+        assert!(trace.involves(&Address::new_id(1002)));
+        assert!(!trace.involves(&Address::new_id(1003)));
+    }
+
+    #[test]
+    fn sampling_selects_epochs_and_actors() {
+        let sampling = TraceSampling {
+            interval: 10,
+            watched: vec![Address::new_id(1001)],
+        };
+        assert!(sampling.samples_epoch(20));
+        assert!(!sampling.samples_epoch(25));
+        assert!(!TraceSampling {
+            interval: 0,
+            watched: vec![]
+        }
+        .samples_epoch(20));
+        assert!(TraceSampling::default().samples_epoch(25));
+
+        let watched = sampling
+            .resolve_watched(&MemoryDB::default(), &empty_state())
+            .unwrap();
+        assert!(watched.watches_message(&Address::new_id(100), &Address::new_id(1001)));
+        assert!(!watched.watches_message(&Address::new_id(100), &Address::new_id(1000)));
+        let trace = ExecutionTrace::from_events(
+            cid(b"msg"),
+            25,
+            vec![call(100, 1000), call(1000, 1001), ret(0), ret(0)],
+        );
+        assert!(watched.watches_trace(&trace));
+    }
+
+    #[test]
+    fn watched_key_addresses_are_resolved_to_ids() {
+        let db = MemoryDB::default();
+        let key = Address::new_secp256k1(&[7; 65]).unwrap();
+        let id = 1001;
+        let mut address_map = make_empty_map::<_, ActorID>(&db, HAMT_BIT_WIDTH);
+        address_map.set(key.to_bytes().into(), id).unwrap();
+        let init = fil_actor_init_state::v11::State {
+            address_map: address_map.flush().unwrap(),
+            next_id: id + 1,
+            network_name: "test".into(),
+        };
+        let mut state = StateTree::new(db.clone(), StateTreeVersion::V5).unwrap();
+        state
+            .set_actor(
+                &Address::INIT_ACTOR,
+                ActorState::new(
+                    Cid::default(),
+                    db.put_cbor_default(&init).unwrap(),
+                    TokenAmount::default(),
+                    0,
+                    None,
+                ),
+            )
+            .unwrap();
+        let state_root = state.flush().unwrap();
+        let sampling = TraceSampling {
+            interval: 0,
+            watched: vec![key],
+        };
+
+        let watched = sampling.resolve_watched(&db, &state_root).unwrap();
+        assert!(watched.watches_message(&Address::new_id(id), &Address::new_id(1000)));
+        assert!(watched.watches_message(&key, &Address::new_id(1000)));
+        let trace = ExecutionTrace::from_events(
+            cid(b"msg"),
+            25,
+            vec![call(100, 1000), call(1000, id), ret(0), ret(0)],
+        );
+        assert!(watched.watches_trace(&trace));
+    }
+
+    #[test]
+    fn store_keeps_the_most_recent_traces() {
+        let store = ExecutionTraceStore::new(nonzero!(2usize));
+        for i in 0..3u8 {
+            store.insert(ExecutionTrace::from_events(
+                cid(&[i]),
+                i.into(),
+                vec![call(100, 1000 + i as u64), ret(0)],
+            ));
+        }
+        assert!(store.get(&cid(&[0])).is_none());
+        assert_eq!(store.get(&cid(&[2])).unwrap().epoch, 2);
+        let traces = store.involving(&Address::new_id(100), 10);
+        assert_eq!(
+            traces.iter().map(|trace| trace.epoch).collect::<Vec<_>>(),
+            [2, 1]
+        );
+    }
+}
@@ -83,6 +83,26 @@ pub trait RewardCalc: Send + Sync + 'static {
     ) -> Result<Option<Message>, anyhow::Error>;
 }
 
+/// How [`VM::dry_run`] applies a message.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DryRunKind {
+    /// As if included on chain: the sender is charged for the gas, and its
+    /// sequence and balance are checked.
+    Explicit,
+    /// Without charging gas to the sender nor checking its sequence, as for
+    /// calls to the actors that don't change the chain state.
+    Implicit,
+}
+
+/// Whether the FVM records the execution events of the messages, see
+/// [`crate::interpreter::ExecutionTrace`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum VMTrace {
+    Traced,
+    #[default]
+    NotTraced,
+}
+
 /// Interpreter which handles execution of state transitioning messages and
 /// returns receipts from the VM execution.
 pub enum VM<DB: Blockstore + 'static> {
@@ -114,6 +134,7 @@ where
         multi_engine: &MultiEngine,
         chain_config: Arc<ChainConfig>,
         timestamp: u64,
+        trace: VMTrace,
     ) -> Result<Self, anyhow::Error> {
         let network_version = chain_config.network_version(epoch);
         if network_version >= NetworkVersion::V18 {
@@ -128,6 +149,9 @@ where
             let mut context = config.for_epoch(epoch, timestamp, root);
             context.set_base_fee(base_fee.into());
             context.set_circulating_supply(circ_supply.into());
+            if trace == VMTrace::Traced {
+                context.enable_tracing();
+            }
             let fvm: fvm3::machine::DefaultMachine<DB, ForestExterns_v3<DB>> =
                 fvm3::machine::DefaultMachine::new(
                     &context,
@@ -153,6 +177,9 @@ where
             let mut context = config.for_epoch(epoch, root);
             context.set_base_fee(base_fee.into());
             context.set_circulating_supply(circ_supply.into());
+            if trace == VMTrace::Traced {
+                context.enable_tracing();
+            }
             let fvm: fvm::machine::DefaultMachine<DB, ForestExternsV2<DB>> =
                 fvm::machine::DefaultMachine::new(
                     &engine,
@@ -256,6 +283,7 @@ where
 
             let mut process_msg = |msg: &ChainMessage| -> Result<(), anyhow::Error> {
                 let cid = msg.cid()?;
+                let _span = tracing::debug_span!("apply_message", %cid).entered();
                 // Ensure no duplicate processing of a message
                 if processed.contains(&cid) {
                     return Ok(());
@@ -336,6 +364,15 @@ where
         }
     }
 
+    /// Executes a message meant to be thrown away rather than included on
+    /// chain. The resulting state isn't flushed, it's discarded with the VM.
+    pub fn dry_run(&mut self, msg: &ChainMessage, kind: DryRunKind) -> anyhow::Result<ApplyRet> {
+        match kind {
+            DryRunKind::Explicit => self.apply_message(msg),
+            DryRunKind::Implicit => self.apply_implicit_message(msg.message()),
+        }
+    }
+
     /// Applies the state transition for a single message.
     /// Returns `ApplyRet` structure which contains the message receipt and some
     /// meta data.
@@ -4,6 +4,7 @@
 mod cid_hashset;
 mod error;
 pub mod json;
+pub mod resolve;
 pub mod selector;
 pub mod util;
 
@@ -26,6 +27,7 @@ pub use libipld_core::serde::{from_ipld, to_ipld};
 mod tests {
     mod cbor_test;
     mod json_tests;
+    mod resolve_tests;
     mod selector_explore;
     mod selector_gen_tests;
     mod walk_tests;
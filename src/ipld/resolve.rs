@@ -0,0 +1,140 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Resolution of IPLD paths against a blockstore, such as
+//! `bafy2.../Parents/0/Messages`. The segments index the lists and maps of
+//! the nodes, links being loaded transparently. Block headers being encoded as
+//! lists, their fields can also be given by name.
+
+use std::str::FromStr;
+
+use crate::blocks::BlockHeader;
+use anyhow::Context;
+use async_trait::async_trait;
+use cid::Cid;
+use fvm_ipld_blockstore::Blockstore;
+use fvm_ipld_encoding::{from_slice, Cbor};
+
+use super::{lookup_segment, selector::LinkResolver, Ipld};
+
+/// Fields of the block headers, in the order of their encoding
+const BLOCK_HEADER_FIELDS: [&str; 16] = [
+    "Miner",
+    "Ticket",
+    "ElectionProof",
+    "BeaconEntries",
+    "WinPoStProof",
+    "Parents",
+    "ParentWeight",
+    "Height",
+    "ParentStateRoot",
+    "ParentMessageReceipts",
+    "Messages",
+    "BLSAggregate",
+    "Timestamp",
+    "BlockSig",
+    "ForkSignaling",
+    "ParentBaseFee",
+];
+
+/// Block loaded while resolving a path
+struct Block {
+    cid: Cid,
+    node: Ipld,
+    bytes: Vec<u8>,
+}
+
+impl Block {
+    /// Whether the block is a header, only decoded when a field is looked up
+    /// by name
+    fn is_header(&self) -> bool {
+        BlockHeader::unmarshal_cbor(&self.bytes).is_ok()
+    }
+}
+
+fn load_block<BS: Blockstore>(bs: &BS, cid: &Cid) -> anyhow::Result<Block> {
+    let bytes = bs
+        .get(cid)?
+        .with_context(|| format!("block {cid} not found"))?;
+    Ok(Block {
+        cid: *cid,
+        node: from_slice(&bytes)?,
+        bytes,
+    })
+}
+
+/// Resolves the path, starting with a CID, optionally prefixed with `/ipfs/`.
+/// Returns the node at the end of the path and the CID of the block holding
+/// it, a link at the end of the path being loaded too.
+pub fn resolve_path<BS: Blockstore>(bs: &BS, path: &str) -> anyhow::Result<(Cid, Ipld)> {
+    let mut segments = path
+        .trim_start_matches("/ipfs/")
+        .split('/')
+        .filter(|segment| !segment.is_empty());
+    let root = segments.next().context("empty path")?;
+    let mut block = load_block(bs, &Cid::from_str(root)?)?;
+    let mut node = block.node.clone();
+    // Whether the node is the root of the block, which the header field
+    // names apply to
+    let mut at_block_root = true;
+    for segment in segments {
+        if let Ipld::Link(cid) = node {
+            block = load_block(bs, &cid)?;
+            node = block.node.clone();
+            at_block_root = true;
+        }
+        let next = match lookup_segment(&node, segment) {
+            Some(next) => next,
+            None if at_block_root && block.is_header() => BLOCK_HEADER_FIELDS
+                .iter()
+                .position(|field| field.eq_ignore_ascii_case(segment))
+                .and_then(|index| lookup_segment(&node, &index.to_string()))
+                .with_context(|| format!("no field {segment} in block header {}", block.cid))?,
+            None => anyhow::bail!("no segment {segment} in block {}", block.cid),
+        };
+        node = next.clone();
+        at_block_root = false;
+    }
+    if let Ipld::Link(cid) = node {
+        block = load_block(bs, &cid)?;
+        node = block.node;
+    }
+    Ok((block.cid, node))
+}
+
+/// Error of the [`BlockstoreLinkResolver`] once it has loaded as many blocks
+/// as allowed
+pub const LOAD_LIMIT_REACHED: &str = "block load limit reached";
+
+/// Loads the links traversed by the selectors from a blockstore, up to a
+/// maximum number of blocks
+pub struct BlockstoreLinkResolver<BS> {
+    store: BS,
+    remaining_loads: usize,
+}
+
+impl<BS> BlockstoreLinkResolver<BS> {
+    pub fn new(store: BS, max_loads: usize) -> Self {
+        Self {
+            store,
+            remaining_loads: max_loads,
+        }
+    }
+}
+
+#[async_trait]
+impl<BS> LinkResolver for BlockstoreLinkResolver<BS>
+where
+    BS: Blockstore + Send + Sync,
+{
+    async fn load_link(&mut self, link: &Cid) -> Result<Option<Ipld>, String> {
+        if self.remaining_loads == 0 {
+            return Err(LOAD_LIMIT_REACHED.into());
+        }
+        self.remaining_loads -= 1;
+        match self.store.get(link).map_err(|e| e.to_string())? {
+            Some(bytes) => from_slice(&bytes).map(Some).map_err(|e| e.to_string()),
+            None => Ok(None),
+        }
+    }
+}
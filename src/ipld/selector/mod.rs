@@ -354,6 +354,30 @@ impl Selector {
         }
     }
 
+    /// Returns false if the selector holds a recursion without depth limit
+    pub fn is_bounded(&self) -> bool {
+        match self {
+            ExploreAll { next } | ExploreIndex { next, .. } | ExploreRange { next, .. } => {
+                next.is_bounded()
+            }
+            ExploreFields { fields } => fields.values().all(Selector::is_bounded),
+            ExploreRecursive {
+                sequence,
+                limit,
+                current,
+                ..
+            } => {
+                *limit != RecursionLimit::None
+                    && sequence.is_bounded()
+                    && current
+                        .as_ref()
+                        .map_or(true, |current| current.is_bounded())
+            }
+            ExploreUnion(selectors) => selectors.iter().all(Selector::is_bounded),
+            ExploreRecursiveEdge | Matcher => true,
+        }
+    }
+
     /// Returns true if `matcher`, false otherwise
     pub fn decide(&self) -> bool {
         match self {
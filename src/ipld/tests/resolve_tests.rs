@@ -0,0 +1,56 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use crate::db::MemoryDB;
+use crate::ipld::{
+    resolve::{resolve_path, BlockstoreLinkResolver, LOAD_LIMIT_REACHED},
+    selector::LinkResolver,
+    Ipld,
+};
+use crate::test_utils::ChainGenerator;
+use crate::utils::db::CborStoreExt;
+
+#[test]
+fn resolve_block_header_fields() {
+    let db = MemoryDB::default();
+    let mut generator = ChainGenerator::new();
+    let genesis = generator.genesis().clone();
+    let tipset = generator.mine(&genesis, &[1000], 2);
+    let genesis_cid = db.put_cbor_default(genesis.min_ticket_block()).unwrap();
+    let cid = db.put_cbor_default(tipset.min_ticket_block()).unwrap();
+    assert_eq!(genesis_cid, *genesis.min_ticket_block().cid());
+
+    // Links at the end of the path are loaded
+    let (block, node) = resolve_path(&db, &format!("{cid}/Parents/0")).unwrap();
+    assert_eq!(block, genesis_cid);
+    assert!(matches!(node, Ipld::List(fields) if fields.len() == 16));
+
+    let (block, node) = resolve_path(&db, &format!("/ipfs/{cid}/Height")).unwrap();
+    assert_eq!(block, cid);
+    assert_eq!(node, Ipld::Integer(3));
+    assert_eq!(
+        resolve_path(&db, &format!("{cid}/7")).unwrap(),
+        (cid, Ipld::Integer(3))
+    );
+    assert_eq!(
+        resolve_path(&db, &format!("{cid}/Parents/0/height")).unwrap(),
+        (genesis_cid, Ipld::Integer(0))
+    );
+
+    assert!(resolve_path(&db, &format!("{cid}/Unknown")).is_err());
+    assert!(resolve_path(&db, &format!("{cid}/Parents/5")).is_err());
+    assert!(resolve_path(&db, "").is_err());
+}
+
+#[tokio::test]
+async fn link_resolver_load_limit() {
+    let db = MemoryDB::default();
+    let cid = db.put_cbor_default(&(1, 2)).unwrap();
+    let mut resolver = BlockstoreLinkResolver::new(&db, 2);
+    assert!(resolver.load_link(&cid).await.unwrap().is_some());
+    assert!(resolver.load_link(&cid).await.unwrap().is_some());
+    assert_eq!(
+        resolver.load_link(&cid).await.unwrap_err(),
+        LOAD_LIMIT_REACHED
+    );
+}
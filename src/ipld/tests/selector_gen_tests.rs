@@ -116,6 +116,7 @@ fn gen_explore_recursive() {
         stop_at: None,
         current: None,
     };
+    assert!(expected.is_bounded());
 
     deserialize_and_check(test_json, expected);
 
@@ -133,6 +134,8 @@ fn gen_explore_recursive() {
         stop_at: None,
         current: None,
     };
+    assert!(!expected.is_bounded());
+    assert!(!ExploreUnion(vec![Matcher, expected.clone()]).is_bounded());
 
     deserialize_and_check(test_json, expected);
 }
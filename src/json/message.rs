@@ -107,6 +107,12 @@ pub mod json {
 
         use super::*;
 
+        /// Wrapper for serializing and de-serializing a `Message` vector from
+        /// JSON, `null` being an empty vector.
+        #[derive(Deserialize, Serialize)]
+        #[serde(transparent)]
+        pub struct MessageJsonVec(#[serde(with = "self")] pub Vec<Message>);
+
         pub fn serialize<S>(m: &[Message], serializer: S) -> Result<S::Ok, S::Error>
         where
             S: Serializer,
@@ -132,7 +138,7 @@ pub mod tests {
     use crate::shim::message::Message;
     use quickcheck_macros::quickcheck;
 
-    use super::json::{MessageJson, MessageJsonRef};
+    use super::json::{vec::MessageJsonVec, MessageJson, MessageJsonRef};
 
     #[quickcheck]
     fn message_roundtrip(message: Message) {
@@ -145,4 +151,10 @@ pub mod tests {
             assert_eq!(message, parsed.0)
         }
     }
+
+    #[test]
+    fn null_is_an_empty_vec() {
+        let MessageJsonVec(messages) = serde_json::from_str("null").unwrap();
+        assert!(messages.is_empty());
+    }
 }
@@ -54,6 +54,36 @@ pub mod json {
             Ok(None)
         }
     }
+
+    pub mod vec {
+        use serde::{self, Deserialize, Deserializer, Serialize, Serializer};
+
+        use super::*;
+
+        pub fn serialize<S>(v: &[TokenAmount], serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+        {
+            v.iter()
+                .map(|s| s.atto().to_string())
+                .collect::<Vec<_>>()
+                .serialize(serializer)
+        }
+
+        pub fn deserialize<'de, D>(deserializer: D) -> Result<Vec<TokenAmount>, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            let v: Vec<String> = Deserialize::deserialize(deserializer)?;
+            v.iter()
+                .map(|s| {
+                    Ok(TokenAmount::from_atto(
+                        BigInt::from_str(s).map_err(serde::de::Error::custom)?,
+                    ))
+                })
+                .collect()
+        }
+    }
 }
 
 #[cfg(test)]
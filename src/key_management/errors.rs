@@ -23,4 +23,7 @@ pub enum Error {
     Other(String),
     #[error("Could not convert from KeyInfo to Key")]
     KeyInfoConversion,
+    /// The encrypted key store waits for its passphrase
+    #[error("Keystore is locked, unlock it with `forest-cli wallet unlock`")]
+    Locked,
 }
@@ -133,12 +133,19 @@ pub struct KeyStore {
     key_info: HashMap<String, KeyInfo>,
     persistence: Option<PersistentKeyStore>,
     encryption: Option<EncryptedKeyStore>,
+    /// Directory of the encrypted keystore not decrypted yet, see
+    /// [`KeyStore::unlock`]
+    locked: Option<PathBuf>,
 }
 
 pub enum KeyStoreConfig {
     Memory,
     Persistent(PathBuf),
     Encrypted(PathBuf, String),
+    /// Encrypted keystore whose passphrase is not known yet. The keys are
+    /// unavailable until [`KeyStore::unlock`] succeeds, the entries put in
+    /// the meantime are only kept in memory.
+    Locked(PathBuf),
 }
 
 /// Persistent `KeyStore` in JSON clear text in `KEYSTORE_LOCATION`
@@ -178,6 +185,13 @@ impl KeyStore {
                 key_info: HashMap::new(),
                 persistence: None,
                 encryption: None,
+                locked: None,
+            }),
+            KeyStoreConfig::Locked(location) => Ok(Self {
+                key_info: HashMap::new(),
+                persistence: None,
+                encryption: None,
+                locked: Some(location),
             }),
             KeyStoreConfig::Persistent(location) => {
                 let file_path = location.join(KEYSTORE_NAME);
@@ -215,6 +229,7 @@ impl KeyStore {
                             key_info,
                             persistence: Some(PersistentKeyStore { file_path }),
                             encryption: None,
+                            locked: None,
                         })
                     }
                     Err(e) => {
@@ -227,6 +242,7 @@ impl KeyStore {
                                 key_info: HashMap::new(),
                                 persistence: Some(PersistentKeyStore { file_path }),
                                 encryption: None,
+                                locked: None,
                             })
                         } else {
                             Err(Error::Other(e.to_string()))
@@ -272,6 +288,7 @@ impl KeyStore {
                                     salt,
                                     encryption_key,
                                 }),
+                                locked: None,
                             })
                         } else {
                             // Existing encrypted keystore
@@ -303,6 +320,7 @@ impl KeyStore {
                                     salt,
                                     encryption_key,
                                 }),
+                                locked: None,
                             })
                         }
                     }
@@ -322,6 +340,7 @@ impl KeyStore {
                                 salt,
                                 encryption_key,
                             }),
+                            locked: None,
                         })
                     }
                 }
@@ -329,6 +348,39 @@ impl KeyStore {
         }
     }
 
+    /// Returns `true` if the keystore is encrypted and waits for its
+    /// passphrase.
+    pub fn is_locked(&self) -> bool {
+        self.locked.is_some()
+    }
+
+    /// Fails with [`Error::Locked`] if the keystore is locked, to be checked
+    /// before using or changing the keys.
+    pub fn ensure_unlocked(&self) -> Result<(), Error> {
+        match self.is_locked() {
+            true => Err(Error::Locked),
+            false => Ok(()),
+        }
+    }
+
+    /// Decrypts the locked keystore with the given passphrase. The entries
+    /// put while locked are kept only if the decrypted keystore doesn't
+    /// contain them.
+    pub fn unlock(&mut self, passphrase: String) -> Result<(), Error> {
+        let location = self
+            .locked
+            .clone()
+            .ok_or_else(|| Error::Other("Keystore is not locked".to_string()))?;
+        let mut unlocked = Self::new(KeyStoreConfig::Encrypted(location, passphrase))?;
+        for (key, key_info) in std::mem::take(&mut self.key_info) {
+            if !unlocked.key_info.contains_key(&key) {
+                unlocked.put(key, key_info)?;
+            }
+        }
+        *self = unlocked;
+        Ok(())
+    }
+
     pub fn flush(&self) -> anyhow::Result<()> {
         match &self.persistence {
             Some(persistent_keystore) => {
@@ -573,6 +625,35 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn test_unlock_encrypted_keystore() -> Result<()> {
+        let keystore_location = tempfile::tempdir()?.into_path();
+        let mut ks = KeyStore::new(KeyStoreConfig::Encrypted(
+            keystore_location.clone(),
+            PASSPHRASE.to_string(),
+        ))?;
+        let key = wallet::generate_key(SignatureType::BLS)?;
+        ks.put("wallet".to_string(), key.key_info.clone())?;
+
+        let mut locked = KeyStore::new(KeyStoreConfig::Locked(keystore_location))?;
+        ensure!(locked.is_locked());
+        ensure!(matches!(
+            locked.ensure_unlocked(),
+            Err(crate::key_management::Error::Locked)
+        ));
+        let session_key = wallet::generate_key(SignatureType::Secp256k1)?;
+        locked.put("session".to_string(), session_key.key_info.clone())?;
+
+        ensure!(locked.unlock("wrong".to_string()).is_err());
+        ensure!(locked.is_locked());
+
+        locked.unlock(PASSPHRASE.to_string())?;
+        ensure!(!locked.is_locked());
+        ensure!(locked.get("wallet")? == key.key_info);
+        ensure!(locked.get("session")? == session_key.key_info);
+        Ok(())
+    }
+
     #[test]
     fn test_read_write_keystore() -> Result<()> {
         let keystore_location = tempfile::tempdir()?.into_path();
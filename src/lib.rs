@@ -24,11 +24,16 @@ mod chain;
 mod chain_sync;
 mod cli;
 mod cli_shared;
+mod conformance;
 mod daemon;
 mod db;
 mod deleg_cns;
+mod eth;
+mod events;
+mod f3;
 mod fil_cns;
 mod genesis;
+mod health;
 mod interpreter;
 mod ipld;
 mod json;
@@ -38,6 +43,7 @@ mod libp2p_bitswap;
 mod message;
 mod message_pool;
 mod metrics;
+mod miner_monitor;
 mod networks;
 mod rpc;
 mod rpc_api;
@@ -47,7 +53,10 @@ mod state_manager;
 mod state_migration;
 mod statediff;
 mod test_utils;
+mod tool;
 mod utils;
+mod wallet;
+mod watch;
 
 /// These items are semver-exempt, and exist for forest author use only
 // We want to have doctests, but don't want our internals to be public because:
@@ -77,6 +86,18 @@ pub mod doctest_private {
 #[doc(hidden)]
 pub mod benchmark_private {}
 
+/// These items are semver-exempt, and exist for forest author use only
+// Allow fuzzing the decoders of the chain structures, see `fuzz/`
+#[cfg(feature = "fuzzing-private")]
+#[doc(hidden)]
+pub mod fuzzing_private {
+    pub use crate::{
+        blocks::BlockHeader,
+        message::SignedMessage,
+        shim::{executor::Receipt, message::Message},
+    };
+}
+
 // These should be made private in https://github.com/ChainSafe/forest/issues/3013
 pub use auth::{verify_token, JWT_IDENTIFIER};
 pub use cli::main::main as forest_main;
@@ -85,3 +106,5 @@ pub use daemon::main::main as forestd_main;
 pub use key_management::{
     KeyStore, KeyStoreConfig, ENCRYPTED_KEYSTORE_NAME, FOREST_KEYSTORE_PHRASE_ENV, KEYSTORE_NAME,
 };
+pub use tool::main::main as forest_tool_main;
+pub use wallet::main::main as forest_wallet_main;
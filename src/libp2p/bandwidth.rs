@@ -0,0 +1,493 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Accounting of the bandwidth used by the node, per peer and per protocol.
+//! The substreams of the connections are wrapped to count the bytes going
+//! through them, and attributed to the protocol their `multistream-select`
+//! negotiation settles on.
+
+use std::{
+    io::{self, IoSlice, IoSliceMut},
+    pin::Pin,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    task::{Context, Poll},
+    time::Instant,
+};
+
+use ahash::HashMap;
+use futures::prelude::*;
+use libp2p::{
+    core::muxing::{StreamMuxer, StreamMuxerEvent},
+    PeerId,
+};
+use parking_lot::{Mutex, RwLock};
+use pin_project_lite::pin_project;
+use prometheus::core::GenericCounter;
+use serde::{Deserialize, Serialize};
+
+use super::metrics::{self, values};
+
+/// Label of the bytes exchanged on substreams whose protocol is unknown
+pub const UNKNOWN_PROTOCOL: &str = "unknown";
+/// Label of the bytes exchanged on protocols past [`MAX_PROTOCOLS`]
+pub const OTHER_PROTOCOLS: &str = "other";
+/// The protocols are given by the peers, this bounds the accounting of
+/// misbehaving ones
+const MAX_PROTOCOLS: usize = 64;
+/// Negotiations longer than this are given up on
+const MAX_NEGOTIATION_BYTES: usize = 1024;
+
+const MULTISTREAM_HEADER: &[u8] = b"/multistream/1.0.0";
+const MULTISTREAM_NOT_AVAILABLE: &[u8] = b"na";
+
+/// Bandwidth used, in bytes, and its rate over the last update, in bytes per
+/// second
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct BandwidthStats {
+    pub total_in: u64,
+    pub total_out: u64,
+    pub rate_in: f64,
+    pub rate_out: f64,
+}
+
+#[derive(Default)]
+struct Meter {
+    total_in: AtomicU64,
+    total_out: AtomicU64,
+    /// Totals at the last update of the rates, and the rates
+    rates: Mutex<(u64, u64, f64, f64)>,
+    /// `Prometheus` counters of the bytes in and out, if exported
+    counters: Option<[GenericCounter<prometheus::core::AtomicU64>; 2]>,
+}
+
+impl Meter {
+    fn with_counters(protocol: &str) -> Self {
+        let counter = |direction| {
+            metrics::NETWORK_BANDWIDTH_BYTES_TOTAL.with_label_values(&[protocol, direction])
+        };
+        Self {
+            counters: Some([counter(values::IN), counter(values::OUT)]),
+            ..Default::default()
+        }
+    }
+
+    fn record(&self, bytes_in: u64, bytes_out: u64) {
+        if bytes_in > 0 {
+            self.total_in.fetch_add(bytes_in, Ordering::Relaxed);
+        }
+        if bytes_out > 0 {
+            self.total_out.fetch_add(bytes_out, Ordering::Relaxed);
+        }
+        if let Some([counter_in, counter_out]) = &self.counters {
+            counter_in.inc_by(bytes_in);
+            counter_out.inc_by(bytes_out);
+        }
+    }
+
+    fn update_rates(&self, elapsed_secs: f64) {
+        let total_in = self.total_in.load(Ordering::Relaxed);
+        let total_out = self.total_out.load(Ordering::Relaxed);
+        let mut rates = self.rates.lock();
+        let (last_in, last_out, _, _) = *rates;
+        *rates = (
+            total_in,
+            total_out,
+            (total_in - last_in) as f64 / elapsed_secs,
+            (total_out - last_out) as f64 / elapsed_secs,
+        );
+    }
+
+    fn stats(&self) -> BandwidthStats {
+        let (_, _, rate_in, rate_out) = *self.rates.lock();
+        BandwidthStats {
+            total_in: self.total_in.load(Ordering::Relaxed),
+            total_out: self.total_out.load(Ordering::Relaxed),
+            rate_in,
+            rate_out,
+        }
+    }
+}
+
+/// Bandwidth counters of the node, shared by the connections
+pub struct BandwidthCounters {
+    total: Meter,
+    by_peer: RwLock<HashMap<PeerId, Arc<Meter>>>,
+    by_protocol: RwLock<HashMap<String, Arc<Meter>>>,
+    last_update: Mutex<Instant>,
+}
+
+impl Default for BandwidthCounters {
+    fn default() -> Self {
+        Self {
+            total: Default::default(),
+            by_peer: Default::default(),
+            by_protocol: Default::default(),
+            last_update: Mutex::new(Instant::now()),
+        }
+    }
+}
+
+impl BandwidthCounters {
+    pub fn total(&self) -> BandwidthStats {
+        self.total.stats()
+    }
+
+    /// Bandwidth used with the connected peers
+    pub fn by_peer(&self) -> HashMap<PeerId, BandwidthStats> {
+        let by_peer = self.by_peer.read();
+        by_peer.iter().map(|(k, v)| (*k, v.stats())).collect()
+    }
+
+    /// Bandwidth used by each protocol, keyed by protocol ID
+    pub fn by_protocol(&self) -> HashMap<String, BandwidthStats> {
+        let by_protocol = self.by_protocol.read();
+        by_protocol
+            .iter()
+            .map(|(k, v)| (k.clone(), v.stats()))
+            .collect()
+    }
+
+    /// Updates the rates with the bandwidth used since the last update, and
+    /// forgets about the peers no connection is open with anymore.
+    pub fn update_rates(&self) {
+        let now = Instant::now();
+        let elapsed_secs = {
+            let mut last_update = self.last_update.lock();
+            let elapsed = now.duration_since(*last_update).as_secs_f64();
+            *last_update = now;
+            elapsed
+        };
+        if elapsed_secs == 0.0 {
+            return;
+        }
+        self.total.update_rates(elapsed_secs);
+        self.by_peer
+            .write()
+            .retain(|_, meter| Arc::strong_count(meter) > 1);
+        for meter in self.by_peer.read().values() {
+            meter.update_rates(elapsed_secs);
+        }
+        for meter in self.by_protocol.read().values() {
+            meter.update_rates(elapsed_secs);
+        }
+    }
+
+    fn peer_meter(&self, peer_id: PeerId) -> Arc<Meter> {
+        self.by_peer.write().entry(peer_id).or_default().clone()
+    }
+
+    fn protocol_meter(&self, protocol: &str) -> Arc<Meter> {
+        if let Some(meter) = self.by_protocol.read().get(protocol) {
+            return meter.clone();
+        }
+        let mut by_protocol = self.by_protocol.write();
+        let protocol = if by_protocol.len() < MAX_PROTOCOLS || by_protocol.contains_key(protocol) {
+            protocol
+        } else {
+            OTHER_PROTOCOLS
+        };
+        by_protocol
+            .entry(protocol.to_owned())
+            .or_insert_with(|| Arc::new(Meter::with_counters(protocol)))
+            .clone()
+    }
+
+    /// Wraps the muxer of a connection with a peer so that its substreams are
+    /// accounted for.
+    pub fn instrument<M>(self: &Arc<Self>, peer_id: PeerId, muxer: M) -> InstrumentedMuxer<M> {
+        InstrumentedMuxer {
+            inner: muxer,
+            peer: self.peer_meter(peer_id),
+            counters: self.clone(),
+        }
+    }
+}
+
+pin_project! {
+    /// Muxer counting the bytes going through its substreams
+    pub struct InstrumentedMuxer<M> {
+        #[pin]
+        inner: M,
+        peer: Arc<Meter>,
+        counters: Arc<BandwidthCounters>,
+    }
+}
+
+impl<M> InstrumentedMuxer<M> {
+    fn substream<S>(&self, inner: S, dialer: bool) -> InstrumentedStream<S> {
+        InstrumentedStream {
+            inner,
+            dialer,
+            negotiation: Some(Negotiation::default()),
+            protocol: None,
+            peer: self.peer.clone(),
+            counters: self.counters.clone(),
+        }
+    }
+}
+
+impl<M: StreamMuxer> StreamMuxer for InstrumentedMuxer<M> {
+    type Substream = InstrumentedStream<M::Substream>;
+    type Error = M::Error;
+
+    fn poll_inbound(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Self::Substream, Self::Error>> {
+        let inner = std::task::ready!(self.as_mut().project().inner.poll_inbound(cx))?;
+        Poll::Ready(Ok(self.substream(inner, false)))
+    }
+
+    fn poll_outbound(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Self::Substream, Self::Error>> {
+        let inner = std::task::ready!(self.as_mut().project().inner.poll_outbound(cx))?;
+        Poll::Ready(Ok(self.substream(inner, true)))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.project().inner.poll_close(cx)
+    }
+
+    fn poll(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<StreamMuxerEvent, Self::Error>> {
+        self.project().inner.poll(cx)
+    }
+}
+
+/// Progress of the `multistream-select` negotiation of a substream, as seen
+/// from the messages of the listener: its header, then `na` for each protocol
+/// it rejects, and the protocol it accepts.
+#[derive(Default)]
+struct Negotiation {
+    listener_bytes: Vec<u8>,
+    /// Bytes exchanged before the protocol is known
+    pending_in: u64,
+    pending_out: u64,
+}
+
+enum NegotiationState {
+    Pending,
+    Done(String),
+    Failed,
+}
+
+impl Negotiation {
+    fn on_listener_bytes(&mut self, bytes: &[u8]) -> NegotiationState {
+        self.listener_bytes.extend_from_slice(bytes);
+        let mut rest = self.listener_bytes.as_slice();
+        loop {
+            let (len, tail) = match unsigned_varint::decode::usize(rest) {
+                Ok(decoded) => decoded,
+                Err(unsigned_varint::decode::Error::Insufficient) => break,
+                Err(_) => return NegotiationState::Failed,
+            };
+            if tail.len() < len {
+                break;
+            }
+            let Some(line) = tail[..len].strip_suffix(b"\n") else {
+                return NegotiationState::Failed;
+            };
+            match line {
+                MULTISTREAM_HEADER | MULTISTREAM_NOT_AVAILABLE => rest = &tail[len..],
+                protocol => {
+                    return NegotiationState::Done(String::from_utf8_lossy(protocol).into_owned())
+                }
+            }
+        }
+        if self.listener_bytes.len() > MAX_NEGOTIATION_BYTES {
+            NegotiationState::Failed
+        } else {
+            NegotiationState::Pending
+        }
+    }
+}
+
+pin_project! {
+    /// Substream counting the bytes going through it
+    pub struct InstrumentedStream<S> {
+        #[pin]
+        inner: S,
+        /// Whether the local node opened the substream
+        dialer: bool,
+        negotiation: Option<Negotiation>,
+        protocol: Option<Arc<Meter>>,
+        peer: Arc<Meter>,
+        counters: Arc<BandwidthCounters>,
+    }
+}
+
+impl<S> InstrumentedStream<S> {
+    fn record(self: Pin<&mut Self>, bytes_in: &[u8], bytes_out: &[u8]) {
+        let this = self.project();
+        let (len_in, len_out) = (bytes_in.len() as u64, bytes_out.len() as u64);
+        this.counters.total.record(len_in, len_out);
+        this.peer.record(len_in, len_out);
+        if let Some(protocol) = this.protocol.as_ref() {
+            protocol.record(len_in, len_out);
+            return;
+        }
+        let Some(negotiation) = this.negotiation.as_mut() else {
+            return;
+        };
+        negotiation.pending_in += len_in;
+        negotiation.pending_out += len_out;
+        let listener_bytes = if *this.dialer { bytes_in } else { bytes_out };
+        if listener_bytes.is_empty() {
+            return;
+        }
+        let protocol = match negotiation.on_listener_bytes(listener_bytes) {
+            NegotiationState::Pending => return,
+            NegotiationState::Done(protocol) => protocol,
+            NegotiationState::Failed => UNKNOWN_PROTOCOL.to_owned(),
+        };
+        let meter = this.counters.protocol_meter(&protocol);
+        meter.record(negotiation.pending_in, negotiation.pending_out);
+        *this.protocol = Some(meter);
+        *this.negotiation = None;
+    }
+}
+
+impl<S: AsyncRead> AsyncRead for InstrumentedStream<S> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let n = std::task::ready!(self.as_mut().project().inner.poll_read(cx, buf))?;
+        self.record(&buf[..n], &[]);
+        Poll::Ready(Ok(n))
+    }
+
+    fn poll_read_vectored(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &mut [IoSliceMut<'_>],
+    ) -> Poll<io::Result<usize>> {
+        let n = std::task::ready!(self.as_mut().project().inner.poll_read_vectored(cx, bufs))?;
+        let mut remaining = n;
+        for buf in bufs.iter() {
+            let len = remaining.min(buf.len());
+            self.as_mut().record(&buf[..len], &[]);
+            remaining -= len;
+        }
+        Poll::Ready(Ok(n))
+    }
+}
+
+impl<S: AsyncWrite> AsyncWrite for InstrumentedStream<S> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let n = std::task::ready!(self.as_mut().project().inner.poll_write(cx, buf))?;
+        self.record(&[], &buf[..n]);
+        Poll::Ready(Ok(n))
+    }
+
+    fn poll_write_vectored(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        let n = std::task::ready!(self.as_mut().project().inner.poll_write_vectored(cx, bufs))?;
+        let mut remaining = n;
+        for buf in bufs {
+            let len = remaining.min(buf.len());
+            self.as_mut().record(&[], &buf[..len]);
+            remaining -= len;
+        }
+        Poll::Ready(Ok(n))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.project().inner.poll_flush(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.project().inner.poll_close(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(line: &[u8]) -> Vec<u8> {
+        let mut buf = unsigned_varint::encode::usize_buffer();
+        let mut message = unsigned_varint::encode::usize(line.len() + 1, &mut buf).to_vec();
+        message.extend_from_slice(line);
+        message.push(b'\n');
+        message
+    }
+
+    #[test]
+    fn negotiation_settles_on_accepted_protocol() {
+        let mut negotiation = Negotiation::default();
+        let header = message(MULTISTREAM_HEADER);
+        assert!(matches!(
+            negotiation.on_listener_bytes(&header[..3]),
+            NegotiationState::Pending
+        ));
+        assert!(matches!(
+            negotiation.on_listener_bytes(&header[3..]),
+            NegotiationState::Pending
+        ));
+        assert!(matches!(
+            negotiation.on_listener_bytes(&message(MULTISTREAM_NOT_AVAILABLE)),
+            NegotiationState::Pending
+        ));
+        match negotiation.on_listener_bytes(&message(b"/fil/chain/xchg/0.0.1")) {
+            NegotiationState::Done(protocol) => assert_eq!(protocol, "/fil/chain/xchg/0.0.1"),
+            _ => panic!("the protocol should be negotiated"),
+        }
+    }
+
+    #[test]
+    fn garbage_fails_negotiation() {
+        let mut negotiation = Negotiation::default();
+        assert!(matches!(
+            negotiation.on_listener_bytes(&[3, b'a', b'b', b'c']),
+            NegotiationState::Failed
+        ));
+    }
+
+    #[tokio::test]
+    async fn substream_bytes_are_accounted() {
+        let counters = Arc::new(BandwidthCounters::default());
+        let peer = PeerId::random();
+        let mut response = message(MULTISTREAM_HEADER);
+        response.extend(message(b"/fil/hello/1.0.0"));
+        response.extend([0; 10]);
+        // The request overwrites the first bytes of the cursor, the response
+        // is read after them
+        let mut stream = InstrumentedStream {
+            inner: futures::io::Cursor::new([vec![0; 5], response.clone()].concat()),
+            dialer: true,
+            negotiation: Some(Negotiation::default()),
+            protocol: None,
+            peer: counters.peer_meter(peer),
+            counters: counters.clone(),
+        };
+        stream.write_all(&[1; 5]).await.unwrap();
+        let mut read = vec![0; response.len()];
+        stream.read_exact(&mut read[..7]).await.unwrap();
+        stream.read_exact(&mut read[7..]).await.unwrap();
+
+        let hello = &counters.by_protocol()["/fil/hello/1.0.0"];
+        assert_eq!(
+            (hello.total_in, hello.total_out),
+            (response.len() as u64, 5)
+        );
+        assert_eq!(counters.by_peer()[&peer], counters.total());
+        assert_eq!(counters.total().total_in, response.len() as u64);
+    }
+}
@@ -1,21 +1,22 @@
 // Copyright 2019-2023 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
-use crate::libp2p_bitswap::BitswapBehaviour;
+use crate::libp2p_bitswap::{BitswapBehaviour, BitswapServerConfig};
 use crate::utils::{encoding::blake2b_256, version::FOREST_VERSION_STRING};
 use ahash::{HashMap, HashSet};
 use libp2p::{
     core::identity::Keypair,
+    dcutr,
     gossipsub::{
-        self, IdentTopic as Topic, MessageAuthenticity, MessageId, PublishError, SubscriptionError,
-        ValidationMode,
+        self, IdentTopic as Topic, MessageAcceptance, MessageAuthenticity, MessageId, PublishError,
+        SubscriptionError, ValidationMode,
     },
     identify,
     identity::PeerId,
     kad::QueryId,
     metrics::{Metrics, Recorder},
-    ping,
-    swarm::{keep_alive, NetworkBehaviour},
+    ping, relay,
+    swarm::{behaviour::toggle::Toggle, keep_alive, NetworkBehaviour},
     Multiaddr,
 };
 use log::warn;
@@ -37,6 +38,8 @@ pub(in crate::libp2p) struct ForestBehaviour {
     ping: ping::Behaviour,
     identify: identify::Behaviour,
     keep_alive: keep_alive::Behaviour,
+    relay_client: relay::client::Behaviour,
+    dcutr: Toggle<dcutr::Behaviour>,
     pub(super) hello: HelloBehaviour,
     pub(super) chain_exchange: ChainExchangeBehaviour,
     pub(super) bitswap: BitswapBehaviour,
@@ -54,10 +57,17 @@ impl Recorder<ForestBehaviourEvent> for Metrics {
 }
 
 impl ForestBehaviour {
-    pub fn new(local_key: &Keypair, config: &Libp2pConfig, network_name: &str) -> Self {
+    pub fn new(
+        local_key: &Keypair,
+        config: &Libp2pConfig,
+        network_name: &str,
+        relay_client: relay::client::Behaviour,
+    ) -> Self {
         let mut gs_config_builder = gossipsub::ConfigBuilder::default();
         gs_config_builder.max_transmit_size(1 << 20);
         gs_config_builder.validation_mode(ValidationMode::Strict);
+        // Messages are only forwarded once accepted by the topic validators
+        gs_config_builder.validate_messages();
         gs_config_builder.message_id_fn(|msg: &gossipsub::Message| {
             let s = blake2b_256(&msg.data);
             MessageId::from(s)
@@ -85,7 +95,11 @@ impl ForestBehaviour {
                 b"/chain/ipfs/bitswap",
             ],
             Default::default(),
-        );
+        )
+        .with_server_config(BitswapServerConfig {
+            enabled: config.serve_bitswap,
+            ..Default::default()
+        });
         if let Err(err) = crate::libp2p_bitswap::register_metrics(prometheus::default_registry()) {
             warn!("Fail to register prometheus metrics for libp2p_bitswap: {err}");
         }
@@ -107,9 +121,14 @@ impl ForestBehaviour {
                     .with_agent_version(format!("forest-{}", FOREST_VERSION_STRING.as_str())),
             ),
             keep_alive: keep_alive::Behaviour::default(),
+            relay_client,
+            dcutr: config
+                .hole_punching
+                .then(|| dcutr::Behaviour::new(local_key.public().to_peer_id()))
+                .into(),
             bitswap,
             hello: HelloBehaviour::default(),
-            chain_exchange: ChainExchangeBehaviour::default(),
+            chain_exchange: ChainExchangeBehaviour::new(&config.chain_exchange_compression),
         }
     }
 
@@ -118,6 +137,28 @@ impl ForestBehaviour {
         self.discovery.bootstrap()
     }
 
+    /// Adds a bootstrap peer to the discovery, the address is expected to end
+    /// with the `/p2p/` component.
+    pub fn add_bootstrap_peer(&mut self, peer_id: &PeerId, mut addr: Multiaddr) -> bool {
+        addr.pop();
+        self.discovery.add_address(peer_id, addr)
+    }
+
+    /// Removes a bootstrap peer from the discovery.
+    pub fn remove_bootstrap_peer(&mut self, peer_id: &PeerId) {
+        self.discovery.remove_peer(peer_id)
+    }
+
+    /// Denies the dials to a peer until the given Unix timestamp, in seconds.
+    pub fn set_dial_backoff(&mut self, peer_id: PeerId, until: i64) {
+        self.discovery.set_dial_backoff(peer_id, until)
+    }
+
+    /// Allows the dials to a peer again.
+    pub fn clear_dial_backoff(&mut self, peer_id: &PeerId) {
+        self.discovery.clear_dial_backoff(peer_id)
+    }
+
     /// Publish data over the gossip network.
     pub fn publish(
         &mut self,
@@ -127,6 +168,23 @@ impl ForestBehaviour {
         self.gossipsub.publish(topic, data)
     }
 
+    /// Reports the outcome of the validation of a gossip message, only the
+    /// accepted messages are forwarded to other peers.
+    pub fn report_message_validation_result(
+        &mut self,
+        message_id: &MessageId,
+        propagation_source: &PeerId,
+        acceptance: MessageAcceptance,
+    ) {
+        if let Err(e) = self.gossipsub.report_message_validation_result(
+            message_id,
+            propagation_source,
+            acceptance,
+        ) {
+            warn!("Failed to report gossip message validation result: {e}");
+        }
+    }
+
     /// Subscribe to a gossip topic.
     pub fn subscribe(&mut self, topic: &Topic) -> Result<bool, SubscriptionError> {
         self.gossipsub.subscribe(topic)
@@ -0,0 +1,113 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Bootstrap peers that are added or removed at runtime with the
+//! `Filecoin.NetAddPeer` and `Filecoin.NetRemovePeer` RPC methods. They are
+//! persisted next to the chain data and used on top of the bootstrap peers
+//! from the configuration, so that private networks can be re-wired without a
+//! restart or a rebuild.
+
+use libp2p::{multiaddr::Protocol, Multiaddr, PeerId};
+use serde::{Deserialize, Serialize};
+
+use crate::utils::db::file_backed_obj::FileBackedObject;
+
+/// Name of the file in the chain data directory the peers are persisted to
+pub const BOOTSTRAP_PEERS_FILE_NAME: &str = "bootstrap_peers.yaml";
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BootstrapPeers {
+    peers: Vec<Multiaddr>,
+}
+
+impl BootstrapPeers {
+    pub fn peers(&self) -> &[Multiaddr] {
+        &self.peers
+    }
+
+    /// Adds a peer address, returns `false` when it is already present.
+    pub fn add(&mut self, addr: Multiaddr) -> bool {
+        if self.peers.contains(&addr) {
+            false
+        } else {
+            self.peers.push(addr);
+            true
+        }
+    }
+
+    /// Removes all the addresses of a peer, returns `false` when there are
+    /// none.
+    pub fn remove(&mut self, peer_id: &PeerId) -> bool {
+        let len = self.peers.len();
+        self.peers
+            .retain(|addr| peer_id_from_multiaddr(addr).as_ref() != Some(peer_id));
+        self.peers.len() != len
+    }
+}
+
+impl FileBackedObject for BootstrapPeers {
+    fn serialize(&self) -> anyhow::Result<Vec<u8>> {
+        Ok(serde_yaml::to_string(self)?.into_bytes())
+    }
+
+    fn deserialize(bytes: &[u8]) -> anyhow::Result<Self> {
+        Ok(serde_yaml::from_slice(bytes)?)
+    }
+}
+
+/// Extracts the peer identifier from the trailing `/p2p/` component of an
+/// address.
+pub fn peer_id_from_multiaddr(addr: &Multiaddr) -> Option<PeerId> {
+    match addr.iter().last()? {
+        Protocol::P2p(mh) => PeerId::from_multihash(mh).ok(),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn peer_addr(peer_id: &PeerId, port: u16) -> Multiaddr {
+        format!("/ip4/127.0.0.1/tcp/{port}/p2p/{peer_id}")
+            .parse()
+            .unwrap()
+    }
+
+    #[test]
+    fn add_and_remove() {
+        let peer_id = PeerId::random();
+        let other = PeerId::random();
+        let mut peers = BootstrapPeers::default();
+        assert!(peers.add(peer_addr(&peer_id, 1234)));
+        assert!(!peers.add(peer_addr(&peer_id, 1234)));
+        assert!(peers.add(peer_addr(&peer_id, 1235)));
+        assert!(peers.add(peer_addr(&other, 1234)));
+
+        assert!(peers.remove(&peer_id));
+        assert!(!peers.remove(&peer_id));
+        assert_eq!(peers.peers(), &[peer_addr(&other, 1234)]);
+    }
+
+    #[test]
+    fn file_backed_round_trip() {
+        let mut peers = BootstrapPeers::default();
+        peers.add(peer_addr(&PeerId::random(), 1234));
+        let bytes = FileBackedObject::serialize(&peers).unwrap();
+        let deserialized: BootstrapPeers = FileBackedObject::deserialize(&bytes).unwrap();
+        assert_eq!(peers, deserialized);
+    }
+
+    #[test]
+    fn peer_id_parsing() {
+        let peer_id = PeerId::random();
+        assert_eq!(
+            peer_id_from_multiaddr(&peer_addr(&peer_id, 1234)),
+            Some(peer_id)
+        );
+        assert_eq!(
+            peer_id_from_multiaddr(&"/ip4/127.0.0.1/tcp/1234".parse().unwrap()),
+            None
+        );
+    }
+}
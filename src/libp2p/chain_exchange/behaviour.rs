@@ -21,6 +21,24 @@ pub struct ChainExchangeBehaviour {
 }
 
 impl ChainExchangeBehaviour {
+    /// Speaks the protocol with the given response compressions, in order of
+    /// preference, and falls back to uncompressed responses.
+    pub fn new(compression: &[ChainExchangeCompression]) -> Self {
+        let protocols = compression
+            .iter()
+            .map(|c| ChainExchangeProtocolName(Some(*c)))
+            .chain(std::iter::once(ChainExchangeProtocolName(None)))
+            .map(|protocol| (protocol, ProtocolSupport::Full));
+        Self {
+            inner: InnerBehaviour::new(
+                ChainExchangeCodec::default(),
+                protocols,
+                Default::default(),
+            ),
+            response_channels: Default::default(),
+        }
+    }
+
     pub fn send_request(
         &mut self,
         peer: &PeerId,
@@ -81,19 +99,6 @@ impl ChainExchangeBehaviour {
     }
 }
 
-impl Default for ChainExchangeBehaviour {
-    fn default() -> Self {
-        Self {
-            inner: InnerBehaviour::new(
-                ChainExchangeCodec::default(),
-                [(ChainExchangeProtocolName, ProtocolSupport::Full)],
-                Default::default(),
-            ),
-            response_channels: Default::default(),
-        }
-    }
-}
-
 impl NetworkBehaviour for ChainExchangeBehaviour {
     type ConnectionHandler = <InnerBehaviour as NetworkBehaviour>::ConnectionHandler;
 
@@ -0,0 +1,262 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use std::io::{self, Read};
+
+use async_trait::async_trait;
+use futures::prelude::*;
+use libp2p::{
+    core::ProtocolName,
+    request_response::{self, Codec},
+};
+use serde::{Deserialize, Serialize};
+
+use super::{ChainExchangeRequest, ChainExchangeResponse};
+use crate::libp2p::{
+    metrics::{self, values},
+    rpc::CborRequestResponse,
+};
+
+/// Libp2p protocol ID for `ChainExchange`.
+pub const CHAIN_XCHG_PROTOCOL_ID: &[u8] = b"/fil/chain/xchg/0.0.1";
+/// Libp2p protocol ID for `ChainExchange` with `snappy` compressed responses.
+pub const CHAIN_XCHG_SNAPPY_PROTOCOL_ID: &[u8] = b"/fil/chain/xchg/0.0.1/snappy";
+/// Libp2p protocol ID for `ChainExchange` with `zstd` compressed responses.
+pub const CHAIN_XCHG_ZSTD_PROTOCOL_ID: &[u8] = b"/fil/chain/xchg/0.0.1/zstd";
+
+/// Decompressed responses over 512MB are likely malicious
+const MAX_DECOMPRESSED_BYTES: usize = 512 * 1024 * 1024;
+
+/// Compression of the `ChainExchange` responses, on top of the protocol
+/// spoken by all implementations. Peers negotiate it through the protocol ID,
+/// and fall back to uncompressed responses when they share none.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChainExchangeCompression {
+    /// Cheap on CPU, with a lower compression ratio.
+    Snappy,
+    /// Higher compression ratio, for links with less bandwidth.
+    Zstd,
+}
+
+#[cfg(test)]
+impl quickcheck::Arbitrary for ChainExchangeCompression {
+    fn arbitrary(g: &mut quickcheck::Gen) -> Self {
+        *g.choose(&[
+            ChainExchangeCompression::Snappy,
+            ChainExchangeCompression::Zstd,
+        ])
+        .unwrap()
+    }
+}
+
+impl ChainExchangeCompression {
+    fn label(self) -> &'static str {
+        match self {
+            Self::Snappy => values::SNAPPY,
+            Self::Zstd => values::ZSTD,
+        }
+    }
+
+    fn compress(self, bytes: &[u8]) -> io::Result<Vec<u8>> {
+        let _timer = metrics::CHAIN_EXCHANGE_COMPRESSION_TIME
+            .with_label_values(&[self.label(), values::COMPRESS])
+            .start_timer();
+        let compressed = match self {
+            Self::Snappy => snap::raw::Encoder::new()
+                .compress_vec(bytes)
+                .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?,
+            Self::Zstd => zstd::bulk::compress(bytes, zstd::DEFAULT_COMPRESSION_LEVEL)?,
+        };
+        self.track_bytes(values::COMPRESS, bytes.len(), compressed.len());
+        Ok(compressed)
+    }
+
+    fn decompress(self, bytes: &[u8], max_bytes_allowed: usize) -> io::Result<Vec<u8>> {
+        let _timer = metrics::CHAIN_EXCHANGE_COMPRESSION_TIME
+            .with_label_values(&[self.label(), values::DECOMPRESS])
+            .start_timer();
+        let too_large = || {
+            io::Error::new(
+                io::ErrorKind::Other,
+                format!("Decompressed size exceeds the maximum allowed {max_bytes_allowed}B"),
+            )
+        };
+        let decompressed = match self {
+            Self::Snappy => {
+                let len = snap::raw::decompress_len(bytes)
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+                if len > max_bytes_allowed {
+                    return Err(too_large());
+                }
+                snap::raw::Decoder::new()
+                    .decompress_vec(bytes)
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?
+            }
+            Self::Zstd => {
+                let mut decompressed = vec![];
+                zstd::stream::read::Decoder::new(bytes)?
+                    .take(max_bytes_allowed as u64 + 1)
+                    .read_to_end(&mut decompressed)?;
+                if decompressed.len() > max_bytes_allowed {
+                    return Err(too_large());
+                }
+                decompressed
+            }
+        };
+        self.track_bytes(values::DECOMPRESS, decompressed.len(), bytes.len());
+        Ok(decompressed)
+    }
+
+    fn track_bytes(self, operation: &str, raw: usize, compressed: usize) {
+        for (form, len) in [(values::RAW, raw), (values::COMPRESSED, compressed)] {
+            metrics::CHAIN_EXCHANGE_COMPRESSION_BYTES_TOTAL
+                .with_label_values(&[self.label(), operation, form])
+                .inc_by(len as u64);
+        }
+    }
+}
+
+/// Type to satisfy `ProtocolName` interface for `ChainExchange` RPC, with the
+/// compression of the responses if any.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub struct ChainExchangeProtocolName(pub Option<ChainExchangeCompression>);
+
+impl ProtocolName for ChainExchangeProtocolName {
+    fn protocol_name(&self) -> &[u8] {
+        match self.0 {
+            None => CHAIN_XCHG_PROTOCOL_ID,
+            Some(ChainExchangeCompression::Snappy) => CHAIN_XCHG_SNAPPY_PROTOCOL_ID,
+            Some(ChainExchangeCompression::Zstd) => CHAIN_XCHG_ZSTD_PROTOCOL_ID,
+        }
+    }
+}
+
+/// `ChainExchange` protocol codec to be used within the RPC service. Requests
+/// are always plain `Cbor`, responses are compressed as negotiated.
+#[derive(Clone, Default)]
+pub struct ChainExchangeCodec {
+    inner:
+        CborRequestResponse<ChainExchangeProtocolName, ChainExchangeRequest, ChainExchangeResponse>,
+}
+
+#[async_trait]
+impl Codec for ChainExchangeCodec {
+    type Protocol = ChainExchangeProtocolName;
+    type Request = ChainExchangeRequest;
+    type Response = ChainExchangeResponse;
+
+    async fn read_request<T>(
+        &mut self,
+        protocol: &Self::Protocol,
+        io: &mut T,
+    ) -> io::Result<Self::Request>
+    where
+        T: AsyncRead + Unpin + Send,
+    {
+        self.inner.read_request(protocol, io).await
+    }
+
+    async fn read_response<T>(
+        &mut self,
+        protocol: &Self::Protocol,
+        io: &mut T,
+    ) -> io::Result<Self::Response>
+    where
+        T: AsyncRead + Unpin + Send,
+    {
+        let Some(compression) = protocol.0 else {
+            return self.inner.read_response(protocol, io).await;
+        };
+        let mut bytes = vec![];
+        io.read_to_end(&mut bytes).await?;
+        let bytes = compression.decompress(&bytes, MAX_DECOMPRESSED_BYTES)?;
+        serde_ipld_dagcbor::de::from_reader(bytes.as_slice())
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))
+    }
+
+    async fn write_request<T>(
+        &mut self,
+        protocol: &Self::Protocol,
+        io: &mut T,
+        req: Self::Request,
+    ) -> io::Result<()>
+    where
+        T: AsyncWrite + Unpin + Send,
+    {
+        self.inner.write_request(protocol, io, req).await
+    }
+
+    async fn write_response<T>(
+        &mut self,
+        protocol: &Self::Protocol,
+        io: &mut T,
+        res: Self::Response,
+    ) -> io::Result<()>
+    where
+        T: AsyncWrite + Unpin + Send,
+    {
+        let Some(compression) = protocol.0 else {
+            return self.inner.write_response(protocol, io, res).await;
+        };
+        let bytes = fvm_ipld_encoding::to_vec(&res)
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))?;
+        io.write_all(&compression.compress(&bytes)?).await?;
+        io.close().await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use quickcheck_macros::quickcheck;
+
+    use super::*;
+    use crate::libp2p::chain_exchange::ChainExchangeResponseStatus;
+
+    #[quickcheck]
+    fn compression_round_trip(compression: ChainExchangeCompression, bytes: Vec<u8>) {
+        let compressed = compression.compress(&bytes).unwrap();
+        let decompressed = compression
+            .decompress(&compressed, MAX_DECOMPRESSED_BYTES)
+            .unwrap();
+        assert_eq!(decompressed, bytes);
+    }
+
+    #[test]
+    fn decompressed_size_is_limited() {
+        let bytes = vec![0; 1024];
+        for compression in [
+            ChainExchangeCompression::Snappy,
+            ChainExchangeCompression::Zstd,
+        ] {
+            let compressed = compression.compress(&bytes).unwrap();
+            assert!(compression.decompress(&compressed, 1023).is_err());
+            assert!(compression.decompress(&compressed, 1024).is_ok());
+        }
+    }
+
+    #[tokio::test]
+    async fn responses_round_trip() {
+        let response = ChainExchangeResponse {
+            status: ChainExchangeResponseStatus::PartialResponse,
+            message: "partial".into(),
+            chain: vec![],
+        };
+        for protocol in [
+            ChainExchangeProtocolName(None),
+            ChainExchangeProtocolName(Some(ChainExchangeCompression::Snappy)),
+            ChainExchangeProtocolName(Some(ChainExchangeCompression::Zstd)),
+        ] {
+            let mut codec = ChainExchangeCodec::default();
+            let mut io = futures::io::Cursor::new(vec![]);
+            codec
+                .write_response(&protocol, &mut io, response.clone())
+                .await
+                .unwrap();
+            io.set_position(0);
+            let read = codec.read_response(&protocol, &mut io).await.unwrap();
+            assert_eq!(read, response);
+        }
+    }
+}
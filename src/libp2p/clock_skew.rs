@@ -0,0 +1,163 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use ahash::HashMap;
+use libp2p::PeerId;
+
+use crate::blocks::timing::DEFAULT_ALLOWABLE_CLOCK_DRIFT_SECS;
+use crate::libp2p::hello::HelloResponse;
+
+/// Minimum number of peers sampled before telling whether the clock of the
+/// node is off
+const MIN_CLOCK_SAMPLES: usize = 5;
+
+/// Latency of a peer and offset of its clock from ours, estimated from a
+/// `Hello` round trip the way NTP does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClockSample {
+    /// Round trip time, without the time spent by the peer on the request
+    pub round_trip: Duration,
+    /// Offset of the clock of the peer from ours in nanoseconds, positive if
+    /// the peer is ahead
+    pub offset: i64,
+}
+
+impl ClockSample {
+    /// Estimates the sample from the times the request was sent and the
+    /// response received, by our clock, and the times the peer reported.
+    pub fn from_hello(sent: SystemTime, received: SystemTime, response: &HelloResponse) -> Self {
+        let unix_nanos =
+            |t: SystemTime| t.duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos() as i128;
+        let (t0, t3) = (unix_nanos(sent), unix_nanos(received));
+        let (t1, t2) = (response.arrival as i128, response.sent as i128);
+        let round_trip = ((t3 - t0) - (t2 - t1)).max(0);
+        let offset = ((t1 - t0) + (t2 - t3)) / 2;
+        Self {
+            round_trip: Duration::from_nanos(round_trip.min(u64::MAX as i128) as u64),
+            offset: offset.clamp(i64::MIN as i128, i64::MAX as i128) as i64,
+        }
+    }
+
+    /// Returns true if the clock of the peer is off from ours by more than
+    /// the allowed drift, even accounting for the uncertainty of the
+    /// estimation, which is half the round trip time.
+    fn is_skewed(&self) -> bool {
+        let allowed = Duration::from_secs(DEFAULT_ALLOWABLE_CLOCK_DRIFT_SECS) + self.round_trip / 2;
+        self.offset.unsigned_abs() > allowed.as_nanos() as u64
+    }
+}
+
+/// Clock skew of the node, as detected from the clocks of its peers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(in crate::libp2p) struct ClockSkew {
+    /// Number of peers whose clocks are off from ours
+    pub skewed_peers: usize,
+    /// Number of peers sampled
+    pub sampled_peers: usize,
+    /// Median offset of the clocks of the peers from ours in nanoseconds
+    pub median_offset: i64,
+}
+
+/// Keeps the last clock sample of each connected peer. Blocks are rejected
+/// as coming from the future when the clock of the node is behind the ones of
+/// the miners by more than the allowed drift, so the node is deemed skewed
+/// when most of its peers disagree with its clock.
+#[derive(Debug, Default)]
+pub(in crate::libp2p) struct ClockSkewDetector {
+    samples: HashMap<PeerId, ClockSample>,
+}
+
+impl ClockSkewDetector {
+    pub fn insert(&mut self, peer_id: PeerId, sample: ClockSample) {
+        self.samples.insert(peer_id, sample);
+    }
+
+    pub fn remove(&mut self, peer_id: &PeerId) {
+        self.samples.remove(peer_id);
+    }
+
+    /// Number of peers whose clocks are off from ours by more than the
+    /// allowed drift
+    pub fn skewed_peers(&self) -> usize {
+        self.samples.values().filter(|s| s.is_skewed()).count()
+    }
+
+    /// Returns the skew of the clock of the node if most of the peers
+    /// disagree with it, given enough of them are sampled.
+    pub fn skew(&self) -> Option<ClockSkew> {
+        let sampled_peers = self.samples.len();
+        let skewed_peers = self.skewed_peers();
+        if sampled_peers < MIN_CLOCK_SAMPLES || skewed_peers * 2 <= sampled_peers {
+            return None;
+        }
+        let mut offsets: Vec<_> = self.samples.values().map(|s| s.offset).collect();
+        offsets.sort_unstable();
+        Some(ClockSkew {
+            skewed_peers,
+            sampled_peers,
+            median_offset: offsets[offsets.len() / 2],
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn response(arrival: SystemTime, sent: SystemTime) -> HelloResponse {
+        let unix_nanos = |t: SystemTime| t.duration_since(UNIX_EPOCH).unwrap().as_nanos() as u64;
+        HelloResponse {
+            arrival: unix_nanos(arrival),
+            sent: unix_nanos(sent),
+        }
+    }
+
+    fn sample(offset: Duration, ahead: bool) -> ClockSample {
+        let t0 = UNIX_EPOCH + Duration::from_secs(1_000_000);
+        let peer_time = |t: SystemTime| if ahead { t + offset } else { t - offset };
+        // 100ms each way, 50ms spent by the peer on the request
+        let t1 = peer_time(t0 + Duration::from_millis(100));
+        let t2 = t1 + Duration::from_millis(50);
+        let t3 = t0 + Duration::from_millis(250);
+        ClockSample::from_hello(t0, t3, &response(t1, t2))
+    }
+
+    #[test]
+    fn sample_from_hello() {
+        let s = sample(Duration::from_secs(3), true);
+        assert_eq!(s.round_trip, Duration::from_millis(200));
+        assert_eq!(s.offset, Duration::from_secs(3).as_nanos() as i64);
+        assert!(s.is_skewed());
+
+        let s = sample(Duration::from_secs(3), false);
+        assert_eq!(s.offset, -(Duration::from_secs(3).as_nanos() as i64));
+        assert!(s.is_skewed());
+
+        assert!(!sample(Duration::from_millis(500), true).is_skewed());
+    }
+
+    #[test]
+    fn skew_needs_most_peers() {
+        let mut detector = ClockSkewDetector::default();
+        let peers: Vec<_> = (0..6).map(|_| PeerId::random()).collect();
+        for peer in &peers[..3] {
+            detector.insert(*peer, sample(Duration::from_secs(5), true));
+        }
+        // Not enough samples yet
+        assert_eq!(detector.skew(), None);
+        for peer in &peers[3..] {
+            detector.insert(*peer, sample(Duration::ZERO, true));
+        }
+        // Half of the peers only
+        assert_eq!(detector.skewed_peers(), 3);
+        assert_eq!(detector.skew(), None);
+
+        detector.remove(&peers[5]);
+        let skew = detector.skew().unwrap();
+        assert_eq!(skew.skewed_peers, 3);
+        assert_eq!(skew.sampled_peers, 5);
+        assert_eq!(skew.median_offset, Duration::from_secs(5).as_nanos() as i64);
+    }
+}
@@ -1,17 +1,25 @@
 // Copyright 2019-2023 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
+use std::path::PathBuf;
+
 use libp2p::Multiaddr;
+
+use super::chain_exchange::ChainExchangeCompression;
 use serde::{Deserialize, Serialize};
 
 /// Libp2p configuration for the Forest node.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 #[serde(default)]
 pub struct Libp2pConfig {
-    /// Local addresses. TCP and WebSocket with DNS are supported. By making it
-    /// empty, the libp2p node will not be capable of working as a callee but
-    /// can still work as a dialer
+    /// Local addresses. TCP, WebSocket with DNS and QUIC are supported. By
+    /// making it empty, the libp2p node will not be capable of working as a
+    /// callee but can still work as a dialer
     pub listening_multiaddrs: Vec<Multiaddr>,
+    /// Addresses that are announced to the peers in addition to the
+    /// listening ones, e.g. the public address of a router that forwards
+    /// traffic to this node.
+    pub announce_multiaddrs: Vec<Multiaddr>,
     /// Bootstrap peer list.
     pub bootstrap_peers: Vec<Multiaddr>,
     /// MDNS discovery enabled.
@@ -20,16 +28,68 @@ pub struct Libp2pConfig {
     pub kademlia: bool,
     /// Target peer count.
     pub target_peer_count: u32,
+    /// Peer count above which the lowest-scored peers are pruned, down to
+    /// the target peer count.
+    pub max_peer_count: u32,
+    /// Peers that are never pruned. The bootstrap peers are always protected.
+    pub protected_peers: Vec<String>,
+    /// Serve blocks that the node has to peers over `bitswap`.
+    pub serve_bitswap: bool,
+    /// Compressions of the `ChainExchange` responses to negotiate with the
+    /// peers, in order of preference. Peers that support none of them get
+    /// uncompressed responses.
+    pub chain_exchange_compression: Vec<ChainExchangeCompression>,
+    /// Window, in seconds, within which a message published over `gossipsub`
+    /// again by the node is skipped.
+    pub publish_dedup_window_secs: u64,
+    /// Maximum number of messages the node publishes per second on the
+    /// messages topic, the others wait for their turn. `0` means unlimited.
+    pub publish_rate_limit: u32,
+    /// Pre-shared key file of a private network, in the
+    /// `/key/swarm/psk/1.0.0/` format. The node then only connects to the
+    /// peers with the same key.
+    pub pre_shared_key_path: Option<PathBuf>,
+    /// QUIC transport enabled, alongside TCP. It's not available in private
+    /// networks.
+    pub quic: bool,
+    /// Circuit relays, with their `/p2p/` component, through which the node
+    /// also listens, to be reachable behind a NAT.
+    pub relays: Vec<Multiaddr>,
+    /// Upgrade of the relayed connections to direct ones with hole punching
+    /// (`DCUtR`) enabled.
+    pub hole_punching: bool,
+    /// Mapping of the TCP listening ports on the router, with UPnP or
+    /// NAT-PMP, enabled. The external addresses of the mappings are announced
+    /// to the peers.
+    pub port_mapping: bool,
 }
 
 impl Default for Libp2pConfig {
     fn default() -> Self {
         Self {
-            listening_multiaddrs: vec!["/ip4/0.0.0.0/tcp/0".parse().expect("Infallible")],
+            listening_multiaddrs: vec![
+                "/ip4/0.0.0.0/tcp/0".parse().expect("Infallible"),
+                "/ip4/0.0.0.0/udp/0/quic-v1".parse().expect("Infallible"),
+            ],
+            announce_multiaddrs: vec![],
             bootstrap_peers: vec![],
             mdns: false,
             kademlia: true,
             target_peer_count: 75,
+            max_peer_count: 100,
+            protected_peers: vec![],
+            serve_bitswap: true,
+            chain_exchange_compression: vec![
+                ChainExchangeCompression::Zstd,
+                ChainExchangeCompression::Snappy,
+            ],
+            publish_dedup_window_secs: 120,
+            publish_rate_limit: 50,
+            pre_shared_key_path: None,
+            quic: true,
+            relays: vec![],
+            hole_punching: true,
+            port_mapping: false,
         }
     }
 }
@@ -10,6 +10,7 @@ use std::{
 };
 
 use ahash::{HashMap, HashMapExt, HashSet, HashSetExt};
+use chrono::Utc;
 use libp2p::{
     core::Multiaddr,
     identity::{PeerId, PublicKey},
@@ -154,6 +155,7 @@ impl<'a> DiscoveryConfig<'a> {
             peers,
             peer_addresses,
             target_peer_count,
+            dial_backoffs: HashMap::new(),
         }
     }
 }
@@ -183,6 +185,9 @@ pub struct DiscoveryBehaviour {
     peer_addresses: HashMap<PeerId, HashSet<Multiaddr>>,
     /// Number of connected peers to pause discovery on.
     target_peer_count: u64,
+    /// Unix timestamps, in seconds, until which the peers that failed to be
+    /// dialed are not dialed again, whatever the dial comes from
+    dial_backoffs: HashMap<PeerId, i64>,
 }
 
 impl DiscoveryBehaviour {
@@ -196,6 +201,34 @@ impl DiscoveryBehaviour {
         &self.peer_addresses
     }
 
+    /// Adds a known address of a peer to Kademlia, returns `false` when
+    /// Kademlia is not activated.
+    pub fn add_address(&mut self, peer_id: &PeerId, addr: Multiaddr) -> bool {
+        if let Some(active_kad) = self.kademlia.as_mut() {
+            active_kad.add_address(peer_id, addr);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Removes a peer from the Kademlia routing table.
+    pub fn remove_peer(&mut self, peer_id: &PeerId) {
+        if let Some(active_kad) = self.kademlia.as_mut() {
+            active_kad.remove_peer(peer_id);
+        }
+    }
+
+    /// Denies the dials to a peer until the given Unix timestamp, in seconds.
+    pub fn set_dial_backoff(&mut self, peer_id: PeerId, until: i64) {
+        self.dial_backoffs.insert(peer_id, until);
+    }
+
+    /// Allows the dials to a peer again.
+    pub fn clear_dial_backoff(&mut self, peer_id: &PeerId) {
+        self.dial_backoffs.remove(peer_id);
+    }
+
     /// Bootstrap Kademlia network
     pub fn bootstrap(&mut self) -> Result<QueryId, String> {
         if let Some(active_kad) = self.kademlia.as_mut() {
@@ -265,6 +298,16 @@ impl NetworkBehaviour for DiscoveryBehaviour {
         addresses: &[libp2p::Multiaddr],
         effective_role: libp2p::core::Endpoint,
     ) -> Result<Vec<libp2p::Multiaddr>, ConnectionDenied> {
+        if let Some(peer_id) = maybe_peer {
+            if let Some(&until) = self.dial_backoffs.get(&peer_id) {
+                if until > Utc::now().timestamp() {
+                    return Err(ConnectionDenied::new(format!(
+                        "peer {peer_id} is backed off after failing to be dialed"
+                    )));
+                }
+                self.dial_backoffs.remove(&peer_id);
+            }
+        }
         self.kademlia.handle_pending_outbound_connection(
             connection_id,
             maybe_peer,
@@ -435,3 +478,36 @@ impl NetworkBehaviour for DiscoveryBehaviour {
         Poll::Pending
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use libp2p::{core::Endpoint, identity::Keypair};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn dials_are_denied_during_the_backoff() {
+        let mut config = DiscoveryConfig::new(Keypair::generate_ed25519().public(), "test");
+        config.with_kademlia(false);
+        let mut discovery = config.finish();
+        let peer_id = PeerId::random();
+        let dial = |discovery: &mut DiscoveryBehaviour| {
+            discovery.handle_pending_outbound_connection(
+                ConnectionId::new_unchecked(0),
+                Some(peer_id),
+                &[],
+                Endpoint::Dialer,
+            )
+        };
+
+        assert!(dial(&mut discovery).is_ok());
+        discovery.set_dial_backoff(peer_id, Utc::now().timestamp() + 60);
+        assert!(dial(&mut discovery).is_err());
+        discovery.clear_dial_backoff(&peer_id);
+        assert!(dial(&mut discovery).is_ok());
+        // An expired backoff is forgotten
+        discovery.set_dial_backoff(peer_id, Utc::now().timestamp() - 1);
+        assert!(dial(&mut discovery).is_ok());
+        assert!(discovery.dial_backoffs.is_empty());
+    }
+}
@@ -10,10 +10,8 @@ use libp2p::gossipsub::{
 use crate::libp2p::{PUBSUB_BLOCK_STR, PUBSUB_MSG_STR};
 
 // All these parameters are copied from what Lotus has set for their Topic
-// scores. They are currently unused because enabling them causes GossipSub
-// blocks to come delayed usually by 1 second compared to when we have these
-// parameters disabled. Leaving these here so that we can enable and fix these
-// parameters when they are needed.
+// scores. Invalid message deliveries are the messages rejected by the topic
+// validators, see `gossip_validation`.
 
 fn build_msg_topic_config() -> TopicScoreParams {
     TopicScoreParams {
@@ -56,7 +54,7 @@ fn build_block_topic_config() -> TopicScoreParams {
         time_in_mesh_quantum: Duration::from_secs(1),
         time_in_mesh_cap: 1.0,
 
-        // deliveries decay after 10min, cap at 100 blocks
+        // deliveries decay after 1 hour, cap at 100 blocks
         first_message_deliveries_weight: 5.0,
         first_message_deliveries_decay: score_parameter_decay(Duration::from_secs(60 * 60)),
         // 100 blocks in 10 minutes
@@ -0,0 +1,271 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Validation of `gossipsub` messages. Every message is validated by the
+//! validator registered for its topic before it is forwarded to other peers
+//! and handed over to the rest of the node. Rejected messages count against
+//! the score of the peer that propagated them. The validators verify
+//! signatures, so the service runs them on blocking threads, off the swarm
+//! event loop.
+
+use ahash::{HashMap, HashMapExt};
+use libp2p::gossipsub::{IdentTopic, MessageAcceptance, TopicHash};
+use log::warn;
+
+use crate::blocks::{
+    timing::{self, BlockTiming},
+    BlockHeader, GossipBlock, BLOCK_MESSAGE_LIMIT,
+};
+use crate::libp2p::{
+    metrics::{self, values},
+    PubsubMessage,
+};
+use crate::message::{signature_cache, Message as MessageTrait, SignedMessage};
+use crate::shim::{address::Protocol, crypto::SignatureType};
+
+/// Upper bound of the size of a single message on the messages topic, same as
+/// in Lotus.
+const MAX_MESSAGE_SIZE: usize = 64 << 10;
+
+/// Outcome of the validation of a single `gossipsub` message
+#[derive(Debug)]
+pub enum ValidationResult {
+    /// The message is valid and is forwarded to other peers
+    Accept(PubsubMessage),
+    /// The message is invalid and the peer that propagated it is penalized
+    Reject(String),
+    /// The message is dropped without penalizing the peer
+    Ignore(String),
+}
+
+impl ValidationResult {
+    pub fn acceptance(&self) -> MessageAcceptance {
+        match self {
+            Self::Accept(_) => MessageAcceptance::Accept,
+            Self::Reject(_) => MessageAcceptance::Reject,
+            Self::Ignore(_) => MessageAcceptance::Ignore,
+        }
+    }
+}
+
+/// Decodes and validates the messages of a single topic
+pub trait TopicValidator: Send + Sync {
+    fn validate(&self, data: &[u8]) -> ValidationResult;
+}
+
+/// Validators of the subscribed topics
+#[derive(Default)]
+pub struct GossipValidators {
+    validators: HashMap<TopicHash, Box<dyn TopicValidator>>,
+}
+
+impl GossipValidators {
+    /// Creates the registry with the validators of the Filecoin blocks and
+    /// messages topics of the given network.
+    pub fn new(network_name: &str, block_timing: BlockTiming) -> Self {
+        let mut validators = Self {
+            validators: HashMap::new(),
+        };
+        validators.register(
+            &IdentTopic::new(format!(
+                "{}/{network_name}",
+                crate::libp2p::PUBSUB_BLOCK_STR
+            )),
+            BlockValidator {
+                timing: block_timing,
+            },
+        );
+        validators.register(
+            &IdentTopic::new(format!("{}/{network_name}", crate::libp2p::PUBSUB_MSG_STR)),
+            MessageValidator,
+        );
+        validators
+    }
+
+    /// Registers the validator of a topic, replacing the previous one
+    pub fn register(&mut self, topic: &IdentTopic, validator: impl TopicValidator + 'static) {
+        self.validators.insert(topic.hash(), Box::new(validator));
+    }
+
+    /// Validates a message with the validator of its topic. Messages of topics
+    /// without a validator are ignored.
+    pub fn validate(&self, topic: &TopicHash, data: &[u8]) -> ValidationResult {
+        let Some(validator) = self.validators.get(topic) else {
+            return ValidationResult::Ignore(format!("unknown topic {topic}"));
+        };
+        let result = validator.validate(data);
+        let value = match &result {
+            ValidationResult::Accept(_) => values::ACCEPT,
+            ValidationResult::Reject(_) => values::REJECT,
+            ValidationResult::Ignore(_) => values::IGNORE,
+        };
+        metrics::GOSSIP_VALIDATION_TOTAL
+            .with_label_values(&[topic.as_str(), value])
+            .inc();
+        result
+    }
+}
+
+/// Validates the blocks topic. Only the checks that do not need the chain
+/// state are done here, the full validation happens when the block is synced.
+pub struct BlockValidator {
+    timing: BlockTiming,
+}
+
+impl TopicValidator for BlockValidator {
+    fn validate(&self, data: &[u8]) -> ValidationResult {
+        let block = match fvm_ipld_encoding::from_slice::<GossipBlock>(data) {
+            Ok(block) => block,
+            Err(e) => return ValidationResult::Reject(format!("malformed block: {e}")),
+        };
+        let now = timing::now_secs();
+        match validate_block(&block, now, &self.timing) {
+            Ok(()) => ValidationResult::Accept(PubsubMessage::Block(block)),
+            Err(result) => result,
+        }
+    }
+}
+
+fn validate_block(
+    block: &GossipBlock,
+    now: u64,
+    timing: &BlockTiming,
+) -> Result<(), ValidationResult> {
+    let message_count = block.bls_messages.len() + block.secpk_messages.len();
+    if message_count > BLOCK_MESSAGE_LIMIT {
+        return Err(ValidationResult::Reject(format!(
+            "block has too many messages ({message_count} > {BLOCK_MESSAGE_LIMIT})"
+        )));
+    }
+    validate_header(&block.header)?;
+    // Our own clock might be off, do not penalize the peer for it
+    if let Err(e) = timing.check_clock_drift(block.header.timestamp(), now) {
+        return Err(ValidationResult::Ignore(e.to_string()));
+    }
+    if timing.is_late(block.header.timestamp(), now) {
+        warn!(
+            "Received block {} of epoch {} from miner {} {}s after its timestamp",
+            block.header.cid(),
+            block.header.epoch(),
+            block.header.miner_address(),
+            now - block.header.timestamp()
+        );
+    }
+    Ok(())
+}
+
+fn validate_header(header: &BlockHeader) -> Result<(), ValidationResult> {
+    let reject = |reason: &str| Err(ValidationResult::Reject(reason.to_owned()));
+    if header.miner_address().protocol() != Protocol::ID {
+        return reject("miner address is not an ID address");
+    }
+    match header.signature() {
+        Some(signature) if signature.signature_type() == SignatureType::Bls => {}
+        Some(_) => return reject("block signature is not a BLS signature"),
+        None => return reject("block has no signature"),
+    }
+    if header.election_proof().is_none() {
+        return reject("block has no election proof");
+    }
+    if header.ticket().is_none() {
+        return reject("block has no ticket");
+    }
+    if header.bls_aggregate().is_none() {
+        return reject("block has no BLS aggregate signature");
+    }
+    Ok(())
+}
+
+/// Validates the messages topic. The signature is verified when the sender is
+/// given by its key address, messages sent from ID addresses are verified by
+/// the message pool that can resolve the key.
+pub struct MessageValidator;
+
+impl TopicValidator for MessageValidator {
+    fn validate(&self, data: &[u8]) -> ValidationResult {
+        if data.len() > MAX_MESSAGE_SIZE {
+            return ValidationResult::Reject(format!(
+                "message is too big ({} > {MAX_MESSAGE_SIZE})",
+                data.len()
+            ));
+        }
+        let message = match fvm_ipld_encoding::from_slice::<SignedMessage>(data) {
+            Ok(message) => message,
+            Err(e) => return ValidationResult::Reject(format!("malformed message: {e}")),
+        };
+        if message.from().protocol() != Protocol::ID {
+            if let Err(e) = signature_cache::verify_signed_message(&message) {
+                return ValidationResult::Reject(format!("invalid message signature: {e}"));
+            }
+        }
+        ValidationResult::Accept(PubsubMessage::Message(message))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::blocks::{ElectionProof, Ticket};
+    use crate::shim::{address::Address, crypto::Signature};
+
+    use super::*;
+    use crate::networks::ChainConfig;
+
+    fn timing() -> BlockTiming {
+        BlockTiming::from(&ChainConfig::default())
+    }
+
+    fn block(ticket: Option<Ticket>, timestamp: u64) -> GossipBlock {
+        let header = BlockHeader::builder()
+            .miner_address(Address::new_id(1000))
+            .signature(Some(Signature::new_bls(vec![0; 96])))
+            .election_proof(Some(ElectionProof::default()))
+            .ticket(ticket)
+            .bls_aggregate(Some(Signature::new_bls(vec![0; 96])))
+            .timestamp(timestamp)
+            .build()
+            .unwrap();
+        GossipBlock {
+            header,
+            bls_messages: vec![],
+            secpk_messages: vec![],
+        }
+    }
+
+    #[test]
+    fn accepts_sane_block() {
+        assert!(validate_block(&block(Some(Ticket::default()), 100), 100, &timing()).is_ok());
+    }
+
+    #[test]
+    fn ignores_block_from_the_future() {
+        let drift = timing().allowable_clock_drift_secs;
+        let block = block(Some(Ticket::default()), 100 + drift + 1);
+        let result = validate_block(&block, 100, &timing());
+        assert!(matches!(result, Err(ValidationResult::Ignore(_))));
+    }
+
+    #[test]
+    fn rejects_block_without_ticket() {
+        let result = validate_block(&block(None, 100), 100, &timing());
+        assert!(matches!(result, Err(ValidationResult::Reject(_))));
+    }
+
+    #[test]
+    fn rejects_malformed_data_and_counts_it() {
+        let validators = GossipValidators::new("testnet", timing());
+        let topic = IdentTopic::new(format!("{}/testnet", crate::libp2p::PUBSUB_MSG_STR)).hash();
+        let counter =
+            metrics::GOSSIP_VALIDATION_TOTAL.with_label_values(&[topic.as_str(), values::REJECT]);
+        let before = counter.get();
+        let result = validators.validate(&topic, b"garbage");
+        assert_eq!(result.acceptance(), MessageAcceptance::Reject);
+        assert_eq!(counter.get(), before + 1);
+    }
+
+    #[test]
+    fn ignores_unknown_topic() {
+        let validators = GossipValidators::new("testnet", timing());
+        let result = validators.validate(&IdentTopic::new("unknown").hash(), b"");
+        assert_eq!(result.acceptance(), MessageAcceptance::Ignore);
+    }
+}
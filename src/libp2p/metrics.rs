@@ -2,7 +2,10 @@
 // SPDX-License-Identifier: Apache-2.0, MIT
 
 use lazy_static::lazy_static;
-use prometheus::core::{AtomicU64, GenericCounter, GenericGauge};
+use prometheus::{
+    core::{AtomicU64, GenericCounter, GenericCounterVec, GenericGauge, Opts},
+    Histogram, HistogramOpts, HistogramVec,
+};
 
 lazy_static! {
     pub static ref PEER_FAILURE_TOTAL: Box<GenericCounter<AtomicU64>> = {
@@ -46,4 +49,219 @@ lazy_static! {
             .expect("Registering the bad_peers metric with the metrics registry must succeed");
         bad_peers
     };
+    pub static ref PROTECTED_PEERS: Box<GenericGauge<AtomicU64>> = {
+        let protected_peers = Box::new(
+            GenericGauge::<AtomicU64>::new(
+                "protected_peers",
+                "Number of peers that are protected from pruning",
+            )
+            .expect("Defining the protected_peers metric must succeed"),
+        );
+        prometheus::default_registry()
+            .register(protected_peers.clone())
+            .expect(
+                "Registering the protected_peers metric with the metrics registry must succeed",
+            );
+        protected_peers
+    };
+    pub static ref PRUNED_PEERS_TOTAL: Box<GenericCounter<AtomicU64>> = {
+        let pruned_peers_total = Box::new(
+            GenericCounter::<AtomicU64>::new(
+                "pruned_peers_total",
+                "Total number of low-quality peers pruned above the high-water mark",
+            )
+            .expect("Defining the pruned_peers_total metric must succeed"),
+        );
+        prometheus::default_registry()
+            .register(pruned_peers_total.clone())
+            .expect(
+                "Registering the pruned_peers_total metric with the metrics registry must succeed",
+            );
+        pruned_peers_total
+    };
+    pub static ref REACHABILITY: Box<GenericGauge<AtomicU64>> = {
+        let reachability = Box::new(
+            GenericGauge::<AtomicU64>::new(
+                "reachability",
+                "Reachability of the node: 0 for unknown, 1 for public and 2 for private",
+            )
+            .expect("Defining the reachability metric must succeed"),
+        );
+        prometheus::default_registry()
+            .register(reachability.clone())
+            .expect("Registering the reachability metric with the metrics registry must succeed");
+        reachability
+    };
+    pub static ref GOSSIP_VALIDATION_TOTAL: Box<GenericCounterVec<AtomicU64>> = {
+        let gossip_validation_total = Box::new(
+            GenericCounterVec::<AtomicU64>::new(
+                Opts::new(
+                    "gossip_validation_total",
+                    "Total number of validated gossipsub messages by topic and result",
+                ),
+                &[labels::TOPIC, labels::RESULT],
+            )
+            .expect("Defining the gossip_validation_total metric must succeed"),
+        );
+        prometheus::default_registry().register(gossip_validation_total.clone()).expect(
+            "Registering the gossip_validation_total metric with the metrics registry must succeed"
+        );
+        gossip_validation_total
+    };
+    pub static ref CHAIN_EXCHANGE_COMPRESSION_BYTES_TOTAL: Box<GenericCounterVec<AtomicU64>> = {
+        let chain_exchange_compression_bytes_total = Box::new(
+            GenericCounterVec::<AtomicU64>::new(
+                Opts::new(
+                    "chain_exchange_compression_bytes_total",
+                    "Total size of the compressed chain exchange responses, raw and compressed, by compression and operation",
+                ),
+                &[labels::COMPRESSION, labels::OPERATION, labels::FORM],
+            )
+            .expect("Defining the chain_exchange_compression_bytes_total metric must succeed"),
+        );
+        prometheus::default_registry().register(chain_exchange_compression_bytes_total.clone()).expect(
+            "Registering the chain_exchange_compression_bytes_total metric with the metrics registry must succeed"
+        );
+        chain_exchange_compression_bytes_total
+    };
+    pub static ref CHAIN_EXCHANGE_COMPRESSION_TIME: Box<HistogramVec> = {
+        let chain_exchange_compression_time = Box::new(
+            HistogramVec::new(
+                HistogramOpts {
+                    common_opts: Opts::new(
+                        "chain_exchange_compression_time",
+                        "Duration of the compression and decompression of chain exchange responses",
+                    ),
+                    buckets: vec![],
+                },
+                &[labels::COMPRESSION, labels::OPERATION],
+            )
+            .expect("Defining the chain_exchange_compression_time metric must succeed"),
+        );
+        prometheus::default_registry().register(chain_exchange_compression_time.clone()).expect(
+            "Registering the chain_exchange_compression_time metric with the metrics registry must succeed"
+        );
+        chain_exchange_compression_time
+    };
+    pub static ref GOSSIP_PUBLISH_SKIPPED_TOTAL: Box<GenericCounter<AtomicU64>> = {
+        let gossip_publish_skipped_total = Box::new(
+            GenericCounter::<AtomicU64>::new(
+                "gossip_publish_skipped_total",
+                "Total number of gossipsub messages not published by the node, as duplicates of recent ones or over the queue capacity",
+            )
+            .expect("Defining the gossip_publish_skipped_total metric must succeed"),
+        );
+        prometheus::default_registry()
+            .register(gossip_publish_skipped_total.clone())
+            .expect(
+                "Registering the gossip_publish_skipped_total metric with the metrics registry must succeed",
+            );
+        gossip_publish_skipped_total
+    };
+    pub static ref GOSSIP_PUBLISH_QUEUE_LEN: Box<GenericGauge<AtomicU64>> = {
+        let gossip_publish_queue_len = Box::new(
+            GenericGauge::<AtomicU64>::new(
+                "gossip_publish_queue_len",
+                "Number of gossipsub messages waiting for publication within the rate limit",
+            )
+            .expect("Defining the gossip_publish_queue_len metric must succeed"),
+        );
+        prometheus::default_registry()
+            .register(gossip_publish_queue_len.clone())
+            .expect(
+                "Registering the gossip_publish_queue_len metric with the metrics registry must succeed",
+            );
+        gossip_publish_queue_len
+    };
+    pub static ref NETWORK_BANDWIDTH_BYTES_TOTAL: Box<GenericCounterVec<AtomicU64>> = {
+        let network_bandwidth_bytes_total = Box::new(
+            GenericCounterVec::<AtomicU64>::new(
+                Opts::new(
+                    "network_bandwidth_bytes_total",
+                    "Total number of bytes exchanged with the peers by protocol and direction",
+                ),
+                &[labels::PROTOCOL, labels::DIRECTION],
+            )
+            .expect("Defining the network_bandwidth_bytes_total metric must succeed"),
+        );
+        prometheus::default_registry().register(network_bandwidth_bytes_total.clone()).expect(
+            "Registering the network_bandwidth_bytes_total metric with the metrics registry must succeed"
+        );
+        network_bandwidth_bytes_total
+    };
+    pub static ref HELLO_ROUND_TRIP_TIME: Box<Histogram> = {
+        let hello_round_trip_time = Box::new(
+            Histogram::with_opts(HistogramOpts {
+                common_opts: Opts::new(
+                    "hello_round_trip_time",
+                    "Round trip time of the hello requests, without the time spent by the peers on them",
+                ),
+                buckets: vec![],
+            })
+            .expect("Defining the hello_round_trip_time metric must succeed"),
+        );
+        prometheus::default_registry().register(hello_round_trip_time.clone()).expect(
+            "Registering the hello_round_trip_time metric with the metrics registry must succeed"
+        );
+        hello_round_trip_time
+    };
+    pub static ref CLOCK_SKEWED_PEERS: Box<GenericGauge<AtomicU64>> = {
+        let clock_skewed_peers = Box::new(
+            GenericGauge::<AtomicU64>::new(
+                "clock_skewed_peers",
+                "Number of connected peers whose clocks are off from the one of the node by more than the allowed drift",
+            )
+            .expect("Defining the clock_skewed_peers metric must succeed"),
+        );
+        prometheus::default_registry()
+            .register(clock_skewed_peers.clone())
+            .expect(
+                "Registering the clock_skewed_peers metric with the metrics registry must succeed",
+            );
+        clock_skewed_peers
+    };
+    pub static ref CLOCK_SKEW_DETECTED: Box<GenericGauge<AtomicU64>> = {
+        let clock_skew_detected = Box::new(
+            GenericGauge::<AtomicU64>::new(
+                "clock_skew_detected",
+                "Whether most of the peers disagree with the clock of the node, 1 if so",
+            )
+            .expect("Defining the clock_skew_detected metric must succeed"),
+        );
+        prometheus::default_registry()
+            .register(clock_skew_detected.clone())
+            .expect(
+                "Registering the clock_skew_detected metric with the metrics registry must succeed",
+            );
+        clock_skew_detected
+    };
+}
+
+pub mod labels {
+    pub const TOPIC: &str = "topic";
+    pub const RESULT: &str = "result";
+    pub const COMPRESSION: &str = "compression";
+    pub const OPERATION: &str = "operation";
+    pub const FORM: &str = "form";
+    pub const PROTOCOL: &str = "protocol";
+    pub const DIRECTION: &str = "direction";
+}
+
+pub mod values {
+    // gossip_validation_total
+    pub const ACCEPT: &str = "accept";
+    pub const REJECT: &str = "reject";
+    pub const IGNORE: &str = "ignore";
+
+    // chain_exchange_compression_bytes_total, chain_exchange_compression_time
+    pub const SNAPPY: &str = "snappy";
+    pub const ZSTD: &str = "zstd";
+    pub const COMPRESS: &str = "compress";
+    pub const DECOMPRESS: &str = "decompress";
+    pub const RAW: &str = "raw";
+    pub const COMPRESSED: &str = "compressed";
+
+    // network_bandwidth_bytes_total
+    pub const IN: &str = "in";
+    pub const OUT: &str = "out";
 }
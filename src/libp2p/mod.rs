@@ -1,14 +1,21 @@
 // Copyright 2019-2023 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
+mod bandwidth;
 mod behaviour;
+mod bootstrap;
 pub mod chain_exchange;
+mod clock_skew;
 mod config;
 mod discovery;
 mod gossip_params;
+mod gossip_validation;
 pub mod hello;
 mod metrics;
+mod nat;
 mod peer_manager;
+mod peer_store;
+mod publish_queue;
 pub mod rpc;
 mod service;
 
@@ -20,7 +27,10 @@ pub use libp2p::{
 pub use multihash::Multihash;
 
 pub(in crate::libp2p) use self::behaviour::*;
-pub use self::{config::*, peer_manager::*, service::*};
+pub use self::{
+    bandwidth::BandwidthStats, bootstrap::BOOTSTRAP_PEERS_FILE_NAME, clock_skew::ClockSample,
+    config::*, nat::Reachability, peer_manager::*, peer_store::PeerStore, service::*,
+};
 #[cfg(test)]
 mod tests {
     mod decode_test;
@@ -0,0 +1,301 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Reachability detection. The node is considered publicly reachable once a
+//! peer with a public address dials it. It is considered to be behind a NAT
+//! when enough distinct peers observe the same public address of the node but
+//! nobody manages to dial it.
+//!
+//! When enabled, the TCP listening ports of the node are also mapped on the
+//! router, with UPnP or NAT-PMP, so that the peers can dial it.
+
+use std::{
+    net::{Ipv4Addr, Ipv6Addr, SocketAddrV4},
+    time::Duration,
+};
+
+use ahash::{HashMap, HashSet};
+use anyhow::Context;
+use libp2p::{multiaddr::Protocol, Multiaddr, PeerId};
+use log::{info, warn};
+
+/// Number of distinct peers that need to observe a public address of the node
+/// before it is considered to be behind a NAT.
+const MIN_OBSERVERS: usize = 4;
+
+/// Upper bound of the tracked observed addresses.
+const MAX_OBSERVED_ADDRS: usize = 64;
+
+/// Lease of the port mappings on the router, renewed at half of it.
+const PORT_MAPPING_LEASE: Duration = Duration::from_secs(60 * 60);
+
+/// Time given to the router to answer a port mapping request.
+const PORT_MAPPING_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Description of the port mappings on the router.
+const PORT_MAPPING_DESCRIPTION: &str = "forest";
+
+/// Reachability of the node from the public internet, the numeric values
+/// match the ones of `go-libp2p`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Reachability {
+    #[default]
+    Unknown = 0,
+    Public = 1,
+    Private = 2,
+}
+
+#[derive(Debug, Default)]
+pub(in crate::libp2p) struct ReachabilityTracker {
+    /// Public IP addresses of the node as observed by its peers
+    observers: HashMap<Multiaddr, HashSet<PeerId>>,
+    /// Whether a peer with a public address has dialed the node
+    public_inbound: bool,
+    status: Reachability,
+}
+
+impl ReachabilityTracker {
+    pub fn status(&self) -> Reachability {
+        self.status
+    }
+
+    /// Public IP address of the node that most peers agree on
+    pub fn public_addr(&self) -> Option<&Multiaddr> {
+        self.observers
+            .iter()
+            .max_by_key(|(_, peers)| peers.len())
+            .map(|(addr, _)| addr)
+    }
+
+    /// Records the address of the node as observed by a peer, returns the new
+    /// status when it changes.
+    pub fn on_observed_addr(&mut self, peer: PeerId, observed: &Multiaddr) -> Option<Reachability> {
+        let ip = observed.iter().next().filter(is_public_ip)?;
+        let addr = Multiaddr::empty().with(ip);
+        if self.observers.len() >= MAX_OBSERVED_ADDRS && !self.observers.contains_key(&addr) {
+            return None;
+        }
+        self.observers.entry(addr).or_default().insert(peer);
+        self.evaluate()
+    }
+
+    /// Records an inbound connection, returns the new status when it changes.
+    pub fn on_inbound_connection(&mut self, remote: &Multiaddr) -> Option<Reachability> {
+        if remote.iter().next().filter(is_public_ip).is_some() {
+            self.public_inbound = true;
+        }
+        self.evaluate()
+    }
+
+    fn evaluate(&mut self) -> Option<Reachability> {
+        let status = if self.public_inbound {
+            Reachability::Public
+        } else if self
+            .observers
+            .values()
+            .any(|peers| peers.len() >= MIN_OBSERVERS)
+        {
+            Reachability::Private
+        } else {
+            Reachability::Unknown
+        };
+        if status != self.status {
+            self.status = status;
+            Some(status)
+        } else {
+            None
+        }
+    }
+}
+
+/// Local address of a listening address whose port can be mapped on the
+/// router, a TCP port on a private IPv4 address.
+pub(in crate::libp2p) fn port_mapping_candidate(listen_addr: &Multiaddr) -> Option<SocketAddrV4> {
+    let mut protocols = listen_addr.iter();
+    match (protocols.next(), protocols.next(), protocols.next()) {
+        (Some(Protocol::Ip4(ip)), Some(Protocol::Tcp(port)), None) if ip.is_private() => {
+            Some(SocketAddrV4::new(ip, port))
+        }
+        _ => None,
+    }
+}
+
+/// Keeps a TCP port of the node mapped on the router, renewing the lease
+/// before it expires, and sends the external address of the mapping whenever
+/// it changes. Stops once the receiver is gone, the mapping then expires with
+/// its lease.
+pub(in crate::libp2p) async fn keep_port_mapped(
+    local: SocketAddrV4,
+    tx: flume::Sender<(u16, Multiaddr)>,
+) {
+    let mut mapped = None;
+    while !tx.is_disconnected() {
+        match map_port(local).await {
+            Ok(external) if mapped.as_ref() != Some(&external) => {
+                info!("Mapped port {} on the router to {external}", local.port());
+                if tx
+                    .send_async((local.port(), external.clone()))
+                    .await
+                    .is_err()
+                {
+                    return;
+                }
+                mapped = Some(external);
+            }
+            Ok(_) => {}
+            Err(e) => warn!("Failed to map port {} on the router: {e:#}", local.port()),
+        }
+        tokio::time::sleep(PORT_MAPPING_LEASE / 2).await;
+    }
+}
+
+/// Maps a TCP port of the node on the router with UPnP, or with NAT-PMP when
+/// the router doesn't support UPnP, and returns the external address of the
+/// mapping.
+async fn map_port(local: SocketAddrV4) -> anyhow::Result<Multiaddr> {
+    let external = match map_port_with_upnp(local).await {
+        Ok(external) => external,
+        Err(upnp_error) => map_port_with_natpmp(local)
+            .await
+            .with_context(|| format!("UPnP failed: {upnp_error:#}, NAT-PMP failed"))?,
+    };
+    Ok(Multiaddr::empty()
+        .with(Protocol::Ip4(*external.ip()))
+        .with(Protocol::Tcp(external.port())))
+}
+
+async fn map_port_with_upnp(local: SocketAddrV4) -> anyhow::Result<SocketAddrV4> {
+    let gateway = igd::aio::search_gateway(igd::SearchOptions {
+        timeout: Some(PORT_MAPPING_TIMEOUT),
+        ..Default::default()
+    })
+    .await?;
+    let ip = gateway.get_external_ip().await?;
+    gateway
+        .add_port(
+            igd::PortMappingProtocol::TCP,
+            local.port(),
+            local,
+            PORT_MAPPING_LEASE.as_secs() as u32,
+            PORT_MAPPING_DESCRIPTION,
+        )
+        .await?;
+    Ok(SocketAddrV4::new(ip, local.port()))
+}
+
+async fn map_port_with_natpmp(local: SocketAddrV4) -> anyhow::Result<SocketAddrV4> {
+    let client = natpmp::new_tokio_natpmp().await?;
+    client.send_public_address_request().await?;
+    let ip = match tokio::time::timeout(PORT_MAPPING_TIMEOUT, client.recv()).await?? {
+        natpmp::Response::Gateway(gateway) => *gateway.public_address(),
+        response => anyhow::bail!("unexpected response {response:?}"),
+    };
+    client
+        .send_port_mapping_request(
+            natpmp::Protocol::TCP,
+            local.port(),
+            local.port(),
+            PORT_MAPPING_LEASE.as_secs() as u32,
+        )
+        .await?;
+    // The router may pick another external port
+    let port = match tokio::time::timeout(PORT_MAPPING_TIMEOUT, client.recv()).await?? {
+        natpmp::Response::TCP(mapping) => mapping.public_port(),
+        response => anyhow::bail!("unexpected response {response:?}"),
+    };
+    Ok(SocketAddrV4::new(ip, port))
+}
+
+fn is_public_ip(protocol: &Protocol) -> bool {
+    match protocol {
+        Protocol::Ip4(ip) => is_public_ipv4(ip),
+        Protocol::Ip6(ip) => is_public_ipv6(ip),
+        _ => false,
+    }
+}
+
+fn is_public_ipv4(ip: &Ipv4Addr) -> bool {
+    !(ip.is_private()
+        || ip.is_loopback()
+        || ip.is_link_local()
+        || ip.is_unspecified()
+        || ip.is_broadcast()
+        || ip.is_documentation()
+        // Shared address space, see RFC 6598
+        || (ip.octets()[0] == 100 && (ip.octets()[1] & 0b1100_0000 == 0b0100_0000)))
+}
+
+fn is_public_ipv6(ip: &Ipv6Addr) -> bool {
+    !(ip.is_loopback()
+        || ip.is_unspecified()
+        // Unique local addresses, see RFC 4193
+        || (ip.segments()[0] & 0xfe00) == 0xfc00
+        // Link-local unicast addresses
+        || (ip.segments()[0] & 0xffc0) == 0xfe80)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn private_after_enough_observers() {
+        let mut tracker = ReachabilityTracker::default();
+        let observed: Multiaddr = "/ip4/1.2.3.4/tcp/51234".parse().unwrap();
+        for _ in 0..MIN_OBSERVERS - 1 {
+            assert_eq!(tracker.on_observed_addr(PeerId::random(), &observed), None);
+        }
+        assert_eq!(
+            tracker.on_observed_addr(PeerId::random(), &observed),
+            Some(Reachability::Private)
+        );
+        assert_eq!(
+            tracker.public_addr(),
+            Some(&"/ip4/1.2.3.4".parse().unwrap())
+        );
+    }
+
+    #[test]
+    fn public_after_inbound_connection() {
+        let mut tracker = ReachabilityTracker::default();
+        let local: Multiaddr = "/ip4/192.168.1.2/tcp/1234".parse().unwrap();
+        assert_eq!(tracker.on_inbound_connection(&local), None);
+        let remote: Multiaddr = "/ip4/8.8.8.8/tcp/1234".parse().unwrap();
+        assert_eq!(
+            tracker.on_inbound_connection(&remote),
+            Some(Reachability::Public)
+        );
+        assert_eq!(tracker.status(), Reachability::Public);
+    }
+
+    #[test]
+    fn maps_tcp_ports_of_private_addresses() {
+        let candidate = |addr: &str| port_mapping_candidate(&addr.parse().unwrap());
+        assert_eq!(
+            candidate("/ip4/192.168.1.2/tcp/1234"),
+            Some("192.168.1.2:1234".parse().unwrap())
+        );
+        assert_eq!(candidate("/ip4/127.0.0.1/tcp/1234"), None);
+        assert_eq!(candidate("/ip4/8.8.8.8/tcp/1234"), None);
+        assert_eq!(candidate("/ip4/192.168.1.2/udp/1234/quic-v1"), None);
+        assert_eq!(candidate("/ip4/192.168.1.2/tcp/1234/ws"), None);
+        assert_eq!(candidate("/ip6/fd00::1/tcp/1234"), None);
+    }
+
+    #[test]
+    fn ignores_non_public_observations() {
+        let mut tracker = ReachabilityTracker::default();
+        for addr in [
+            "/ip4/10.0.0.1/tcp/1",
+            "/ip4/127.0.0.1/tcp/1",
+            "/ip6/fd00::1/tcp/1",
+        ] {
+            let addr: Multiaddr = addr.parse().unwrap();
+            for _ in 0..MIN_OBSERVERS {
+                tracker.on_observed_addr(PeerId::random(), &addr);
+            }
+        }
+        assert_eq!(tracker.status(), Reachability::Unknown);
+        assert_eq!(tracker.public_addr(), None);
+    }
+}
@@ -3,18 +3,21 @@
 
 use std::{
     cmp::Ordering,
-    sync::Arc,
+    sync::{
+        atomic::{self, AtomicUsize},
+        Arc,
+    },
     time::{Duration, Instant},
 };
 
 use crate::blocks::Tipset;
 use ahash::{HashMap, HashSet};
 use flume::{Receiver, Sender};
-use log::{debug, trace, warn};
+use log::{debug, info, trace, warn};
 use rand::seq::SliceRandom;
 use tokio::sync::RwLock;
 
-use crate::libp2p::*;
+use crate::libp2p::{clock_skew::ClockSkewDetector, *};
 
 /// New peer multiplier slightly less than 1 to incentivize choosing new peers.
 const NEW_PEER_MUL: f64 = 0.9;
@@ -22,6 +25,10 @@ const NEW_PEER_MUL: f64 = 0.9;
 /// Defines max number of peers to send each chain exchange request to.
 pub(in crate::libp2p) const SHUFFLE_PEERS_PREFIX: usize = 100;
 
+/// Interval at which the connected peers are checked against the high-water
+/// mark.
+const PEER_PRUNING_INTERVAL: Duration = Duration::from_secs(60);
+
 /// Local duration multiplier, affects duration delta change.
 const LOCAL_INV_ALPHA: u32 = 5;
 /// Global duration multiplier, affects duration delta change.
@@ -51,6 +58,41 @@ impl PeerInfo {
     }
 }
 
+impl PeerInfo {
+    /// Cost of sending a request to the peer, based on its failure rate and
+    /// latency. Lower is better.
+    fn cost(&self, average_global_time: Duration) -> f64 {
+        if (self.successes + self.failures) > 0 {
+            // Calculate cost based on fail rate and latency
+            let fail_rate = f64::from(self.failures) / f64::from(self.successes);
+            self.average_time.as_secs_f64() + fail_rate * average_global_time.as_secs_f64()
+        } else {
+            // There have been no failures or successes
+            average_global_time.as_secs_f64() * NEW_PEER_MUL
+        }
+    }
+}
+
+/// Request statistics and score of a peer. The score is in the `[0, 1]`
+/// range, higher is better.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PeerStats {
+    pub score: f64,
+    pub successes: u32,
+    pub failures: u32,
+    pub average_time: Duration,
+    pub protected: bool,
+}
+
+/// Converts a request cost into a score, higher is better.
+fn cost_to_score(cost: f64) -> f64 {
+    if cost.is_finite() {
+        1. / (1. + cost)
+    } else {
+        0.
+    }
+}
+
 /// Peer tracking sets, these are handled together to avoid race conditions or
 /// deadlocks when updating state.
 #[derive(Default)]
@@ -75,17 +117,30 @@ pub struct PeerManager {
     peer_ops_rx: Receiver<PeerOperation>,
     /// Peer ban list, key is peer id, value is expiration time
     peer_ban_list: RwLock<HashMap<PeerId, Option<Instant>>>,
+    /// Peers that are never pruned
+    protected_peers: RwLock<HashSet<PeerId>>,
+    /// Peer count the pruning goes down to
+    low_water: AtomicUsize,
+    /// Peer count above which the peers are pruned
+    high_water: AtomicUsize,
+    /// Clock samples of the peers, from the hello requests
+    clock_skew: RwLock<ClockSkewDetector>,
 }
 
 impl Default for PeerManager {
     fn default() -> Self {
         let (peer_ops_tx, peer_ops_rx) = flume::unbounded();
+        let config = Libp2pConfig::default();
         PeerManager {
             peers: Default::default(),
             avg_global_time: Default::default(),
             peer_ops_tx,
             peer_ops_rx,
             peer_ban_list: Default::default(),
+            protected_peers: Default::default(),
+            low_water: AtomicUsize::new(config.target_peer_count as usize),
+            high_water: AtomicUsize::new(config.max_peer_count as usize),
+            clock_skew: Default::default(),
         }
     }
 }
@@ -118,17 +173,7 @@ impl PeerManager {
         let mut peers: Vec<_> = peer_lk
             .full_peers
             .iter()
-            .map(|(p, info)| {
-                let cost = if (info.successes + info.failures) > 0 {
-                    // Calculate cost based on fail rate and latency
-                    let fail_rate = f64::from(info.failures) / f64::from(info.successes);
-                    info.average_time.as_secs_f64() + fail_rate * average_time.as_secs_f64()
-                } else {
-                    // There have been no failures or successes
-                    average_time.as_secs_f64() * NEW_PEER_MUL
-                };
-                (p, cost)
-            })
+            .map(|(p, info)| (p, info.cost(*average_time)))
             .collect();
 
         // Unstable sort because hashmap iter order doesn't need to be preserved.
@@ -228,9 +273,165 @@ impl PeerManager {
         if removed {
             metrics::FULL_PEERS.dec();
         }
+        let mut clock_skew = self.clock_skew.write().await;
+        clock_skew.remove(peer_id);
+        metrics::CLOCK_SKEWED_PEERS.set(clock_skew.skewed_peers() as u64);
+        metrics::CLOCK_SKEW_DETECTED.set(clock_skew.skew().is_some() as u64);
         removed
     }
 
+    /// Logs the latency and clock offset of a peer measured by a hello
+    /// request, and warns when most of the peers disagree with the clock of
+    /// the node, blocks being rejected as coming from the future otherwise.
+    pub async fn log_clock_sample(&self, peer_id: PeerId, sample: ClockSample) {
+        metrics::HELLO_ROUND_TRIP_TIME.observe(sample.round_trip.as_secs_f64());
+        let mut clock_skew = self.clock_skew.write().await;
+        let was_skewed = clock_skew.skew().is_some();
+        clock_skew.insert(peer_id, sample);
+        metrics::CLOCK_SKEWED_PEERS.set(clock_skew.skewed_peers() as u64);
+        match clock_skew.skew() {
+            Some(skew) if !was_skewed => {
+                metrics::CLOCK_SKEW_DETECTED.set(1);
+                warn!(
+                    "System clock is likely off: {} of {} peers disagree with it by more than {}s, by {:.3}s (median). Blocks may be rejected as coming from the future, check the time synchronization of the host",
+                    skew.skewed_peers,
+                    skew.sampled_peers,
+                    crate::blocks::timing::DEFAULT_ALLOWABLE_CLOCK_DRIFT_SECS,
+                    skew.median_offset as f64 / 1e9,
+                );
+            }
+            None if was_skewed => {
+                metrics::CLOCK_SKEW_DETECTED.set(0);
+                info!("System clock agrees with most peers again");
+            }
+            _ => {}
+        }
+    }
+
+    /// Returns the request statistics and scores of the full peers.
+    pub async fn peer_stats(&self) -> HashMap<PeerId, PeerStats> {
+        let peers = self.peers.read().await;
+        let average_time = *self.avg_global_time.read().await;
+        let protected_peers = self.protected_peers.read().await;
+        peers
+            .full_peers
+            .iter()
+            .map(|(&peer, info)| {
+                let stats = PeerStats {
+                    score: cost_to_score(info.cost(average_time)),
+                    successes: info.successes,
+                    failures: info.failures,
+                    average_time: info.average_time,
+                    protected: protected_peers.contains(&peer),
+                };
+                (peer, stats)
+            })
+            .collect()
+    }
+
+    /// Returns the score of a full peer.
+    pub async fn peer_score(&self, peer_id: &PeerId) -> Option<f64> {
+        let peers = self.peers.read().await;
+        let average_time = *self.avg_global_time.read().await;
+        peers
+            .full_peers
+            .get(peer_id)
+            .map(|info| cost_to_score(info.cost(average_time)))
+    }
+
+    /// Returns the number of peers marked as bad.
+    pub async fn bad_peer_count(&self) -> usize {
+        self.peers.read().await.bad_peers.len()
+    }
+
+    /// Protects a peer from being pruned.
+    pub async fn protect_peer(&self, peer_id: PeerId) {
+        if self.protected_peers.write().await.insert(peer_id) {
+            metrics::PROTECTED_PEERS.inc();
+        }
+    }
+
+    /// Removes the pruning protection of a peer.
+    pub async fn unprotect_peer(&self, peer_id: &PeerId) {
+        if self.protected_peers.write().await.remove(peer_id) {
+            metrics::PROTECTED_PEERS.dec();
+        }
+    }
+
+    /// Returns true if the peer is protected from being pruned.
+    pub async fn is_peer_protected(&self, peer_id: &PeerId) -> bool {
+        self.protected_peers.read().await.contains(peer_id)
+    }
+
+    /// When there are more than `high_water` full peers, removes the
+    /// lowest-scored unprotected peers until `low_water` peers are left and
+    /// requests to disconnect from them. Returns the pruned peers.
+    pub async fn prune_peers(&self, low_water: usize, high_water: usize) -> Vec<PeerId> {
+        let pruned: Vec<_> = {
+            let mut peers = self.peers.write().await;
+            if peers.full_peers.len() <= high_water {
+                return vec![];
+            }
+            let excess = peers.full_peers.len() - low_water.min(high_water);
+            let average_time = *self.avg_global_time.read().await;
+            let protected_peers = self.protected_peers.read().await;
+            let mut candidates: Vec<_> = peers
+                .full_peers
+                .iter()
+                .filter(|(p, _)| !protected_peers.contains(p))
+                .map(|(&p, info)| (p, info.cost(average_time)))
+                .collect();
+            // Most expensive peers first
+            candidates
+                .sort_unstable_by(|(_, v1), (_, v2)| v2.partial_cmp(v1).unwrap_or(Ordering::Equal));
+            let pruned: Vec<_> = candidates
+                .into_iter()
+                .take(excess)
+                .map(|(p, _)| p)
+                .collect();
+            for peer in pruned.iter() {
+                if remove_peer(&mut peers, peer) {
+                    metrics::FULL_PEERS.dec();
+                }
+            }
+            pruned
+        };
+
+        for &peer in pruned.iter() {
+            metrics::PRUNED_PEERS_TOTAL.inc();
+            if let Err(e) = self
+                .peer_ops_tx
+                .send_async(PeerOperation::Disconnect(peer))
+                .await
+            {
+                warn!("prune_peers err: {e}");
+            }
+        }
+        pruned
+    }
+
+    /// Periodically prunes the peers, see [`PeerManager::prune_peers`].
+    /// Sets the peer counts used by [`PeerManager::peer_pruning_loop_task`].
+    pub fn set_peer_count_limits(&self, low_water: usize, high_water: usize) {
+        self.low_water.store(low_water, atomic::Ordering::Relaxed);
+        self.high_water.store(high_water, atomic::Ordering::Relaxed);
+    }
+
+    pub async fn peer_pruning_loop_task(self: Arc<Self>) -> anyhow::Result<()> {
+        loop {
+            tokio::time::sleep(PEER_PRUNING_INTERVAL).await;
+            let pruned = self
+                .prune_peers(
+                    self.low_water.load(atomic::Ordering::Relaxed),
+                    self.high_water.load(atomic::Ordering::Relaxed),
+                )
+                .await;
+            if !pruned.is_empty() {
+                debug!("Pruned {} low-quality peers", pruned.len());
+            }
+        }
+    }
+
     /// Gets peer operation receiver
     pub fn peer_ops_rx(&self) -> &Receiver<PeerOperation> {
         &self.peer_ops_rx
@@ -314,4 +515,63 @@ fn log_time(info: &mut PeerInfo, dur: Duration) {
 pub enum PeerOperation {
     Ban(PeerId, String),
     Unban(PeerId),
+    Disconnect(PeerId),
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::blocks::{BlockHeader, Tipset};
+    use crate::shim::address::Address;
+
+    use super::*;
+
+    fn head() -> Arc<Tipset> {
+        let header = BlockHeader::builder()
+            .miner_address(Address::new_id(0))
+            .build()
+            .unwrap();
+        Arc::new(Tipset::from(header))
+    }
+
+    #[tokio::test]
+    async fn scores_prefer_reliable_peers() {
+        let pm = PeerManager::default();
+        let (good, bad) = (PeerId::random(), PeerId::random());
+        pm.log_success(good, Duration::from_millis(100)).await;
+        pm.log_success(bad, Duration::from_millis(100)).await;
+        pm.log_failure(bad, Duration::from_secs(5)).await;
+        pm.log_global_success(Duration::from_millis(200)).await;
+
+        let good_score = pm.peer_score(&good).await.unwrap();
+        let bad_score = pm.peer_score(&bad).await.unwrap();
+        assert!(good_score > bad_score);
+        assert!((0. ..=1.).contains(&good_score));
+        assert_eq!(pm.sorted_peers().await, vec![good, bad]);
+    }
+
+    #[tokio::test]
+    async fn prune_peers_keeps_protected_and_best_peers() {
+        let pm = PeerManager::default();
+        let peers: Vec<_> = (0..6).map(|_| PeerId::random()).collect();
+        for (i, &peer) in peers.iter().enumerate() {
+            pm.update_peer_head(peer, head()).await;
+            pm.log_success(peer, Duration::from_millis(100 * (i as u64 + 1)))
+                .await;
+        }
+        // The slowest peer is protected
+        pm.protect_peer(peers[5]).await;
+
+        // Below the high-water mark nothing happens
+        assert!(pm.prune_peers(2, 6).await.is_empty());
+
+        let pruned = pm.prune_peers(3, 4).await;
+        assert_eq!(pruned.len(), 3);
+        assert!(!pruned.contains(&peers[5]));
+        assert!(pruned
+            .iter()
+            .all(|p| [peers[2], peers[3], peers[4]].contains(p)));
+        assert_eq!(pm.peer_stats().await.len(), 3);
+        assert!(pm.peer_stats().await[&peers[5]].protected);
+        assert_eq!(pm.peer_ops_rx().len(), 3);
+    }
 }
@@ -0,0 +1,228 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Address book of the peers the node has connected to, persisted in the
+//! database so that a restarted node dials the peers it knows to be good
+//! instead of relying on the bootstrap peers alone. Peers failing to be dialed
+//! are backed off exponentially, and peers not seen for a while are forgotten.
+
+use std::cmp::Reverse;
+
+use crate::db::Store;
+use ahash::HashMap;
+use libp2p::{Multiaddr, PeerId};
+use serde::{Deserialize, Serialize};
+use serde_with::{serde_as, DisplayFromStr};
+
+/// Key of the peer store in the database
+const PEER_STORE_KEY: &[u8] = b"/libp2p/peers";
+
+/// Maximum number of peers kept, the ones seen last first
+const MAX_PEERS: usize = 1000;
+
+/// Maximum number of addresses kept per peer, the ones dialed last first
+const MAX_ADDRS_PER_PEER: usize = 8;
+
+/// Peers not seen for this long, in seconds, are forgotten (7 days)
+const PEER_TTL_SECS: i64 = 7 * 24 * 60 * 60;
+
+/// Backoff after the first dial failure, doubled on each new failure up to
+/// [`MAX_BACKOFF_SECS`]
+const BASE_BACKOFF_SECS: i64 = 30;
+const MAX_BACKOFF_SECS: i64 = 60 * 60;
+
+#[serde_as]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PeerRecord {
+    #[serde_as(as = "DisplayFromStr")]
+    pub peer_id: PeerId,
+    /// Addresses the peer was dialed at, the last one first
+    pub addrs: Vec<Multiaddr>,
+    /// Unix timestamps, in seconds, of the last connection or disconnection
+    /// of the peer, and of the end of its dial backoff
+    pub last_seen: i64,
+    pub backoff_until: i64,
+    /// Dial failures since the last connection
+    pub failures: u32,
+}
+
+#[derive(Debug, Default)]
+pub struct PeerStore {
+    peers: HashMap<PeerId, PeerRecord>,
+    /// Whether the peers changed since they were last saved
+    dirty: bool,
+}
+
+impl PeerStore {
+    /// Loads the peers saved in the database, if any.
+    pub fn load<DB: Store>(db: &DB) -> anyhow::Result<Self> {
+        let Some(bytes) = db.read(PEER_STORE_KEY)? else {
+            return Ok(Self::default());
+        };
+        let records: Vec<PeerRecord> = fvm_ipld_encoding::from_slice(&bytes)?;
+        Ok(Self {
+            peers: records
+                .into_iter()
+                .map(|record| (record.peer_id, record))
+                .collect(),
+            dirty: false,
+        })
+    }
+
+    /// Saves the peers to the database if they changed, forgetting the ones
+    /// not seen for too long.
+    pub fn save<DB: Store>(&mut self, db: &DB, now: i64) -> anyhow::Result<()> {
+        self.prune(now);
+        if !self.dirty {
+            return Ok(());
+        }
+        let records: Vec<&PeerRecord> = self.peers.values().collect();
+        db.write(PEER_STORE_KEY, fvm_ipld_encoding::to_vec(&records)?)?;
+        self.dirty = false;
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.peers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.peers.is_empty()
+    }
+
+    pub fn get(&self, peer_id: &PeerId) -> Option<&PeerRecord> {
+        self.peers.get(peer_id)
+    }
+
+    pub fn records(&self) -> impl Iterator<Item = &PeerRecord> {
+        self.peers.values()
+    }
+
+    /// Records a connection to a peer dialed at `addr`, ending its backoff.
+    pub fn on_connected(&mut self, peer_id: PeerId, addr: Multiaddr, now: i64) {
+        let record = self.peers.entry(peer_id).or_insert_with(|| PeerRecord {
+            peer_id,
+            addrs: vec![],
+            last_seen: now,
+            backoff_until: 0,
+            failures: 0,
+        });
+        record.addrs.retain(|known| known != &addr);
+        record.addrs.insert(0, addr);
+        record.addrs.truncate(MAX_ADDRS_PER_PEER);
+        record.last_seen = now;
+        record.backoff_until = 0;
+        record.failures = 0;
+        self.dirty = true;
+    }
+
+    /// Records that a known peer is, or just was, connected.
+    pub fn on_seen(&mut self, peer_id: &PeerId, now: i64) {
+        if let Some(record) = self.peers.get_mut(peer_id) {
+            record.last_seen = now;
+            self.dirty = true;
+        }
+    }
+
+    /// Records a failure to dial a known peer, backing it off.
+    pub fn on_dial_failure(&mut self, peer_id: &PeerId, now: i64) {
+        if let Some(record) = self.peers.get_mut(peer_id) {
+            record.failures = record.failures.saturating_add(1);
+            let backoff = BASE_BACKOFF_SECS
+                .saturating_mul(1 << (record.failures - 1).min(16))
+                .min(MAX_BACKOFF_SECS);
+            record.backoff_until = now + backoff;
+            self.dirty = true;
+        }
+    }
+
+    /// Returns the peers to dial, the ones seen last first, leaving out those
+    /// backed off and those `excluded`, e.g. because they are connected.
+    pub fn dial_candidates(
+        &self,
+        now: i64,
+        limit: usize,
+        excluded: impl Fn(&PeerId) -> bool,
+    ) -> Vec<&PeerRecord> {
+        let mut candidates: Vec<&PeerRecord> = self
+            .peers
+            .values()
+            .filter(|record| record.backoff_until <= now && !excluded(&record.peer_id))
+            .collect();
+        candidates.sort_by_key(|record| Reverse(record.last_seen));
+        candidates.truncate(limit);
+        candidates
+    }
+
+    /// Forgets the peers not seen for too long, and the ones seen last
+    /// beyond [`MAX_PEERS`].
+    fn prune(&mut self, now: i64) {
+        let len = self.peers.len();
+        self.peers
+            .retain(|_, record| record.last_seen + PEER_TTL_SECS > now);
+        if self.peers.len() > MAX_PEERS {
+            let mut last_seen: Vec<i64> = self.peers.values().map(|r| r.last_seen).collect();
+            last_seen.sort_unstable_by_key(|&seen| Reverse(seen));
+            let oldest_kept = last_seen[MAX_PEERS - 1];
+            self.peers
+                .retain(|_, record| record.last_seen >= oldest_kept);
+        }
+        if self.peers.len() != len {
+            self.dirty = true;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::MemoryDB;
+
+    fn addr(port: u16) -> Multiaddr {
+        format!("/ip4/127.0.0.1/tcp/{port}").parse().unwrap()
+    }
+
+    #[test]
+    fn dial_backoff() {
+        let peer_id = PeerId::random();
+        let mut store = PeerStore::default();
+        store.on_dial_failure(&peer_id, 0);
+        assert!(store.is_empty());
+
+        store.on_connected(peer_id, addr(1234), 100);
+        store.on_dial_failure(&peer_id, 200);
+        store.on_dial_failure(&peer_id, 200);
+        assert_eq!(store.get(&peer_id).unwrap().backoff_until, 200 + 60);
+        assert!(store.dial_candidates(259, 10, |_| false).is_empty());
+        assert_eq!(store.dial_candidates(260, 10, |_| false).len(), 1);
+        assert!(store.dial_candidates(260, 10, |p| p == &peer_id).is_empty());
+
+        for _ in 0..32 {
+            store.on_dial_failure(&peer_id, 200);
+        }
+        assert_eq!(
+            store.get(&peer_id).unwrap().backoff_until,
+            200 + MAX_BACKOFF_SECS
+        );
+
+        store.on_connected(peer_id, addr(1235), 300);
+        let record = store.get(&peer_id).unwrap();
+        assert_eq!((record.failures, record.backoff_until), (0, 0));
+        assert_eq!(record.addrs, vec![addr(1235), addr(1234)]);
+    }
+
+    #[test]
+    fn save_and_load() {
+        let db = MemoryDB::default();
+        let (seen, stale) = (PeerId::random(), PeerId::random());
+        let mut store = PeerStore::default();
+        store.on_connected(seen, addr(1234), PEER_TTL_SECS);
+        store.on_connected(stale, addr(1235), 0);
+        store.save(&db, PEER_TTL_SECS + 1).unwrap();
+
+        let loaded = PeerStore::load(&db).unwrap();
+        assert_eq!(loaded.len(), 1);
+        assert_eq!(loaded.get(&seen), store.get(&seen));
+        assert!(PeerStore::load(&MemoryDB::default()).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,134 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+use ahash::HashSet;
+use cid::{
+    multihash::{Code::Blake2b256, MultihashDigest},
+    Cid,
+};
+use fvm_ipld_encoding::DAG_CBOR;
+use libp2p::gossipsub::IdentTopic;
+
+/// Messages queued over this are dropped, the rate limit being far from
+/// keeping up with them
+const MAX_QUEUED_MESSAGES: usize = 10_000;
+
+/// Queue of the messages published over `gossipsub` by the node itself. It
+/// skips the messages already published within the deduplication window, and
+/// spreads the others to stay within the rate limit, so that republishing the
+/// pending messages in bulk doesn't get the node penalized by its peers.
+pub(in crate::libp2p) struct PublishQueue {
+    dedup_window: Duration,
+    /// Maximum number of messages published per second, `0` means unlimited
+    rate_limit: usize,
+    /// Messages published within the deduplication window, oldest first
+    published: VecDeque<(Instant, Cid)>,
+    published_cids: HashSet<Cid>,
+    queue: VecDeque<(IdentTopic, Vec<u8>)>,
+    /// Start of the current second of the rate limit, and the number of
+    /// messages published since
+    rate_window: (Instant, usize),
+}
+
+impl PublishQueue {
+    pub fn new(dedup_window: Duration, rate_limit: u32) -> Self {
+        Self {
+            dedup_window,
+            rate_limit: rate_limit as usize,
+            published: VecDeque::new(),
+            published_cids: HashSet::default(),
+            queue: VecDeque::new(),
+            rate_window: (Instant::now(), 0),
+        }
+    }
+
+    /// Queues a message for publication. Returns `false` if it's a duplicate
+    /// of one already queued or published within the deduplication window, or
+    /// if the queue is full.
+    pub fn push(&mut self, topic: IdentTopic, message: Vec<u8>, now: Instant) -> bool {
+        self.expire(now);
+        if self.queue.len() >= MAX_QUEUED_MESSAGES {
+            return false;
+        }
+        let cid = Cid::new_v1(DAG_CBOR, Blake2b256.digest(&message));
+        if !self.published_cids.insert(cid) {
+            return false;
+        }
+        self.published.push_back((now, cid));
+        self.queue.push_back((topic, message));
+        true
+    }
+
+    /// Takes the messages that can be published now without exceeding the
+    /// rate limit, in the order they were queued.
+    pub fn pop_ready(&mut self, now: Instant) -> Vec<(IdentTopic, Vec<u8>)> {
+        self.expire(now);
+        let count = if self.rate_limit == 0 {
+            self.queue.len()
+        } else {
+            let (start, published) = &mut self.rate_window;
+            if now.duration_since(*start) >= Duration::from_secs(1) {
+                *start = now;
+                *published = 0;
+            }
+            let count = self.queue.len().min(self.rate_limit - *published);
+            *published += count;
+            count
+        };
+        self.queue.drain(..count).collect()
+    }
+
+    /// Number of messages waiting for publication
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+
+    fn expire(&mut self, now: Instant) {
+        while let Some((published_at, cid)) = self.published.front() {
+            if now.duration_since(*published_at) < self.dedup_window {
+                break;
+            }
+            self.published_cids.remove(cid);
+            self.published.pop_front();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn topic() -> IdentTopic {
+        IdentTopic::new("/fil/msgs/calibnet")
+    }
+
+    #[test]
+    fn duplicates_within_window_are_skipped() {
+        let mut queue = PublishQueue::new(Duration::from_secs(60), 0);
+        let now = Instant::now();
+        assert!(queue.push(topic(), vec![1, 2, 3], now));
+        assert!(!queue.push(topic(), vec![1, 2, 3], now));
+        assert_eq!(queue.pop_ready(now).len(), 1);
+        assert!(!queue.push(topic(), vec![1, 2, 3], now + Duration::from_secs(59)));
+        assert!(queue.push(topic(), vec![1, 2, 3], now + Duration::from_secs(60)));
+    }
+
+    #[test]
+    fn publication_respects_rate_limit() {
+        let mut queue = PublishQueue::new(Duration::from_secs(60), 2);
+        let now = Instant::now();
+        for i in 0..5 {
+            assert!(queue.push(topic(), vec![i], now));
+        }
+        let ready: Vec<_> = queue.pop_ready(now).into_iter().map(|(_, m)| m).collect();
+        assert_eq!(ready, vec![vec![0], vec![1]]);
+        assert!(queue.pop_ready(now + Duration::from_millis(500)).is_empty());
+        assert_eq!(queue.pop_ready(now + Duration::from_secs(1)).len(), 2);
+        assert_eq!(queue.len(), 1);
+    }
+}
@@ -2,24 +2,28 @@
 // SPDX-License-Identifier: Apache-2.0, MIT
 
 use std::{
-    path::Path,
+    collections::hash_map::Entry,
+    path::{Path, PathBuf},
     sync::Arc,
-    time::{Duration, SystemTime, UNIX_EPOCH},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 
-use crate::blocks::GossipBlock;
+use crate::blocks::{timing::BlockTiming, GossipBlock};
 use crate::chain::ChainStore;
+use crate::db::Store;
 use crate::libp2p_bitswap::{
     request_manager::BitswapRequestManager, BitswapStoreRead, BitswapStoreReadWrite,
 };
 use crate::message::SignedMessage;
+use crate::networks::ChainConfig;
 use crate::shim::clock::ChainEpoch;
-use crate::utils::io::read_file_to_vec;
+use crate::utils::{db::file_backed_obj::FileBacked, io::read_file_to_vec};
 use ahash::{HashMap, HashSet};
 use anyhow::Context;
+use chrono::Utc;
 use cid::Cid;
 use flume::Sender;
-use futures::{channel::oneshot::Sender as OneShotSender, select};
+use futures::{channel::oneshot::Sender as OneShotSender, future, select};
 use futures_util::stream::StreamExt;
 use fvm_ipld_blockstore::Blockstore;
 pub use libp2p::gossipsub::{IdentTopic, Topic};
@@ -27,16 +31,22 @@ pub use libp2p::gossipsub::{IdentTopic, Topic};
 #[allow(deprecated)]
 use libp2p::swarm::ConnectionLimits;
 use libp2p::{
-    core::{self, identity::Keypair, muxing::StreamMuxerBox, transport::Boxed, Multiaddr},
-    gossipsub,
+    core::{
+        self, identity::Keypair, muxing::StreamMuxerBox, transport::Boxed, ConnectedPoint,
+        Multiaddr,
+    },
+    gossipsub, identify,
     metrics::{Metrics, Recorder},
     multiaddr::Protocol,
     noise, ping,
+    pnet::{PnetConfig, PreSharedKey},
+    relay,
     request_response::{self, RequestId, ResponseChannel},
-    swarm::{SwarmBuilder, SwarmEvent},
+    swarm::{dial_opts::DialOpts, AddressScore, DialError, SwarmBuilder, SwarmEvent},
     yamux, PeerId, Swarm, Transport,
 };
 use log::{debug, error, info, trace, warn};
+use parking_lot::Mutex;
 use tokio_stream::wrappers::IntervalStream;
 
 use super::{
@@ -44,9 +54,15 @@ use super::{
     ForestBehaviour, ForestBehaviourEvent, Libp2pConfig,
 };
 use crate::libp2p::{
+    bandwidth::{BandwidthCounters, BandwidthStats},
+    bootstrap::{peer_id_from_multiaddr, BootstrapPeers},
     chain_exchange::ChainExchangeBehaviour,
     discovery::DiscoveryEvent,
+    gossip_validation::{GossipValidators, ValidationResult},
     hello::{HelloBehaviour, HelloRequest, HelloResponse},
+    nat::{keep_port_mapped, port_mapping_candidate, Reachability, ReachabilityTracker},
+    peer_store::PeerStore,
+    publish_queue::PublishQueue,
     rpc::RequestResponseError,
     PeerManager, PeerOperation,
 };
@@ -94,6 +110,13 @@ pub const BITSWAP_TIMEOUT: Duration = Duration::from_secs(10);
 
 const BAN_PEER_DURATION: Duration = Duration::from_secs(60 * 60); //1h
 
+/// Interval at which the messages queued within the rate limit are published
+const PUBLISH_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Interval at which the peer store is saved, and the known peers are dialed
+/// if the node is short of peers
+const PEER_STORE_INTERVAL: Duration = Duration::from_secs(60);
+
 /// Events emitted by this Service.
 #[allow(clippy::large_enum_variant)]
 #[derive(Debug)]
@@ -176,6 +199,12 @@ pub enum NetRPCMethods {
     NetPeers(OneShotSender<HashMap<PeerId, HashSet<Multiaddr>>>),
     NetConnect(OneShotSender<bool>, PeerId, HashSet<Multiaddr>),
     NetDisconnect(OneShotSender<()>, PeerId),
+    NetAutoNatStatus(OneShotSender<(Reachability, Option<Multiaddr>)>),
+    NetAddPeer(OneShotSender<anyhow::Result<()>>, Multiaddr),
+    NetRemovePeer(OneShotSender<anyhow::Result<bool>>, PeerId),
+    NetBandwidthStats(OneShotSender<BandwidthStats>),
+    NetBandwidthStatsByPeer(OneShotSender<HashMap<PeerId, BandwidthStats>>),
+    NetBandwidthStatsByProtocol(OneShotSender<HashMap<String, BandwidthStats>>),
 }
 
 /// The `Libp2pService` listens to events from the libp2p swarm.
@@ -190,24 +219,42 @@ pub struct Libp2pService<DB> {
     network_sender_out: Sender<NetworkEvent>,
     network_name: String,
     genesis_cid: Cid,
+    /// Bootstrap peers added at runtime, see [`Libp2pService::with_bootstrap_peers_file`]
+    bootstrap_peers: Option<FileBacked<BootstrapPeers>>,
+    bandwidth: Arc<BandwidthCounters>,
+    /// Timing parameters the blocks received over `gossipsub` are checked
+    /// against
+    block_timing: BlockTiming,
+    /// Peers known from this run and the previous ones, see
+    /// [`Libp2pService::peer_store`]
+    peer_store: Arc<Mutex<PeerStore>>,
 }
 
 impl<DB> Libp2pService<DB>
 where
-    DB: Blockstore + BitswapStoreReadWrite + Clone + Sync + Send + 'static,
+    DB: Blockstore + Store + BitswapStoreReadWrite + Clone + Sync + Send + 'static,
 {
     pub fn new(
         config: Libp2pConfig,
         cs: Arc<ChainStore<DB>>,
         peer_manager: Arc<PeerManager>,
         net_keypair: Keypair,
+        pre_shared_key: Option<PreSharedKey>,
         network_name: &str,
         genesis_cid: Cid,
     ) -> Self {
         let peer_id = PeerId::from(net_keypair.public());
 
-        let transport =
-            build_transport(net_keypair.clone()).expect("Failed to build libp2p transport");
+        let bandwidth = Arc::new(BandwidthCounters::default());
+        let (relay_transport, relay_client) = relay::client::new(peer_id);
+        let transport = build_transport(
+            net_keypair.clone(),
+            pre_shared_key,
+            config.quic,
+            relay_transport,
+            bandwidth.clone(),
+        )
+        .expect("Failed to build libp2p transport");
 
         // https://github.com/ChainSafe/forest/issues/2762
         #[allow(deprecated)]
@@ -220,7 +267,7 @@ where
 
         let mut swarm = SwarmBuilder::with_tokio_executor(
             transport,
-            ForestBehaviour::new(&net_keypair, &config, network_name),
+            ForestBehaviour::new(&net_keypair, &config, network_name, relay_client),
             peer_id,
         )
         .connection_limits(limits)
@@ -237,6 +284,11 @@ where
         let (network_sender_in, network_receiver_in) = flume::unbounded();
         let (network_sender_out, network_receiver_out) = flume::unbounded();
 
+        let peer_store = PeerStore::load(cs.blockstore()).unwrap_or_else(|e| {
+            warn!("Failed to load the peer store: {e:#}");
+            PeerStore::default()
+        });
+
         Libp2pService {
             config,
             swarm,
@@ -248,31 +300,121 @@ where
             network_sender_out,
             network_name: network_name.into(),
             genesis_cid,
+            bootstrap_peers: None,
+            bandwidth,
+            block_timing: BlockTiming::from(&ChainConfig::default()),
+            peer_store: Arc::new(Mutex::new(peer_store)),
         }
     }
 
+    /// Returns the peer store, saved periodically while the service runs, to
+    /// be saved once more on shutdown.
+    pub fn peer_store(&self) -> Arc<Mutex<PeerStore>> {
+        self.peer_store.clone()
+    }
+
+    /// Sets the timing parameters of the network, those of mainnet by default.
+    pub fn with_block_timing(mut self, block_timing: BlockTiming) -> Self {
+        self.block_timing = block_timing;
+        self
+    }
+
+    /// Loads the bootstrap peers that were added at runtime from the given
+    /// file and persists the ones that are added or removed from now on.
+    pub fn with_bootstrap_peers_file(mut self, path: PathBuf) -> anyhow::Result<Self> {
+        let bootstrap_peers =
+            FileBacked::load_from_file_or_create(path, BootstrapPeers::default, None)?;
+        for addr in bootstrap_peers.inner().peers() {
+            match peer_id_from_multiaddr(addr) {
+                Some(peer_id) => {
+                    self.swarm
+                        .behaviour_mut()
+                        .add_bootstrap_peer(&peer_id, addr.clone());
+                }
+                None => warn!("Could not parse bootstrap addr {addr}"),
+            }
+        }
+        self.bootstrap_peers = Some(bootstrap_peers);
+        Ok(self)
+    }
+
     /// Starts the libp2p service networking stack. This Future resolves when
     /// shutdown occurs.
     pub async fn run(mut self) -> anyhow::Result<()> {
         info!("Running libp2p service");
+        let quic = self.config.quic && self.config.pre_shared_key_path.is_none();
         for addr in &self.config.listening_multiaddrs {
+            if !quic && addr.iter().any(|p| matches!(p, Protocol::QuicV1)) {
+                warn!("QUIC is disabled, not listening on {addr}");
+                continue;
+            }
             if let Err(err) = Swarm::listen_on(&mut self.swarm, addr.clone()) {
                 error!("Fail to listen on {addr}: {err}");
             }
         }
+        for relay in &self.config.relays {
+            let addr = relay.clone().with(Protocol::P2pCircuit);
+            if let Err(err) = Swarm::listen_on(&mut self.swarm, addr) {
+                error!("Fail to listen through relay {relay}: {err}");
+            }
+        }
+        for addr in &self.config.announce_multiaddrs {
+            info!("Announcing {addr}");
+            self.swarm
+                .add_external_address(addr.clone(), AddressScore::Infinite);
+        }
 
         // Bootstrap with Kademlia
         if let Err(e) = self.swarm.behaviour_mut().bootstrap() {
             warn!("Failed to bootstrap with Kademlia: {e}");
         }
+        // Without Kademlia nothing dials the bootstrap peers, which is the
+        // common setup of private networks
+        if !self.config.kademlia {
+            let persisted = self
+                .bootstrap_peers
+                .as_ref()
+                .map(|peers| peers.inner().peers().to_vec())
+                .unwrap_or_default();
+            for addr in self.config.bootstrap_peers.iter().chain(persisted.iter()) {
+                if let Err(err) = Swarm::dial(&mut self.swarm, addr.clone()) {
+                    warn!("Failed to dial bootstrap peer {addr}: {err}");
+                }
+            }
+        }
+        // The peers known from the previous runs are dialed on the first
+        // tick of the peer store interval, and the backoffs of the ones that
+        // failed to be dialed apply to all the dials
+        {
+            let peer_store = self.peer_store.lock();
+            if !peer_store.is_empty() {
+                info!("Loaded {} known peers", peer_store.len());
+            }
+            let behaviour = self.swarm.behaviour_mut();
+            for record in peer_store.records().filter(|r| r.backoff_until > 0) {
+                behaviour.set_dial_backoff(record.peer_id, record.backoff_until);
+            }
+        }
 
         let bitswap_request_manager = self.swarm.behaviour().bitswap.request_manager();
         let mut swarm_stream = self.swarm.fuse();
         let mut network_stream = self.network_receiver_in.stream().fuse();
         let mut interval =
             IntervalStream::new(tokio::time::interval(Duration::from_secs(15))).fuse();
-        let pubsub_block_str = format!("{}/{}", PUBSUB_BLOCK_STR, self.network_name);
-        let pubsub_msg_str = format!("{}/{}", PUBSUB_MSG_STR, self.network_name);
+        let mut bandwidth_interval =
+            IntervalStream::new(tokio::time::interval(Duration::from_secs(1))).fuse();
+        let mut publish_interval =
+            IntervalStream::new(tokio::time::interval(PUBLISH_INTERVAL)).fuse();
+        let mut peer_store_interval =
+            IntervalStream::new(tokio::time::interval(PEER_STORE_INTERVAL)).fuse();
+        let mut publish_queue = PublishQueue::new(
+            Duration::from_secs(self.config.publish_dedup_window_secs),
+            self.config.publish_rate_limit,
+        );
+        let gossip_validators =
+            Arc::new(GossipValidators::new(&self.network_name, self.block_timing));
+        let (gossip_validation_tx, gossip_validation_rx) = flume::unbounded();
+        let mut gossip_validation_rx_stream = gossip_validation_rx.stream().fuse();
 
         let (cx_response_tx, cx_response_rx) = flume::unbounded();
 
@@ -282,6 +424,11 @@ where
             .stream()
             .fuse();
         let mut peer_ops_rx_stream = self.peer_manager.peer_ops_rx().stream().fuse();
+        let mut reachability = ReachabilityTracker::default();
+        let (port_mapping_tx, port_mapping_rx) = flume::unbounded();
+        let mut port_mapping_rx_stream = port_mapping_rx.stream().fuse();
+        // External addresses of the mapped ports, by local port
+        let mut mapped_ports: HashMap<u16, Option<Multiaddr>> = HashMap::default();
         let mut libp2p_registry = Default::default();
         let metrics = Metrics::new(&mut libp2p_registry);
         crate::metrics::add_metrics_registry("libp2p".into(), libp2p_registry).await;
@@ -295,13 +442,56 @@ where
                             swarm_stream.get_mut(),
                             &bitswap_request_manager,
                             &self.peer_manager,
+                            &mut reachability,
                             event,
                             &self.cs,
                             &self.genesis_cid,
                             &self.network_sender_out,
                             cx_response_tx.clone(),
-                            &pubsub_block_str,
-                            &pubsub_msg_str,).await;
+                            &gossip_validators,
+                            &gossip_validation_tx).await;
+                    },
+                    Some(SwarmEvent::ConnectionEstablished { peer_id, endpoint, .. }) => {
+                        match endpoint {
+                            ConnectedPoint::Listener { send_back_addr, .. } => {
+                                let status = reachability.on_inbound_connection(&send_back_addr);
+                                if let Some(status) = status {
+                                    on_reachability_changed(status);
+                                }
+                                self.peer_store.lock().on_seen(&peer_id, Utc::now().timestamp());
+                            }
+                            ConnectedPoint::Dialer { address, .. } => {
+                                let now = Utc::now().timestamp();
+                                self.peer_store.lock().on_connected(peer_id, address, now);
+                                let behaviour = swarm_stream.get_mut().behaviour_mut();
+                                behaviour.clear_dial_backoff(&peer_id);
+                            }
+                        }
+                    },
+                    Some(SwarmEvent::NewListenAddr { address, .. }) => {
+                        let local = port_mapping_candidate(&address)
+                            .filter(|_| self.config.port_mapping);
+                        if let Some(local) = local {
+                            // All the interfaces share the port
+                            if let Entry::Vacant(entry) = mapped_ports.entry(local.port()) {
+                                entry.insert(None);
+                                tokio::spawn(keep_port_mapped(local, port_mapping_tx.clone()));
+                            }
+                        }
+                    },
+                    Some(SwarmEvent::ConnectionClosed { peer_id, .. }) => {
+                        self.peer_store.lock().on_seen(&peer_id, Utc::now().timestamp());
+                    },
+                    // The dials denied during a backoff don't extend it
+                    Some(SwarmEvent::OutgoingConnectionError { peer_id: Some(peer_id), error }) => {
+                        if !matches!(error, DialError::Denied { .. }) {
+                            let mut peer_store = self.peer_store.lock();
+                            peer_store.on_dial_failure(&peer_id, Utc::now().timestamp());
+                            if let Some(record) = peer_store.get(&peer_id) {
+                                let behaviour = swarm_stream.get_mut().behaviour_mut();
+                                behaviour.set_dial_backoff(peer_id, record.backoff_until);
+                            }
+                        }
                     },
                     None => { break; },
                     _ => { },
@@ -313,15 +503,53 @@ where
                             swarm_stream.get_mut(),
                             self.cs.clone(),
                             bitswap_request_manager.clone(),
+                            &reachability,
+                            &mut self.bootstrap_peers,
+                            &mut publish_queue,
+                            &self.bandwidth,
                             message,
                             &self.network_sender_out).await;
                     }
                     None => { break; }
                 },
+                bandwidth_event = bandwidth_interval.next() => if bandwidth_event.is_some() {
+                    self.bandwidth.update_rates();
+                },
+                publish_event = publish_interval.next() => if publish_event.is_some() {
+                    publish_queued_messages(swarm_stream.get_mut(), &mut publish_queue);
+                },
+                peer_store_event = peer_store_interval.next() => if peer_store_event.is_some() {
+                    let mut peer_store = self.peer_store.lock();
+                    if let Err(e) = peer_store.save(self.cs.blockstore(), Utc::now().timestamp()) {
+                        warn!("Failed to save the peer store: {e:#}");
+                    }
+                    let swarm = swarm_stream.get_mut();
+                    let missing = (self.config.target_peer_count as usize)
+                        .saturating_sub(swarm.behaviour().peers().len());
+                    dial_known_peers(swarm, &peer_store, missing);
+                },
                 interval_event = interval.next() => if interval_event.is_some() {
                     // Print peer count on an interval.
                     debug!("Peers connected: {}", swarm_stream.get_mut().behaviour_mut().peers().len());
                 },
+                port_mapping_opt = port_mapping_rx_stream.next() => {
+                    if let Some((port, external)) = port_mapping_opt {
+                        let swarm = swarm_stream.get_mut();
+                        let previous = mapped_ports.insert(port, Some(external.clone()));
+                        if let Some(Some(previous)) = previous {
+                            swarm.remove_external_address(&previous);
+                        }
+                        swarm.add_external_address(external, AddressScore::Infinite);
+                    }
+                },
+                gossip_validation_opt = gossip_validation_rx_stream.next() => {
+                    if let Some(validation) = gossip_validation_opt {
+                        handle_gossip_validation(
+                            swarm_stream.get_mut(),
+                            validation,
+                            &self.network_sender_out).await;
+                    }
+                },
                 cs_pair_opt = cx_response_rx_stream.next() => {
                     if let Some((_request_id, channel, cx_response)) = cs_pair_opt {
                         let behaviour = swarm_stream.get_mut().behaviour_mut();
@@ -343,6 +571,13 @@ where
                 },
             };
         }
+        if let Err(e) = self
+            .peer_store
+            .lock()
+            .save(self.cs.blockstore(), Utc::now().timestamp())
+        {
+            warn!("Failed to save the peer store: {e:#}");
+        }
         Ok(())
     }
 
@@ -372,22 +607,78 @@ fn handle_peer_ops(swarm: &mut Swarm<ForestBehaviour>, peer_ops: PeerOperation)
             #[allow(deprecated)]
             swarm.unban_peer_id(peer_id);
         }
+        Disconnect(peer_id) => {
+            debug!("Disconnecting from {peer_id}");
+            let _ = Swarm::disconnect_peer_id(swarm, peer_id);
+        }
+    }
+}
+
+/// Dials up to `limit` of the known peers that are not connected nor backed
+/// off.
+fn dial_known_peers(swarm: &mut Swarm<ForestBehaviour>, peer_store: &PeerStore, limit: usize) {
+    let candidates = peer_store.dial_candidates(Utc::now().timestamp(), limit, |peer_id| {
+        swarm.is_connected(peer_id)
+    });
+    let dial_opts: Vec<DialOpts> = candidates
+        .into_iter()
+        .map(|record| {
+            DialOpts::peer_id(record.peer_id)
+                .addresses(record.addrs.clone())
+                .build()
+        })
+        .collect();
+    for opts in dial_opts {
+        if let Err(err) = swarm.dial(opts) {
+            debug!("Failed to dial a known peer: {err}");
+        }
+    }
+}
+
+fn on_reachability_changed(status: Reachability) {
+    info!("Reachability changed to {status:?}");
+    super::metrics::REACHABILITY.set(status as u64);
+}
+
+fn publish_queued_messages(swarm: &mut Swarm<ForestBehaviour>, publish_queue: &mut PublishQueue) {
+    for (topic, message) in publish_queue.pop_ready(Instant::now()) {
+        if let Err(e) = swarm.behaviour_mut().publish(topic, message) {
+            warn!("Failed to send gossipsub message: {:?}", e);
+        }
     }
+    super::metrics::GOSSIP_PUBLISH_QUEUE_LEN.set(publish_queue.len() as u64);
 }
 
+#[allow(clippy::too_many_arguments)]
 async fn handle_network_message(
     swarm: &mut Swarm<ForestBehaviour>,
     store: Arc<impl BitswapStoreReadWrite>,
     bitswap_request_manager: Arc<BitswapRequestManager>,
+    reachability: &ReachabilityTracker,
+    bootstrap_peers: &mut Option<FileBacked<BootstrapPeers>>,
+    publish_queue: &mut PublishQueue,
+    bandwidth: &BandwidthCounters,
     message: NetworkMessage,
     network_sender_out: &Sender<NetworkEvent>,
 ) {
     match message {
-        NetworkMessage::PubsubMessage { topic, message } => {
+        // Blocks are rare and time-sensitive, only the messages go through
+        // the queue
+        NetworkMessage::PubsubMessage { topic, message }
+            if topic.hash().as_str().starts_with(PUBSUB_BLOCK_STR) =>
+        {
             if let Err(e) = swarm.behaviour_mut().publish(topic, message) {
                 warn!("Failed to send gossipsub message: {:?}", e);
             }
         }
+        NetworkMessage::PubsubMessage { topic, message } => {
+            if publish_queue.push(topic, message, Instant::now()) {
+                publish_queued_messages(swarm, publish_queue);
+            } else {
+                debug!("Skipping a gossipsub message, duplicate of a recent one or over the queue capacity");
+                super::metrics::GOSSIP_PUBLISH_SKIPPED_TOTAL.inc();
+            }
+        }
         NetworkMessage::HelloRequest {
             peer_id,
             request,
@@ -429,7 +720,10 @@ async fn handle_network_message(
         }
         NetworkMessage::JSONRPCRequest { method } => match method {
             NetRPCMethods::NetAddrsListen(response_channel) => {
-                let listeners = Swarm::listeners(swarm).cloned().collect();
+                let listeners = Swarm::listeners(swarm)
+                    .cloned()
+                    .chain(swarm.external_addresses().map(|record| record.addr.clone()))
+                    .collect();
                 let peer_id = Swarm::local_peer_id(swarm);
 
                 if response_channel.send((*peer_id, listeners)).is_err() {
@@ -444,6 +738,8 @@ async fn handle_network_message(
             }
             NetRPCMethods::NetConnect(response_channel, peer_id, addresses) => {
                 let mut success = false;
+                // An explicit connection request overrides the backoff
+                swarm.behaviour_mut().clear_dial_backoff(&peer_id);
 
                 for mut multiaddr in addresses {
                     multiaddr.push(Protocol::P2p(peer_id.into()));
@@ -464,10 +760,73 @@ async fn handle_network_message(
                     warn!("Failed to disconnect from a peer");
                 }
             }
+            NetRPCMethods::NetAutoNatStatus(response_channel) => {
+                let status = (reachability.status(), reachability.public_addr().cloned());
+                if response_channel.send(status).is_err() {
+                    warn!("Failed to get the reachability status");
+                }
+            }
+            NetRPCMethods::NetAddPeer(response_channel, addr) => {
+                let result = add_bootstrap_peer(swarm, bootstrap_peers, addr);
+                if response_channel.send(result).is_err() {
+                    warn!("Failed to add a bootstrap peer");
+                }
+            }
+            NetRPCMethods::NetBandwidthStats(response_channel) => {
+                if response_channel.send(bandwidth.total()).is_err() {
+                    warn!("Failed to get the bandwidth stats");
+                }
+            }
+            NetRPCMethods::NetBandwidthStatsByPeer(response_channel) => {
+                if response_channel.send(bandwidth.by_peer()).is_err() {
+                    warn!("Failed to get the bandwidth stats by peer");
+                }
+            }
+            NetRPCMethods::NetBandwidthStatsByProtocol(response_channel) => {
+                if response_channel.send(bandwidth.by_protocol()).is_err() {
+                    warn!("Failed to get the bandwidth stats by protocol");
+                }
+            }
+            NetRPCMethods::NetRemovePeer(response_channel, peer_id) => {
+                swarm.behaviour_mut().remove_bootstrap_peer(&peer_id);
+                let result = match bootstrap_peers {
+                    Some(peers) => {
+                        let mut removed = false;
+                        peers
+                            .with_inner(|peers| removed = peers.remove(&peer_id))
+                            .map(|()| removed)
+                    }
+                    None => Ok(false),
+                };
+                if response_channel.send(result).is_err() {
+                    warn!("Failed to remove a bootstrap peer");
+                }
+            }
         },
     }
 }
 
+fn add_bootstrap_peer(
+    swarm: &mut Swarm<ForestBehaviour>,
+    bootstrap_peers: &mut Option<FileBacked<BootstrapPeers>>,
+    addr: Multiaddr,
+) -> anyhow::Result<()> {
+    let peer_id = peer_id_from_multiaddr(&addr)
+        .with_context(|| format!("bootstrap address {addr} does not end with a peer id"))?;
+    swarm
+        .behaviour_mut()
+        .add_bootstrap_peer(&peer_id, addr.clone());
+    if let Err(err) = Swarm::dial(swarm, addr.clone()) {
+        warn!("Failed to dial bootstrap peer {addr}: {err}");
+    }
+    if let Some(peers) = bootstrap_peers {
+        peers.with_inner(|peers| {
+            peers.add(addr);
+        })?;
+    }
+    Ok(())
+}
+
 async fn handle_discovery_event(
     discovery_out: DiscoveryEvent,
     network_sender_out: &Sender<NetworkEvent>,
@@ -484,55 +843,63 @@ async fn handle_discovery_event(
     }
 }
 
-async fn handle_gossip_event(
+/// Outcome of the validation of a gossip message, with its id, the peer that
+/// propagated it and its topic
+type GossipValidation = (
+    gossipsub::MessageId,
+    PeerId,
+    gossipsub::TopicHash,
+    ValidationResult,
+);
+
+fn handle_gossip_event(
     e: gossipsub::Event,
-    network_sender_out: &Sender<NetworkEvent>,
-    pubsub_block_str: &str,
-    pubsub_msg_str: &str,
+    gossip_validators: &Arc<GossipValidators>,
+    gossip_validation_tx: &Sender<GossipValidation>,
 ) {
     if let gossipsub::Event::Message {
         propagation_source: source,
         message,
-        message_id: _,
+        message_id,
     } = e
     {
-        let topic = message.topic.as_str();
-        let message = message.data;
         trace!("Got a Gossip Message from {:?}", source);
-        if topic == pubsub_block_str {
-            match fvm_ipld_encoding::from_slice::<GossipBlock>(&message) {
-                Ok(b) => {
-                    emit_event(
-                        network_sender_out,
-                        NetworkEvent::PubsubMessage {
-                            source,
-                            message: PubsubMessage::Block(b),
-                        },
-                    )
-                    .await;
-                }
-                Err(e) => {
-                    warn!("Gossip Block from peer {source:?} could not be deserialized: {e}",);
-                }
+        // Verifying the signatures would stall the swarm, the validation runs
+        // on a blocking thread and its outcome is reported once known
+        let gossip_validators = gossip_validators.clone();
+        let gossip_validation_tx = gossip_validation_tx.clone();
+        tokio::task::spawn_blocking(move || {
+            let result = gossip_validators.validate(&message.topic, &message.data);
+            if let Err(e) = gossip_validation_tx.send((message_id, source, message.topic, result)) {
+                debug!("Failed to send the gossip validation result: {e}");
             }
-        } else if topic == pubsub_msg_str {
-            match fvm_ipld_encoding::from_slice::<SignedMessage>(&message) {
-                Ok(m) => {
-                    emit_event(
-                        network_sender_out,
-                        NetworkEvent::PubsubMessage {
-                            source,
-                            message: PubsubMessage::Message(m),
-                        },
-                    )
-                    .await;
-                }
-                Err(e) => {
-                    warn!("Gossip Message from peer {source:?} could not be deserialized: {e}");
-                }
-            }
-        } else {
-            warn!("Getting gossip messages from unknown topic: {topic}");
+        });
+    }
+}
+
+async fn handle_gossip_validation(
+    swarm: &mut Swarm<ForestBehaviour>,
+    (message_id, source, topic, result): GossipValidation,
+    network_sender_out: &Sender<NetworkEvent>,
+) {
+    swarm.behaviour_mut().report_message_validation_result(
+        &message_id,
+        &source,
+        result.acceptance(),
+    );
+    match result {
+        ValidationResult::Accept(message) => {
+            emit_event(
+                network_sender_out,
+                NetworkEvent::PubsubMessage { source, message },
+            )
+            .await;
+        }
+        ValidationResult::Reject(reason) => {
+            warn!("Rejected gossip message on {topic} from peer {source:?}: {reason}");
+        }
+        ValidationResult::Ignore(reason) => {
+            debug!("Ignored gossip message on {topic} from peer {source:?}: {reason}");
         }
     }
 }
@@ -755,6 +1122,7 @@ async fn handle_forest_behaviour_event<DB>(
     swarm: &mut Swarm<ForestBehaviour>,
     bitswap_request_manager: &Arc<BitswapRequestManager>,
     peer_manager: &Arc<PeerManager>,
+    reachability: &mut ReachabilityTracker,
     event: ForestBehaviourEvent,
     db: &Arc<ChainStore<DB>>,
     genesis_cid: &Cid,
@@ -764,8 +1132,8 @@ async fn handle_forest_behaviour_event<DB>(
         ResponseChannel<ChainExchangeResponse>,
         ChainExchangeResponse,
     )>,
-    pubsub_block_str: &str,
-    pubsub_msg_str: &str,
+    gossip_validators: &Arc<GossipValidators>,
+    gossip_validation_tx: &Sender<GossipValidation>,
 ) where
     DB: Blockstore + BitswapStoreRead + Clone + Sync + Send + 'static,
 {
@@ -774,7 +1142,7 @@ async fn handle_forest_behaviour_event<DB>(
             handle_discovery_event(discovery_out, network_sender_out).await
         }
         ForestBehaviourEvent::Gossipsub(e) => {
-            handle_gossip_event(e, network_sender_out, pubsub_block_str, pubsub_msg_str).await
+            handle_gossip_event(e, gossip_validators, gossip_validation_tx)
         }
         ForestBehaviourEvent::Hello(rr_event) => {
             handle_hello_event(
@@ -796,8 +1164,15 @@ async fn handle_forest_behaviour_event<DB>(
             }
         }
         ForestBehaviourEvent::Ping(ping_event) => handle_ping_event(ping_event, peer_manager).await,
+        ForestBehaviourEvent::Identify(identify::Event::Received { peer_id, info }) => {
+            if let Some(status) = reachability.on_observed_addr(peer_id, &info.observed_addr) {
+                on_reachability_changed(status);
+            }
+        }
         ForestBehaviourEvent::Identify(_) => {}
         ForestBehaviourEvent::KeepAlive(_) => {}
+        ForestBehaviourEvent::RelayClient(event) => debug!("Relay client: {event:?}"),
+        ForestBehaviourEvent::Dcutr(event) => debug!("Hole punching: {event:?}"),
         ForestBehaviourEvent::ChainExchange(ce_event) => {
             handle_chain_exchange_event(
                 &mut swarm.behaviour_mut().chain_exchange,
@@ -817,28 +1192,85 @@ async fn emit_event(sender: &Sender<NetworkEvent>, event: NetworkEvent) {
     }
 }
 
-/// Builds the transport stack that libp2p will communicate over. When support
-/// of other protocols like `udp`, `quic`, `http` are added, remember to update
-/// code comment in [`Libp2pConfig`].
+/// Builds the transport stack that libp2p will communicate over: TCP,
+/// WebSocket with DNS, the relays and, if enabled, QUIC, as listed in
+/// [`Libp2pConfig`]. With a pre-shared key, the connections are encrypted with
+/// it before anything else, and the peers without it can't connect. QUIC,
+/// which does its own encryption, is then left out.
 ///
 /// As a reference `lotus` uses the default `go-libp2p` transport builder which
 /// has all above protocols enabled.
-pub fn build_transport(local_key: Keypair) -> anyhow::Result<Boxed<(PeerId, StreamMuxerBox)>> {
+pub fn build_transport(
+    local_key: Keypair,
+    pre_shared_key: Option<PreSharedKey>,
+    quic: bool,
+    relay_transport: relay::client::Transport,
+    bandwidth: Arc<BandwidthCounters>,
+) -> anyhow::Result<Boxed<(PeerId, StreamMuxerBox)>> {
     let build_tcp = || libp2p::tcp::tokio::Transport::new(libp2p::tcp::Config::new().nodelay(true));
     let build_dns_tcp = || libp2p::dns::TokioDnsConfig::system(build_tcp());
-    let transport =
-        libp2p::websocket::WsConfig::new(build_dns_tcp()?).or_transport(build_dns_tcp()?);
+    let transport = relay_transport
+        .or_transport(libp2p::websocket::WsConfig::new(build_dns_tcp()?))
+        .or_transport(build_dns_tcp()?);
+    // The nodes of the in-process test clusters listen on `/memory/` addresses
+    #[cfg(test)]
+    let transport = transport.or_transport(libp2p::core::transport::MemoryTransport::default());
+    let transport = transport.and_then(move |socket, _| async move {
+        match pre_shared_key {
+            Some(psk) => PnetConfig::new(psk)
+                .handshake(socket)
+                .await
+                .map(future::Either::Left),
+            None => Ok(future::Either::Right(socket)),
+        }
+    });
 
     let auth_config = noise::Config::new(&local_key).context("Noise key generation failed")?;
 
-    Ok(transport
+    let transport = transport
         .upgrade(core::upgrade::Version::V1)
         .authenticate(auth_config)
         .multiplex(yamux::Config::default())
         .timeout(Duration::from_secs(20))
+        .map(|(peer_id, muxer), _| (peer_id, StreamMuxerBox::new(muxer)));
+    let transport = if quic && pre_shared_key.is_none() {
+        let quic = libp2p::quic::tokio::Transport::new(libp2p::quic::Config::new(&local_key));
+        transport
+            .or_transport(quic)
+            .map(|output, _| match output {
+                future::Either::Left(output) => output,
+                future::Either::Right((peer_id, connection)) => {
+                    (peer_id, StreamMuxerBox::new(connection))
+                }
+            })
+            .boxed()
+    } else {
+        transport.boxed()
+    };
+
+    Ok(transport
+        .map(move |(peer_id, muxer), _| {
+            let muxer = bandwidth.instrument(peer_id, muxer);
+            (peer_id, StreamMuxerBox::new(muxer))
+        })
         .boxed())
 }
 
+/// Reads the pre-shared key of a private network, in the
+/// `/key/swarm/psk/1.0.0/` format.
+pub fn read_pre_shared_key(path: &Path) -> anyhow::Result<PreSharedKey> {
+    let key = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read the pre-shared key {}", path.display()))?;
+    let key: PreSharedKey = key
+        .parse()
+        .with_context(|| format!("Invalid pre-shared key {}", path.display()))?;
+    info!(
+        "Using the pre-shared key {} of a private network",
+        key.fingerprint()
+    );
+    Ok(key)
+}
+
 /// Fetch key-pair from disk, returning none if it cannot be decoded.
 pub fn get_keypair(path: &Path) -> Option<Keypair> {
     match read_file_to_vec(path) {
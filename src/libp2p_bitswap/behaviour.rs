@@ -19,6 +19,7 @@ pub type BitswapBehaviourEvent = request_response::Event<Vec<BitswapMessage>, ()
 pub struct BitswapBehaviour {
     inner: request_response::Behaviour<BitswapRequestResponseCodec>,
     request_manager: Arc<BitswapRequestManager>,
+    server: Arc<BitswapServer>,
 }
 
 impl BitswapBehaviour {
@@ -33,9 +34,16 @@ impl BitswapBehaviour {
         BitswapBehaviour {
             inner: request_response::Behaviour::new(BitswapRequestResponseCodec, protocols, cfg),
             request_manager: Default::default(),
+            server: Default::default(),
         }
     }
 
+    /// Replaces the configuration of the serving side of `bitswap`
+    pub fn with_server_config(mut self, config: BitswapServerConfig) -> Self {
+        self.server = Arc::new(BitswapServer::new(config));
+        self
+    }
+
     /// Gets mutable borrow of the inner [`request_response::Behaviour`]
     pub fn inner_mut(&mut self) -> &mut request_response::Behaviour<BitswapRequestResponseCodec> {
         &mut self.inner
@@ -68,6 +76,11 @@ impl BitswapBehaviour {
 
 // Request Manager related API(s)
 impl BitswapBehaviour {
+    /// Gets the associated [`BitswapServer`]
+    pub fn server(&self) -> Arc<BitswapServer> {
+        self.server.clone()
+    }
+
     /// Gets the associated [`BitswapRequestManager`]
     pub fn request_manager(&self) -> Arc<BitswapRequestManager> {
         self.request_manager.clone()
@@ -179,6 +192,7 @@ impl NetworkBehaviour for BitswapBehaviour {
             }
             FromSwarm::ConnectionClosed(e) => {
                 self.request_manager.on_peer_disconnected(&e.peer_id);
+                self.server.on_peer_disconnected(&e.peer_id);
             }
             _ => {}
         };
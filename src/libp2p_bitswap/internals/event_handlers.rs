@@ -30,10 +30,13 @@ pub fn handle_event_impl<S: BitswapStoreRead>(
                 // Close inbound stream immediately since `go-bitswap` does not read this
                 // stream. responses will be sent over a new outbound request
                 _ = bitswap.inner_mut().send_response(channel, ());
+                let server = bitswap.server();
                 for message in request {
                     match message {
                         BitswapMessage::Request(request) => {
-                            if let Some(response) = handle_inbound_request(store, &request) {
+                            if let Some(response) =
+                                server.handle_inbound_request(peer, store, &request)
+                            {
                                 bitswap.send_response(&peer, (request.cid, response));
                             }
                         }
@@ -50,6 +53,11 @@ pub fn handle_event_impl<S: BitswapStoreRead>(
                                 }
                                 BitswapResponse::Block(data) => {
                                     metrics::message_counter_inbound_response_block().inc();
+                                    for (wanting_peer, response) in
+                                        server.on_block_received(peer, &cid, &data)
+                                    {
+                                        bitswap.send_response(&wanting_peer, (cid, response));
+                                    }
                                     Some(BitswapInboundResponseEvent::DataBlock(peer, cid, data))
                                 }
                             } {
@@ -67,35 +75,3 @@ pub fn handle_event_impl<S: BitswapStoreRead>(
 
     Ok(())
 }
-
-fn handle_inbound_request<S: BitswapStoreRead>(
-    store: &S,
-    request: &BitswapRequest,
-) -> Option<BitswapResponse> {
-    if request.cancel {
-        return None;
-    }
-
-    match request.ty {
-        RequestType::Have => {
-            metrics::message_counter_inbound_request_have().inc();
-            let have = store.contains(&request.cid).ok().unwrap_or_default();
-            if have || request.send_dont_have {
-                Some(BitswapResponse::Have(have))
-            } else {
-                None
-            }
-        }
-        RequestType::Block => {
-            metrics::message_counter_inbound_request_block().inc();
-            let block = store.get(&request.cid).ok().unwrap_or_default();
-            if let Some(data) = block {
-                Some(BitswapResponse::Block(data))
-            } else if request.send_dont_have {
-                Some(BitswapResponse::Have(false))
-            } else {
-                None
-            }
-        }
-    }
-}
@@ -75,6 +75,31 @@ pub(in crate::libp2p_bitswap) fn message_counter_inbound_request_block() -> Gene
     MESSAGE_COUNTER.with_label_values(&["inbound_request_block"])
 }
 
+pub(in crate::libp2p_bitswap) fn message_counter_inbound_request_cancel(
+) -> GenericCounter<AtomicU64> {
+    MESSAGE_COUNTER.with_label_values(&["inbound_request_cancel"])
+}
+
+pub(in crate::libp2p_bitswap) fn message_counter_inbound_request_ignored(
+) -> GenericCounter<AtomicU64> {
+    MESSAGE_COUNTER.with_label_values(&["inbound_request_ignored"])
+}
+
+pub(in crate::libp2p_bitswap) fn message_counter_inbound_request_rate_limited(
+) -> GenericCounter<AtomicU64> {
+    MESSAGE_COUNTER.with_label_values(&["inbound_request_rate_limited"])
+}
+
+pub(in crate::libp2p_bitswap) fn message_counter_inbound_request_want_list_full(
+) -> GenericCounter<AtomicU64> {
+    MESSAGE_COUNTER.with_label_values(&["inbound_request_want_list_full"])
+}
+
+pub(in crate::libp2p_bitswap) fn message_counter_want_list_fulfilled() -> GenericCounter<AtomicU64>
+{
+    MESSAGE_COUNTER.with_label_values(&["want_list_fulfilled"])
+}
+
 pub(in crate::libp2p_bitswap) fn message_counter_outbound_request_cancel(
 ) -> GenericCounter<AtomicU64> {
     MESSAGE_COUNTER.with_label_values(&["outbound_request_cancel"])
@@ -139,6 +164,10 @@ pub(in crate::libp2p_bitswap) fn peer_container_capacity() -> GenericGauge<Atomi
     CONTAINER_CAPACITIES.with_label_values(&["peer_container_capacity"])
 }
 
+pub(in crate::libp2p_bitswap) fn ledger_container_capacity() -> GenericGauge<AtomicU64> {
+    CONTAINER_CAPACITIES.with_label_values(&["ledger_container_capacity"])
+}
+
 pub(in crate::libp2p_bitswap) fn response_channel_container_capacity() -> GenericGauge<AtomicU64> {
     CONTAINER_CAPACITIES.with_label_values(&["response_channel_container_capacity"])
 }
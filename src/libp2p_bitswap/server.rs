@@ -0,0 +1,404 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Serving side of `bitswap`. Answers inbound want-list entries from the local
+//! block store, remembers the entries that cannot be answered yet, keeps a
+//! per-peer ledger of the exchanged data and rate limits the peers.
+
+use std::time::{Duration, Instant};
+
+use ahash::{HashMap, HashMapExt};
+use libipld::multihash::{Code, MultihashDigest};
+use libp2p::PeerId;
+use parking_lot::RwLock;
+
+use crate::libp2p_bitswap::*;
+
+const RATE_LIMIT_WINDOW: Duration = Duration::from_secs(1);
+
+/// Configuration of the `bitswap` server
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BitswapServerConfig {
+    /// Whether inbound requests are answered at all
+    pub enabled: bool,
+    /// Maximum number of pending want-list entries that are kept per peer
+    pub max_want_list_size: usize,
+    /// Maximum number of requests per second that are handled per peer
+    pub max_requests_per_second: u32,
+    /// Maximum number of block bytes per second that are sent to each peer
+    pub max_bytes_per_second: u64,
+}
+
+impl Default for BitswapServerConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_want_list_size: 1024,
+            max_requests_per_second: 512,
+            max_bytes_per_second: 16 * 1024 * 1024,
+        }
+    }
+}
+
+/// Data exchanged with a single peer
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BitswapLedgerStats {
+    pub blocks_sent: u64,
+    pub bytes_sent: u64,
+    pub blocks_received: u64,
+    pub bytes_received: u64,
+    pub want_list_size: usize,
+}
+
+#[derive(Debug)]
+struct Ledger {
+    stats: BitswapLedgerStats,
+    /// Pending want-list entries of the peer that could not be answered yet
+    want_list: HashMap<Cid, RequestType>,
+    window_start: Instant,
+    requests_in_window: u32,
+    bytes_in_window: u64,
+}
+
+impl Ledger {
+    fn new(now: Instant) -> Self {
+        Self {
+            stats: Default::default(),
+            want_list: HashMap::new(),
+            window_start: now,
+            requests_in_window: 0,
+            bytes_in_window: 0,
+        }
+    }
+
+    fn roll_window(&mut self, now: Instant) {
+        if now.duration_since(self.window_start) >= RATE_LIMIT_WINDOW {
+            self.window_start = now;
+            self.requests_in_window = 0;
+            self.bytes_in_window = 0;
+        }
+    }
+}
+
+/// Serving side of `bitswap`, see the module documentation
+#[derive(Debug, Default)]
+pub struct BitswapServer {
+    config: BitswapServerConfig,
+    ledgers: RwLock<HashMap<PeerId, Ledger>>,
+}
+
+impl BitswapServer {
+    /// Creates a [`BitswapServer`] with the given configuration
+    pub fn new(config: BitswapServerConfig) -> Self {
+        Self {
+            config,
+            ledgers: Default::default(),
+        }
+    }
+
+    /// Gets the configuration of the server
+    pub fn config(&self) -> &BitswapServerConfig {
+        &self.config
+    }
+
+    /// Gets the ledger statistics of a peer
+    pub fn ledger(&self, peer: &PeerId) -> Option<BitswapLedgerStats> {
+        self.ledgers
+            .read()
+            .get(peer)
+            .map(|ledger| BitswapLedgerStats {
+                want_list_size: ledger.want_list.len(),
+                ..ledger.stats.clone()
+            })
+    }
+
+    /// Handles a single inbound want-list entry, returning the response to
+    /// send back, if any
+    pub fn handle_inbound_request<S: BitswapStoreRead>(
+        &self,
+        peer: PeerId,
+        store: &S,
+        request: &BitswapRequest,
+    ) -> Option<BitswapResponse> {
+        if !self.config.enabled {
+            metrics::message_counter_inbound_request_ignored().inc();
+            return None;
+        }
+
+        {
+            let now = Instant::now();
+            let mut ledgers = self.ledgers.write();
+            let ledger = Self::ledger_mut(&mut ledgers, peer, now);
+
+            if request.cancel {
+                metrics::message_counter_inbound_request_cancel().inc();
+                ledger.want_list.remove(&request.cid);
+                return None;
+            }
+
+            ledger.roll_window(now);
+            if ledger.requests_in_window >= self.config.max_requests_per_second {
+                metrics::message_counter_inbound_request_rate_limited().inc();
+                return None;
+            }
+            ledger.requests_in_window += 1;
+        }
+
+        // The store is read without the lock of the ledgers, which the other
+        // peers need meanwhile
+        match request.ty {
+            RequestType::Have => {
+                metrics::message_counter_inbound_request_have().inc();
+                let have = store.contains(&request.cid).ok().unwrap_or_default();
+                if have || request.send_dont_have {
+                    Some(BitswapResponse::Have(have))
+                } else {
+                    let mut ledgers = self.ledgers.write();
+                    self.add_want(
+                        Self::ledger_mut(&mut ledgers, peer, Instant::now()),
+                        request,
+                    );
+                    None
+                }
+            }
+            RequestType::Block => {
+                metrics::message_counter_inbound_request_block().inc();
+                let block = store.get(&request.cid).ok().unwrap_or_default();
+                let mut ledgers = self.ledgers.write();
+                let ledger = Self::ledger_mut(&mut ledgers, peer, Instant::now());
+                if let Some(data) = block {
+                    if ledger.bytes_in_window + data.len() as u64 > self.config.max_bytes_per_second
+                    {
+                        metrics::message_counter_inbound_request_rate_limited().inc();
+                        // Try again once the block is seen next time
+                        self.add_want(ledger, request);
+                        return None;
+                    }
+                    ledger.bytes_in_window += data.len() as u64;
+                    ledger.stats.blocks_sent += 1;
+                    ledger.stats.bytes_sent += data.len() as u64;
+                    ledger.want_list.remove(&request.cid);
+                    Some(BitswapResponse::Block(data))
+                } else if request.send_dont_have {
+                    Some(BitswapResponse::Have(false))
+                } else {
+                    self.add_want(ledger, request);
+                    None
+                }
+            }
+        }
+    }
+
+    /// Records a block received from a peer and returns the responses for the
+    /// peers whose pending want-list entries the block fulfills
+    pub fn on_block_received(
+        &self,
+        from: PeerId,
+        cid: &Cid,
+        data: &[u8],
+    ) -> Vec<(PeerId, BitswapResponse)> {
+        let now = Instant::now();
+        let mut ledgers = self.ledgers.write();
+        let ledger = Self::ledger_mut(&mut ledgers, from, now);
+        ledger.stats.blocks_received += 1;
+        ledger.stats.bytes_received += data.len() as u64;
+
+        // Only forward data that matches the requested `cid`
+        if !self.config.enabled || !is_valid_block(cid, data) {
+            return vec![];
+        }
+
+        let mut responses = vec![];
+        for (&peer, ledger) in ledgers.iter_mut() {
+            let Some(ty) = ledger.want_list.remove(cid) else {
+                continue;
+            };
+            ledger.roll_window(now);
+            match ty {
+                RequestType::Have => responses.push((peer, BitswapResponse::Have(true))),
+                RequestType::Block => {
+                    if ledger.bytes_in_window + data.len() as u64 > self.config.max_bytes_per_second
+                    {
+                        metrics::message_counter_inbound_request_rate_limited().inc();
+                        continue;
+                    }
+                    ledger.bytes_in_window += data.len() as u64;
+                    ledger.stats.blocks_sent += 1;
+                    ledger.stats.bytes_sent += data.len() as u64;
+                    responses.push((peer, BitswapResponse::Block(data.to_vec())));
+                }
+            }
+            metrics::message_counter_want_list_fulfilled().inc();
+        }
+        responses
+    }
+
+    /// Drops the ledger of a disconnected peer
+    pub(in crate::libp2p_bitswap) fn on_peer_disconnected(&self, peer: &PeerId) {
+        let mut ledgers = self.ledgers.write();
+        if ledgers.remove(peer).is_some() {
+            metrics::ledger_container_capacity().set(ledgers.capacity() as _);
+        }
+    }
+
+    fn ledger_mut(
+        ledgers: &mut HashMap<PeerId, Ledger>,
+        peer: PeerId,
+        now: Instant,
+    ) -> &mut Ledger {
+        if !ledgers.contains_key(&peer) {
+            ledgers.insert(peer, Ledger::new(now));
+            metrics::ledger_container_capacity().set(ledgers.capacity() as _);
+        }
+        ledgers.get_mut(&peer).expect("Infallible")
+    }
+
+    fn add_want(&self, ledger: &mut Ledger, request: &BitswapRequest) {
+        if ledger.want_list.len() < self.config.max_want_list_size
+            || ledger.want_list.contains_key(&request.cid)
+        {
+            ledger.want_list.insert(request.cid, request.ty);
+        } else {
+            metrics::message_counter_inbound_request_want_list_full().inc();
+        }
+    }
+}
+
+fn is_valid_block(cid: &Cid, data: &[u8]) -> bool {
+    Code::try_from(cid.hash().code())
+        .map(|code| code.digest(data) == *cid.hash())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct TestStore(HashMap<Cid, Vec<u8>>);
+
+    impl BitswapStoreRead for TestStore {
+        fn contains(&self, cid: &Cid) -> anyhow::Result<bool> {
+            Ok(self.0.contains_key(cid))
+        }
+
+        fn get(&self, cid: &Cid) -> anyhow::Result<Option<Vec<u8>>> {
+            Ok(self.0.get(cid).cloned())
+        }
+    }
+
+    fn block(n: u8, len: usize) -> (Cid, Vec<u8>) {
+        let data = vec![n; len];
+        let cid = Cid::new_v1(0x55, Code::Sha2_256.digest(&data));
+        (cid, data)
+    }
+
+    #[test]
+    fn serves_known_blocks_and_updates_ledger() {
+        let server = BitswapServer::default();
+        let peer = PeerId::random();
+        let (cid, data) = block(0, 100);
+        let store = TestStore([(cid, data.clone())].into_iter().collect());
+
+        let response = server.handle_inbound_request(peer, &store, &BitswapRequest::new_block(cid));
+        assert_eq!(response, Some(BitswapResponse::Block(data)));
+        let stats = server.ledger(&peer).unwrap();
+        assert_eq!(stats.blocks_sent, 1);
+        assert_eq!(stats.bytes_sent, 100);
+    }
+
+    #[test]
+    fn store_is_read_without_the_ledgers_lock() {
+        // Reads the ledgers while the server reads the store
+        struct LedgerReadingStore<'a>(&'a BitswapServer, PeerId, TestStore);
+
+        impl BitswapStoreRead for LedgerReadingStore<'_> {
+            fn contains(&self, cid: &Cid) -> anyhow::Result<bool> {
+                self.0.ledger(&self.1);
+                self.2.contains(cid)
+            }
+
+            fn get(&self, cid: &Cid) -> anyhow::Result<Option<Vec<u8>>> {
+                self.0.ledger(&self.1);
+                self.2.get(cid)
+            }
+        }
+
+        let server = BitswapServer::default();
+        let peer = PeerId::random();
+        let (cid, data) = block(0, 100);
+        let store = LedgerReadingStore(
+            &server,
+            peer,
+            TestStore([(cid, data.clone())].into_iter().collect()),
+        );
+        let response = server.handle_inbound_request(peer, &store, &BitswapRequest::new_have(cid));
+        assert_eq!(response, Some(BitswapResponse::Have(true)));
+        let response = server.handle_inbound_request(peer, &store, &BitswapRequest::new_block(cid));
+        assert_eq!(response, Some(BitswapResponse::Block(data)));
+    }
+
+    #[test]
+    fn disabled_server_ignores_requests() {
+        let server = BitswapServer::new(BitswapServerConfig {
+            enabled: false,
+            ..Default::default()
+        });
+        let (cid, data) = block(0, 100);
+        let store = TestStore([(cid, data)].into_iter().collect());
+        let request = BitswapRequest::new_block(cid).send_dont_have(true);
+        assert_eq!(
+            server.handle_inbound_request(PeerId::random(), &store, &request),
+            None
+        );
+    }
+
+    #[test]
+    fn fulfills_pending_wants() {
+        let server = BitswapServer::default();
+        let store = TestStore::default();
+        let (cid, data) = block(0, 100);
+        let wanting_block = PeerId::random();
+        let wanting_have = PeerId::random();
+        let cancelling = PeerId::random();
+
+        server.handle_inbound_request(wanting_block, &store, &BitswapRequest::new_block(cid));
+        server.handle_inbound_request(wanting_have, &store, &BitswapRequest::new_have(cid));
+        server.handle_inbound_request(cancelling, &store, &BitswapRequest::new_block(cid));
+        server.handle_inbound_request(cancelling, &store, &BitswapRequest::new_cancel(cid));
+        assert_eq!(server.ledger(&wanting_block).unwrap().want_list_size, 1);
+        assert_eq!(server.ledger(&cancelling).unwrap().want_list_size, 0);
+
+        let responses = server.on_block_received(PeerId::random(), &cid, &data);
+        assert_eq!(responses.len(), 2);
+        assert!(responses.contains(&(wanting_block, BitswapResponse::Block(data))));
+        assert!(responses.contains(&(wanting_have, BitswapResponse::Have(true))));
+        assert_eq!(server.ledger(&wanting_block).unwrap().want_list_size, 0);
+    }
+
+    #[test]
+    fn rate_limits_requests_per_peer() {
+        let server = BitswapServer::new(BitswapServerConfig {
+            max_requests_per_second: 2,
+            ..Default::default()
+        });
+        let peer = PeerId::random();
+        let (cid, data) = block(0, 100);
+        let store = TestStore([(cid, data)].into_iter().collect());
+        let request = BitswapRequest::new_have(cid);
+
+        assert!(server
+            .handle_inbound_request(peer, &store, &request)
+            .is_some());
+        assert!(server
+            .handle_inbound_request(peer, &store, &request)
+            .is_some());
+        assert!(server
+            .handle_inbound_request(peer, &store, &request)
+            .is_none());
+        // Other peers are not affected
+        assert!(server
+            .handle_inbound_request(PeerId::random(), &store, &request)
+            .is_some());
+    }
+}
@@ -2,6 +2,7 @@
 // SPDX-License-Identifier: Apache-2.0, MIT
 
 pub mod chain_message;
+pub mod signature_cache;
 pub mod signed_message;
 
 use crate::shim::{address::Address, econ::TokenAmount, message::Message as ShimMessage};
@@ -91,14 +92,16 @@ impl Message for ShimMessage {
     }
 }
 
-/// Semantic validation and validates the message has enough gas.
+/// Semantic validation and validates the message has enough gas, and no more
+/// than the block gas limit of the network.
 pub fn valid_for_block_inclusion(
     msg: &ShimMessage,
     min_gas: Gas,
     version: NetworkVersion,
+    block_gas_limit: u64,
 ) -> Result<(), anyhow::Error> {
     use crate::shim::address::ZERO_ADDRESS;
-    use fvm_shared3::{BLOCK_GAS_LIMIT, TOTAL_FILECOIN};
+    use fvm_shared3::TOTAL_FILECOIN;
     if msg.version != 0 {
         anyhow::bail!("Message version: {} not supported", msg.version);
     }
@@ -120,7 +123,7 @@ pub fn valid_for_block_inclusion(
     if msg.gas_premium > msg.gas_fee_cap {
         anyhow::bail!("gas_fee_cap less than gas_premium");
     }
-    if msg.gas_limit > BLOCK_GAS_LIMIT {
+    if msg.gas_limit > block_gas_limit {
         anyhow::bail!(
             "gas_limit {} cannot be greater than block gas limit",
             msg.gas_limit
@@ -0,0 +1,141 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Cache of the verified message signatures, shared by the gossip
+//! validation, the message pool and the block validation. A message is
+//! usually received over gossip, added to the message pool and then included
+//! in a block, or fetched with chain exchange, without its signature having
+//! to be verified at each step.
+//!
+//! Entries are keyed by the CID of the unsigned message, which is also what
+//! is signed. As the CID of a BLS signed message doesn't cover its
+//! signature, the signature and the signer are kept to only report a hit for
+//! the very same ones.
+
+use std::num::NonZeroUsize;
+
+use crate::shim::{address::Address, crypto::Signature};
+use cid::Cid;
+use fvm_ipld_encoding::Cbor;
+use lazy_static::lazy_static;
+use lru::LruCache;
+use nonzero_ext::nonzero;
+use parking_lot::Mutex;
+use prometheus::core::{AtomicU64, GenericCounterVec, Opts};
+
+use super::{Message as _, SignedMessage};
+
+const SIGNATURE_CACHE_SIZE: NonZeroUsize = nonzero!(32000usize);
+
+mod values {
+    pub const HIT: &str = "hit";
+    pub const MISS: &str = "miss";
+}
+
+lazy_static! {
+    static ref SIGNATURE_CACHE: Mutex<LruCache<Cid, (Signature, Address)>> =
+        Mutex::new(LruCache::new(SIGNATURE_CACHE_SIZE));
+    pub static ref SIGNATURE_CACHE_LOOKUPS: Box<GenericCounterVec<AtomicU64>> = {
+        let signature_cache_lookups = Box::new(
+            GenericCounterVec::<AtomicU64>::new(
+                Opts::new(
+                    "signature_cache_lookups",
+                    "Number of message signature verifications, by whether the signature was cached",
+                ),
+                &["result"],
+            )
+            .expect("Defining the signature_cache_lookups metric must succeed"),
+        );
+        prometheus::default_registry().register(signature_cache_lookups.clone()).expect(
+            "Registering the signature_cache_lookups metric with the metrics registry must succeed",
+        );
+        signature_cache_lookups
+    };
+}
+
+/// Verifies that `signer` signed the message, unless it was already verified.
+pub fn verify_signature(
+    message: &Cid,
+    signature: &Signature,
+    signer: &Address,
+) -> Result<(), String> {
+    let cached = matches!(
+        SIGNATURE_CACHE.lock().get(message),
+        Some((sig, addr)) if sig == signature && addr == signer
+    );
+    if cached {
+        SIGNATURE_CACHE_LOOKUPS
+            .with_label_values(&[values::HIT])
+            .inc();
+        return Ok(());
+    }
+    SIGNATURE_CACHE_LOOKUPS
+        .with_label_values(&[values::MISS])
+        .inc();
+    signature.verify(&message.to_bytes(), signer)?;
+    SIGNATURE_CACHE
+        .lock()
+        .put(*message, (signature.clone(), *signer));
+    Ok(())
+}
+
+/// Verifies that the from address of the message generated the signature,
+/// unless it was already verified.
+pub fn verify_signed_message(message: &SignedMessage) -> Result<(), String> {
+    let cid = message.message().cid().map_err(|e| e.to_string())?;
+    verify_signature(&cid, message.signature(), &message.from())
+}
+
+/// Records the signature of the message as valid, for tests to use messages
+/// with dummy signatures.
+#[cfg(test)]
+pub fn insert(message: &SignedMessage) {
+    SIGNATURE_CACHE.lock().put(
+        message.message().cid().unwrap(),
+        (message.signature().clone(), message.from()),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::key_management::{generate_key, sign, Key};
+    use crate::shim::{
+        crypto::SignatureType,
+        message::{Message, Message_v3},
+    };
+
+    use super::*;
+
+    fn signed_message(key: &Key) -> SignedMessage {
+        let message: Message = Message_v3 {
+            from: key.address.into(),
+            ..Message_v3::default()
+        }
+        .into();
+        let signature = sign(
+            SignatureType::BLS,
+            key.key_info.private_key(),
+            &message.cid().unwrap().to_bytes(),
+        )
+        .unwrap();
+        SignedMessage::new_unchecked(message, signature)
+    }
+
+    #[test]
+    fn cached_signatures_must_match() {
+        let key = generate_key(SignatureType::BLS).unwrap();
+        let other = generate_key(SignatureType::BLS).unwrap();
+        let message = signed_message(&key);
+        verify_signed_message(&message).unwrap();
+        verify_signed_message(&message).unwrap();
+
+        // The CID of a BLS message doesn't cover its signature, a cached
+        // message with another signature must be verified again
+        let mut forged = message.clone();
+        forged.signature = signed_message(&other).signature;
+        assert!(verify_signed_message(&forged).is_err());
+        let cid = message.message().cid().unwrap();
+        assert!(verify_signature(&cid, message.signature(), &other.address).is_err());
+        verify_signature(&cid, message.signature(), &key.address).unwrap();
+    }
+}
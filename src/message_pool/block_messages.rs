@@ -0,0 +1,303 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Rules the messages of a block follow as a set, on top of those each of
+//! them follows on its own: a message is included once, their gas limits add
+//! up to at most the one of a block, and the sequences of each sender follow
+//! the one of its actor. The validation of the blocks received and the
+//! selection of the messages of the blocks mined share them.
+//!
+//! The blocks mined carry the aggregate of the signatures of their BLS
+//! messages, which is computed the way Lotus does so that the headers match.
+
+use crate::message::{Message as MessageTrait, SignedMessage};
+use crate::shim::{
+    address::Address,
+    crypto::{verify_bls_aggregate, Signature, SignatureType},
+    message::Message,
+};
+use ahash::{HashMap, HashSet};
+use bls_signatures::Serialize as _;
+use cid::Cid;
+use fvm_ipld_encoding::Cbor;
+
+/// Aggregate of no BLS signature, the compressed point at infinity of G2, as
+/// found in the headers of the blocks without BLS messages
+pub const EMPTY_BLS_AGGREGATE: [u8; 96] = {
+    let mut bytes = [0; 96];
+    bytes[0] = 0xc0;
+    bytes
+};
+
+/// Checks the messages of a block one after the other, BLS messages first and
+/// then SECP ones, as they are executed.
+pub struct BlockMessagesValidator {
+    block_gas_limit: u64,
+    gas_limit: u64,
+    /// Next sequence of the senders of the messages included
+    sequences: HashMap<Address, u64>,
+    included: HashSet<Cid>,
+}
+
+impl BlockMessagesValidator {
+    pub fn new(block_gas_limit: u64) -> Self {
+        Self {
+            block_gas_limit,
+            gas_limit: 0,
+            sequences: HashMap::default(),
+            included: HashSet::default(),
+        }
+    }
+
+    /// Checks that the message can follow those already included, and
+    /// includes it if so. `actor_sequence` returns the sequence of the actor
+    /// of a sender in the base state of the block, and is only called on the
+    /// first message of each sender.
+    pub fn include(
+        &mut self,
+        msg: &Message,
+        actor_sequence: impl FnOnce(&Address) -> anyhow::Result<u64>,
+    ) -> anyhow::Result<()> {
+        let cid = msg.cid()?;
+        if self.included.contains(&cid) {
+            anyhow::bail!("duplicate message {cid}");
+        }
+
+        let gas_limit = self.gas_limit + msg.gas_limit();
+        if gas_limit > self.block_gas_limit {
+            anyhow::bail!("block gas limit exceeded");
+        }
+
+        let from = msg.from();
+        let sequence = match self.sequences.get(&from) {
+            Some(sequence) => *sequence,
+            None => actor_sequence(&from)?,
+        };
+        if sequence != msg.sequence() {
+            anyhow::bail!(
+                "Message has incorrect sequence (exp: {} got: {})",
+                sequence,
+                msg.sequence()
+            );
+        }
+
+        self.included.insert(cid);
+        self.gas_limit = gas_limit;
+        self.sequences.insert(from, sequence + 1);
+        Ok(())
+    }
+}
+
+/// Checks that `signature` aggregates the signatures of the BLS messages of a
+/// block, given the signed bytes of the messages and the public keys of their
+/// senders
+pub fn verify_bls_messages_aggregate(
+    data: &[impl AsRef<[u8]>],
+    pub_keys: &[impl AsRef<[u8]>],
+    signature: &Signature,
+) -> bool {
+    verify_bls_aggregate(
+        &data.iter().map(AsRef::as_ref).collect::<Vec<_>>(),
+        &pub_keys.iter().map(AsRef::as_ref).collect::<Vec<_>>(),
+        signature,
+    )
+}
+
+/// The messages of a block split as they are stored: the BLS ones, of which
+/// only the unsigned messages are, and the others, signed, in their order
+pub struct SplitBlockMessages<'a> {
+    pub bls: Vec<&'a Message>,
+    pub secp: Vec<&'a SignedMessage>,
+    /// Aggregate of the signatures of the BLS messages
+    pub bls_aggregate: Signature,
+}
+
+/// Splits the messages of a block by signature type and aggregates the
+/// signatures of the BLS ones. Delegated messages go with the `secp256k1` ones.
+pub fn split_block_messages<'a>(
+    messages: impl IntoIterator<Item = &'a SignedMessage>,
+) -> anyhow::Result<SplitBlockMessages<'a>> {
+    let (bls, secp): (Vec<_>, Vec<_>) = messages.into_iter().partition(|msg| msg.is_bls());
+    Ok(SplitBlockMessages {
+        bls_aggregate: aggregate_bls_signatures(bls.iter().map(|msg| msg.signature()))?,
+        bls: bls.into_iter().map(SignedMessage::message).collect(),
+        secp,
+    })
+}
+
+/// Aggregates BLS signatures, [`EMPTY_BLS_AGGREGATE`] being the aggregate of
+/// none.
+pub fn aggregate_bls_signatures<'a>(
+    signatures: impl IntoIterator<Item = &'a Signature>,
+) -> anyhow::Result<Signature> {
+    let signatures = signatures
+        .into_iter()
+        .map(|signature| {
+            anyhow::ensure!(
+                signature.signature_type() == SignatureType::BLS,
+                "can't aggregate a {:?} signature",
+                signature.signature_type()
+            );
+            Ok(bls_signatures::Signature::from_bytes(signature.bytes())?)
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    if signatures.is_empty() {
+        return Ok(Signature::new_bls(EMPTY_BLS_AGGREGATE.to_vec()));
+    }
+    Ok(Signature::new_bls(
+        bls_signatures::aggregate(&signatures)?.as_bytes(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shim::message::Message_v3;
+    use bls_signatures::{PrivateKey, Serialize};
+    use quickcheck_macros::quickcheck;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    /// Signs messages of the given sequences with keys generated from `seed`,
+    /// returning the signed messages and the public keys of their senders
+    fn bls_messages(seed: u64, sequences: &[u64]) -> (Vec<SignedMessage>, Vec<Vec<u8>>) {
+        let mut rng = StdRng::seed_from_u64(seed);
+        sequences
+            .iter()
+            .map(|&sequence| {
+                let key = PrivateKey::generate(&mut rng);
+                let msg = message(100, sequence, 10);
+                let signature = key.sign(msg.cid().unwrap().to_bytes());
+                (
+                    SignedMessage::new_unchecked(msg, Signature::new_bls(signature.as_bytes())),
+                    key.public_key().as_bytes(),
+                )
+            })
+            .unzip()
+    }
+
+    fn message(from: u64, sequence: u64, gas_limit: u64) -> Message {
+        Message_v3 {
+            from: Address::new_id(from).into(),
+            to: Address::new_id(1).into(),
+            sequence,
+            gas_limit,
+            ..Message_v3::default()
+        }
+        .into()
+    }
+
+    #[test]
+    fn sequences_follow_the_actor() {
+        let mut validator = BlockMessagesValidator::new(1_000);
+        let actor_sequence = |_: &Address| -> anyhow::Result<u64> { Ok(5) };
+        validator
+            .include(&message(100, 5, 10), actor_sequence)
+            .unwrap();
+        validator
+            .include(&message(100, 6, 10), |_| unreachable!())
+            .unwrap();
+        assert!(validator
+            .include(&message(100, 8, 10), actor_sequence)
+            .is_err());
+        assert!(validator
+            .include(&message(101, 4, 10), actor_sequence)
+            .is_err());
+    }
+
+    #[test]
+    fn duplicates_are_rejected() {
+        let mut validator = BlockMessagesValidator::new(1_000);
+        validator.include(&message(100, 0, 10), |_| Ok(0)).unwrap();
+        let error = validator
+            .include(&message(100, 0, 10), |_| Ok(0))
+            .unwrap_err();
+        assert!(error.to_string().starts_with("duplicate message"));
+    }
+
+    #[test]
+    fn gas_limits_add_up_to_the_block_one() {
+        let mut validator = BlockMessagesValidator::new(100);
+        validator.include(&message(100, 0, 60), |_| Ok(0)).unwrap();
+        assert!(validator.include(&message(101, 0, 60), |_| Ok(0)).is_err());
+        // A rejected message isn't included
+        validator.include(&message(101, 0, 40), |_| Ok(0)).unwrap();
+    }
+
+    #[test]
+    fn empty_aggregate_matches_lotus() {
+        let aggregate = aggregate_bls_signatures([]).unwrap();
+        assert_eq!(aggregate.signature_type(), SignatureType::BLS);
+        assert_eq!(
+            hex::encode(aggregate.bytes()),
+            format!("c0{}", "0".repeat(190))
+        );
+        // The point at infinity is a valid signature
+        bls_signatures::Signature::from_bytes(aggregate.bytes()).unwrap();
+    }
+
+    #[test]
+    fn aggregate_of_one_signature_is_itself() {
+        let (messages, _) = bls_messages(0, &[0]);
+        let aggregate = aggregate_bls_signatures([messages[0].signature()]).unwrap();
+        assert_eq!(&aggregate, messages[0].signature());
+    }
+
+    #[test]
+    fn secp_signatures_are_not_aggregated() {
+        let signature = Signature::new_secp256k1(vec![0; 65]);
+        assert!(aggregate_bls_signatures([&signature]).is_err());
+    }
+
+    #[quickcheck]
+    fn aggregate_verifies_in_any_order(seed: u64, count: u8) {
+        let sequences = (0..u64::from(count % 8) + 1).collect::<Vec<_>>();
+        let (messages, pub_keys) = bls_messages(seed, &sequences);
+        let signatures = messages.iter().map(SignedMessage::signature);
+        let aggregate = aggregate_bls_signatures(signatures.clone()).unwrap();
+        let data = messages
+            .iter()
+            .map(|msg| msg.message().cid().unwrap().to_bytes())
+            .collect::<Vec<_>>();
+        assert!(verify_bls_messages_aggregate(&data, &pub_keys, &aggregate));
+        assert_eq!(
+            aggregate_bls_signatures(signatures.rev()).unwrap(),
+            aggregate
+        );
+    }
+
+    #[quickcheck]
+    fn split_keeps_the_order_of_messages(seed: u64, secp_positions: Vec<bool>) {
+        let (bls, _) = bls_messages(seed, &[0, 1, 2]);
+        let secp = (0..secp_positions.len() as u64)
+            .map(|sequence| {
+                SignedMessage::new_unchecked(
+                    message(200, sequence, 10),
+                    Signature::new_secp256k1(vec![0; 65]),
+                )
+            })
+            .collect::<Vec<_>>();
+        let (mut bls_iter, mut secp_iter) = (bls.iter(), secp.iter());
+        let mut mixed = secp_positions
+            .iter()
+            .filter_map(|&is_secp| {
+                if is_secp {
+                    secp_iter.next()
+                } else {
+                    bls_iter.next()
+                }
+            })
+            .collect::<Vec<_>>();
+        mixed.extend(bls_iter);
+
+        let split = split_block_messages(mixed).unwrap();
+        assert_eq!(
+            split.bls,
+            bls.iter().map(SignedMessage::message).collect::<Vec<_>>()
+        );
+        assert_eq!(split.secp, secp.iter().collect::<Vec<_>>());
+        assert_eq!(
+            split.bls_aggregate,
+            aggregate_bls_signatures(bls.iter().map(SignedMessage::signature)).unwrap()
+        );
+    }
+}
@@ -1,12 +1,22 @@
 // Copyright 2019-2023 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
-use std::f64::consts::E;
+use std::{f64::consts::E, num::NonZeroUsize, sync::Arc};
 
+use lazy_static::lazy_static;
+use lru::LruCache;
+use nonzero_ext::nonzero;
+use parking_lot::Mutex;
 use statrs::function::gamma::ln_gamma;
 
-const MAX_BLOCKS: usize = 15;
 const MU: f64 = 5.0;
+const BLOCK_PROBABILITIES_CACHE_SIZE: NonZeroUsize = nonzero!(1024usize);
+
+lazy_static! {
+    /// Probability tables, by ticket quality bits and number of blocks
+    static ref BLOCK_PROBABILITIES: Mutex<LruCache<(u64, usize), Arc<Vec<f64>>>> =
+        Mutex::new(LruCache::new(BLOCK_PROBABILITIES_CACHE_SIZE));
+}
 
 fn poiss_pdf(x: f64, mu: f64, cond: f64) -> f64 {
     let ln_gamma = ln_gamma(x + 1.0);
@@ -15,21 +25,20 @@ fn poiss_pdf(x: f64, mu: f64, cond: f64) -> f64 {
     E.powf(exponent)
 }
 
-/// Calculate the number of winners for each block number, up to [`MAX_BLOCKS`].
+/// Calculate the number of winners for each block number, up to `max_blocks`.
 // * This will be needed for optimal message selection
 #[cfg(test)]
-// TODO following two can be lazy_static
-fn no_winners_prob() -> Vec<f64> {
-    (0..MAX_BLOCKS)
+fn no_winners_prob(max_blocks: usize) -> Vec<f64> {
+    (0..max_blocks)
         .map(|i| poiss_pdf(i as f64, MU, MU))
         .collect()
 }
 
-/// Calculate the number of winners for each block number, up to [`MAX_BLOCKS`],
+/// Calculate the number of winners for each block number, up to `max_blocks`,
 /// assuming at least one winner.
-fn no_winners_prob_assuming_more_than_one() -> Vec<f64> {
+fn no_winners_prob_assuming_more_than_one(max_blocks: usize) -> Vec<f64> {
     let cond = (E.powf(5.0) - 1.0).log(E);
-    (0..MAX_BLOCKS)
+    (0..max_blocks)
         .map(|i| poiss_pdf(i as f64, MU, cond))
         .collect()
 }
@@ -77,10 +86,12 @@ fn bino_pdf(x: f64, trials: f64, p: f64) -> f64 {
     coef * pow
 }
 
-pub fn block_probabilities(tq: f64) -> Vec<f64> {
-    let no_winners = no_winners_prob_assuming_more_than_one();
+/// Probabilities of a block of the given ticket quality to be at each place of
+/// a tipset of up to `max_blocks` blocks
+pub fn block_probabilities(tq: f64, max_blocks: usize) -> Vec<f64> {
+    let no_winners = no_winners_prob_assuming_more_than_one(max_blocks);
     let p = 1.0 - tq;
-    (0..MAX_BLOCKS)
+    (0..max_blocks)
         .map(|place| {
             no_winners
                 .iter()
@@ -93,19 +104,38 @@ pub fn block_probabilities(tq: f64) -> Vec<f64> {
         .collect()
 }
 
+/// [`block_probabilities`], computed once per distinct ticket quality
+pub fn cached_block_probabilities(tq: f64, max_blocks: usize) -> Arc<Vec<f64>> {
+    let key = (tq.to_bits(), max_blocks);
+    if let Some(probabilities) = BLOCK_PROBABILITIES.lock().get(&key) {
+        return probabilities.clone();
+    }
+    let probabilities = Arc::new(block_probabilities(tq, max_blocks));
+    BLOCK_PROBABILITIES.lock().put(key, probabilities.clone());
+    probabilities
+}
+
 #[test]
 fn test_block_probability() {
-    let bp = block_probabilities(1.0 - 0.15);
+    let bp = block_probabilities(1.0 - 0.15, 15);
     for i in 0..bp.len() - 1 {
         assert!(bp[i] >= bp[i + 1]);
     }
 }
 
+#[test]
+fn test_cached_block_probability() {
+    let cached = cached_block_probabilities(0.5, 15);
+    assert_eq!(*cached, block_probabilities(0.5, 15));
+    assert!(Arc::ptr_eq(&cached, &cached_block_probabilities(0.5, 15)));
+    assert_eq!(cached_block_probabilities(0.5, 10).len(), 10);
+}
+
 #[test]
 fn test_winner_probability() {
     use rand::{thread_rng, Rng};
     let n = 1_000_000;
-    let winner_prob = no_winners_prob();
+    let winner_prob = no_winners_prob(15);
     let mut sum = 0.0;
 
     // Generates a radnom number from 0 to not including 1
@@ -113,7 +143,7 @@ fn test_winner_probability() {
 
     for _ in 0..n {
         let mut miners_rand: f64 = rng.gen::<f64>() * f64::MAX;
-        for prob in winner_prob.iter().take(MAX_BLOCKS) {
+        for prob in winner_prob.iter() {
             miners_rand -= prob;
             if miners_rand < 0.0 {
                 break;
@@ -14,6 +14,7 @@ const SIZE_LIMIT_HIGH: i64 = 30000;
 const PRUNE_COOLDOWN: Duration = Duration::from_secs(60); // 1 minute
 const REPLACE_BY_FEE_RATIO: f64 = 1.25;
 const GAS_LIMIT_OVERESTIMATION: f64 = 1.25;
+const GREEDY_SELECTION_THRESHOLD: f64 = 0.84;
 
 /// Configuration available for the [`crate::message_pool::MessagePool`].
 ///
@@ -26,6 +27,25 @@ pub struct MpoolConfig {
     pub replace_by_fee_ratio: f64,
     pub prune_cooldown: Duration,
     pub gas_limit_overestimation: f64,
+    /// Break the ties between the message chains of equal performance with a
+    /// hash of their sender salted per node, rather than always in the same
+    /// order, so that the miners don't all favour the same senders
+    #[serde(default)]
+    pub randomize_chain_ties: bool,
+    /// Ticket quality above which messages are selected greedily, as the block
+    /// is then more likely to be the first of its tipset than at any other
+    /// place. Below, they are selected by their expected reward over the
+    /// places the block may take.
+    #[serde(default = "default_greedy_selection_threshold")]
+    pub greedy_selection_threshold: f64,
+    /// Set on the nodes syncing the headers only, whose head changes miss
+    /// the messages. Set from the sync configuration, not stored.
+    #[serde(skip)]
+    pub headers_only: bool,
+}
+
+fn default_greedy_selection_threshold() -> f64 {
+    GREEDY_SELECTION_THRESHOLD
 }
 
 impl Default for MpoolConfig {
@@ -37,6 +57,9 @@ impl Default for MpoolConfig {
             replace_by_fee_ratio: REPLACE_BY_FEE_RATIO,
             prune_cooldown: PRUNE_COOLDOWN,
             gas_limit_overestimation: GAS_LIMIT_OVERESTIMATION,
+            randomize_chain_ties: false,
+            greedy_selection_threshold: GREEDY_SELECTION_THRESHOLD,
+            headers_only: false,
         }
     }
 }
@@ -49,6 +72,8 @@ impl MpoolConfig {
         replace_by_fee_ratio: f64,
         prune_cooldown: Duration,
         gas_limit_overestimation: f64,
+        randomize_chain_ties: bool,
+        greedy_selection_threshold: f64,
     ) -> Result<Self, String> {
         // Validate if parameters are valid
         if replace_by_fee_ratio < REPLACE_BY_FEE_RATIO {
@@ -56,6 +81,11 @@ impl MpoolConfig {
                 "replace_by_fee_ratio:{replace_by_fee_ratio} is less than required: {REPLACE_BY_FEE_RATIO}"
             ));
         }
+        if !(0.0..=1.0).contains(&greedy_selection_threshold) {
+            return Err(format!(
+                "greedy_selection_threshold of: {greedy_selection_threshold} is not between 0 and 1"
+            ));
+        }
         if gas_limit_overestimation < 1.0 {
             return Err(format!(
                 "gas_limit_overestimation of: {} is less than required: {}",
@@ -69,6 +99,9 @@ impl MpoolConfig {
             replace_by_fee_ratio,
             prune_cooldown,
             gas_limit_overestimation,
+            randomize_chain_ties,
+            greedy_selection_threshold,
+            headers_only: false,
         })
     }
 
@@ -1,5 +1,6 @@
 // Copyright 2019-2023 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
+mod block_messages;
 mod block_prob;
 mod config;
 mod errors;
@@ -7,11 +8,12 @@ mod msg_chain;
 mod msgpool;
 
 pub use self::{
+    block_messages::*,
     block_prob::*,
     config::*,
     errors::*,
     msgpool::{
-        msg_pool::MessagePool,
+        msg_pool::{MessagePool, SenderStats, SequenceGaps},
         provider::{MpoolRpcProvider, Provider},
         *,
     },
@@ -3,6 +3,8 @@
 
 use std::{
     cmp::Ordering,
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
     mem,
     ops::{Index, IndexMut},
 };
@@ -42,6 +44,9 @@ new_key_type! {
 pub(in crate::message_pool) struct Chains {
     pub map: SlotMap<NodeKey, MsgChainNode>,
     pub key_vec: Vec<NodeKey>,
+    /// Salt of the tie breakers of the chains, when the ties between chains of
+    /// different senders are broken
+    tie_break_salt: Option<u64>,
 }
 
 impl Chains {
@@ -49,9 +54,30 @@ impl Chains {
         Self {
             map: SlotMap::with_key(),
             key_vec: vec![],
+            tie_break_salt: None,
         }
     }
 
+    /// Chains whose ties, in gas performance and reward, are broken by a hash
+    /// of their sender salted with `salt`. Nodes using different salts order
+    /// the chains of equal performance differently.
+    pub(in crate::message_pool) fn with_tie_break_salt(salt: u64) -> Self {
+        Self {
+            tie_break_salt: Some(salt),
+            ..Self::new()
+        }
+    }
+
+    /// Tie breaker of the chains of the given sender
+    fn tie_breaker(&self, actor: &Address) -> u64 {
+        self.tie_break_salt.map_or(0, |salt| {
+            let mut hasher = DefaultHasher::new();
+            salt.hash(&mut hasher);
+            actor.hash(&mut hasher);
+            hasher.finish()
+        })
+    }
+
     /// Pushes a `msg` chain node into slot map and places the key in the
     /// `node_vec` passed as parameter.
     pub(in crate::message_pool) fn push_with(
@@ -285,13 +311,20 @@ pub struct MsgChainNode {
     pub merged: bool,
     pub next: Option<NodeKey>,
     pub prev: Option<NodeKey>,
+    /// Orders the chains of equal performance and reward, see
+    /// [`Chains::with_tie_break_salt`]
+    pub tie_breaker: u64,
 }
 
 impl MsgChainNode {
     pub fn compare(&self, other: &Self) -> Ordering {
-        if approx_cmp(self.gas_perf, other.gas_perf) == Ordering::Greater
-            || approx_cmp(self.gas_perf, other.gas_perf) == Ordering::Equal
+        let perf = approx_cmp(self.gas_perf, other.gas_perf);
+        if perf == Ordering::Greater
+            || perf == Ordering::Equal
                 && self.gas_reward.cmp(&other.gas_reward) == Ordering::Greater
+            || perf == Ordering::Equal
+                && self.gas_reward == other.gas_reward
+                && self.tie_breaker > other.tie_breaker
         {
             return Ordering::Greater;
         }
@@ -308,6 +341,10 @@ impl MsgChainNode {
             || (approx_cmp(self.eff_perf, other.eff_perf) == Ordering::Equal
                 && approx_cmp(self.gas_perf, other.gas_perf) == Ordering::Equal
                 && self.gas_reward > other.gas_reward)
+            || (approx_cmp(self.eff_perf, other.eff_perf) == Ordering::Equal
+                && approx_cmp(self.gas_perf, other.gas_perf) == Ordering::Equal
+                && self.gas_reward == other.gas_reward
+                && self.tie_breaker > other.tie_breaker)
         {
             return Ordering::Greater;
         }
@@ -354,6 +391,7 @@ impl std::default::Default for MsgChainNode {
             merged: false,
             next: None,
             prev: None,
+            tie_breaker: 0,
         }
     }
 }
@@ -420,7 +458,7 @@ where
             break;
         }
         gas_limit += m.gas_limit();
-        if gas_limit > fvm_shared3::BLOCK_GAS_LIMIT {
+        if gas_limit > chain_config.block_gas_limit {
             break;
         }
 
@@ -447,6 +485,7 @@ where
 
     let mut cur_chain = MsgChainNode::default();
     let mut node_vec = vec![];
+    let tie_breaker = chains.tie_breaker(actor);
 
     let new_chain = |m: SignedMessage, i: usize| -> MsgChainNode {
         let gl = m.gas_limit();
@@ -462,6 +501,7 @@ where
             merged: false,
             prev: None,
             next: None,
+            tie_breaker,
         }
     };
 
@@ -561,3 +601,36 @@ fn approx_cmp(a: f64, b: f64) -> Ordering {
         a.partial_cmp(&b).unwrap()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ties_are_broken_by_the_salted_sender() {
+        let (a, b) = (Address::new_id(1000), Address::new_id(1001));
+        let node = |chains: &Chains, actor| MsgChainNode {
+            gas_perf: 1.0,
+            tie_breaker: chains.tie_breaker(actor),
+            ..Default::default()
+        };
+
+        // Without salt, the order of equal chains is the one they're sorted in
+        let unsalted = Chains::new();
+        assert_eq!(node(&unsalted, &a).tie_breaker, 0);
+        assert_eq!(node(&unsalted, &b).tie_breaker, 0);
+
+        // With some salt, each node orders them consistently, but the salts
+        // don't all give the same order
+        let orders: Vec<Ordering> = (0..64)
+            .map(|salt| {
+                let chains = Chains::with_tie_break_salt(salt);
+                let (na, nb) = (node(&chains, &a), node(&chains, &b));
+                assert_eq!(na.compare(&nb), nb.compare(&na).reverse());
+                na.compare(&nb)
+            })
+            .collect();
+        assert!(orders.contains(&Ordering::Greater));
+        assert!(orders.contains(&Ordering::Less));
+    }
+}
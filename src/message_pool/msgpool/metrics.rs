@@ -1,8 +1,12 @@
 // Copyright 2019-2023 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
+use crate::shim::address::{Address, Protocol};
 use lazy_static::lazy_static;
-use prometheus::core::{AtomicU64, GenericGauge};
+use prometheus::{
+    core::{AtomicU64, GenericCounter, GenericGauge, GenericGaugeVec, Opts},
+    exponential_buckets, Histogram, HistogramOpts, HistogramVec,
+};
 
 lazy_static! {
     pub static ref MPOOL_MESSAGE_TOTAL: Box<GenericGauge<AtomicU64>> = {
@@ -20,4 +24,101 @@ lazy_static! {
             );
         mpool_message_total
     };
+    pub static ref MPOOL_PENDING_MESSAGES: Box<GenericGaugeVec<AtomicU64>> = {
+        let mpool_pending_messages = Box::new(
+            GenericGaugeVec::<AtomicU64>::new(
+                Opts::new(
+                    "mpool_pending_messages",
+                    "Number of messages in the message pool by sender address class",
+                ),
+                &[labels::SENDER_CLASS],
+            )
+            .expect("Defining the mpool_pending_messages metric must succeed"),
+        );
+        prometheus::default_registry()
+            .register(mpool_pending_messages.clone())
+            .expect(
+                "Registering the mpool_pending_messages metric with the metrics registry must succeed",
+            );
+        mpool_pending_messages
+    };
+    pub static ref MPOOL_SELECTION_TIME: Box<Histogram> = {
+        let mpool_selection_time = Box::new(
+            Histogram::with_opts(HistogramOpts {
+                common_opts: Opts::new(
+                    "mpool_selection_time",
+                    "Duration of the selection of messages for a block",
+                ),
+                buckets: vec![],
+            })
+            .expect("Defining the mpool_selection_time metric must succeed"),
+        );
+        prometheus::default_registry()
+            .register(mpool_selection_time.clone())
+            .expect(
+                "Registering the mpool_selection_time metric with the metrics registry must succeed",
+            );
+        mpool_selection_time
+    };
+    pub static ref MPOOL_SELECTION_GAS_REWARD: Box<HistogramVec> = {
+        let mpool_selection_gas_reward = Box::new(
+            HistogramVec::new(
+                HistogramOpts {
+                    common_opts: Opts::new(
+                        "mpool_selection_gas_reward",
+                        "Gas reward of the messages selected for a block, in nanoFIL, by selection strategy",
+                    ),
+                    buckets: exponential_buckets(1e3, 10.0, 8)
+                        .expect("Defining the mpool_selection_gas_reward buckets must succeed"),
+                },
+                &[labels::STRATEGY],
+            )
+            .expect("Defining the mpool_selection_gas_reward metric must succeed"),
+        );
+        prometheus::default_registry()
+            .register(mpool_selection_gas_reward.clone())
+            .expect(
+                "Registering the mpool_selection_gas_reward metric with the metrics registry must succeed",
+            );
+        mpool_selection_gas_reward
+    };
+    pub static ref MPOOL_REPUBLISHED_TOTAL: Box<GenericCounter<AtomicU64>> = {
+        let mpool_republished_total = Box::new(
+            GenericCounter::<AtomicU64>::new(
+                "mpool_republished_total",
+                "Total number of local messages republished over gossipsub",
+            )
+            .expect("Defining the mpool_republished_total metric must succeed"),
+        );
+        prometheus::default_registry()
+            .register(mpool_republished_total.clone())
+            .expect(
+                "Registering the mpool_republished_total metric with the metrics registry must succeed",
+            );
+        mpool_republished_total
+    };
+}
+
+pub mod labels {
+    pub const SENDER_CLASS: &str = "sender_class";
+    pub const STRATEGY: &str = "strategy";
+}
+
+pub mod values {
+    /// Messages selected by gas performance, for high ticket qualities
+    pub const GREEDY: &str = "greedy";
+    /// Messages selected by gas performance weighted by the probabilities of
+    /// the block to be at each place of the tipset
+    pub const OPTIMAL: &str = "optimal";
+}
+
+/// Class of the sender of a message, as the label of `mpool_pending_messages`
+pub fn sender_class(sender: &Address) -> &'static str {
+    match sender.protocol() {
+        Protocol::ID => "id",
+        Protocol::Secp256k1 => "secp256k1",
+        Protocol::Actor => "actor",
+        Protocol::BLS => "bls",
+        Protocol::Delegated => "delegated",
+    }
 }
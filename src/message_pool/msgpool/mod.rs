@@ -14,7 +14,7 @@ use crate::blocks::Tipset;
 use crate::libp2p::{NetworkMessage, Topic, PUBSUB_MSG_STR};
 use crate::message::{Message as MessageTrait, SignedMessage};
 use crate::networks::ChainConfig;
-use crate::shim::{address::Address, crypto::Signature};
+use crate::shim::{address::Address, crypto::Signature, econ::TokenAmount};
 use ahash::{HashMap, HashMapExt, HashSet, HashSetExt};
 use cid::Cid;
 use fvm_ipld_encoding::Cbor;
@@ -39,6 +39,12 @@ const BASE_FEE_LOWER_BOUND_FACTOR: i64 = 10;
 const REPUB_MSG_LIMIT: usize = 30;
 const MIN_GAS: u64 = 1298450;
 
+/// Returns the lowest gas premium a message can be replaced with, when it has
+/// the given premium.
+pub fn min_rbf_premium(premium: &TokenAmount) -> TokenAmount {
+    premium.clone() + (premium * RBF_NUM).div_floor(RBF_DENOM) + TokenAmount::from_atto(2u8)
+}
+
 /// Get the state of the `base_sequence` for a given address in the current
 /// Tipset
 fn get_state_sequence<T>(api: &T, addr: &Address, cur_ts: &Tipset) -> Result<u64, Error>
@@ -96,6 +102,7 @@ where
             })
             .await
             .map_err(|_| Error::Other("Network receiver dropped".to_string()))?;
+        metrics::MPOOL_REPUBLISHED_TOTAL.inc();
     }
 
     let mut republished_t = HashSet::new();
@@ -148,7 +155,7 @@ where
 
     chains.sort(false);
 
-    let mut gas_limit = fvm_shared3::BLOCK_GAS_LIMIT;
+    let mut gas_limit = chain_config.block_gas_limit;
     let mut i = 0;
     'l: while i < chains.len() {
         let chain = &mut chains[i];
@@ -218,6 +225,8 @@ pub async fn head_change<T>(
 where
     T: Provider + 'static,
 {
+    // The cached states of the actors are those of the previous heads
+    api.clear_actor_cache();
     let mut repub = false;
     let mut rmsgs: HashMap<Address, HashMap<u64, SignedMessage>> = HashMap::new();
     for ts in revert {
@@ -326,8 +335,9 @@ pub mod tests {
     use std::{borrow::BorrowMut, time::Duration};
 
     use crate::blocks::Tipset;
+    use crate::db::MemoryDB;
     use crate::key_management::{KeyStore, KeyStoreConfig, Wallet};
-    use crate::message::SignedMessage;
+    use crate::message::{signature_cache, SignedMessage};
     use crate::networks::ChainConfig;
     use crate::shim::{
         address::Address,
@@ -335,7 +345,7 @@ pub mod tests {
         econ::TokenAmount,
         message::{Message, Message_v3},
     };
-    use num_traits::Zero;
+    use num_traits::{ToPrimitive, Zero};
     use test_provider::*;
     use tokio::task::JoinSet;
 
@@ -369,10 +379,9 @@ pub mod tests {
     }
 
     // Create a fake signed message with a dummy signature. While the signature is
-    // not valid, it has been added to the validation cache and the message will
+    // not valid, it has been added to the signature cache and the message will
     // appear authentic.
     pub fn create_fake_smsg(
-        pool: &MessagePool<TestApi>,
         to: &Address,
         from: &Address,
         sequence: u64,
@@ -391,8 +400,7 @@ pub mod tests {
         .into();
         let sig = Signature::new_secp256k1(vec![]);
         let signed = SignedMessage::new_unchecked(umsg, sig);
-        let cid = signed.cid().unwrap();
-        pool.sig_val_cache.lock().put(cid, ());
+        signature_cache::insert(&signed);
         signed
     }
 
@@ -612,6 +620,212 @@ pub mod tests {
         assert_eq!(cur_ts.as_ref(), &tipset);
     }
 
+    #[tokio::test]
+    async fn test_local_messages_are_restored() {
+        let keystore = KeyStore::new(KeyStoreConfig::Memory).unwrap();
+        let mut wallet = Wallet::new(keystore);
+        let sender = wallet.generate_addr(SignatureType::Secp256k1).unwrap();
+        let target = wallet.generate_addr(SignatureType::Secp256k1).unwrap();
+        let db = MemoryDB::default();
+
+        let (tx, _rx) = flume::bounded(50);
+        let mut services = JoinSet::new();
+        let new_mpool = |services: &mut JoinSet<_>| {
+            let tma = TestApi::default();
+            tma.set_state_sequence(&sender, 0);
+            MessagePool::new(
+                tma,
+                "mptest".to_string(),
+                tx.clone(),
+                Default::default(),
+                Arc::default(),
+                services,
+            )
+            .unwrap()
+        };
+
+        let mpool = new_mpool(&mut services);
+        for i in 0..2 {
+            let msg = create_smsg(&target, &sender, wallet.borrow_mut(), i, 1000000, 1);
+            mpool.push(msg).await.unwrap();
+        }
+        mpool.save_local(&db).unwrap();
+
+        let restarted = new_mpool(&mut services);
+        assert_eq!(restarted.get_sequence(&sender).unwrap(), 0);
+        restarted.restore_local(&db).unwrap();
+        assert_eq!(restarted.get_sequence(&sender).unwrap(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_replace_by_fee_and_stats() {
+        let keystore = KeyStore::new(KeyStoreConfig::Memory).unwrap();
+        let mut wallet = Wallet::new(keystore);
+        let sender = wallet.generate_addr(SignatureType::Secp256k1).unwrap();
+        let target = wallet.generate_addr(SignatureType::Secp256k1).unwrap();
+        let tma = TestApi::default();
+        tma.set_state_sequence(&sender, 0);
+        let (tx, _rx) = flume::bounded(50);
+        let mut services = JoinSet::new();
+        let mpool = MessagePool::new(
+            tma,
+            "mptest".to_string(),
+            tx,
+            Default::default(),
+            Arc::default(),
+            &mut services,
+        )
+        .unwrap();
+
+        for i in [0, 1, 3] {
+            let msg = create_smsg(&target, &sender, wallet.borrow_mut(), i, 1000000, 1);
+            mpool.push(msg).await.unwrap();
+        }
+        let stats = mpool.stats().unwrap();
+        assert_eq!(stats.len(), 1);
+        assert_eq!(
+            (
+                stats[0].state_sequence,
+                stats[0].past,
+                stats[0].current,
+                stats[0].future
+            ),
+            (0, 0, 2, 1)
+        );
+
+        let min_premium = min_rbf_premium(&TokenAmount::from_atto(1));
+        let premium = min_premium.atto().to_u64().unwrap();
+        let too_low = create_smsg(
+            &target,
+            &sender,
+            wallet.borrow_mut(),
+            0,
+            1000000,
+            premium - 1,
+        );
+        assert_eq!(mpool.push(too_low).await, Err(Error::GasPriceTooLow));
+        let replacement = create_smsg(&target, &sender, wallet.borrow_mut(), 0, 1000000, premium);
+        mpool.push(replacement).await.unwrap();
+
+        mpool.clear(true);
+        assert!(mpool.stats().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_sequence_gaps() {
+        let keystore = KeyStore::new(KeyStoreConfig::Memory).unwrap();
+        let mut wallet = Wallet::new(keystore);
+        let sender = wallet.generate_addr(SignatureType::Secp256k1).unwrap();
+        let target = wallet.generate_addr(SignatureType::Secp256k1).unwrap();
+        let tma = TestApi::default();
+        tma.set_state_sequence(&sender, 0);
+        let (tx, _rx) = flume::bounded(50);
+        let mut services = JoinSet::new();
+        let mpool = MessagePool::new(
+            tma,
+            "mptest".to_string(),
+            tx,
+            Default::default(),
+            Arc::default(),
+            &mut services,
+        )
+        .unwrap();
+
+        let gaps = mpool.sequence_gaps(&sender).unwrap();
+        assert!(gaps.gaps.is_empty() && gaps.stranded.is_empty());
+
+        for i in [0, 1, 3, 5] {
+            let msg = create_smsg(&target, &sender, wallet.borrow_mut(), i, 1000000, 1);
+            mpool.push(msg).await.unwrap();
+        }
+        let gaps = mpool.sequence_gaps(&sender).unwrap();
+        assert_eq!(gaps.state_sequence, 0);
+        assert_eq!(gaps.gaps, vec![2, 4]);
+        assert_eq!(gaps.stranded, vec![3, 5]);
+    }
+
+    #[tokio::test]
+    async fn test_push_batch() {
+        let keystore = KeyStore::new(KeyStoreConfig::Memory).unwrap();
+        let mut wallet = Wallet::new(keystore);
+        let sender = wallet.generate_addr(SignatureType::Secp256k1).unwrap();
+        let target = wallet.generate_addr(SignatureType::Secp256k1).unwrap();
+        let tma = TestApi::default();
+        tma.set_state_sequence(&sender, 0);
+        let (tx, _rx) = flume::bounded(50);
+        let mut services = JoinSet::new();
+        let mpool = MessagePool::new(
+            tma,
+            "mptest".to_string(),
+            tx,
+            Default::default(),
+            Arc::default(),
+            &mut services,
+        )
+        .unwrap();
+
+        // The second message takes the sequence of the first one, none is
+        // added
+        let batch: Vec<_> = [0, 0, 1]
+            .into_iter()
+            .map(|i| create_smsg(&target, &sender, wallet.borrow_mut(), i, 1000000, 1))
+            .collect();
+        let errors = mpool.push_batch(batch).await.unwrap().unwrap_err();
+        assert_eq!(errors, vec![None, Some(Error::DuplicateSequence), None]);
+        assert_eq!(mpool.get_sequence(&sender).unwrap(), 0);
+
+        let batch: Vec<_> = (0..3)
+            .map(|i| create_smsg(&target, &sender, wallet.borrow_mut(), i, 1000000, 1))
+            .collect();
+        let cids = mpool.push_batch(batch).await.unwrap().unwrap();
+        assert_eq!(cids.len(), 3);
+        assert_eq!(mpool.get_sequence(&sender).unwrap(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_push_batch_keeps_replaced_messages() {
+        let keystore = KeyStore::new(KeyStoreConfig::Memory).unwrap();
+        let mut wallet = Wallet::new(keystore);
+        let sender = wallet.generate_addr(SignatureType::Secp256k1).unwrap();
+        let target = wallet.generate_addr(SignatureType::Secp256k1).unwrap();
+        let tma = TestApi::default();
+        tma.set_state_sequence(&sender, 0);
+        let (tx, _rx) = flume::bounded(50);
+        let mut services = JoinSet::new();
+        let mpool = MessagePool::new(
+            tma,
+            "mptest".to_string(),
+            tx,
+            Default::default(),
+            Arc::default(),
+            &mut services,
+        )
+        .unwrap();
+
+        let originals: Vec<_> = (0..2)
+            .map(|i| create_smsg(&target, &sender, wallet.borrow_mut(), i, 1000000, 1))
+            .collect();
+        for msg in &originals {
+            mpool.push(msg.clone()).await.unwrap();
+        }
+        let mut added = mpool.subscribe_added();
+
+        // The first message replaces a pending one, the second one doesn't pay
+        // enough to replace the other
+        let premium = min_rbf_premium(&TokenAmount::from_atto(1))
+            .atto()
+            .to_u64()
+            .unwrap();
+        let batch = vec![
+            create_smsg(&target, &sender, wallet.borrow_mut(), 0, 1000000, premium),
+            create_smsg(&target, &sender, wallet.borrow_mut(), 1, 1000000, 2),
+        ];
+        let errors = mpool.push_batch(batch).await.unwrap().unwrap_err();
+        assert_eq!(errors, vec![None, Some(Error::GasPriceTooLow)]);
+        assert_eq!(mpool.pending_for(&sender).unwrap(), originals);
+        assert!(added.try_recv().is_err());
+    }
+
     #[tokio::test]
     async fn test_msg_chains() {
         let keystore = KeyStore::new(KeyStoreConfig::Memory).unwrap();
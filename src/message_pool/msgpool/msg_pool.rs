@@ -6,13 +6,15 @@
 // inclusion in the chain. Messages are added either directly for locally
 // published messages or through pubsub propagation.
 
-use std::{num::NonZeroUsize, sync::Arc, time::Duration};
+use std::{collections::hash_map::Entry, num::NonZeroUsize, sync::Arc, time::Duration};
 
 use crate::blocks::{BlockHeader, Tipset, TipsetKeys};
 use crate::chain::{HeadChange, MINIMUM_BASE_FEE};
 use crate::db::Store;
 use crate::libp2p::{NetworkMessage, Topic, PUBSUB_MSG_STR};
-use crate::message::{valid_for_block_inclusion, ChainMessage, Message, SignedMessage};
+use crate::message::{
+    signature_cache, valid_for_block_inclusion, ChainMessage, Message, SignedMessage,
+};
 use crate::networks::{ChainConfig, NEWEST_NETWORK_VERSION};
 use crate::shim::{
     address::Address,
@@ -31,6 +33,7 @@ use lru::LruCache;
 use nonzero_ext::nonzero;
 use num::BigInt;
 use parking_lot::{Mutex, RwLock as SyncRwLock};
+use serde::{Deserialize, Serialize};
 use tokio::{sync::broadcast::error::RecvError, task::JoinSet, time::interval};
 
 use crate::message_pool::{
@@ -38,8 +41,8 @@ use crate::message_pool::{
     errors::Error,
     head_change, metrics,
     msgpool::{
-        recover_sig, republish_pending_messages, select_messages_for_block,
-        BASE_FEE_LOWER_BOUND_FACTOR_CONSERVATIVE, RBF_DENOM, RBF_NUM,
+        min_rbf_premium, recover_sig, republish_pending_messages, select_messages_for_block,
+        BASE_FEE_LOWER_BOUND_FACTOR_CONSERVATIVE,
     },
     provider::Provider,
     utils::get_base_fee_lower_bound,
@@ -47,7 +50,8 @@ use crate::message_pool::{
 
 // LruCache sizes have been taken from the lotus implementation
 const BLS_SIG_CACHE_SIZE: NonZeroUsize = nonzero!(40000usize);
-const SIG_VAL_CACHE_SIZE: NonZeroUsize = nonzero!(32000usize);
+/// Store key of the local messages saved on shutdown
+const LOCAL_MESSAGES_KEY: &[u8] = b"/mpool/local";
 
 /// Simple structure that contains a hash-map of messages where k: a message
 /// from address, v: a message which corresponds to that address.
@@ -70,32 +74,47 @@ impl MsgSet {
     /// Add a signed message to the `MsgSet`. Increase `next_sequence` if the
     /// message has a sequence greater than any existing message sequence.
     pub fn add(&mut self, m: SignedMessage) -> Result<(), Error> {
-        if self.msgs.is_empty() || m.sequence() >= self.next_sequence {
-            self.next_sequence = m.sequence() + 1;
-        }
+        self.check_add(&m)?;
+        self.insert(m);
+        Ok(())
+    }
+
+    /// Checks that a signed message can be added to the `MsgSet`: it must not
+    /// be there yet, and it must pay enough more than the message it replaces,
+    /// if any.
+    fn check_add(&self, m: &SignedMessage) -> Result<(), Error> {
         if let Some(exms) = self.msgs.get(&m.sequence()) {
             if m.cid()? != exms.cid()? {
                 let premium = TokenAmount::from(&exms.message().gas_premium);
-                let min_price = premium.clone()
-                    + ((premium * RBF_NUM).div_floor(RBF_DENOM))
-                    + TokenAmount::from_atto(1u8);
-                if m.message().gas_premium <= min_price.into() {
+                if TokenAmount::from(&m.message().gas_premium) < min_rbf_premium(&premium) {
                     return Err(Error::GasPriceTooLow);
                 }
             } else {
                 return Err(Error::DuplicateSequence);
             }
         }
+        Ok(())
+    }
+
+    /// Inserts a checked signed message, replacing the message with the same
+    /// sequence.
+    fn insert(&mut self, m: SignedMessage) {
+        if self.msgs.is_empty() || m.sequence() >= self.next_sequence {
+            self.next_sequence = m.sequence() + 1;
+        }
+        let sender_class = metrics::sender_class(&m.from());
         if self.msgs.insert(m.sequence(), m).is_none() {
             metrics::MPOOL_MESSAGE_TOTAL.inc();
+            metrics::MPOOL_PENDING_MESSAGES
+                .with_label_values(&[sender_class])
+                .inc();
         }
-        Ok(())
     }
 
     /// Removes message with the given sequence. If applied, update the set's
     /// next sequence.
     pub fn rm(&mut self, sequence: u64, applied: bool) {
-        if self.msgs.remove(&sequence).is_none() {
+        let Some(removed) = self.msgs.remove(&sequence) else {
             if applied && sequence >= self.next_sequence {
                 self.next_sequence = sequence + 1;
                 while self.msgs.get(&self.next_sequence).is_some() {
@@ -103,8 +122,11 @@ impl MsgSet {
                 }
             }
             return;
-        }
+        };
         metrics::MPOOL_MESSAGE_TOTAL.dec();
+        metrics::MPOOL_PENDING_MESSAGES
+            .with_label_values(&[metrics::sender_class(&removed.from())])
+            .dec();
 
         // adjust next sequence
         if applied {
@@ -123,6 +145,63 @@ impl MsgSet {
     }
 }
 
+/// Pending messages of a sender, counted by how their sequences compare to the
+/// one of the sender in the current state.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct SenderStats {
+    #[serde(with = "crate::json::address::json")]
+    pub address: Address,
+    /// Sequence of the sender in the state of the current tipset
+    pub state_sequence: u64,
+    /// Messages with an already used sequence, left to be pruned
+    pub past: u64,
+    /// Messages following the state sequence without gaps, which can be
+    /// included in the next blocks
+    pub current: u64,
+    /// Messages after a gap in the sequences
+    pub future: u64,
+    /// Messages with a fee cap below the current base fee
+    pub below_base_fee: u64,
+}
+
+/// Gaps in the sequences of the pending messages of a sender, which keep the
+/// messages after them from being included.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct SequenceGaps {
+    #[serde(with = "crate::json::address::json")]
+    pub address: Address,
+    /// Sequence of the sender in the state of the current tipset
+    pub state_sequence: u64,
+    /// Sequences without a pending message, from the state sequence to the
+    /// highest pending one
+    pub gaps: Vec<u64>,
+    /// Sequences of the pending messages after the first gap
+    pub stranded: Vec<u64>,
+}
+
+/// Returns the missing sequences from `state_sequence` to the highest of
+/// `sequences`, and the sequences after the first missing one.
+fn find_sequence_gaps(state_sequence: u64, sequences: &[u64]) -> (Vec<u64>, Vec<u64>) {
+    let pending: HashSet<u64> = sequences.iter().copied().collect();
+    let Some(highest) = sequences.iter().copied().max() else {
+        return (vec![], vec![]);
+    };
+    let gaps: Vec<u64> = (state_sequence..highest)
+        .filter(|sequence| !pending.contains(sequence))
+        .collect();
+    let mut stranded: Vec<u64> = match gaps.first() {
+        Some(first_gap) => pending
+            .into_iter()
+            .filter(|sequence| sequence > first_gap)
+            .collect(),
+        None => vec![],
+    };
+    stranded.sort_unstable();
+    (gaps, stranded)
+}
+
 /// This contains all necessary information needed for the message pool.
 /// Keeps track of messages to apply, as well as context needed for verifying
 /// transactions.
@@ -146,8 +225,6 @@ pub struct MessagePool<T> {
     pub network_sender: flume::Sender<NetworkMessage>,
     /// A cache for BLS signature keyed by Cid
     pub bls_sig_cache: Arc<Mutex<LruCache<Cid, Signature>>>,
-    /// A cache for BLS signature keyed by Cid
-    pub sig_val_cache: Arc<Mutex<LruCache<Cid, ()>>>,
     /// A set of republished messages identified by their Cid
     pub republished: Arc<SyncRwLock<HashSet<Cid>>>,
     /// Acts as a signal to republish messages from the republished set of
@@ -156,9 +233,13 @@ pub struct MessagePool<T> {
     // TODO look into adding a cap to `local_msgs`
     local_msgs: Arc<SyncRwLock<HashSet<SignedMessage>>>,
     /// Configurable parameters of the message pool
-    pub config: MpoolConfig,
+    pub config: SyncRwLock<MpoolConfig>,
     /// Chain configuration
     pub chain_config: Arc<ChainConfig>,
+    /// Salt of the tie breakers of the message chains, drawn on start
+    pub(in crate::message_pool) tie_break_salt: u64,
+    /// Publishes the messages added to the pool
+    added_tx: tokio::sync::broadcast::Sender<SignedMessage>,
 }
 
 impl<T> MessagePool<T>
@@ -181,7 +262,6 @@ where
         let pending = Arc::new(SyncRwLock::new(HashMap::new()));
         let tipset = Arc::new(Mutex::new(api.get_heaviest_tipset()));
         let bls_sig_cache = Arc::new(Mutex::new(LruCache::new(BLS_SIG_CACHE_SIZE)));
-        let sig_val_cache = Arc::new(Mutex::new(LruCache::new(SIG_VAL_CACHE_SIZE)));
         let local_msgs = Arc::new(SyncRwLock::new(HashSet::new()));
         let republished = Arc::new(SyncRwLock::new(HashSet::new()));
         let block_delay = chain_config.block_delay_secs;
@@ -196,13 +276,14 @@ where
             max_tx_pool_size: 5000,
             network_name,
             bls_sig_cache,
-            sig_val_cache,
             local_msgs,
             republished,
-            config,
+            config: SyncRwLock::new(config),
             network_sender,
             repub_trigger,
             chain_config: Arc::clone(&chain_config),
+            tie_break_salt: rand::random(),
+            added_tx: tokio::sync::broadcast::channel(1024).0,
         };
 
         mp.load_local()?;
@@ -216,6 +297,7 @@ where
 
         let cur_tipset = mp.cur_tipset.clone();
         let repub_trigger = Arc::new(mp.repub_trigger.clone());
+        let headers_only = mp.config.read().headers_only;
 
         // Reacts to new HeadChanges
         services.spawn(async move {
@@ -235,7 +317,7 @@ where
                                 vec![tipset.as_ref().clone()],
                             ),
                         };
-                        head_change(
+                        let result = head_change(
                             api.as_ref(),
                             bls_sig_cache.as_ref(),
                             repub_trigger.clone(),
@@ -245,8 +327,13 @@ where
                             rev,
                             app,
                         )
-                        .await
-                        .context("Error changing head")?;
+                        .await;
+                        match result {
+                            // The messages of the tipsets are missing on the
+                            // nodes syncing the headers only
+                            Err(e) if headers_only => warn!("Error changing head: {e}"),
+                            result => result.context("Error changing head")?,
+                        }
                     }
                     Err(RecvError::Lagged(e)) => {
                         warn!("Head change subscriber lagged: skipping {} events", e);
@@ -308,25 +395,123 @@ where
         let cid = msg.cid().map_err(|err| Error::Other(err.to_string()))?;
         let cur_ts = self.cur_tipset.lock().clone();
         let publish = self.add_tipset(msg.clone(), &cur_ts, true)?;
+        self.add_local_and_publish(msg, publish).await?;
+        Ok(cid)
+    }
+
+    /// Adds a message pushed to the pool to the local ones, and publishes it
+    /// if asked to.
+    async fn add_local_and_publish(&self, msg: SignedMessage, publish: bool) -> Result<(), Error> {
         let msg_ser = msg.marshal_cbor()?;
         self.add_local(msg)?;
         if publish {
-            self.network_sender
-                .send_async(NetworkMessage::PubsubMessage {
-                    topic: Topic::new(format!("{}/{}", PUBSUB_MSG_STR, self.network_name)),
-                    message: msg_ser,
+            self.publish(msg_ser).await?;
+        }
+        Ok(())
+    }
+
+    /// Publishes a serialized signed message on the messages topic.
+    async fn publish(&self, msg_ser: Vec<u8>) -> Result<(), Error> {
+        self.network_sender
+            .send_async(NetworkMessage::PubsubMessage {
+                topic: Topic::new(format!("{}/{}", PUBSUB_MSG_STR, self.network_name)),
+                message: msg_ser,
+            })
+            .await
+            .map_err(|_| Error::Other("Network receiver dropped".to_string()))
+    }
+
+    /// Pushes a batch of signed messages to the `MessagePool`, as a whole: the
+    /// messages are all checked, against the state and then against the
+    /// pending messages under the same lock they are added with, so either
+    /// all of them are added or none is. If the batch is rejected, the inner
+    /// error holds the error of each message, if it has one.
+    pub async fn push_batch(
+        &self,
+        msgs: Vec<SignedMessage>,
+    ) -> Result<Result<Vec<Cid>, Vec<Option<Error>>>, Error> {
+        let cur_ts = self.cur_tipset.lock().clone();
+        let mut sequences = HashSet::new();
+        let mut publish = Vec::with_capacity(msgs.len());
+        let errors: Vec<Option<Error>> = msgs
+            .iter()
+            .map(|msg| {
+                if !sequences.insert((msg.from(), msg.sequence())) {
+                    return Some(Error::DuplicateSequence);
+                }
+                self.check_message(msg)
+                    .and_then(|()| self.verify_add(msg, &cur_ts, true))
+                    .and_then(|publish_msg| {
+                        store_message(self.api.as_ref(), self.bls_sig_cache.as_ref(), msg)?;
+                        publish.push(publish_msg);
+                        Ok(())
+                    })
+                    .err()
+            })
+            .collect();
+        if errors.iter().any(Option::is_some) {
+            return Ok(Err(errors));
+        }
+
+        // Everything that can fail once the batch is added is done before
+        let mut state_sequences = HashMap::new();
+        let mut cids = Vec::with_capacity(msgs.len());
+        let mut msgs_ser = Vec::with_capacity(msgs.len());
+        for msg in &msgs {
+            if let Entry::Vacant(entry) = state_sequences.entry(msg.from()) {
+                entry.insert(self.get_state_sequence(&msg.from(), &cur_ts)?);
+            }
+            cids.push(msg.cid().map_err(|err| Error::Other(err.to_string()))?);
+            msgs_ser.push(msg.marshal_cbor()?);
+        }
+
+        {
+            let mut pending = self.pending.write();
+            let errors: Vec<Option<Error>> = msgs
+                .iter()
+                .map(|msg| {
+                    let mset = pending.get(&msg.from())?;
+                    mset.check_add(msg).err()
                 })
-                .await
-                .map_err(|_| Error::Other("Network receiver dropped".to_string()))?;
+                .collect();
+            if errors.iter().any(Option::is_some) {
+                return Ok(Err(errors));
+            }
+            for msg in &msgs {
+                pending
+                    .entry(msg.from())
+                    .or_insert_with(|| MsgSet::new(state_sequences[&msg.from()]))
+                    .insert(msg.clone());
+            }
         }
-        Ok(cid)
+
+        for ((msg, publish), msg_ser) in msgs.into_iter().zip(publish).zip(msgs_ser) {
+            // The subscribers only learn of the batch once it is added
+            if self.added_tx.receiver_count() > 0 {
+                let _ = self.added_tx.send(msg.clone());
+            }
+            self.add_local(msg)?;
+            // The local messages are republished anyway, the batch is not
+            // failed once added
+            if publish {
+                if let Err(e) = self.publish(msg_ser).await {
+                    warn!("Failed to publish a message of a batch: {e}");
+                }
+            }
+        }
+        Ok(Ok(cids))
     }
 
     fn check_message(&self, msg: &SignedMessage) -> Result<(), Error> {
         if msg.marshal_cbor()?.len() > 32 * 1024 {
             return Err(Error::MessageTooBig);
         }
-        valid_for_block_inclusion(msg.message(), Gas::new(0), NEWEST_NETWORK_VERSION)?;
+        valid_for_block_inclusion(
+            msg.message(),
+            Gas::new(0),
+            NEWEST_NETWORK_VERSION,
+            self.chain_config.block_gas_limit,
+        )?;
         if msg.value() > TokenAmount::from(&*fvm_shared::TOTAL_FILECOIN) {
             return Err(Error::MessageValueTooHigh);
         }
@@ -347,27 +532,24 @@ where
         Ok(())
     }
 
-    /// Verify the message signature. first check if it has already been
-    /// verified and put into cache. If it has not, then manually verify it
-    /// then put it into cache for future use.
+    /// Verify the message signature, unless it has already been verified by
+    /// another subsystem and put into the shared signature cache.
     fn verify_msg_sig(&self, msg: &SignedMessage) -> Result<(), Error> {
-        let cid = msg.cid()?;
-
-        if let Some(()) = self.sig_val_cache.lock().get(&cid) {
-            return Ok(());
-        }
-
-        msg.verify().map_err(Error::Other)?;
-
-        self.sig_val_cache.lock().put(cid, ());
-
-        Ok(())
+        signature_cache::verify_signed_message(msg).map_err(Error::Other)
     }
 
     /// Verify the `state_sequence` and balance for the sender of the message
     /// given then call `add_locked` to finish adding the `signed_message`
     /// to pending.
     fn add_tipset(&self, msg: SignedMessage, cur_ts: &Tipset, local: bool) -> Result<bool, Error> {
+        let publish = self.verify_add(&msg, cur_ts, local)?;
+        self.add_helper(msg)?;
+        Ok(publish)
+    }
+
+    /// Checks the sequence, the sender and the balance of a message against
+    /// the state of `cur_ts`, and returns whether it is to be published.
+    fn verify_add(&self, msg: &SignedMessage, cur_ts: &Tipset, local: bool) -> Result<bool, Error> {
         let sequence = self.get_state_sequence(&msg.from(), cur_ts)?;
 
         if sequence > msg.message().sequence {
@@ -384,7 +566,7 @@ where
             ));
         }
 
-        let publish = verify_msg_before_add(&msg, cur_ts, local, &self.chain_config)?;
+        let publish = verify_msg_before_add(msg, cur_ts, local, &self.chain_config)?;
 
         let balance = self.get_state_balance(&msg.from(), cur_ts)?;
 
@@ -392,7 +574,6 @@ where
         if balance < msg_balance {
             return Err(Error::NotEnoughFunds);
         }
-        self.add_helper(msg)?;
         Ok(publish)
     }
 
@@ -403,13 +584,25 @@ where
     fn add_helper(&self, msg: SignedMessage) -> Result<(), Error> {
         let from = msg.from();
         let cur_ts = self.cur_tipset.lock().clone();
+        let added = (self.added_tx.receiver_count() > 0).then(|| msg.clone());
         add_helper(
             self.api.as_ref(),
             self.bls_sig_cache.as_ref(),
             self.pending.as_ref(),
             msg,
             self.get_state_sequence(&from, &cur_ts)?,
-        )
+        )?;
+        if let Some(msg) = added {
+            // There may be no subscriber left, which is fine
+            let _ = self.added_tx.send(msg);
+        }
+        Ok(())
+    }
+
+    /// Subscribes to the messages added to the pool, locally or from the
+    /// network.
+    pub fn subscribe_added(&self) -> tokio::sync::broadcast::Receiver<SignedMessage> {
+        self.added_tx.subscribe()
     }
 
     /// Get the sequence for a given address, return Error if there is a failure
@@ -538,34 +731,133 @@ where
     /// If `local = true`, the local messages will be removed as well as pending
     /// messages. If `local = false`, pending messages will be removed while
     /// retaining local messages.
-    pub fn clear(&mut self, local: bool) {
+    pub fn clear(&self, local: bool) {
+        let local_addrs = self.local_addrs.read();
+        let mut pending = self.pending.write();
         if local {
-            for a in self.local_addrs.read().iter() {
-                let pending = self.pending.read().get(a).cloned();
-                if let Some(mset) = pending {
-                    for m in mset.msgs.values() {
-                        if !self.local_msgs.write().remove(m) {
-                            warn!("error deleting local message");
-                        }
+            let mut local_msgs = self.local_msgs.write();
+            for mset in local_addrs.iter().filter_map(|a| pending.get(a)) {
+                for m in mset.msgs.values() {
+                    if !local_msgs.remove(m) {
+                        warn!("error deleting local message");
                     }
                 }
             }
-            self.pending.write().clear();
             self.republished.write().clear();
-        } else {
-            let local_addrs = self.local_addrs.read();
-            let mut pending = self.pending.write();
-            pending.retain(|a, _| local_addrs.contains(a));
         }
+        pending.retain(|a, mset| {
+            let keep = !local && local_addrs.contains(a);
+            if !keep {
+                for m in mset.msgs.values() {
+                    metrics::MPOOL_MESSAGE_TOTAL.dec();
+                    metrics::MPOOL_PENDING_MESSAGES
+                        .with_label_values(&[metrics::sender_class(&m.from())])
+                        .dec();
+                }
+            }
+            keep
+        });
     }
 
-    pub fn get_config(&self) -> &MpoolConfig {
-        &self.config
+    /// Returns the statistics of the pending messages of each sender, against
+    /// its sequence in the state of the current tipset.
+    pub fn stats(&self) -> Result<Vec<SenderStats>, Error> {
+        let cur_ts = self.cur_tipset.lock().clone();
+        let base_fee = cur_ts.blocks()[0].parent_base_fee().clone();
+        let pending = self.pending.read().clone();
+        let mut stats = Vec::with_capacity(pending.len());
+        for (address, mset) in pending {
+            let state_sequence = self.get_state_sequence(&address, &cur_ts)?;
+            let mut sequences: Vec<u64> = mset.msgs.keys().copied().collect();
+            sequences.sort_unstable();
+            let (mut past, mut current, mut future) = (0, 0, 0);
+            let mut next_sequence = state_sequence;
+            for sequence in sequences {
+                if sequence < state_sequence {
+                    past += 1;
+                } else if sequence == next_sequence {
+                    current += 1;
+                    next_sequence += 1;
+                } else {
+                    future += 1;
+                }
+            }
+            let below_base_fee = mset
+                .msgs
+                .values()
+                .filter(|m| TokenAmount::from(&m.message().gas_fee_cap) < base_fee)
+                .count() as u64;
+            stats.push(SenderStats {
+                address,
+                state_sequence,
+                past,
+                current,
+                future,
+                below_base_fee,
+            });
+        }
+        stats.sort_by_key(|s| s.address);
+        Ok(stats)
     }
-    pub fn set_config<DB: Store>(&mut self, db: &DB, cfg: MpoolConfig) -> Result<(), Error> {
+
+    /// Finds the gaps in the sequences of the pending messages of a sender.
+    pub fn sequence_gaps(&self, address: &Address) -> Result<SequenceGaps, Error> {
+        let cur_ts = self.cur_tipset.lock().clone();
+        let state_sequence = self.get_state_sequence(address, &cur_ts)?;
+        let sequences: Vec<u64> = self
+            .pending
+            .read()
+            .get(address)
+            .map(|mset| mset.msgs.keys().copied().collect())
+            .unwrap_or_default();
+        let (gaps, stranded) = find_sequence_gaps(state_sequence, &sequences);
+        Ok(SequenceGaps {
+            address: *address,
+            state_sequence,
+            gaps,
+            stranded,
+        })
+    }
+
+    /// Saves the local messages to the store, so that they are added back to
+    /// the pool by [`MessagePool::restore_local`] after a restart.
+    pub fn save_local<DB: Store>(&self, db: &DB) -> Result<(), Error> {
+        let local_msgs: Vec<SignedMessage> = self.local_msgs.read().iter().cloned().collect();
+        let bytes =
+            fvm_ipld_encoding::to_vec(&local_msgs).map_err(|e| Error::Other(e.to_string()))?;
+        db.write(LOCAL_MESSAGES_KEY, bytes)
+            .map_err(|e| Error::Other(e.to_string()))
+    }
+
+    /// Adds the local messages saved by [`MessagePool::save_local`] back to
+    /// the pool. The ones that are no longer valid, e.g. because they have
+    /// been included in the meantime, are dropped.
+    pub fn restore_local<DB: Store>(&self, db: &DB) -> Result<(), Error> {
+        let Some(bytes) = db
+            .read(LOCAL_MESSAGES_KEY)
+            .map_err(|e| Error::Other(e.to_string()))?
+        else {
+            return Ok(());
+        };
+        let mut local_msgs: Vec<SignedMessage> =
+            fvm_ipld_encoding::from_slice(&bytes).map_err(|e| Error::Other(e.to_string()))?;
+        local_msgs.sort_by_key(|msg| msg.sequence());
+        for msg in local_msgs {
+            match self.add(msg.clone()) {
+                Ok(()) => self.add_local(msg)?,
+                Err(e) => warn!("Dropping saved local message: {e}"),
+            }
+        }
+        Ok(())
+    }
+
+    pub fn get_config(&self) -> MpoolConfig {
+        self.config.read().clone()
+    }
+    pub fn set_config<DB: Store>(&self, db: &DB, cfg: MpoolConfig) -> Result<(), Error> {
         cfg.save_config(db)
             .map_err(|e| Error::Other(e.to_string()))?;
-        self.config = cfg;
+        *self.config.write() = cfg;
         Ok(())
     }
 
@@ -607,20 +899,7 @@ pub(in crate::message_pool) fn add_helper<T>(
 where
     T: Provider,
 {
-    if msg.signature().signature_type() == SignatureType::BLS {
-        bls_sig_cache
-            .lock()
-            .put(msg.cid()?, msg.signature().clone());
-    }
-
-    if msg.message().gas_limit > 100_000_000 {
-        return Err(Error::Other(
-            "given message has too high of a gas limit".to_string(),
-        ));
-    }
-
-    api.put_message(&ChainMessage::Signed(msg.clone()))?;
-    api.put_message(&ChainMessage::Unsigned(msg.message().clone()))?;
+    store_message(api, bls_sig_cache, &msg)?;
 
     let mut pending = pending.write();
     let msett = pending.get_mut(&msg.from());
@@ -646,7 +925,12 @@ fn verify_msg_before_add(
     let epoch = cur_ts.epoch();
     let min_gas = price_list_by_network_version(chain_config.network_version(epoch))
         .on_chain_message(m.marshal_cbor()?.len());
-    valid_for_block_inclusion(m.message(), min_gas.total(), NEWEST_NETWORK_VERSION)?;
+    valid_for_block_inclusion(
+        m.message(),
+        min_gas.total(),
+        NEWEST_NETWORK_VERSION,
+        chain_config.block_gas_limit,
+    )?;
     if !cur_ts.blocks().is_empty() {
         let base_fee = cur_ts.blocks()[0].parent_base_fee();
         let base_fee_lower_bound =
@@ -663,6 +947,33 @@ fn verify_msg_before_add(
     Ok(local)
 }
 
+/// Caches the signature of a BLS message, and stores the message unless its
+/// gas limit is too high.
+fn store_message<T>(
+    api: &T,
+    bls_sig_cache: &Mutex<LruCache<Cid, Signature>>,
+    msg: &SignedMessage,
+) -> Result<(), Error>
+where
+    T: Provider,
+{
+    if msg.signature().signature_type() == SignatureType::BLS {
+        bls_sig_cache
+            .lock()
+            .put(msg.cid()?, msg.signature().clone());
+    }
+
+    if msg.message().gas_limit > 100_000_000 {
+        return Err(Error::Other(
+            "given message has too high of a gas limit".to_string(),
+        ));
+    }
+
+    api.put_message(&ChainMessage::Signed(msg.clone()))?;
+    api.put_message(&ChainMessage::Unsigned(msg.message().clone()))?;
+    Ok(())
+}
+
 /// Remove a message from pending given the from address and sequence.
 pub fn remove(
     from: &Address,
@@ -1,7 +1,7 @@
 // Copyright 2019-2023 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
-use std::sync::Arc;
+use std::{num::NonZeroUsize, sync::Arc};
 
 use crate::blocks::{BlockHeader, Tipset, TipsetKeys};
 use crate::chain::HeadChange;
@@ -18,6 +18,9 @@ use crate::utils::db::CborStoreExt;
 use async_trait::async_trait;
 use cid::Cid;
 use fvm_ipld_blockstore::Blockstore;
+use lru::LruCache;
+use nonzero_ext::nonzero;
+use parking_lot::Mutex;
 use tokio::sync::broadcast::{Receiver as Subscriber, Sender as Publisher};
 
 use crate::message_pool::errors::Error;
@@ -38,6 +41,9 @@ pub trait Provider {
     /// `StateTree` will be rooted at. Return `ActorState` or Error
     /// depending on whether or not `ActorState` is found
     fn get_actor_after(&self, addr: &Address, ts: &Tipset) -> Result<ActorState, Error>;
+    /// Drop the actor states cached by `get_actor_after`, called on every head
+    /// change of the message pool
+    fn clear_actor_cache(&self);
     /// Return the signed messages for given block header
     fn messages_for_block(
         &self,
@@ -51,11 +57,18 @@ pub trait Provider {
     fn chain_compute_base_fee(&self, ts: &Tipset) -> Result<TokenAmount, Error>;
 }
 
+/// Number of actor states cached by the [`MpoolRpcProvider`]
+const ACTOR_CACHE_SIZE: NonZeroUsize = nonzero!(8192usize);
+
 /// This is the default Provider implementation that will be used for the
 /// `mpool` RPC.
 pub struct MpoolRpcProvider<DB> {
     subscriber: Publisher<HeadChange>,
     sm: Arc<StateManager<DB>>,
+    /// Actor states of the senders, keyed by their address and the state root
+    /// they were read from, so that adding and selecting messages of the same
+    /// senders doesn't load them from the state tree again
+    actor_cache: Mutex<LruCache<(Address, Cid), ActorState>>,
 }
 
 impl<DB> MpoolRpcProvider<DB>
@@ -66,7 +79,11 @@ where
     where
         DB: Blockstore + Clone,
     {
-        MpoolRpcProvider { subscriber, sm }
+        MpoolRpcProvider {
+            subscriber,
+            sm,
+            actor_cache: Mutex::new(LruCache::new(ACTOR_CACHE_SIZE)),
+        }
     }
 }
 
@@ -93,13 +110,24 @@ where
     }
 
     fn get_actor_after(&self, addr: &Address, ts: &Tipset) -> Result<ActorState, Error> {
+        let key = (*addr, *ts.parent_state());
+        if let Some(actor) = self.actor_cache.lock().get(&key) {
+            return Ok(actor.clone());
+        }
+
         let state = StateTree::new_from_root(self.sm.blockstore(), ts.parent_state())
             .map_err(|e| Error::Other(e.to_string()))?;
 
         let actor = state
             .get_actor(addr)
-            .map_err(|e| Error::Other(e.to_string()))?;
-        actor.ok_or_else(|| Error::Other("No actor state".to_owned()))
+            .map_err(|e| Error::Other(e.to_string()))?
+            .ok_or_else(|| Error::Other("No actor state".to_owned()))?;
+        self.actor_cache.lock().put(key, actor.clone());
+        Ok(actor)
+    }
+
+    fn clear_actor_cache(&self) {
+        self.actor_cache.lock().clear();
     }
 
     fn messages_for_block(
@@ -9,27 +9,29 @@
 use std::{borrow::BorrowMut, cmp::Ordering, sync::Arc};
 
 use crate::blocks::Tipset;
+use crate::chain::{reorg_ops, HeadChange};
 use crate::message::{Message, SignedMessage};
 use crate::shim::{address::Address, econ::TokenAmount};
 use ahash::{HashMap, HashMapExt};
+use num_traits::{ToPrimitive, Zero};
 use parking_lot::RwLock;
 use rand::{prelude::SliceRandom, thread_rng};
 
 use super::{msg_pool::MessagePool, provider::Provider};
 use crate::message_pool::{
     add_to_selected_msgs,
+    block_messages::BlockMessagesValidator,
+    metrics,
     msg_chain::{create_message_chains, Chains, NodeKey},
     msg_pool::MsgSet,
     msgpool::MIN_GAS,
-    remove_from_selected_msgs, Error,
+    remove_from_selected_msgs,
+    utils::get_gas_reward,
+    Error,
 };
 
 type Pending = HashMap<Address, HashMap<u64, SignedMessage>>;
 
-// A cap on maximum number of message to include in a block
-const MAX_BLOCK_MSGS: usize = 16000;
-const MAX_BLOCKS: usize = 15;
-
 impl<T> MessagePool<T>
 where
     T: Provider,
@@ -38,32 +40,71 @@ where
     /// for inclusion from the pool, given the ticket quality of a miner.
     /// This method selects messages for including in a block.
     pub fn select_messages(&self, ts: &Tipset, tq: f64) -> Result<Vec<SignedMessage>, Error> {
+        let _timer = metrics::MPOOL_SELECTION_TIME.start_timer();
         let cur_ts = self.cur_tipset.lock().clone();
+        let base_fee = self.api.chain_compute_base_fee(ts)?;
         // if the ticket quality is high enough that the first block has higher
         // probability than any other block, then we don't bother with optimal
         // selection because the first block will always have higher effective
         // performance. Otherwise we select message optimally based on effective
         // performance of chains.
-        let mut msgs = if tq > 0.84 {
-            self.select_messages_greedy(&cur_ts, ts)
+        let greedy = tq > self.config.read().greedy_selection_threshold;
+        let mut msgs = if greedy {
+            self.select_messages_greedy(&cur_ts, ts, &base_fee)
         } else {
-            self.select_messages_optimal(&cur_ts, ts, tq)
+            self.select_messages_optimal(&cur_ts, ts, tq, &base_fee)
         }?;
 
-        if msgs.len() > MAX_BLOCK_MSGS {
-            msgs.truncate(MAX_BLOCK_MSGS)
+        if msgs.len() > self.chain_config.block_message_limit {
+            msgs.truncate(self.chain_config.block_message_limit)
         }
 
+        // Leave out the messages the validation of the block would reject
+        let mut validator = BlockMessagesValidator::new(self.chain_config.block_gas_limit);
+        msgs.retain(|m| {
+            let included = validator.include(m.message(), |from| {
+                Ok(self.api.get_actor_after(from, ts)?.sequence)
+            });
+            if let Err(e) = &included {
+                log::warn!(
+                    "Leaving out message {} of {} from the selection: {e}",
+                    m.sequence(),
+                    m.from()
+                );
+            }
+            included.is_ok()
+        });
+
+        let strategy = if greedy {
+            metrics::values::GREEDY
+        } else {
+            metrics::values::OPTIMAL
+        };
+        let reward = msgs.iter().fold(TokenAmount::zero(), |reward, m| {
+            reward + get_gas_reward(m, &base_fee)
+        });
+        metrics::MPOOL_SELECTION_GAS_REWARD
+            .with_label_values(&[strategy])
+            .observe(reward.atto().to_f64().unwrap_or(f64::MAX) / 1e9);
+
         Ok(msgs)
     }
 
+    /// Empty message chains, whose ties are broken as configured
+    fn new_chains(&self) -> Chains {
+        if self.config.read().randomize_chain_ties {
+            Chains::with_tie_break_salt(self.tie_break_salt)
+        } else {
+            Chains::new()
+        }
+    }
+
     fn select_messages_greedy(
         &self,
         cur_ts: &Tipset,
         ts: &Tipset,
+        base_fee: &TokenAmount,
     ) -> Result<Vec<SignedMessage>, Error> {
-        let base_fee = self.api.chain_compute_base_fee(ts)?;
-
         // 0. Load messages from the target tipset; if it is the same as the current
         // tipset in    the mpool, then this is just the pending messages
         let mut pending = self.get_pending_messages(cur_ts, ts)?;
@@ -72,7 +113,7 @@ where
             return Ok(Vec::new());
         }
         // 0b. Select all priority messages that fit in the block
-        let (result, gas_limit) = self.select_priority_messages(&mut pending, &base_fee, ts)?;
+        let (result, gas_limit) = self.select_priority_messages(&mut pending, base_fee, ts)?;
 
         // check if block has been filled
         if gas_limit < MIN_GAS {
@@ -81,20 +122,20 @@ where
 
         // 1. Create a list of dependent message chains with maximal gas reward per
         // limit consumed
-        let mut chains = Chains::new();
+        let mut chains = self.new_chains();
         for (actor, mset) in pending.into_iter() {
             create_message_chains(
                 self.api.as_ref(),
                 &actor,
                 &mset,
-                &base_fee,
+                base_fee,
                 ts,
                 &mut chains,
                 &self.chain_config,
             )?;
         }
 
-        let (msgs, _) = merge_and_trim(&mut chains, result, &base_fee, gas_limit, MIN_GAS);
+        let (msgs, _) = merge_and_trim(&mut chains, result, base_fee, gas_limit, MIN_GAS);
         Ok(msgs)
     }
 
@@ -103,9 +144,8 @@ where
         cur_ts: &Tipset,
         target_tipset: &Tipset,
         ticket_quality: f64,
+        base_fee: &TokenAmount,
     ) -> Result<Vec<SignedMessage>, Error> {
-        let base_fee = self.api.chain_compute_base_fee(target_tipset)?;
-
         // 0. Load messages from the target tipset; if it is the same as the current
         // tipset in    the mpool, then this is just the pending messages
         let mut pending = self.get_pending_messages(cur_ts, target_tipset)?;
@@ -116,7 +156,7 @@ where
 
         // 0b. Select all priority messages that fit in the block
         let (mut result, mut gas_limit) =
-            self.select_priority_messages(&mut pending, &base_fee, target_tipset)?;
+            self.select_priority_messages(&mut pending, base_fee, target_tipset)?;
 
         // check if block has been filled
         if gas_limit < MIN_GAS {
@@ -125,13 +165,13 @@ where
 
         // 1. Create a list of dependent message chains with maximal gas reward per
         // limit consumed
-        let mut chains = Chains::new();
+        let mut chains = self.new_chains();
         for (actor, mset) in pending.into_iter() {
             create_message_chains(
                 self.api.as_ref(),
                 &actor,
                 &mset,
-                &base_fee,
+                base_fee,
                 target_tipset,
                 &mut chains,
                 &self.chain_config,
@@ -154,10 +194,11 @@ where
         // from the    priority message selection) as we have to account for
         // what other miners are doing
         let mut next_chain = 0;
-        let mut partitions: Vec<Vec<NodeKey>> = vec![vec![]; MAX_BLOCKS];
+        let max_blocks = self.chain_config.max_tipset_blocks;
+        let mut partitions: Vec<Vec<NodeKey>> = vec![vec![]; max_blocks];
         let mut i = 0;
-        while i < MAX_BLOCKS && next_chain < chains.len() {
-            let mut gas_limit = fvm_shared3::BLOCK_GAS_LIMIT;
+        while i < max_blocks && next_chain < chains.len() {
+            let mut gas_limit = self.chain_config.block_gas_limit;
             while next_chain < chains.len() {
                 let chain_key = chains.key_vec[next_chain];
                 next_chain += 1;
@@ -177,9 +218,10 @@ where
         // 4. Compute effective performance for each chain, based on the partition they
         // fall into    The effective performance is the gas_perf of the chain *
         // block probability
-        let block_prob = crate::message_pool::block_probabilities(ticket_quality);
+        let block_prob =
+            crate::message_pool::cached_block_probabilities(ticket_quality, max_blocks);
         let mut eff_chains = 0;
-        for i in 0..MAX_BLOCKS {
+        for i in 0..max_blocks {
             for k in &partitions[i] {
                 if let Some(node) = chains.get_mut(*k) {
                     node.eff_perf = node.gas_perf * block_prob[i];
@@ -291,7 +333,7 @@ where
         'tail_loop: while gas_limit >= MIN_GAS && last < chains.len() {
             // trim if necessary
             if chains[last].gas_limit > gas_limit {
-                chains.trim_msgs_at(last, gas_limit, &base_fee);
+                chains.trim_msgs_at(last, gas_limit, base_fee);
             }
 
             // push down if it hasn't been invalidated
@@ -367,7 +409,7 @@ where
                 }
 
                 // dependencies fit, just trim it
-                chains.trim_msgs_at(i, gas_limit - dep_gas_limit, &base_fee);
+                chains.trim_msgs_at(i, gas_limit - dep_gas_limit, base_fee);
                 last += i;
                 continue 'tail_loop;
             }
@@ -422,7 +464,7 @@ where
 
                 // do they fit as it? if it doesn't fit, trim to make it fit if possible
                 if chain_gas_limit > gas_limit {
-                    chains.trim_msgs_at(i, gas_limit - dep_gas_limit, &base_fee);
+                    chains.trim_msgs_at(i, gas_limit - dep_gas_limit, base_fee);
 
                     if !chains[i].valid {
                         continue;
@@ -494,13 +536,13 @@ where
         base_fee: &TokenAmount,
         ts: &Tipset,
     ) -> Result<(Vec<SignedMessage>, u64), Error> {
-        let result = Vec::with_capacity(self.config.size_limit_low() as usize);
-        let gas_limit = fvm_shared3::BLOCK_GAS_LIMIT;
+        let result = Vec::with_capacity(self.config.read().size_limit_low() as usize);
+        let gas_limit = self.chain_config.block_gas_limit;
         let min_gas = 1298450;
 
         // 1. Get priority actor chains
-        let priority = self.config.priority_addrs();
-        let mut chains = Chains::new();
+        let priority = self.config.read().priority_addrs().to_vec();
+        let mut chains = self.new_chains();
         for actor in priority.iter() {
             // remove actor from pending set as we are processing these messages.
             if let Some(mset) = pending.remove(actor) {
@@ -634,54 +676,39 @@ pub(in crate::message_pool) fn run_head_change<T>(
 where
     T: Provider,
 {
-    // TODO: This logic should probably be implemented in the ChainStore. It handles
-    // reorgs.
-    let mut left = Arc::new(from);
-    let mut right = Arc::new(to);
-    let mut left_chain = Vec::new();
-    let mut right_chain = Vec::new();
-    while left != right {
-        if left.epoch() > right.epoch() {
-            left_chain.push(left.as_ref().clone());
-            let par = api.load_tipset(left.parents())?;
-            left = par;
-        } else {
-            right_chain.push(right.as_ref().clone());
-            let par = api.load_tipset(right.parents())?;
-            right = par;
-        }
-    }
-    for ts in left_chain {
-        let mut msgs: Vec<SignedMessage> = Vec::new();
-        for block in ts.blocks() {
-            let (_, smsgs) = api.messages_for_block(block)?;
-            msgs.extend(smsgs);
-        }
-        for msg in msgs {
-            add_to_selected_msgs(msg, rmsgs);
-        }
-    }
-
-    for ts in right_chain {
-        for b in ts.blocks() {
-            let (msgs, smsgs) = api.messages_for_block(b)?;
-
-            for msg in smsgs {
-                remove_from_selected_msgs(
-                    &msg.from(),
-                    pending,
-                    msg.sequence(),
-                    rmsgs.borrow_mut(),
-                )?;
+    for change in reorg_ops(|tsk| api.load_tipset(tsk), Arc::new(from), Arc::new(to))? {
+        match change {
+            HeadChange::Revert(ts) => {
+                for block in ts.blocks() {
+                    let (_, smsgs) = api.messages_for_block(block)?;
+                    for msg in smsgs {
+                        add_to_selected_msgs(msg, rmsgs);
+                    }
+                }
             }
-            for msg in msgs {
-                remove_from_selected_msgs(
-                    &msg.from.into(),
-                    pending,
-                    msg.sequence,
-                    rmsgs.borrow_mut(),
-                )?;
+            HeadChange::Apply(ts) => {
+                for b in ts.blocks() {
+                    let (msgs, smsgs) = api.messages_for_block(b)?;
+
+                    for msg in smsgs {
+                        remove_from_selected_msgs(
+                            &msg.from(),
+                            pending,
+                            msg.sequence(),
+                            rmsgs.borrow_mut(),
+                        )?;
+                    }
+                    for msg in msgs {
+                        remove_from_selected_msgs(
+                            &msg.from.into(),
+                            pending,
+                            msg.sequence,
+                            rmsgs.borrow_mut(),
+                        )?;
+                    }
+                }
             }
+            HeadChange::Current(_) => {}
         }
     }
     Ok(())
@@ -695,6 +722,7 @@ mod test_selection {
     use crate::key_management::{KeyStore, KeyStoreConfig, Wallet};
     use crate::message::Message;
     use crate::shim::crypto::SignatureType;
+    use crate::test_utils::ChainGenerator;
     use tokio::task::JoinSet;
 
     use super::*;
@@ -929,7 +957,6 @@ mod test_selection {
         for i in 0..nmsgs {
             let bias = (nmsgs - i) / 3;
             let m = create_fake_smsg(
-                &mpool,
                 &a2,
                 &a1,
                 i as u64,
@@ -938,7 +965,6 @@ mod test_selection {
             );
             mpool.add(m).unwrap();
             let m = create_fake_smsg(
-                &mpool,
                 &a1,
                 &a2,
                 i as u64,
@@ -964,7 +990,7 @@ mod test_selection {
         let db = MemoryDB::default();
 
         let mut joinset = JoinSet::new();
-        let mut mpool = make_test_mpool(&mut joinset);
+        let mpool = make_test_mpool(&mut joinset);
 
         let ks1 = KeyStore::new(KeyStoreConfig::Memory).unwrap();
         let mut w1 = Wallet::new(ks1);
@@ -975,7 +1001,7 @@ mod test_selection {
         let a2 = w2.generate_addr(SignatureType::Secp256k1).unwrap();
 
         // set priority addrs to a1
-        let mut mpool_cfg = mpool.get_config().clone();
+        let mut mpool_cfg = mpool.get_config();
         mpool_cfg.priority_addrs.push(a1);
         mpool.set_config(&db, mpool_cfg).unwrap();
 
@@ -1105,7 +1131,6 @@ mod test_selection {
         for i in 0..(n_msgs as usize) {
             let bias = (n_msgs as usize - i) / 3;
             let m = create_fake_smsg(
-                &mpool,
                 &a2,
                 &a1,
                 i as u64,
@@ -1180,7 +1205,6 @@ mod test_selection {
         for i in 0..n_msgs as usize {
             let bias = (n_msgs as usize - i) / 3;
             let m = create_fake_smsg(
-                &mpool,
                 &a2,
                 &a1,
                 i as u64,
@@ -1189,7 +1213,6 @@ mod test_selection {
             );
             mpool.add(m).unwrap();
             let m = create_fake_smsg(
-                &mpool,
                 &a1,
                 &a2,
                 i as u64,
@@ -1300,7 +1323,6 @@ mod test_selection {
                 let premium =
                     500000 + 10000 * (n_actors - j) + (n_msgs + 2 - i) / (30 * n_actors) + i % 3;
                 let m = create_fake_smsg(
-                    &mpool,
                     &actors[j as usize],
                     &actors[j as usize],
                     i as u64,
@@ -1350,4 +1372,69 @@ mod test_selection {
             nonces[who] += 1;
         }
     }
+
+    #[tokio::test]
+    async fn run_head_change_over_deep_reorg() {
+        let mut joinset = JoinSet::new();
+        let mpool = make_test_mpool(&mut joinset);
+
+        let keystore = KeyStore::new(KeyStoreConfig::Memory).unwrap();
+        let mut wallet = Wallet::new(keystore);
+        let sender = wallet.generate_addr(SignatureType::Secp256k1).unwrap();
+        let target = wallet.generate_addr(SignatureType::Secp256k1).unwrap();
+        let messages: Vec<_> = (0..6)
+            .map(|i| create_smsg(&target, &sender, &mut wallet, i, TEST_GAS_LIMIT, 1))
+            .collect();
+
+        // The main chain includes a message per tipset, the fork, starting
+        // after null rounds, includes the first two in one of its blocks and
+        // the last one
+        let mut generator = ChainGenerator::new();
+        let genesis = generator.genesis().clone();
+        let base = generator.mine(&genesis, &[1000], 0);
+        let main = generator.extend(&base, &[1000], 5);
+        let mut fork = vec![generator.mine(&base, &[1001, 1002], 3)];
+        fork.extend(generator.extend(&fork[0], &[1001], 6));
+        for tipset in [&genesis, &base].into_iter().chain(&main).chain(&fork) {
+            mpool.api.add_tipset(tipset);
+        }
+        for (tipset, message) in main.iter().zip(&messages) {
+            mpool
+                .api
+                .set_block_messages(tipset.min_ticket_block(), vec![message.clone()]);
+        }
+        mpool
+            .api
+            .set_block_messages(&fork[0].blocks()[1], messages[..2].to_vec());
+        mpool
+            .api
+            .set_block_messages(&fork[4].blocks()[0], vec![messages[5].clone()]);
+
+        let mut rmsgs = HashMap::new();
+        run_head_change(
+            mpool.api.as_ref(),
+            &mpool.pending,
+            main.last().unwrap().as_ref().clone(),
+            fork.last().unwrap().as_ref().clone(),
+            &mut rmsgs,
+        )
+        .unwrap();
+        // The messages reverted and not included again are selectable
+        let mut reverted: Vec<_> = rmsgs[&sender].keys().copied().collect();
+        reverted.sort();
+        assert_eq!(reverted, vec![2, 3, 4]);
+
+        // Switching back reverts the messages of the fork only
+        let mut rmsgs = HashMap::new();
+        run_head_change(
+            mpool.api.as_ref(),
+            &mpool.pending,
+            fork.last().unwrap().as_ref().clone(),
+            main.last().unwrap().as_ref().clone(),
+            &mut rmsgs,
+        )
+        .unwrap();
+        let reverted: Vec<_> = rmsgs[&sender].keys().copied().collect();
+        assert_eq!(reverted, vec![5]);
+    }
 }
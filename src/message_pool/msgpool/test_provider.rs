@@ -68,6 +68,11 @@ impl TestApi {
         self.inner.lock().set_block_messages(h, msgs)
     }
 
+    /// Adds a tipset that can be loaded from `TestApi`
+    pub fn add_tipset(&self, ts: &Tipset) {
+        self.inner.lock().tipsets.push(ts.clone())
+    }
+
     /// Set the heaviest tipset for `TestApi`
     pub fn set_heaviest_tipset(&self, ts: Arc<Tipset>) {
         self.publisher.send(HeadChange::Apply(ts)).unwrap();
@@ -159,6 +164,8 @@ impl Provider for TestApi {
         Ok(actor.into())
     }
 
+    fn clear_actor_cache(&self) {}
+
     fn messages_for_block(
         &self,
         h: &BlockHeader,
@@ -8,7 +8,7 @@ use ahash::{HashMap, HashMapExt};
 use axum::{http::StatusCode, response::IntoResponse, routing::get, Router};
 use lazy_static::lazy_static;
 use log::warn;
-use prometheus::core::{AtomicU64, GenericCounterVec, Opts};
+use prometheus::core::{AtomicU64, GenericCounterVec, GenericGauge, Opts};
 use prometheus::{Encoder, TextEncoder};
 use std::{net::TcpListener, path::PathBuf};
 use tokio::sync::RwLock;
@@ -118,6 +118,52 @@ lazy_static! {
             .expect("Registering the lru_cache_miss metric with the metrics registry must succeed");
         lru_cache_miss
     };
+    pub static ref FINALIZED_EPOCH: Box<GenericGauge<AtomicU64>> = {
+        let finalized_epoch = Box::new(
+            GenericGauge::<AtomicU64>::new(
+                "finalized_epoch",
+                "Epoch of the latest tipset that can no longer be reverted",
+            )
+            .expect("Defining the finalized_epoch metric must succeed"),
+        );
+        prometheus::default_registry()
+            .register(finalized_epoch.clone())
+            .expect(
+                "Registering the finalized_epoch metric with the metrics registry must succeed",
+            );
+        finalized_epoch
+    };
+    pub static ref BUFFERED_WRITE_BYTES: Box<GenericGauge<AtomicU64>> = {
+        let buffered_write_bytes = Box::new(
+            GenericGauge::<AtomicU64>::new(
+                "buffered_write_bytes",
+                "Memory held by the buffers of the writes to the database in progress",
+            )
+            .expect("Defining the buffered_write_bytes metric must succeed"),
+        );
+        prometheus::default_registry()
+            .register(buffered_write_bytes.clone())
+            .expect(
+                "Registering the buffered_write_bytes metric with the metrics registry must succeed",
+            );
+        buffered_write_bytes
+    };
+    pub static ref CHAIN_EVENTS: Box<GenericCounterVec<AtomicU64>> = {
+        let chain_events = Box::new(
+            GenericCounterVec::<AtomicU64>::new(
+                Opts::new(
+                    "chain_events",
+                    "Events of the node delivered to the Prometheus sink",
+                ),
+                &[labels::KIND],
+            )
+            .expect("Defining the chain_events metric must succeed"),
+        );
+        prometheus::default_registry()
+            .register(chain_events.clone())
+            .expect("Registering the chain_events metric with the metrics registry must succeed");
+        chain_events
+    };
 }
 
 pub mod labels {
@@ -131,4 +177,6 @@ pub mod values {
     pub const SKIP: &str = "skip";
     /// tipset cache in state manager
     pub const STATE_MANAGER_TIPSET: &str = "sm_tipset";
+    /// In-memory block cache in front of the persistent blockstore.
+    pub const BLOCKSTORE: &str = "blockstore";
 }
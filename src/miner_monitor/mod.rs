@@ -0,0 +1,312 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Lightweight monitoring of storage providers, read from the state of the
+//! chain: the sectors of the configured miners expiring soon, the faults of
+//! their deadlines and their upcoming proving windows. The summaries are
+//! refreshed once per deadline and served over RPC, and new faults or
+//! expirations are published as events for the configured sinks to alert on.
+
+use std::sync::Arc;
+
+use crate::blocks::Tipset;
+use crate::chain::HeadChange;
+use crate::events::{self, ChainEvent};
+use crate::shim::{address::Address, clock::ChainEpoch};
+use crate::state_manager::{
+    for_each_expiration_while, proving_period_start, proving_windows, StateManager,
+};
+use anyhow::Context;
+use fil_actor_interface::miner;
+use fvm_ipld_bitfield::BitField;
+use fvm_ipld_blockstore::Blockstore;
+use log::warn;
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast::error::RecvError;
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct MinerMonitorConfig {
+    /// Addresses of the monitored miners
+    pub miners: Vec<String>,
+    /// Number of epochs ahead in which sector expirations are reported
+    pub expiration_horizon: ChainEpoch,
+}
+
+impl Default for MinerMonitorConfig {
+    fn default() -> Self {
+        Self {
+            miners: vec![],
+            // 14 days
+            expiration_horizon: 40320,
+        }
+    }
+}
+
+/// Sectors of a deadline and its next proving window
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct DeadlineStatus {
+    pub index: u64,
+    /// Window in which the deadline is proven, the current one if it is open
+    pub open: ChainEpoch,
+    pub close: ChainEpoch,
+    pub sectors: u64,
+    pub active_sectors: u64,
+    pub faulty_sectors: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct MinerSummary {
+    #[serde(with = "crate::json::address::json")]
+    pub miner: Address,
+    /// Epoch of the state the summary was read from
+    pub epoch: ChainEpoch,
+    /// Index of the deadline open at `epoch`
+    pub current_deadline: u64,
+    pub sectors: u64,
+    pub active_sectors: u64,
+    pub faulty_sectors: u64,
+    /// Sectors expiring within the configured horizon, and the earliest
+    /// expiration of all the sectors, as read from the expiration queues of
+    /// the partitions, which round the expirations up to the end of the
+    /// proving window of their deadline
+    pub expiring_sectors: u64,
+    pub next_expiration: Option<ChainEpoch>,
+    /// Deadlines with sectors, by the opening of their next proving window
+    pub deadlines: Vec<DeadlineStatus>,
+}
+
+/// Reads the summary of a miner from the parent state of `tipset`.
+pub fn miner_summary<DB>(
+    state_manager: &StateManager<DB>,
+    tipset: &Tipset,
+    miner: &Address,
+    expiration_horizon: ChainEpoch,
+) -> anyhow::Result<MinerSummary>
+where
+    DB: Blockstore + Clone + Send + Sync + 'static,
+{
+    let store = state_manager.blockstore();
+    let policy = &state_manager.chain_config().policy;
+    let actor = state_manager
+        .get_actor(miner, *tipset.parent_state())?
+        .with_context(|| format!("miner {miner} not found"))?;
+    let state = miner::State::load(store, actor.code, actor.state)?;
+
+    let epoch = tipset.epoch();
+    let (current_deadline, windows) = proving_windows(policy, proving_period_start(&state), epoch);
+    let mut deadlines = vec![];
+    let mut expiring_sectors = 0;
+    let mut next_expiration = None;
+    state.for_each_deadline(policy, store, |index, deadline| {
+        let mut sectors = BitField::new();
+        let mut active = BitField::new();
+        let mut faulty = BitField::new();
+        deadline.for_each(store, |_, partition: miner::Partition| {
+            sectors |= partition.all_sectors();
+            active |= &partition.active_sectors();
+            faulty |= partition.faulty_sectors();
+            // The expiration queues are walked rather than the sectors, up to
+            // the horizon
+            for_each_expiration_while(store, &partition, |expiration, count| {
+                if count == 0 {
+                    return Ok(true);
+                }
+                next_expiration = Some(
+                    next_expiration.map_or(expiration, |next: ChainEpoch| next.min(expiration)),
+                );
+                if expiration > epoch + expiration_horizon {
+                    return Ok(false);
+                }
+                expiring_sectors += count;
+                Ok(true)
+            })?;
+            Ok(())
+        })?;
+        if !sectors.is_empty() {
+            let (open, close) = windows[index as usize];
+            deadlines.push(DeadlineStatus {
+                index,
+                open,
+                close,
+                sectors: sectors.len(),
+                active_sectors: active.len(),
+                faulty_sectors: faulty.len(),
+            });
+        }
+        Ok(())
+    })?;
+    deadlines.sort_by_key(|deadline| deadline.open);
+
+    Ok(MinerSummary {
+        miner: *miner,
+        epoch,
+        current_deadline,
+        sectors: deadlines.iter().map(|d| d.sectors).sum(),
+        active_sectors: deadlines.iter().map(|d| d.active_sectors).sum(),
+        faulty_sectors: deadlines.iter().map(|d| d.faulty_sectors).sum(),
+        expiring_sectors,
+        next_expiration,
+        deadlines,
+    })
+}
+
+/// Events to publish on the change of the summary of a miner
+fn alerts(
+    previous: Option<&MinerSummary>,
+    summary: &MinerSummary,
+    expiration_horizon: ChainEpoch,
+) -> Vec<ChainEvent> {
+    let mut alerts = vec![];
+    if summary.faulty_sectors > previous.map_or(0, |previous| previous.faulty_sectors) {
+        alerts.push(ChainEvent::MinerFaults {
+            miner: summary.miner,
+            epoch: summary.epoch,
+            faulty_sectors: summary.faulty_sectors,
+        });
+    }
+    if summary.expiring_sectors > previous.map_or(0, |previous| previous.expiring_sectors) {
+        alerts.push(ChainEvent::SectorsExpiring {
+            miner: summary.miner,
+            epoch: summary.epoch,
+            sectors: summary.expiring_sectors,
+            before: summary.epoch + expiration_horizon,
+        });
+    }
+    alerts
+}
+
+/// Monitor of the configured miners, with their latest summaries
+#[derive(Default)]
+pub struct MinerMonitor {
+    expiration_horizon: ChainEpoch,
+    miners: Vec<Address>,
+    summaries: RwLock<Vec<MinerSummary>>,
+}
+
+impl MinerMonitor {
+    pub fn new(config: &MinerMonitorConfig) -> anyhow::Result<Self> {
+        let miners = config
+            .miners
+            .iter()
+            .map(|miner| {
+                miner
+                    .parse()
+                    .with_context(|| format!("invalid miner address {miner}"))
+            })
+            .collect::<anyhow::Result<_>>()?;
+        Ok(Self {
+            expiration_horizon: config.expiration_horizon,
+            miners,
+            summaries: Default::default(),
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.miners.is_empty()
+    }
+
+    /// The latest summaries of the miners, read once per deadline
+    pub fn summaries(&self) -> Vec<MinerSummary> {
+        self.summaries.read().clone()
+    }
+
+    /// Refreshes the summaries of the miners when the head enters a new
+    /// deadline, and publishes the new faults and expirations.
+    pub async fn monitor_loop<DB>(
+        self: Arc<Self>,
+        state_manager: Arc<StateManager<DB>>,
+    ) -> anyhow::Result<()>
+    where
+        DB: Blockstore + Clone + Send + Sync + 'static,
+    {
+        if self.is_empty() {
+            return Ok(());
+        }
+        let window = state_manager.chain_config().policy.wpost_challenge_window;
+        let mut head_changes = state_manager.chain_store().publisher().subscribe();
+        let mut last_window = None;
+        loop {
+            let head = match head_changes.recv().await {
+                Ok(HeadChange::Apply(head)) => head,
+                Ok(_) => continue,
+                Err(RecvError::Lagged(_)) => state_manager.chain_store().heaviest_tipset(),
+                Err(RecvError::Closed) => return Ok(()),
+            };
+            let head_window = head.epoch().div_euclid(window);
+            if last_window == Some(head_window) {
+                continue;
+            }
+            last_window = Some(head_window);
+
+            let monitor = self.clone();
+            let state_manager = state_manager.clone();
+            let summaries = tokio::task::spawn_blocking(move || {
+                monitor
+                    .miners
+                    .iter()
+                    .filter_map(|miner| {
+                        miner_summary(&state_manager, &head, miner, monitor.expiration_horizon)
+                            .map_err(|e| warn!("Failed to read the state of miner {miner}: {e:#}"))
+                            .ok()
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .await?;
+
+            let mut previous = self.summaries.write();
+            for summary in &summaries {
+                let previous = previous.iter().find(|p| p.miner == summary.miner);
+                for alert in alerts(previous, summary, self.expiration_horizon) {
+                    events::publish(alert);
+                }
+            }
+            *previous = summaries;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn summary(faulty_sectors: u64, expiring_sectors: u64) -> MinerSummary {
+        MinerSummary {
+            miner: Address::new_id(1000),
+            epoch: 100,
+            current_deadline: 0,
+            sectors: 10,
+            active_sectors: 10 - faulty_sectors,
+            faulty_sectors,
+            expiring_sectors,
+            next_expiration: None,
+            deadlines: vec![],
+        }
+    }
+
+    #[test]
+    fn alerts_on_new_faults_and_expirations() {
+        assert!(alerts(None, &summary(0, 0), 10).is_empty());
+        assert_eq!(
+            alerts(None, &summary(2, 0), 10),
+            vec![ChainEvent::MinerFaults {
+                miner: Address::new_id(1000),
+                epoch: 100,
+                faulty_sectors: 2,
+            }]
+        );
+        assert!(alerts(Some(&summary(2, 0)), &summary(1, 0), 10).is_empty());
+        assert_eq!(
+            alerts(Some(&summary(2, 1)), &summary(2, 3), 10),
+            vec![ChainEvent::SectorsExpiring {
+                miner: Address::new_id(1000),
+                epoch: 100,
+                sectors: 3,
+                before: 110,
+            }]
+        );
+    }
+}
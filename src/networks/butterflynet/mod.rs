@@ -0,0 +1,151 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Butterflynet, the short-lived test network of the upcoming upgrades. It is
+//! reset often, so its genesis isn't embedded but downloaded on the first
+//! start, and the preset follows its latest reset: all the upgrades up to
+//! network version 20 at genesis, which needs no actor bundle.
+
+use lazy_static::lazy_static;
+
+use super::{drand::DRAND_MAINNET, DrandPoint, Height, HeightInfo};
+
+/// Location of the genesis of the current butterflynet reset
+pub const GENESIS_URL: &str =
+    "https://raw.githubusercontent.com/filecoin-project/lotus/master/build/genesis/butterflynet.car";
+
+/// Default bootstrap peer ids.
+pub const DEFAULT_BOOTSTRAP: &[&str] =
+    &const_str::split!(include_str!("../../../build/bootstrap/butterflynet"), "\n");
+
+// https://github.com/ethereum-lists/chains/blob/master/_data/chains/eip155-3141592.json
+pub const ETH_CHAIN_ID: u64 = 3141592;
+
+lazy_static! {
+/// Height epochs.
+pub static ref HEIGHT_INFOS: [HeightInfo; 21] = [
+    HeightInfo {
+        height: Height::Breeze,
+        epoch: -21,
+        bundle: None,
+    },
+    HeightInfo {
+        height: Height::Smoke,
+        epoch: -20,
+        bundle: None,
+    },
+    HeightInfo {
+        height: Height::Ignition,
+        epoch: -19,
+        bundle: None,
+    },
+    HeightInfo {
+        height: Height::ActorsV2,
+        epoch: -18,
+        bundle: None,
+    },
+    HeightInfo {
+        height: Height::Tape,
+        epoch: -17,
+        bundle: None,
+    },
+    HeightInfo {
+        height: Height::Liftoff,
+        epoch: -16,
+        bundle: None,
+    },
+    HeightInfo {
+        height: Height::Kumquat,
+        epoch: -15,
+        bundle: None,
+    },
+    HeightInfo {
+        height: Height::Calico,
+        epoch: -14,
+        bundle: None,
+    },
+    HeightInfo {
+        height: Height::Persian,
+        epoch: -13,
+        bundle: None,
+    },
+    HeightInfo {
+        height: Height::Orange,
+        epoch: -12,
+        bundle: None,
+    },
+    HeightInfo {
+        height: Height::Trust,
+        epoch: -11,
+        bundle: None,
+    },
+    HeightInfo {
+        height: Height::Norwegian,
+        epoch: -10,
+        bundle: None,
+    },
+    HeightInfo {
+        height: Height::Turbo,
+        epoch: -9,
+        bundle: None,
+    },
+    HeightInfo {
+        height: Height::Hyperdrive,
+        epoch: -8,
+        bundle: None,
+    },
+    HeightInfo {
+        height: Height::Chocolate,
+        epoch: -7,
+        bundle: None,
+    },
+    HeightInfo {
+        height: Height::OhSnap,
+        epoch: -6,
+        bundle: None,
+    },
+    HeightInfo {
+        height: Height::Skyr,
+        epoch: -5,
+        bundle: None,
+    },
+    HeightInfo {
+        height: Height::Shark,
+        epoch: -4,
+        bundle: None,
+    },
+    HeightInfo {
+        height: Height::Hygge,
+        epoch: -3,
+        bundle: None,
+    },
+    HeightInfo {
+        height: Height::Lightning,
+        epoch: -2,
+        bundle: None,
+    },
+    HeightInfo {
+        height: Height::Thunder,
+        epoch: -1,
+        bundle: None,
+    },
+];
+}
+
+pub(super) static DRAND_SCHEDULE: [DrandPoint<'static>; 1] = [DrandPoint {
+    height: 0,
+    config: &DRAND_MAINNET,
+}];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_boostrap_list_not_empty() {
+        assert!(!DEFAULT_BOOTSTRAP.is_empty());
+        DEFAULT_BOOTSTRAP.iter().for_each(|addr| {
+            assert!(addr.parse::<multiaddr::Multiaddr>().is_ok());
+        });
+    }
+}
@@ -0,0 +1,180 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Custom chains, defined in a TOML file given to `--chain` rather than
+//! packaged with Forest. A chain spec overrides the parameters of one of the
+//! presets, `devnet` by default, e.g.:
+//!
+//! ```toml
+//! name = "localnet"
+//! genesis_cid = "bafy2bzacea..."
+//! bootstrap_peers = ["/dns4/bootstrap-0.example.com/tcp/1347/p2p/12D3KooW..."]
+//! block_delay_secs = 30
+//!
+//! [[upgrades]]
+//! height = "Thunder"
+//! epoch = 1000
+//! bundle = { manifest = "bafy2bzace...", url = "https://example.com/builtin-actors.car" }
+//! ```
+
+use std::path::Path;
+
+use crate::shim::clock::ChainEpoch;
+use anyhow::Context;
+use cid::Cid;
+use serde::{Deserialize, Serialize};
+use serde_with::{serde_as, DisplayFromStr};
+use url::Url;
+
+use super::{ActorBundleInfo, ChainConfig, Height, NetworkChain};
+
+#[serde_as]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ChainSpec {
+    /// Name of the network, used in the gossip topics and the data directory
+    pub name: String,
+    /// Preset the parameters not given are taken from, `devnet` by default
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    #[serde(default)]
+    pub base: Option<NetworkChain>,
+    pub genesis_cid: Option<String>,
+    pub bootstrap_peers: Option<Vec<String>>,
+    pub block_delay_secs: Option<u64>,
+    pub propagation_delay_secs: Option<u64>,
+    pub allowable_clock_drift_secs: Option<u64>,
+    pub eth_chain_id: Option<u64>,
+    pub block_gas_limit: Option<u64>,
+    pub block_message_limit: Option<usize>,
+    pub max_tipset_blocks: Option<usize>,
+    /// Epochs of the network upgrades, and the actor bundles they introduce
+    #[serde(default)]
+    pub upgrades: Vec<UpgradeSpec>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct UpgradeSpec {
+    pub height: Height,
+    pub epoch: ChainEpoch,
+    pub bundle: Option<BundleSpec>,
+}
+
+#[serde_as]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BundleSpec {
+    #[serde_as(as = "DisplayFromStr")]
+    pub manifest: Cid,
+    pub url: Url,
+}
+
+impl ChainSpec {
+    pub fn from_file(path: &Path) -> anyhow::Result<Self> {
+        let toml = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read the chain spec {}", path.display()))?;
+        toml::from_str(&toml).with_context(|| format!("invalid chain spec {}", path.display()))
+    }
+
+    /// Returns the configuration of the chain, the parameters not given being
+    /// those of the base preset
+    pub fn into_config(self) -> anyhow::Result<ChainConfig> {
+        let base = self
+            .base
+            .unwrap_or_else(|| NetworkChain::Devnet("devnet".into()));
+        let mut config = ChainConfig::from_chain(&base);
+        config.network = NetworkChain::Devnet(self.name);
+        // The genesis of the base preset isn't the one of the custom chain
+        config.genesis_cid = self.genesis_cid;
+        if let Some(bootstrap_peers) = self.bootstrap_peers {
+            config.bootstrap_peers = bootstrap_peers;
+        }
+        if let Some(block_delay_secs) = self.block_delay_secs {
+            config.block_delay_secs = block_delay_secs;
+        }
+        if let Some(propagation_delay_secs) = self.propagation_delay_secs {
+            config.propagation_delay_secs = propagation_delay_secs;
+        }
+        if let Some(allowable_clock_drift_secs) = self.allowable_clock_drift_secs {
+            config.allowable_clock_drift_secs = allowable_clock_drift_secs;
+        }
+        if let Some(eth_chain_id) = self.eth_chain_id {
+            config.eth_chain_id = eth_chain_id;
+        }
+        if let Some(block_gas_limit) = self.block_gas_limit {
+            config.block_gas_limit = block_gas_limit;
+        }
+        if let Some(block_message_limit) = self.block_message_limit {
+            config.block_message_limit = block_message_limit;
+        }
+        if let Some(max_tipset_blocks) = self.max_tipset_blocks {
+            config.max_tipset_blocks = max_tipset_blocks;
+        }
+        for upgrade in self.upgrades {
+            let info = config.height_info_mut(upgrade.height)?;
+            info.epoch = upgrade.epoch;
+            if let Some(BundleSpec { manifest, url }) = upgrade.bundle {
+                info.bundle = Some(ActorBundleInfo { manifest, url });
+            }
+        }
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spec_overrides_the_base_preset() {
+        let spec: ChainSpec = toml::from_str(
+            r#"
+            name = "butterflynet"
+            base = "calibnet"
+            genesis_cid = "bafy2bzacecnamqgqmifpluoeldx7zzglxcljo6oja4vrmtj7432rphldpdmm2"
+            bootstrap_peers = ["/ip4/127.0.0.1/tcp/1347"]
+            block_delay_secs = 30
+            block_gas_limit = 5000000000
+
+            [[upgrades]]
+            height = "Thunder"
+            epoch = 1000
+            bundle = { manifest = "bafy2bzacebzz376j5kizfck56366kdz5aut6ktqrvqbi3efa2d4l2o2m653ts", url = "https://example.com/builtin-actors.car" }
+            "#,
+        )
+        .unwrap();
+        let config = spec.into_config().unwrap();
+        let calibnet = ChainConfig::calibnet();
+        assert_eq!(config.network, NetworkChain::Devnet("butterflynet".into()));
+        assert_eq!(config.bootstrap_peers, vec!["/ip4/127.0.0.1/tcp/1347"]);
+        assert_eq!(config.block_delay_secs, 30);
+        assert_eq!(config.block_gas_limit, 5_000_000_000);
+        assert_eq!(config.max_tipset_blocks, calibnet.max_tipset_blocks);
+        assert_eq!(
+            config.propagation_delay_secs,
+            calibnet.propagation_delay_secs
+        );
+        assert_eq!(config.epoch(Height::Thunder), 1000);
+        assert_eq!(
+            config.epoch(Height::Lightning),
+            calibnet.epoch(Height::Lightning)
+        );
+        assert!(config.height_infos[Height::Thunder as usize]
+            .bundle
+            .is_some());
+    }
+
+    #[test]
+    fn devnet_is_the_default_base() {
+        let spec: ChainSpec = toml::from_str(r#"name = "localnet""#).unwrap();
+        let config = spec.into_config().unwrap();
+        assert_eq!(config.network, NetworkChain::Devnet("localnet".into()));
+        assert_eq!(config.height_infos, ChainConfig::devnet().height_infos);
+        assert_eq!(config.genesis_cid, None);
+    }
+
+    #[test]
+    fn unknown_keys_are_rejected() {
+        assert!(toml::from_str::<ChainSpec>("name = \"test\"\nblock_delay = 30").is_err());
+    }
+}
@@ -1,26 +1,33 @@
 // Copyright 2019-2023 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
-use std::{fmt::Display, str::FromStr, sync::Arc};
+use std::{fmt::Display, path::Path, str::FromStr, sync::Arc};
 
 use crate::beacon::{BeaconPoint, BeaconSchedule, DrandBeacon, DrandConfig};
+use crate::blocks::timing::DEFAULT_ALLOWABLE_CLOCK_DRIFT_SECS;
 use crate::shim::clock::{ChainEpoch, EPOCH_DURATION_SECONDS};
 use crate::shim::sector::{RegisteredPoStProof, RegisteredSealProof};
-use crate::shim::version::NetworkVersion;
+use crate::shim::version::{NetworkVersion, NetworkVersion_v3};
 use crate::shim::Inner;
 use anyhow::Error;
 use cid::Cid;
 use fil_actors_shared::v10::runtime::Policy;
 use serde::{Deserialize, Serialize};
-use strum_macros::Display;
+use serde_with::{serde_as, DisplayFromStr};
+use strum::IntoEnumIterator;
+use strum_macros::{Display, EnumIter};
 use url::Url;
 
+mod chain_spec;
 mod drand;
 
+pub mod butterflynet;
 pub mod calibnet;
 pub mod devnet;
 pub mod mainnet;
 
+pub use chain_spec::{BundleSpec, ChainSpec, UpgradeSpec};
+
 /// Newest network version for all networks
 pub const NEWEST_NETWORK_VERSION: NetworkVersion = NetworkVersion::V17;
 
@@ -30,6 +37,11 @@ const DEFAULT_RECENT_STATE_ROOTS: i64 = 2000;
 // Lotus uses a window size of 8: https://github.com/filecoin-project/lotus/blob/c1d22d8b3298fdce573107413729be608e72187d/chain/sync.go#L56
 const DEFAULT_REQUEST_WINDOW: usize = 32;
 
+const DEFAULT_BLOCK_GAS_LIMIT: u64 = fvm_shared3::BLOCK_GAS_LIMIT;
+// A cap on maximum number of message to include in a block
+const DEFAULT_BLOCK_MESSAGE_LIMIT: usize = 16000;
+const DEFAULT_MAX_TIPSET_BLOCKS: usize = 15;
+
 /// Forest builtin `filecoin` network chains. In general only `mainnet` and its
 /// chain information should be considered stable.
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -37,6 +49,7 @@ const DEFAULT_REQUEST_WINDOW: usize = 32;
 pub enum NetworkChain {
     Mainnet,
     Calibnet,
+    Butterflynet,
     Devnet(String),
 }
 
@@ -46,7 +59,8 @@ impl FromStr for NetworkChain {
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s {
             "mainnet" => Ok(NetworkChain::Mainnet),
-            "calibnet" => Ok(NetworkChain::Calibnet),
+            "calibnet" | "calibration" => Ok(NetworkChain::Calibnet),
+            "butterflynet" => Ok(NetworkChain::Butterflynet),
             name => Ok(NetworkChain::Devnet(name.to_owned())),
         }
     }
@@ -57,6 +71,7 @@ impl Display for NetworkChain {
         match self {
             NetworkChain::Mainnet => write!(f, "mainnet"),
             NetworkChain::Calibnet => write!(f, "calibnet"),
+            NetworkChain::Butterflynet => write!(f, "butterflynet"),
             NetworkChain::Devnet(name) => write!(f, "{name}"),
         }
     }
@@ -69,7 +84,7 @@ impl NetworkChain {
 }
 
 /// Defines the meaningful heights of the protocol.
-#[derive(Debug, Display, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[derive(Debug, Display, EnumIter, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
 pub enum Height {
     Breeze,
     Smoke,
@@ -128,6 +143,31 @@ impl From<Height> for NetworkVersion {
     }
 }
 
+impl Height {
+    /// Version of the built-in actors the upgrade runs
+    pub fn actors_version(self) -> u64 {
+        match self {
+            Height::Breeze | Height::Smoke | Height::Ignition => 0,
+            Height::ActorsV2
+            | Height::Tape
+            | Height::Liftoff
+            | Height::Kumquat
+            | Height::Calico
+            | Height::Persian
+            | Height::Orange => 2,
+            Height::Trust | Height::Norwegian => 3,
+            Height::Turbo => 4,
+            Height::Hyperdrive => 5,
+            Height::Chocolate => 6,
+            Height::OhSnap => 7,
+            Height::Skyr => 8,
+            Height::Shark => 9,
+            Height::Hygge => 10,
+            Height::Lightning | Height::Thunder => 11,
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
 pub struct ActorBundleInfo {
     pub manifest: Cid,
@@ -141,12 +181,58 @@ pub struct HeightInfo {
     pub bundle: Option<ActorBundleInfo>,
 }
 
+/// Override of the epoch of a network upgrade, given as `<upgrade>=<epoch>`
+/// where the upgrade is either its name (e.g. `Shark`) or the network version
+/// it introduces (e.g. `nv17`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UpgradeOverride {
+    pub height: Height,
+    pub epoch: ChainEpoch,
+}
+
+impl FromStr for UpgradeOverride {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (upgrade, epoch) = s
+            .split_once('=')
+            .ok_or_else(|| anyhow::anyhow!("expected <upgrade>=<epoch>, got {s}"))?;
+        let height = match upgrade.to_lowercase().strip_prefix("nv") {
+            Some(version) => {
+                let version = NetworkVersion::from(NetworkVersion_v3::new(version.parse()?));
+                // The first upgrade introducing the version
+                Height::iter()
+                    .find(|height| NetworkVersion::from(*height) == version)
+                    .ok_or_else(|| anyhow::anyhow!("unsupported network version {upgrade}"))?
+            }
+            None => Height::iter()
+                .find(|height| height.to_string().eq_ignore_ascii_case(upgrade))
+                .ok_or_else(|| anyhow::anyhow!("unknown network upgrade {upgrade}"))?,
+        };
+        Ok(Self {
+            height,
+            epoch: epoch.parse()?,
+        })
+    }
+}
+
 pub fn sort_by_epoch(height_info_slice: &[HeightInfo]) -> Vec<HeightInfo> {
     let mut height_info_vec = height_info_slice.to_vec();
     height_info_vec.sort_by(|a, b| a.epoch.cmp(&b.epoch));
     height_info_vec
 }
 
+/// A tipset that is known to be final. The syncer never reorganizes the chain
+/// below it.
+#[serde_as]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub epoch: ChainEpoch,
+    /// Block CIDs of the tipset
+    #[serde_as(as = "Vec<DisplayFromStr>")]
+    pub tipset: Vec<Cid>,
+}
+
 #[derive(Clone)]
 struct DrandPoint<'a> {
     pub height: ChainEpoch,
@@ -162,6 +248,9 @@ pub struct ChainConfig {
     pub bootstrap_peers: Vec<String>,
     pub block_delay_secs: u64,
     pub propagation_delay_secs: u64,
+    /// Drift allowed between the clocks of the miners and of the node, see
+    /// [`crate::blocks::timing`]
+    pub allowable_clock_drift_secs: u64,
     pub height_infos: Vec<HeightInfo>,
     #[serde(default = "default_policy")]
     pub policy: Policy,
@@ -170,6 +259,14 @@ pub struct ChainConfig {
     /// the exported snapshot.
     pub recent_state_roots: i64,
     pub request_window: usize,
+    /// Known final tipsets the chain is checked against when syncing
+    pub checkpoints: Vec<Checkpoint>,
+    /// Gas limit of the messages of a block
+    pub block_gas_limit: u64,
+    /// Number of messages the message pool selects for a block at most
+    pub block_message_limit: usize,
+    /// Number of blocks of a tipset the message selection plans for
+    pub max_tipset_blocks: usize,
 }
 
 impl ChainConfig {
@@ -181,11 +278,16 @@ impl ChainConfig {
             bootstrap_peers: DEFAULT_BOOTSTRAP.iter().map(|x| x.to_string()).collect(),
             block_delay_secs: EPOCH_DURATION_SECONDS as u64,
             propagation_delay_secs: 10,
+            allowable_clock_drift_secs: DEFAULT_ALLOWABLE_CLOCK_DRIFT_SECS,
             height_infos: HEIGHT_INFOS.to_vec(),
             policy: Policy::mainnet(),
             eth_chain_id: ETH_CHAIN_ID,
             recent_state_roots: DEFAULT_RECENT_STATE_ROOTS,
             request_window: DEFAULT_REQUEST_WINDOW,
+            checkpoints: Vec::new(),
+            block_gas_limit: DEFAULT_BLOCK_GAS_LIMIT,
+            block_message_limit: DEFAULT_BLOCK_MESSAGE_LIMIT,
+            max_tipset_blocks: DEFAULT_MAX_TIPSET_BLOCKS,
         }
     }
 
@@ -197,11 +299,58 @@ impl ChainConfig {
             bootstrap_peers: DEFAULT_BOOTSTRAP.iter().map(|x| x.to_string()).collect(),
             block_delay_secs: EPOCH_DURATION_SECONDS as u64,
             propagation_delay_secs: 10,
+            allowable_clock_drift_secs: DEFAULT_ALLOWABLE_CLOCK_DRIFT_SECS,
             height_infos: HEIGHT_INFOS.to_vec(),
             policy: Policy::calibnet(),
             eth_chain_id: ETH_CHAIN_ID,
             recent_state_roots: DEFAULT_RECENT_STATE_ROOTS,
             request_window: DEFAULT_REQUEST_WINDOW,
+            checkpoints: Vec::new(),
+            block_gas_limit: DEFAULT_BLOCK_GAS_LIMIT,
+            block_message_limit: DEFAULT_BLOCK_MESSAGE_LIMIT,
+            max_tipset_blocks: DEFAULT_MAX_TIPSET_BLOCKS,
+        }
+    }
+
+    pub fn butterflynet() -> Self {
+        use butterflynet::*;
+        let mut policy = Policy::mainnet();
+        policy.minimum_consensus_power = (2i64 << 30).into();
+        policy.minimum_verified_allocation_size = (1i64 << 20).into();
+        policy.pre_commit_challenge_delay = 150;
+
+        #[allow(clippy::disallowed_types)]
+        let allowed_proof_types = std::collections::HashSet::from_iter(vec![
+            <RegisteredSealProof as Inner>::FVM::StackedDRG512MiBV1,
+            <RegisteredSealProof as Inner>::FVM::StackedDRG32GiBV1,
+            <RegisteredSealProof as Inner>::FVM::StackedDRG64GiBV1,
+        ]);
+        policy.valid_pre_commit_proof_type = allowed_proof_types;
+        #[allow(clippy::disallowed_types)]
+        let allowed_proof_types = std::collections::HashSet::from_iter(vec![
+            <RegisteredPoStProof as Inner>::FVM::StackedDRGWindow512MiBV1,
+            <RegisteredPoStProof as Inner>::FVM::StackedDRGWindow32GiBV1,
+            <RegisteredPoStProof as Inner>::FVM::StackedDRGWindow64GiBV1,
+        ]);
+        policy.valid_post_proof_type = allowed_proof_types;
+
+        Self {
+            network: NetworkChain::Butterflynet,
+            // The genesis changes with each reset
+            genesis_cid: None,
+            bootstrap_peers: DEFAULT_BOOTSTRAP.iter().map(|x| x.to_string()).collect(),
+            block_delay_secs: EPOCH_DURATION_SECONDS as u64,
+            propagation_delay_secs: 6,
+            allowable_clock_drift_secs: DEFAULT_ALLOWABLE_CLOCK_DRIFT_SECS,
+            height_infos: HEIGHT_INFOS.to_vec(),
+            policy,
+            eth_chain_id: ETH_CHAIN_ID,
+            recent_state_roots: DEFAULT_RECENT_STATE_ROOTS,
+            request_window: DEFAULT_REQUEST_WINDOW,
+            checkpoints: Vec::new(),
+            block_gas_limit: DEFAULT_BLOCK_GAS_LIMIT,
+            block_message_limit: DEFAULT_BLOCK_MESSAGE_LIMIT,
+            max_tipset_blocks: DEFAULT_MAX_TIPSET_BLOCKS,
         }
     }
 
@@ -231,11 +380,16 @@ impl ChainConfig {
             bootstrap_peers: Vec::new(),
             block_delay_secs: 4,
             propagation_delay_secs: 1,
+            allowable_clock_drift_secs: DEFAULT_ALLOWABLE_CLOCK_DRIFT_SECS,
             height_infos: HEIGHT_INFOS.to_vec(),
             policy,
             eth_chain_id: ETH_CHAIN_ID,
             recent_state_roots: DEFAULT_RECENT_STATE_ROOTS,
             request_window: DEFAULT_REQUEST_WINDOW,
+            checkpoints: Vec::new(),
+            block_gas_limit: DEFAULT_BLOCK_GAS_LIMIT,
+            block_message_limit: DEFAULT_BLOCK_MESSAGE_LIMIT,
+            max_tipset_blocks: DEFAULT_MAX_TIPSET_BLOCKS,
         }
     }
 
@@ -243,6 +397,7 @@ impl ChainConfig {
         match network_chain {
             NetworkChain::Mainnet => Self::mainnet(),
             NetworkChain::Calibnet => Self::calibnet(),
+            NetworkChain::Butterflynet => Self::butterflynet(),
             NetworkChain::Devnet(name) => Self {
                 network: NetworkChain::Devnet(name.clone()),
                 ..Self::devnet()
@@ -250,6 +405,35 @@ impl ChainConfig {
         }
     }
 
+    /// Returns the configuration of the `--chain` argument: either the name
+    /// of a preset, or the path of a chain spec TOML file.
+    pub fn from_chain_arg(chain: &str) -> anyhow::Result<Self> {
+        match chain.ends_with(".toml") {
+            true => ChainSpec::from_file(Path::new(chain))?.into_config(),
+            false => Ok(Self::from_chain(&chain.parse()?)),
+        }
+    }
+
+    /// Moves a network upgrade to another epoch. Only devnets and custom
+    /// chains can be changed, to exercise the upgrades in tests.
+    pub fn override_upgrade(&mut self, upgrade: UpgradeOverride) -> anyhow::Result<()> {
+        anyhow::ensure!(
+            self.network.is_devnet(),
+            "the upgrades of {} can't be overridden",
+            self.network
+        );
+        self.height_info_mut(upgrade.height)?.epoch = upgrade.epoch;
+        Ok(())
+    }
+
+    /// The height infos are indexed by height
+    fn height_info_mut(&mut self, height: Height) -> anyhow::Result<&mut HeightInfo> {
+        self.height_infos
+            .get_mut(height as usize)
+            .filter(|info| info.height == height)
+            .ok_or_else(|| anyhow::anyhow!("unknown upgrade {height}"))
+    }
+
     pub fn network_version(&self, epoch: ChainEpoch) -> NetworkVersion {
         let height = sort_by_epoch(&self.height_infos)
             .iter()
@@ -268,6 +452,7 @@ impl ChainConfig {
         let ds_iter = match self.network {
             NetworkChain::Mainnet => mainnet::DRAND_SCHEDULE.iter(),
             NetworkChain::Calibnet => calibnet::DRAND_SCHEDULE.iter(),
+            NetworkChain::Butterflynet => butterflynet::DRAND_SCHEDULE.iter(),
             NetworkChain::Devnet(_) => devnet::DRAND_SCHEDULE.iter(),
         };
 
@@ -297,13 +482,33 @@ impl ChainConfig {
         match self.network {
             NetworkChain::Mainnet => Some(mainnet::DEFAULT_GENESIS),
             NetworkChain::Calibnet => Some(calibnet::DEFAULT_GENESIS),
-            NetworkChain::Devnet(_) => None,
+            NetworkChain::Butterflynet | NetworkChain::Devnet(_) => None,
+        }
+    }
+
+    /// Location of the genesis of the networks which don't embed it, but
+    /// download it
+    pub fn genesis_url(&self) -> Option<Url> {
+        match self.network {
+            NetworkChain::Butterflynet => {
+                Some(Url::parse(butterflynet::GENESIS_URL).expect("the genesis URL must be valid"))
+            }
+            _ => None,
         }
     }
 
     pub fn is_testnet(&self) -> bool {
         !matches!(self.network, NetworkChain::Mainnet)
     }
+
+    /// Returns the checkpoint with the highest epoch, if any. As a tipset key
+    /// commits to the whole chain below it, the latest checkpoint implies all
+    /// the previous ones.
+    pub fn latest_checkpoint(&self) -> Option<&Checkpoint> {
+        self.checkpoints
+            .iter()
+            .max_by_key(|checkpoint| checkpoint.epoch)
+    }
 }
 
 impl Default for ChainConfig {
@@ -316,3 +521,48 @@ impl Default for ChainConfig {
 fn default_policy() -> Policy {
     Policy::mainnet()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_upgrade_overrides() {
+        let upgrade: UpgradeOverride = "nv17=10".parse().unwrap();
+        assert_eq!(upgrade.height, Height::Shark);
+        assert_eq!(upgrade.epoch, 10);
+        // The first upgrade to a version is the one introducing it
+        assert_eq!(
+            "nv5=1".parse::<UpgradeOverride>().unwrap().height,
+            Height::Tape
+        );
+        let upgrade: UpgradeOverride = "thunder=-1".parse().unwrap();
+        assert_eq!(upgrade.height, Height::Thunder);
+        assert_eq!(upgrade.epoch, -1);
+
+        assert!("nv99=10".parse::<UpgradeOverride>().is_err());
+        assert!("shark".parse::<UpgradeOverride>().is_err());
+        assert!("hurricane=10".parse::<UpgradeOverride>().is_err());
+    }
+
+    #[test]
+    fn override_devnet_upgrades_only() {
+        let upgrade: UpgradeOverride = "nv18=100".parse().unwrap();
+        let mut devnet = ChainConfig::devnet();
+        devnet.override_upgrade(upgrade).unwrap();
+        assert_eq!(devnet.epoch(Height::Hygge), 100);
+        assert!(ChainConfig::mainnet().override_upgrade(upgrade).is_err());
+    }
+
+    #[test]
+    fn butterflynet_preset() {
+        let config = ChainConfig::from_chain_arg("butterflynet").unwrap();
+        assert_eq!(config.network, NetworkChain::Butterflynet);
+        assert_eq!(config.network.to_string(), "butterflynet");
+        // All the upgrades are at genesis
+        assert_eq!(config.network_version(0), NetworkVersion::V20);
+        assert!(config.genesis_bytes().is_none());
+        assert!(config.genesis_url().is_some());
+        assert!(ChainConfig::calibnet().genesis_url().is_none());
+    }
+}
@@ -7,24 +7,43 @@ use std::{
     sync::Arc,
 };
 
-use crate::beacon::Beacon;
+use crate::beacon::{json::BeaconEntryJson, Beacon};
 use crate::blocks::{
     header::json::BlockHeaderJson, tipset_json::TipsetJson, tipset_keys_json::TipsetKeysJson,
     BlockHeader, Tipset,
 };
-use crate::json::{cid::CidJson, message::json::MessageJson};
+use crate::chain::Scale;
+use crate::db::rolling::GcRequest;
+use crate::db::MemoryDB;
+use crate::ipld::{
+    json::IpldJson,
+    resolve::{resolve_path, BlockstoreLinkResolver, LOAD_LIMIT_REACHED},
+    WALK_SNAPSHOT_PROGRESS_EXPORT,
+};
+use crate::json::{
+    cid::CidJson, message::json::MessageJson, message_receipt::json::ReceiptJson,
+    signed_message::json::SignedMessageJson,
+};
+use crate::message::Message as _;
 use crate::rpc_api::{
     chain_api::*,
-    data_types::{BlockMessages, RPCState},
+    data_types::{
+        ApiMessage, BlockMessages, ForkHeadJson, IpldObject, RPCState, ReceiptInclusionProof,
+        SelectedNode,
+    },
 };
 use crate::shim::message::Message;
-use crate::utils::io::VoidAsyncWriter;
+use crate::utils::{
+    car::{finish_car_v2, forest::write_forest_car, CARV2_DATA_OFFSET},
+    io::VoidAsyncWriter,
+    operations::{self, OperationKind},
+};
 use anyhow::{Context, Result};
 use fvm_ipld_blockstore::Blockstore;
 use fvm_ipld_encoding::CborStore;
 use hex::ToHex;
 use jsonrpc_v2::{Data, Error as JsonRpcError, Params};
-use sha2::{digest::Output, Sha256};
+use sha2::{digest::Output, Digest, Sha256};
 use tempfile::NamedTempFile;
 use tokio::{io::AsyncWriteExt, sync::Mutex};
 use tokio_util::compat::TokioAsyncReadCompatExt;
@@ -55,6 +74,8 @@ pub(in crate::rpc) async fn chain_export<DB, B>(
         tipset_keys: TipsetKeysJson(tsk),
         skip_checksum,
         dry_run,
+        car_v2,
+        forest_car,
     }): Params<ChainExportParams>,
 ) -> Result<ChainExportResult, JsonRpcError>
 where
@@ -79,6 +100,9 @@ where
             "recent-stateroots must be greater than {chain_finality}"
         ))?;
     }
+    if car_v2 && forest_car {
+        Err("car-v2 and forest-car exports are mutually exclusive")?;
+    }
 
     let output_dir = output_path.parent().ok_or_else(|| JsonRpcError::Provided {
         code: http::StatusCode::INTERNAL_SERVER_ERROR.as_u16() as _,
@@ -88,22 +112,53 @@ where
     let head = data.chain_store.tipset_from_keys(&tsk)?;
     let start_ts = data.chain_store.tipset_by_height(epoch, head, true)?;
 
-    match if dry_run {
-        data.chain_store
-            .export::<_, Sha256>(
+    let export = async {
+        if dry_run {
+            data.chain_store
+                .export::<_, Sha256>(
+                    &start_ts,
+                    recent_roots,
+                    VoidAsyncWriter::default(),
+                    true, // `compressed` is always on
+                    skip_checksum,
+                )
+                .await
+        } else if car_v2 {
+            export_car_v2(&data, &start_ts, recent_roots, &temp_path, skip_checksum)
+                .await
+                .map_err(crate::chain::Error::from)
+        } else if forest_car {
+            export_converted(
+                &data,
                 &start_ts,
                 recent_roots,
-                VoidAsyncWriter::default(),
-                true, // `compressed` is always on
+                &temp_path,
                 skip_checksum,
+                write_forest_car,
             )
             .await
-    } else {
-        let file = tokio::fs::File::create(&temp_path).await?;
-        data.chain_store
-            .export::<_, Sha256>(&start_ts, recent_roots, file.compat(), true, skip_checksum)
-            .await
-    } {
+            .map_err(crate::chain::Error::from)
+        } else {
+            let file = tokio::fs::File::create(&temp_path)
+                .await
+                .context("failed to create the snapshot file")?;
+            data.chain_store
+                .export::<_, Sha256>(&start_ts, recent_roots, file.compat(), true, skip_checksum)
+                .await
+        }
+    };
+    // Cancelling drops the export, the temporary file being deleted on return
+    match operations::run(
+        OperationKind::SnapshotExport,
+        format!(
+            "snapshot export at epoch {epoch} to {}",
+            output_path.display()
+        ),
+        Some(WALK_SNAPSHOT_PROGRESS_EXPORT.clone()),
+        |_| async { Ok(export.await?) },
+    )
+    .await
+    {
         Ok(checksum_opt) if !dry_run => {
             // `persist`is expected to succeed since we've made sure the temp-file is in the
             // same folder as the final file.
@@ -121,6 +176,98 @@ where
     Ok(output_path)
 }
 
+/// Exports the CARv1 payload of a CARv2 archive straight to `path`, after the
+/// space of the CARv2 header, then appends the index and writes the header.
+/// The checksum is the one of the whole archive.
+async fn export_car_v2<DB, B>(
+    data: &RPCState<DB, B>,
+    start_ts: &Tipset,
+    recent_roots: i64,
+    path: &Path,
+    skip_checksum: bool,
+) -> Result<Option<Output<Sha256>>>
+where
+    DB: Blockstore + Clone + Send + Sync + 'static,
+    B: Beacon,
+{
+    use std::io::{Seek, SeekFrom};
+
+    let output_dir = path
+        .parent()
+        .context("Failed to determine export directory")?
+        .to_path_buf();
+    let mut file = std::fs::File::options()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(path)?;
+    file.seek(SeekFrom::Start(CARV2_DATA_OFFSET))?;
+    let writer = tokio::fs::File::from_std(file.try_clone()?);
+    data.chain_store
+        .export::<_, Sha256>(start_ts, recent_roots, writer.compat(), false, true)
+        .await?;
+
+    tokio::task::spawn_blocking(move || {
+        finish_car_v2(&mut file, &output_dir)?;
+        if skip_checksum {
+            return Ok(None);
+        }
+        file.rewind()?;
+        checksum(file).map(Some)
+    })
+    .await?
+}
+
+/// Returns the SHA-256 checksum of the content read
+fn checksum(mut reader: impl std::io::Read) -> Result<Output<Sha256>> {
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0; 1 << 20];
+    loop {
+        match reader.read(&mut buffer)? {
+            0 => return Ok(hasher.finalize()),
+            n => hasher.update(&buffer[..n]),
+        }
+    }
+}
+
+/// Exports an uncompressed CARv1 archive next to `path`, then converts it
+/// to the archive written at `path`. The checksum is the one of the converted
+/// archive.
+async fn export_converted<DB, B, F>(
+    data: &RPCState<DB, B>,
+    start_ts: &Tipset,
+    recent_roots: i64,
+    path: &Path,
+    skip_checksum: bool,
+    convert: F,
+) -> Result<Option<Output<Sha256>>>
+where
+    DB: Blockstore + Clone + Send + Sync + 'static,
+    B: Beacon,
+    F: FnOnce(std::fs::File, std::fs::File) -> Result<()> + Send + 'static,
+{
+    let output_dir = path
+        .parent()
+        .context("Failed to determine export directory")?;
+    let car_v1_path = NamedTempFile::new_in(output_dir)?.into_temp_path();
+    let file = tokio::fs::File::create(&car_v1_path).await?;
+    data.chain_store
+        .export::<_, Sha256>(start_ts, recent_roots, file.compat(), false, true)
+        .await?;
+
+    let path = path.to_path_buf();
+    tokio::task::spawn_blocking(move || {
+        let car_v1 = std::fs::File::open(&car_v1_path)?;
+        convert(car_v1, std::fs::File::create(&path)?)?;
+        if skip_checksum {
+            return Ok(None);
+        }
+        checksum(std::fs::File::open(&path)?).map(Some)
+    })
+    .await?
+}
+
 /// Prints hex-encoded representation of SHA-256 checksum and saves it to a file
 /// with the same name but with a `.sha256sum` extension.
 async fn save_checksum(source: &Path, hash: Output<Sha256>) -> Result<()> {
@@ -213,6 +360,67 @@ where
     Ok(ret)
 }
 
+pub(in crate::rpc) async fn chain_get_parent_messages<DB, B>(
+    data: Data<RPCState<DB, B>>,
+    Params(params): Params<ChainGetParentMessagesParams>,
+) -> Result<ChainGetParentMessagesResult, JsonRpcError>
+where
+    DB: Blockstore + Clone + Send + Sync + 'static,
+    B: Beacon,
+{
+    let (CidJson(blk_cid),) = params;
+    let chain_store = data.state_manager.chain_store();
+    let blk: BlockHeader = chain_store
+        .blockstore()
+        .get_cbor(&blk_cid)?
+        .ok_or("can't find block with that cid")?;
+    // The genesis block has no parent
+    if blk.epoch() == 0 {
+        return Ok(vec![]);
+    }
+    let parent = chain_store.tipset_from_keys(blk.parents())?;
+    chain_store
+        .messages_for_tipset(&parent)?
+        .into_iter()
+        .map(|msg| -> Result<_, JsonRpcError> {
+            Ok(ApiMessage {
+                cid: CidJson(msg.cid()?),
+                message: MessageJson(msg.message().clone()),
+            })
+        })
+        .collect()
+}
+
+pub(in crate::rpc) async fn chain_get_parent_receipts<DB, B>(
+    data: Data<RPCState<DB, B>>,
+    Params(params): Params<ChainGetParentReceiptsParams>,
+) -> Result<ChainGetParentReceiptsResult, JsonRpcError>
+where
+    DB: Blockstore + Clone + Send + Sync + 'static,
+    B: Beacon,
+{
+    let (CidJson(blk_cid),) = params;
+    let chain_store = data.state_manager.chain_store();
+    let blk: BlockHeader = chain_store
+        .blockstore()
+        .get_cbor(&blk_cid)?
+        .ok_or("can't find block with that cid")?;
+    // The genesis block has no parent
+    if blk.epoch() == 0 {
+        return Ok(vec![]);
+    }
+    // The receipts are indexed as the messages of the parent tipset are
+    // executed, deduplicated
+    let parent = chain_store.tipset_from_keys(blk.parents())?;
+    (0..chain_store.messages_for_tipset(&parent)?.len())
+        .map(|i| -> Result<_, JsonRpcError> {
+            let receipt = crate::chain::get_parent_reciept(chain_store.blockstore(), &blk, i)?
+                .ok_or_else(|| format!("missing receipt of the message at index {i}"))?;
+            Ok(ReceiptJson(receipt))
+        })
+        .collect()
+}
+
 pub(in crate::rpc) async fn chain_get_tipset_by_height<DB, B>(
     data: Data<RPCState<DB, B>>,
     Params(params): Params<ChainGetTipsetByHeightParams>,
@@ -354,3 +562,232 @@ where
         .set_heaviest_tipset(new_head)
         .map_err(Into::into)
 }
+
+pub(in crate::rpc) async fn chain_get_message_inclusion_proof<DB, B>(
+    data: Data<RPCState<DB, B>>,
+    Params(params): Params<ChainGetMessageInclusionProofParams>,
+) -> Result<ChainGetMessageInclusionProofResult, JsonRpcError>
+where
+    DB: Blockstore + Clone + Send + Sync + 'static,
+    B: Beacon,
+{
+    let (CidJson(blk_cid), CidJson(msg_cid)) = params;
+    let db = data.state_manager.blockstore();
+    let blk: BlockHeader = db
+        .get_cbor(&blk_cid)?
+        .ok_or("can't find block with that cid")?;
+    Ok(crate::chain::message_inclusion_proof(db, &blk, &msg_cid)?)
+}
+
+pub(in crate::rpc) async fn chain_get_receipt_inclusion_proof<DB, B>(
+    data: Data<RPCState<DB, B>>,
+    Params(params): Params<ChainGetReceiptInclusionProofParams>,
+) -> Result<ChainGetReceiptInclusionProofResult, JsonRpcError>
+where
+    DB: Blockstore + Clone + Send + Sync + 'static,
+    B: Beacon,
+{
+    let (CidJson(msg_cid), TipsetKeysJson(tsk)) = params;
+    let chain_store = data.state_manager.chain_store();
+    let ts = chain_store.tipset_from_keys(&tsk)?;
+    // The receipts are indexed as the messages are executed, deduplicated
+    let index = chain_store
+        .messages_for_tipset(&ts)?
+        .iter()
+        .map(|msg| msg.cid())
+        .collect::<Result<Vec<_>, _>>()?
+        .iter()
+        .position(|cid| cid == &msg_cid)
+        .ok_or("message not executed in that tipset")?;
+    let head = chain_store.heaviest_tipset();
+    if ts.epoch() >= head.epoch() {
+        return Err("the tipset executing the message has no child yet".into());
+    }
+    let child = chain_store.tipset_by_height(ts.epoch() + 1, head, false)?;
+    if child.parents() != ts.key() {
+        return Err("the tipset executing the message isn't on the current chain".into());
+    }
+    let receipts_root = *child.blocks()[0].message_receipts();
+    let (receipt, proof) = crate::chain::receipt_inclusion_proof(
+        data.state_manager.blockstore(),
+        &receipts_root,
+        index as u64,
+    )?;
+    Ok(ReceiptInclusionProof {
+        receipt: ReceiptJson(receipt),
+        receipts_root: CidJson(receipts_root),
+        proof,
+    })
+}
+
+/// Computes the root of the messages of a block the way its header commits
+/// to them, without storing anything
+pub(in crate::rpc) async fn chain_compute_message_root(
+    Params(params): Params<ChainComputeMessageRootParams>,
+) -> Result<ChainComputeMessageRootResult, JsonRpcError> {
+    let (bls_messages, secp_messages) = params;
+    let bls_messages = bls_messages
+        .into_iter()
+        .map(|MessageJson(msg)| msg)
+        .collect::<Vec<_>>();
+    let secp_messages = secp_messages
+        .into_iter()
+        .map(|SignedMessageJson(msg)| msg)
+        .collect::<Vec<_>>();
+    Ok(CidJson(crate::chain::compute_message_root(
+        &MemoryDB::default(),
+        &bls_messages,
+        &secp_messages,
+    )?))
+}
+
+/// Computes the root of the receipts of a tipset the way the headers of its
+/// children commit to them, without storing anything
+pub(in crate::rpc) async fn chain_compute_receipts_root(
+    Params(params): Params<ChainComputeReceiptsRootParams>,
+) -> Result<ChainComputeReceiptsRootResult, JsonRpcError> {
+    let (receipts,) = params;
+    let receipts = receipts
+        .into_iter()
+        .map(|ReceiptJson(receipt)| receipt)
+        .collect::<Vec<_>>();
+    Ok(CidJson(crate::chain::compute_receipts_root(
+        &MemoryDB::default(),
+        &receipts,
+    )?))
+}
+
+pub(in crate::rpc) async fn chain_get_beacon_entry<DB, B>(
+    data: Data<RPCState<DB, B>>,
+    Params(params): Params<ChainGetBeaconEntryParams>,
+) -> Result<ChainGetBeaconEntryResult, JsonRpcError>
+where
+    DB: Blockstore + Clone + Send + Sync + 'static,
+    B: Beacon,
+{
+    let (epoch,) = params;
+    let chain_store = data.state_manager.chain_store();
+    let ts = chain_store.tipset_by_height(epoch, chain_store.heaviest_tipset(), true)?;
+    Ok(BeaconEntryJson(chain_store.latest_beacon_entry(&ts)?))
+}
+
+pub(in crate::rpc) async fn chain_tipset_weight<DB, B, S>(
+    data: Data<RPCState<DB, B>>,
+    Params(params): Params<ChainTipSetWeightParams>,
+) -> Result<ChainTipSetWeightResult, JsonRpcError>
+where
+    DB: Blockstore + Clone + Send + Sync + 'static,
+    B: Beacon,
+    S: Scale,
+{
+    let (TipsetKeysJson(tsk),) = params;
+    let chain_store = data.state_manager.chain_store();
+    let ts = chain_store.tipset_from_keys(&tsk)?;
+    // Computed from the power table of the state rather than read from the
+    // cache of the chain store, so that the cached weights can be audited
+    Ok(S::weight(chain_store.blockstore(), &ts)?.to_string())
+}
+
+pub(in crate::rpc) async fn chain_get_fork_heads<DB, B>(
+    data: Data<RPCState<DB, B>>,
+) -> Result<ChainGetForkHeadsResult, JsonRpcError>
+where
+    DB: Blockstore + Clone + Send + Sync + 'static,
+    B: Beacon,
+{
+    Ok(data
+        .state_manager
+        .chain_store()
+        .fork_heads()
+        .into_iter()
+        .map(|head| ForkHeadJson {
+            tipset: TipsetJson(head.tipset),
+            weight: head.weight,
+        })
+        .collect())
+}
+
+pub(in crate::rpc) async fn chain_prune<DB, B>(
+    data: Data<RPCState<DB, B>>,
+    Params(ChainPruneParams {
+        recent_roots,
+        dry_run,
+    }): Params<ChainPruneParams>,
+) -> Result<ChainPruneResult, JsonRpcError>
+where
+    DB: Blockstore + Clone + Send + Sync + 'static,
+    B: Beacon,
+{
+    let chain_finality = data.state_manager.chain_config().policy.chain_finality;
+    if recent_roots < chain_finality {
+        Err(&format!(
+            "recent-stateroots must be greater than {chain_finality}"
+        ))?;
+    }
+
+    let (tx, rx) = flume::bounded(1);
+    data.gc_event_tx
+        .send_async(GcRequest {
+            recent_state_roots: Some(recent_roots),
+            dry_run,
+            responder: tx,
+        })
+        .await?;
+    Ok(rx.recv_async().await??)
+}
+
+/// Maximum number of blocks loaded by `Forest.ChainSelectNodes`
+const MAX_LOADED_BLOCKS: usize = 1000;
+
+pub(in crate::rpc) async fn chain_get_node<DB, B>(
+    data: Data<RPCState<DB, B>>,
+    Params((path,)): Params<ChainGetNodeParams>,
+) -> Result<ChainGetNodeResult, JsonRpcError>
+where
+    DB: Blockstore + Clone + Send + Sync + 'static,
+    B: Beacon,
+{
+    let (cid, node) = resolve_path(data.state_manager.blockstore(), &path)?;
+    Ok(IpldObject {
+        cid,
+        obj: IpldJson(node),
+    })
+}
+
+pub(in crate::rpc) async fn chain_select_nodes<DB, B>(
+    data: Data<RPCState<DB, B>>,
+    Params((path, selector, limit)): Params<ChainSelectNodesParams>,
+) -> Result<ChainSelectNodesResult, JsonRpcError>
+where
+    DB: Blockstore + Clone + Send + Sync + 'static,
+    B: Beacon,
+{
+    if !selector.is_bounded() {
+        return Err("recursive selectors must have a depth limit".into());
+    }
+    let limit = limit.min(MAX_LOADED_BLOCKS);
+    let store = data.state_manager.blockstore().clone();
+    let (cid, node) = resolve_path(&store, &path)?;
+    let nodes = parking_lot::Mutex::new(Vec::new());
+    let walked = selector
+        .walk_matching(
+            &node,
+            Some(BlockstoreLinkResolver::new(store, limit)),
+            |progress, ipld| {
+                nodes.lock().push(SelectedNode {
+                    path: progress.path().to_string(),
+                    cid: progress.last_block().map(|block| block.link).unwrap_or(cid),
+                    obj: IpldJson(ipld.clone()),
+                });
+                Ok(())
+            },
+        )
+        .await;
+    match walked {
+        Ok(()) => {}
+        // The nodes selected from the blocks loaded so far are returned
+        Err(crate::ipld::Error::Link(e)) if e == LOAD_LIMIT_REACHED => {}
+        Err(e) => return Err(e.into()),
+    }
+    Ok(nodes.into_inner())
+}
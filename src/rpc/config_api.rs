@@ -0,0 +1,38 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use crate::beacon::Beacon;
+use crate::cli_shared::cli::ConfigEvent;
+use crate::rpc_api::{config_api::*, data_types::RPCState};
+use fvm_ipld_blockstore::Blockstore;
+use jsonrpc_v2::{Data, Error as JsonRpcError, Params};
+
+async fn send_config_event<DB: Blockstore, B: Beacon>(
+    data: &RPCState<DB, B>,
+    event: ConfigEvent,
+) -> Result<(), JsonRpcError> {
+    let (tx, rx) = flume::bounded(1);
+    data.config_event_tx.send_async((event, tx)).await?;
+    rx.recv_async().await??;
+    Ok(())
+}
+
+pub(in crate::rpc) async fn config_reload<
+    DB: Blockstore + Clone + Send + Sync + 'static,
+    B: Beacon,
+>(
+    data: Data<RPCState<DB, B>>,
+    Params(_): Params<ConfigReloadParams>,
+) -> Result<ConfigReloadResult, JsonRpcError> {
+    send_config_event(&data, ConfigEvent::Reload).await
+}
+
+pub(in crate::rpc) async fn config_set<
+    DB: Blockstore + Clone + Send + Sync + 'static,
+    B: Beacon,
+>(
+    data: Data<RPCState<DB, B>>,
+    Params((key, value)): Params<ConfigSetParams>,
+) -> Result<ConfigSetResult, JsonRpcError> {
+    send_config_event(&data, ConfigEvent::Set { key, value }).await
+}
@@ -2,6 +2,7 @@
 // SPDX-License-Identifier: Apache-2.0, MIT
 
 use crate::beacon::Beacon;
+use crate::db::rolling::GcRequest;
 use crate::rpc_api::{data_types::RPCState, db_api::*};
 use fvm_ipld_blockstore::Blockstore;
 use jsonrpc_v2::{Data, Error as JsonRpcError, Params};
@@ -11,7 +12,13 @@ pub(in crate::rpc) async fn db_gc<DB: Blockstore + Clone + Send + Sync + 'static
     Params(_): Params<DBGCParams>,
 ) -> Result<DBGCResult, JsonRpcError> {
     let (tx, rx) = flume::bounded(1);
-    data.gc_event_tx.send_async(tx).await?;
+    data.gc_event_tx
+        .send_async(GcRequest {
+            recent_state_roots: None,
+            dry_run: false,
+            responder: tx,
+        })
+        .await?;
     rx.recv_async().await??;
     Ok(())
 }
@@ -0,0 +1,90 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+#![allow(clippy::unused_async)]
+
+use crate::beacon::Beacon;
+use crate::chain::fee_history;
+use crate::eth::{EthAddress, EthBlockNumber, EthFeeHistory, EthUint64};
+use crate::networks::Height;
+use crate::rpc_api::{data_types::RPCState, eth_api::*};
+use crate::shim::address::{Address, Protocol};
+use crate::state_manager::ExecutionLane;
+use anyhow::Context;
+use fvm_ipld_blockstore::Blockstore;
+use jsonrpc_v2::{Data, Error as JsonRpcError, Params};
+
+/// Returns the Ethereum address of an actor: its delegated `f410` address if
+/// it has one, or its masked actor ID otherwise. Addresses other than `f410`
+/// ones are resolved through the init actor at the head of the chain.
+pub(in crate::rpc) async fn filecoin_address_to_eth_address<
+    DB: Blockstore + Clone + Send + Sync + 'static,
+    B: Beacon,
+>(
+    data: Data<RPCState<DB, B>>,
+    Params((address,)): Params<FilecoinAddressToEthAddressParams>,
+) -> Result<FilecoinAddressToEthAddressResult, JsonRpcError> {
+    let address: Address = address.into();
+    if address.protocol() == Protocol::Delegated {
+        return Ok(EthAddress::from_filecoin_address(&address)?);
+    }
+
+    let state_manager = &data.state_manager;
+    let heaviest = state_manager.chain_store().heaviest_tipset();
+    let id_address = state_manager.lookup_required_id(&address, &heaviest)?;
+    let actor = state_manager
+        .get_actor(&id_address, *heaviest.parent_state())?
+        .with_context(|| format!("actor {address} not found"))?;
+    if let Some(delegated) = actor.delegated_address.map(Address::from) {
+        if let Ok(eth_address) = EthAddress::from_filecoin_address(&delegated) {
+            return Ok(eth_address);
+        }
+    }
+    Ok(EthAddress::from_filecoin_address(&id_address)?)
+}
+
+/// Returns the ID address masked by an Ethereum address, or its `f410`
+/// address otherwise.
+pub(in crate::rpc) async fn eth_address_to_filecoin_address(
+    Params((eth_address,)): Params<EthAddressToFilecoinAddressParams>,
+) -> Result<EthAddressToFilecoinAddressResult, JsonRpcError> {
+    Ok(eth_address.to_filecoin_address()?.into())
+}
+
+/// Returns the fee history of the last `count` tipsets, up to the given one,
+/// as `eth_feeHistory` does.
+pub(in crate::rpc) async fn eth_fee_history<
+    DB: Blockstore + Clone + Send + Sync + 'static,
+    B: Beacon,
+>(
+    data: Data<RPCState<DB, B>>,
+    Params((EthUint64(count), newest, percentiles)): Params<EthFeeHistoryParams>,
+) -> Result<EthFeeHistoryResult, JsonRpcError> {
+    let state_manager = &data.state_manager;
+    let chain_store = state_manager.chain_store();
+    let heaviest = chain_store.heaviest_tipset();
+    let newest = match newest {
+        EthBlockNumber::Latest => heaviest,
+        EthBlockNumber::Earliest => chain_store.tipset_by_height(0, heaviest, true)?,
+        EthBlockNumber::Number(epoch) => {
+            if !(0..=heaviest.epoch()).contains(&epoch) {
+                return Err(format!("block {epoch} is not in the chain").into());
+            }
+            chain_store.tipset_by_height(epoch, heaviest, true)?
+        }
+    };
+    // The gas used by the messages of the newest tipset is only known once it
+    // is executed
+    let (_, newest_receipts) = state_manager
+        .tipset_state_in_lane(&newest, ExecutionLane::Rpc)
+        .await?;
+    let history = fee_history(
+        chain_store,
+        newest,
+        newest_receipts,
+        count,
+        &percentiles,
+        state_manager.chain_config().epoch(Height::Smoke),
+        state_manager.chain_config().block_gas_limit,
+    )?;
+    Ok(EthFeeHistory::new(&history, !percentiles.is_empty()))
+}
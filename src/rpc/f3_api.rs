@@ -0,0 +1,34 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use crate::beacon::Beacon;
+use crate::rpc_api::{data_types::RPCState, f3_api::*};
+use anyhow::Context;
+use fvm_ipld_blockstore::Blockstore;
+use jsonrpc_v2::{Data, Error as JsonRpcError, Params};
+
+pub(in crate::rpc) async fn f3_get_certificate<
+    DB: Blockstore + Clone + Send + Sync + 'static,
+    B: Beacon,
+>(
+    data: Data<RPCState<DB, B>>,
+    Params((instance,)): Params<F3GetCertificateParams>,
+) -> Result<F3GetCertificateResult, JsonRpcError> {
+    Ok(data
+        .f3
+        .certificate(instance)
+        .with_context(|| format!("no certificate for F3 instance {instance}"))?)
+}
+
+pub(in crate::rpc) async fn f3_get_latest_certificate<
+    DB: Blockstore + Clone + Send + Sync + 'static,
+    B: Beacon,
+>(
+    data: Data<RPCState<DB, B>>,
+    Params(_): Params<F3GetLatestCertificateParams>,
+) -> Result<F3GetLatestCertificateResult, JsonRpcError> {
+    Ok(data
+        .f3
+        .latest_certificate()
+        .context("no F3 certificate received yet")?)
+}
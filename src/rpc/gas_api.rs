@@ -4,16 +4,18 @@
 
 use crate::beacon::Beacon;
 use crate::blocks::{tipset_keys_json::TipsetKeysJson, TipsetKeys};
-use crate::chain::{BASE_FEE_MAX_CHANGE_DENOM, BLOCK_GAS_TARGET, MINIMUM_BASE_FEE};
+use crate::chain::{fee_history, BASE_FEE_MAX_CHANGE_DENOM, BLOCK_GAS_TARGET, MINIMUM_BASE_FEE};
 use crate::json::{address::json::AddressJson, message::json::MessageJson};
 use crate::message::{ChainMessage, Message as MessageTrait};
+use crate::networks::Height;
 use crate::rpc_api::{
     data_types::{MessageSendSpec, RPCState},
+    errors::ApiError,
     gas_api::*,
 };
 use crate::shim::{econ::TokenAmount, message::Message};
+use crate::state_manager::ExecutionLane;
 use fvm_ipld_blockstore::Blockstore;
-use fvm_shared3::BLOCK_GAS_LIMIT;
 use jsonrpc_v2::{Data, Error as JsonRpcError, Params};
 use num::BigInt;
 use num_traits::{FromPrimitive, Zero};
@@ -183,15 +185,16 @@ where
     B: Beacon,
 {
     let mut msg = msg;
-    msg.set_gas_limit(BLOCK_GAS_LIMIT);
+    msg.set_gas_limit(data.state_manager.chain_config().block_gas_limit);
     msg.set_gas_fee_cap(TokenAmount::from_atto(MINIMUM_BASE_FEE + 1));
     msg.set_gas_premium(TokenAmount::from_atto(1));
 
     let curr_ts = data.state_manager.chain_store().heaviest_tipset();
     let from_a = data
         .state_manager
-        .resolve_to_key_addr(&msg.from.into(), &curr_ts)
-        .await?;
+        .resolve_to_key_addr(&msg.from.into(), &curr_ts, ExecutionLane::Rpc)
+        .await
+        .map_err(ApiError::from)?;
 
     let pending = data.mpool.pending_for(&from_a);
     let prior_messages: Vec<ChainMessage> = pending
@@ -201,12 +204,13 @@ where
     let ts = data.mpool.cur_tipset.lock().clone();
     let res = data
         .state_manager
-        .call_with_gas(&mut ChainMessage::Unsigned(msg), &prior_messages, Some(ts))
-        .await?;
+        .call_with_gas(&mut msg, &prior_messages, Some(ts))
+        .await
+        .map_err(ApiError::from)?;
     match res.msg_rct {
         Some(rct) => {
             if rct.exit_code().value() != 0 {
-                return Ok(-1);
+                return Err(ApiError::execution(rct.exit_code().into(), res.error).into());
             }
             // TODO: Figure out why we always under estimate the gas calculation so we dont
             // need to add 200000 https://github.com/ChainSafe/forest/issues/901
@@ -257,3 +261,31 @@ where
     // TODO: Cap Gas Fee https://github.com/ChainSafe/forest/issues/901
     Ok(msg)
 }
+
+/// Returns the base fees and the premium percentiles of the last `count`
+/// tipsets, up to the given one.
+pub(in crate::rpc) async fn gas_fee_history<DB, B>(
+    data: Data<RPCState<DB, B>>,
+    Params(params): Params<GasFeeHistoryParams>,
+) -> Result<GasFeeHistoryResult, JsonRpcError>
+where
+    DB: Blockstore + Clone + Send + Sync + 'static,
+    B: Beacon,
+{
+    let (count, TipsetKeysJson(tsk), percentiles) = params;
+    let chain_store = data.state_manager.chain_store();
+    let newest = chain_store.tipset_from_keys(&tsk)?;
+    let (_, newest_receipts) = data
+        .state_manager
+        .tipset_state_in_lane(&newest, ExecutionLane::Rpc)
+        .await?;
+    Ok(fee_history(
+        chain_store,
+        newest,
+        newest_receipts,
+        count,
+        &percentiles,
+        data.state_manager.chain_config().epoch(Height::Smoke),
+        data.state_manager.chain_config().block_gas_limit,
+    )?)
+}
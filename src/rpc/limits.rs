@@ -0,0 +1,173 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Limits on the size and the shape of the RPC requests, and on the
+//! parameters of the costly methods, so that a node serving a public gateway
+//! can't be exhausted by a few requests. The requests beyond the limits are
+//! rejected with a JSON-RPC error before they are dispatched.
+
+use crate::shim::clock::ChainEpoch;
+use jsonrpc_v2::Error as JsonRpcError;
+use serde::{Deserialize, Serialize};
+
+use super::rpc_util::get_error_obj;
+
+/// JSON-RPC error code of the requests that can't be parsed
+pub const PARSE_ERROR: i64 = -32700;
+/// JSON-RPC error code of the requests beyond the limits
+pub const INVALID_REQUEST: i64 = -32600;
+/// JSON-RPC error code of the parameters beyond the limits
+pub const INVALID_PARAMS: i64 = -32602;
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct RpcLimitsConfig {
+    /// Maximum size in bytes of the body of an HTTP request, or of a
+    /// WebSocket message
+    pub max_request_body_size: usize,
+    /// Maximum nesting of the arrays and objects of a request
+    pub max_json_depth: usize,
+    /// Maximum number of requests in a batch
+    pub max_batch_size: usize,
+    /// Maximum number of epochs behind the head the methods executing
+    /// tipsets again can go back to, 0 for no limit
+    pub max_lookback_epochs: ChainEpoch,
+}
+
+impl Default for RpcLimitsConfig {
+    fn default() -> Self {
+        Self {
+            max_request_body_size: 10 * 1024 * 1024,
+            max_json_depth: 64,
+            max_batch_size: 100,
+            // A day
+            max_lookback_epochs: 2880,
+        }
+    }
+}
+
+impl RpcLimitsConfig {
+    /// Checks the size and the nesting of a request, returning the reason it
+    /// is rejected.
+    pub fn check_request(&self, request: &[u8]) -> Result<(), String> {
+        if request.len() > self.max_request_body_size {
+            return Err(format!(
+                "Request of {} bytes is larger than the limit of {} bytes",
+                request.len(),
+                self.max_request_body_size
+            ));
+        }
+        if json_depth(request) > self.max_json_depth {
+            return Err(format!(
+                "Request is nested deeper than the limit of {}",
+                self.max_json_depth
+            ));
+        }
+        Ok(())
+    }
+
+    /// Checks the number of requests of a batch, returning the reason it is
+    /// rejected.
+    pub fn check_batch_size(&self, size: usize) -> Result<(), String> {
+        if size == 0 {
+            return Err("Batch is empty".into());
+        }
+        if size > self.max_batch_size {
+            return Err(format!(
+                "Batch of {size} requests is larger than the limit of {}",
+                self.max_batch_size
+            ));
+        }
+        Ok(())
+    }
+
+    /// Checks that `epoch` is within the lookback limit of the methods
+    /// executing tipsets again, and not after the head.
+    pub fn check_lookback(&self, epoch: ChainEpoch, head: ChainEpoch) -> Result<(), JsonRpcError> {
+        if epoch > head {
+            return Err(get_error_obj(
+                INVALID_PARAMS,
+                format!("Epoch {epoch} is after the head at {head}"),
+            ));
+        }
+        if self.max_lookback_epochs > 0 && epoch < head - self.max_lookback_epochs {
+            return Err(get_error_obj(
+                INVALID_PARAMS,
+                format!(
+                    "Epoch {epoch} is more than {} epochs behind the head at {head}",
+                    self.max_lookback_epochs
+                ),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Returns the deepest nesting of the arrays and objects of a JSON document,
+/// without parsing it.
+pub fn json_depth(json: &[u8]) -> usize {
+    let (mut depth, mut max_depth) = (0usize, 0);
+    let (mut in_string, mut escaped) = (false, false);
+    for &byte in json {
+        if in_string {
+            match byte {
+                _ if escaped => escaped = false,
+                b'\\' => escaped = true,
+                b'"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match byte {
+            b'"' => in_string = true,
+            b'[' | b'{' => {
+                depth += 1;
+                max_depth = max_depth.max(depth);
+            }
+            b']' | b'}' => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+    }
+    max_depth
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn depth_of_json() {
+        assert_eq!(json_depth(b"1"), 0);
+        assert_eq!(json_depth(br#"{"params": [[1], [[2]]]}"#), 4);
+        assert_eq!(json_depth(br#"{"params": ["[[[\"{{"]}"#), 2);
+    }
+
+    #[test]
+    fn request_limits() {
+        let limits = RpcLimitsConfig {
+            max_request_body_size: 32,
+            max_json_depth: 2,
+            max_batch_size: 2,
+            max_lookback_epochs: 10,
+        };
+        limits.check_request(br#"{"params": [1]}"#).unwrap();
+        limits.check_request(br#"{"params": [[1]]}"#).unwrap_err();
+        limits.check_request(&[b' '; 33]).unwrap_err();
+
+        limits.check_batch_size(2).unwrap();
+        limits.check_batch_size(0).unwrap_err();
+        limits.check_batch_size(3).unwrap_err();
+
+        assert!(limits.check_lookback(90, 100).is_ok());
+        assert!(limits.check_lookback(89, 100).is_err());
+        assert!(limits.check_lookback(100, 100).is_ok());
+        assert!(limits.check_lookback(101, 100).is_err());
+        assert!(RpcLimitsConfig::default().check_lookback(0, 100).is_ok());
+        assert!(RpcLimitsConfig::default().check_lookback(0, 3000).is_err());
+        let unlimited = RpcLimitsConfig {
+            max_lookback_epochs: 0,
+            ..Default::default()
+        };
+        assert!(unlimited.check_lookback(0, 3000).is_ok());
+    }
+}
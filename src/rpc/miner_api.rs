@@ -0,0 +1,86 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use crate::beacon::Beacon;
+use crate::blocks::{gossip_block::json::GossipBlockJson, BlockHeader, GossipBlock};
+use crate::chain::Scale;
+use crate::networks::Height;
+use crate::rpc_api::{data_types::RPCState, miner_api::*};
+use crate::state_manager::ExecutionLane;
+use fvm_ipld_blockstore::Blockstore;
+use jsonrpc_v2::{Data, Error as JsonRpcError, Params};
+
+/// Assembles the block described by the template on top of its parents and
+/// signs it with the key of the miner's worker, which has to be in the
+/// keystore. The messages of the block are persisted, but the block itself is
+/// neither stored nor published, that is left to the caller.
+pub(in crate::rpc) async fn miner_create_block<DB, B, S>(
+    data: Data<RPCState<DB, B>>,
+    Params(params): Params<MinerCreateBlockParams>,
+) -> Result<MinerCreateBlockResult, JsonRpcError>
+where
+    DB: Blockstore + Clone + Send + Sync + 'static,
+    B: Beacon,
+    S: Scale,
+{
+    let (template,) = params;
+    let state_manager = &data.state_manager;
+    let blockstore = state_manager.blockstore();
+
+    let parent = data.chain_store.tipset_from_keys(&template.parents)?;
+    let (parent_state_root, parent_receipts) = state_manager
+        .tipset_state_in_lane(&parent, ExecutionLane::Rpc)
+        .await?;
+    let work_addr = state_manager.get_miner_work_addr(parent_state_root, &template.miner)?;
+
+    let persisted =
+        crate::chain::persist_block_messages(blockstore, template.messages.iter().collect())?;
+    let parent_base_fee = crate::chain::compute_base_fee(
+        blockstore,
+        &parent,
+        state_manager.chain_config().epoch(Height::Smoke),
+    )?;
+    let parent_weight = S::weight(blockstore, &parent)?;
+
+    let mut header = BlockHeader::builder()
+        .miner_address(template.miner)
+        .parents(template.parents)
+        .ticket(template.ticket)
+        .election_proof(template.eproof)
+        .beacon_entries(template.beacon_values)
+        .epoch(template.epoch)
+        .timestamp(template.timestamp)
+        .winning_post_proof(template.winning_post_proof)
+        .weight(parent_weight)
+        .parent_base_fee(parent_base_fee)
+        .state_root(parent_state_root)
+        .message_receipts(parent_receipts)
+        .messages(persisted.msg_cid)
+        .bls_aggregate(Some(persisted.bls_agg))
+        .build()?;
+
+    let keystore = data.keystore.read().await;
+    keystore.ensure_unlocked()?;
+    let key = crate::key_management::find_key(&work_addr, &keystore)?;
+    header.signature = Some(crate::key_management::sign(
+        *key.key_info.key_type(),
+        key.key_info.private_key(),
+        &header.to_signing_bytes(),
+    )?);
+
+    Ok(GossipBlockJson(GossipBlock {
+        header,
+        bls_messages: persisted.bls_cids,
+        secpk_messages: persisted.secp_cids,
+    }))
+}
+
+pub(in crate::rpc) async fn miner_monitor_summary<DB, B>(
+    data: Data<RPCState<DB, B>>,
+) -> Result<MinerMonitorSummaryResult, JsonRpcError>
+where
+    DB: Blockstore + Clone + Send + Sync + 'static,
+    B: Beacon,
+{
+    Ok(data.miner_monitor.summaries())
+}
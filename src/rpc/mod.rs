@@ -5,8 +5,13 @@ mod auth_api;
 mod beacon_api;
 mod chain_api;
 mod common_api;
+mod config_api;
 mod db_api;
+mod eth_api;
+mod f3_api;
 mod gas_api;
+mod limits;
+mod miner_api;
 mod mpool_api;
 mod net_api;
 mod node_api;
@@ -14,20 +19,26 @@ mod progress_api;
 mod rpc_http_handler;
 mod rpc_util;
 mod rpc_ws_handler;
+mod snapshot_server;
 mod state_api;
 mod sync_api;
 mod wallet_api;
+mod watch_api;
 
 use std::{net::TcpListener, sync::Arc};
 
 use crate::beacon::Beacon;
 use crate::chain::Scale;
 use crate::rpc_api::{
-    auth_api::*, beacon_api::*, chain_api::*, common_api::*, data_types::RPCState, db_api::*,
-    gas_api::*, mpool_api::*, net_api::*, node_api::NODE_STATUS, progress_api::GET_PROGRESS,
-    state_api::*, sync_api::*, wallet_api::*,
+    auth_api::*, beacon_api::*, chain_api::*, common_api::*, config_api::*, data_types::RPCState,
+    db_api::*, eth_api::*, f3_api::*, gas_api::*, miner_api::*, mpool_api::*, net_api::*,
+    node_api::NODE_STATUS, progress_api::*, state_api::*, sync_api::*, wallet_api::*, watch_api::*,
+};
+use axum::{
+    extract::DefaultBodyLimit,
+    routing::{get, post},
+    Extension,
 };
-use axum::routing::{get, post};
 use fvm_ipld_blockstore::Blockstore;
 use jsonrpc_v2::{Data, Error as JSONRPCError, Params, Server};
 use log::info;
@@ -38,9 +49,13 @@ use crate::rpc::{
     common_api::{shutdown, start_time, version},
     rpc_http_handler::rpc_http_handler,
     rpc_ws_handler::rpc_ws_handler,
+    snapshot_server::{snapshot_file_handler, snapshot_handler, SnapshotRoutesState},
     state_api::*,
 };
 
+pub use limits::RpcLimitsConfig;
+pub use snapshot_server::{SnapshotServer, SnapshotServerConfig};
+
 pub type RpcResult<T> = Result<T, JSONRPCError>;
 
 pub async fn start_rpc<DB, B, S>(
@@ -48,6 +63,7 @@ pub async fn start_rpc<DB, B, S>(
     rpc_endpoint: TcpListener,
     forest_version: &'static str,
     shutdown_send: Sender<()>,
+    snapshot_server: Option<SnapshotServer>,
 ) -> Result<(), JSONRPCError>
 where
     DB: Blockstore + Clone + Send + Sync + 'static,
@@ -62,9 +78,10 @@ where
     use wallet_api::*;
 
     let block_delay = state.state_manager.chain_config().block_delay_secs;
+    let limits = Arc::new(state.rpc_limits.clone());
     let rpc_server = Arc::new(
         Server::new()
-            .with_data(Data(state))
+            .with_data(Data(state.clone()))
             // Auth API
             .with_method(AUTH_NEW, auth_new::<DB, B>)
             .with_method(AUTH_VERIFY, auth_verify::<DB, B>)
@@ -76,6 +93,14 @@ where
             .with_method(CHAIN_READ_OBJ, chain_read_obj::<DB, B>)
             .with_method(CHAIN_HAS_OBJ, chain_has_obj::<DB, B>)
             .with_method(CHAIN_GET_BLOCK_MESSAGES, chain_get_block_messages::<DB, B>)
+            .with_method(
+                CHAIN_GET_PARENT_MESSAGES,
+                chain_get_parent_messages::<DB, B>,
+            )
+            .with_method(
+                CHAIN_GET_PARENT_RECEIPTS,
+                chain_get_parent_receipts::<DB, B>,
+            )
             .with_method(
                 CHAIN_GET_TIPSET_BY_HEIGHT,
                 chain_get_tipset_by_height::<DB, B>,
@@ -91,14 +116,63 @@ where
             .with_method(CHAIN_GET_BLOCK, chain_api::chain_get_block::<DB, B>)
             .with_method(CHAIN_GET_NAME, chain_api::chain_get_name::<DB, B>)
             .with_method(CHAIN_SET_HEAD, chain_api::chain_set_head::<DB, B>)
+            .with_method(
+                CHAIN_GET_MESSAGE_INCLUSION_PROOF,
+                chain_api::chain_get_message_inclusion_proof::<DB, B>,
+            )
+            .with_method(
+                CHAIN_GET_RECEIPT_INCLUSION_PROOF,
+                chain_api::chain_get_receipt_inclusion_proof::<DB, B>,
+            )
+            .with_method(
+                CHAIN_COMPUTE_MESSAGE_ROOT,
+                chain_api::chain_compute_message_root,
+            )
+            .with_method(
+                CHAIN_COMPUTE_RECEIPTS_ROOT,
+                chain_api::chain_compute_receipts_root,
+            )
+            .with_method(
+                CHAIN_GET_BEACON_ENTRY,
+                chain_api::chain_get_beacon_entry::<DB, B>,
+            )
+            .with_method(
+                CHAIN_TIPSET_WEIGHT,
+                chain_api::chain_tipset_weight::<DB, B, S>,
+            )
+            .with_method(
+                CHAIN_GET_FORK_HEADS,
+                chain_api::chain_get_fork_heads::<DB, B>,
+            )
+            .with_method(CHAIN_PRUNE, chain_api::chain_prune::<DB, B>)
+            .with_method(CHAIN_GET_NODE, chain_api::chain_get_node::<DB, B>)
+            .with_method(CHAIN_SELECT_NODES, chain_api::chain_select_nodes::<DB, B>)
             // Message Pool API
+            .with_method(MPOOL_GET_NONCE, mpool_get_nonce::<DB, B>)
             .with_method(MPOOL_PENDING, mpool_pending::<DB, B>)
             .with_method(MPOOL_PUSH, mpool_push::<DB, B>)
             .with_method(MPOOL_PUSH_MESSAGE, mpool_push_message::<DB, B>)
+            .with_method(MPOOL_BATCH_PUSH, mpool_batch_push::<DB, B>)
+            .with_method(MPOOL_BATCH_PUSH_MESSAGE, mpool_batch_push_message::<DB, B>)
+            .with_method(MPOOL_CLEAR, mpool_clear::<DB, B>)
+            .with_method(MPOOL_STAT, mpool_stat::<DB, B>)
+            .with_method(MPOOL_SEQUENCE_GAPS, mpool_sequence_gaps::<DB, B>)
+            // Miner API
+            .with_method(
+                MINER_CREATE_BLOCK,
+                miner_api::miner_create_block::<DB, B, S>,
+            )
+            .with_method(
+                MINER_MONITOR_SUMMARY,
+                miner_api::miner_monitor_summary::<DB, B>,
+            )
             // Sync API
             .with_method(SYNC_CHECK_BAD, sync_check_bad::<DB, B>)
             .with_method(SYNC_MARK_BAD, sync_mark_bad::<DB, B>)
+            .with_method(SYNC_UNMARK_BAD, sync_unmark_bad::<DB, B>)
+            .with_method(SYNC_UNMARK_ALL_BAD, sync_unmark_all_bad::<DB, B>)
             .with_method(SYNC_STATE, sync_state::<DB, B>)
+            .with_method(SYNC_CHECKPOINT, sync_checkpoint::<DB, B>)
             // Wallet API
             .with_method(WALLET_BALANCE, wallet_balance::<DB, B>)
             .with_method(WALLET_DEFAULT_ADDRESS, wallet_default_address::<DB, B>)
@@ -109,6 +183,8 @@ where
             .with_method(WALLET_NEW, wallet_new::<DB, B>)
             .with_method(WALLET_SET_DEFAULT, wallet_set_default::<DB, B>)
             .with_method(WALLET_SIGN, wallet_sign::<DB, B>)
+            .with_method(WALLET_SIGN_BATCH, wallet_sign_batch::<DB, B>)
+            .with_method(WALLET_UNLOCK, wallet_unlock::<DB, B>)
             .with_method(WALLET_VERIFY, wallet_verify::<DB, B>)
             // State API
             .with_method(STATE_CALL, state_call::<DB, B>)
@@ -118,14 +194,50 @@ where
             .with_method(STATE_REPLAY, state_replay::<DB, B>)
             .with_method(STATE_MARKET_BALANCE, state_market_balance::<DB, B>)
             .with_method(STATE_MARKET_DEALS, state_market_deals::<DB, B>)
+            .with_method(STATE_HEAD_STATE, state_head_state::<DB, B>)
+            .with_method(STATE_LOOKUP_ID, state_lookup_id::<DB, B>)
+            .with_method(STATE_ACCOUNT_KEY, state_account_key::<DB, B>)
+            .with_method(STATE_MINER_POWER, state_miner_power::<DB, B>)
+            .with_method(STATE_LIST_MINERS, state_list_miners::<DB, B>)
+            .with_method(STATE_BALANCE_HISTORY, state_balance_history::<DB, B>)
+            .with_method(
+                STATE_BALANCE_HISTORY_RECOMPUTE,
+                state_balance_history_recompute::<DB, B>,
+            )
+            .with_method(
+                STATE_MINER_DISPUTABLE_POSTS,
+                state_miner_disputable_posts::<DB, B>,
+            )
+            .with_method(
+                STATE_DISPUTE_POST_MESSAGE,
+                state_dispute_post_message::<DB, B>,
+            )
             .with_method(STATE_GET_RECEIPT, state_get_receipt::<DB, B>)
             .with_method(STATE_WAIT_MSG, state_wait_msg::<DB, B>)
             .with_method(STATE_FETCH_ROOT, state_fetch_root::<DB, B>)
+            .with_method(STATE_EXECUTION_TRACE, state_execution_trace::<DB, B>)
+            .with_method(
+                STATE_LIST_EXECUTION_TRACES,
+                state_list_execution_traces::<DB, B>,
+            )
+            .with_method(STATE_COMPUTE, state_compute::<DB, B>)
+            .with_method(STATE_COMPUTE_MESSAGES, state_compute_messages::<DB, B>)
+            .with_method(
+                STATE_GET_RANDOMNESS_FROM_TICKETS,
+                state_get_randomness_from_tickets::<DB, B>,
+            )
+            .with_method(
+                STATE_GET_RANDOMNESS_FROM_BEACON,
+                state_get_randomness_from_beacon::<DB, B>,
+            )
+            .with_method(STATE_DECODE_PARAMS, state_decode_params::<DB, B>)
+            .with_method(STATE_ENCODE_PARAMS, state_encode_params::<DB, B>)
             // Gas API
             .with_method(GAS_ESTIMATE_FEE_CAP, gas_estimate_fee_cap::<DB, B>)
             .with_method(GAS_ESTIMATE_GAS_LIMIT, gas_estimate_gas_limit::<DB, B>)
             .with_method(GAS_ESTIMATE_GAS_PREMIUM, gas_estimate_gas_premium::<DB, B>)
             .with_method(GAS_ESTIMATE_MESSAGE_GAS, gas_estimate_message_gas::<DB, B>)
+            .with_method(GAS_FEE_HISTORY, gas_fee_history::<DB, B>)
             // Common API
             .with_method(VERSION, move || version(block_delay, forest_version))
             .with_method(SHUTDOWN, move || shutdown(shutdown_send.clone()))
@@ -133,21 +245,77 @@ where
             // Net API
             .with_method(NET_ADDRS_LISTEN, net_api::net_addrs_listen::<DB, B>)
             .with_method(NET_PEERS, net_api::net_peers::<DB, B>)
+            .with_method(NET_INFO, net_api::net_info::<DB, B>)
+            .with_method(NET_AUTO_NAT_STATUS, net_api::net_auto_nat_status::<DB, B>)
             .with_method(NET_CONNECT, net_api::net_connect::<DB, B>)
             .with_method(NET_DISCONNECT, net_api::net_disconnect::<DB, B>)
+            .with_method(NET_ADD_PEER, net_api::net_add_peer::<DB, B>)
+            .with_method(NET_REMOVE_PEER, net_api::net_remove_peer::<DB, B>)
+            .with_method(NET_BANDWIDTH_STATS, net_api::net_bandwidth_stats::<DB, B>)
+            .with_method(
+                NET_BANDWIDTH_STATS_BY_PEER,
+                net_api::net_bandwidth_stats_by_peer::<DB, B>,
+            )
+            .with_method(
+                NET_BANDWIDTH_STATS_BY_PROTOCOL,
+                net_api::net_bandwidth_stats_by_protocol::<DB, B>,
+            )
             // DB API
             .with_method(DB_GC, db_api::db_gc::<DB, B>)
+            // Config API
+            .with_method(CONFIG_RELOAD, config_api::config_reload::<DB, B>)
+            .with_method(CONFIG_SET, config_api::config_set::<DB, B>)
+            // Eth API
+            .with_method(
+                FILECOIN_ADDRESS_TO_ETH_ADDRESS,
+                eth_api::filecoin_address_to_eth_address::<DB, B>,
+            )
+            .with_method(
+                ETH_ADDRESS_TO_FILECOIN_ADDRESS,
+                eth_api::eth_address_to_filecoin_address,
+            )
+            .with_method(ETH_FEE_HISTORY, eth_api::eth_fee_history::<DB, B>)
+            .with_method(ETH_FEE_HISTORY_ALIAS, eth_api::eth_fee_history::<DB, B>)
+            // F3 API
+            .with_method(F3_GET_CERTIFICATE, f3_api::f3_get_certificate::<DB, B>)
+            .with_method(
+                F3_GET_LATEST_CERTIFICATE,
+                f3_api::f3_get_latest_certificate::<DB, B>,
+            )
             // Progress API
             .with_method(GET_PROGRESS, progress_api::get_progress)
+            .with_method(OPERATION_STATUS, progress_api::operation_status)
+            .with_method(OPERATION_CANCEL, progress_api::operation_cancel)
+            // Watch API
+            .with_method(WATCH_ADDRESS, watch_api::watch_address::<DB, B>)
+            .with_method(UNWATCH_ADDRESS, watch_api::unwatch_address::<DB, B>)
+            .with_method(WATCH_LIST, watch_api::watch_list::<DB, B>)
+            .with_method(WATCH_EVENTS, watch_api::watch_events::<DB, B>)
             // Node API
             .with_method(NODE_STATUS, node_api::node_status::<DB, B>)
             .finish_unwrapped(),
     );
 
-    let app = axum::Router::new()
+    let mut app = axum::Router::new()
         .route("/rpc/v0", get(rpc_ws_handler))
         .route("/rpc/v0", post(rpc_http_handler))
-        .with_state(rpc_server);
+        .layer(DefaultBodyLimit::max(limits.max_request_body_size))
+        .layer(Extension(limits))
+        .layer(Extension(state.address_watcher.clone()))
+        .with_state(rpc_server.clone());
+    if let Some(server) = snapshot_server {
+        info!("Serving snapshots at /snapshot");
+        app = app.merge(
+            axum::Router::new()
+                .route("/snapshot", get(snapshot_handler::<DB, B>))
+                .route("/snapshot/:filename", get(snapshot_file_handler::<DB, B>))
+                .with_state(Arc::new(SnapshotRoutesState {
+                    server,
+                    rpc_state: state,
+                    rpc_server,
+                })),
+        );
+    }
 
     info!("Ready for RPC connections");
     let server = axum::Server::from_tcp(rpc_endpoint)?.serve(app.into_make_service());
@@ -2,24 +2,47 @@
 // SPDX-License-Identifier: Apache-2.0, MIT
 #![allow(clippy::unused_async)]
 
-use std::convert::TryFrom;
+use std::{convert::TryFrom, sync::Arc};
 
 use crate::beacon::Beacon;
-use crate::blocks::TipsetKeys;
+use crate::blocks::{Tipset, TipsetKeys};
 use crate::json::{
+    address::json::AddressJson,
     cid::{vec::CidJsonVec, CidJson},
     message::json::MessageJson,
     signed_message::json::SignedMessageJson,
 };
+use crate::key_management::KeyStore;
 use crate::message::SignedMessage;
-use crate::rpc_api::{data_types::RPCState, mpool_api::*};
-use crate::shim::address::Protocol;
-use ahash::{HashSet, HashSetExt};
+use crate::rpc_api::{
+    data_types::{BatchItemResult, MessageSendSpec, RPCState},
+    mpool_api::*,
+};
+use crate::shim::{
+    address::{Address, Protocol},
+    message::Message,
+};
+use crate::state_manager::ExecutionLane;
+use ahash::{HashMap, HashMapExt, HashSet, HashSetExt};
 use fvm_ipld_blockstore::Blockstore;
 use fvm_ipld_encoding::Cbor;
 use jsonrpc_v2::{Data, Error as JsonRpcError, Params};
 
-use super::gas_api::estimate_message_gas;
+use super::{gas_api::estimate_message_gas, rpc_util::get_error_message};
+
+/// Return the sequence of the next message from the given address, including
+/// the messages pending in `mpool`
+pub(in crate::rpc) async fn mpool_get_nonce<DB, B>(
+    data: Data<RPCState<DB, B>>,
+    Params(params): Params<MpoolGetNonceParams>,
+) -> Result<MpoolGetNonceResult, JsonRpcError>
+where
+    DB: Blockstore + Clone + Send + Sync + 'static,
+    B: Beacon,
+{
+    let (AddressJson(address),) = params;
+    Ok(data.mpool.get_sequence(&address)?)
+}
 
 /// Return `Vec` of pending messages in `mpool`
 pub(in crate::rpc) async fn mpool_pending<DB, B>(
@@ -111,10 +134,11 @@ where
     let from = umsg.from;
 
     let mut keystore = data.keystore.as_ref().write().await;
+    keystore.ensure_unlocked()?;
     let heaviest_tipset = data.state_manager.chain_store().heaviest_tipset();
     let key_addr = data
         .state_manager
-        .resolve_to_key_addr(&from.into(), &heaviest_tipset)
+        .resolve_to_key_addr(&from.into(), &heaviest_tipset, ExecutionLane::Rpc)
         .await?;
 
     if umsg.sequence != 0 {
@@ -132,19 +156,201 @@ where
     }
     let nonce = data.mpool.get_sequence(&from.into())?;
     umsg.sequence = nonce;
-    let key = crate::key_management::Key::try_from(crate::key_management::try_find(
-        &key_addr,
-        &mut keystore,
-    )?)?;
+    let smsg = sign_message(umsg, &key_addr, &mut keystore)?;
+
+    data.mpool.as_ref().push(smsg.clone()).await?;
+
+    Ok(SignedMessageJson(smsg))
+}
+
+/// Sign a message with the key of `key_addr`
+fn sign_message(
+    umsg: Message,
+    key_addr: &Address,
+    keystore: &mut KeyStore,
+) -> Result<SignedMessage, JsonRpcError> {
+    let key =
+        crate::key_management::Key::try_from(crate::key_management::try_find(key_addr, keystore)?)?;
     let sig = crate::key_management::sign(
         *key.key_info.key_type(),
         key.key_info.private_key(),
         umsg.cid().unwrap().to_bytes().as_slice(),
     )?;
 
-    let smsg = SignedMessage::new_from_parts(umsg, sig)?;
+    Ok(SignedMessage::new_from_parts(umsg, sig)?)
+}
 
-    data.mpool.as_ref().push(smsg.clone()).await?;
+/// Estimate the gas of a message of a batch and sign it, with the sequence
+/// following the one of the previous message of its sender in the batch
+async fn prepare_batch_message<DB, B>(
+    data: &Data<RPCState<DB, B>>,
+    umsg: Message,
+    spec: Option<MessageSendSpec>,
+    heaviest_tipset: &Arc<Tipset>,
+    keystore: &mut KeyStore,
+    sequences: &mut HashMap<Address, u64>,
+) -> Result<SignedMessage, JsonRpcError>
+where
+    DB: Blockstore + Clone + Send + Sync + 'static,
+    B: Beacon,
+{
+    let from: Address = umsg.from.into();
+    if umsg.sequence != 0 {
+        return Err(
+            "Expected nonce for MpoolBatchPushMessage is 0, and will be calculated for you.".into(),
+        );
+    }
+    let key_addr = data
+        .state_manager
+        .resolve_to_key_addr(&from, heaviest_tipset, ExecutionLane::Rpc)
+        .await?;
+    let mut umsg = estimate_message_gas::<DB, B>(data, umsg, spec, Default::default()).await?;
+    if umsg.gas_premium > umsg.gas_fee_cap {
+        return Err("After estimation, gas premium is greater than gas fee cap".into());
+    }
+    if from.protocol() == Protocol::ID {
+        umsg.from = key_addr.into();
+    }
 
-    Ok(SignedMessageJson(smsg))
+    let sender: Address = umsg.from.into();
+    umsg.sequence = match sequences.get(&sender) {
+        Some(sequence) => sequence + 1,
+        None => data.mpool.get_sequence(&sender)?,
+    };
+    let smsg = sign_message(umsg, &key_addr, keystore)?;
+    sequences.insert(sender, smsg.sequence());
+    Ok(smsg)
+}
+
+/// Error of the valid messages of a batch that was rejected
+const BATCH_REJECTED: &str = "not added: the batch has invalid messages";
+
+/// Results of a rejected batch, from the errors of its messages
+fn rejected_batch<T>(errors: Vec<Option<String>>) -> Vec<BatchItemResult<T>> {
+    errors
+        .into_iter()
+        .map(|error| BatchItemResult::err(error.as_deref().unwrap_or(BATCH_REJECTED)))
+        .collect()
+}
+
+/// Add a batch of `SignedMessage` to `mpool`, either all of them or none, and
+/// return the CID or the error of each message
+pub(in crate::rpc) async fn mpool_batch_push<DB, B>(
+    data: Data<RPCState<DB, B>>,
+    Params(params): Params<MpoolBatchPushParams>,
+) -> Result<MpoolBatchPushResult, JsonRpcError>
+where
+    DB: Blockstore + Clone + Send + Sync + 'static,
+    B: Beacon,
+{
+    let (smsgs,) = params;
+    let smsgs = smsgs
+        .into_iter()
+        .map(|SignedMessageJson(smsg)| smsg)
+        .collect();
+
+    Ok(match data.mpool.as_ref().push_batch(smsgs).await? {
+        Ok(cids) => cids
+            .into_iter()
+            .map(|cid| BatchItemResult::ok(CidJson(cid)))
+            .collect(),
+        Err(errors) => rejected_batch(
+            errors
+                .into_iter()
+                .map(|error| error.map(|e| e.to_string()))
+                .collect(),
+        ),
+    })
+}
+
+/// Sign a batch of `UnsignedMessage`, with the next sequences of their
+/// senders, and add them to `mpool`, either all of them or none. Return the
+/// `SignedMessage` or the error of each message.
+pub(in crate::rpc) async fn mpool_batch_push_message<DB, B>(
+    data: Data<RPCState<DB, B>>,
+    Params(params): Params<MpoolBatchPushMessageParams>,
+) -> Result<MpoolBatchPushMessageResult, JsonRpcError>
+where
+    DB: Blockstore + Clone + Send + Sync + 'static,
+    B: Beacon,
+{
+    let (umsgs, spec) = params;
+
+    // The keystore stays locked until the messages are pushed, for the
+    // sequences not to be given twice
+    let mut keystore = data.keystore.as_ref().write().await;
+    keystore.ensure_unlocked()?;
+    let heaviest_tipset = data.state_manager.chain_store().heaviest_tipset();
+    let mut sequences: HashMap<Address, u64> = HashMap::new();
+    let mut signed = Vec::with_capacity(umsgs.len());
+    for MessageJson(umsg) in umsgs {
+        let smsg = prepare_batch_message(
+            &data,
+            umsg,
+            spec.clone(),
+            &heaviest_tipset,
+            &mut keystore,
+            &mut sequences,
+        )
+        .await;
+        signed.push(smsg.map_err(|e| get_error_message(&e)));
+    }
+
+    if signed.iter().any(Result::is_err) {
+        return Ok(rejected_batch(
+            signed.into_iter().map(|smsg| smsg.err()).collect(),
+        ));
+    }
+    let smsgs: Vec<_> = signed.into_iter().flatten().collect();
+    Ok(match data.mpool.as_ref().push_batch(smsgs.clone()).await? {
+        Ok(_) => smsgs
+            .into_iter()
+            .map(|smsg| BatchItemResult::ok(SignedMessageJson(smsg)))
+            .collect(),
+        Err(errors) => rejected_batch(
+            errors
+                .into_iter()
+                .map(|error| error.map(|e| e.to_string()))
+                .collect(),
+        ),
+    })
+}
+
+/// Remove the pending messages from `mpool`, the local ones too if asked to
+pub(in crate::rpc) async fn mpool_clear<DB, B>(
+    data: Data<RPCState<DB, B>>,
+    Params(params): Params<MpoolClearParams>,
+) -> Result<MpoolClearResult, JsonRpcError>
+where
+    DB: Blockstore + Clone + Send + Sync + 'static,
+    B: Beacon,
+{
+    let (local,) = params;
+    data.mpool.clear(local);
+    Ok(())
+}
+
+/// Return the statistics of the pending messages of each sender in `mpool`
+pub(in crate::rpc) async fn mpool_stat<DB, B>(
+    data: Data<RPCState<DB, B>>,
+) -> Result<MpoolStatResult, JsonRpcError>
+where
+    DB: Blockstore + Clone + Send + Sync + 'static,
+    B: Beacon,
+{
+    Ok(data.mpool.stats()?)
+}
+
+/// Return the gaps in the sequences of the pending messages of a sender in
+/// `mpool`
+pub(in crate::rpc) async fn mpool_sequence_gaps<DB, B>(
+    data: Data<RPCState<DB, B>>,
+    Params(params): Params<MpoolSequenceGapsParams>,
+) -> Result<MpoolSequenceGapsResult, JsonRpcError>
+where
+    DB: Blockstore + Clone + Send + Sync + 'static,
+    B: Beacon,
+{
+    let (AddressJson(address),) = params;
+    Ok(data.mpool.sequence_gaps(&address)?)
 }
@@ -4,9 +4,9 @@
 use std::str::FromStr;
 
 use crate::beacon::Beacon;
-use crate::libp2p::{NetRPCMethods, NetworkMessage, PeerId};
+use crate::libp2p::{Multiaddr, NetRPCMethods, NetworkMessage, PeerId};
 use crate::rpc_api::{
-    data_types::{AddrInfo, RPCState},
+    data_types::{AddrInfo, NatStatus, NetInfo, PeerScoreInfo, RPCState},
     net_api::*,
 };
 use futures::channel::oneshot;
@@ -31,6 +31,7 @@ pub(in crate::rpc) async fn net_addrs_listen<
     Ok(AddrInfo {
         id: id.to_string(),
         addrs,
+        score: None,
     })
 }
 
@@ -44,18 +45,76 @@ pub(in crate::rpc) async fn net_peers<DB: Blockstore + Clone + Send + Sync + 'st
 
     data.network_send.send_async(req).await?;
     let peer_addresses = rx.await?;
+    let peer_stats = data.peer_manager.peer_stats().await;
 
     let connections = peer_addresses
         .into_iter()
         .map(|(id, addrs)| AddrInfo {
             id: id.to_string(),
             addrs,
+            score: peer_stats.get(&id).map(|stats| stats.score),
         })
         .collect();
 
     Ok(connections)
 }
 
+pub(in crate::rpc) async fn net_info<DB: Blockstore + Clone + Send + Sync + 'static, B: Beacon>(
+    data: Data<RPCState<DB, B>>,
+) -> Result<NetInfoResult, JsonRpcError> {
+    let (tx, rx) = oneshot::channel();
+    let req = NetworkMessage::JSONRPCRequest {
+        method: NetRPCMethods::NetPeers(tx),
+    };
+
+    data.network_send.send_async(req).await?;
+    let num_connected = rx.await?.len();
+
+    let mut peers: Vec<_> = data
+        .peer_manager
+        .peer_stats()
+        .await
+        .into_iter()
+        .map(|(id, stats)| PeerScoreInfo {
+            id: id.to_string(),
+            score: stats.score,
+            successes: stats.successes,
+            failures: stats.failures,
+            average_time_ms: stats.average_time.as_millis() as u64,
+            protected: stats.protected,
+        })
+        .collect();
+    peers.sort_by(|a, b| b.score.total_cmp(&a.score));
+
+    Ok(NetInfo {
+        num_connected,
+        num_full_peers: peers.len(),
+        num_bad_peers: data.peer_manager.bad_peer_count().await,
+        num_protected: peers.iter().filter(|p| p.protected).count(),
+        peers,
+    })
+}
+
+pub(in crate::rpc) async fn net_auto_nat_status<
+    DB: Blockstore + Clone + Send + Sync + 'static,
+    B: Beacon,
+>(
+    data: Data<RPCState<DB, B>>,
+) -> Result<NetAutoNatStatusResult, JsonRpcError> {
+    let (tx, rx) = oneshot::channel();
+    let req = NetworkMessage::JSONRPCRequest {
+        method: NetRPCMethods::NetAutoNatStatus(tx),
+    };
+
+    data.network_send.send_async(req).await?;
+    let (reachability, public_addr) = rx.await?;
+
+    Ok(NatStatus {
+        reachability: reachability as i32,
+        public_addr: public_addr.map(|addr| addr.to_string()).unwrap_or_default(),
+    })
+}
+
 pub(in crate::rpc) async fn net_connect<
     DB: Blockstore + Clone + Send + Sync + 'static,
     B: Beacon,
@@ -63,7 +122,7 @@ pub(in crate::rpc) async fn net_connect<
     data: Data<RPCState<DB, B>>,
     Params(params): Params<NetConnectParams>,
 ) -> Result<NetConnectResult, JsonRpcError> {
-    let (AddrInfo { id, addrs },) = params;
+    let (AddrInfo { id, addrs, .. },) = params;
     let (_, id) = multibase::decode(format!("{}{}", "z", id))?;
     let peer_id = PeerId::from_bytes(&id)?;
 
@@ -103,3 +162,92 @@ pub(in crate::rpc) async fn net_disconnect<
 
     Ok(())
 }
+
+pub(in crate::rpc) async fn net_add_peer<
+    DB: Blockstore + Clone + Send + Sync + 'static,
+    B: Beacon,
+>(
+    data: Data<RPCState<DB, B>>,
+    Params(params): Params<NetAddPeerParams>,
+) -> Result<NetAddPeerResult, JsonRpcError> {
+    let (addr,) = params;
+    let addr = Multiaddr::from_str(&addr)?;
+
+    let (tx, rx) = oneshot::channel();
+    let req = NetworkMessage::JSONRPCRequest {
+        method: NetRPCMethods::NetAddPeer(tx, addr),
+    };
+
+    data.network_send.send_async(req).await?;
+    rx.await??;
+
+    Ok(())
+}
+
+pub(in crate::rpc) async fn net_remove_peer<
+    DB: Blockstore + Clone + Send + Sync + 'static,
+    B: Beacon,
+>(
+    data: Data<RPCState<DB, B>>,
+    Params(params): Params<NetRemovePeerParams>,
+) -> Result<NetRemovePeerResult, JsonRpcError> {
+    let (id,) = params;
+    let peer_id = PeerId::from_str(&id)?;
+
+    let (tx, rx) = oneshot::channel();
+    let req = NetworkMessage::JSONRPCRequest {
+        method: NetRPCMethods::NetRemovePeer(tx, peer_id),
+    };
+
+    data.network_send.send_async(req).await?;
+    Ok(rx.await??)
+}
+
+pub(in crate::rpc) async fn net_bandwidth_stats<
+    DB: Blockstore + Clone + Send + Sync + 'static,
+    B: Beacon,
+>(
+    data: Data<RPCState<DB, B>>,
+) -> Result<NetBandwidthStatsResult, JsonRpcError> {
+    let (tx, rx) = oneshot::channel();
+    let req = NetworkMessage::JSONRPCRequest {
+        method: NetRPCMethods::NetBandwidthStats(tx),
+    };
+
+    data.network_send.send_async(req).await?;
+    Ok(rx.await?)
+}
+
+pub(in crate::rpc) async fn net_bandwidth_stats_by_peer<
+    DB: Blockstore + Clone + Send + Sync + 'static,
+    B: Beacon,
+>(
+    data: Data<RPCState<DB, B>>,
+) -> Result<NetBandwidthStatsByPeerResult, JsonRpcError> {
+    let (tx, rx) = oneshot::channel();
+    let req = NetworkMessage::JSONRPCRequest {
+        method: NetRPCMethods::NetBandwidthStatsByPeer(tx),
+    };
+
+    data.network_send.send_async(req).await?;
+    let stats = rx.await?;
+    Ok(stats
+        .into_iter()
+        .map(|(peer_id, stats)| (peer_id.to_string(), stats))
+        .collect())
+}
+
+pub(in crate::rpc) async fn net_bandwidth_stats_by_protocol<
+    DB: Blockstore + Clone + Send + Sync + 'static,
+    B: Beacon,
+>(
+    data: Data<RPCState<DB, B>>,
+) -> Result<NetBandwidthStatsByProtocolResult, JsonRpcError> {
+    let (tx, rx) = oneshot::channel();
+    let req = NetworkMessage::JSONRPCRequest {
+        method: NetRPCMethods::NetBandwidthStatsByProtocol(tx),
+    };
+
+    data.network_send.send_async(req).await?;
+    Ok(rx.await?)
+}
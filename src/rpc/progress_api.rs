@@ -8,7 +8,11 @@ use std::sync::atomic;
 use crate::ipld::{
     ProgressBarCurrentTotalPair, WALK_SNAPSHOT_PROGRESS_DB_GC, WALK_SNAPSHOT_PROGRESS_EXPORT,
 };
-use crate::rpc_api::progress_api::{GetProgressParams, GetProgressResult, GetProgressType};
+use crate::rpc_api::progress_api::{
+    GetProgressParams, GetProgressResult, GetProgressType, OperationCancelParams,
+    OperationCancelResult, OperationStatusResult,
+};
+use crate::utils::operations;
 
 use crate::rpc::*;
 
@@ -25,3 +29,14 @@ pub(in crate::rpc) async fn get_progress(
         tracker.1.load(atomic::Ordering::Relaxed),
     ))
 }
+
+pub(in crate::rpc) async fn operation_status() -> RpcResult<OperationStatusResult> {
+    Ok(operations::statuses())
+}
+
+pub(in crate::rpc) async fn operation_cancel(
+    Params((id,)): Params<OperationCancelParams>,
+) -> RpcResult<OperationCancelResult> {
+    operations::cancel(id)?;
+    Ok(())
+}
@@ -1,43 +1,149 @@
 // Copyright 2019-2023 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
-use crate::rpc_api::data_types::JsonRpcServerState;
-use axum::response::IntoResponse;
-use http::{HeaderMap, StatusCode};
+use std::sync::Arc;
+
+use crate::rpc_api::{data_types::JsonRpcServerState, errors::INTERNAL_ERROR_CODE};
+use axum::{
+    body::Bytes,
+    extract::{rejection::BytesRejection, Extension},
+    response::IntoResponse,
+};
+use http::{HeaderMap, HeaderValue, StatusCode};
 use jsonrpc_v2::RequestObject as JsonRpcRequestObject;
+use serde::Deserialize;
+
+use crate::rpc::{
+    limits::{RpcLimitsConfig, INVALID_REQUEST, PARSE_ERROR},
+    rpc_util::{
+        call_rpc_str, check_permissions, get_auth_header, get_error_str, get_error_str_with_id,
+        is_streaming_method, permission_error_code,
+    },
+};
 
-use crate::rpc::rpc_util::{call_rpc_str, check_permissions, get_auth_header, is_streaming_method};
+/// A single request, or a batch of requests answered with an array of
+/// responses
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RpcRequest {
+    Single(JsonRpcRequestObject),
+    Batch(Vec<JsonRpcRequestObject>),
+}
 
 pub async fn rpc_http_handler(
     headers: HeaderMap,
     axum::extract::State(rpc_server): axum::extract::State<JsonRpcServerState>,
-    axum::Json(rpc_call): axum::Json<JsonRpcRequestObject>,
+    Extension(limits): Extension<Arc<RpcLimitsConfig>>,
+    body: Result<Bytes, BytesRejection>,
 ) -> impl IntoResponse {
     let response_headers = [("content-type", "application/json-rpc;charset=utf-8")];
-    if let Err((code, msg)) = check_permissions(
+    let body = match body {
+        Ok(body) => body,
+        Err(rejection) => {
+            return (
+                rejection.status(),
+                response_headers,
+                get_error_str(INVALID_REQUEST, rejection.body_text()),
+            )
+        }
+    };
+    if let Err(msg) = limits.check_request(&body) {
+        return (
+            StatusCode::BAD_REQUEST,
+            response_headers,
+            get_error_str(INVALID_REQUEST, msg),
+        );
+    }
+    let request = match serde_json::from_slice(&body) {
+        Ok(request) => request,
+        Err(e) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                response_headers,
+                get_error_str(PARSE_ERROR, format!("Invalid request: {e}")),
+            )
+        }
+    };
+
+    let authorization_header = get_auth_header(headers);
+    match request {
+        RpcRequest::Single(rpc_call) => {
+            match handle_rpc_call(&rpc_server, authorization_header, rpc_call).await {
+                Ok(result) => (StatusCode::OK, response_headers, result),
+                Err((status, error)) => (status, response_headers, error),
+            }
+        }
+        RpcRequest::Batch(rpc_calls) => {
+            if let Err(msg) = limits.check_batch_size(rpc_calls.len()) {
+                return (
+                    StatusCode::BAD_REQUEST,
+                    response_headers,
+                    get_error_str(INVALID_REQUEST, msg),
+                );
+            }
+            let mut responses = Vec::with_capacity(rpc_calls.len());
+            for rpc_call in rpc_calls {
+                let response = match handle_rpc_call(
+                    &rpc_server,
+                    authorization_header.clone(),
+                    rpc_call,
+                )
+                .await
+                {
+                    Ok(result) | Err((_, result)) => result,
+                };
+                // Notifications get no response
+                if response != "null" {
+                    responses.push(response);
+                }
+            }
+            (
+                StatusCode::OK,
+                response_headers,
+                format!("[{}]", responses.join(",")),
+            )
+        }
+    }
+}
+
+/// Checks the permissions of a call and dispatches it, returning its response,
+/// or the HTTP status of its failure and the JSON-RPC error object echoing the
+/// id of the call
+async fn handle_rpc_call(
+    rpc_server: &JsonRpcServerState,
+    authorization_header: Option<HeaderValue>,
+    rpc_call: JsonRpcRequestObject,
+) -> Result<String, (StatusCode, String)> {
+    let id = rpc_call.id_ref().cloned().unwrap_or(jsonrpc_v2::Id::Null);
+    let error = |status: StatusCode, code: i64, msg: String| {
+        (status, get_error_str_with_id(code, msg, id.clone()))
+    };
+
+    if let Err((status, msg)) = check_permissions(
         rpc_server.clone(),
         rpc_call.method_ref(),
-        get_auth_header(headers),
+        authorization_header,
     )
     .await
     {
-        return (code, response_headers, msg);
+        return Err(error(status, permission_error_code(status), msg));
     }
 
     if is_streaming_method(rpc_call.method_ref()) {
-        return (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            response_headers,
+        return Err(error(
+            StatusCode::BAD_REQUEST,
+            INVALID_REQUEST,
             "This endpoint cannot handle streaming methods".into(),
-        );
+        ));
     }
 
-    match call_rpc_str(rpc_server.clone(), rpc_call).await {
-        Ok(result) => (StatusCode::OK, response_headers, result),
-        Err(err) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            response_headers,
-            err.to_string(),
-        ),
-    }
+    call_rpc_str(rpc_server.clone(), rpc_call)
+        .await
+        .map_err(|err| {
+            error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                INTERNAL_ERROR_CODE,
+                err.to_string(),
+            )
+        })
 }
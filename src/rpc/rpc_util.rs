@@ -1,7 +1,10 @@
 // Copyright 2019-2023 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
-use crate::rpc_api::{auth_api::*, check_access, data_types::JsonRpcServerState, ACCESS_MAP};
+use crate::rpc_api::{
+    auth_api::*, check_access, data_types::JsonRpcServerState, errors::INTERNAL_ERROR_CODE,
+    watch_api::WATCH_SUBSCRIBE, ACCESS_MAP,
+};
 use http::{HeaderMap, HeaderValue, StatusCode};
 use log::{debug, error};
 use serde::de::DeserializeOwned;
@@ -18,22 +21,58 @@ pub fn get_error_obj(code: i64, message: String) -> jsonrpc_v2::Error {
     }
 }
 
+/// The message of an RPC error, to report it within a result
+pub fn get_error_message(error: &jsonrpc_v2::Error) -> String {
+    match error {
+        jsonrpc_v2::Error::Full { message, .. } => message.clone(),
+        jsonrpc_v2::Error::Provided { message, .. } => message.to_string(),
+    }
+}
+
 pub fn get_error_res(code: i64, message: String) -> jsonrpc_v2::ResponseObject {
+    get_error_res_with_id(code, message, jsonrpc_v2::Id::Null)
+}
+
+/// The error response to the request with the given id
+pub fn get_error_res_with_id(
+    code: i64,
+    message: String,
+    id: jsonrpc_v2::Id,
+) -> jsonrpc_v2::ResponseObject {
     jsonrpc_v2::ResponseObject::Error {
         jsonrpc: jsonrpc_v2::V2,
         error: get_error_obj(code, message),
-        id: jsonrpc_v2::Id::Null,
+        id,
     }
 }
 
 pub fn get_error_str(code: i64, message: String) -> String {
-    match serde_json::to_string(&get_error_res(code, message)) {
+    get_error_str_with_id(code, message, jsonrpc_v2::Id::Null)
+}
+
+/// The serialized error response to the request with the given id
+pub fn get_error_str_with_id(code: i64, message: String, id: jsonrpc_v2::Id) -> String {
+    match serde_json::to_string(&get_error_res_with_id(code, message, id)) {
         Ok(err_str) => err_str,
         Err(err) => format!("Failed to serialize error data. Error was: {err}"),
     }
 }
 
-const STREAMING_METHODS: [&str; 0] = [];
+/// JSON-RPC error code of the methods that don't exist
+pub const METHOD_NOT_FOUND: i64 = -32601;
+/// Error code of the calls without the permission of the method
+pub const PERMISSION_DENIED: i64 = -32001;
+
+/// The JSON-RPC error code of a failure of [`check_permissions`]
+pub fn permission_error_code(status: StatusCode) -> i64 {
+    match status {
+        StatusCode::FORBIDDEN => PERMISSION_DENIED,
+        StatusCode::NOT_FOUND => METHOD_NOT_FOUND,
+        _ => INTERNAL_ERROR_CODE,
+    }
+}
+
+const STREAMING_METHODS: [&str; 1] = [WATCH_SUBSCRIBE];
 
 pub fn is_streaming_method(method_name: &str) -> bool {
     STREAMING_METHODS.contains(&method_name)
@@ -85,14 +124,50 @@ pub fn get_auth_header(headers: HeaderMap) -> Option<HeaderValue> {
 }
 
 // Calls an RPC method and returns the full response as a string.
+#[tracing::instrument(skip_all, fields(method = rpc_request.method_ref()))]
 pub async fn call_rpc_str(
     rpc_server: JsonRpcServerState,
     rpc_request: jsonrpc_v2::RequestObject,
 ) -> anyhow::Result<String> {
-    let rpc_subscription_response = rpc_server.handle(rpc_request).await;
+    let rpc_subscription_response = match rpc_server.handle(rpc_request).await {
+        jsonrpc_v2::ResponseObjects::One(response) => {
+            jsonrpc_v2::ResponseObjects::One(with_error_code(response))
+        }
+        jsonrpc_v2::ResponseObjects::Many(responses) => {
+            jsonrpc_v2::ResponseObjects::Many(responses.into_iter().map(with_error_code).collect())
+        }
+        jsonrpc_v2::ResponseObjects::Empty => jsonrpc_v2::ResponseObjects::Empty,
+    };
     Ok(serde_json::to_string(&rpc_subscription_response)?)
 }
 
+/// Gives the code of [`crate::rpc_api::errors::ApiError::Internal`] to the
+/// errors of the methods that don't return an `ApiError`, which `jsonrpc_v2`
+/// reports with code 0.
+fn with_error_code(response: jsonrpc_v2::ResponseObject) -> jsonrpc_v2::ResponseObject {
+    match response {
+        jsonrpc_v2::ResponseObject::Error {
+            jsonrpc,
+            error:
+                jsonrpc_v2::Error::Full {
+                    code: 0,
+                    message,
+                    data,
+                },
+            id,
+        } => jsonrpc_v2::ResponseObject::Error {
+            jsonrpc,
+            error: jsonrpc_v2::Error::Full {
+                code: INTERNAL_ERROR_CODE,
+                message,
+                data,
+            },
+            id,
+        },
+        response => response,
+    }
+}
+
 // Returns both the RPC response string and the result value in a tuple.
 pub async fn call_rpc<T>(
     rpc_server: JsonRpcServerState,
@@ -144,3 +219,40 @@ where
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn untyped_errors_are_internal() {
+        let untyped = jsonrpc_v2::ResponseObject::Error {
+            jsonrpc: jsonrpc_v2::V2,
+            error: jsonrpc_v2::Error::from("tipset not found"),
+            id: jsonrpc_v2::Id::Null,
+        };
+        let response = serde_json::to_value(with_error_code(untyped)).unwrap();
+        assert_eq!(response["error"]["code"], INTERNAL_ERROR_CODE);
+        assert_eq!(response["error"]["message"], "tipset not found");
+
+        let typed = get_error_res(4, "actor not found: f01234".into());
+        let response = serde_json::to_value(with_error_code(typed)).unwrap();
+        assert_eq!(response["error"]["code"], 4);
+    }
+
+    #[test]
+    fn permission_errors_echo_the_id() {
+        let response = get_error_str_with_id(
+            permission_error_code(StatusCode::FORBIDDEN),
+            "Forbidden".into(),
+            jsonrpc_v2::Id::Num(7),
+        );
+        let response: serde_json::Value = serde_json::from_str(&response).unwrap();
+        assert_eq!(response["id"], 7);
+        assert_eq!(response["error"]["code"], PERMISSION_DENIED);
+        assert_eq!(
+            permission_error_code(StatusCode::NOT_FOUND),
+            METHOD_NOT_FOUND
+        );
+    }
+}
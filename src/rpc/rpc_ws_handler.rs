@@ -1,23 +1,56 @@
 // Copyright 2019-2023 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
-use std::sync::Arc;
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+use std::time::Duration;
 
-use crate::rpc_api::data_types::JsonRpcServerState;
+use crate::rpc_api::{
+    data_types::JsonRpcServerState,
+    watch_api::{WatchSubscribeParams, WATCH_SUBSCRIBE},
+};
+use crate::watch::AddressWatcher;
 use axum::{
     extract::{
         ws::{Message, WebSocket},
         WebSocketUpgrade,
     },
     response::IntoResponse,
+    Extension,
 };
 use crossbeam::atomic::AtomicCell;
 use futures::{stream::SplitSink, SinkExt, StreamExt};
 use http::{HeaderMap, HeaderValue};
 use log::{debug, error, info, warn};
-use tokio::sync::RwLock;
+use serde::Deserialize;
+use tokio::sync::{broadcast::error::RecvError, RwLock};
+
+use crate::rpc::{
+    limits::{RpcLimitsConfig, INVALID_REQUEST},
+    rpc_util::{
+        call_rpc_str, check_permissions, get_auth_header, get_error_str, is_streaming_method,
+    },
+};
+
+/// Method of the notifications carrying the values of a channel
+const CHANNEL_VALUE_METHOD: &str = "xrpc.ch.val";
 
-use crate::rpc::rpc_util::{call_rpc_str, check_permissions, get_auth_header, get_error_str};
+/// Interval between the checks that the socket of an idle subscription is
+/// still open
+const SOCKET_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Source of the channel identifiers of the subscriptions
+static NEXT_CHANNEL_ID: AtomicU64 = AtomicU64::new(1);
+
+/// A request to a streaming method, of which only the identifier and the
+/// parameters are read
+#[derive(Deserialize)]
+struct StreamingRequest<T> {
+    id: serde_json::Value,
+    params: T,
+}
 
 async fn rpc_ws_task(
     authorization_header: Option<HeaderValue>,
@@ -44,21 +77,105 @@ async fn rpc_ws_task(
     Ok(())
 }
 
+/// Serves a streaming method: replies with the identifier of a channel, then
+/// sends the values on the channel until the socket is closed.
+async fn rpc_ws_stream_task(
+    authorization_header: Option<HeaderValue>,
+    request_text: String,
+    rpc_server: JsonRpcServerState,
+    address_watcher: Arc<AddressWatcher>,
+    is_socket_active: Arc<AtomicCell<bool>>,
+    ws_sender: Arc<RwLock<SplitSink<WebSocket, Message>>>,
+) -> anyhow::Result<()> {
+    let request: StreamingRequest<WatchSubscribeParams> = serde_json::from_str(&request_text)?;
+    check_permissions(rpc_server, WATCH_SUBSCRIBE, authorization_header)
+        .await
+        .map_err(|(_, e)| anyhow::Error::msg(e))?;
+    info!("RPC WS subscribed to method: {WATCH_SUBSCRIBE}");
+
+    let (after,) = request.params;
+    let channel = NEXT_CHANNEL_ID.fetch_add(1, Ordering::Relaxed);
+    let send = |value: serde_json::Value| {
+        let ws_sender = ws_sender.clone();
+        async move {
+            let text = serde_json::to_string(&value)?;
+            ws_sender.write().await.send(Message::Text(text)).await?;
+            anyhow::Ok(())
+        }
+    };
+    // Subscribing before reading the recorded events, so that none is missed
+    // in between
+    let mut live = address_watcher.subscribe();
+    send(serde_json::json!({"jsonrpc": "2.0", "result": channel, "id": request.id})).await?;
+
+    let mut last = after;
+    let mut check_socket = tokio::time::interval(SOCKET_CHECK_INTERVAL);
+    loop {
+        for event in address_watcher.events_after(last, usize::MAX) {
+            last = event.seq;
+            send(serde_json::json!({
+                "jsonrpc": "2.0",
+                "method": CHANNEL_VALUE_METHOD,
+                "params": [channel, event],
+            }))
+            .await?;
+        }
+        loop {
+            let event = tokio::select! {
+                event = live.recv() => event,
+                _ = check_socket.tick() => {
+                    if is_socket_active.load() {
+                        continue;
+                    }
+                    return Ok(());
+                }
+            };
+            match event {
+                Ok(event) if event.seq > last => {
+                    last = event.seq;
+                    send(serde_json::json!({
+                        "jsonrpc": "2.0",
+                        "method": CHANNEL_VALUE_METHOD,
+                        "params": [channel, event],
+                    }))
+                    .await?;
+                }
+                Ok(_) => {}
+                // The events missed are read from the recorded ones
+                Err(RecvError::Lagged(_)) => break,
+                Err(RecvError::Closed) => return Ok(()),
+            }
+        }
+    }
+}
+
 pub async fn rpc_ws_handler(
     headers: HeaderMap,
     axum::extract::State(rpc_server): axum::extract::State<JsonRpcServerState>,
+    Extension(limits): Extension<Arc<RpcLimitsConfig>>,
+    Extension(address_watcher): Extension<Arc<AddressWatcher>>,
     ws: WebSocketUpgrade,
 ) -> impl IntoResponse {
     let authorization_header = get_auth_header(headers);
-    ws.on_upgrade(move |socket| async {
-        rpc_ws_handler_inner(socket, authorization_header, rpc_server).await
-    })
+    ws.max_message_size(limits.max_request_body_size)
+        .on_upgrade(move |socket| async {
+            rpc_ws_handler_inner(
+                socket,
+                authorization_header,
+                rpc_server,
+                limits,
+                address_watcher,
+            )
+            .await
+        })
 }
 
 async fn rpc_ws_handler_inner(
     socket: WebSocket,
     authorization_header: Option<HeaderValue>,
     rpc_server: JsonRpcServerState,
+    limits: Arc<RpcLimitsConfig>,
+    address_watcher: Arc<AddressWatcher>,
 ) {
     info!("Accepted WS connection!");
     let (sender, mut receiver) = socket.split();
@@ -74,32 +191,60 @@ async fn rpc_ws_handler_inner(
                 let task_rpc_server = rpc_server.clone();
                 let task_socket_active = socket_active.clone();
                 let task_ws_sender = ws_sender.clone();
+                if let Err(msg) = limits.check_request(request_text.as_bytes()) {
+                    error!("Rejected WS request: {msg}");
+                    if let Err(e) = task_ws_sender
+                        .write()
+                        .await
+                        .send(Message::Text(get_error_str(INVALID_REQUEST, msg)))
+                        .await
+                    {
+                        warn!("{e}");
+                    }
+                    continue;
+                }
                 match serde_json::from_str(&request_text)
                     as Result<jsonrpc_v2::RequestObject, serde_json::Error>
                 {
                     Ok(rpc_call) => {
+                        let streaming = is_streaming_method(rpc_call.method_ref());
+                        let task_address_watcher = address_watcher.clone();
                         tokio::task::spawn(async move {
-                            match rpc_ws_task(
-                                authorization_header,
-                                rpc_call,
-                                task_rpc_server,
-                                task_socket_active,
-                                task_ws_sender.clone(),
-                            )
-                            .await
-                            {
+                            let result = if streaming {
+                                rpc_ws_stream_task(
+                                    authorization_header,
+                                    request_text,
+                                    task_rpc_server,
+                                    task_address_watcher,
+                                    task_socket_active,
+                                    task_ws_sender.clone(),
+                                )
+                                .await
+                            } else {
+                                rpc_ws_task(
+                                    authorization_header,
+                                    rpc_call,
+                                    task_rpc_server,
+                                    task_socket_active,
+                                    task_ws_sender.clone(),
+                                )
+                                .await
+                            };
+                            match result {
                                 Ok(_) => {
                                     debug!("WS RPC task success.");
                                 }
                                 Err(e) => {
                                     let msg = format!("WS RPC task error: {e}");
                                     error!("{}", msg);
-                                    task_ws_sender
+                                    if let Err(e) = task_ws_sender
                                         .write()
                                         .await
                                         .send(Message::Text(get_error_str(3, msg)))
                                         .await
-                                        .unwrap();
+                                    {
+                                        warn!("{e}");
+                                    }
                                 }
                             }
                         });
@@ -0,0 +1,306 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Snapshots served over HTTP, next to the RPC endpoints, for new nodes to
+//! bootstrap from a running one. `GET /snapshot` exports a snapshot of the
+//! current chain, unless a recent one is cached, and redirects to
+//! `/snapshot/<file name>`. That URL stays valid until the next export, so
+//! that interrupted downloads can be resumed with a `Range` header.
+
+use std::{
+    io::SeekFrom,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use crate::beacon::Beacon;
+use crate::cli_shared::snapshot::{self, TrustedVendor};
+use crate::rpc::rpc_util::{check_permissions, get_auth_header};
+use crate::rpc_api::{
+    chain_api::CHAIN_SNAPSHOT_DOWNLOAD,
+    data_types::{JsonRpcServerState, RPCState},
+};
+use axum::{
+    body::{boxed, StreamBody},
+    extract::{Path as UrlPath, State},
+    response::{IntoResponse, Redirect, Response},
+};
+use chrono::Utc;
+use futures::StreamExt;
+use fvm_ipld_blockstore::Blockstore;
+use http::{header, HeaderMap, StatusCode};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use serde_with::{serde_as, DurationSeconds};
+use sha2::Sha256;
+use tempfile::NamedTempFile;
+use tokio::{
+    io::{AsyncReadExt, AsyncSeekExt},
+    sync::{Mutex, Semaphore},
+};
+use tokio_util::{compat::TokioAsyncReadCompatExt, io::ReaderStream};
+
+#[serde_as]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct SnapshotServerConfig {
+    /// Serves snapshots at `/snapshot` on the RPC address. Downloads require
+    /// a token with the `write` permission.
+    pub enabled: bool,
+    /// Downloads served at the same time, the others are rejected until one
+    /// completes
+    pub max_concurrent_downloads: usize,
+    /// Age in seconds below which the cached snapshot is served instead of
+    /// exporting a new one
+    #[serde_as(as = "DurationSeconds<u64>")]
+    pub max_age: Duration,
+}
+
+impl Default for SnapshotServerConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_concurrent_downloads: 2,
+            max_age: Duration::from_secs(6 * 60 * 60),
+        }
+    }
+}
+
+/// Time after which clients rejected for too many downloads are told to retry
+const RETRY_AFTER_SECS: u64 = 60;
+
+pub struct SnapshotServer {
+    config: SnapshotServerConfig,
+    /// Directory the snapshots are exported to
+    dir: PathBuf,
+    downloads: Arc<Semaphore>,
+    /// File name and creation time of the latest snapshot. Locked during the
+    /// exports, so that concurrent requests wait for the same one.
+    latest: Mutex<Option<(String, Instant)>>,
+}
+
+impl SnapshotServer {
+    pub fn new(config: SnapshotServerConfig, dir: PathBuf) -> Self {
+        Self {
+            downloads: Arc::new(Semaphore::new(config.max_concurrent_downloads)),
+            config,
+            dir,
+            latest: Mutex::new(None),
+        }
+    }
+
+    /// Returns the file name of a snapshot recent enough, exporting a new one
+    /// if needed.
+    async fn latest_snapshot<DB, B>(&self, state: &RPCState<DB, B>) -> anyhow::Result<String>
+    where
+        DB: Blockstore + Clone + Send + Sync + 'static,
+        B: Beacon,
+    {
+        let mut latest = self.latest.lock().await;
+        if let Some((filename, created)) = &*latest {
+            if created.elapsed() < self.config.max_age && self.dir.join(filename).exists() {
+                return Ok(filename.clone());
+            }
+        }
+
+        tokio::fs::create_dir_all(&self.dir).await?;
+        let head = state.chain_store.heaviest_tipset();
+        let chain_config = state.state_manager.chain_config();
+        let filename = snapshot::filename(
+            TrustedVendor::Forest,
+            &chain_config.network,
+            Utc::now().date_naive(),
+            head.epoch(),
+        );
+        info!("Exporting snapshot {filename} for download");
+        let temp_path = NamedTempFile::new_in(&self.dir)?.into_temp_path();
+        let file = tokio::fs::File::create(&temp_path).await?;
+        state
+            .chain_store
+            .export::<_, Sha256>(
+                &head,
+                chain_config.policy.chain_finality,
+                file.compat(),
+                true,
+                true,
+            )
+            .await?;
+        temp_path.persist(self.dir.join(&filename))?;
+
+        // The previous snapshot is kept for the downloads in progress to be
+        // resumed, the older ones are removed
+        let previous = latest.replace((filename.clone(), Instant::now()));
+        let mut entries = tokio::fs::read_dir(&self.dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let name = entry.file_name();
+            let keep = name == filename.as_str()
+                || matches!(&previous, Some((previous, _)) if name == previous.as_str());
+            if !keep {
+                if let Err(e) = tokio::fs::remove_file(entry.path()).await {
+                    warn!("Failed to remove snapshot {}: {e}", entry.path().display());
+                }
+            }
+        }
+        Ok(filename)
+    }
+
+    /// Streams the snapshot, or the requested range of it
+    async fn serve(&self, path: &Path, headers: &HeaderMap) -> Result<Response, Response> {
+        let mut file = match tokio::fs::File::open(path).await {
+            Ok(file) => file,
+            Err(_) => return Err((StatusCode::NOT_FOUND, "Snapshot not found").into_response()),
+        };
+        let len = file.metadata().await.map_err(internal_error)?.len();
+        let range = match headers
+            .get(header::RANGE)
+            .and_then(|range| range.to_str().ok())
+        {
+            Some(range) => match parse_range(range, len) {
+                Some(range) => Some(range),
+                None => {
+                    return Err((
+                        StatusCode::RANGE_NOT_SATISFIABLE,
+                        [(header::CONTENT_RANGE, format!("bytes */{len}"))],
+                    )
+                        .into_response())
+                }
+            },
+            None => None,
+        };
+
+        let Ok(permit) = self.downloads.clone().try_acquire_owned() else {
+            return Err((
+                StatusCode::SERVICE_UNAVAILABLE,
+                [(header::RETRY_AFTER, RETRY_AFTER_SECS.to_string())],
+                "Too many snapshot downloads in progress",
+            )
+                .into_response());
+        };
+
+        let (start, end) = range.unwrap_or((0, len.saturating_sub(1)));
+        file.seek(SeekFrom::Start(start))
+            .await
+            .map_err(internal_error)?;
+        let content_len = if len == 0 { 0 } else { end - start + 1 };
+        let body = ReaderStream::new(file.take(content_len)).map(move |chunk| {
+            // The permit is released once the body is dropped
+            let _permit = &permit;
+            chunk
+        });
+
+        let mut response = Response::builder()
+            .header(header::CONTENT_TYPE, "application/octet-stream")
+            .header(header::CONTENT_LENGTH, content_len)
+            .header(header::ACCEPT_RANGES, "bytes");
+        response = match range {
+            Some((start, end)) => response
+                .status(StatusCode::PARTIAL_CONTENT)
+                .header(header::CONTENT_RANGE, format!("bytes {start}-{end}/{len}")),
+            None => response.status(StatusCode::OK),
+        };
+        response
+            .body(boxed(StreamBody::new(body)))
+            .map_err(internal_error)
+    }
+}
+
+/// State of the snapshot routes
+pub struct SnapshotRoutesState<DB, B>
+where
+    DB: Blockstore,
+    B: Beacon,
+{
+    pub server: SnapshotServer,
+    pub rpc_state: Arc<RPCState<DB, B>>,
+    pub rpc_server: JsonRpcServerState,
+}
+
+pub async fn snapshot_handler<DB, B>(
+    headers: HeaderMap,
+    State(state): State<Arc<SnapshotRoutesState<DB, B>>>,
+) -> Response
+where
+    DB: Blockstore + Clone + Send + Sync + 'static,
+    B: Beacon,
+{
+    if let Err(e) = authorize(&state.rpc_server, headers).await {
+        return e.into_response();
+    }
+    match state.server.latest_snapshot(&state.rpc_state).await {
+        Ok(filename) => Redirect::temporary(&format!("/snapshot/{filename}")).into_response(),
+        Err(e) => internal_error(e),
+    }
+}
+
+pub async fn snapshot_file_handler<DB, B>(
+    headers: HeaderMap,
+    UrlPath(filename): UrlPath<String>,
+    State(state): State<Arc<SnapshotRoutesState<DB, B>>>,
+) -> Response
+where
+    DB: Blockstore + Clone + Send + Sync + 'static,
+    B: Beacon,
+{
+    if let Err(e) = authorize(&state.rpc_server, headers.clone()).await {
+        return e.into_response();
+    }
+    if filename.contains('/') || filename.starts_with('.') {
+        return (StatusCode::BAD_REQUEST, "Invalid snapshot name").into_response();
+    }
+    info!("Serving snapshot {filename}");
+    match state
+        .server
+        .serve(&state.server.dir.join(&filename), &headers)
+        .await
+    {
+        Ok(response) | Err(response) => response,
+    }
+}
+
+async fn authorize(
+    rpc_server: &JsonRpcServerState,
+    headers: HeaderMap,
+) -> Result<(), (StatusCode, String)> {
+    check_permissions(
+        rpc_server.clone(),
+        CHAIN_SNAPSHOT_DOWNLOAD,
+        get_auth_header(headers),
+    )
+    .await
+}
+
+fn internal_error(e: impl std::fmt::Display) -> Response {
+    (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response()
+}
+
+/// Parses a single range, e.g. `bytes=0-99`, `bytes=100-` or `bytes=-100`,
+/// into the first and last bytes of the file of length `len` to send.
+/// Returns `None` if the range can't be satisfied.
+fn parse_range(range: &str, len: u64) -> Option<(u64, u64)> {
+    let (start, end) = range.strip_prefix("bytes=")?.trim().split_once('-')?;
+    let last = len.checked_sub(1)?;
+    let (start, end) = match (start, end) {
+        ("", suffix) => (len - suffix.parse::<u64>().ok()?.min(len), last),
+        (start, "") => (start.parse().ok()?, last),
+        (start, end) => (start.parse().ok()?, end.parse::<u64>().ok()?.min(last)),
+    };
+    (start <= end).then_some((start, end))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_ranges() {
+        assert_eq!(parse_range("bytes=0-99", 1000), Some((0, 99)));
+        assert_eq!(parse_range("bytes=100-", 1000), Some((100, 999)));
+        assert_eq!(parse_range("bytes=-100", 1000), Some((900, 999)));
+        assert_eq!(parse_range("bytes=900-2000", 1000), Some((900, 999)));
+        assert_eq!(parse_range("bytes=1000-", 1000), None);
+        assert_eq!(parse_range("bytes=10-5", 1000), None);
+        assert_eq!(parse_range("bytes=0-", 0), None);
+        assert_eq!(parse_range("items=0-1", 1000), None);
+    }
+}
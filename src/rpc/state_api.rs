@@ -3,22 +3,31 @@
 #![allow(clippy::unused_async)]
 
 use crate::beacon::Beacon;
-use crate::blocks::tipset_keys_json::TipsetKeysJson;
+use crate::blocks::{tipset_keys_json::TipsetKeysJson, Tipset};
 use crate::ipld::json::IpldJson;
 use crate::ipld::CidHashSet;
-use crate::json::cid::CidJson;
+use crate::json::{
+    address::json::AddressJson,
+    cid::CidJson,
+    message::json::{vec::MessageJsonVec, MessageJson},
+};
 use crate::libp2p::NetworkMessage;
+use crate::message::ChainMessage;
 use crate::rpc_api::{
-    data_types::{MarketDeal, MessageLookup, RPCState},
+    data_types::{BytesJson, ComputedState, MarketDeal, MessageLookup, RPCState, RandomnessJson},
+    errors::ApiError,
     state_api::*,
 };
 use crate::shim::address::Address;
-use crate::state_manager::InvocResult;
+use crate::shim::executor::ApplyRet;
+use crate::state_manager::{method_registry, ExecutionLane, InvocResult, StateManager};
 use ahash::{HashMap, HashMapExt};
+use anyhow::Context;
 use cid::Cid;
 use fil_actor_interface::market;
 use fvm_ipld_blockstore::Blockstore;
 use fvm_ipld_encoding::{CborStore, DAG_CBOR};
+use fvm_shared::MethodNum;
 use jsonrpc_v2::{Data, Error as JsonRpcError, Params};
 use libipld_core::ipld::Ipld;
 use parking_lot::Mutex;
@@ -43,7 +52,9 @@ pub(in crate::rpc) async fn state_call<
         .state_manager
         .chain_store()
         .tipset_from_keys(&key.into())?;
-    Ok(state_manager.call(&mut message, Some(tipset))?)
+    Ok(state_manager
+        .call(&mut message, Some(tipset))
+        .map_err(ApiError::from)?)
 }
 
 /// returns the result of executing the indicated message, assuming it was
@@ -62,7 +73,10 @@ pub(in crate::rpc) async fn state_replay<
         .state_manager
         .chain_store()
         .tipset_from_keys(&key.into())?;
-    let (msg, ret) = state_manager.replay(&tipset, cid).await?;
+    let (msg, ret) = state_manager
+        .replay(&tipset, cid)
+        .await
+        .map_err(ApiError::from)?;
 
     Ok(InvocResult {
         msg,
@@ -71,6 +85,49 @@ pub(in crate::rpc) async fn state_replay<
     })
 }
 
+/// Returns the execution trace of a message recently applied by the node.
+/// Requires the node to record execution traces.
+pub(in crate::rpc) async fn state_execution_trace<
+    DB: Blockstore + Clone + Send + Sync + 'static,
+    B: Beacon,
+>(
+    data: Data<RPCState<DB, B>>,
+    Params(params): Params<StateExecutionTraceParams>,
+) -> Result<StateExecutionTraceResult, JsonRpcError> {
+    let (CidJson(cid),) = params;
+    let traces = data
+        .state_manager
+        .execution_traces()
+        .ok_or("execution traces are not recorded by this node")?;
+    Ok(traces
+        .get(&cid)
+        .ok_or_else(|| format!("no execution trace for message {cid}"))?)
+}
+
+/// Returns up to `limit` of the recent execution traces in which the actor
+/// sent or received a call, the most recent first.
+pub(in crate::rpc) async fn state_list_execution_traces<
+    DB: Blockstore + Clone + Send + Sync + 'static,
+    B: Beacon,
+>(
+    data: Data<RPCState<DB, B>>,
+    Params(params): Params<StateListExecutionTracesParams>,
+) -> Result<StateListExecutionTracesResult, JsonRpcError> {
+    let (AddressJson(address), limit) = params;
+    let traces = data
+        .state_manager
+        .execution_traces()
+        .ok_or("execution traces are not recorded by this node")?;
+    // Traces refer to the senders by ID
+    let heaviest = data.state_manager.chain_store().heaviest_tipset();
+    let actor = data
+        .state_manager
+        .lookup_id(&address, &heaviest)
+        .map_err(ApiError::from)?
+        .unwrap_or(address);
+    Ok(traces.involving(&actor, limit))
+}
+
 /// gets network name from state manager
 pub(in crate::rpc) async fn state_network_name<
     DB: Blockstore + Clone + Send + Sync + 'static,
@@ -113,9 +170,166 @@ pub(in crate::rpc) async fn state_market_balance<
         .state_manager
         .chain_store()
         .tipset_from_keys(&key.into())?;
-    data.state_manager
+    Ok(data
+        .state_manager
         .market_balance(&address, &tipset)
-        .map_err(|e| e.into())
+        .map_err(ApiError::from)?)
+}
+
+/// Returns the state resulting from the execution of the head, or its parent
+/// state if it is still being computed and the caller doesn't wait for it.
+pub(in crate::rpc) async fn state_head_state<
+    DB: Blockstore + Clone + Send + Sync + 'static,
+    B: Beacon,
+>(
+    data: Data<RPCState<DB, B>>,
+    Params((wait,)): Params<StateHeadStateParams>,
+) -> Result<StateHeadStateResult, JsonRpcError> {
+    let head = data.state_manager.chain_store().heaviest_tipset();
+    Ok(data.state_manager.head_state(&head, wait).await?)
+}
+
+/// Returns the ID address of an actor, resolved through the init actor at the
+/// given tipset.
+pub(in crate::rpc) async fn state_lookup_id<
+    DB: Blockstore + Clone + Send + Sync + 'static,
+    B: Beacon,
+>(
+    data: Data<RPCState<DB, B>>,
+    Params(params): Params<StateLookupIdParams>,
+) -> Result<StateLookupIdResult, JsonRpcError> {
+    let (AddressJson(address), TipsetKeysJson(key)) = params;
+    let tipset = data.state_manager.chain_store().tipset_from_keys(&key)?;
+    Ok(data
+        .state_manager
+        .lookup_required_id(&address, &tipset)
+        .map_err(ApiError::from)?
+        .into())
+}
+
+/// Returns the public key address of an account actor, or the delegated
+/// address of an actor that has one, at the given tipset.
+pub(in crate::rpc) async fn state_account_key<
+    DB: Blockstore + Clone + Send + Sync + 'static,
+    B: Beacon,
+>(
+    data: Data<RPCState<DB, B>>,
+    Params(params): Params<StateAccountKeyParams>,
+) -> Result<StateAccountKeyResult, JsonRpcError> {
+    let (AddressJson(address), TipsetKeysJson(key)) = params;
+    let tipset = data.state_manager.chain_store().tipset_from_keys(&key)?;
+    Ok(data
+        .state_manager
+        .resolve_to_key_addr(&address, &tipset, ExecutionLane::Rpc)
+        .await
+        .map_err(ApiError::from)?
+        .into())
+}
+
+pub(in crate::rpc) async fn state_miner_power<
+    DB: Blockstore + Clone + Send + Sync + 'static,
+    B: Beacon,
+>(
+    data: Data<RPCState<DB, B>>,
+    Params(params): Params<StateMinerPowerParams>,
+) -> Result<StateMinerPowerResult, JsonRpcError> {
+    let (AddressJson(address), TipsetKeysJson(key)) = params;
+    let tipset = data.state_manager.chain_store().tipset_from_keys(&key)?;
+    Ok(data
+        .state_manager
+        .miner_power(tipset.parent_state(), &address)
+        .map_err(ApiError::from)?)
+}
+
+pub(in crate::rpc) async fn state_list_miners<
+    DB: Blockstore + Clone + Send + Sync + 'static,
+    B: Beacon,
+>(
+    data: Data<RPCState<DB, B>>,
+    Params(params): Params<StateListMinersParams>,
+) -> Result<StateListMinersResult, JsonRpcError> {
+    let (TipsetKeysJson(key), start, limit) = params;
+    if limit == 0 {
+        return Err("the page size must be positive".into());
+    }
+    let tipset = data.state_manager.chain_store().tipset_from_keys(&key)?;
+    Ok(data
+        .state_manager
+        .list_miners(
+            tipset.parent_state(),
+            start.as_ref().map(|AddressJson(addr)| addr),
+            limit,
+        )
+        .map_err(ApiError::from)?)
+}
+
+/// Returns the balance of an actor over a range of epochs, see
+/// [`crate::state_manager::StateManager::balance_history`].
+pub(in crate::rpc) async fn state_balance_history<
+    DB: Blockstore + Clone + Send + Sync + 'static,
+    B: Beacon,
+>(
+    data: Data<RPCState<DB, B>>,
+    Params((AddressJson(address), from, to, step)): Params<StateBalanceHistoryParams>,
+) -> Result<StateBalanceHistoryResult, JsonRpcError> {
+    Ok(data
+        .state_manager
+        .balance_history(&address, from, to, step, false)
+        .await
+        .map_err(ApiError::from)?)
+}
+
+/// Returns the balance of an actor over a range of epochs, computing again
+/// the states pruned from the store.
+pub(in crate::rpc) async fn state_balance_history_recompute<
+    DB: Blockstore + Clone + Send + Sync + 'static,
+    B: Beacon,
+>(
+    data: Data<RPCState<DB, B>>,
+    Params((AddressJson(address), from, to, step)): Params<StateBalanceHistoryParams>,
+) -> Result<StateBalanceHistoryResult, JsonRpcError> {
+    // The states recomputed are further bounded by the distance to the
+    // nearest state available
+    let head = data.state_manager.chain_store().heaviest_tipset();
+    data.rpc_limits.check_lookback(from, head.epoch())?;
+    Ok(data
+        .state_manager
+        .balance_history(&address, from, to, step, true)
+        .await
+        .map_err(ApiError::from)?)
+}
+
+/// Lists the Window `PoSt` proofs of a miner that can still be disputed.
+pub(in crate::rpc) async fn state_miner_disputable_posts<
+    DB: Blockstore + Clone + Send + Sync + 'static,
+    B: Beacon,
+>(
+    data: Data<RPCState<DB, B>>,
+    Params(params): Params<StateMinerDisputablePoStsParams>,
+) -> Result<StateMinerDisputablePoStsResult, JsonRpcError> {
+    let (AddressJson(miner), TipsetKeysJson(key)) = params;
+    let tipset = data.state_manager.chain_store().tipset_from_keys(&key)?;
+    Ok(data
+        .state_manager
+        .disputable_posts(&miner, &tipset)
+        .map_err(ApiError::from)?)
+}
+
+/// Builds the `DisputeWindowedPoSt` message disputing a proof of a miner.
+pub(in crate::rpc) async fn state_dispute_post_message<
+    DB: Blockstore + Clone + Send + Sync + 'static,
+    B: Beacon,
+>(
+    data: Data<RPCState<DB, B>>,
+    Params(params): Params<StateDisputePoStMessageParams>,
+) -> Result<StateDisputePoStMessageResult, JsonRpcError> {
+    let (AddressJson(miner), AddressJson(from), deadline, post_index) = params;
+    let head = data.state_manager.chain_store().heaviest_tipset();
+    Ok(MessageJson(
+        data.state_manager
+            .dispute_post_message(&miner, &from, deadline, post_index, &head)
+            .map_err(ApiError::from)?,
+    ))
 }
 
 pub(in crate::rpc) async fn state_market_deals<
@@ -129,8 +343,9 @@ pub(in crate::rpc) async fn state_market_deals<
     let ts = data.chain_store.tipset_from_keys(&tsk)?;
     let actor = data
         .state_manager
-        .get_actor(&Address::MARKET_ACTOR, *ts.parent_state())?
-        .ok_or("Market actor address could not be resolved")?;
+        .get_actor(&Address::MARKET_ACTOR, *ts.parent_state())
+        .map_err(ApiError::from)?
+        .ok_or_else(|| ApiError::actor_not_found(&Address::MARKET_ACTOR))?;
     let market_state =
         market::State::load(data.state_manager.blockstore(), actor.code, actor.state)?;
 
@@ -174,7 +389,7 @@ pub(in crate::rpc) async fn state_get_receipt<
     state_manager
         .get_receipt(tipset, cid)
         .map(|s| s.into())
-        .map_err(|e| e.into())
+        .map_err(|e| ApiError::from(e).into())
 }
 /// looks back in the chain for a message. If not found, it blocks until the
 /// message arrives on chain, and gets to the indicated confidence depth.
@@ -188,7 +403,10 @@ pub(in crate::rpc) async fn state_wait_msg<
     let (cidjson, confidence) = params;
     let state_manager = &data.state_manager;
     let cid: Cid = cidjson.into();
-    let (tipset, receipt) = state_manager.wait_for_message(cid, confidence).await?;
+    let (tipset, receipt) = state_manager
+        .wait_for_message(cid, confidence)
+        .await
+        .map_err(ApiError::from)?;
     let tipset = tipset.ok_or("wait for msg returned empty tuple")?;
     let receipt = receipt.ok_or("wait for msg returned empty receipt")?;
     let ipld: Ipld = if receipt.return_data().bytes().is_empty() {
@@ -337,3 +555,164 @@ pub(in crate::rpc) async fn state_fetch_root<
 fn lock_pop<T>(mutex: &Mutex<Vec<T>>) -> Option<T> {
     mutex.lock().pop()
 }
+
+/// Executes the tipset at the given epoch again, from the state of its
+/// parent, and stores the resulting state. Archival nodes use it to backfill
+/// the states they don't have, one epoch after the other, and to verify the
+/// ones they have.
+pub(in crate::rpc) async fn state_compute<
+    DB: Blockstore + Clone + Send + Sync + 'static,
+    B: Beacon,
+>(
+    data: Data<RPCState<DB, B>>,
+    Params((epoch,)): Params<StateComputeParams>,
+) -> Result<StateComputeResult, JsonRpcError> {
+    let head = data.chain_store.heaviest_tipset();
+    data.rpc_limits.check_lookback(epoch, head.epoch())?;
+    let tipset = data
+        .chain_store
+        .tipset_by_height(epoch, head.clone(), true)?;
+    if !data.chain_store.db.has(tipset.parent_state())? {
+        return Err(format!(
+            "Missing the parent state {} of epoch {}, compute the earlier epochs first",
+            tipset.parent_state(),
+            tipset.epoch()
+        )
+        .into());
+    }
+    let (state_root, receipt_root) = data
+        .state_manager
+        .compute_tipset_state(
+            tipset.clone(),
+            None::<fn(&Cid, &ChainMessage, &ApplyRet) -> anyhow::Result<()>>,
+            ExecutionLane::Rpc,
+        )
+        .await?;
+    let expected_state_root = if tipset.epoch() < head.epoch() {
+        let child = data
+            .chain_store
+            .tipset_by_height(tipset.epoch() + 1, head, false)?;
+        Some(CidJson(*child.parent_state()))
+    } else {
+        None
+    };
+    Ok(ComputedState {
+        epoch: tipset.epoch(),
+        state_root: CidJson(state_root),
+        receipt_root: CidJson(receipt_root),
+        expected_state_root,
+    })
+}
+
+/// Applies the messages, and the cron, at `epoch` on top of the state of the
+/// tipset, the heaviest one if the key is empty. The messages aren't included
+/// on chain.
+pub(in crate::rpc) async fn state_compute_messages<
+    DB: Blockstore + Clone + Send + Sync + 'static,
+    B: Beacon,
+>(
+    data: Data<RPCState<DB, B>>,
+    Params((epoch, MessageJsonVec(messages), TipsetKeysJson(key))): Params<
+        StateComputeMessagesParams,
+    >,
+) -> Result<StateComputeMessagesResult, JsonRpcError> {
+    let head = data.chain_store.heaviest_tipset();
+    let tipset = if key.cids().is_empty() {
+        head.clone()
+    } else {
+        data.chain_store.tipset_from_keys(&key)?
+    };
+    // The tipset is the one executed, and `epoch` follows it by a bounded
+    // number of epochs
+    data.rpc_limits
+        .check_lookback(tipset.epoch(), head.epoch())?;
+    Ok(data
+        .state_manager
+        .compute_state(epoch, messages, tipset)
+        .await
+        .map_err(ApiError::from)?)
+}
+
+/// Draws randomness from the ticket chain of the tipset, as the actors do
+pub(in crate::rpc) async fn state_get_randomness_from_tickets<
+    DB: Blockstore + Clone + Send + Sync + 'static,
+    B: Beacon,
+>(
+    data: Data<RPCState<DB, B>>,
+    Params((pers, round, RandomnessJson(entropy), TipsetKeysJson(tsk))): Params<
+        StateGetRandomnessFromTicketsParams,
+    >,
+) -> Result<StateGetRandomnessFromTicketsResult, JsonRpcError> {
+    let randomness = data
+        .state_manager
+        .get_chain_randomness(&tsk, pers, round, &entropy)?;
+    Ok(RandomnessJson(randomness.to_vec()))
+}
+
+/// Draws randomness from the beacon entries of the tipset, as the actors do
+pub(in crate::rpc) async fn state_get_randomness_from_beacon<
+    DB: Blockstore + Clone + Send + Sync + 'static,
+    B: Beacon,
+>(
+    data: Data<RPCState<DB, B>>,
+    Params((pers, round, RandomnessJson(entropy), TipsetKeysJson(tsk))): Params<
+        StateGetRandomnessFromBeaconParams,
+    >,
+) -> Result<StateGetRandomnessFromBeaconResult, JsonRpcError> {
+    let randomness = data
+        .state_manager
+        .get_beacon_randomness(&tsk, pers, round, &entropy)?;
+    Ok(RandomnessJson(randomness.to_vec()))
+}
+
+/// Looks up a method of the built-in actor with the given code, in the
+/// actors version running at the given tipset.
+fn builtin_method<DB: Blockstore + Clone + Send + Sync + 'static>(
+    state_manager: &StateManager<DB>,
+    tipset: &Tipset,
+    code: &Cid,
+    number: MethodNum,
+) -> anyhow::Result<method_registry::Method> {
+    let system_actor = state_manager
+        .get_actor(&Address::SYSTEM_ACTOR, *tipset.parent_state())?
+        .context("system actor not found")?;
+    let name =
+        method_registry::builtin_actor_name(state_manager.blockstore(), &system_actor.state, code)?;
+    let version =
+        method_registry::actors_version(state_manager.get_network_version(tipset.epoch()))?;
+    method_registry::lookup(&name, version, number)
+        .with_context(|| format!("unknown method {number} of the {name} actor v{version}"))
+}
+
+/// Decodes the parameters of a call to a built-in actor into JSON.
+pub(in crate::rpc) async fn state_decode_params<
+    DB: Blockstore + Clone + Send + Sync + 'static,
+    B: Beacon,
+>(
+    data: Data<RPCState<DB, B>>,
+    Params((address, method_num, BytesJson(params), key)): Params<StateDecodeParamsParams>,
+) -> Result<StateDecodeParamsResult, JsonRpcError> {
+    let state_manager = &data.state_manager;
+    let address: Address = address.into();
+    let tipset = state_manager.chain_store().tipset_from_keys(&key.into())?;
+    let actor = state_manager
+        .get_actor(&address, *tipset.parent_state())?
+        .with_context(|| format!("actor {address} not found"))?;
+    let method = builtin_method(state_manager, &tipset, &actor.code, method_num)?;
+    Ok(method.params.decode(&params)?)
+}
+
+/// Encodes JSON parameters of a call to the built-in actor with the given
+/// code into CBOR, for the actors version running at the head of the chain.
+pub(in crate::rpc) async fn state_encode_params<
+    DB: Blockstore + Clone + Send + Sync + 'static,
+    B: Beacon,
+>(
+    data: Data<RPCState<DB, B>>,
+    Params((code, method_num, params)): Params<StateEncodeParamsParams>,
+) -> Result<StateEncodeParamsResult, JsonRpcError> {
+    let state_manager = &data.state_manager;
+    let heaviest = state_manager.chain_store().heaviest_tipset();
+    let method = builtin_method(state_manager, &heaviest, &code.into(), method_num)?;
+    Ok(BytesJson(method.params.encode(&params)?))
+}
@@ -3,6 +3,7 @@
 #![allow(clippy::unused_async)]
 
 use crate::beacon::Beacon;
+use crate::blocks::tipset_keys_json::TipsetKeysJson;
 use crate::chain_sync::SyncState;
 use crate::json::cid::CidJson;
 use crate::rpc_api::{
@@ -41,6 +42,48 @@ where
     Ok(())
 }
 
+/// Removes a block from the bad blocks, so that it is validated again.
+pub(in crate::rpc) async fn sync_unmark_bad<DB, B>(
+    data: Data<RPCState<DB, B>>,
+    Params(params): Params<SyncUnmarkBadParams>,
+) -> Result<SyncUnmarkBadResult, JsonRpcError>
+where
+    DB: Blockstore,
+    B: Beacon,
+{
+    let (CidJson(cid),) = params;
+    data.bad_blocks.remove(&cid);
+    Ok(())
+}
+
+/// Removes all the blocks from the bad blocks.
+pub(in crate::rpc) async fn sync_unmark_all_bad<DB, B>(
+    data: Data<RPCState<DB, B>>,
+) -> Result<SyncUnmarkAllBadResult, JsonRpcError>
+where
+    DB: Blockstore,
+    B: Beacon,
+{
+    data.bad_blocks.clear();
+    Ok(())
+}
+
+/// Marks a tipset as checkpointed, meaning the chain will never be
+/// reorganized below it.
+pub(in crate::rpc) async fn sync_checkpoint<DB, B>(
+    data: Data<RPCState<DB, B>>,
+    Params(params): Params<SyncCheckpointParams>,
+) -> Result<SyncCheckpointResult, JsonRpcError>
+where
+    DB: Blockstore + Send + Sync,
+    B: Beacon,
+{
+    let (TipsetKeysJson(tsk),) = params;
+    let ts = data.chain_store.tipset_from_keys(&tsk)?;
+    data.chain_store.set_checkpoint(ts)?;
+    Ok(())
+}
+
 async fn clone_state(state: &RwLock<SyncState>) -> SyncState {
     state.read().clone()
 }
@@ -153,6 +196,7 @@ mod tests {
         let (new_mined_block_tx, _) = flume::bounded(5);
         let start_time = chrono::Utc::now();
         let (gc_event_tx, _) = flume::unbounded();
+        let (config_event_tx, _) = flume::unbounded();
 
         let state = Arc::new(RPCState {
             state_manager,
@@ -160,6 +204,7 @@ mod tests {
             mpool: Arc::new(pool),
             bad_blocks: Default::default(),
             sync_state: Arc::new(parking_lot::RwLock::new(Default::default())),
+            peer_manager: Default::default(),
             network_send,
             network_name: TEST_NET_NAME.to_owned(),
             start_time,
@@ -167,6 +212,11 @@ mod tests {
             beacon,
             new_mined_block_tx,
             gc_event_tx,
+            config_event_tx,
+            f3: Default::default(),
+            address_watcher: Default::default(),
+            miner_monitor: Default::default(),
+            rpc_limits: Default::default(),
         });
         (state, network_rx)
     }
@@ -187,10 +237,19 @@ mod tests {
         assert!(sync_mark_bad(Data(state.clone()), Params((cid.clone(),)))
             .await
             .is_ok());
-        match sync_check_bad(Data(state), Params((cid,))).await {
+        match sync_check_bad(Data(state.clone()), Params((cid.clone(),))).await {
             Ok(reason) => assert_eq!(reason, "Marked bad manually through RPC API"),
             Err(e) => std::panic::panic_any(e),
         }
+
+        // Unmark it and check that it is no longer bad
+        assert!(sync_unmark_bad(Data(state.clone()), Params((cid.clone(),)))
+            .await
+            .is_ok());
+        match sync_check_bad(Data(state), Params((cid,))).await {
+            Ok(reason) => assert_eq!(reason, ""),
+            Err(e) => std::panic::panic_any(e),
+        }
     }
 
     #[tokio::test]
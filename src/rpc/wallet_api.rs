@@ -5,14 +5,21 @@ use std::{convert::TryFrom, str::FromStr};
 
 use crate::beacon::Beacon;
 use crate::json::{address::json::AddressJson, signature::json::SignatureJson};
-use crate::key_management::{json::KeyInfoJson, Error, Key};
-use crate::rpc_api::{data_types::RPCState, wallet_api::*};
-use crate::shim::{address::Address, econ::TokenAmount, state_tree::StateTree};
+use crate::key_management::{json::KeyInfoJson, Error, Key, KeyStore};
+use crate::rpc_api::{
+    data_types::{BatchItemResult, RPCState},
+    wallet_api::*,
+};
+use crate::shim::{address::Address, crypto::Signature, econ::TokenAmount, state_tree::StateTree};
+use crate::state_manager::ExecutionLane;
 use base64::{prelude::BASE64_STANDARD, Engine};
 use fvm_ipld_blockstore::Blockstore;
 use jsonrpc_v2::{Data, Error as JsonRpcError, Params};
+use log::info;
 use num_traits::Zero;
 
+use super::rpc_util::get_error_message;
+
 /// Return the balance from `StateManager` for a given `Address`
 pub(in crate::rpc) async fn wallet_balance<DB, B>(
     data: Data<RPCState<DB, B>>,
@@ -51,6 +58,7 @@ where
     B: Beacon,
 {
     let keystore = data.keystore.read().await;
+    keystore.ensure_unlocked()?;
 
     let addr = crate::key_management::get_default(&keystore)?;
     Ok(addr.map(|s| s.to_string()))
@@ -69,6 +77,7 @@ where
     let addr = Address::from_str(&addr_str)?;
 
     let keystore = data.keystore.read().await;
+    keystore.ensure_unlocked()?;
 
     let key_info = crate::key_management::export_key_info(&addr, &keystore)?;
     Ok(KeyInfoJson(key_info))
@@ -87,6 +96,7 @@ where
     let addr = Address::from_str(&addr_str)?;
 
     let keystore = data.keystore.read().await;
+    keystore.ensure_unlocked()?;
 
     let key = crate::key_management::find_key(&addr, &keystore).is_ok();
     Ok(key)
@@ -111,6 +121,7 @@ where
     let addr = format!("wallet-{}", key.address);
 
     let mut keystore = data.keystore.write().await;
+    keystore.ensure_unlocked()?;
 
     if let Err(error) = keystore.put(addr, key.key_info) {
         match error {
@@ -134,6 +145,7 @@ where
     B: Beacon,
 {
     let keystore = data.keystore.read().await;
+    keystore.ensure_unlocked()?;
     Ok(crate::key_management::list_addrs(&keystore)?
         .into_iter()
         .map(AddressJson::from)
@@ -151,6 +163,7 @@ where
 {
     let (sig_raw,) = params;
     let mut keystore = data.keystore.write().await;
+    keystore.ensure_unlocked()?;
     let key = crate::key_management::generate_key(sig_raw.0)?;
 
     let addr = format!("wallet-{}", key.address);
@@ -174,6 +187,7 @@ where
 {
     let (address,) = params;
     let mut keystore = data.keystore.write().await;
+    keystore.ensure_unlocked()?;
 
     let addr_string = format!("wallet-{}", address.0);
     let key_info = keystore.get(&addr_string)?;
@@ -196,24 +210,83 @@ where
     let address = addr.0;
     let heaviest_tipset = data.state_manager.chain_store().heaviest_tipset();
     let key_addr = state_manager
-        .resolve_to_key_addr(&address, &heaviest_tipset)
+        .resolve_to_key_addr(&address, &heaviest_tipset, ExecutionLane::Rpc)
         .await?;
     let keystore = &mut *data.keystore.write().await;
-    let key = match crate::key_management::find_key(&key_addr, keystore) {
+    keystore.ensure_unlocked()?;
+
+    Ok(SignatureJson(sign_data(&key_addr, &msg_string, keystore)?))
+}
+
+/// Sign base64 encoded data with the key of `key_addr`
+fn sign_data(
+    key_addr: &Address,
+    msg_string: &[u8],
+    keystore: &mut KeyStore,
+) -> Result<Signature, JsonRpcError> {
+    let key = match crate::key_management::find_key(key_addr, keystore) {
         Ok(key) => key,
         Err(_) => {
-            let key_info = crate::key_management::try_find(&key_addr, keystore)?;
+            let key_info = crate::key_management::try_find(key_addr, keystore)?;
             Key::try_from(key_info)?
         }
     };
 
-    let sig = crate::key_management::sign(
+    Ok(crate::key_management::sign(
         *key.key_info.key_type(),
         key.key_info.private_key(),
         &BASE64_STANDARD.decode(msg_string)?,
-    )?;
+    )?)
+}
 
-    Ok(SignatureJson(sig))
+/// Sign each vector of bytes with the key of its address, reporting the
+/// error of each one that can't be signed
+pub(in crate::rpc) async fn wallet_sign_batch<DB, B>(
+    data: Data<RPCState<DB, B>>,
+    Params(params): Params<WalletSignBatchParams>,
+) -> Result<WalletSignBatchResult, JsonRpcError>
+where
+    DB: Blockstore + Clone + Send + Sync + 'static,
+    B: Beacon,
+{
+    let (items,) = params;
+    let heaviest_tipset = data.state_manager.chain_store().heaviest_tipset();
+    let keystore = &mut *data.keystore.write().await;
+    keystore.ensure_unlocked()?;
+
+    let mut results = Vec::with_capacity(items.len());
+    for (AddressJson(address), msg_string) in items {
+        let sig = match data
+            .state_manager
+            .resolve_to_key_addr(&address, &heaviest_tipset, ExecutionLane::Rpc)
+            .await
+        {
+            Ok(key_addr) => sign_data(&key_addr, &msg_string, keystore),
+            Err(e) => Err(e.into()),
+        };
+        results.push(match sig {
+            Ok(sig) => BatchItemResult::ok(SignatureJson(sig)),
+            Err(e) => BatchItemResult::err(get_error_message(&e)),
+        });
+    }
+    Ok(results)
+}
+
+/// Unlock the encrypted keystore, if the node was started without its
+/// passphrase
+pub(in crate::rpc) async fn wallet_unlock<DB, B>(
+    data: Data<RPCState<DB, B>>,
+    Params(params): Params<WalletUnlockParams>,
+) -> Result<WalletUnlockResult, JsonRpcError>
+where
+    DB: Blockstore,
+    B: Beacon,
+{
+    let (passphrase,) = params;
+    let mut keystore = data.keystore.write().await;
+    keystore.unlock(passphrase)?;
+    info!("Keystore unlocked, signing is enabled");
+    Ok(())
 }
 
 /// Verify a Signature, true if verified, false otherwise
@@ -0,0 +1,64 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+#![allow(clippy::unused_async)]
+
+use crate::beacon::Beacon;
+use crate::json::address::json::AddressJson;
+use crate::rpc_api::{data_types::RPCState, watch_api::*};
+use fvm_ipld_blockstore::Blockstore;
+use jsonrpc_v2::{Data, Error as JsonRpcError, Params};
+
+/// Largest number of events returned at once
+const MAX_EVENTS: usize = 1000;
+
+pub(in crate::rpc) async fn watch_address<
+    DB: Blockstore + Clone + Send + Sync + 'static,
+    B: Beacon,
+>(
+    data: Data<RPCState<DB, B>>,
+    Params((AddressJson(address), webhook)): Params<WatchAddressParams>,
+) -> Result<WatchAddressResult, JsonRpcError> {
+    if let Some(webhook) = &webhook {
+        reqwest::Url::parse(webhook).map_err(|e| format!("invalid webhook {webhook}: {e}"))?;
+    }
+    // Messages refer to the actor by either address
+    let heaviest = data.state_manager.chain_store().heaviest_tipset();
+    let id = data.state_manager.lookup_id(&address, &heaviest)?;
+    data.address_watcher.watch(Watch {
+        address,
+        id,
+        webhook,
+    })?;
+    Ok(())
+}
+
+pub(in crate::rpc) async fn unwatch_address<
+    DB: Blockstore + Clone + Send + Sync + 'static,
+    B: Beacon,
+>(
+    data: Data<RPCState<DB, B>>,
+    Params((AddressJson(address),)): Params<UnwatchAddressParams>,
+) -> Result<UnwatchAddressResult, JsonRpcError> {
+    Ok(data.address_watcher.unwatch(&address)?)
+}
+
+pub(in crate::rpc) async fn watch_list<
+    DB: Blockstore + Clone + Send + Sync + 'static,
+    B: Beacon,
+>(
+    data: Data<RPCState<DB, B>>,
+) -> Result<WatchListResult, JsonRpcError> {
+    Ok(data.address_watcher.watches())
+}
+
+pub(in crate::rpc) async fn watch_events<
+    DB: Blockstore + Clone + Send + Sync + 'static,
+    B: Beacon,
+>(
+    data: Data<RPCState<DB, B>>,
+    Params((after, limit)): Params<WatchEventsParams>,
+) -> Result<WatchEventsResult, JsonRpcError> {
+    Ok(data
+        .address_watcher
+        .events_after(after, limit.min(MAX_EVENTS)))
+}
@@ -3,27 +3,43 @@
 
 use std::sync::Arc;
 
-use crate::beacon::{Beacon, BeaconSchedule};
-use crate::blocks::{tipset_keys_json::TipsetKeysJson, Tipset};
-use crate::chain::ChainStore;
+use crate::beacon::{Beacon, BeaconEntry, BeaconSchedule};
+use crate::blocks::{
+    tipset_json::TipsetJson, tipset_keys_json::TipsetKeysJson, ElectionProof, Ticket, Tipset,
+    TipsetKeys,
+};
+use crate::chain::{ChainStore, InclusionProof};
 use crate::chain_sync::{BadBlockCache, SyncState};
+use crate::cli_shared::cli::ConfigEvent;
+use crate::db::rolling::GcRequest;
+use crate::f3::F3Client;
 use crate::ipld::json::IpldJson;
-use crate::json::{cid::CidJson, message_receipt::json::ReceiptJson, token_amount::json};
+use crate::json::{
+    cid::CidJson, message::json::MessageJson, message_receipt::json::ReceiptJson,
+    token_amount::json,
+};
 use crate::key_management::KeyStore;
 pub use crate::libp2p::{Multiaddr, Protocol};
-use crate::libp2p::{Multihash, NetworkMessage};
+use crate::libp2p::{Multihash, NetworkMessage, PeerManager};
 use crate::message::signed_message::SignedMessage;
 use crate::message_pool::{MessagePool, MpoolRpcProvider};
-use crate::shim::{econ::TokenAmount, message::Message};
+use crate::miner_monitor::MinerMonitor;
+use crate::rpc::RpcLimitsConfig;
+use crate::shim::{
+    address::Address, clock::ChainEpoch, econ::TokenAmount, message::Message, sector::PoStProof,
+};
 use crate::state_manager::StateManager;
+use crate::watch::AddressWatcher;
 use ahash::HashSet;
 use chrono::Utc;
 use cid::Cid;
 use fil_actor_interface::market::{DealProposal, DealState};
 use fvm_ipld_blockstore::Blockstore;
 use jsonrpc_v2::{MapRouter as JsonRpcMapRouter, Server as JsonRpcServer};
+use num::BigInt;
 use parking_lot::RwLock as SyncRwLock;
 use serde::{Deserialize, Serialize};
+use serde_with::{base64::Base64, serde_as};
 use tokio::sync::RwLock;
 
 /// This is where you store persistent data, or at least access to stateful
@@ -39,12 +55,18 @@ where
     pub mpool: Arc<MessagePool<MpoolRpcProvider<DB>>>,
     pub bad_blocks: Arc<BadBlockCache>,
     pub sync_state: Arc<SyncRwLock<SyncState>>,
+    pub peer_manager: Arc<PeerManager>,
     pub network_send: flume::Sender<NetworkMessage>,
     pub network_name: String,
     pub start_time: chrono::DateTime<Utc>,
     pub new_mined_block_tx: flume::Sender<Arc<Tipset>>,
     pub beacon: Arc<BeaconSchedule<B>>,
-    pub gc_event_tx: flume::Sender<flume::Sender<anyhow::Result<()>>>,
+    pub gc_event_tx: flume::Sender<GcRequest>,
+    pub config_event_tx: flume::Sender<(ConfigEvent, flume::Sender<anyhow::Result<()>>)>,
+    pub f3: Arc<F3Client>,
+    pub address_watcher: Arc<AddressWatcher>,
+    pub miner_monitor: Arc<MinerMonitor>,
+    pub rpc_limits: RpcLimitsConfig,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -69,13 +91,46 @@ pub struct BlockMessages {
     pub cids: Vec<Cid>,
 }
 
+/// Message along with its CID
 #[derive(Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
+pub struct ApiMessage {
+    pub cid: CidJson,
+    pub message: MessageJson,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
 pub struct MessageSendSpec {
     #[serde(with = "json")]
     max_fee: TokenAmount,
 }
 
+/// Result of an item of a batch request, with the error of the item if it
+/// failed
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct BatchItemResult<T> {
+    pub result: Option<T>,
+    pub error: Option<String>,
+}
+
+impl<T> BatchItemResult<T> {
+    pub fn ok(result: T) -> Self {
+        Self {
+            result: Some(result),
+            error: None,
+        }
+    }
+
+    pub fn err(error: impl ToString) -> Self {
+        Self {
+            result: None,
+            error: Some(error.to_string()),
+        }
+    }
+}
+
 #[derive(Serialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct MarketDeal {
@@ -94,6 +149,97 @@ pub struct MessageLookup {
     pub return_dec: IpldJson,
 }
 
+/// State computed by executing the tipset at an epoch again
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ComputedState {
+    /// Epoch of the tipset executed, lower than the one requested if it was
+    /// a null round
+    pub epoch: ChainEpoch,
+    pub state_root: CidJson,
+    pub receipt_root: CidJson,
+    /// Parent state of the next tipset of the chain, if any, that the
+    /// computed state is expected to match
+    pub expected_state_root: Option<CidJson>,
+}
+
+/// Randomness or entropy, encoded in base64 like the Lotus `abi.Randomness`
+#[serde_as]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct RandomnessJson(#[serde_as(as = "Base64")] pub Vec<u8>);
+
+/// Raw bytes, encoded in base64 like the Lotus `[]byte`
+#[serde_as]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct BytesJson(#[serde_as(as = "Base64")] pub Vec<u8>);
+
+/// Receipt of a message, with the proof of its inclusion in the receipts root
+/// of the tipset following the one executing the message
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ReceiptInclusionProof {
+    pub receipt: ReceiptJson,
+    /// Root the proof starts from, the `ParentMessageReceipts` of the blocks
+    /// of the next tipset
+    pub receipts_root: CidJson,
+    pub proof: InclusionProof,
+}
+
+/// Head of a fork tracked by the chain store, with its weight
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ForkHeadJson {
+    pub tipset: TipsetJson,
+    #[serde(with = "crate::json::bigint::json")]
+    pub weight: BigInt,
+}
+
+/// Node found at an IPLD path, with the CID of the block holding it
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct IpldObject {
+    #[serde(with = "crate::json::cid")]
+    pub cid: Cid,
+    pub obj: IpldJson,
+}
+
+/// Node matched by a selector
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct SelectedNode {
+    /// Path of the node from the node the selector was applied to
+    pub path: String,
+    /// CID of the block holding the node
+    #[serde(with = "crate::json::cid")]
+    pub cid: Cid,
+    pub obj: IpldJson,
+}
+
+// Miner API
+/// Template of a block to be assembled and signed by the node, as in Lotus
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct BlockTemplate {
+    #[serde(with = "crate::json::address::json")]
+    pub miner: Address,
+    #[serde(with = "crate::blocks::tipset_keys_json")]
+    pub parents: TipsetKeys,
+    #[serde(with = "crate::blocks::ticket::json::opt")]
+    pub ticket: Option<Ticket>,
+    #[serde(with = "crate::blocks::election_proof::json::opt")]
+    pub eproof: Option<ElectionProof>,
+    #[serde(with = "crate::beacon::beacon_entries::json::vec")]
+    pub beacon_values: Vec<BeaconEntry>,
+    #[serde(with = "crate::json::signed_message::json::vec")]
+    pub messages: Vec<SignedMessage>,
+    pub epoch: ChainEpoch,
+    pub timestamp: u64,
+    #[serde(rename = "WinningPoStProof", with = "crate::json::sector::json::vec")]
+    pub winning_post_proof: Vec<PoStProof>,
+}
+
 // Net API
 #[derive(Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
@@ -101,6 +247,41 @@ pub struct AddrInfo {
     #[serde(rename = "ID")]
     pub id: String,
     pub addrs: HashSet<Multiaddr>,
+    /// Score of the peer in the `[0, 1]` range, higher is better. Only set
+    /// for peers that the node exchanged requests with.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub score: Option<f64>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct NetInfo {
+    pub num_connected: usize,
+    pub num_full_peers: usize,
+    pub num_bad_peers: usize,
+    pub num_protected: usize,
+    pub peers: Vec<PeerScoreInfo>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct NatStatus {
+    /// `0` for unknown, `1` for public and `2` for private
+    pub reachability: i32,
+    pub public_addr: String,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct PeerScoreInfo {
+    #[serde(rename = "ID")]
+    pub id: String,
+    pub score: f64,
+    pub successes: u32,
+    pub failures: u32,
+    /// Average response time in milliseconds
+    pub average_time_ms: u64,
+    pub protected: bool,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -0,0 +1,202 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Errors returned by the API. Each kind has its own JSON-RPC error code, the
+//! same as Lotus for the kinds it also distinguishes, so that clients can
+//! tell them apart without parsing the messages.
+//!
+//! The methods reading the state, estimating gas or executing messages tell
+//! the failures of messages and the missing actors apart. The errors of the
+//! other methods, which don't return an [`ApiError`], are reported as
+//! [`ApiError::Internal`] ones.
+
+use crate::shim::{address::Address, error::ExitCode};
+use crate::state_manager::Error as StateManagerError;
+use fvm_shared3::error::ExitCode as ExitCodeV3;
+use serde::{Deserialize, Serialize};
+
+/// JSON-RPC error code of an internal error
+pub const INTERNAL_ERROR_CODE: i64 = -32603;
+/// Error code of a message that ran out of gas
+pub const OUT_OF_GAS_CODE: i64 = 3;
+/// Error code of an actor missing from the state
+pub const ACTOR_NOT_FOUND_CODE: i64 = 4;
+/// Error code of a message that aborted with a non-zero exit code
+pub const EXECUTION_FAILED_CODE: i64 = 11;
+
+/// Error of an API method. It deliberately doesn't implement `Display`: the
+/// `jsonrpc_v2` conversion from any displayable error would otherwise apply,
+/// and lose its code.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ApiError {
+    /// The message ran out of gas
+    OutOfGas { backtrace: Option<String> },
+    /// The message aborted with a non-zero exit code
+    ExecutionFailed {
+        exit_code: ExitCode,
+        backtrace: Option<String>,
+    },
+    /// The address doesn't resolve to an actor
+    ActorNotFound(String),
+    /// Any other failure
+    Internal(String),
+}
+
+/// Details attached to the errors, in the `data` member of the JSON-RPC error
+/// object
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ApiErrorData {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exit_code: Option<u32>,
+    /// Backtrace of the actor calls that led to the failure
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub backtrace: Option<String>,
+}
+
+impl ApiError {
+    /// Error of a message whose execution failed, given the exit code of its
+    /// receipt and the failure information of the VM
+    pub fn execution(exit_code: ExitCode, backtrace: Option<String>) -> Self {
+        if exit_code.value() == ExitCodeV3::SYS_OUT_OF_GAS.value() {
+            Self::OutOfGas { backtrace }
+        } else {
+            Self::ExecutionFailed {
+                exit_code,
+                backtrace,
+            }
+        }
+    }
+
+    pub fn actor_not_found(addr: &Address) -> Self {
+        Self::ActorNotFound(addr.to_string())
+    }
+
+    pub fn code(&self) -> i64 {
+        match self {
+            Self::OutOfGas { .. } => OUT_OF_GAS_CODE,
+            Self::ExecutionFailed { .. } => EXECUTION_FAILED_CODE,
+            Self::ActorNotFound(_) => ACTOR_NOT_FOUND_CODE,
+            Self::Internal(_) => INTERNAL_ERROR_CODE,
+        }
+    }
+
+    pub fn message(&self) -> String {
+        match self {
+            Self::OutOfGas { .. } => "message execution failed: out of gas".into(),
+            Self::ExecutionFailed { exit_code, .. } => {
+                format!("message execution failed: exit code {}", exit_code.value())
+            }
+            Self::ActorNotFound(addr) => format!("actor not found: {addr}"),
+            Self::Internal(message) => message.clone(),
+        }
+    }
+
+    pub fn data(&self) -> Option<ApiErrorData> {
+        match self {
+            Self::OutOfGas { backtrace } => Some(ApiErrorData {
+                exit_code: Some(ExitCodeV3::SYS_OUT_OF_GAS.value()),
+                backtrace: backtrace.clone(),
+            }),
+            Self::ExecutionFailed {
+                exit_code,
+                backtrace,
+            } => Some(ApiErrorData {
+                exit_code: Some(exit_code.value()),
+                backtrace: backtrace.clone(),
+            }),
+            Self::ActorNotFound(_) | Self::Internal(_) => None,
+        }
+    }
+
+    /// Rebuilds the error from the members of a JSON-RPC error object, e.g.
+    /// on the client side. Codes of no known kind give [`ApiError::Internal`].
+    pub fn from_parts(code: i64, message: String, data: Option<ApiErrorData>) -> Self {
+        let data = data.unwrap_or_default();
+        match code {
+            OUT_OF_GAS_CODE => Self::OutOfGas {
+                backtrace: data.backtrace,
+            },
+            EXECUTION_FAILED_CODE => Self::ExecutionFailed {
+                exit_code: data.exit_code.unwrap_or_default().into(),
+                backtrace: data.backtrace,
+            },
+            ACTOR_NOT_FOUND_CODE => Self::ActorNotFound(
+                message
+                    .strip_prefix("actor not found: ")
+                    .unwrap_or(&message)
+                    .to_owned(),
+            ),
+            _ => Self::Internal(message),
+        }
+    }
+}
+
+impl From<StateManagerError> for ApiError {
+    fn from(e: StateManagerError) -> Self {
+        match e {
+            StateManagerError::ActorNotFound(addr) => Self::ActorNotFound(addr),
+            e => Self::Internal(e.to_string()),
+        }
+    }
+}
+
+impl From<anyhow::Error> for ApiError {
+    fn from(e: anyhow::Error) -> Self {
+        match e.downcast::<StateManagerError>() {
+            Ok(e) => e.into(),
+            Err(e) => Self::Internal(e.to_string()),
+        }
+    }
+}
+
+impl From<ApiError> for jsonrpc_v2::Error {
+    fn from(e: ApiError) -> Self {
+        jsonrpc_v2::Error::Full {
+            code: e.code(),
+            message: e.message(),
+            data: e.data().map(|data| Box::new(data) as _),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn out_of_gas_is_told_from_other_exit_codes() {
+        let out_of_gas = ApiError::execution(ExitCodeV3::SYS_OUT_OF_GAS.into(), None);
+        assert_eq!(out_of_gas.code(), OUT_OF_GAS_CODE);
+        let forbidden = ApiError::execution(ExitCodeV3::USR_FORBIDDEN.into(), None);
+        assert_eq!(forbidden.code(), EXECUTION_FAILED_CODE);
+    }
+
+    #[test]
+    fn actor_not_found_survives_anyhow() {
+        let e = anyhow::Error::from(StateManagerError::ActorNotFound("f01234".into()));
+        assert_eq!(ApiError::from(e), ApiError::ActorNotFound("f01234".into()));
+        let e = anyhow::anyhow!("database is closed");
+        assert_eq!(ApiError::from(e).code(), INTERNAL_ERROR_CODE);
+    }
+
+    #[test]
+    fn round_trip_through_parts() {
+        let errors = [
+            ApiError::OutOfGas {
+                backtrace: Some("00: f01234 (method 2) -- out of gas (7)".into()),
+            },
+            ApiError::ExecutionFailed {
+                exit_code: ExitCodeV3::USR_ILLEGAL_ARGUMENT.into(),
+                backtrace: None,
+            },
+            ApiError::ActorNotFound("f01234".into()),
+            ApiError::Internal("database is closed".into()),
+        ];
+        for e in errors {
+            let data = serde_json::to_value(e.data()).unwrap();
+            let data = serde_json::from_value(data).unwrap();
+            assert_eq!(ApiError::from_parts(e.code(), e.message(), data), e);
+        }
+    }
+}
@@ -11,6 +11,7 @@ use ahash::{HashMap, HashMapExt};
 use once_cell::sync::Lazy;
 
 pub mod data_types;
+pub mod errors;
 
 /// Access levels to be checked against JWT claims
 pub enum Access {
@@ -38,6 +39,8 @@ pub static ACCESS_MAP: Lazy<HashMap<&str, Access>> = Lazy::new(|| {
     access.insert(chain_api::CHAIN_READ_OBJ, Access::Read);
     access.insert(chain_api::CHAIN_HAS_OBJ, Access::Read);
     access.insert(chain_api::CHAIN_GET_BLOCK_MESSAGES, Access::Read);
+    access.insert(chain_api::CHAIN_GET_PARENT_MESSAGES, Access::Read);
+    access.insert(chain_api::CHAIN_GET_PARENT_RECEIPTS, Access::Read);
     access.insert(chain_api::CHAIN_GET_TIPSET_BY_HEIGHT, Access::Read);
     access.insert(chain_api::CHAIN_GET_GENESIS, Access::Read);
     access.insert(chain_api::CHAIN_HEAD, Access::Read);
@@ -47,16 +50,40 @@ pub static ACCESS_MAP: Lazy<HashMap<&str, Access>> = Lazy::new(|| {
     access.insert(chain_api::CHAIN_VALIDATE_TIPSET_CHECKPOINTS, Access::Read);
     access.insert(chain_api::CHAIN_GET_NAME, Access::Read);
     access.insert(chain_api::CHAIN_SET_HEAD, Access::Admin);
+    access.insert(chain_api::CHAIN_SNAPSHOT_DOWNLOAD, Access::Write);
+    access.insert(chain_api::CHAIN_GET_MESSAGE_INCLUSION_PROOF, Access::Read);
+    access.insert(chain_api::CHAIN_GET_RECEIPT_INCLUSION_PROOF, Access::Read);
+    access.insert(chain_api::CHAIN_COMPUTE_MESSAGE_ROOT, Access::Read);
+    access.insert(chain_api::CHAIN_COMPUTE_RECEIPTS_ROOT, Access::Read);
+    access.insert(chain_api::CHAIN_GET_BEACON_ENTRY, Access::Read);
+    access.insert(chain_api::CHAIN_TIPSET_WEIGHT, Access::Read);
+    access.insert(chain_api::CHAIN_GET_FORK_HEADS, Access::Read);
+    access.insert(chain_api::CHAIN_GET_NODE, Access::Read);
+    access.insert(chain_api::CHAIN_SELECT_NODES, Access::Read);
+    access.insert(chain_api::CHAIN_PRUNE, Access::Admin);
 
     // Message Pool API
+    access.insert(mpool_api::MPOOL_GET_NONCE, Access::Read);
     access.insert(mpool_api::MPOOL_PENDING, Access::Read);
     access.insert(mpool_api::MPOOL_PUSH, Access::Write);
     access.insert(mpool_api::MPOOL_PUSH_MESSAGE, Access::Sign);
+    access.insert(mpool_api::MPOOL_BATCH_PUSH, Access::Write);
+    access.insert(mpool_api::MPOOL_BATCH_PUSH_MESSAGE, Access::Sign);
+    access.insert(mpool_api::MPOOL_CLEAR, Access::Write);
+    access.insert(mpool_api::MPOOL_STAT, Access::Read);
+    access.insert(mpool_api::MPOOL_SEQUENCE_GAPS, Access::Read);
+
+    // Miner API
+    access.insert(miner_api::MINER_CREATE_BLOCK, Access::Write);
+    access.insert(miner_api::MINER_MONITOR_SUMMARY, Access::Read);
 
     // Sync API
     access.insert(sync_api::SYNC_CHECK_BAD, Access::Read);
     access.insert(sync_api::SYNC_MARK_BAD, Access::Admin);
+    access.insert(sync_api::SYNC_UNMARK_BAD, Access::Admin);
+    access.insert(sync_api::SYNC_UNMARK_ALL_BAD, Access::Admin);
     access.insert(sync_api::SYNC_STATE, Access::Read);
+    access.insert(sync_api::SYNC_CHECKPOINT, Access::Admin);
 
     // Wallet API
     access.insert(wallet_api::WALLET_BALANCE, Access::Write);
@@ -69,6 +96,8 @@ pub static ACCESS_MAP: Lazy<HashMap<&str, Access>> = Lazy::new(|| {
     access.insert(wallet_api::WALLET_NEW, Access::Write);
     access.insert(wallet_api::WALLET_SET_DEFAULT, Access::Write);
     access.insert(wallet_api::WALLET_SIGN, Access::Sign);
+    access.insert(wallet_api::WALLET_SIGN_BATCH, Access::Sign);
+    access.insert(wallet_api::WALLET_UNLOCK, Access::Admin);
     access.insert(wallet_api::WALLET_VERIFY, Access::Read);
 
     // State API
@@ -76,17 +105,35 @@ pub static ACCESS_MAP: Lazy<HashMap<&str, Access>> = Lazy::new(|| {
     access.insert(state_api::STATE_REPLAY, Access::Read);
     access.insert(state_api::STATE_MARKET_BALANCE, Access::Read);
     access.insert(state_api::STATE_MARKET_DEALS, Access::Read);
+    access.insert(state_api::STATE_HEAD_STATE, Access::Read);
+    access.insert(state_api::STATE_LOOKUP_ID, Access::Read);
+    access.insert(state_api::STATE_ACCOUNT_KEY, Access::Read);
+    access.insert(state_api::STATE_MINER_POWER, Access::Read);
+    access.insert(state_api::STATE_LIST_MINERS, Access::Read);
+    access.insert(state_api::STATE_BALANCE_HISTORY, Access::Read);
+    access.insert(state_api::STATE_BALANCE_HISTORY_RECOMPUTE, Access::Write);
+    access.insert(state_api::STATE_MINER_DISPUTABLE_POSTS, Access::Read);
+    access.insert(state_api::STATE_DISPUTE_POST_MESSAGE, Access::Read);
     access.insert(state_api::STATE_GET_RECEIPT, Access::Read);
     access.insert(state_api::STATE_WAIT_MSG, Access::Read);
     access.insert(state_api::STATE_NETWORK_NAME, Access::Read);
     access.insert(state_api::STATE_NETWORK_VERSION, Access::Read);
     access.insert(state_api::STATE_FETCH_ROOT, Access::Read);
+    access.insert(state_api::STATE_EXECUTION_TRACE, Access::Read);
+    access.insert(state_api::STATE_LIST_EXECUTION_TRACES, Access::Read);
+    access.insert(state_api::STATE_COMPUTE, Access::Admin);
+    access.insert(state_api::STATE_COMPUTE_MESSAGES, Access::Read);
+    access.insert(state_api::STATE_GET_RANDOMNESS_FROM_TICKETS, Access::Read);
+    access.insert(state_api::STATE_GET_RANDOMNESS_FROM_BEACON, Access::Read);
+    access.insert(state_api::STATE_DECODE_PARAMS, Access::Read);
+    access.insert(state_api::STATE_ENCODE_PARAMS, Access::Read);
 
     // Gas API
     access.insert(gas_api::GAS_ESTIMATE_GAS_LIMIT, Access::Read);
     access.insert(gas_api::GAS_ESTIMATE_GAS_PREMIUM, Access::Read);
     access.insert(gas_api::GAS_ESTIMATE_FEE_CAP, Access::Read);
     access.insert(gas_api::GAS_ESTIMATE_MESSAGE_GAS, Access::Read);
+    access.insert(gas_api::GAS_FEE_HISTORY, Access::Read);
 
     // Common API
     access.insert(common_api::VERSION, Access::Read);
@@ -96,14 +143,44 @@ pub static ACCESS_MAP: Lazy<HashMap<&str, Access>> = Lazy::new(|| {
     // Net API
     access.insert(net_api::NET_ADDRS_LISTEN, Access::Read);
     access.insert(net_api::NET_PEERS, Access::Read);
+    access.insert(net_api::NET_INFO, Access::Read);
+    access.insert(net_api::NET_AUTO_NAT_STATUS, Access::Read);
     access.insert(net_api::NET_CONNECT, Access::Write);
     access.insert(net_api::NET_DISCONNECT, Access::Write);
+    access.insert(net_api::NET_ADD_PEER, Access::Admin);
+    access.insert(net_api::NET_REMOVE_PEER, Access::Admin);
+    access.insert(net_api::NET_BANDWIDTH_STATS, Access::Read);
+    access.insert(net_api::NET_BANDWIDTH_STATS_BY_PEER, Access::Read);
+    access.insert(net_api::NET_BANDWIDTH_STATS_BY_PROTOCOL, Access::Read);
 
     // DB API
     access.insert(db_api::DB_GC, Access::Write);
 
+    // Config API
+    access.insert(config_api::CONFIG_RELOAD, Access::Admin);
+    access.insert(config_api::CONFIG_SET, Access::Admin);
+
+    // Eth API
+    access.insert(eth_api::FILECOIN_ADDRESS_TO_ETH_ADDRESS, Access::Read);
+    access.insert(eth_api::ETH_ADDRESS_TO_FILECOIN_ADDRESS, Access::Read);
+    access.insert(eth_api::ETH_FEE_HISTORY, Access::Read);
+    access.insert(eth_api::ETH_FEE_HISTORY_ALIAS, Access::Read);
+
+    // F3 API
+    access.insert(f3_api::F3_GET_CERTIFICATE, Access::Read);
+    access.insert(f3_api::F3_GET_LATEST_CERTIFICATE, Access::Read);
+
     // Progress API
     access.insert(progress_api::GET_PROGRESS, Access::Read);
+    access.insert(progress_api::OPERATION_STATUS, Access::Read);
+    access.insert(progress_api::OPERATION_CANCEL, Access::Admin);
+
+    // Watch API
+    access.insert(watch_api::WATCH_ADDRESS, Access::Admin);
+    access.insert(watch_api::UNWATCH_ADDRESS, Access::Admin);
+    access.insert(watch_api::WATCH_LIST, Access::Read);
+    access.insert(watch_api::WATCH_EVENTS, Access::Read);
+    access.insert(watch_api::WATCH_SUBSCRIBE, Access::Read);
     // Node API
     access.insert(node_api::NODE_STATUS, Access::Read);
 
@@ -161,15 +238,24 @@ pub mod beacon_api {
 pub mod chain_api {
     use std::path::PathBuf;
 
+    use crate::beacon::json::BeaconEntryJson;
     use crate::blocks::{
         header::json::BlockHeaderJson, tipset_json::TipsetJson, tipset_keys_json::TipsetKeysJson,
         TipsetKeys,
     };
-    use crate::json::{cid::CidJson, message::json::MessageJson};
+    use crate::chain::MessageInclusionProof;
+    use crate::db::rolling::GcReport;
+    use crate::ipld::selector::Selector;
+    use crate::json::{
+        cid::CidJson, message::json::MessageJson, message_receipt::json::ReceiptJson,
+        signed_message::json::SignedMessageJson,
+    };
     use crate::shim::clock::ChainEpoch;
     use serde::{Deserialize, Serialize};
 
-    use crate::rpc_api::data_types::BlockMessages;
+    use crate::rpc_api::data_types::{
+        ApiMessage, BlockMessages, ForkHeadJson, IpldObject, ReceiptInclusionProof, SelectedNode,
+    };
 
     pub const CHAIN_GET_MESSAGE: &str = "Filecoin.ChainGetMessage";
     pub type ChainGetMessageParams = (CidJson,);
@@ -185,10 +271,21 @@ pub mod chain_api {
         pub tipset_keys: TipsetKeysJson,
         pub skip_checksum: bool,
         pub dry_run: bool,
+        /// Write an uncompressed CARv2 archive, with an index of its blocks
+        #[serde(default)]
+        pub car_v2: bool,
+        /// Write a `.forest.car.zst` archive, made of independent zstd frames
+        /// and ending with an index of its blocks
+        #[serde(default)]
+        pub forest_car: bool,
     }
 
     pub type ChainExportResult = PathBuf;
 
+    /// Not a JSON-RPC method: permission of the snapshot downloads served
+    /// over HTTP
+    pub const CHAIN_SNAPSHOT_DOWNLOAD: &str = "Forest.ChainSnapshotDownload";
+
     pub const CHAIN_READ_OBJ: &str = "Filecoin.ChainReadObj";
     pub type ChainReadObjParams = (CidJson,);
     pub type ChainReadObjResult = String;
@@ -201,6 +298,16 @@ pub mod chain_api {
     pub type ChainGetBlockMessagesParams = (CidJson,);
     pub type ChainGetBlockMessagesResult = BlockMessages;
 
+    pub const CHAIN_GET_PARENT_MESSAGES: &str = "Filecoin.ChainGetParentMessages";
+    pub type ChainGetParentMessagesParams = (CidJson,);
+    /// Messages executed by the parent tipset of the block, in the order of
+    /// its receipts
+    pub type ChainGetParentMessagesResult = Vec<ApiMessage>;
+
+    pub const CHAIN_GET_PARENT_RECEIPTS: &str = "Filecoin.ChainGetParentReceipts";
+    pub type ChainGetParentReceiptsParams = (CidJson,);
+    pub type ChainGetParentReceiptsResult = Vec<ReceiptJson>;
+
     pub const CHAIN_GET_TIPSET_BY_HEIGHT: &str = "Filecoin.ChainGetTipsetByHeight";
     pub type ChainGetTipsetByHeightParams = (ChainEpoch, TipsetKeys);
     pub type ChainGetTipsetByHeightResult = TipsetJson;
@@ -236,18 +343,82 @@ pub mod chain_api {
     pub const CHAIN_SET_HEAD: &str = "Filecoin.ChainSetHead";
     pub type ChainSetHeadParams = (TipsetKeys,);
     pub type ChainSetHeadResult = ();
+
+    pub const CHAIN_GET_MESSAGE_INCLUSION_PROOF: &str = "Forest.ChainGetMessageInclusionProof";
+    /// Block and message CIDs
+    pub type ChainGetMessageInclusionProofParams = (CidJson, CidJson);
+    pub type ChainGetMessageInclusionProofResult = MessageInclusionProof;
+
+    pub const CHAIN_GET_RECEIPT_INCLUSION_PROOF: &str = "Forest.ChainGetReceiptInclusionProof";
+    /// Message CID and key of the tipset executing it
+    pub type ChainGetReceiptInclusionProofParams = (CidJson, TipsetKeysJson);
+    pub type ChainGetReceiptInclusionProofResult = ReceiptInclusionProof;
+
+    pub const CHAIN_COMPUTE_MESSAGE_ROOT: &str = "Forest.ChainComputeMessageRoot";
+    /// BLS messages, unsigned, and the other messages of a block
+    pub type ChainComputeMessageRootParams = (Vec<MessageJson>, Vec<SignedMessageJson>);
+    pub type ChainComputeMessageRootResult = CidJson;
+
+    pub const CHAIN_COMPUTE_RECEIPTS_ROOT: &str = "Forest.ChainComputeReceiptsRoot";
+    /// Receipts of the messages of a tipset, in execution order
+    pub type ChainComputeReceiptsRootParams = (Vec<ReceiptJson>,);
+    pub type ChainComputeReceiptsRootResult = CidJson;
+
+    pub const CHAIN_GET_BEACON_ENTRY: &str = "Forest.ChainGetBeaconEntry";
+    /// Latest beacon entry included in the chain up to the epoch
+    pub type ChainGetBeaconEntryParams = (ChainEpoch,);
+    pub type ChainGetBeaconEntryResult = BeaconEntryJson;
+
+    pub const CHAIN_TIPSET_WEIGHT: &str = "Filecoin.ChainTipSetWeight";
+    pub type ChainTipSetWeightParams = (TipsetKeysJson,);
+    /// Weight as a decimal string, like the Lotus `types.BigInt`
+    pub type ChainTipSetWeightResult = String;
+
+    pub const CHAIN_GET_FORK_HEADS: &str = "Forest.ChainGetForkHeads";
+    pub type ChainGetForkHeadsParams = ();
+    /// Heads of the forks within the chain finality, from the heaviest one
+    pub type ChainGetForkHeadsResult = Vec<ForkHeadJson>;
+
+    pub const CHAIN_PRUNE: &str = "Forest.ChainPrune";
+
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    pub struct ChainPruneParams {
+        /// Number of recent state roots to keep, at least the chain finality
+        pub recent_roots: i64,
+        /// Only report the reclaimable space
+        pub dry_run: bool,
+    }
+
+    pub type ChainPruneResult = GcReport;
+
+    pub const CHAIN_GET_NODE: &str = "Filecoin.ChainGetNode";
+    /// IPLD path starting with a CID, e.g. `bafy2.../Parents/0/Messages`
+    pub type ChainGetNodeParams = (String,);
+    pub type ChainGetNodeResult = IpldObject;
+
+    pub const CHAIN_SELECT_NODES: &str = "Forest.ChainSelectNodes";
+    /// IPLD path of the node to start from, selector, with a depth limit on its
+    /// recursions, and maximum number of blocks loaded
+    pub type ChainSelectNodesParams = (String, Selector, usize);
+    pub type ChainSelectNodesResult = Vec<SelectedNode>;
 }
 
 /// Message Pool API
 pub mod mpool_api {
     use crate::json::{
+        address::json::AddressJson,
         cid::{vec::CidJsonVec, CidJson},
         message::json::MessageJson,
         signed_message::json::SignedMessageJson,
     };
     use crate::message::SignedMessage;
+    use crate::message_pool::{SenderStats, SequenceGaps};
 
-    use crate::rpc_api::data_types::MessageSendSpec;
+    use crate::rpc_api::data_types::{BatchItemResult, MessageSendSpec};
+
+    pub const MPOOL_GET_NONCE: &str = "Filecoin.MpoolGetNonce";
+    pub type MpoolGetNonceParams = (AddressJson,);
+    pub type MpoolGetNonceResult = u64;
 
     pub const MPOOL_PENDING: &str = "Filecoin.MpoolPending";
     pub type MpoolPendingParams = (CidJsonVec,);
@@ -260,10 +431,53 @@ pub mod mpool_api {
     pub const MPOOL_PUSH_MESSAGE: &str = "Filecoin.MpoolPushMessage";
     pub type MpoolPushMessageParams = (MessageJson, Option<MessageSendSpec>);
     pub type MpoolPushMessageResult = SignedMessageJson;
+
+    /// Pushes signed messages, either all of them or none if any is invalid
+    pub const MPOOL_BATCH_PUSH: &str = "Forest.MpoolBatchPush";
+    pub type MpoolBatchPushParams = (Vec<SignedMessageJson>,);
+    pub type MpoolBatchPushResult = Vec<BatchItemResult<CidJson>>;
+
+    /// Estimates the gas of messages, gives them the next sequences of their
+    /// senders, then signs and pushes them, either all of them or none
+    pub const MPOOL_BATCH_PUSH_MESSAGE: &str = "Forest.MpoolBatchPushMessage";
+    pub type MpoolBatchPushMessageParams = (Vec<MessageJson>, Option<MessageSendSpec>);
+    pub type MpoolBatchPushMessageResult = Vec<BatchItemResult<SignedMessageJson>>;
+
+    /// Removes the pending messages, the local ones too if the flag is set
+    pub const MPOOL_CLEAR: &str = "Filecoin.MpoolClear";
+    pub type MpoolClearParams = (bool,);
+    pub type MpoolClearResult = ();
+
+    pub const MPOOL_STAT: &str = "Forest.MpoolStat";
+    pub type MpoolStatParams = ();
+    pub type MpoolStatResult = Vec<SenderStats>;
+
+    /// Finds the sequences missing from the pending messages of a sender
+    pub const MPOOL_SEQUENCE_GAPS: &str = "Forest.MpoolSequenceGaps";
+    pub type MpoolSequenceGapsParams = (AddressJson,);
+    pub type MpoolSequenceGapsResult = SequenceGaps;
+}
+
+/// Miner API
+pub mod miner_api {
+    use crate::blocks::gossip_block::json::GossipBlockJson;
+    use crate::miner_monitor::MinerSummary;
+
+    use crate::rpc_api::data_types::BlockTemplate;
+
+    pub const MINER_CREATE_BLOCK: &str = "Filecoin.MinerCreateBlock";
+    pub type MinerCreateBlockParams = (BlockTemplate,);
+    pub type MinerCreateBlockResult = GossipBlockJson;
+
+    /// Returns the latest summaries of the miners monitored by the node
+    pub const MINER_MONITOR_SUMMARY: &str = "Forest.MinerMonitorSummary";
+    pub type MinerMonitorSummaryParams = ();
+    pub type MinerMonitorSummaryResult = Vec<MinerSummary>;
 }
 
 /// Sync API
 pub mod sync_api {
+    use crate::blocks::tipset_keys_json::TipsetKeysJson;
     use crate::json::cid::CidJson;
 
     use crate::rpc_api::data_types::RPCSyncState;
@@ -276,9 +490,21 @@ pub mod sync_api {
     pub type SyncMarkBadParams = (CidJson,);
     pub type SyncMarkBadResult = ();
 
+    pub const SYNC_UNMARK_BAD: &str = "Filecoin.SyncUnmarkBad";
+    pub type SyncUnmarkBadParams = (CidJson,);
+    pub type SyncUnmarkBadResult = ();
+
+    pub const SYNC_UNMARK_ALL_BAD: &str = "Filecoin.SyncUnmarkAllBad";
+    pub type SyncUnmarkAllBadParams = ();
+    pub type SyncUnmarkAllBadResult = ();
+
     pub const SYNC_STATE: &str = "Filecoin.SyncState";
     pub type SyncStateParams = ();
     pub type SyncStateResult = RPCSyncState;
+
+    pub const SYNC_CHECKPOINT: &str = "Filecoin.SyncCheckpoint";
+    pub type SyncCheckpointParams = (TipsetKeysJson,);
+    pub type SyncCheckpointResult = ();
 }
 
 /// Wallet API
@@ -288,6 +514,7 @@ pub mod wallet_api {
         signature::json::{signature_type::SignatureTypeJson, SignatureJson},
     };
     use crate::key_management::json::KeyInfoJson;
+    use crate::rpc_api::data_types::BatchItemResult;
 
     pub const WALLET_BALANCE: &str = "Filecoin.WalletBalance";
     pub type WalletBalanceParams = (String,);
@@ -325,6 +552,15 @@ pub mod wallet_api {
     pub type WalletSignParams = (AddressJson, Vec<u8>);
     pub type WalletSignResult = SignatureJson;
 
+    /// Signs each of the data with the key of its address
+    pub const WALLET_SIGN_BATCH: &str = "Forest.WalletSignBatch";
+    pub type WalletSignBatchParams = (Vec<(AddressJson, Vec<u8>)>,);
+    pub type WalletSignBatchResult = Vec<BatchItemResult<SignatureJson>>;
+
+    pub const WALLET_UNLOCK: &str = "Filecoin.WalletUnlock";
+    pub type WalletUnlockParams = (String,);
+    pub type WalletUnlockResult = ();
+
     pub const WALLET_VERIFY: &str = "Filecoin.WalletVerify";
     pub type WalletVerifyParams = (AddressJson, Vec<u8>, SignatureJson);
     pub type WalletVerifyResult = bool;
@@ -333,15 +569,24 @@ pub mod wallet_api {
 /// State API
 pub mod state_api {
     use crate::blocks::tipset_keys_json::TipsetKeysJson;
+    use crate::interpreter::ExecutionTrace;
+    use crate::json::message::json::vec::MessageJsonVec;
     use crate::json::{
         address::json::AddressJson, cid::CidJson, message::json::MessageJson,
         message_receipt::json::ReceiptJson,
     };
     use crate::shim::version::NetworkVersion;
-    use crate::state_manager::{InvocResult, MarketBalance};
+    use crate::state_manager::{
+        BalanceAt, ComputeStateOutput, DisputablePoSt, HeadState, InvocResult, MarketBalance,
+        MinerPower, MinersPage,
+    };
     use ahash::HashMap;
 
-    use crate::rpc_api::data_types::{MarketDeal, MessageLookup};
+    use crate::rpc_api::data_types::{
+        BytesJson, ComputedState, MarketDeal, MessageLookup, RandomnessJson,
+    };
+    use crate::shim::clock::ChainEpoch;
+    use fvm_shared::MethodNum;
 
     pub const STATE_CALL: &str = "Filecoin.StateCall";
     pub type StateCallParams = (MessageJson, TipsetKeysJson);
@@ -367,6 +612,51 @@ pub mod state_api {
     pub type StateMarketDealsParams = (TipsetKeysJson,);
     pub type StateMarketDealsResult = HashMap<String, MarketDeal>;
 
+    /// Takes whether to wait for the state of the head to be computed, rather
+    /// than get its parent state if it isn't yet.
+    pub const STATE_HEAD_STATE: &str = "Forest.StateHeadState";
+    pub type StateHeadStateParams = (bool,);
+    pub type StateHeadStateResult = HeadState;
+
+    pub const STATE_LOOKUP_ID: &str = "Filecoin.StateLookupID";
+    pub type StateLookupIdParams = (AddressJson, TipsetKeysJson);
+    pub type StateLookupIdResult = AddressJson;
+
+    pub const STATE_ACCOUNT_KEY: &str = "Filecoin.StateAccountKey";
+    pub type StateAccountKeyParams = (AddressJson, TipsetKeysJson);
+    pub type StateAccountKeyResult = AddressJson;
+
+    pub const STATE_MINER_POWER: &str = "Filecoin.StateMinerPower";
+    pub type StateMinerPowerParams = (AddressJson, TipsetKeysJson);
+    pub type StateMinerPowerResult = MinerPower;
+
+    /// Paginated counterpart of `Filecoin.StateListMiners`, taking the first
+    /// miner of the page, `null` for the first page, and the size of the page.
+    pub const STATE_LIST_MINERS: &str = "Forest.StateListMiners";
+    pub type StateListMinersParams = (TipsetKeysJson, Option<AddressJson>, u64);
+    pub type StateListMinersResult = MinersPage;
+
+    /// Takes the address, the first and last epochs, and the step between
+    /// epochs.
+    pub const STATE_BALANCE_HISTORY: &str = "Forest.StateBalanceHistory";
+    pub type StateBalanceHistoryParams = (AddressJson, ChainEpoch, ChainEpoch, u64);
+    pub type StateBalanceHistoryResult = Vec<BalanceAt>;
+
+    /// Counterpart of `Forest.StateBalanceHistory` computing again the states
+    /// pruned from the store, which executes tipsets on behalf of the caller.
+    pub const STATE_BALANCE_HISTORY_RECOMPUTE: &str = "Forest.StateBalanceHistoryRecompute";
+
+    pub const STATE_MINER_DISPUTABLE_POSTS: &str = "Forest.StateMinerDisputablePoSts";
+    pub type StateMinerDisputablePoStsParams = (AddressJson, TipsetKeysJson);
+    pub type StateMinerDisputablePoStsResult = Vec<DisputablePoSt>;
+
+    /// Takes the miner, the sender of the message, the deadline and the index
+    /// of the proof. The message is checked against the head of the chain,
+    /// and its gas is left to be estimated.
+    pub const STATE_DISPUTE_POST_MESSAGE: &str = "Forest.StateDisputePoStMessage";
+    pub type StateDisputePoStMessageParams = (AddressJson, AddressJson, u64, u64);
+    pub type StateDisputePoStMessageResult = MessageJson;
+
     pub const STATE_GET_RECEIPT: &str = "Filecoin.StateGetReceipt";
     pub type StateGetReceiptParams = (CidJson, TipsetKeysJson);
     pub type StateGetReceiptResult = ReceiptJson;
@@ -378,11 +668,45 @@ pub mod state_api {
     pub const STATE_FETCH_ROOT: &str = "Filecoin.StateFetchRoot";
     pub type StateFetchRootParams = (CidJson,);
     pub type StateFetchRootResult = String;
+
+    pub const STATE_EXECUTION_TRACE: &str = "Filecoin.StateExecutionTrace";
+    pub type StateExecutionTraceParams = (CidJson,);
+    pub type StateExecutionTraceResult = ExecutionTrace;
+
+    pub const STATE_LIST_EXECUTION_TRACES: &str = "Filecoin.StateListExecutionTraces";
+    pub type StateListExecutionTracesParams = (AddressJson, usize);
+    pub type StateListExecutionTracesResult = Vec<ExecutionTrace>;
+
+    pub const STATE_COMPUTE: &str = "Forest.StateCompute";
+    pub type StateComputeParams = (ChainEpoch,);
+    pub type StateComputeResult = ComputedState;
+
+    pub const STATE_COMPUTE_MESSAGES: &str = "Filecoin.StateCompute";
+    pub type StateComputeMessagesParams = (ChainEpoch, MessageJsonVec, TipsetKeysJson);
+    pub type StateComputeMessagesResult = ComputeStateOutput;
+
+    pub const STATE_GET_RANDOMNESS_FROM_TICKETS: &str = "Filecoin.StateGetRandomnessFromTickets";
+    pub type StateGetRandomnessFromTicketsParams =
+        (i64, ChainEpoch, RandomnessJson, TipsetKeysJson);
+    pub type StateGetRandomnessFromTicketsResult = RandomnessJson;
+
+    pub const STATE_GET_RANDOMNESS_FROM_BEACON: &str = "Filecoin.StateGetRandomnessFromBeacon";
+    pub type StateGetRandomnessFromBeaconParams = (i64, ChainEpoch, RandomnessJson, TipsetKeysJson);
+    pub type StateGetRandomnessFromBeaconResult = RandomnessJson;
+
+    pub const STATE_DECODE_PARAMS: &str = "Filecoin.StateDecodeParams";
+    pub type StateDecodeParamsParams = (AddressJson, MethodNum, BytesJson, TipsetKeysJson);
+    pub type StateDecodeParamsResult = serde_json::Value;
+
+    pub const STATE_ENCODE_PARAMS: &str = "Filecoin.StateEncodeParams";
+    pub type StateEncodeParamsParams = (CidJson, MethodNum, serde_json::Value);
+    pub type StateEncodeParamsResult = BytesJson;
 }
 
 /// Gas API
 pub mod gas_api {
     use crate::blocks::tipset_keys_json::TipsetKeysJson;
+    use crate::chain::FeeHistory;
     use crate::json::{address::json::AddressJson, message::json::MessageJson};
 
     use crate::rpc_api::data_types::MessageSendSpec;
@@ -402,6 +726,10 @@ pub mod gas_api {
     pub const GAS_ESTIMATE_MESSAGE_GAS: &str = "Filecoin.GasEstimateMessageGas";
     pub type GasEstimateMessageGasParams = (MessageJson, Option<MessageSendSpec>, TipsetKeysJson);
     pub type GasEstimateMessageGasResult = MessageJson;
+
+    pub const GAS_FEE_HISTORY: &str = "Forest.GasFeeHistory";
+    pub type GasFeeHistoryParams = (u64, TipsetKeysJson, Vec<f64>);
+    pub type GasFeeHistoryResult = FeeHistory;
 }
 
 /// Common API
@@ -425,7 +753,9 @@ pub mod common_api {
 
 /// Net API
 pub mod net_api {
-    use crate::rpc_api::data_types::AddrInfo;
+    use crate::libp2p::BandwidthStats;
+    use crate::rpc_api::data_types::{AddrInfo, NatStatus, NetInfo};
+    use ahash::HashMap;
 
     pub const NET_ADDRS_LISTEN: &str = "Filecoin.NetAddrsListen";
     pub type NetAddrsListenParams = ();
@@ -435,6 +765,14 @@ pub mod net_api {
     pub type NetPeersParams = ();
     pub type NetPeersResult = Vec<AddrInfo>;
 
+    pub const NET_INFO: &str = "Filecoin.NetInfo";
+    pub type NetInfoParams = ();
+    pub type NetInfoResult = NetInfo;
+
+    pub const NET_AUTO_NAT_STATUS: &str = "Filecoin.NetAutoNatStatus";
+    pub type NetAutoNatStatusParams = ();
+    pub type NetAutoNatStatusResult = NatStatus;
+
     pub const NET_CONNECT: &str = "Filecoin.NetConnect";
     pub type NetConnectParams = (AddrInfo,);
     pub type NetConnectResult = ();
@@ -442,6 +780,28 @@ pub mod net_api {
     pub const NET_DISCONNECT: &str = "Filecoin.NetDisconnect";
     pub type NetDisconnectParams = (String,);
     pub type NetDisconnectResult = ();
+
+    pub const NET_ADD_PEER: &str = "Filecoin.NetAddPeer";
+    pub type NetAddPeerParams = (String,);
+    pub type NetAddPeerResult = ();
+
+    pub const NET_REMOVE_PEER: &str = "Filecoin.NetRemovePeer";
+    pub type NetRemovePeerParams = (String,);
+    pub type NetRemovePeerResult = bool;
+
+    pub const NET_BANDWIDTH_STATS: &str = "Filecoin.NetBandwidthStats";
+    pub type NetBandwidthStatsParams = ();
+    pub type NetBandwidthStatsResult = BandwidthStats;
+
+    /// Keyed by peer ID
+    pub const NET_BANDWIDTH_STATS_BY_PEER: &str = "Filecoin.NetBandwidthStatsByPeer";
+    pub type NetBandwidthStatsByPeerParams = ();
+    pub type NetBandwidthStatsByPeerResult = HashMap<String, BandwidthStats>;
+
+    /// Keyed by protocol ID
+    pub const NET_BANDWIDTH_STATS_BY_PROTOCOL: &str = "Filecoin.NetBandwidthStatsByProtocol";
+    pub type NetBandwidthStatsByProtocolParams = ();
+    pub type NetBandwidthStatsByProtocolResult = HashMap<String, BandwidthStats>;
 }
 
 /// DB API
@@ -451,6 +811,50 @@ pub mod db_api {
     pub type DBGCResult = ();
 }
 
+/// Config API
+pub mod config_api {
+    pub const CONFIG_RELOAD: &str = "Filecoin.ConfigReload";
+    pub type ConfigReloadParams = ();
+    pub type ConfigReloadResult = ();
+
+    pub const CONFIG_SET: &str = "Filecoin.ConfigSet";
+    pub type ConfigSetParams = (String, String);
+    pub type ConfigSetResult = ();
+}
+
+/// Eth API
+pub mod eth_api {
+    use crate::eth::{EthAddress, EthBlockNumber, EthFeeHistory, EthUint64};
+    use crate::json::address::json::AddressJson;
+
+    pub const FILECOIN_ADDRESS_TO_ETH_ADDRESS: &str = "Filecoin.FilecoinAddressToEthAddress";
+    pub type FilecoinAddressToEthAddressParams = (AddressJson,);
+    pub type FilecoinAddressToEthAddressResult = EthAddress;
+
+    pub const ETH_ADDRESS_TO_FILECOIN_ADDRESS: &str = "Filecoin.EthAddressToFilecoinAddress";
+    pub type EthAddressToFilecoinAddressParams = (EthAddress,);
+    pub type EthAddressToFilecoinAddressResult = AddressJson;
+
+    pub const ETH_FEE_HISTORY: &str = "Filecoin.EthFeeHistory";
+    /// Name of the method in the Ethereum JSON-RPC API
+    pub const ETH_FEE_HISTORY_ALIAS: &str = "eth_feeHistory";
+    pub type EthFeeHistoryParams = (EthUint64, EthBlockNumber, Vec<f64>);
+    pub type EthFeeHistoryResult = EthFeeHistory;
+}
+
+/// F3 API
+pub mod f3_api {
+    use crate::f3::FinalityCertificate;
+
+    pub const F3_GET_CERTIFICATE: &str = "Filecoin.F3GetCertificate";
+    pub type F3GetCertificateParams = (u64,);
+    pub type F3GetCertificateResult = FinalityCertificate;
+
+    pub const F3_GET_LATEST_CERTIFICATE: &str = "Filecoin.F3GetLatestCertificate";
+    pub type F3GetLatestCertificateParams = ();
+    pub type F3GetLatestCertificateResult = FinalityCertificate;
+}
+
 /// Progress API
 pub mod progress_api {
     use serde::{Deserialize, Serialize};
@@ -464,6 +868,53 @@ pub mod progress_api {
         SnapshotExport,
         DatabaseGarbageCollection,
     }
+
+    pub use crate::utils::operations::{OperationKind, OperationState, OperationStatus};
+
+    /// Lists the operations in progress and the recently ended ones
+    pub const OPERATION_STATUS: &str = "Forest.OperationStatus";
+    pub type OperationStatusParams = ();
+    pub type OperationStatusResult = Vec<OperationStatus>;
+
+    /// Cancels an operation in progress given its ID
+    pub const OPERATION_CANCEL: &str = "Forest.OperationCancel";
+    pub type OperationCancelParams = (u64,);
+    pub type OperationCancelResult = ();
+}
+
+/// Watch API
+pub mod watch_api {
+    use crate::json::address::json::AddressJson;
+
+    pub use crate::watch::{Watch, WatchEvent, WatchEventKind};
+
+    /// Watches an address, posting its events to the given webhook if any
+    pub const WATCH_ADDRESS: &str = "Forest.WatchAddress";
+    pub type WatchAddressParams = (AddressJson, Option<String>);
+    pub type WatchAddressResult = ();
+
+    /// Stops watching an address, returns whether it was watched
+    pub const UNWATCH_ADDRESS: &str = "Forest.UnwatchAddress";
+    pub type UnwatchAddressParams = (AddressJson,);
+    pub type UnwatchAddressResult = bool;
+
+    pub const WATCH_LIST: &str = "Forest.WatchList";
+    pub type WatchListParams = ();
+    pub type WatchListResult = Vec<Watch>;
+
+    /// Returns up to the given number of the events following the one of the
+    /// given sequence number, 0 for the oldest ones
+    pub const WATCH_EVENTS: &str = "Forest.WatchEvents";
+    pub type WatchEventsParams = (u64, usize);
+    pub type WatchEventsResult = Vec<WatchEvent>;
+
+    /// Streams the events following the one of the given sequence number, 0
+    /// for the oldest ones, and then the new events as they happen. Only
+    /// served over WebSocket, the events are sent as `xrpc.ch.val`
+    /// notifications on the channel returned.
+    pub const WATCH_SUBSCRIBE: &str = "Forest.WatchSubscribe";
+    pub type WatchSubscribeParams = (u64,);
+    pub type WatchSubscribeResult = u64;
 }
 
 /// Node API
@@ -85,3 +85,72 @@ pub async fn chain_set_head(
 ) -> Result<ChainSetHeadResult, Error> {
     call(CHAIN_SET_HEAD, params, auth_token).await
 }
+
+pub async fn chain_get_message_inclusion_proof(
+    params: ChainGetMessageInclusionProofParams,
+    auth_token: &Option<String>,
+) -> Result<ChainGetMessageInclusionProofResult, Error> {
+    call(CHAIN_GET_MESSAGE_INCLUSION_PROOF, params, auth_token).await
+}
+
+pub async fn chain_get_receipt_inclusion_proof(
+    params: ChainGetReceiptInclusionProofParams,
+    auth_token: &Option<String>,
+) -> Result<ChainGetReceiptInclusionProofResult, Error> {
+    call(CHAIN_GET_RECEIPT_INCLUSION_PROOF, params, auth_token).await
+}
+
+pub async fn chain_compute_message_root(
+    params: ChainComputeMessageRootParams,
+    auth_token: &Option<String>,
+) -> Result<ChainComputeMessageRootResult, Error> {
+    call(CHAIN_COMPUTE_MESSAGE_ROOT, params, auth_token).await
+}
+
+pub async fn chain_compute_receipts_root(
+    params: ChainComputeReceiptsRootParams,
+    auth_token: &Option<String>,
+) -> Result<ChainComputeReceiptsRootResult, Error> {
+    call(CHAIN_COMPUTE_RECEIPTS_ROOT, params, auth_token).await
+}
+
+pub async fn chain_get_beacon_entry(
+    params: ChainGetBeaconEntryParams,
+    auth_token: &Option<String>,
+) -> Result<ChainGetBeaconEntryResult, Error> {
+    call(CHAIN_GET_BEACON_ENTRY, params, auth_token).await
+}
+
+pub async fn chain_tipset_weight(
+    params: ChainTipSetWeightParams,
+    auth_token: &Option<String>,
+) -> Result<ChainTipSetWeightResult, Error> {
+    call(CHAIN_TIPSET_WEIGHT, params, auth_token).await
+}
+
+pub async fn chain_get_fork_heads(
+    auth_token: &Option<String>,
+) -> Result<ChainGetForkHeadsResult, Error> {
+    call(CHAIN_GET_FORK_HEADS, (), auth_token).await
+}
+
+pub async fn chain_prune(
+    params: ChainPruneParams,
+    auth_token: &Option<String>,
+) -> Result<ChainPruneResult, Error> {
+    call(CHAIN_PRUNE, params, auth_token).await
+}
+
+pub async fn chain_get_node(
+    params: ChainGetNodeParams,
+    auth_token: &Option<String>,
+) -> Result<ChainGetNodeResult, Error> {
+    call(CHAIN_GET_NODE, params, auth_token).await
+}
+
+pub async fn chain_select_nodes(
+    params: ChainSelectNodesParams,
+    auth_token: &Option<String>,
+) -> Result<ChainSelectNodesResult, Error> {
+    call(CHAIN_SELECT_NODES, params, auth_token).await
+}
@@ -0,0 +1,21 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use crate::rpc_api::config_api::*;
+use jsonrpc_v2::Error;
+
+use crate::rpc_client::call;
+
+pub async fn config_reload(
+    params: ConfigReloadParams,
+    auth_token: &Option<String>,
+) -> Result<ConfigReloadResult, Error> {
+    call(CONFIG_RELOAD, params, auth_token).await
+}
+
+pub async fn config_set(
+    params: ConfigSetParams,
+    auth_token: &Option<String>,
+) -> Result<ConfigSetResult, Error> {
+    call(CONFIG_SET, params, auth_token).await
+}
@@ -0,0 +1,28 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use crate::rpc_api::eth_api::*;
+use jsonrpc_v2::Error;
+
+use crate::rpc_client::call;
+
+pub async fn filecoin_address_to_eth_address(
+    params: FilecoinAddressToEthAddressParams,
+    auth_token: &Option<String>,
+) -> Result<FilecoinAddressToEthAddressResult, Error> {
+    call(FILECOIN_ADDRESS_TO_ETH_ADDRESS, params, auth_token).await
+}
+
+pub async fn eth_address_to_filecoin_address(
+    params: EthAddressToFilecoinAddressParams,
+    auth_token: &Option<String>,
+) -> Result<EthAddressToFilecoinAddressResult, Error> {
+    call(ETH_ADDRESS_TO_FILECOIN_ADDRESS, params, auth_token).await
+}
+
+pub async fn eth_fee_history(
+    params: EthFeeHistoryParams,
+    auth_token: &Option<String>,
+) -> Result<EthFeeHistoryResult, Error> {
+    call(ETH_FEE_HISTORY, params, auth_token).await
+}
@@ -0,0 +1,21 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use crate::rpc_api::f3_api::*;
+use jsonrpc_v2::Error;
+
+use crate::rpc_client::call;
+
+pub async fn f3_get_certificate(
+    params: F3GetCertificateParams,
+    auth_token: &Option<String>,
+) -> Result<F3GetCertificateResult, Error> {
+    call(F3_GET_CERTIFICATE, params, auth_token).await
+}
+
+pub async fn f3_get_latest_certificate(
+    params: F3GetLatestCertificateParams,
+    auth_token: &Option<String>,
+) -> Result<F3GetLatestCertificateResult, Error> {
+    call(F3_GET_LATEST_CERTIFICATE, params, auth_token).await
+}
@@ -0,0 +1,21 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use crate::rpc_api::gas_api::*;
+use jsonrpc_v2::Error;
+
+use crate::rpc_client::call;
+
+pub async fn gas_estimate_message_gas(
+    params: GasEstimateMessageGasParams,
+    auth_token: &Option<String>,
+) -> Result<GasEstimateMessageGasResult, Error> {
+    call(GAS_ESTIMATE_MESSAGE_GAS, params, auth_token).await
+}
+
+pub async fn gas_fee_history(
+    params: GasFeeHistoryParams,
+    auth_token: &Option<String>,
+) -> Result<GasFeeHistoryResult, Error> {
+    call(GAS_FEE_HISTORY, params, auth_token).await
+}
@@ -0,0 +1,21 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use crate::rpc_api::miner_api::*;
+use jsonrpc_v2::Error;
+
+use crate::rpc_client::call;
+
+pub async fn miner_create_block(
+    params: MinerCreateBlockParams,
+    auth_token: &Option<String>,
+) -> Result<MinerCreateBlockResult, Error> {
+    call(MINER_CREATE_BLOCK, params, auth_token).await
+}
+
+pub async fn miner_monitor_summary(
+    params: MinerMonitorSummaryParams,
+    auth_token: &Option<String>,
+) -> Result<MinerMonitorSummaryResult, Error> {
+    call(MINER_MONITOR_SUMMARY, params, auth_token).await
+}
@@ -5,7 +5,12 @@
 pub mod auth_ops;
 pub mod chain_ops;
 pub mod common_ops;
+pub mod config_ops;
 pub mod db_ops;
+pub mod eth_ops;
+pub mod f3_ops;
+pub mod gas_ops;
+pub mod miner_ops;
 pub mod mpool_ops;
 pub mod net_ops;
 pub mod node_ops;
@@ -13,8 +18,9 @@ pub mod progress_ops;
 pub mod state_ops;
 pub mod sync_ops;
 pub mod wallet_ops;
+pub mod watch_ops;
 
-use std::env;
+use std::{env, str::FromStr};
 
 use crate::libp2p::{Multiaddr, Protocol};
 use crate::utils::net::{https_client, hyper, hyper::http::HeaderValue, HyperBodyExt};
@@ -42,22 +48,29 @@ pub struct ApiInfo {
     pub token: Option<String>,
 }
 
+impl FromStr for ApiInfo {
+    type Err = libp2p::multiaddr::Error;
+
+    /// Parses the `[<token>:]<multiaddr>` format of `FULLNODE_API_INFO`
+    fn from_str(api_info: &str) -> Result<Self, Self::Err> {
+        let (multiaddr, token) = match api_info.split_once(':') {
+            // Typically this is when a JWT was provided
+            Some((jwt, host)) => (host.parse()?, Some(jwt.to_owned())),
+            // Use entire API_INFO as host string
+            None => (api_info.parse()?, None),
+        };
+
+        Ok(ApiInfo { multiaddr, token })
+    }
+}
+
 pub static API_INFO: Lazy<ApiInfo> = Lazy::new(|| {
     // Get API_INFO environment variable if exists, otherwise, use default
     // multiaddress
-    let api_info = env::var(API_INFO_KEY).unwrap_or_else(|_| DEFAULT_MULTIADDRESS.to_owned());
-
-    let (multiaddr, token) = match api_info.split_once(':') {
-        // Typically this is when a JWT was provided
-        Some((jwt, host)) => (
-            host.parse().expect("Parse multiaddress"),
-            Some(jwt.to_owned()),
-        ),
-        // Use entire API_INFO env var as host string
-        None => (api_info.parse().expect("Parse multiaddress"), None),
-    };
-
-    ApiInfo { multiaddr, token }
+    env::var(API_INFO_KEY)
+        .unwrap_or_else(|_| DEFAULT_MULTIADDRESS.to_owned())
+        .parse()
+        .expect("Parse multiaddress")
 });
 
 /// Error object in a response
@@ -65,6 +78,9 @@ pub static API_INFO: Lazy<ApiInfo> = Lazy::new(|| {
 pub struct JsonRpcError {
     pub code: i64,
     pub message: String,
+    /// Details of the error, see [`crate::rpc_api::errors::ApiErrorData`]
+    #[serde(default)]
+    pub data: Option<serde_json::Value>,
 }
 
 #[derive(Deserialize)]
@@ -143,6 +159,21 @@ fn multiaddress_to_url(multiaddr: Multiaddr) -> String {
 
 /// Utility method for sending RPC requests over HTTP
 async fn call<P, R>(method_name: &str, params: P, token: &Option<String>) -> Result<R, Error>
+where
+    P: Serialize,
+    R: DeserializeOwned,
+{
+    let token = API_INFO.token.clone().or_else(|| token.clone());
+    call_api(&API_INFO.multiaddr, &token, method_name, params).await
+}
+
+/// Sends an RPC request over HTTP to the node behind the given address
+pub async fn call_api<P, R>(
+    multiaddr: &Multiaddr,
+    token: &Option<String>,
+    method_name: &str,
+    params: P,
+) -> Result<R, Error>
 where
     P: Serialize,
     R: DeserializeOwned,
@@ -152,7 +183,7 @@ where
         .with_params(serde_json::to_value(params)?)
         .finish();
 
-    let api_url = multiaddress_to_url(API_INFO.multiaddr.to_owned());
+    let api_url = multiaddress_to_url(multiaddr.to_owned());
 
     debug!("Using JSON-RPC v2 HTTP URL: {}", api_url);
 
@@ -163,15 +194,8 @@ where
         hyper::Request::post(&api_url).body(serde_json::to_string(&rpc_req)?.into())?;
     let headers_mut = request.headers_mut();
     headers_mut.insert("content-type", HeaderValue::from_static("application/json"));
-    match API_INFO.token.to_owned() {
-        Some(jwt) => {
-            headers_mut.insert("Authorization", HeaderValue::from_str(&jwt)?);
-        }
-        None => {
-            if let Some(jwt) = token {
-                headers_mut.insert("Authorization", HeaderValue::from_str(jwt)?);
-            }
-        }
+    if let Some(jwt) = token {
+        headers_mut.insert("Authorization", HeaderValue::from_str(jwt)?);
     }
     let response = client.request(request).await?;
     let code = response.status();
@@ -198,7 +222,7 @@ where
     match rpc_res {
         JsonRpcResponse::Result { result, .. } => Ok(result),
         JsonRpcResponse::Error { error, .. } => Err(Error::Full {
-            data: None,
+            data: error.data.map(|data| Box::new(data) as _),
             code: error.code,
             message: error.message,
         }),
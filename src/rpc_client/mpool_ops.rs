@@ -6,6 +6,20 @@ use jsonrpc_v2::Error;
 
 use crate::rpc_client::call;
 
+pub async fn mpool_get_nonce(
+    params: MpoolGetNonceParams,
+    auth_token: &Option<String>,
+) -> Result<MpoolGetNonceResult, Error> {
+    call(MPOOL_GET_NONCE, params, auth_token).await
+}
+
+pub async fn mpool_push(
+    params: MpoolPushParams,
+    auth_token: &Option<String>,
+) -> Result<MpoolPushResult, Error> {
+    call(MPOOL_PUSH, params, auth_token).await
+}
+
 pub async fn mpool_pending(
     params: MpoolPendingParams,
     auth_token: &Option<String>,
@@ -19,3 +33,38 @@ pub async fn mpool_push_message(
 ) -> Result<MpoolPushMessageResult, Error> {
     call(MPOOL_PUSH_MESSAGE, params, auth_token).await
 }
+
+pub async fn mpool_batch_push(
+    params: MpoolBatchPushParams,
+    auth_token: &Option<String>,
+) -> Result<MpoolBatchPushResult, Error> {
+    call(MPOOL_BATCH_PUSH, params, auth_token).await
+}
+
+pub async fn mpool_batch_push_message(
+    params: MpoolBatchPushMessageParams,
+    auth_token: &Option<String>,
+) -> Result<MpoolBatchPushMessageResult, Error> {
+    call(MPOOL_BATCH_PUSH_MESSAGE, params, auth_token).await
+}
+
+pub async fn mpool_clear(
+    params: MpoolClearParams,
+    auth_token: &Option<String>,
+) -> Result<MpoolClearResult, Error> {
+    call(MPOOL_CLEAR, params, auth_token).await
+}
+
+pub async fn mpool_stat(
+    params: MpoolStatParams,
+    auth_token: &Option<String>,
+) -> Result<MpoolStatResult, Error> {
+    call(MPOOL_STAT, params, auth_token).await
+}
+
+pub async fn mpool_sequence_gaps(
+    params: MpoolSequenceGapsParams,
+    auth_token: &Option<String>,
+) -> Result<MpoolSequenceGapsResult, Error> {
+    call(MPOOL_SEQUENCE_GAPS, params, auth_token).await
+}
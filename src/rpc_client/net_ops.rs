@@ -20,6 +20,20 @@ pub async fn net_peers(
     call(NET_PEERS, params, auth_token).await
 }
 
+pub async fn net_info(
+    params: NetInfoParams,
+    auth_token: &Option<String>,
+) -> Result<NetInfoResult, Error> {
+    call(NET_INFO, params, auth_token).await
+}
+
+pub async fn net_auto_nat_status(
+    params: NetAutoNatStatusParams,
+    auth_token: &Option<String>,
+) -> Result<NetAutoNatStatusResult, Error> {
+    call(NET_AUTO_NAT_STATUS, params, auth_token).await
+}
+
 pub async fn net_connect(
     params: NetConnectParams,
     auth_token: &Option<String>,
@@ -33,3 +47,38 @@ pub async fn net_disconnect(
 ) -> Result<NetDisconnectResult, Error> {
     call(NET_DISCONNECT, params, auth_token).await
 }
+
+pub async fn net_add_peer(
+    params: NetAddPeerParams,
+    auth_token: &Option<String>,
+) -> Result<NetAddPeerResult, Error> {
+    call(NET_ADD_PEER, params, auth_token).await
+}
+
+pub async fn net_remove_peer(
+    params: NetRemovePeerParams,
+    auth_token: &Option<String>,
+) -> Result<NetRemovePeerResult, Error> {
+    call(NET_REMOVE_PEER, params, auth_token).await
+}
+
+pub async fn net_bandwidth_stats(
+    params: NetBandwidthStatsParams,
+    auth_token: &Option<String>,
+) -> Result<NetBandwidthStatsResult, Error> {
+    call(NET_BANDWIDTH_STATS, params, auth_token).await
+}
+
+pub async fn net_bandwidth_stats_by_peer(
+    params: NetBandwidthStatsByPeerParams,
+    auth_token: &Option<String>,
+) -> Result<NetBandwidthStatsByPeerResult, Error> {
+    call(NET_BANDWIDTH_STATS_BY_PEER, params, auth_token).await
+}
+
+pub async fn net_bandwidth_stats_by_protocol(
+    params: NetBandwidthStatsByProtocolParams,
+    auth_token: &Option<String>,
+) -> Result<NetBandwidthStatsByProtocolResult, Error> {
+    call(NET_BANDWIDTH_STATS_BY_PROTOCOL, params, auth_token).await
+}
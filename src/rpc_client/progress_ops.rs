@@ -12,3 +12,17 @@ pub async fn get_progress(
 ) -> Result<GetProgressResult, Error> {
     call(GET_PROGRESS, params, auth_token).await
 }
+
+pub async fn operation_status(
+    params: OperationStatusParams,
+    auth_token: &Option<String>,
+) -> Result<OperationStatusResult, Error> {
+    call(OPERATION_STATUS, params, auth_token).await
+}
+
+pub async fn operation_cancel(
+    params: OperationCancelParams,
+    auth_token: &Option<String>,
+) -> Result<OperationCancelResult, Error> {
+    call(OPERATION_CANCEL, params, auth_token).await
+}
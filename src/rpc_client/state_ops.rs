@@ -12,3 +12,122 @@ pub async fn state_fetch_root(
 ) -> Result<StateFetchRootResult, Error> {
     call(STATE_FETCH_ROOT, params, auth_token).await
 }
+
+pub async fn state_execution_trace(
+    params: StateExecutionTraceParams,
+    auth_token: &Option<String>,
+) -> Result<StateExecutionTraceResult, Error> {
+    call(STATE_EXECUTION_TRACE, params, auth_token).await
+}
+
+pub async fn state_list_execution_traces(
+    params: StateListExecutionTracesParams,
+    auth_token: &Option<String>,
+) -> Result<StateListExecutionTracesResult, Error> {
+    call(STATE_LIST_EXECUTION_TRACES, params, auth_token).await
+}
+
+pub async fn state_compute(
+    params: StateComputeParams,
+    auth_token: &Option<String>,
+) -> Result<StateComputeResult, Error> {
+    call(STATE_COMPUTE, params, auth_token).await
+}
+
+pub async fn state_compute_messages(
+    params: StateComputeMessagesParams,
+    auth_token: &Option<String>,
+) -> Result<StateComputeMessagesResult, Error> {
+    call(STATE_COMPUTE_MESSAGES, params, auth_token).await
+}
+
+pub async fn state_get_randomness_from_tickets(
+    params: StateGetRandomnessFromTicketsParams,
+    auth_token: &Option<String>,
+) -> Result<StateGetRandomnessFromTicketsResult, Error> {
+    call(STATE_GET_RANDOMNESS_FROM_TICKETS, params, auth_token).await
+}
+
+pub async fn state_get_randomness_from_beacon(
+    params: StateGetRandomnessFromBeaconParams,
+    auth_token: &Option<String>,
+) -> Result<StateGetRandomnessFromBeaconResult, Error> {
+    call(STATE_GET_RANDOMNESS_FROM_BEACON, params, auth_token).await
+}
+
+pub async fn state_decode_params(
+    params: StateDecodeParamsParams,
+    auth_token: &Option<String>,
+) -> Result<StateDecodeParamsResult, Error> {
+    call(STATE_DECODE_PARAMS, params, auth_token).await
+}
+
+pub async fn state_encode_params(
+    params: StateEncodeParamsParams,
+    auth_token: &Option<String>,
+) -> Result<StateEncodeParamsResult, Error> {
+    call(STATE_ENCODE_PARAMS, params, auth_token).await
+}
+
+pub async fn state_miner_power(
+    params: StateMinerPowerParams,
+    auth_token: &Option<String>,
+) -> Result<StateMinerPowerResult, Error> {
+    call(STATE_MINER_POWER, params, auth_token).await
+}
+
+pub async fn state_list_miners(
+    params: StateListMinersParams,
+    auth_token: &Option<String>,
+) -> Result<StateListMinersResult, Error> {
+    call(STATE_LIST_MINERS, params, auth_token).await
+}
+
+pub async fn state_balance_history(
+    params: StateBalanceHistoryParams,
+    auth_token: &Option<String>,
+) -> Result<StateBalanceHistoryResult, Error> {
+    call(STATE_BALANCE_HISTORY, params, auth_token).await
+}
+
+pub async fn state_balance_history_recompute(
+    params: StateBalanceHistoryParams,
+    auth_token: &Option<String>,
+) -> Result<StateBalanceHistoryResult, Error> {
+    call(STATE_BALANCE_HISTORY_RECOMPUTE, params, auth_token).await
+}
+
+pub async fn state_miner_disputable_posts(
+    params: StateMinerDisputablePoStsParams,
+    auth_token: &Option<String>,
+) -> Result<StateMinerDisputablePoStsResult, Error> {
+    call(STATE_MINER_DISPUTABLE_POSTS, params, auth_token).await
+}
+
+pub async fn state_dispute_post_message(
+    params: StateDisputePoStMessageParams,
+    auth_token: &Option<String>,
+) -> Result<StateDisputePoStMessageResult, Error> {
+    call(STATE_DISPUTE_POST_MESSAGE, params, auth_token).await
+}
+
+pub async fn state_lookup_id(
+    params: StateLookupIdParams,
+    auth_token: &Option<String>,
+) -> Result<StateLookupIdResult, Error> {
+    call(STATE_LOOKUP_ID, params, auth_token).await
+}
+
+pub async fn state_account_key(
+    params: StateAccountKeyParams,
+    auth_token: &Option<String>,
+) -> Result<StateAccountKeyResult, Error> {
+    call(STATE_ACCOUNT_KEY, params, auth_token).await
+}
+
+pub async fn state_head_state(
+    params: StateHeadStateParams,
+    auth_token: &Option<String>,
+) -> Result<StateHeadStateResult, Error> {
+    call(STATE_HEAD_STATE, params, auth_token).await
+}
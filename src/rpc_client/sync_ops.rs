@@ -20,9 +20,30 @@ pub async fn sync_mark_bad(
     call(SYNC_MARK_BAD, params, auth_token).await
 }
 
+pub async fn sync_unmark_bad(
+    params: SyncUnmarkBadParams,
+    auth_token: &Option<String>,
+) -> Result<SyncUnmarkBadResult, JsonRpcError> {
+    call(SYNC_UNMARK_BAD, params, auth_token).await
+}
+
+pub async fn sync_unmark_all_bad(
+    params: SyncUnmarkAllBadParams,
+    auth_token: &Option<String>,
+) -> Result<SyncUnmarkAllBadResult, JsonRpcError> {
+    call(SYNC_UNMARK_ALL_BAD, params, auth_token).await
+}
+
 pub async fn sync_status(
     params: SyncStateParams,
     auth_token: &Option<String>,
 ) -> Result<SyncStateResult, JsonRpcError> {
     call(SYNC_STATE, params, auth_token).await
 }
+
+pub async fn sync_checkpoint(
+    params: SyncCheckpointParams,
+    auth_token: &Option<String>,
+) -> Result<SyncCheckpointResult, JsonRpcError> {
+    call(SYNC_CHECKPOINT, params, auth_token).await
+}
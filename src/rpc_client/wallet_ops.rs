@@ -69,6 +69,20 @@ pub async fn wallet_sign(
     call(WALLET_SIGN, message, auth_token).await
 }
 
+pub async fn wallet_sign_batch(
+    params: WalletSignBatchParams,
+    auth_token: &Option<String>,
+) -> Result<WalletSignBatchResult, Error> {
+    call(WALLET_SIGN_BATCH, params, auth_token).await
+}
+
+pub async fn wallet_unlock(
+    passphrase: WalletUnlockParams,
+    auth_token: &Option<String>,
+) -> Result<WalletUnlockResult, Error> {
+    call(WALLET_UNLOCK, passphrase, auth_token).await
+}
+
 pub async fn wallet_verify(
     message: WalletVerifyParams,
     auth_token: &Option<String>,
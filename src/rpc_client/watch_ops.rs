@@ -0,0 +1,35 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use crate::rpc_api::watch_api::*;
+use jsonrpc_v2::Error;
+
+use crate::rpc_client::call;
+
+pub async fn watch_address(
+    params: WatchAddressParams,
+    auth_token: &Option<String>,
+) -> Result<WatchAddressResult, Error> {
+    call(WATCH_ADDRESS, params, auth_token).await
+}
+
+pub async fn unwatch_address(
+    params: UnwatchAddressParams,
+    auth_token: &Option<String>,
+) -> Result<UnwatchAddressResult, Error> {
+    call(UNWATCH_ADDRESS, params, auth_token).await
+}
+
+pub async fn watch_list(
+    params: WatchListParams,
+    auth_token: &Option<String>,
+) -> Result<WatchListResult, Error> {
+    call(WATCH_LIST, params, auth_token).await
+}
+
+pub async fn watch_events(
+    params: WatchEventsParams,
+    auth_token: &Option<String>,
+) -> Result<WatchEventsResult, Error> {
+    call(WATCH_EVENTS, params, auth_token).await
+}
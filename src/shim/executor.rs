@@ -1,14 +1,21 @@
 // Copyright 2019-2023 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
-use std::borrow::Borrow;
+use std::{borrow::Borrow, fmt};
 
+use cid::Cid;
 use fvm::executor::ApplyRet as ApplyRet_v2;
 use fvm3::executor::ApplyRet as ApplyRet_v3;
+use fvm_ipld_encoding::RawBytes as RawBytes_v2;
 use fvm_ipld_encoding3::RawBytes;
+use fvm_shared::error::ExitCode as ExitCode_v2;
 use fvm_shared::receipt::Receipt as Receipt_v2;
 use fvm_shared3::error::ExitCode;
+use fvm_shared3::event::StampedEvent;
 pub use fvm_shared3::receipt::Receipt as Receipt_v3;
-use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde::{
+    de::{self, SeqAccess, Visitor},
+    Deserialize, Deserializer, Serialize, Serializer,
+};
 
 use crate::shim::econ::TokenAmount;
 
@@ -58,6 +65,14 @@ impl ApplyRet {
             ApplyRet::V3(v3) => Receipt::V3(v3.msg_receipt.clone()),
         }
     }
+
+    /// Events emitted by the actors, only supported from FVM v3
+    pub fn events(&self) -> Vec<StampedEvent> {
+        match self {
+            ApplyRet::V2(_) => Vec::new(),
+            ApplyRet::V3(v3) => v3.events.clone(),
+        }
+    }
 }
 
 #[derive(PartialEq, Clone, Debug)]
@@ -78,12 +93,57 @@ impl Serialize for Receipt {
     }
 }
 
+/// Receipts are tuples of 3 elements before the events were introduced in
+/// NV18, and of 4 elements after, the last one being the root of the events.
 impl<'de> Deserialize<'de> for Receipt {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
         D: Deserializer<'de>,
     {
-        Receipt_v2::deserialize(deserializer).map(Receipt::V2)
+        struct ReceiptVisitor;
+
+        impl<'de> Visitor<'de> for ReceiptVisitor {
+            type Value = Receipt;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a receipt tuple of 3 or 4 elements")
+            }
+
+            fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+            where
+                A: SeqAccess<'de>,
+            {
+                let exit_code: u32 = seq
+                    .next_element()?
+                    .ok_or_else(|| de::Error::invalid_length(0, &self))?;
+                let return_data: RawBytes = seq
+                    .next_element()?
+                    .ok_or_else(|| de::Error::invalid_length(1, &self))?;
+                let gas_used: u64 = seq
+                    .next_element()?
+                    .ok_or_else(|| de::Error::invalid_length(2, &self))?;
+                match seq.next_element::<Option<Cid>>()? {
+                    None => Ok(Receipt::V2(Receipt_v2 {
+                        exit_code: ExitCode_v2::new(exit_code),
+                        return_data: RawBytes_v2::from(Vec::<u8>::from(return_data)),
+                        gas_used: i64::try_from(gas_used).map_err(de::Error::custom)?,
+                    })),
+                    Some(events_root) => {
+                        if seq.next_element::<de::IgnoredAny>()?.is_some() {
+                            return Err(de::Error::invalid_length(5, &self));
+                        }
+                        Ok(Receipt::V3(Receipt_v3 {
+                            exit_code: ExitCode::new(exit_code),
+                            return_data,
+                            gas_used,
+                            events_root,
+                        }))
+                    }
+                }
+            }
+        }
+
+        deserializer.deserialize_seq(ReceiptVisitor)
     }
 }
 
@@ -108,6 +168,14 @@ impl Receipt {
             Receipt::V3(v3) => v3.gas_used,
         }
     }
+
+    /// Root of the events emitted by the message, only supported from FVM v3
+    pub fn events_root(&self) -> Option<Cid> {
+        match self {
+            Receipt::V2(_) => None,
+            Receipt::V3(v3) => v3.events_root,
+        }
+    }
 }
 
 impl From<Receipt_v3> for Receipt {
@@ -0,0 +1,125 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Bounds the number of tipsets executed at the same time. Executions are
+//! split in lanes by what they are done for, each lane having its own slots,
+//! so that computing states for the API or the backfill job never delays the
+//! validation of the next head of the chain.
+
+use std::{fmt, sync::Arc};
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// Number of tipsets each lane executes at the same time
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct ExecutionConfig {
+    /// Executions of the tipsets synced, and of the bases of the blocks mined
+    pub validation_lanes: usize,
+    /// Executions requested through the API, e.g. `StateCompute` or
+    /// `StateReplay`
+    pub rpc_lanes: usize,
+    /// Executions of the backfill job
+    pub backfill_lanes: usize,
+}
+
+impl Default for ExecutionConfig {
+    fn default() -> Self {
+        Self {
+            validation_lanes: 4,
+            rpc_lanes: 2,
+            backfill_lanes: 1,
+        }
+    }
+}
+
+/// What a tipset is executed for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExecutionLane {
+    Validation,
+    Rpc,
+    Backfill,
+}
+
+impl fmt::Display for ExecutionLane {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Validation => write!(f, "validation"),
+            Self::Rpc => write!(f, "rpc"),
+            Self::Backfill => write!(f, "backfill"),
+        }
+    }
+}
+
+pub(in crate::state_manager) struct ExecutionPool {
+    validation: Arc<Semaphore>,
+    rpc: Arc<Semaphore>,
+    backfill: Arc<Semaphore>,
+}
+
+impl ExecutionPool {
+    /// Lanes configured with no slot get one, for their executions to
+    /// eventually run.
+    pub fn new(config: &ExecutionConfig) -> Self {
+        let lane = |slots: usize| Arc::new(Semaphore::new(slots.max(1)));
+        Self {
+            validation: lane(config.validation_lanes),
+            rpc: lane(config.rpc_lanes),
+            backfill: lane(config.backfill_lanes),
+        }
+    }
+
+    fn semaphore(&self, lane: ExecutionLane) -> &Arc<Semaphore> {
+        match lane {
+            ExecutionLane::Validation => &self.validation,
+            ExecutionLane::Rpc => &self.rpc,
+            ExecutionLane::Backfill => &self.backfill,
+        }
+    }
+
+    /// Waits for a slot of the lane, held until the permit is dropped
+    pub async fn acquire(&self, lane: ExecutionLane) -> OwnedSemaphorePermit {
+        self.semaphore(lane)
+            .clone()
+            .acquire_owned()
+            .await
+            .expect("the semaphores of the pool are never closed")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn lanes_are_bounded_independently() {
+        let pool = ExecutionPool::new(&ExecutionConfig {
+            validation_lanes: 1,
+            rpc_lanes: 1,
+            backfill_lanes: 0,
+        });
+        let _rpc = pool.acquire(ExecutionLane::Rpc).await;
+        let _backfill = pool.acquire(ExecutionLane::Backfill).await;
+
+        // A busy RPC lane doesn't hold the validation back
+        let validation = pool.acquire(ExecutionLane::Validation).await;
+        assert_eq!(
+            pool.semaphore(ExecutionLane::Validation)
+                .available_permits(),
+            0
+        );
+        drop(validation);
+        assert_eq!(
+            pool.semaphore(ExecutionLane::Validation)
+                .available_permits(),
+            1
+        );
+
+        assert_eq!(pool.semaphore(ExecutionLane::Rpc).available_permits(), 0);
+        assert_eq!(
+            pool.semaphore(ExecutionLane::Backfill).available_permits(),
+            0
+        );
+    }
+}
@@ -0,0 +1,729 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Registry of the parameters and return values of the methods of the
+//! built-in actors, to decode them from CBOR into readable JSON and to encode
+//! them back. Parameters are mostly CBOR tuples, so the registry names their
+//! fields, and tells which ones are addresses or token amounts to print them
+//! the way users know them.
+
+use std::str::FromStr;
+
+use crate::ipld::json::{IpldJson, IpldJsonRef};
+use crate::networks::Height;
+use crate::shim::{
+    address::Address,
+    econ::TokenAmount,
+    machine::{
+        Manifest, ACCOUNT_ACTOR_NAME, EAM_ACTOR_NAME, EVM_ACTOR_NAME, INIT_ACTOR_NAME,
+        MARKET_ACTOR_NAME, MINER_ACTOR_NAME, MULTISIG_ACTOR_NAME, PAYCH_ACTOR_NAME,
+        POWER_ACTOR_NAME, REWARD_ACTOR_NAME, VERIFREG_ACTOR_NAME,
+    },
+    version::NetworkVersion,
+};
+use anyhow::{bail, ensure, Context};
+use cid::Cid;
+use fil_actor_account_state::v11 as account;
+use fil_actor_init_state::v11 as init;
+use fil_actor_interface::{market, multisig, reward};
+use fil_actor_miner_state::v11 as miner;
+use fil_actor_power_state::v11 as power;
+use fvm_ipld_blockstore::Blockstore;
+use fvm_ipld_encoding::CborStore;
+use fvm_shared::{MethodNum, METHOD_SEND};
+use libipld_core::ipld::Ipld;
+use num::BigInt;
+use serde_json::Value;
+use strum::IntoEnumIterator;
+
+/// How a value is shown in JSON
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    /// Address, as a string such as `f01234`
+    Address,
+    /// Token amount, as a string of attoFIL
+    TokenAmount,
+    /// Anything else, as IPLD JSON
+    Any,
+}
+
+/// Layout of the parameters or of the return value of a method
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Schema {
+    /// Nothing
+    Empty,
+    /// Single value
+    Value(Kind),
+    /// Tuple, shown as an object with the given fields
+    Tuple(&'static [(&'static str, Kind)]),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Method {
+    pub name: &'static str,
+    pub params: Schema,
+    pub returns: Schema,
+}
+
+struct Entry {
+    actor: &'static str,
+    number: MethodNum,
+    /// First actors version with this layout of the method
+    since: u64,
+    method: Method,
+}
+
+const fn entry(
+    actor: &'static str,
+    number: MethodNum,
+    since: u64,
+    name: &'static str,
+    params: Schema,
+    returns: Schema,
+) -> Entry {
+    Entry {
+        actor,
+        number,
+        since,
+        method: Method {
+            name,
+            params,
+            returns,
+        },
+    }
+}
+
+const SEND: Method = Method {
+    name: "Send",
+    params: Schema::Empty,
+    returns: Schema::Empty,
+};
+
+use Kind::{Address as Addr, Any, TokenAmount as Amount};
+use Schema::{Empty, Tuple, Value as Single};
+
+const EXEC_RETURN: Schema = Tuple(&[("IdAddress", Addr), ("RobustAddress", Addr)]);
+const EAM_CREATE_RETURN: Schema = Tuple(&[
+    ("ActorId", Any),
+    ("RobustAddress", Addr),
+    ("EthAddress", Any),
+]);
+
+/// Methods whose parameters are decoded, numbered after the `Method` enums of
+/// the actor crates. The actors Forest has no state crate for (payment
+/// channels, verified registry, EAM and EVM) are numbered after the
+/// built-in actors sources.
+const METHODS: &[Entry] = &[
+    // Account
+    entry(
+        ACCOUNT_ACTOR_NAME,
+        account::Method::Constructor as MethodNum,
+        8,
+        "Constructor",
+        Single(Addr),
+        Empty,
+    ),
+    entry(
+        ACCOUNT_ACTOR_NAME,
+        account::Method::PubkeyAddress as MethodNum,
+        8,
+        "PubkeyAddress",
+        Empty,
+        Single(Addr),
+    ),
+    entry(
+        ACCOUNT_ACTOR_NAME,
+        account::Method::AuthenticateMessageExported as MethodNum,
+        9,
+        "AuthenticateMessage",
+        Tuple(&[("Signature", Any), ("Message", Any)]),
+        Single(Any),
+    ),
+    // Init
+    entry(
+        INIT_ACTOR_NAME,
+        init::Method::Exec as MethodNum,
+        8,
+        "Exec",
+        Tuple(&[("CodeCid", Any), ("ConstructorParams", Any)]),
+        EXEC_RETURN,
+    ),
+    entry(
+        INIT_ACTOR_NAME,
+        init::Method::Exec4 as MethodNum,
+        10,
+        "Exec4",
+        Tuple(&[
+            ("CodeCid", Any),
+            ("ConstructorParams", Any),
+            ("SubAddress", Any),
+        ]),
+        EXEC_RETURN,
+    ),
+    // Multisig
+    entry(
+        MULTISIG_ACTOR_NAME,
+        multisig::Method::Propose as MethodNum,
+        8,
+        "Propose",
+        Tuple(&[
+            ("To", Addr),
+            ("Value", Amount),
+            ("Method", Any),
+            ("Params", Any),
+        ]),
+        Tuple(&[
+            ("TxnId", Any),
+            ("Applied", Any),
+            ("Code", Any),
+            ("Ret", Any),
+        ]),
+    ),
+    entry(
+        MULTISIG_ACTOR_NAME,
+        multisig::Method::Approve as MethodNum,
+        8,
+        "Approve",
+        Tuple(&[("Id", Any), ("ProposalHash", Any)]),
+        Tuple(&[("Applied", Any), ("Code", Any), ("Ret", Any)]),
+    ),
+    entry(
+        MULTISIG_ACTOR_NAME,
+        multisig::Method::Cancel as MethodNum,
+        8,
+        "Cancel",
+        Tuple(&[("Id", Any), ("ProposalHash", Any)]),
+        Empty,
+    ),
+    entry(
+        MULTISIG_ACTOR_NAME,
+        multisig::Method::AddSigner as MethodNum,
+        8,
+        "AddSigner",
+        Tuple(&[("Signer", Addr), ("Increase", Any)]),
+        Empty,
+    ),
+    entry(
+        MULTISIG_ACTOR_NAME,
+        multisig::Method::RemoveSigner as MethodNum,
+        8,
+        "RemoveSigner",
+        Tuple(&[("Signer", Addr), ("Decrease", Any)]),
+        Empty,
+    ),
+    entry(
+        MULTISIG_ACTOR_NAME,
+        multisig::Method::SwapSigner as MethodNum,
+        8,
+        "SwapSigner",
+        Tuple(&[("From", Addr), ("To", Addr)]),
+        Empty,
+    ),
+    entry(
+        MULTISIG_ACTOR_NAME,
+        multisig::Method::ChangeNumApprovalsThreshold as MethodNum,
+        8,
+        "ChangeNumApprovalsThreshold",
+        Tuple(&[("NewThreshold", Any)]),
+        Empty,
+    ),
+    entry(
+        MULTISIG_ACTOR_NAME,
+        multisig::Method::LockBalance as MethodNum,
+        8,
+        "LockBalance",
+        Tuple(&[
+            ("StartEpoch", Any),
+            ("UnlockDuration", Any),
+            ("Amount", Amount),
+        ]),
+        Empty,
+    ),
+    // Miner
+    entry(
+        MINER_ACTOR_NAME,
+        miner::Method::ChangeWorkerAddress as MethodNum,
+        8,
+        "ChangeWorkerAddress",
+        Tuple(&[("NewWorker", Addr), ("NewControlAddrs", Any)]),
+        Empty,
+    ),
+    entry(
+        MINER_ACTOR_NAME,
+        miner::Method::ChangePeerID as MethodNum,
+        8,
+        "ChangePeerID",
+        Tuple(&[("NewID", Any)]),
+        Empty,
+    ),
+    entry(
+        MINER_ACTOR_NAME,
+        miner::Method::SubmitWindowedPoSt as MethodNum,
+        8,
+        "SubmitWindowedPoSt",
+        Tuple(&[
+            ("Deadline", Any),
+            ("Partitions", Any),
+            ("Proofs", Any),
+            ("ChainCommitEpoch", Any),
+            ("ChainCommitRand", Any),
+        ]),
+        Empty,
+    ),
+    entry(
+        MINER_ACTOR_NAME,
+        miner::Method::PreCommitSector as MethodNum,
+        8,
+        "PreCommitSector",
+        Tuple(&[
+            ("SealProof", Any),
+            ("SectorNumber", Any),
+            ("SealedCID", Any),
+            ("SealRandEpoch", Any),
+            ("DealIDs", Any),
+            ("Expiration", Any),
+            ("ReplaceCapacity", Any),
+            ("ReplaceSectorDeadline", Any),
+            ("ReplaceSectorPartition", Any),
+            ("ReplaceSectorNumber", Any),
+        ]),
+        Empty,
+    ),
+    entry(
+        MINER_ACTOR_NAME,
+        miner::Method::ProveCommitSector as MethodNum,
+        8,
+        "ProveCommitSector",
+        Tuple(&[("SectorNumber", Any), ("Proof", Any)]),
+        Empty,
+    ),
+    entry(
+        MINER_ACTOR_NAME,
+        miner::Method::ExtendSectorExpiration as MethodNum,
+        8,
+        "ExtendSectorExpiration",
+        Tuple(&[("Extensions", Any)]),
+        Empty,
+    ),
+    entry(
+        MINER_ACTOR_NAME,
+        miner::Method::DeclareFaults as MethodNum,
+        8,
+        "DeclareFaults",
+        Tuple(&[("Faults", Any)]),
+        Empty,
+    ),
+    entry(
+        MINER_ACTOR_NAME,
+        miner::Method::DeclareFaultsRecovered as MethodNum,
+        8,
+        "DeclareFaultsRecovered",
+        Tuple(&[("Recoveries", Any)]),
+        Empty,
+    ),
+    entry(
+        MINER_ACTOR_NAME,
+        miner::Method::WithdrawBalance as MethodNum,
+        8,
+        "WithdrawBalance",
+        Tuple(&[("AmountRequested", Amount)]),
+        Empty,
+    ),
+    entry(
+        MINER_ACTOR_NAME,
+        miner::Method::WithdrawBalance as MethodNum,
+        9,
+        "WithdrawBalance",
+        Tuple(&[("AmountRequested", Amount)]),
+        Single(Amount),
+    ),
+    entry(
+        MINER_ACTOR_NAME,
+        miner::Method::ChangeMultiaddrs as MethodNum,
+        8,
+        "ChangeMultiaddrs",
+        Tuple(&[("NewMultiaddrs", Any)]),
+        Empty,
+    ),
+    entry(
+        MINER_ACTOR_NAME,
+        miner::Method::ConfirmUpdateWorkerKey as MethodNum,
+        8,
+        "ConfirmUpdateWorkerKey",
+        Empty,
+        Empty,
+    ),
+    entry(
+        MINER_ACTOR_NAME,
+        miner::Method::ChangeOwnerAddress as MethodNum,
+        8,
+        "ChangeOwnerAddress",
+        Single(Addr),
+        Empty,
+    ),
+    // Power
+    entry(
+        POWER_ACTOR_NAME,
+        power::Method::CreateMiner as MethodNum,
+        8,
+        "CreateMiner",
+        Tuple(&[
+            ("Owner", Addr),
+            ("Worker", Addr),
+            ("WindowPoStProofType", Any),
+            ("Peer", Any),
+            ("Multiaddrs", Any),
+        ]),
+        EXEC_RETURN,
+    ),
+    // Market
+    entry(
+        MARKET_ACTOR_NAME,
+        market::Method::AddBalance as MethodNum,
+        8,
+        "AddBalance",
+        Single(Addr),
+        Empty,
+    ),
+    entry(
+        MARKET_ACTOR_NAME,
+        market::Method::WithdrawBalance as MethodNum,
+        8,
+        "WithdrawBalance",
+        Tuple(&[("ProviderOrClientAddress", Addr), ("Amount", Amount)]),
+        Empty,
+    ),
+    entry(
+        MARKET_ACTOR_NAME,
+        market::Method::WithdrawBalance as MethodNum,
+        9,
+        "WithdrawBalance",
+        Tuple(&[("ProviderOrClientAddress", Addr), ("Amount", Amount)]),
+        Single(Amount),
+    ),
+    entry(
+        MARKET_ACTOR_NAME,
+        market::Method::PublishStorageDeals as MethodNum,
+        8,
+        "PublishStorageDeals",
+        Tuple(&[("Deals", Any)]),
+        Tuple(&[("IDs", Any), ("ValidDeals", Any)]),
+    ),
+    // Reward
+    entry(
+        REWARD_ACTOR_NAME,
+        reward::Method::AwardBlockReward as MethodNum,
+        8,
+        "AwardBlockReward",
+        Tuple(&[
+            ("Miner", Addr),
+            ("Penalty", Amount),
+            ("GasReward", Amount),
+            ("WinCount", Any),
+        ]),
+        Empty,
+    ),
+    entry(
+        REWARD_ACTOR_NAME,
+        reward::Method::ThisEpochReward as MethodNum,
+        8,
+        "ThisEpochReward",
+        Empty,
+        Tuple(&[
+            ("ThisEpochRewardSmoothed", Any),
+            ("ThisEpochBaselinePower", Any),
+        ]),
+    ),
+    // Payment channel
+    entry(
+        PAYCH_ACTOR_NAME,
+        1,
+        8,
+        "Constructor",
+        Tuple(&[("From", Addr), ("To", Addr)]),
+        Empty,
+    ),
+    entry(
+        PAYCH_ACTOR_NAME,
+        2,
+        8,
+        "UpdateChannelState",
+        Tuple(&[("Sv", Any), ("Secret", Any)]),
+        Empty,
+    ),
+    entry(PAYCH_ACTOR_NAME, 3, 8, "Settle", Empty, Empty),
+    entry(PAYCH_ACTOR_NAME, 4, 8, "Collect", Empty, Empty),
+    // Verified registry
+    entry(
+        VERIFREG_ACTOR_NAME,
+        2,
+        8,
+        "AddVerifier",
+        Tuple(&[("Address", Addr), ("Allowance", Any)]),
+        Empty,
+    ),
+    entry(
+        VERIFREG_ACTOR_NAME,
+        3,
+        8,
+        "RemoveVerifier",
+        Single(Addr),
+        Empty,
+    ),
+    entry(
+        VERIFREG_ACTOR_NAME,
+        4,
+        8,
+        "AddVerifiedClient",
+        Tuple(&[("Address", Addr), ("Allowance", Any)]),
+        Empty,
+    ),
+    // Ethereum Address Manager
+    entry(
+        EAM_ACTOR_NAME,
+        2,
+        10,
+        "Create",
+        Tuple(&[("Initcode", Any), ("Nonce", Any)]),
+        EAM_CREATE_RETURN,
+    ),
+    entry(
+        EAM_ACTOR_NAME,
+        3,
+        10,
+        "Create2",
+        Tuple(&[("Initcode", Any), ("Salt", Any)]),
+        EAM_CREATE_RETURN,
+    ),
+    entry(
+        EAM_ACTOR_NAME,
+        4,
+        10,
+        "CreateExternal",
+        Single(Any),
+        EAM_CREATE_RETURN,
+    ),
+    // EVM
+    entry(
+        EVM_ACTOR_NAME,
+        3844450837,
+        10,
+        "InvokeContract",
+        Single(Any),
+        Single(Any),
+    ),
+];
+
+/// Version of the built-in actors running at the given network version
+pub fn actors_version(network_version: NetworkVersion) -> anyhow::Result<u64> {
+    Height::iter()
+        .find(|height| NetworkVersion::from(*height) == network_version)
+        .map(Height::actors_version)
+        .with_context(|| format!("unknown network version {network_version:?}"))
+}
+
+/// Looks up a method of a built-in actor, by the name of the actor in the
+/// manifest, for the given actors version.
+pub fn lookup(actor: &str, version: u64, number: MethodNum) -> Option<Method> {
+    if number == METHOD_SEND {
+        return Some(SEND);
+    }
+    METHODS
+        .iter()
+        .filter(|e| e.actor == actor && e.number == number && e.since <= version)
+        .max_by_key(|e| e.since)
+        .map(|e| e.method)
+}
+
+/// Name of the built-in actor with the given code in the manifest of the
+/// given state
+pub fn builtin_actor_name<DB: Blockstore>(
+    store: &DB,
+    system_state: &Cid,
+    code: &Cid,
+) -> anyhow::Result<String> {
+    let state: fil_actor_system_state::v10::State = store
+        .get_cbor(system_state)?
+        .context("failed to load the system actor state")?;
+    let manifest = Manifest::load_with_actors(store, &state.builtin_actors, 1)?;
+    manifest
+        .builtin_actors()
+        .find(|(_, c)| *c == code)
+        .map(|(name, _)| name.clone())
+        .with_context(|| format!("{code} is not the code of a built-in actor"))
+}
+
+impl Kind {
+    fn to_json(self, ipld: Ipld) -> anyhow::Result<Value> {
+        match (self, ipld) {
+            (Kind::Address, Ipld::Bytes(bytes)) => {
+                Ok(Value::String(Address::from_bytes(&bytes)?.to_string()))
+            }
+            (Kind::TokenAmount, ipld @ Ipld::Bytes(_)) => {
+                let amount: TokenAmount =
+                    fvm_ipld_encoding::from_slice(&fvm_ipld_encoding::to_vec(&ipld)?)?;
+                Ok(Value::String(amount.atto().to_string()))
+            }
+            (Kind::Address | Kind::TokenAmount, Ipld::Null) => Ok(Value::Null),
+            (Kind::Address | Kind::TokenAmount, ipld) => {
+                bail!("expected {self:?} bytes, got {ipld:?}")
+            }
+            (Kind::Any, ipld) => Ok(serde_json::to_value(IpldJsonRef(&ipld))?),
+        }
+    }
+
+    fn to_ipld(self, value: &Value) -> anyhow::Result<Ipld> {
+        match (self, value) {
+            (Kind::Address | Kind::TokenAmount, Value::Null) => Ok(Ipld::Null),
+            (Kind::Address, Value::String(s)) => Ok(Ipld::Bytes(Address::from_str(s)?.to_bytes())),
+            (Kind::TokenAmount, Value::String(s)) => {
+                let amount = TokenAmount::from_atto(s.parse::<BigInt>()?);
+                Ok(fvm_ipld_encoding::from_slice(&fvm_ipld_encoding::to_vec(
+                    &amount,
+                )?)?)
+            }
+            (Kind::Address | Kind::TokenAmount, value) => {
+                bail!("expected {self:?} string, got {value}")
+            }
+            (Kind::Any, value) => Ok(serde_json::from_value::<IpldJson>(value.clone())?.0),
+        }
+    }
+}
+
+impl Schema {
+    /// Decodes CBOR parameters or return value into JSON
+    pub fn decode(&self, bytes: &[u8]) -> anyhow::Result<Value> {
+        if let Schema::Empty = self {
+            ensure!(bytes.is_empty(), "expected no value");
+            return Ok(Value::Null);
+        }
+        let ipld: Ipld = fvm_ipld_encoding::from_slice(bytes)?;
+        match (self, ipld) {
+            (Schema::Empty, _) => unreachable!("handled above"),
+            (Schema::Value(kind), ipld) => kind.to_json(ipld),
+            (Schema::Tuple(fields), Ipld::List(values)) => {
+                ensure!(
+                    values.len() == fields.len(),
+                    "expected {} fields, got {}",
+                    fields.len(),
+                    values.len()
+                );
+                let object = fields
+                    .iter()
+                    .zip(values)
+                    .map(|((name, kind), value)| Ok((name.to_string(), kind.to_json(value)?)))
+                    .collect::<anyhow::Result<serde_json::Map<_, _>>>()?;
+                Ok(Value::Object(object))
+            }
+            (Schema::Tuple(_), ipld) => bail!("expected a tuple, got {ipld:?}"),
+        }
+    }
+
+    /// Encodes JSON parameters or return value into CBOR
+    pub fn encode(&self, value: &Value) -> anyhow::Result<Vec<u8>> {
+        let ipld = match (self, value) {
+            (Schema::Empty, Value::Null) => return Ok(vec![]),
+            (Schema::Empty, value) => bail!("expected no value, got {value}"),
+            (Schema::Value(kind), value) => kind.to_ipld(value)?,
+            (Schema::Tuple(fields), Value::Object(object)) => Ipld::List(
+                fields
+                    .iter()
+                    .map(|(name, kind)| {
+                        let value = object
+                            .get(*name)
+                            .with_context(|| format!("missing field {name}"))?;
+                        kind.to_ipld(value)
+                    })
+                    .collect::<anyhow::Result<_>>()?,
+            ),
+            (Schema::Tuple(_), value) => bail!("expected an object, got {value}"),
+        };
+        Ok(fvm_ipld_encoding::to_vec(&ipld)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lookup_by_version() {
+        let v8 = lookup(MINER_ACTOR_NAME, 8, 16).unwrap();
+        let v11 = lookup(MINER_ACTOR_NAME, 11, 16).unwrap();
+        assert_eq!(v8.name, "WithdrawBalance");
+        assert_eq!(v8.returns, Schema::Empty);
+        assert_eq!(v11.returns, Schema::Value(Kind::TokenAmount));
+
+        assert_eq!(lookup(INIT_ACTOR_NAME, 9, 3), None);
+        assert_eq!(lookup(INIT_ACTOR_NAME, 10, 3).unwrap().name, "Exec4");
+        assert_eq!(lookup(EVM_ACTOR_NAME, 11, 0), Some(SEND));
+        assert_eq!(lookup(MINER_ACTOR_NAME, 11, 12345), None);
+    }
+
+    #[test]
+    fn storage_methods_are_known() {
+        for number in [5, 6, 7, 8, 10, 11] {
+            assert!(lookup(MINER_ACTOR_NAME, 11, number).is_some());
+        }
+        let publish = lookup(MARKET_ACTOR_NAME, 11, 4).unwrap();
+        assert_eq!(publish.name, "PublishStorageDeals");
+        assert_eq!(
+            lookup(REWARD_ACTOR_NAME, 11, 2).unwrap().name,
+            "AwardBlockReward"
+        );
+        assert_eq!(
+            lookup(PAYCH_ACTOR_NAME, 11, 2).unwrap().name,
+            "UpdateChannelState"
+        );
+    }
+
+    #[test]
+    fn actors_versions() {
+        assert_eq!(actors_version(NetworkVersion::V3).unwrap(), 0);
+        assert_eq!(actors_version(NetworkVersion::V15).unwrap(), 7);
+        assert_eq!(actors_version(NetworkVersion::V18).unwrap(), 10);
+        assert_eq!(actors_version(NetworkVersion::V20).unwrap(), 11);
+        let next = NetworkVersion::from(crate::shim::version::NetworkVersion_v3::new(21));
+        assert!(actors_version(next).is_err());
+    }
+
+    #[test]
+    fn tuple_round_trip() {
+        let method = lookup(MULTISIG_ACTOR_NAME, 11, 2).unwrap();
+        let params = serde_json::json!({
+            "To": "f01234",
+            "Value": "1000000000000000000",
+            "Method": { "/": { "int": "0" } },
+            "Params": { "/": { "bytes": "mAA" } },
+        });
+        let bytes = method.params.encode(&params).unwrap();
+        assert_eq!(method.params.decode(&bytes).unwrap(), params);
+    }
+
+    #[test]
+    fn single_values() {
+        let method = lookup(MARKET_ACTOR_NAME, 11, 2).unwrap();
+        let bytes = fvm_ipld_encoding::to_vec(&Address::new_id(99)).unwrap();
+        assert_eq!(
+            method.params.decode(&bytes).unwrap(),
+            Value::String("f099".into())
+        );
+        assert_eq!(
+            method.params.encode(&Value::String("f099".into())).unwrap(),
+            bytes
+        );
+        assert!(SEND.params.decode(&[]).unwrap().is_null());
+        assert!(SEND.params.decode(&bytes).is_err());
+    }
+
+    #[test]
+    fn mismatched_params() {
+        let method = lookup(MINER_ACTOR_NAME, 11, 3).unwrap();
+        let bytes = fvm_ipld_encoding::to_vec(&(Address::new_id(1),)).unwrap();
+        assert!(method.params.decode(&bytes).is_err());
+        assert!(method
+            .params
+            .encode(&serde_json::json!({ "NewWorker": "f01" }))
+            .is_err());
+    }
+}
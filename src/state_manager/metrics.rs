@@ -2,7 +2,10 @@
 // SPDX-License-Identifier: Apache-2.0, MIT
 
 use lazy_static::lazy_static;
-use prometheus::{core::Opts, Histogram, HistogramOpts};
+use prometheus::{
+    core::{AtomicU64, GenericGauge, Opts},
+    Histogram, HistogramOpts,
+};
 
 lazy_static! {
     pub static ref APPLY_BLOCKS_TIME: Box<Histogram> =
@@ -22,4 +25,17 @@ lazy_static! {
         );
             apply_blocks_time
         };
+    pub static ref TIPSET_STATE_CACHE_SIZE_BYTES: Box<GenericGauge<AtomicU64>> = {
+        let tipset_state_cache_size = Box::new(
+            GenericGauge::<AtomicU64>::new(
+                "tipset_state_cache_size_bytes",
+                "Approximate memory used by the cache of the states computed per tipset",
+            )
+            .expect("Defining the tipset_state_cache_size_bytes metric must succeed"),
+        );
+        prometheus::default_registry()
+            .register(tipset_state_cache_size.clone())
+            .expect("Registering the tipset_state_cache_size_bytes metric with the metrics registry must succeed");
+        tipset_state_cache_size
+    };
 }
@@ -0,0 +1,154 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Readers of the miner actor state not covered by the actor interface, for
+//! the miner monitor and the Window `PoSt` queries of the RPC API.
+
+use crate::shim::clock::ChainEpoch;
+use cid::Cid;
+use fil_actor_interface::miner;
+use fvm_ipld_amt::Amt;
+use fvm_ipld_blockstore::Blockstore;
+
+/// Start of the current proving period of a miner
+pub fn proving_period_start(state: &miner::State) -> ChainEpoch {
+    match state {
+        miner::State::V8(st) => st.proving_period_start,
+        miner::State::V9(st) => st.proving_period_start,
+        miner::State::V10(st) => st.proving_period_start,
+        miner::State::V11(st) => st.proving_period_start,
+    }
+}
+
+/// Walks the expiration queue of a partition in the order of the epochs,
+/// with the number of sectors expiring at each epoch, on time or early, while
+/// `f` returns `true`. The epochs of the queue are quantized to the end of the
+/// proving window of the deadline of the partition.
+pub fn for_each_expiration_while<BS: Blockstore>(
+    store: &BS,
+    partition: &miner::Partition,
+    mut f: impl FnMut(ChainEpoch, u64) -> anyhow::Result<bool>,
+) -> anyhow::Result<()> {
+    match partition {
+        miner::Partition::V8(p) => {
+            Amt::<fil_actor_miner_state::v8::ExpirationSet, _>::load(&p.expiration_q, store)?
+                .for_each_while(|epoch, set| {
+                    f(
+                        epoch as ChainEpoch,
+                        set.on_time_sectors.len() + set.early_sectors.len(),
+                    )
+                })?
+        }
+        miner::Partition::V9(p) => {
+            Amt::<fil_actor_miner_state::v9::ExpirationSet, _>::load(&p.expiration_q, store)?
+                .for_each_while(|epoch, set| {
+                    f(
+                        epoch as ChainEpoch,
+                        set.on_time_sectors.len() + set.early_sectors.len(),
+                    )
+                })?
+        }
+        miner::Partition::V10(p) => {
+            Amt::<fil_actor_miner_state::v10::ExpirationSet, _>::load(&p.expiration_q, store)?
+                .for_each_while(|epoch, set| {
+                    f(
+                        epoch as ChainEpoch,
+                        set.on_time_sectors.len() + set.early_sectors.len(),
+                    )
+                })?
+        }
+        miner::Partition::V11(p) => {
+            Amt::<fil_actor_miner_state::v11::ExpirationSet, _>::load(&p.expiration_q, store)?
+                .for_each_while(|epoch, set| {
+                    f(
+                        epoch as ChainEpoch,
+                        set.on_time_sectors.len() + set.early_sectors.len(),
+                    )
+                })?
+        }
+    }
+    Ok(())
+}
+
+/// Root of the Window `PoSt` proofs of the last proving window of a deadline,
+/// accepted optimistically
+pub fn optimistic_post_submissions_snapshot(deadline: &miner::Deadline) -> Cid {
+    match deadline {
+        miner::Deadline::V8(dl) => dl.optimistic_post_submissions_snapshot,
+        miner::Deadline::V9(dl) => dl.optimistic_post_submissions_snapshot,
+        miner::Deadline::V10(dl) => dl.optimistic_post_submissions_snapshot,
+        miner::Deadline::V11(dl) => dl.optimistic_post_submissions_snapshot,
+    }
+}
+
+/// Walks the Window `PoSt` proofs of the last proving window of a deadline,
+/// with the partitions each of them proves.
+pub fn for_each_optimistic_post<BS: Blockstore>(
+    store: &BS,
+    deadline: &miner::Deadline,
+    mut f: impl FnMut(u64, Vec<u64>) -> anyhow::Result<()>,
+) -> anyhow::Result<()> {
+    let root = optimistic_post_submissions_snapshot(deadline);
+    match deadline {
+        miner::Deadline::V8(_) => {
+            Amt::<fil_actor_miner_state::v8::WindowedPoSt, _>::load(&root, store)?
+                .for_each(|index, post| f(index, post.partitions.iter().collect()))?
+        }
+        miner::Deadline::V9(_) => {
+            Amt::<fil_actor_miner_state::v9::WindowedPoSt, _>::load(&root, store)?
+                .for_each(|index, post| f(index, post.partitions.iter().collect()))?
+        }
+        miner::Deadline::V10(_) => {
+            Amt::<fil_actor_miner_state::v10::WindowedPoSt, _>::load(&root, store)?
+                .for_each(|index, post| f(index, post.partitions.iter().collect()))?
+        }
+        miner::Deadline::V11(_) => {
+            Amt::<fil_actor_miner_state::v11::WindowedPoSt, _>::load(&root, store)?
+                .for_each(|index, post| f(index, post.partitions.iter().collect()))?
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+
+    use super::*;
+    use crate::db::MemoryDB;
+    use fil_actor_miner_state::v11::{ExpirationSet, Partition};
+    use fvm_ipld_bitfield::BitField;
+
+    #[test]
+    fn expirations_are_walked_in_order() {
+        let db = MemoryDB::default();
+        let mut queue = Amt::<ExpirationSet, _>::new(&db);
+        for (epoch, on_time, early) in [(300, vec![1, 2], vec![3]), (120, vec![4], vec![])] {
+            let set = ExpirationSet {
+                on_time_sectors: BitField::try_from_bits(on_time).unwrap(),
+                early_sectors: BitField::try_from_bits(early).unwrap(),
+                ..Default::default()
+            };
+            queue.set(epoch, set).unwrap();
+        }
+        let mut partition = Partition::new(&db).unwrap();
+        partition.expiration_q = queue.flush().unwrap();
+        let partition = miner::Partition::V11(Cow::Owned(partition));
+
+        let mut expirations = vec![];
+        for_each_expiration_while(&db, &partition, |epoch, sectors| {
+            expirations.push((epoch, sectors));
+            Ok(true)
+        })
+        .unwrap();
+        assert_eq!(expirations, vec![(120, 1), (300, 3)]);
+
+        let mut expirations = vec![];
+        for_each_expiration_while(&db, &partition, |epoch, sectors| {
+            expirations.push((epoch, sectors));
+            Ok(false)
+        })
+        .unwrap();
+        assert_eq!(expirations, vec![(120, 1)]);
+    }
+}
@@ -3,25 +3,36 @@
 
 pub mod chain_rand;
 mod errors;
+mod execution_pool;
+pub mod method_registry;
 mod metrics;
+mod miner_state;
+mod power_state;
+mod tipset_state_cache;
 mod utils;
+mod window_post;
 use crate::state_migration::run_state_migrations;
 pub use utils::is_valid_for_sending;
 
 mod vm_circ_supply;
 
-use std::{num::NonZeroUsize, sync::Arc};
+use std::{cell::Cell, num::NonZeroUsize, sync::Arc};
 
 use crate::beacon::{BeaconSchedule, DrandBeacon};
 use crate::blocks::{BlockHeader, Tipset, TipsetKeys};
-use crate::chain::{ChainStore, HeadChange};
-use crate::interpreter::{resolve_to_key_addr, BlockMessages, RewardCalc, VM};
+use crate::chain::{compute_receipts_root, ChainStore, HeadChange};
+use crate::db::MemoryOverlay;
+use crate::interpreter::{
+    resolve_to_key_addr, BlockMessages, DryRunKind, ExecutionTrace, ExecutionTraceStore,
+    RewardCalc, TraceSampling, VMTrace, WatchedActors, VM,
+};
 use crate::json::message_receipt;
-use crate::message::{ChainMessage, Message as MessageTrait};
+use crate::message::{ChainMessage, Message as MessageTrait, SignedMessage};
 use crate::networks::ChainConfig;
 use crate::shim::clock::ChainEpoch;
 use crate::shim::{
     address::{Address, Payload, Protocol, BLS_PUB_LEN},
+    crypto::Signature,
     econ::TokenAmount,
     executor::{ApplyRet, Receipt},
     externs::Rand,
@@ -29,143 +40,32 @@ use crate::shim::{
     state_tree::{ActorState, StateTree},
     version::NetworkVersion,
 };
-use ahash::{HashMap, HashMapExt};
+use ahash::{HashMap, HashMapExt, HashSet};
+use anyhow::Context;
 use chain_rand::ChainRand;
 use cid::Cid;
 use fil_actor_interface::*;
 use fil_actors_shared::v10::runtime::Policy;
 use futures::{channel::oneshot, select, FutureExt};
-use fvm_ipld_amt::Amtv0 as Amt;
 use fvm_ipld_blockstore::Blockstore;
 use fvm_ipld_encoding::Cbor;
 use fvm_ipld_encoding::CborStore;
-use lru::LruCache;
-use nonzero_ext::nonzero;
 use num::BigInt;
 use num_traits::identities::Zero;
 use once_cell::unsync::Lazy;
-use parking_lot::Mutex as SyncMutex;
 use serde::{Deserialize, Serialize};
-use tokio::sync::{broadcast::error::RecvError, Mutex as TokioMutex, RwLock};
+use tokio::sync::{broadcast::error::RecvError, RwLock};
 use tracing::{debug, error, info, instrument, trace, warn};
 use vm_circ_supply::GenesisInfo;
 
 pub use self::errors::*;
-
-const DEFAULT_TIPSET_CACHE_SIZE: NonZeroUsize = nonzero!(1024usize);
-
-/// Intermediary for retrieving state objects and updating actor states.
-type CidPair = (Cid, Cid);
-
-// Various structures for implementing the tipset state cache
-
-struct TipsetStateCacheInner {
-    values: LruCache<TipsetKeys, CidPair>,
-    pending: Vec<(TipsetKeys, Arc<TokioMutex<()>>)>,
-}
-
-impl Default for TipsetStateCacheInner {
-    fn default() -> Self {
-        Self {
-            values: LruCache::new(DEFAULT_TIPSET_CACHE_SIZE),
-            pending: Vec::with_capacity(8),
-        }
-    }
-}
-
-struct TipsetStateCache {
-    cache: Arc<SyncMutex<TipsetStateCacheInner>>,
-}
-
-enum Status {
-    Done(CidPair),
-    Empty(Arc<TokioMutex<()>>),
-}
-
-impl TipsetStateCache {
-    pub fn new() -> Self {
-        Self {
-            cache: Arc::new(SyncMutex::new(TipsetStateCacheInner::default())),
-        }
-    }
-
-    fn with_inner<F, T>(&self, func: F) -> T
-    where
-        F: FnOnce(&mut TipsetStateCacheInner) -> T,
-    {
-        let mut lock = self.cache.lock();
-        func(&mut lock)
-    }
-
-    pub async fn get_or_else<F, Fut>(&self, key: &TipsetKeys, compute: F) -> anyhow::Result<CidPair>
-    where
-        F: Fn() -> Fut,
-        Fut: core::future::Future<Output = anyhow::Result<CidPair>>,
-    {
-        let status = self.with_inner(|inner| match inner.values.get(key) {
-            Some(v) => Status::Done(*v),
-            None => {
-                let option = inner
-                    .pending
-                    .iter()
-                    .find(|(k, _)| k == key)
-                    .map(|(_, mutex)| mutex);
-                match option {
-                    Some(mutex) => Status::Empty(mutex.clone()),
-                    None => {
-                        let mutex = Arc::new(TokioMutex::new(()));
-                        inner.pending.push((key.clone(), mutex.clone()));
-                        Status::Empty(mutex)
-                    }
-                }
-            }
-        });
-        match status {
-            Status::Done(x) => {
-                crate::metrics::LRU_CACHE_HIT
-                    .with_label_values(&[crate::metrics::values::STATE_MANAGER_TIPSET])
-                    .inc();
-                Ok(x)
-            }
-            Status::Empty(mtx) => {
-                let _guard = mtx.lock().await;
-                match self.get(key) {
-                    Some(v) => {
-                        // While locking someone else computed the pending task
-                        crate::metrics::LRU_CACHE_HIT
-                            .with_label_values(&[crate::metrics::values::STATE_MANAGER_TIPSET])
-                            .inc();
-
-                        Ok(v)
-                    }
-                    None => {
-                        // Entry does not have state computed yet, compute value and fill the cache
-                        crate::metrics::LRU_CACHE_MISS
-                            .with_label_values(&[crate::metrics::values::STATE_MANAGER_TIPSET])
-                            .inc();
-
-                        let cid_pair = compute().await?;
-
-                        // Write back to cache, release lock and return value
-                        self.insert(key.clone(), cid_pair);
-                        Ok(cid_pair)
-                    }
-                }
-            }
-        }
-    }
-
-    fn get(&self, key: &TipsetKeys) -> Option<CidPair> {
-        self.with_inner(|inner| inner.values.get(key).copied())
-    }
-
-    fn insert(&self, key: TipsetKeys, value: CidPair) {
-        self.with_inner(|inner| {
-            inner.pending.retain(|(k, _)| k != &key);
-            inner.values.put(key, value);
-        });
-    }
-}
+use self::execution_pool::ExecutionPool;
+pub use self::execution_pool::{ExecutionConfig, ExecutionLane};
+pub use self::miner_state::*;
+pub use self::power_state::*;
+pub use self::tipset_state_cache::DEFAULT_TIPSET_STATE_CACHE_SIZE;
+use self::tipset_state_cache::{CidPair, TipsetStateCache};
+pub use self::window_post::*;
 
 /// Type to represent invocation of state call results.
 #[derive(Serialize, Deserialize)]
@@ -181,6 +81,36 @@ pub struct InvocResult {
 /// An alias Result that represents an `InvocResult` and an Error.
 type StateCallResult = Result<InvocResult, Error>;
 
+/// Maximum number of epochs between a tipset and the epoch
+/// [`StateManager::compute_state`] applies messages at, null rounds whose cron
+/// runs before the messages
+pub const MAX_COMPUTE_STATE_EPOCHS: ChainEpoch = 20;
+
+/// State resulting from messages applied on top of a tipset, see
+/// [`StateManager::compute_state`].
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ComputeStateOutput {
+    #[serde(with = "crate::json::cid")]
+    pub root: Cid,
+    pub trace: Vec<ComputedMessage>,
+}
+
+/// Outcome of a message applied by [`StateManager::compute_state`], cron
+/// messages included.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ComputedMessage {
+    #[serde(with = "crate::json::cid")]
+    pub msg_cid: Cid,
+    #[serde(with = "crate::json::message::json")]
+    pub msg: Message,
+    #[serde(with = "message_receipt::json")]
+    pub msg_rct: Receipt,
+    pub error: Option<String>,
+    pub execution_trace: ExecutionTrace,
+}
+
 /// External format for returning market balance from state.
 #[derive(Default, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
@@ -189,6 +119,74 @@ pub struct MarketBalance {
     locked: TokenAmount,
 }
 
+/// State root to read the head from, see [`StateManager::head_state`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct HeadState {
+    pub epoch: ChainEpoch,
+    #[serde(with = "crate::json::cid")]
+    pub state_root: Cid,
+    /// Whether `state_root` results from the execution of the head, rather
+    /// than being the parent state of the head
+    pub computed: bool,
+}
+
+/// Maximum number of epochs [`StateManager::balance_history`] reports at
+/// once
+pub const MAX_BALANCE_HISTORY_EPOCHS: u64 = 2000;
+
+/// Maximum number of epochs [`StateManager::balance_history`] executes again
+/// per call to compute the states missing from the store
+pub const MAX_BALANCE_HISTORY_RECOMPUTE_EPOCHS: u64 = 120;
+
+/// Balance of an actor at an epoch, see [`StateManager::balance_history`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct BalanceAt {
+    pub epoch: ChainEpoch,
+    /// State the balance is read from
+    #[serde(with = "crate::json::cid")]
+    pub state_root: Cid,
+    /// Zero if the actor doesn't exist at that epoch
+    #[serde(with = "crate::json::token_amount::json")]
+    pub balance: TokenAmount,
+}
+
+/// States computed again by [`StateManager::balance_history`], which are kept
+/// in memory rather than written to the store
+struct RecomputedStates<DB> {
+    db: MemoryOverlay<DB>,
+    /// State roots whose whole state tree is known to be available
+    complete: HashSet<Cid>,
+    /// Number of epochs that can still be executed
+    budget: u64,
+}
+
+impl<DB: Blockstore> RecomputedStates<DB> {
+    /// Returns whether the whole state tree at `state_root` is available, the
+    /// actors and the heads of their states, loading all of it the first time.
+    fn is_complete(&mut self, state_root: &Cid) -> bool {
+        if self.complete.contains(state_root) {
+            return true;
+        }
+        let complete = StateTree::new_from_root(&self.db, state_root)
+            .and_then(|state| {
+                state.for_each(|address, actor| {
+                    anyhow::ensure!(
+                        self.db.has(&actor.state)?,
+                        "the state of {address} is missing"
+                    );
+                    Ok(())
+                })
+            })
+            .is_ok();
+        if complete {
+            self.complete.insert(*state_root);
+        }
+        complete
+    }
+}
+
 /// State manager handles all interactions with the internal Filecoin actors
 /// state. This encapsulates the [`ChainStore`] functionality, which only
 /// handles chain data, to allow for interactions with the underlying state of
@@ -204,6 +202,13 @@ pub struct StateManager<DB> {
     chain_config: Arc<ChainConfig>,
     engine: crate::shim::machine::MultiEngine,
     reward_calc: Arc<dyn RewardCalc>,
+    /// Execution traces of the recently applied messages, when enabled
+    execution_traces: Option<ExecutionTraceStore>,
+    /// Slots of the tipset executions
+    execution_pool: ExecutionPool,
+    /// Whether the state of each new head is computed in the background, see
+    /// [`StateManager::compute_head_states`]
+    precompute_head_state: bool,
 }
 
 impl<DB> StateManager<DB>
@@ -220,15 +225,58 @@ where
 
         Ok(Self {
             cs,
-            cache: TipsetStateCache::new(),
+            cache: TipsetStateCache::new(DEFAULT_TIPSET_STATE_CACHE_SIZE),
             genesis_info: GenesisInfo::from_chain_config(&chain_config),
             beacon,
             chain_config,
             engine: crate::shim::machine::MultiEngine::default(),
             reward_calc,
+            execution_traces: None,
+            execution_pool: ExecutionPool::new(&ExecutionConfig::default()),
+            precompute_head_state: false,
         })
     }
 
+    /// Records the execution traces of the messages applied when computing
+    /// tipset states, selected by `sampling`, keeping the `capacity` most
+    /// recent ones.
+    pub fn with_execution_traces(
+        mut self,
+        capacity: NonZeroUsize,
+        sampling: TraceSampling,
+    ) -> Self {
+        self.execution_traces = Some(ExecutionTraceStore::new(capacity).with_sampling(sampling));
+        self
+    }
+
+    /// Bounds the memory used by the cache of the states computed per tipset,
+    /// `0` disabling it.
+    pub fn with_tipset_state_cache_size(mut self, max_size_bytes: usize) -> Self {
+        self.cache = TipsetStateCache::new(max_size_bytes);
+        self
+    }
+
+    /// Bounds the number of tipsets executed at the same time by each
+    /// [`ExecutionLane`].
+    pub fn with_execution_lanes(mut self, config: &ExecutionConfig) -> Self {
+        self.execution_pool = ExecutionPool::new(config);
+        self
+    }
+
+    /// Makes the RPC requests on the head read its parent state while its
+    /// own state is computed in the background by
+    /// [`StateManager::compute_head_states`], instead of waiting for it.
+    pub fn with_precomputed_head_states(mut self) -> Self {
+        self.precompute_head_state = true;
+        self
+    }
+
+    /// Returns the store of the recent execution traces, if tracing is
+    /// enabled.
+    pub fn execution_traces(&self) -> Option<&ExecutionTraceStore> {
+        self.execution_traces.as_ref()
+    }
+
     pub fn beacon_schedule(&self) -> Arc<BeaconSchedule<DrandBeacon>> {
         self.beacon.clone()
     }
@@ -242,6 +290,47 @@ where
         &self.chain_config
     }
 
+    /// Draws randomness from the ticket chain of the tipset `blocks`, at the
+    /// given round, with the rules of the network version of that round.
+    /// Before network version 13, a null round draws from the tipset before
+    /// it, and from the tipset after it since.
+    pub fn get_chain_randomness(
+        &self,
+        blocks: &TipsetKeys,
+        pers: i64,
+        round: ChainEpoch,
+        entropy: &[u8],
+    ) -> anyhow::Result<[u8; 32]> {
+        let chain_rand = self.chain_rand(blocks.clone());
+        if self.get_network_version(round) >= NetworkVersion::V13 {
+            chain_rand.get_chain_randomness_v2(blocks, pers, round, entropy)
+        } else {
+            chain_rand.get_chain_randomness(blocks, pers, round, entropy, true)
+        }
+    }
+
+    /// Draws randomness from the beacon entries of the tipset `blocks`, at
+    /// the given round, with the rules of the network version of that round.
+    /// Since network version 14, the beacon entry of the round itself is used
+    /// rather than the latest one of the chain.
+    pub fn get_beacon_randomness(
+        &self,
+        blocks: &TipsetKeys,
+        pers: i64,
+        round: ChainEpoch,
+        entropy: &[u8],
+    ) -> anyhow::Result<[u8; 32]> {
+        let chain_rand = self.chain_rand(blocks.clone());
+        let network_version = self.get_network_version(round);
+        if network_version >= NetworkVersion::V14 {
+            chain_rand.get_beacon_randomness_v3(blocks, pers, round, entropy)
+        } else if network_version == NetworkVersion::V13 {
+            chain_rand.get_beacon_randomness_v2(blocks, pers, round, entropy)
+        } else {
+            chain_rand.get_beacon_randomness(blocks, pers, round, entropy, true)
+        }
+    }
+
     /// Gets actor from given [`Cid`], if it exists.
     pub fn get_actor(&self, addr: &Address, state_cid: Cid) -> anyhow::Result<Option<ActorState>> {
         let state = StateTree::new_from_root(self.blockstore(), &state_cid)?;
@@ -268,6 +357,7 @@ where
         let name = match &self.chain_config.network {
             crate::networks::NetworkChain::Mainnet => "testnetnet",
             crate::networks::NetworkChain::Calibnet => "calibrationnet",
+            crate::networks::NetworkChain::Butterflynet => "butterflynet",
             crate::networks::NetworkChain::Devnet(name) => name,
         }
         .to_string();
@@ -291,20 +381,7 @@ where
         state_cid: Cid,
         addr: &Address,
     ) -> anyhow::Result<Address, Error> {
-        let state = StateTree::new_from_root(self.blockstore(), &state_cid)
-            .map_err(|e| Error::Other(e.to_string()))?;
-
-        let act = state
-            .get_actor(addr)
-            .map_err(|e| Error::State(e.to_string()))?
-            .ok_or_else(|| Error::State("Miner actor not found".to_string()))?;
-
-        let ms = miner::State::load(self.blockstore(), act.code, act.state)?;
-
-        let info = ms.info(self.blockstore()).map_err(|e| e.to_string())?;
-
-        let addr = resolve_to_key_addr(&state, self.blockstore(), &info.worker().into())?;
-        Ok(addr)
+        miner_work_addr(self.blockstore(), state_cid, addr)
     }
 
     /// Returns specified actor's claimed power and total network power as a
@@ -340,10 +417,59 @@ where
         Ok(None)
     }
 
+    /// Returns the power of a miner and the total network power at the given
+    /// state. Unlike [`StateManager::get_power`], the power of miners below
+    /// the consensus minimum is returned too.
+    pub fn miner_power(&self, state_cid: &Cid, addr: &Address) -> Result<MinerPower, Error> {
+        let actor = self
+            .get_actor(&Address::POWER_ACTOR, *state_cid)?
+            .ok_or_else(|| Error::State("Power actor address could not be resolved".to_string()))?;
+
+        let spas = power::State::load(self.blockstore(), actor.code, actor.state)?;
+
+        let miner_power = spas
+            .miner_power(self.blockstore(), &addr.into())?
+            .ok_or_else(|| Error::State(format!("Miner for address {addr} not found")))?;
+        let has_min_power = spas.miner_nominal_power_meets_consensus_minimum(
+            &self.chain_config.policy,
+            self.blockstore(),
+            &addr.into(),
+        )?;
+
+        Ok(MinerPower {
+            miner_power: miner_power.into(),
+            total_power: spas.total_power().into(),
+            has_min_power,
+        })
+    }
+
+    /// Returns a page of the miners with a power claim at the given state, see
+    /// [`list_miners`].
+    pub fn list_miners(
+        &self,
+        state_cid: &Cid,
+        start: Option<&Address>,
+        limit: u64,
+    ) -> Result<MinersPage, Error> {
+        let actor = self
+            .get_actor(&Address::POWER_ACTOR, *state_cid)?
+            .ok_or_else(|| Error::State("Power actor address could not be resolved".to_string()))?;
+
+        let state = power::State::load(self.blockstore(), actor.code, actor.state)?;
+
+        Ok(list_miners(
+            self.blockstore(),
+            &claims_root(&state),
+            start,
+            limit,
+        )?)
+    }
+
     /// Performs the state transition for the tipset and applies all unique
     /// messages in all blocks. This function returns the state root and
     /// receipt root of the transition.
     #[allow(clippy::too_many_arguments)]
+    #[instrument(skip_all, fields(epoch = epoch))]
     pub fn apply_blocks<R, CB>(
         self: &Arc<Self>,
         parent_epoch: ChainEpoch,
@@ -363,6 +489,27 @@ where
 
         let db = self.blockstore().clone();
 
+        // Whether all the messages of the tipset are traced, and whether any
+        // of them is traced at all
+        let (sampled, traced, watched) = match &self.execution_traces {
+            Some(traces) => {
+                let sampling = traces.sampling();
+                let sampled = sampling.samples_epoch(epoch);
+                // The messages reach the watched actors by key or by ID
+                let watched = sampling.resolve_watched(self.blockstore(), p_state)?;
+                let watching = messages
+                    .iter()
+                    .flat_map(|block| &block.messages)
+                    .any(|msg| watched.watches_message(&msg.from(), &msg.to()));
+                (sampled, sampled || watching, watched)
+            }
+            None => (false, false, WatchedActors::default()),
+        };
+        let trace = if traced {
+            VMTrace::Traced
+        } else {
+            VMTrace::NotTraced
+        };
         let create_vm = |state_root, epoch, timestamp| {
             VM::new(
                 state_root,
@@ -378,9 +525,32 @@ where
                 &self.engine,
                 Arc::clone(self.chain_config()),
                 timestamp,
+                trace,
             )
         };
 
+        // Records the execution traces before handing the results over to the
+        // callback
+        let current_epoch = Cell::new(parent_epoch);
+        let mut traced_callback = |cid: &Cid, msg: &ChainMessage, ret: &ApplyRet| {
+            match &self.execution_traces {
+                Some(traces) if traced => {
+                    let trace = ExecutionTrace::new(*cid, current_epoch.get(), ret);
+                    if sampled
+                        || watched.watches_message(&msg.from(), &msg.to())
+                        || watched.watches_trace(&trace)
+                    {
+                        traces.insert(trace);
+                    }
+                }
+                _ => {}
+            }
+            match &mut callback {
+                Some(callback) => callback(cid, msg, ret),
+                None => Ok(()),
+            }
+        };
+
         let mut parent_state = *p_state;
         let genesis_timestamp = Lazy::new(|| {
             self.chain_store()
@@ -391,10 +561,11 @@ where
 
         for epoch_i in parent_epoch..epoch {
             if epoch_i > parent_epoch {
+                current_epoch.set(epoch_i);
                 let timestamp = *genesis_timestamp + ((EPOCH_DURATION_SECONDS * epoch_i) as u64);
                 let mut vm = create_vm(parent_state, epoch_i, timestamp)?;
                 // run cron for null rounds if any
-                if let Err(e) = vm.run_cron(epoch_i, callback.as_mut()) {
+                if let Err(e) = vm.run_cron(epoch_i, Some(&mut traced_callback)) {
                     error!("Beginning of epoch cron failed to run: {}", e);
                 }
 
@@ -414,10 +585,11 @@ where
         let mut vm = create_vm(parent_state, epoch, tipset.min_timestamp())?;
 
         // Apply tipset messages
-        let receipts = vm.apply_block_messages(messages, epoch, callback)?;
+        current_epoch.set(epoch);
+        let receipts = vm.apply_block_messages(messages, epoch, Some(traced_callback))?;
 
         // Construct receipt root from receipts
-        let receipt_root = Amt::new_from_iter(self.blockstore(), receipts)?;
+        let receipt_root = compute_receipts_root(self.blockstore(), &receipts)?;
 
         // Flush changes to blockstore
         let state_root = vm.flush()?;
@@ -430,6 +602,17 @@ where
     /// state for a given tipset is guaranteed not to be computed twice.
     #[instrument(skip(self))]
     pub async fn tipset_state(self: &Arc<Self>, tipset: &Arc<Tipset>) -> anyhow::Result<CidPair> {
+        self.tipset_state_in_lane(tipset, ExecutionLane::Validation)
+            .await
+    }
+
+    /// Same as [`StateManager::tipset_state`], with the execution counted
+    /// against the slots of the given lane.
+    pub async fn tipset_state_in_lane(
+        self: &Arc<Self>,
+        tipset: &Arc<Tipset>,
+        lane: ExecutionLane,
+    ) -> anyhow::Result<CidPair> {
         let key = tipset.key();
         self.cache
             .get_or_else(key, || async move {
@@ -448,7 +631,7 @@ where
                     let no_func =
                         None::<fn(&Cid, &ChainMessage, &ApplyRet) -> Result<(), anyhow::Error>>;
                     let ts_state = self
-                        .compute_tipset_state(Arc::clone(tipset), no_func)
+                        .compute_tipset_state(Arc::clone(tipset), no_func, lane)
                         .await?;
                     debug!("Completed tipset state calculation {:?}", tipset.cids());
                     ts_state
@@ -459,41 +642,428 @@ where
             .await
     }
 
-    #[instrument(skip(self, rand))]
-    fn call_raw(
+    /// Returns the state resulting from the execution of `tipset` if it has
+    /// been computed already.
+    pub fn tipset_state_if_computed(&self, tipset: &Tipset) -> Option<CidPair> {
+        self.cache.get(tipset.key())
+    }
+
+    /// Returns the state resulting from the execution of the head `tipset`.
+    /// If it is still being computed, this either waits for it or returns the
+    /// parent state of the head right away, flagged as such.
+    pub async fn head_state(
+        self: &Arc<Self>,
+        tipset: &Arc<Tipset>,
+        wait: bool,
+    ) -> anyhow::Result<HeadState> {
+        let computed = match self.tipset_state_if_computed(tipset) {
+            Some((state_root, _)) => Some(state_root),
+            None if wait => Some(
+                self.tipset_state_in_lane(tipset, ExecutionLane::Rpc)
+                    .await?
+                    .0,
+            ),
+            None => None,
+        };
+        Ok(HeadState {
+            epoch: tipset.epoch(),
+            state_root: computed.unwrap_or(*tipset.parent_state()),
+            computed: computed.is_some(),
+        })
+    }
+
+    /// Returns the state resulting from the execution of `tipset` for an RPC
+    /// request. While the state of the head is computed in the background,
+    /// the requests on the head don't wait for it and get its parent state,
+    /// not flagged as computed, as `Forest.StateHeadState` does when not
+    /// asked to wait.
+    pub async fn rpc_tipset_state(
+        self: &Arc<Self>,
+        tipset: &Arc<Tipset>,
+    ) -> anyhow::Result<HeadState> {
+        let wait = !self.precompute_head_state || tipset.key() != self.cs.heaviest_tipset().key();
+        self.head_state(tipset, wait).await
+    }
+
+    /// Computes the state of each new head in the background, so that the
+    /// requests needing it don't all wait for the execution of a heavy
+    /// tipset. The heads replaced while the node lags behind are skipped. The
+    /// executions go through the backfill lane, so that they don't hold up
+    /// the validation of the blocks.
+    pub async fn compute_head_states(self: Arc<Self>) -> anyhow::Result<()> {
+        let mut head_changes = self.cs.publisher().subscribe();
+        loop {
+            let head = match head_changes.recv().await {
+                Ok(HeadChange::Apply(head)) => head,
+                Ok(_) => continue,
+                Err(RecvError::Lagged(_)) => self.cs.heaviest_tipset(),
+                Err(RecvError::Closed) => return Ok(()),
+            };
+            if head.key() != self.cs.heaviest_tipset().key() {
+                continue;
+            }
+            if let Err(e) = self
+                .tipset_state_in_lane(&head, ExecutionLane::Backfill)
+                .await
+            {
+                warn!(
+                    "Failed to compute the state of head {}: {e:#}",
+                    head.epoch()
+                );
+            }
+        }
+    }
+
+    /// Returns the balance of an actor every `step` epochs from `from` to `to`
+    /// included, read from the state at the start of each epoch, as
+    /// `Filecoin.StateGetActor` does. The states pruned from the store are
+    /// computed again from the nearest earlier complete state if `recompute`
+    /// is set, which archival nodes have no need for. The recomputed states
+    /// are kept in memory for the call, and cover at most
+    /// [`MAX_BALANCE_HISTORY_RECOMPUTE_EPOCHS`] epochs.
+    pub async fn balance_history(
+        self: &Arc<Self>,
+        address: &Address,
+        from: ChainEpoch,
+        to: ChainEpoch,
+        step: u64,
+        recompute: bool,
+    ) -> anyhow::Result<Vec<BalanceAt>> {
+        let head = self.cs.heaviest_tipset();
+        anyhow::ensure!(step > 0, "the step must be positive");
+        anyhow::ensure!(
+            0 <= from && from <= to && to <= head.epoch(),
+            "epochs {from} to {to} aren't in the chain, whose head is at epoch {}",
+            head.epoch()
+        );
+        let count = (to - from) as u64 / step + 1;
+        anyhow::ensure!(
+            count <= MAX_BALANCE_HISTORY_EPOCHS,
+            "{count} epochs requested, the maximum is {MAX_BALANCE_HISTORY_EPOCHS}"
+        );
+
+        let mut recomputed = RecomputedStates {
+            db: MemoryOverlay::new(self.blockstore().clone()),
+            complete: HashSet::default(),
+            budget: MAX_BALANCE_HISTORY_RECOMPUTE_EPOCHS,
+        };
+        let mut history = Vec::with_capacity(count as usize);
+        for epoch in (from..=to).step_by(step as usize) {
+            // The tipset after a null round has the state of the null round
+            let tipset = self.cs.tipset_by_height(epoch, head.clone(), false)?;
+            let state_root = *tipset.parent_state();
+            // Reading the actor loads the part of the state it needs, which
+            // fails if it was pruned
+            let actor = match StateTree::new_from_root(&recomputed.db, &state_root)
+                .and_then(|state| state.get_actor(address))
+            {
+                Ok(actor) => actor,
+                Err(e) => {
+                    anyhow::ensure!(
+                        recompute,
+                        "the state {state_root} of epoch {epoch} isn't available: {e}"
+                    );
+                    self.recompute_parent_state(&mut recomputed, &tipset)
+                        .await?;
+                    StateTree::new_from_root(&recomputed.db, &state_root)?.get_actor(address)?
+                }
+            };
+            let balance = actor
+                .map(|actor| TokenAmount::from(&actor.balance))
+                .unwrap_or_else(TokenAmount::zero);
+            history.push(BalanceAt {
+                epoch,
+                state_root,
+                balance,
+            });
+        }
+        Ok(history)
+    }
+
+    /// Computes the parent state of `tipset`, and the earlier states missing
+    /// from the store, from the nearest state whose whole state tree is
+    /// available. The states computed are only written to the memory of
+    /// `recomputed`.
+    async fn recompute_parent_state(
+        self: &Arc<Self>,
+        recomputed: &mut RecomputedStates<DB>,
+        tipset: &Arc<Tipset>,
+    ) -> anyhow::Result<()> {
+        let mut missing = vec![];
+        let mut ts = tipset.clone();
+        loop {
+            anyhow::ensure!(
+                ts.epoch() > 0,
+                "no state is available before epoch {}",
+                tipset.epoch()
+            );
+            ts = self.cs.tipset_from_keys(ts.parents())?;
+            missing.push(ts.clone());
+            let distance = (tipset.epoch() - ts.epoch()) as u64;
+            anyhow::ensure!(
+                distance <= recomputed.budget,
+                "the state of epoch {} is more than {} epochs after the nearest state available",
+                tipset.epoch(),
+                recomputed.budget
+            );
+            if recomputed.is_complete(ts.parent_state()) {
+                recomputed.budget -= distance;
+                break;
+            }
+        }
+        // Each state computed is the parent state of the next tipset
+        let children = missing.iter().rev().skip(1).chain(std::iter::once(tipset));
+        for (ts, child) in missing.iter().rev().zip(children) {
+            let permit = self.execution_pool.acquire(ExecutionLane::Backfill).await;
+            let sm = Arc::clone(self);
+            let (db, replayed) = (recomputed.db.clone(), ts.clone());
+            let state_root = tokio::task::spawn_blocking(move || {
+                let _permit = permit;
+                sm.replay_tipset_blocking(&db, replayed)
+            })
+            .await??;
+            anyhow::ensure!(
+                state_root == *child.parent_state(),
+                "the state computed at epoch {} differs from the one of the chain",
+                ts.epoch()
+            );
+            recomputed.complete.insert(state_root);
+        }
+        Ok(())
+    }
+
+    /// Executes `tipset` on top of its parent state, available in `db`, and
+    /// returns the resulting state, whose blocks are written to `db`.
+    fn replay_tipset_blocking(
+        self: &Arc<Self>,
+        db: &MemoryOverlay<DB>,
+        tipset: Arc<Tipset>,
+    ) -> anyhow::Result<Cid> {
+        if tipset.epoch() == 0 {
+            return Ok(*tipset.parent_state());
+        }
+        let parent_epoch = self.cs.tipset_from_keys(tipset.parents())?.epoch();
+        let messages = self.cs.block_msgs_for_tipset(&tipset)?;
+        let rand = self.chain_rand(tipset.key().clone());
+        let base_fee = tipset.blocks()[0].parent_base_fee().clone();
+        let genesis_timestamp = self.chain_store().genesis()?.timestamp();
+        let create_vm = |state_root, epoch, timestamp| {
+            VM::new(
+                state_root,
+                db.clone(),
+                epoch,
+                rand.clone(),
+                base_fee.clone(),
+                self.genesis_info
+                    .get_circulating_supply(epoch, db, &state_root)?,
+                self.reward_calc.clone(),
+                chain_epoch_root(Arc::clone(self), Arc::clone(&tipset)),
+                chain_epoch_tsk(Arc::clone(self), Arc::clone(&tipset)),
+                &self.engine,
+                Arc::clone(self.chain_config()),
+                timestamp,
+                VMTrace::NotTraced,
+            )
+        };
+
+        let mut state_root = *tipset.parent_state();
+        for epoch_i in parent_epoch..tipset.epoch() {
+            if epoch_i > parent_epoch {
+                let timestamp = genesis_timestamp + (EPOCH_DURATION_SECONDS * epoch_i) as u64;
+                let mut vm = create_vm(state_root, epoch_i, timestamp)?;
+                // As in `apply_blocks`, the null round crons may fail
+                if let Err(e) = vm.run_cron(
+                    epoch_i,
+                    None::<&mut fn(&Cid, &ChainMessage, &ApplyRet) -> anyhow::Result<()>>,
+                ) {
+                    error!("Beginning of epoch cron failed to run: {}", e);
+                }
+                state_root = vm.flush()?;
+            }
+            if let Some(new_state) =
+                run_state_migrations(epoch_i, self.chain_config(), db, &state_root)?
+            {
+                state_root = new_state;
+            }
+        }
+        let mut vm = create_vm(state_root, tipset.epoch(), tipset.min_timestamp())?;
+        vm.apply_block_messages(
+            &messages,
+            tipset.epoch(),
+            None::<fn(&Cid, &ChainMessage, &ApplyRet) -> anyhow::Result<()>>,
+        )?;
+        Ok(vm.flush()?)
+    }
+
+    /// Applies the `messages` on top of the state of `tipset`, at `epoch`,
+    /// followed by the cron of `epoch`. The cron of the null rounds in between
+    /// is run before, as well as the migrations of the network upgrades, up to
+    /// [`MAX_COMPUTE_STATE_EPOCHS`] epochs after `tipset`. Returns the
+    /// resulting state root and the outcome of each message, cron included.
+    /// Nothing is added to the chain nor written to the store, the messages
+    /// don't need to be signed.
+    pub async fn compute_state(
+        self: &Arc<Self>,
+        epoch: ChainEpoch,
+        messages: Vec<Message>,
+        tipset: Arc<Tipset>,
+    ) -> anyhow::Result<ComputeStateOutput> {
+        anyhow::ensure!(
+            epoch > tipset.epoch() && epoch - tipset.epoch() <= MAX_COMPUTE_STATE_EPOCHS,
+            "epoch {epoch} must be after the one of the base tipset, {}, by at most {} epochs",
+            tipset.epoch(),
+            MAX_COMPUTE_STATE_EPOCHS
+        );
+        let (base_state, _) = self
+            .tipset_state_in_lane(&tipset, ExecutionLane::Rpc)
+            .await?;
+        let permit = self.execution_pool.acquire(ExecutionLane::Rpc).await;
+        let sm = Arc::clone(self);
+        tokio::task::spawn_blocking(move || {
+            let _permit = permit;
+            sm.compute_state_blocking(epoch, &messages, base_state, tipset)
+        })
+        .await?
+    }
+
+    fn compute_state_blocking(
+        self: &Arc<Self>,
+        epoch: ChainEpoch,
+        messages: &[Message],
+        base_state: Cid,
+        tipset: Arc<Tipset>,
+    ) -> anyhow::Result<ComputeStateOutput> {
+        // The states computed are discarded with the overlay
+        let db = MemoryOverlay::new(self.blockstore().clone());
+        let rand = self.chain_rand(tipset.key().clone());
+        let base_fee = tipset.blocks()[0].parent_base_fee().clone();
+        let genesis_timestamp = self.chain_store().genesis()?.timestamp();
+        let create_vm = |state_root, epoch| {
+            VM::new(
+                state_root,
+                db.clone(),
+                epoch,
+                rand.clone(),
+                base_fee.clone(),
+                self.genesis_info
+                    .get_circulating_supply(epoch, &db, &state_root)?,
+                self.reward_calc.clone(),
+                chain_epoch_root(Arc::clone(self), Arc::clone(&tipset)),
+                chain_epoch_tsk(Arc::clone(self), Arc::clone(&tipset)),
+                &self.engine,
+                Arc::clone(self.chain_config()),
+                genesis_timestamp + (EPOCH_DURATION_SECONDS * epoch) as u64,
+                VMTrace::Traced,
+            )
+        };
+
+        let current_epoch = Cell::new(tipset.epoch());
+        let mut trace = Vec::new();
+        let mut record = |cid: &Cid, msg: &ChainMessage, ret: &ApplyRet| -> anyhow::Result<()> {
+            trace.push(ComputedMessage {
+                msg_cid: *cid,
+                msg: msg.message().clone(),
+                msg_rct: ret.msg_receipt(),
+                error: ret.failure_info(),
+                execution_trace: ExecutionTrace::new(*cid, current_epoch.get(), ret),
+            });
+            Ok(())
+        };
+
+        let mut state_root = base_state;
+        for epoch_i in tipset.epoch()..epoch {
+            if epoch_i > tipset.epoch() {
+                current_epoch.set(epoch_i);
+                let mut vm = create_vm(state_root, epoch_i)?;
+                vm.run_cron(epoch_i, Some(&mut record))
+                    .with_context(|| format!("cron failed at null round {epoch_i}"))?;
+                state_root = vm.flush()?;
+            }
+            if let Some(new_state) =
+                run_state_migrations(epoch_i, self.chain_config(), &db, &state_root)?
+            {
+                state_root = new_state;
+            }
+        }
+
+        current_epoch.set(epoch);
+        let mut vm = create_vm(state_root, epoch)?;
+        for message in messages {
+            let message = ChainMessage::Unsigned(message.clone());
+            let ret = vm.apply_message(&message)?;
+            record(&message.cid()?, &message, &ret)?;
+        }
+        vm.run_cron(epoch, Some(&mut record))?;
+        let root = vm.flush()?;
+        Ok(ComputeStateOutput { root, trace })
+    }
+
+    /// Executes the message on top of `state_root`, after the prior messages,
+    /// without including it on chain nor writing the resulting state. This is
+    /// the execution path shared by `StateCall` and the gas estimation, so
+    /// that they agree on the outcome of a message.
+    #[allow(clippy::too_many_arguments)]
+    #[instrument(skip(self, prior_messages, tipset))]
+    pub fn dry_run(
         self: &Arc<Self>,
         msg: &mut Message,
-        rand: ChainRand<DB>,
+        kind: DryRunKind,
+        prior_messages: &[ChainMessage],
+        state_root: Cid,
+        epoch: ChainEpoch,
+        base_fee: TokenAmount,
         tipset: &Arc<Tipset>,
-    ) -> StateCallResult {
-        let bstate = tipset.parent_state();
-        let bheight = tipset.epoch();
+    ) -> Result<ApplyRet, Error> {
         let store = self.blockstore().clone();
         let mut vm = VM::new(
-            *bstate,
+            state_root,
             store,
-            bheight,
-            rand,
-            TokenAmount::zero(),
+            epoch,
+            self.chain_rand(tipset.key().to_owned()),
+            base_fee,
             self.genesis_info
-                .get_circulating_supply(bheight, self.blockstore(), bstate)?,
+                .get_circulating_supply(epoch, self.blockstore(), &state_root)?,
             self.reward_calc.clone(),
             chain_epoch_root(Arc::clone(self), Arc::clone(tipset)),
             chain_epoch_tsk(Arc::clone(self), Arc::clone(tipset)),
             &self.engine,
             Arc::clone(self.chain_config()),
             tipset.min_timestamp(),
+            VMTrace::NotTraced,
         )?;
 
+        for prior in prior_messages {
+            vm.apply_message(prior)?;
+        }
+
         if msg.gas_limit == 0 {
             msg.gas_limit = 10000000000;
         }
-
-        let actor = self
-            .get_actor(&msg.from.into(), *bstate)?
+        let from_actor = vm
+            .get_actor(&msg.from())
+            .map_err(|e| Error::Other(format!("Could not get actor from state: {e}")))?
             .ok_or_else(|| Error::Other("Could not get actor".to_string()))?;
-        msg.sequence = actor.sequence;
-        let apply_ret = vm.apply_implicit_message(msg)?;
+        msg.sequence = from_actor.sequence;
+
+        let chain_msg = match kind {
+            DryRunKind::Explicit => {
+                // The inclusion gas depends on the size of the signature
+                let state = StateTree::new_from_root(self.blockstore(), &state_root)?;
+                let from = resolve_to_key_addr(&state, self.blockstore(), &msg.from())?;
+                match from.protocol() {
+                    Protocol::Secp256k1 => ChainMessage::Signed(SignedMessage::new_unchecked(
+                        msg.clone(),
+                        Signature::new_secp256k1(vec![
+                            0;
+                            fvm_shared::crypto::signature::SECP_SIG_LEN
+                        ]),
+                    )),
+                    _ => ChainMessage::Unsigned(msg.clone()),
+                }
+            }
+            DryRunKind::Implicit => ChainMessage::Unsigned(msg.clone()),
+        };
+        let apply_ret = vm.dry_run(&chain_msg, kind)?;
         trace!(
             "gas limit {:},gas premium{:?},value {:?}",
             msg.gas_limit,
@@ -503,12 +1073,7 @@ where
         if let Some(err) = &apply_ret.failure_info() {
             warn!("chain call failed: {:?}", err);
         }
-
-        Ok(InvocResult {
-            msg: msg.clone(),
-            msg_rct: Some(apply_ret.msg_receipt()),
-            error: apply_ret.failure_info(),
-        })
+        Ok(apply_ret)
     }
 
     /// runs the given message and returns its result without any persisted
@@ -519,60 +1084,56 @@ where
         tipset: Option<Arc<Tipset>>,
     ) -> StateCallResult {
         let ts = tipset.unwrap_or_else(|| self.cs.heaviest_tipset());
-        let chain_rand = self.chain_rand(ts.key().to_owned());
-        self.call_raw(message, chain_rand, &ts)
+        let apply_ret = self.dry_run(
+            message,
+            DryRunKind::Implicit,
+            &[],
+            *ts.parent_state(),
+            ts.epoch(),
+            TokenAmount::zero(),
+            &ts,
+        )?;
+        Ok(InvocResult {
+            msg: message.clone(),
+            msg_rct: Some(apply_ret.msg_receipt()),
+            error: apply_ret.failure_info(),
+        })
     }
 
     /// Computes message on the given [Tipset] state, after applying other
     /// messages and returns the values computed in the VM.
     pub async fn call_with_gas(
         self: &Arc<Self>,
-        message: &mut ChainMessage,
+        message: &mut Message,
         prior_messages: &[ChainMessage],
         tipset: Option<Arc<Tipset>>,
     ) -> StateCallResult {
         let ts = tipset.unwrap_or_else(|| self.cs.heaviest_tipset());
-        let (st, _) = self
-            .tipset_state(&ts)
+        let state = self
+            .rpc_tipset_state(&ts)
             .await
             .map_err(|_| Error::Other("Could not load tipset state".to_string()))?;
-        let chain_rand = self.chain_rand(ts.key().to_owned());
-
-        let store = self.blockstore().clone();
         // Since we're simulating a future message, pretend we're applying it in the
-        // "next" tipset
-        let epoch = ts.epoch() + 1;
-        let mut vm = VM::new(
-            st,
-            store,
+        // "next" tipset, or in the tipset itself on its parent state if that
+        // is still being computed
+        let epoch = if state.computed {
+            ts.epoch() + 1
+        } else {
+            ts.epoch()
+        };
+        let apply_ret = self.dry_run(
+            message,
+            DryRunKind::Explicit,
+            prior_messages,
+            state.state_root,
             epoch,
-            chain_rand,
             ts.blocks()[0].parent_base_fee().clone(),
-            self.genesis_info
-                .get_circulating_supply(epoch, self.blockstore(), &st)?,
-            self.reward_calc.clone(),
-            chain_epoch_root(Arc::clone(self), Arc::clone(&ts)),
-            chain_epoch_tsk(Arc::clone(self), Arc::clone(&ts)),
-            &self.engine,
-            Arc::clone(self.chain_config()),
-            ts.min_timestamp(),
+            &ts,
         )?;
-
-        for msg in prior_messages {
-            vm.apply_message(msg)?;
-        }
-        let from_actor = vm
-            .get_actor(&message.from())
-            .map_err(|e| Error::Other(format!("Could not get actor from state: {e}")))?
-            .ok_or_else(|| Error::Other("cant find actor in state tree".to_string()))?;
-        message.set_sequence(from_actor.sequence);
-
-        let ret = vm.apply_message(message)?;
-
         Ok(InvocResult {
-            msg: message.message().clone(),
-            msg_rct: Some(ret.msg_receipt()),
-            error: ret.failure_info(),
+            msg: message.clone(),
+            msg_rct: Some(apply_ret.msg_receipt()),
+            error: apply_ret.failure_info(),
         })
     }
 
@@ -599,7 +1160,7 @@ where
             Ok(())
         };
         let result = self
-            .compute_tipset_state(Arc::clone(ts), Some(callback))
+            .compute_tipset_state(Arc::clone(ts), Some(callback), ExecutionLane::Rpc)
             .await;
 
         if let Err(error_message) = result {
@@ -737,19 +1298,25 @@ where
     }
 
     /// Performs a state transition, and returns the state and receipt root of
-    /// the transition.
+    /// the transition. The execution waits for a slot of its `lane`.
     #[instrument(skip(self, tipset, callback))]
     pub async fn compute_tipset_state<CB: 'static>(
         self: &Arc<Self>,
         tipset: Arc<Tipset>,
         callback: Option<CB>,
+        lane: ExecutionLane,
     ) -> Result<CidPair, Error>
     where
         CB: FnMut(&Cid, &ChainMessage, &ApplyRet) -> Result<(), anyhow::Error> + Send,
     {
+        let permit = self.execution_pool.acquire(lane).await;
+        trace!("Executing epoch {} in the {lane} lane", tipset.epoch());
         let sm = Arc::clone(self);
-        tokio::task::spawn_blocking(move || sm.compute_tipset_state_blocking(tipset, callback))
-            .await?
+        tokio::task::spawn_blocking(move || {
+            let _permit = permit;
+            sm.compute_tipset_state_blocking(tipset, callback)
+        })
+        .await?
     }
 
     /// Performs a state transition, and returns the state and receipt root of
@@ -1147,6 +1714,13 @@ where
             .map(Address::new_id))
     }
 
+    /// Like [`StateManager::lookup_id`], but fails if there is no actor at
+    /// the address.
+    pub fn lookup_required_id(&self, addr: &Address, ts: &Tipset) -> Result<Address, Error> {
+        self.lookup_id(addr, ts)?
+            .ok_or_else(|| Error::ActorNotFound(addr.to_string()))
+    }
+
     /// Retrieves market balance in escrow and locked tables.
     pub fn market_balance(
         &self,
@@ -1161,9 +1735,7 @@ where
 
         let market_state = market::State::load(self.blockstore(), actor.code, actor.state)?;
 
-        let new_addr = self
-            .lookup_id(addr, ts)?
-            .ok_or_else(|| Error::State(format!("Failed to resolve address {addr}")))?;
+        let new_addr = self.lookup_required_id(addr, ts)?;
 
         let out = MarketBalance {
             escrow: {
@@ -1184,11 +1756,13 @@ where
     }
 
     /// Similar to `resolve_to_key_addr` in the `forest_vm` [`crate::state_manager`] but does not
-    /// allow `Actor` type of addresses. Uses `ts` to generate the VM state.
+    /// allow `Actor` type of addresses. Uses `ts` to generate the VM state,
+    /// in the given lane if it has to be computed.
     pub async fn resolve_to_key_addr(
         self: &Arc<Self>,
         addr: &Address,
         ts: &Arc<Tipset>,
+        lane: ExecutionLane,
     ) -> Result<Address, anyhow::Error> {
         match addr.protocol() {
             Protocol::BLS | Protocol::Secp256k1 | Protocol::Delegated => return Ok(*addr),
@@ -1208,8 +1782,21 @@ where
         }
 
         // If that fails, compute the tip-set and try again.
-        let (st, _) = self.tipset_state(ts).await?;
+        let st = match lane {
+            ExecutionLane::Rpc => {
+                let state = self.rpc_tipset_state(ts).await?;
+                if !state.computed {
+                    // That is the parent state, searched already
+                    return Err(Error::ActorNotFound(addr.to_string()).into());
+                }
+                state.state_root
+            }
+            _ => self.tipset_state_in_lane(ts, lane).await?.0,
+        };
         let state = StateTree::new_from_root(self.blockstore(), &st)?;
+        if state.get_actor(addr)?.is_none() {
+            return Err(Error::ActorNotFound(addr.to_string()).into());
+        }
 
         resolve_to_key_addr(&state, self.blockstore(), addr)
     }
@@ -1313,3 +1900,273 @@ where
 {
     Box::new(move |round| Ok(sm.get_epoch_tsk(tipset.clone(), round)?))
 }
+
+/// Returns raw work address of a miner given the state root, reading the
+/// state from any store.
+pub fn miner_work_addr<BS: Blockstore>(
+    store: &BS,
+    state_cid: Cid,
+    addr: &Address,
+) -> Result<Address, Error> {
+    let state =
+        StateTree::new_from_root(store, &state_cid).map_err(|e| Error::Other(e.to_string()))?;
+
+    let act = state
+        .get_actor(addr)
+        .map_err(|e| Error::State(e.to_string()))?
+        .ok_or_else(|| Error::State("Miner actor not found".to_string()))?;
+
+    let ms = miner::State::load(store, act.code, act.state)?;
+
+    let info = ms.info(store).map_err(|e| e.to_string())?;
+
+    let addr = resolve_to_key_addr(&state, store, &info.worker().into())?;
+    Ok(addr)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chain::persist_objects;
+    use crate::networks::Height;
+    use crate::shim::state_tree::StateTreeVersion;
+    use crate::test_utils::ChainGenerator;
+    use chain_rand::draw_randomness;
+    use tempfile::TempDir;
+
+    const PERS: i64 = 2;
+    const ENTROPY: &[u8] = b"entropy";
+
+    /// A chain of `genesis`, `before` at epoch 1, null rounds from epoch 2
+    /// to 4, `after` at epoch 5 and `head` at epoch 6. The network upgrades
+    /// to version 13, which drops the lookback over null rounds, after epoch
+    /// 3.
+    struct NullRoundChain {
+        state_manager: StateManager<crate::db::MemoryDB>,
+        before: Arc<Tipset>,
+        after: Arc<Tipset>,
+        head: Arc<Tipset>,
+        _chain_data_root: TempDir,
+    }
+
+    impl NullRoundChain {
+        fn new() -> Self {
+            let mut generator = ChainGenerator::new();
+            let genesis = generator.genesis().clone();
+            let mut chain_config = ChainConfig::default();
+            chain_config.height_infos[Height::Hyperdrive as usize].epoch = 3;
+            let chain_config = Arc::new(chain_config);
+            let chain_data_root = TempDir::new().unwrap();
+            let cs = ChainStore::new(
+                crate::db::MemoryDB::default(),
+                chain_config.clone(),
+                genesis.min_ticket_block(),
+                chain_data_root.path(),
+            )
+            .unwrap();
+
+            let before = generator.mine(&genesis, &[1000], 0);
+            let after = generator.mine(&before, &[1000, 1001], 3);
+            let head = generator.mine(&after, &[1001], 0);
+            for tipset in [&genesis, &before, &after, &head] {
+                persist_objects(cs.blockstore(), tipset.blocks()).unwrap();
+            }
+            let state_manager = StateManager::new(
+                Arc::new(cs),
+                chain_config,
+                Arc::new(crate::interpreter::RewardActorMessageCalc),
+            )
+            .unwrap();
+            Self {
+                state_manager,
+                before,
+                after,
+                head,
+                _chain_data_root: chain_data_root,
+            }
+        }
+
+        fn chain_randomness(&self, round: ChainEpoch) -> anyhow::Result<[u8; 32]> {
+            self.state_manager
+                .get_chain_randomness(self.head.key(), PERS, round, ENTROPY)
+        }
+    }
+
+    fn ticket_randomness(tipset: &Tipset, round: ChainEpoch) -> [u8; 32] {
+        let ticket = tipset.min_ticket().unwrap();
+        draw_randomness(ticket.vrfproof.as_bytes(), PERS, round, ENTROPY).unwrap()
+    }
+
+    #[test]
+    fn chain_randomness_looks_back_over_null_rounds_before_nv13() {
+        let chain = NullRoundChain::new();
+        assert!(chain.state_manager.get_network_version(3) < NetworkVersion::V13);
+        for round in 2..=3 {
+            assert_eq!(
+                chain.chain_randomness(round).unwrap(),
+                ticket_randomness(&chain.before, round)
+            );
+        }
+        assert_eq!(
+            chain.chain_randomness(1).unwrap(),
+            ticket_randomness(&chain.before, 1)
+        );
+    }
+
+    #[test]
+    fn chain_randomness_looks_forward_over_null_rounds_since_nv13() {
+        let chain = NullRoundChain::new();
+        assert_eq!(
+            chain.state_manager.get_network_version(4),
+            NetworkVersion::V13
+        );
+        for round in 4..=5 {
+            assert_eq!(
+                chain.chain_randomness(round).unwrap(),
+                ticket_randomness(&chain.after, round)
+            );
+        }
+        assert_eq!(
+            chain.chain_randomness(6).unwrap(),
+            ticket_randomness(&chain.head, 6)
+        );
+    }
+
+    #[tokio::test]
+    async fn rpc_requests_read_the_parent_state_of_a_head_being_computed() {
+        let chain = NullRoundChain::new();
+        let state_manager = Arc::new(chain.state_manager.with_precomputed_head_states());
+        state_manager
+            .chain_store()
+            .set_heaviest_tipset(chain.head.clone())
+            .unwrap();
+        // The generated chain has no state to execute, so this would fail if
+        // the request waited for the state of the head
+        let state = state_manager.rpc_tipset_state(&chain.head).await.unwrap();
+        assert!(!state.computed);
+        assert_eq!(state.state_root, *chain.head.parent_state());
+        assert_eq!(state.epoch, chain.head.epoch());
+    }
+
+    #[tokio::test]
+    async fn balance_history_over_null_rounds() {
+        let db = crate::db::MemoryDB::default();
+        let state_root = |balance: u64| {
+            let mut state = StateTree::new(db.clone(), StateTreeVersion::V5).unwrap();
+            let actor = ActorState::new(
+                Cid::default(),
+                Cid::default(),
+                TokenAmount::from_atto(balance),
+                0,
+                None,
+            );
+            state.set_actor(&Address::new_id(1000), actor).unwrap();
+            state.flush().unwrap()
+        };
+        let mut generator = ChainGenerator::new();
+        let genesis = generator.genesis().clone();
+        generator.set_state_root(state_root(10));
+        let before = generator.mine(&genesis, &[1000], 0);
+        generator.set_state_root(state_root(20));
+        let after = generator.mine(&before, &[1000], 3);
+        let head = generator.mine(&after, &[1000], 0);
+        // Far enough from the last state available not to be computed again
+        generator.set_state_root(Cid::default());
+        let pruned = generator.mine(&head, &[1000], MAX_BALANCE_HISTORY_RECOMPUTE_EPOCHS);
+        let pruned_head = generator.mine(&pruned, &[1000], 0);
+        let chain_config = Arc::new(ChainConfig::default());
+        let chain_data_root = TempDir::new().unwrap();
+        let cs = ChainStore::new(
+            db,
+            chain_config.clone(),
+            genesis.min_ticket_block(),
+            chain_data_root.path(),
+        )
+        .unwrap();
+        for tipset in [&genesis, &before, &after, &head, &pruned, &pruned_head] {
+            persist_objects(cs.blockstore(), tipset.blocks()).unwrap();
+        }
+        cs.set_heaviest_tipset(pruned_head.clone()).unwrap();
+        let state_manager = Arc::new(
+            StateManager::new(
+                Arc::new(cs),
+                chain_config,
+                Arc::new(crate::interpreter::RewardActorMessageCalc),
+            )
+            .unwrap(),
+        );
+
+        // The null rounds have the state of the tipset after them
+        let history = state_manager
+            .balance_history(&Address::new_id(1000), 1, 6, 1, false)
+            .await
+            .unwrap();
+        assert_eq!(
+            history
+                .iter()
+                .map(|at| (at.epoch, at.balance.clone()))
+                .collect::<Vec<_>>(),
+            [(1, 10), (2, 20), (3, 20), (4, 20), (5, 20), (6, 20)]
+                .map(|(epoch, balance)| (epoch, TokenAmount::from_atto(balance)))
+        );
+        assert_eq!(history[1].state_root, *after.parent_state());
+
+        // Missing actors have no balance
+        let history = state_manager
+            .balance_history(&Address::new_id(1001), 1, 6, 5, false)
+            .await
+            .unwrap();
+        assert_eq!(
+            history.iter().map(|at| at.epoch).collect::<Vec<_>>(),
+            [1, 6]
+        );
+        assert!(history.iter().all(|at| at.balance.is_zero()));
+
+        // Pruned states are only computed again on demand, from a state close
+        // enough
+        let epoch = pruned_head.epoch();
+        let address = Address::new_id(1000);
+        let err = state_manager
+            .balance_history(&address, epoch, epoch, 1, false)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("isn't available"), "{err}");
+        let err = state_manager
+            .balance_history(&address, epoch, epoch, 1, true)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("nearest state available"), "{err}");
+    }
+
+    #[test]
+    fn chain_randomness_rejects_future_rounds() {
+        let chain = NullRoundChain::new();
+        assert!(chain.chain_randomness(7).is_err());
+        // Negative rounds draw from the genesis ticket
+        let genesis = chain.state_manager.chain_store().genesis().unwrap();
+        assert_eq!(
+            chain.chain_randomness(-1).unwrap(),
+            ticket_randomness(&Tipset::from(genesis), -1)
+        );
+    }
+
+    #[test]
+    fn beacon_randomness_tipset_over_null_rounds() {
+        let chain = NullRoundChain::new();
+        let chain_rand = chain.state_manager.chain_rand(chain.head.key().clone());
+        for round in 2..5 {
+            assert_eq!(
+                chain_rand
+                    .get_beacon_randomness_tipset(chain.head.key(), round, true)
+                    .unwrap(),
+                chain.before
+            );
+            assert_eq!(
+                chain_rand
+                    .get_beacon_randomness_tipset(chain.head.key(), round, false)
+                    .unwrap(),
+                chain.after
+            );
+        }
+    }
+}
@@ -0,0 +1,137 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Readers of the power actor state, for the miner and network power queries
+//! of the RPC API.
+
+use crate::shim::address::Address;
+use cid::Cid;
+use fil_actor_interface::power;
+use fil_actors_shared::v11::{builtin::HAMT_BIT_WIDTH, make_map_with_root_and_bitwidth};
+use fvm_ipld_blockstore::Blockstore;
+use fvm_ipld_hamt::BytesKey;
+use libipld_core::ipld::Ipld;
+use num::BigInt;
+use serde::{Deserialize, Serialize};
+
+/// Largest number of miners returned in a page of [`list_miners`]
+pub const MAX_MINERS_PAGE_SIZE: u64 = 10_000;
+
+/// Raw byte and quality adjusted power of a miner, or of the network
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct PowerClaim {
+    #[serde(with = "crate::json::bigint::json")]
+    pub raw_byte_power: BigInt,
+    #[serde(with = "crate::json::bigint::json")]
+    pub quality_adj_power: BigInt,
+}
+
+impl From<power::Claim> for PowerClaim {
+    fn from(claim: power::Claim) -> Self {
+        PowerClaim {
+            raw_byte_power: claim.raw_byte_power,
+            quality_adj_power: claim.quality_adj_power,
+        }
+    }
+}
+
+/// Power of a miner and of the network at a given state, see
+/// [`crate::state_manager::StateManager::miner_power`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct MinerPower {
+    pub miner_power: PowerClaim,
+    pub total_power: PowerClaim,
+    /// Whether the miner meets the consensus minimum power, and can thus be
+    /// elected to mine blocks
+    pub has_min_power: bool,
+}
+
+/// A page of the miners with a power claim, in the order of the claims table.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct MinersPage {
+    #[serde(with = "crate::json::address::json::vec")]
+    pub miners: Vec<Address>,
+    /// First miner of the next page, if there are more miners
+    #[serde(with = "crate::json::address::json::opt")]
+    pub next: Option<Address>,
+}
+
+/// Root of the claims table of a power actor state
+pub fn claims_root(state: &power::State) -> Cid {
+    match state {
+        power::State::V8(st) => st.claims,
+        power::State::V9(st) => st.claims,
+        power::State::V10(st) => st.claims,
+        power::State::V11(st) => st.claims,
+    }
+}
+
+/// Lists `limit` miners of the claims table rooted at `claims`, starting from
+/// the miner `start`, or from the first one. The walk seeks to `start`
+/// directly, so each page costs the same wherever it starts.
+pub fn list_miners<DB: Blockstore>(
+    db: &DB,
+    claims: &Cid,
+    start: Option<&Address>,
+    limit: u64,
+) -> anyhow::Result<MinersPage> {
+    anyhow::ensure!(limit > 0, "the page size must be positive");
+    // The claims are decoded as IPLD as only their keys are used, so that
+    // this works for the claims of any actor version.
+    let claims = make_map_with_root_and_bitwidth::<_, Ipld>(claims, db, HAMT_BIT_WIDTH)?;
+    let limit = limit.min(MAX_MINERS_PAGE_SIZE);
+    let start = start.map(|addr| BytesKey(addr.to_bytes()));
+    let mut miners = Vec::new();
+    let (_, next) = claims.for_each_ranged(start.as_ref(), Some(limit as usize), |key, _| {
+        miners.push(Address::from_bytes(&key.0)?);
+        Ok(())
+    })?;
+    let next = next.map(|key| Address::from_bytes(&key.0)).transpose()?;
+    Ok(MinersPage { miners, next })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::MemoryDB;
+    use fil_actors_shared::v11::make_empty_map;
+
+    fn claims(db: &MemoryDB, count: u64) -> Cid {
+        let mut claims = make_empty_map::<_, u64>(db, HAMT_BIT_WIDTH);
+        for id in 0..count {
+            claims
+                .set(Address::new_id(1000 + id).to_bytes().into(), id)
+                .unwrap();
+        }
+        claims.flush().unwrap()
+    }
+
+    #[test]
+    fn list_miners_pages() {
+        let db = MemoryDB::default();
+        let root = claims(&db, 25);
+
+        let mut miners = Vec::new();
+        let mut page = list_miners(&db, &root, None, 10).unwrap();
+        let mut pages = 1;
+        while let Some(start) = page.next {
+            assert_eq!(page.miners.len(), 10);
+            miners.extend(page.miners);
+            page = list_miners(&db, &root, Some(&start), 10).unwrap();
+            assert_eq!(page.miners.first(), Some(&start));
+            pages += 1;
+        }
+        miners.extend(page.miners);
+        assert_eq!(pages, 3);
+        miners.sort_by_key(|addr| addr.id().unwrap());
+        let expected: Vec<_> = (0..25).map(|id| Address::new_id(1000 + id)).collect();
+        assert_eq!(miners, expected);
+
+        let page = list_miners(&db, &root, None, 25).unwrap();
+        assert_eq!((page.miners.len(), page.next), (25, None));
+        list_miners(&db, &root, None, 0).unwrap_err();
+    }
+}
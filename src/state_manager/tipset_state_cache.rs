@@ -0,0 +1,237 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use std::{mem::size_of, sync::Arc};
+
+use crate::blocks::TipsetKeys;
+use crate::metrics;
+use cid::Cid;
+use lru::LruCache;
+use parking_lot::Mutex as SyncMutex;
+use tokio::sync::Mutex as TokioMutex;
+
+use super::metrics::TIPSET_STATE_CACHE_SIZE_BYTES;
+
+/// State and receipts roots resulting from the execution of a tipset
+pub(super) type CidPair = (Cid, Cid);
+
+/// Default limit of the memory used by the tipset state cache, enough for
+/// tens of thousands of tipsets
+pub const DEFAULT_TIPSET_STATE_CACHE_SIZE: usize = 16 * 1024 * 1024;
+
+/// Approximate memory used by a cache entry, the key being the bulk of it
+fn entry_size(key: &TipsetKeys) -> usize {
+    size_of::<TipsetKeys>() + key.cids.len() * size_of::<Cid>() + size_of::<CidPair>()
+}
+
+struct TipsetStateCacheInner {
+    values: LruCache<TipsetKeys, CidPair>,
+    size_bytes: usize,
+    pending: Vec<(TipsetKeys, Arc<TokioMutex<()>>)>,
+}
+
+/// LRU cache of the states computed per tipset, bounded by the memory its
+/// entries use. Concurrent requests of the same missing state wait for a
+/// single computation of it.
+pub(super) struct TipsetStateCache {
+    max_size_bytes: usize,
+    cache: Arc<SyncMutex<TipsetStateCacheInner>>,
+}
+
+enum Status {
+    Done(CidPair),
+    Empty(Arc<TokioMutex<()>>),
+}
+
+impl TipsetStateCache {
+    pub fn new(max_size_bytes: usize) -> Self {
+        Self {
+            max_size_bytes,
+            cache: Arc::new(SyncMutex::new(TipsetStateCacheInner {
+                values: LruCache::unbounded(),
+                size_bytes: 0,
+                pending: Vec::with_capacity(8),
+            })),
+        }
+    }
+
+    fn with_inner<F, T>(&self, func: F) -> T
+    where
+        F: FnOnce(&mut TipsetStateCacheInner) -> T,
+    {
+        let mut lock = self.cache.lock();
+        func(&mut lock)
+    }
+
+    pub async fn get_or_else<F, Fut>(&self, key: &TipsetKeys, compute: F) -> anyhow::Result<CidPair>
+    where
+        F: Fn() -> Fut,
+        Fut: core::future::Future<Output = anyhow::Result<CidPair>>,
+    {
+        let status = self.with_inner(|inner| match inner.values.get(key) {
+            Some(v) => Status::Done(*v),
+            None => {
+                let option = inner
+                    .pending
+                    .iter()
+                    .find(|(k, _)| k == key)
+                    .map(|(_, mutex)| mutex);
+                match option {
+                    Some(mutex) => Status::Empty(mutex.clone()),
+                    None => {
+                        let mutex = Arc::new(TokioMutex::new(()));
+                        inner.pending.push((key.clone(), mutex.clone()));
+                        Status::Empty(mutex)
+                    }
+                }
+            }
+        });
+        match status {
+            Status::Done(x) => {
+                metrics::LRU_CACHE_HIT
+                    .with_label_values(&[metrics::values::STATE_MANAGER_TIPSET])
+                    .inc();
+                Ok(x)
+            }
+            Status::Empty(mtx) => {
+                let _guard = mtx.lock().await;
+                match self.get(key) {
+                    Some(v) => {
+                        // While locking someone else computed the pending task
+                        metrics::LRU_CACHE_HIT
+                            .with_label_values(&[metrics::values::STATE_MANAGER_TIPSET])
+                            .inc();
+
+                        Ok(v)
+                    }
+                    None => {
+                        // Entry does not have state computed yet, compute value and fill the cache
+                        metrics::LRU_CACHE_MISS
+                            .with_label_values(&[metrics::values::STATE_MANAGER_TIPSET])
+                            .inc();
+
+                        let cid_pair = match compute().await {
+                            Ok(cid_pair) => cid_pair,
+                            Err(e) => {
+                                self.with_inner(|inner| inner.pending.retain(|(k, _)| k != key));
+                                return Err(e);
+                            }
+                        };
+
+                        // Write back to cache, release lock and return value
+                        self.insert(key.clone(), cid_pair);
+                        Ok(cid_pair)
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn get(&self, key: &TipsetKeys) -> Option<CidPair> {
+        self.with_inner(|inner| inner.values.get(key).copied())
+    }
+
+    /// Caches the state of a tipset, evicting the least recently used ones
+    /// until the cache fits into its size limit.
+    fn insert(&self, key: TipsetKeys, value: CidPair) {
+        self.with_inner(|inner| {
+            inner.pending.retain(|(k, _)| k != &key);
+            let size = entry_size(&key);
+            if size > self.max_size_bytes {
+                return;
+            }
+            if inner.values.put(key, value).is_none() {
+                inner.size_bytes += size;
+            }
+            while inner.size_bytes > self.max_size_bytes {
+                match inner.values.pop_lru() {
+                    Some((evicted, _)) => inner.size_bytes -= entry_size(&evicted),
+                    None => break,
+                }
+            }
+            TIPSET_STATE_CACHE_SIZE_BYTES.set(inner.size_bytes as u64);
+        });
+    }
+
+    #[cfg(test)]
+    fn size_in_bytes(&self) -> usize {
+        self.with_inner(|inner| inner.size_bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cid::multihash::{Code, MultihashDigest};
+    use fvm_ipld_encoding::DAG_CBOR;
+
+    use super::*;
+
+    fn cid(n: u8) -> Cid {
+        Cid::new_v1(DAG_CBOR, Code::Blake2b256.digest(&[n]))
+    }
+
+    fn key(n: u8) -> TipsetKeys {
+        TipsetKeys::new(vec![cid(n)])
+    }
+
+    #[tokio::test]
+    async fn computes_once() {
+        let cache = TipsetStateCache::new(DEFAULT_TIPSET_STATE_CACHE_SIZE);
+        let value = (cid(1), cid(2));
+        assert_eq!(
+            cache
+                .get_or_else(&key(0), || async { Ok(value) })
+                .await
+                .unwrap(),
+            value
+        );
+        let cached = cache
+            .get_or_else(&key(0), || async { anyhow::bail!("computed again") })
+            .await
+            .unwrap();
+        assert_eq!(cached, value);
+        assert_eq!(cache.size_in_bytes(), entry_size(&key(0)));
+    }
+
+    #[tokio::test]
+    async fn failures_are_not_cached() {
+        let cache = TipsetStateCache::new(DEFAULT_TIPSET_STATE_CACHE_SIZE);
+        assert!(cache
+            .get_or_else(&key(0), || async { anyhow::bail!("execution failed") })
+            .await
+            .is_err());
+        let value = (cid(1), cid(2));
+        assert_eq!(
+            cache
+                .get_or_else(&key(0), || async { Ok(value) })
+                .await
+                .unwrap(),
+            value
+        );
+    }
+
+    #[test]
+    fn evicts_least_recently_used_within_size_limit() {
+        let cache = TipsetStateCache::new(entry_size(&key(0)) * 2);
+        let value = (cid(1), cid(2));
+        cache.insert(key(0), value);
+        cache.insert(key(1), value);
+        // Touch the first key so that the second one becomes the least
+        // recently used
+        assert!(cache.get(&key(0)).is_some());
+        cache.insert(key(2), value);
+
+        assert!(cache.get(&key(0)).is_some());
+        assert!(cache.get(&key(1)).is_none());
+        assert!(cache.get(&key(2)).is_some());
+        assert_eq!(cache.size_in_bytes(), entry_size(&key(0)) * 2);
+    }
+
+    #[test]
+    fn zero_size_disables_cache() {
+        let cache = TipsetStateCache::new(0);
+        cache.insert(key(0), (cid(1), cid(2)));
+        assert!(cache.get(&key(0)).is_none());
+        assert_eq!(cache.size_in_bytes(), 0);
+    }
+}
@@ -0,0 +1,194 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Proving windows of the miners, and the Window `PoSt` proofs accepted
+//! optimistically that can still be disputed. Any party can dispute such a
+//! proof with a `DisputeWindowedPoSt` message until the dispute window of its
+//! deadline closes, and gets a reward if the proof turns out to be invalid.
+
+use crate::blocks::Tipset;
+use crate::shim::{
+    address::Address,
+    clock::ChainEpoch,
+    message::{Message, Message_v3},
+};
+use anyhow::Context;
+use fil_actor_interface::miner;
+use fil_actor_miner_state::v11 as miner_state;
+use fil_actors_shared::v10::runtime::Policy;
+use fvm_ipld_blockstore::Blockstore;
+use fvm_ipld_encoding3::RawBytes;
+use serde::{Deserialize, Serialize};
+
+use super::{for_each_optimistic_post, proving_period_start, StateManager};
+
+/// Returns the index of the deadline open at `epoch`, and the next proving
+/// window of each deadline, the current one for the deadline open.
+pub fn proving_windows(
+    policy: &Policy,
+    proving_period_start: ChainEpoch,
+    epoch: ChainEpoch,
+) -> (u64, Vec<(ChainEpoch, ChainEpoch)>) {
+    let period = policy.wpost_proving_period;
+    let window = policy.wpost_challenge_window;
+    let offset = proving_period_start.rem_euclid(period);
+    let period_start = epoch - (epoch - offset).rem_euclid(period);
+    let current = ((epoch - period_start) / window) as u64;
+    let windows = (0..policy.wpost_period_deadlines)
+        .map(|index| {
+            let mut open = period_start + index as ChainEpoch * window;
+            if open + window <= epoch {
+                open += period;
+            }
+            (open, open + window)
+        })
+        .collect();
+    (current, windows)
+}
+
+/// Returns the end of the dispute window of the proofs of the last proving
+/// window of a deadline, if it is still open at `epoch`. The proofs can't be
+/// disputed while the deadline is open.
+fn dispute_window_end(
+    policy: &Policy,
+    proving_period_start: ChainEpoch,
+    deadline: u64,
+    epoch: ChainEpoch,
+) -> Option<ChainEpoch> {
+    if proving_period_start > epoch {
+        return None;
+    }
+    let (_, windows) = proving_windows(policy, proving_period_start, epoch);
+    let (open, close) = *windows.get(deadline as usize)?;
+    if open <= epoch {
+        return None;
+    }
+    let end = close - policy.wpost_proving_period + policy.wpost_dispute_window;
+    (epoch < end).then_some(end)
+}
+
+/// A Window `PoSt` proof that can be disputed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct DisputablePoSt {
+    pub deadline: u64,
+    /// Index of the proof among the ones of the deadline
+    pub post_index: u64,
+    /// Partitions of the deadline proven by the proof
+    pub partitions: Vec<u64>,
+    /// Last epoch the proof can be disputed at
+    pub dispute_window_end: ChainEpoch,
+}
+
+impl<DB> StateManager<DB>
+where
+    DB: Blockstore + Clone + Send + Sync + 'static,
+{
+    /// Lists the proofs of a miner that can be disputed at the parent state of
+    /// `tipset`.
+    pub fn disputable_posts(
+        &self,
+        miner: &Address,
+        tipset: &Tipset,
+    ) -> anyhow::Result<Vec<DisputablePoSt>> {
+        let store = self.blockstore();
+        let policy = &self.chain_config.policy;
+        let actor = self
+            .get_actor(miner, *tipset.parent_state())?
+            .with_context(|| format!("miner {miner} not found"))?;
+        let state = miner::State::load(store, actor.code, actor.state)?;
+        let proving_period_start = proving_period_start(&state);
+
+        let mut posts = vec![];
+        state.for_each_deadline(policy, store, |index, deadline| {
+            let Some(end) = dispute_window_end(policy, proving_period_start, index, tipset.epoch())
+            else {
+                return Ok(());
+            };
+            for_each_optimistic_post(store, &deadline, |post_index, partitions| {
+                posts.push(DisputablePoSt {
+                    deadline: index,
+                    post_index,
+                    partitions,
+                    dispute_window_end: end - 1,
+                });
+                Ok(())
+            })
+        })?;
+        Ok(posts)
+    }
+
+    /// Builds the message disputing a proof of a miner, to be sent by `from`
+    /// once its gas is estimated. Fails if the proof can't be disputed at the
+    /// parent state of `tipset`.
+    pub fn dispute_post_message(
+        &self,
+        miner: &Address,
+        from: &Address,
+        deadline: u64,
+        post_index: u64,
+        tipset: &Tipset,
+    ) -> anyhow::Result<Message> {
+        let disputable = self
+            .disputable_posts(miner, tipset)?
+            .into_iter()
+            .any(|post| post.deadline == deadline && post.post_index == post_index);
+        anyhow::ensure!(
+            disputable,
+            "proof {post_index} of deadline {deadline} of miner {miner} can't be disputed"
+        );
+        let params = miner_state::DisputeWindowedPoStParams {
+            deadline,
+            post_index,
+        };
+        Ok(Message_v3 {
+            from: (*from).into(),
+            to: (*miner).into(),
+            method_num: miner_state::Method::DisputeWindowedPoSt as u64,
+            params: RawBytes::serialize(params)?,
+            version: Default::default(),
+            sequence: Default::default(),
+            value: Default::default(),
+            gas_limit: Default::default(),
+            gas_fee_cap: Default::default(),
+            gas_premium: Default::default(),
+        }
+        .into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn windows_of_the_deadlines() {
+        let policy = Policy::mainnet();
+        // 2880 epochs long periods of 48 windows of 60 epochs, starting at
+        // 100 modulo 2880
+        let (current, windows) = proving_windows(&policy, 2980, 3000);
+        assert_eq!(current, 0);
+        assert_eq!(windows[0], (2980, 3040));
+        assert_eq!(windows[1], (3040, 3100));
+        // Already proven in this period
+        let (current, windows) = proving_windows(&policy, 2980, 5000);
+        assert_eq!(current, 33);
+        assert_eq!(windows[33], (4960, 5020));
+        assert_eq!(windows[0], (5860, 5920));
+        assert_eq!(windows[47], (5800, 5860));
+    }
+
+    #[test]
+    fn dispute_windows() {
+        let policy = Policy::mainnet();
+        // Deadline 1 was proven from 3040 to 3100, and can be disputed for
+        // 1800 epochs after
+        assert_eq!(dispute_window_end(&policy, 2980, 1, 3100), Some(4900));
+        assert_eq!(dispute_window_end(&policy, 2980, 1, 4899), Some(4900));
+        assert_eq!(dispute_window_end(&policy, 2980, 1, 4900), None);
+        // Not while it is open
+        assert_eq!(dispute_window_end(&policy, 2980, 1, 3050), None);
+        // Nor before the first proving period
+        assert_eq!(dispute_window_end(&policy, 2980, 1, 2000), None);
+    }
+}
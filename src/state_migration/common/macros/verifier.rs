@@ -14,6 +14,7 @@ macro_rules! impl_verifier {
             use fvm_ipld_encoding::CborStore;
             use $crate::shim::{address::Address, machine::Manifest, state_tree::StateTree};
             use $crate::state_migration::common::{verifier::ActorMigrationVerifier, Migrator};
+            use $crate::statediff::collections::{diff_maps, Change};
 
             use super::*;
 
@@ -37,7 +38,11 @@ macro_rules! impl_verifier {
                     let manifest =
                         Manifest::load_with_actors(&store, &system_actor_state.builtin_actors, 1)?;
                     let manifest_actors_count = manifest.actors_count();
-                    if manifest_actors_count == migrations.len() {
+                    let changes = diff_maps(
+                        manifest.builtin_actors().map(|(_, code)| (*code, ())),
+                        migrations.keys().map(|code| (*code, ())),
+                    );
+                    if changes.is_empty() {
                         log::debug!("Migration spec is correct.");
                     } else {
                         log::warn!(
@@ -46,6 +51,23 @@ macro_rules! impl_verifier {
                             manifest_actors_count
                         );
                     }
+                    for change in changes {
+                        match change {
+                            Change::Removed(code, ()) => {
+                                let name = manifest
+                                    .builtin_actors()
+                                    .find(|(_, actor_code)| **actor_code == code)
+                                    .map(|(name, _)| name.as_str())
+                                    .unwrap_or_default();
+                                log::warn!("No migration for the {name} actor ({code})");
+                            }
+                            Change::Added(code, ()) => {
+                                log::warn!("Migration for an actor not in the manifest ({code})");
+                            }
+                            // Entries without values can't be modified
+                            Change::Modified { .. } => {}
+                        }
+                    }
 
                     Ok(())
                 }
@@ -6,9 +6,11 @@ use std::sync::{
     Arc,
 };
 
+use crate::events::{self, ChainEvent};
 use crate::networks::{ChainConfig, Height};
 use crate::shim::clock::ChainEpoch;
 use crate::utils::misc::reveal_five_trees;
+use crate::utils::operations::{self, OperationKind};
 use cid::Cid;
 use fvm_ipld_blockstore::Blockstore;
 
@@ -40,7 +42,8 @@ where
         BUNDLE_CHECKED.store(true, atomic::Ordering::Relaxed);
         for info in &chain_config.height_infos {
             for (height, _) in &mappings {
-                if height == &info.height {
+                // The upgrades at genesis don't migrate the state
+                if height == &info.height && info.epoch >= 0 {
                     anyhow::ensure!(
                         info.bundle.is_some(),
                         "Actor bundle info for height {height} needs to be defined in `networks/src/lib.rs` to run state migration"
@@ -54,12 +57,25 @@ where
     for (height, migrate) in mappings {
         if epoch == chain_config.epoch(height) {
             log::info!("Running {height} migration at epoch {epoch}");
+            let _span = tracing::info_span!("state_migration", %height, epoch).entered();
             let start_time = std::time::Instant::now();
-            let new_state = migrate(chain_config, db, parent_state, epoch)?;
+            // Migrations run within the validation of a tipset, so they are
+            // only reported, not cancellable
+            let operation = operations::start(
+                OperationKind::StateMigration,
+                format!("{height} state migration at epoch {epoch}"),
+            );
+            let result = migrate(chain_config, db, parent_state, epoch);
+            operation.end(&result);
+            let new_state = result?;
             let elapsed = start_time.elapsed().as_secs_f32();
             if new_state != *parent_state {
                 reveal_five_trees();
                 log::info!("State migration at height {height} was successful, took: {elapsed}s");
+                events::publish(ChainEvent::MigrationCompleted {
+                    epoch,
+                    upgrade: height.to_string(),
+                });
             } else {
                 anyhow:: bail!("State post migration at height {height} must not match. Previous state: {parent_state}, new state: {new_state}. Took {elapsed}s");
             }
@@ -0,0 +1,185 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Differences between the HAMTs, AMTs and RLE+ bitfields of two states, to
+//! find what changed in an actor state between epochs, or over a migration.
+
+use std::collections::BTreeMap;
+
+use cid::Cid;
+use fvm_ipld_amt::Amt;
+use fvm_ipld_bitfield::BitField;
+use fvm_ipld_blockstore::Blockstore;
+use fvm_ipld_hamt::{BytesKey, Hamt};
+use serde::de::DeserializeOwned;
+
+/// Bit width of the HAMTs of the builtin actors
+pub const HAMT_BIT_WIDTH: u32 = 5;
+
+/// Change of an entry between two collections
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change<K, V> {
+    Added(K, V),
+    Removed(K, V),
+    Modified { key: K, pre: V, post: V },
+}
+
+/// Returns the changes from `pre` to `post`, ordered by key
+pub fn diff_maps<K, V>(
+    pre: impl IntoIterator<Item = (K, V)>,
+    post: impl IntoIterator<Item = (K, V)>,
+) -> Vec<Change<K, V>>
+where
+    K: Ord,
+    V: PartialEq,
+{
+    let mut pre: BTreeMap<K, V> = pre.into_iter().collect();
+    let mut changes = Vec::new();
+    for (key, post) in post {
+        match pre.remove(&key) {
+            None => changes.push(Change::Added(key, post)),
+            Some(pre) if pre != post => changes.push(Change::Modified { key, pre, post }),
+            Some(_) => {}
+        }
+    }
+    changes.extend(
+        pre.into_iter()
+            .map(|(key, value)| Change::Removed(key, value)),
+    );
+    changes.sort_by(|a, b| a.key().cmp(b.key()));
+    changes
+}
+
+impl<K, V> Change<K, V> {
+    pub fn key(&self) -> &K {
+        match self {
+            Change::Added(key, _) | Change::Removed(key, _) | Change::Modified { key, .. } => key,
+        }
+    }
+}
+
+/// Returns the changes between two HAMTs, keyed by bytes
+pub fn diff_hamts<V, BS>(bs: &BS, pre: &Cid, post: &Cid) -> anyhow::Result<Vec<Change<Vec<u8>, V>>>
+where
+    V: DeserializeOwned + PartialEq + Clone,
+    BS: Blockstore,
+{
+    if pre == post {
+        return Ok(Vec::new());
+    }
+    let entries = |root: &Cid| -> anyhow::Result<Vec<(Vec<u8>, V)>> {
+        let hamt = Hamt::<_, V, BytesKey>::load_with_bit_width(root, bs, HAMT_BIT_WIDTH)?;
+        let mut entries = Vec::new();
+        hamt.for_each(|key, value| {
+            entries.push((key.0.clone(), value.clone()));
+            Ok(())
+        })?;
+        Ok(entries)
+    };
+    Ok(diff_maps(entries(pre)?, entries(post)?))
+}
+
+/// Returns the changes between two AMTs, by index
+pub fn diff_amts<V, BS>(bs: &BS, pre: &Cid, post: &Cid) -> anyhow::Result<Vec<Change<u64, V>>>
+where
+    V: DeserializeOwned + PartialEq + Clone,
+    BS: Blockstore,
+{
+    if pre == post {
+        return Ok(Vec::new());
+    }
+    let entries = |root: &Cid| -> anyhow::Result<Vec<(u64, V)>> {
+        let amt = Amt::<V, _>::load(root, bs)?;
+        let mut entries = Vec::new();
+        amt.for_each(|index, value| {
+            entries.push((index, value.clone()));
+            Ok(())
+        })?;
+        Ok(entries)
+    };
+    Ok(diff_maps(entries(pre)?, entries(post)?))
+}
+
+/// Bits set and unset between two bitfields
+#[derive(Debug, Default)]
+pub struct BitFieldDiff {
+    pub added: BitField,
+    pub removed: BitField,
+}
+
+pub fn diff_bitfields(pre: &BitField, post: &BitField) -> BitFieldDiff {
+    BitFieldDiff {
+        added: post - pre,
+        removed: pre - post,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::db::MemoryDB;
+
+    use super::*;
+
+    #[test]
+    fn diff_maps_by_key() {
+        let changes = diff_maps(
+            [(1, "a"), (2, "b"), (3, "c")],
+            [(4, "d"), (2, "b"), (1, "e")],
+        );
+        assert_eq!(
+            changes,
+            vec![
+                Change::Modified {
+                    key: 1,
+                    pre: "a",
+                    post: "e"
+                },
+                Change::Removed(3, "c"),
+                Change::Added(4, "d"),
+            ]
+        );
+    }
+
+    #[test]
+    fn diff_collections() {
+        let db = MemoryDB::default();
+        let pre = Amt::new_from_iter(&db, [1u64, 2, 3]).unwrap();
+        let post = Amt::new_from_iter(&db, [1u64, 5]).unwrap();
+        assert_eq!(
+            diff_amts::<u64, _>(&db, &pre, &post).unwrap(),
+            vec![
+                Change::Modified {
+                    key: 1,
+                    pre: 2,
+                    post: 5
+                },
+                Change::Removed(2, 3),
+            ]
+        );
+        assert!(diff_amts::<u64, _>(&db, &pre, &pre).unwrap().is_empty());
+
+        let hamt_root = |entries: &[(&str, u64)]| {
+            let mut hamt = Hamt::<_, u64>::new_with_bit_width(&db, HAMT_BIT_WIDTH);
+            for (key, value) in entries {
+                hamt.set(BytesKey(key.as_bytes().to_vec()), *value).unwrap();
+            }
+            hamt.flush().unwrap()
+        };
+        let pre = hamt_root(&[("a", 1), ("b", 2)]);
+        let post = hamt_root(&[("b", 2), ("c", 3)]);
+        assert_eq!(
+            diff_hamts::<u64, _>(&db, &pre, &post).unwrap(),
+            vec![
+                Change::Removed(b"a".to_vec(), 1),
+                Change::Added(b"c".to_vec(), 3)
+            ]
+        );
+
+        let diff = diff_bitfields(
+            &BitField::try_from_bits([1, 2, 3]).unwrap(),
+            &BitField::try_from_bits([3, 4]).unwrap(),
+        );
+        assert_eq!(diff.added.iter().collect::<Vec<_>>(), vec![4]);
+        assert_eq!(diff.removed.iter().collect::<Vec<_>>(), vec![1, 2]);
+    }
+}
@@ -1,6 +1,7 @@
 // Copyright 2019-2023 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
+pub mod collections;
 mod resolve;
 
 use std::{
@@ -12,6 +13,7 @@ use crate::ipld::json::{IpldJson, IpldJsonRef};
 use crate::json::cid::CidJson;
 use crate::shim::{
     address::Address,
+    machine::Manifest,
     state_tree::{ActorState, StateTree},
 };
 use ahash::HashMap;
@@ -24,11 +26,14 @@ use fil_actor_interface::{
     reward::State as RewardState, system::State as SystemState,
 };
 use fvm_ipld_blockstore::Blockstore;
+use fvm_ipld_encoding::CborStore;
 use libipld_core::ipld::Ipld;
 use resolve::resolve_cids_recursive;
 use serde::{Deserialize, Serialize};
 use similar::{ChangeTag, TextDiff};
 
+use self::collections::{diff_maps, Change};
+
 #[derive(Serialize, Deserialize)]
 struct ActorStateResolved {
     code: CidJson,
@@ -52,6 +57,101 @@ fn actor_to_resolved(
     }
 }
 
+/// Actor state as printed by `forest-tool state read-actor`
+#[derive(Serialize)]
+pub struct ActorStateJson {
+    address: String,
+    /// Name of the builtin actor in the manifest of the state tree, unknown
+    /// before actors v8
+    actor: Option<String>,
+    /// State decoded into the structure of its actor version, with named
+    /// fields, unless the actor isn't a builtin one
+    typed_state: Option<String>,
+    #[serde(flatten)]
+    resolved: ActorStateResolved,
+}
+
+/// Loads the state of the actor at `state_root`, with its links resolved up
+/// to `depth`, which expands the HAMTs and AMTs it holds.
+pub fn read_actor_state<BS: Blockstore>(
+    bs: &BS,
+    state_root: &Cid,
+    address: &Address,
+    depth: Option<u64>,
+) -> anyhow::Result<ActorStateJson> {
+    let state_tree = StateTree::new_from_root(bs, state_root)?;
+    let actor = state_tree
+        .get_actor(address)?
+        .ok_or_else(|| anyhow::anyhow!("Actor {address} not found at state root {state_root}"))?;
+    let actor_name = builtin_actor_name(bs, &state_tree, &actor.code);
+    Ok(ActorStateJson {
+        address: address.to_string(),
+        typed_state: typed_actor_state(bs, actor_name.as_deref(), &actor),
+        actor: actor_name,
+        resolved: actor_to_resolved(bs, &actor, depth),
+    })
+}
+
+/// Decodes the state of a builtin actor through the version aware states of
+/// `fil_actor_interface`. Without the actor name, i.e. before actors v8, each
+/// known actor state is tried in turn.
+fn typed_actor_state(
+    bs: &impl Blockstore,
+    actor_name: Option<&str>,
+    actor: &ActorState,
+) -> Option<String> {
+    macro_rules! load {
+        ($state:ty) => {
+            <$state>::load(bs, actor.code, actor.state)
+                .ok()
+                .map(|state| format!("{state:?}"))
+        };
+    }
+    match actor_name {
+        Some("storageminer") => load!(MinerState),
+        Some("cron") => load!(CronState),
+        Some("account") => load!(AccountState),
+        Some("storagepower") => load!(PowerState),
+        Some("init") => load!(InitState),
+        Some("reward") => load!(RewardState),
+        Some("system") => load!(SystemState),
+        Some("multisig") => load!(MultiSigState),
+        Some("storagemarket") => load!(MarketState),
+        Some("datacap") => load!(DatacapState),
+        Some("evm") => load!(EvmState),
+        Some(_) => None,
+        None => load!(MinerState)
+            .or_else(|| load!(CronState))
+            .or_else(|| load!(AccountState))
+            .or_else(|| load!(PowerState))
+            .or_else(|| load!(InitState))
+            .or_else(|| load!(RewardState))
+            .or_else(|| load!(SystemState))
+            .or_else(|| load!(MultiSigState))
+            .or_else(|| load!(MarketState))
+            .or_else(|| load!(DatacapState))
+            .or_else(|| load!(EvmState)),
+    }
+}
+
+/// Returns the name of the builtin actor with the given code, looked up in
+/// the manifest referenced by the system actor
+fn builtin_actor_name<BS: Blockstore>(
+    bs: &BS,
+    state_tree: &StateTree<&BS>,
+    code: &Cid,
+) -> Option<String> {
+    let system = state_tree.get_actor(&Address::SYSTEM_ACTOR).ok()??;
+    // The system actor state is a tuple whose only field is the manifest
+    // actors, since actors v8
+    let (builtin_actors,): (Cid,) = bs.get_cbor(&system.state).ok()??;
+    let manifest = Manifest::load_with_actors(bs, &builtin_actors, 1).ok()?;
+    manifest
+        .builtin_actors()
+        .find(|(_, actor_code)| *actor_code == code)
+        .map(|(name, _)| name.clone())
+}
+
 fn root_to_state_map<BS: Blockstore>(
     bs: &BS,
     root: &Cid,
@@ -78,18 +178,19 @@ fn try_print_actor_states<BS: Blockstore>(
 ) -> Result<(), anyhow::Error> {
     // For now, resolving to a map, because we need to use go implementation's
     // inefficient caching this would probably be faster in most cases.
-    let mut e_state = root_to_state_map(bs, expected_root)?;
-
-    // Compare state with expected
-    let state_tree = StateTree::new_from_root(bs, root)?;
-
-    state_tree.for_each(|addr: Address, actor| {
-        let calc_pp = pp_actor_state(bs, actor, depth)?;
-
-        if let Some(other) = e_state.remove(&addr) {
-            if &other != actor {
+    let expected = root_to_state_map(bs, expected_root)?;
+    let calculated = root_to_state_map(bs, root)?;
+
+    for change in diff_maps(expected, calculated) {
+        match change {
+            Change::Modified {
+                key: addr,
+                pre,
+                post,
+            } => {
                 let comma = ",";
-                let expected_pp = pp_actor_state(bs, &other, depth)?;
+                let expected_pp = pp_actor_state(bs, &pre, depth)?;
+                let calc_pp = pp_actor_state(bs, &post, depth)?;
                 let expected = expected_pp.split(comma).collect::<Vec<&str>>();
                 let calculated = calc_pp.split(comma).collect::<Vec<&str>>();
                 let diffs = TextDiff::from_slices(&expected, &calculated);
@@ -98,18 +199,18 @@ fn try_print_actor_states<BS: Blockstore>(
                 writeln!(handle, "Address {addr} changed: ")?;
                 print_diffs(&mut handle, diffs)?;
             }
-        } else {
-            // Added actor, print out the json format actor state.
-            println!("{}", format!("+ Address {addr}:\n{calc_pp}").green());
+            Change::Added(addr, actor) => {
+                // Added actor, print out the json format actor state.
+                let calc_pp = pp_actor_state(bs, &actor, depth)?;
+                println!("{}", format!("+ Address {addr}:\n{calc_pp}").green());
+            }
+            Change::Removed(addr, actor) => {
+                // Address that no longer has actor state
+                let expected_json =
+                    serde_json::to_string_pretty(&actor_to_resolved(bs, &actor, depth))?;
+                println!("{}", format!("- Address {addr}:\n{expected_json}").red())
+            }
         }
-
-        Ok(())
-    })?;
-
-    // Print all addresses that no longer have actor state
-    for (addr, state) in e_state.into_iter() {
-        let expected_json = serde_json::to_string_pretty(&actor_to_resolved(bs, &state, depth))?;
-        println!("{}", format!("- Address {addr}:\n{expected_json}").red())
     }
 
     Ok(())
@@ -122,48 +223,8 @@ fn pp_actor_state(
 ) -> Result<String, anyhow::Error> {
     let mut buffer = String::new();
     writeln!(&mut buffer, "{actor_state:?}")?;
-    if let Ok(miner_state) = MinerState::load(bs, actor_state.code, actor_state.state) {
-        write!(&mut buffer, "{miner_state:?}")?;
-        return Ok(buffer);
-    }
-    if let Ok(cron_state) = CronState::load(bs, actor_state.code, actor_state.state) {
-        write!(&mut buffer, "{cron_state:?}")?;
-        return Ok(buffer);
-    }
-    if let Ok(account_state) = AccountState::load(bs, actor_state.code, actor_state.state) {
-        write!(&mut buffer, "{account_state:?}")?;
-        return Ok(buffer);
-    }
-    if let Ok(power_state) = PowerState::load(bs, actor_state.code, actor_state.state) {
-        write!(&mut buffer, "{power_state:?}")?;
-        return Ok(buffer);
-    }
-    if let Ok(init_state) = InitState::load(bs, actor_state.code, actor_state.state) {
-        write!(&mut buffer, "{init_state:?}")?;
-        return Ok(buffer);
-    }
-    if let Ok(reward_state) = RewardState::load(bs, actor_state.code, actor_state.state) {
-        write!(&mut buffer, "{reward_state:?}")?;
-        return Ok(buffer);
-    }
-    if let Ok(system_state) = SystemState::load(bs, actor_state.code, actor_state.state) {
-        write!(&mut buffer, "{system_state:?}")?;
-        return Ok(buffer);
-    }
-    if let Ok(multi_sig_state) = MultiSigState::load(bs, actor_state.code, actor_state.state) {
-        write!(&mut buffer, "{multi_sig_state:?}")?;
-        return Ok(buffer);
-    }
-    if let Ok(market_state) = MarketState::load(bs, actor_state.code, actor_state.state) {
-        write!(&mut buffer, "{market_state:?}")?;
-        return Ok(buffer);
-    }
-    if let Ok(datacap_state) = DatacapState::load(bs, actor_state.code, actor_state.state) {
-        write!(&mut buffer, "{datacap_state:?}")?;
-        return Ok(buffer);
-    }
-    if let Ok(evm_state) = EvmState::load(bs, actor_state.code, actor_state.state) {
-        write!(&mut buffer, "{evm_state:?}")?;
+    if let Some(typed_state) = typed_actor_state(bs, None, actor_state) {
+        write!(&mut buffer, "{typed_state}")?;
         return Ok(buffer);
     }
 
@@ -220,13 +281,17 @@ where
 #[cfg(test)]
 mod tests {
     use crate::db::MemoryDB;
-    use crate::shim::{address::Address, econ::TokenAmount, state_tree::ActorState};
+    use crate::shim::{
+        address::Address,
+        econ::TokenAmount,
+        state_tree::{ActorState, StateTree, StateTreeVersion},
+    };
     use crate::utils::db::CborStoreExt;
     use cid::Cid;
     use fil_actor_account_state::v10::State as AccountState;
     use fvm_ipld_blockstore::Blockstore;
 
-    use super::pp_actor_state;
+    use super::{pp_actor_state, read_actor_state};
 
     fn mk_account_v10(db: &impl Blockstore, account: &AccountState) -> ActorState {
         // mainnet v10 account actor cid
@@ -298,4 +363,52 @@ mod tests {
 }"
         );
     }
+
+    #[test]
+    fn read_actor_state_names_builtin_actors() {
+        let db = MemoryDB::default();
+        let account = Address::new_id(1000);
+        let account_state = mk_account_v10(
+            &db,
+            &AccountState {
+                address: account.into(),
+            },
+        );
+        let manifest_actors = db
+            .put_cbor_default(&vec![
+                ("system".to_string(), Cid::default()),
+                ("account".to_string(), account_state.code),
+                ("cron".to_string(), Cid::default()),
+                ("init".to_string(), Cid::default()),
+            ])
+            .unwrap();
+        let system_state = db.put_cbor_default(&(manifest_actors,)).unwrap();
+        let mut state_tree = StateTree::new(&db, StateTreeVersion::V5).unwrap();
+        state_tree
+            .set_actor(
+                &Address::SYSTEM_ACTOR,
+                ActorState::new(
+                    Cid::default(),
+                    system_state,
+                    TokenAmount::from_atto(0),
+                    0,
+                    None,
+                ),
+            )
+            .unwrap();
+        state_tree.set_actor(&account, account_state).unwrap();
+        let state_root = state_tree.flush().unwrap();
+
+        let json =
+            serde_json::to_value(read_actor_state(&db, &state_root, &account, None).unwrap())
+                .unwrap();
+        assert_eq!(json["address"], account.to_string());
+        assert_eq!(json["actor"], "account");
+        assert_eq!(
+            json["typed_state"],
+            "V10(State { address: Address { payload: ID(1000) } })"
+        );
+        assert_eq!(json["sequence"], 0);
+        assert!(read_actor_state(&db, &state_root, &Address::new_id(1001), None).is_err());
+    }
 }
@@ -0,0 +1,179 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Deterministic generation of chains of block headers, to cover forks, null
+//! rounds, equivocating blocks and deep reorgs in tests, rather than waiting
+//! for a network to exercise them. The headers only carry what the fork
+//! choice and the head change logic look at: epochs, parents, tickets,
+//! timestamps and weights. Their messages are left empty, as are their
+//! states unless set with [`ChainGenerator::set_state_root`], and their
+//! signatures and election proofs are placeholders that only pass the
+//! checks of the `gossipsub` validation.
+
+use std::sync::Arc;
+
+use crate::blocks::{BlockHeader, ElectionProof, Ticket, Tipset};
+use crate::json::vrf::VRFProof;
+use crate::shim::{address::Address, clock::ChainEpoch, crypto::Signature};
+use cid::{
+    multihash::{Code::Blake2b256, MultihashDigest},
+    Cid,
+};
+use fvm_ipld_encoding::DAG_CBOR;
+use num::BigInt;
+
+/// Seconds between two epochs, as on mainnet
+pub const BLOCK_DELAY_SECS: u64 = 30;
+
+/// Weight added to the chain by each block of a tipset
+pub const WEIGHT_PER_BLOCK: u64 = 10;
+
+/// Miner of the genesis block
+const GENESIS_MINER: u64 = 1000;
+
+pub struct ChainGenerator {
+    genesis: Arc<Tipset>,
+    /// Counter making the tickets, and thus the headers, distinct
+    nonce: u64,
+    /// Parent state of the headers mined
+    state_root: Cid,
+}
+
+impl Default for ChainGenerator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ChainGenerator {
+    pub fn new() -> Self {
+        let genesis = BlockHeader::builder()
+            .miner_address(Address::new_id(GENESIS_MINER))
+            .ticket(Some(ticket(0)))
+            .state_root(empty_root())
+            .messages(empty_root())
+            .message_receipts(empty_root())
+            .build()
+            .unwrap();
+        Self {
+            genesis: Arc::new(Tipset::from(genesis)),
+            nonce: 0,
+            state_root: empty_root(),
+        }
+    }
+
+    pub fn genesis(&self) -> &Arc<Tipset> {
+        &self.genesis
+    }
+
+    /// Sets the parent state of the headers mined from now on, for the tests
+    /// reading the states of the chain.
+    pub fn set_state_root(&mut self, state_root: Cid) {
+        self.state_root = state_root;
+    }
+
+    /// Mines a tipset with a block of each of the `miners` on top of
+    /// `parent`, after `null_rounds` epochs without blocks.
+    pub fn mine(&mut self, parent: &Tipset, miners: &[u64], null_rounds: u64) -> Arc<Tipset> {
+        let epoch = parent.epoch() + 1 + null_rounds as ChainEpoch;
+        let headers = miners
+            .iter()
+            .map(|miner| self.header(parent, *miner, epoch))
+            .collect();
+        Arc::new(Tipset::new(headers).unwrap())
+    }
+
+    /// Mines `len` tipsets with a block of each of the `miners`, without null
+    /// rounds. Returns them from the oldest one.
+    pub fn extend(&mut self, parent: &Arc<Tipset>, miners: &[u64], len: usize) -> Vec<Arc<Tipset>> {
+        let mut tipsets: Vec<Arc<Tipset>> = Vec::with_capacity(len);
+        for _ in 0..len {
+            let parent = tipsets.last().unwrap_or(parent);
+            let tipset = self.mine(parent, miners, 0);
+            tipsets.push(tipset);
+        }
+        tipsets
+    }
+
+    /// Returns another block of the same miner, at the same epoch and on top
+    /// of the same `parent`, i.e. a double fork mining fault.
+    pub fn equivocate(&mut self, header: &BlockHeader, parent: &Tipset) -> BlockHeader {
+        assert_eq!(header.parents(), parent.key());
+        self.header(parent, header.miner_address().id().unwrap(), header.epoch())
+    }
+
+    fn header(&mut self, parent: &Tipset, miner: u64, epoch: ChainEpoch) -> BlockHeader {
+        self.nonce += 1;
+        let weight =
+            parent.weight() + BigInt::from(WEIGHT_PER_BLOCK * parent.blocks().len() as u64);
+        BlockHeader::builder()
+            .parents(parent.key().clone())
+            .miner_address(Address::new_id(miner))
+            .epoch(epoch)
+            .weight(weight)
+            .ticket(Some(ticket(self.nonce)))
+            .election_proof(Some(ElectionProof {
+                win_count: 1,
+                vrfproof: VRFProof::default(),
+            }))
+            .signature(Some(Signature::new_bls(vec![])))
+            .bls_aggregate(Some(Signature::new_bls(vec![])))
+            .timestamp(self.genesis.min_timestamp() + epoch as u64 * BLOCK_DELAY_SECS)
+            .state_root(self.state_root)
+            .messages(empty_root())
+            .message_receipts(empty_root())
+            .build()
+            .unwrap()
+    }
+}
+
+fn ticket(nonce: u64) -> Ticket {
+    Ticket::new(VRFProof::new(
+        Blake2b256.digest(&nonce.to_be_bytes()).digest().to_vec(),
+    ))
+}
+
+fn empty_root() -> Cid {
+    Cid::new_v1(DAG_CBOR, Blake2b256.digest(&[]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generates_forks_with_null_rounds() {
+        let mut generator = ChainGenerator::new();
+        let genesis = generator.genesis().clone();
+        let main = generator.extend(&genesis, &[1000], 3);
+        assert_eq!(main[2].epoch(), 3);
+        assert_eq!(main[2].parents(), main[1].key());
+        assert_eq!(main[2].weight(), &BigInt::from(3 * WEIGHT_PER_BLOCK));
+
+        let fork = generator.mine(&main[0], &[1001, 1002], 4);
+        assert_eq!(fork.epoch(), 6);
+        assert_eq!(fork.parents(), main[0].key());
+        assert_eq!(fork.blocks().len(), 2);
+        let next = generator.mine(&fork, &[1001], 0);
+        assert!(next.weight() > main[2].weight());
+        assert_eq!(
+            next.min_timestamp(),
+            genesis.min_timestamp() + 7 * BLOCK_DELAY_SECS
+        );
+    }
+
+    #[test]
+    fn equivocating_blocks_differ() {
+        let mut generator = ChainGenerator::new();
+        let genesis = generator.genesis().clone();
+        let tipset = generator.mine(&genesis, &[1000], 0);
+        let block = tipset.min_ticket_block();
+        let other = generator.equivocate(block, &genesis);
+        assert_ne!(other.cid(), block.cid());
+        assert_eq!(other.miner_address(), block.miner_address());
+        assert_eq!(other.epoch(), block.epoch());
+        assert_eq!(other.parents(), block.parents());
+        // Both blocks can't be in the same tipset
+        assert!(Tipset::new(vec![block.clone(), other]).is_err());
+    }
+}
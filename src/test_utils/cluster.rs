@@ -0,0 +1,477 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Clusters of Forest nodes running in the process of a test, connected over
+//! in-memory transports, to test the networked features deterministically
+//! rather than against a live network. The nodes share the genesis of a
+//! [`ChainGenerator`] that produces their blocks, and each node runs its own
+//! libp2p service on top of its own chain store, so that chain exchange,
+//! `gossipsub` propagation and validation go through the same code as in the
+//! daemon. Each node also has a message pool that is fed the gossiped
+//! messages, as the chain muxer of the daemon does.
+//!
+//! The blocks aren't executed, the nodes have no state manager, and the
+//! message pools see a fixed state through a [`TestApi`]. So the payment
+//! channel actors can't be created or redeemed in a cluster; payment channel
+//! interoperation needs executing nodes and is left out of the clusters.
+
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use crate::blocks::{GossipBlock, Tipset};
+use crate::chain::ChainStore;
+use crate::chain_sync::SyncNetworkContext;
+use crate::db::MemoryDB;
+use crate::libp2p::{
+    Keypair, Libp2pConfig, Libp2pService, Multiaddr, NetRPCMethods, NetworkEvent, NetworkMessage,
+    PeerId, PeerManager, Protocol, PubsubMessage, PUBSUB_BLOCK_STR, PUBSUB_MSG_STR,
+};
+use crate::message::{Message as MessageTrait, SignedMessage};
+use crate::message_pool::{test_provider::TestApi, MessagePool, MpoolConfig};
+use crate::networks::ChainConfig;
+use crate::shim::address::Address;
+use anyhow::{bail, Context};
+use fvm_ipld_encoding::to_vec;
+use libp2p::gossipsub::IdentTopic;
+use log::debug;
+use tempfile::TempDir;
+use tokio::task::{JoinHandle, JoinSet};
+
+use super::ChainGenerator;
+
+/// Name of the network of the clusters, in their `gossipsub` topics
+const NETWORK_NAME: &str = "testnet";
+
+/// Time a node is given to see an event before the test fails
+pub const EVENT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Port of the next `/memory/` address, unique across the clusters of the
+/// process as tests run in parallel
+static NEXT_MEMORY_PORT: AtomicU64 = AtomicU64::new(1);
+
+/// A node of a [`TestCluster`]
+pub struct TestNode {
+    pub peer_id: PeerId,
+    /// Address the node listens on, with its peer ID
+    pub addr: Multiaddr,
+    pub chain_store: Arc<ChainStore<MemoryDB>>,
+    pub peer_manager: Arc<PeerManager>,
+    pub network: SyncNetworkContext<MemoryDB>,
+    pub mpool: Arc<MessagePool<TestApi>>,
+    network_send: flume::Sender<NetworkMessage>,
+    /// Events of the network, once the messages have been given to the
+    /// message pool
+    network_events: flume::Receiver<NetworkEvent>,
+    service: JoinHandle<anyhow::Result<()>>,
+    /// Tasks of the message pool and the dispatch of the network events
+    _services: JoinSet<anyhow::Result<()>>,
+    _data_dir: TempDir,
+}
+
+impl TestNode {
+    /// Starts a node that dials the given peers.
+    fn start(generator: &ChainGenerator, peers: Vec<Multiaddr>) -> anyhow::Result<Self> {
+        let data_dir = TempDir::new()?;
+        let db = MemoryDB::default();
+        let genesis = generator.genesis().min_ticket_block().clone();
+        let chain_store = Arc::new(ChainStore::new(
+            db.clone(),
+            Arc::new(ChainConfig::devnet()),
+            &genesis,
+            data_dir.path(),
+        )?);
+        chain_store.set_genesis(&genesis)?;
+
+        let keypair = Keypair::generate_ed25519();
+        let peer_id = PeerId::from(keypair.public());
+        let port = NEXT_MEMORY_PORT.fetch_add(1, Ordering::Relaxed);
+        let listen_addr = Multiaddr::empty().with(Protocol::Memory(port));
+        let config = Libp2pConfig {
+            listening_multiaddrs: vec![listen_addr.clone()],
+            bootstrap_peers: peers,
+            kademlia: false,
+            mdns: false,
+            quic: false,
+            ..Default::default()
+        };
+        let peer_manager = Arc::new(PeerManager::default());
+        let service = Libp2pService::new(
+            config,
+            chain_store.clone(),
+            peer_manager.clone(),
+            keypair,
+            None,
+            NETWORK_NAME,
+            *genesis.cid(),
+        );
+        let network_send = service.network_sender();
+        let network = SyncNetworkContext::new(network_send.clone(), peer_manager.clone(), db);
+
+        let mut services = JoinSet::new();
+        let mpool = Arc::new(MessagePool::new(
+            TestApi::default(),
+            NETWORK_NAME.into(),
+            network_send.clone(),
+            MpoolConfig::default(),
+            Arc::new(ChainConfig::devnet()),
+            &mut services,
+        )?);
+        let (events_tx, network_events) = flume::unbounded();
+        services.spawn(dispatch_events(
+            service.network_receiver(),
+            mpool.clone(),
+            events_tx,
+        ));
+        Ok(Self {
+            peer_id,
+            addr: listen_addr.with(Protocol::P2p(peer_id.into())),
+            chain_store,
+            peer_manager,
+            network,
+            mpool,
+            network_send,
+            network_events,
+            service: tokio::spawn(service.run()),
+            _services: services,
+            _data_dir: data_dir,
+        })
+    }
+
+    /// Head of the chain of the node
+    pub fn head(&self) -> Arc<Tipset> {
+        self.chain_store.heaviest_tipset()
+    }
+
+    /// Stores the tipsets, given from the oldest one, and makes the last one
+    /// the head of the node.
+    pub fn import(&self, tipsets: &[Arc<Tipset>]) -> anyhow::Result<()> {
+        for tipset in tipsets {
+            crate::chain::persist_objects(self.chain_store.blockstore(), tipset.blocks())?;
+        }
+        if let Some(head) = tipsets.last() {
+            self.chain_store.set_heaviest_tipset(head.clone())?;
+        }
+        Ok(())
+    }
+
+    /// Peers the node is connected to
+    pub async fn peers(&self) -> anyhow::Result<Vec<PeerId>> {
+        let (tx, rx) = flume::bounded(1);
+        self.network_send
+            .send_async(NetworkMessage::JSONRPCRequest {
+                method: NetRPCMethods::NetPeers(tx),
+            })
+            .await?;
+        Ok(rx.recv_async().await?.into_keys().collect())
+    }
+
+    /// Publishes the blocks of a tipset over `gossipsub`, as a miner does.
+    pub async fn publish_tipset(&self, tipset: &Tipset) -> anyhow::Result<()> {
+        let topic = IdentTopic::new(format!("{PUBSUB_BLOCK_STR}/{NETWORK_NAME}"));
+        for header in tipset.blocks() {
+            let block = GossipBlock {
+                header: header.clone(),
+                bls_messages: vec![],
+                secpk_messages: vec![],
+            };
+            self.publish(topic.clone(), to_vec(&block)?).await?;
+        }
+        Ok(())
+    }
+
+    /// Publishes a message over `gossipsub`, as the message pool does.
+    pub async fn publish_message(&self, message: &SignedMessage) -> anyhow::Result<()> {
+        let topic = IdentTopic::new(format!("{PUBSUB_MSG_STR}/{NETWORK_NAME}"));
+        self.publish(topic, to_vec(message)?).await
+    }
+
+    async fn publish(&self, topic: IdentTopic, message: Vec<u8>) -> anyhow::Result<()> {
+        self.network_send
+            .send_async(NetworkMessage::PubsubMessage { topic, message })
+            .await?;
+        Ok(())
+    }
+
+    /// Waits for a network event the `filter` maps to some value, skipping
+    /// the other events, for at most [`EVENT_TIMEOUT`].
+    pub async fn wait_for_event<T>(
+        &self,
+        mut filter: impl FnMut(NetworkEvent) -> Option<T>,
+    ) -> anyhow::Result<T> {
+        tokio::time::timeout(EVENT_TIMEOUT, async {
+            loop {
+                if let Some(value) = filter(self.network_events.recv_async().await?) {
+                    return anyhow::Ok(value);
+                }
+            }
+        })
+        .await
+        .context("timed out waiting for a network event")?
+    }
+
+    /// Waits for the pending messages of `from` in the message pool, sorted by
+    /// sequence, to satisfy `done`, for at most [`EVENT_TIMEOUT`].
+    pub async fn wait_for_pending(
+        &self,
+        from: &Address,
+        mut done: impl FnMut(&[SignedMessage]) -> bool,
+    ) -> anyhow::Result<Vec<SignedMessage>> {
+        let start = tokio::time::Instant::now();
+        loop {
+            let mut pending = self.mpool.pending_for(from).unwrap_or_default();
+            pending.sort_by_key(|msg| msg.sequence());
+            if done(&pending) {
+                return Ok(pending);
+            }
+            if start.elapsed() > EVENT_TIMEOUT {
+                bail!(
+                    "node {} timed out waiting for the messages of {from}",
+                    self.peer_id
+                );
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+    }
+
+    /// Waits for a `gossipsub` message that passed the validation.
+    pub async fn wait_for_pubsub(&self) -> anyhow::Result<(PeerId, PubsubMessage)> {
+        self.wait_for_event(|event| match event {
+            NetworkEvent::PubsubMessage { source, message } => Some((source, message)),
+            _ => None,
+        })
+        .await
+    }
+}
+
+/// Adds the gossiped messages to the message pool, as the chain muxer does,
+/// and hands all the events over to the node.
+async fn dispatch_events(
+    events: flume::Receiver<NetworkEvent>,
+    mpool: Arc<MessagePool<TestApi>>,
+    events_tx: flume::Sender<NetworkEvent>,
+) -> anyhow::Result<()> {
+    while let Ok(event) = events.recv_async().await {
+        if let NetworkEvent::PubsubMessage {
+            message: PubsubMessage::Message(message),
+            ..
+        } = &event
+        {
+            if let Err(e) = mpool.add(message.clone()) {
+                debug!("gossiped message could not be added to the pool: {e}");
+            }
+        }
+        events_tx.send_async(event).await?;
+    }
+    Ok(())
+}
+
+impl Drop for TestNode {
+    fn drop(&mut self) {
+        self.service.abort();
+    }
+}
+
+/// Nodes connected to each other in a full mesh, with a shared genesis
+pub struct TestCluster {
+    pub nodes: Vec<TestNode>,
+    pub generator: ChainGenerator,
+}
+
+impl TestCluster {
+    /// Starts `size` nodes, each dialing the ones started before it, and
+    /// waits for all of them to be connected to each other.
+    pub async fn new(size: usize) -> anyhow::Result<Self> {
+        let generator = ChainGenerator::new();
+        let mut nodes: Vec<TestNode> = Vec::with_capacity(size);
+        for _ in 0..size {
+            let peers = nodes.iter().map(|node| node.addr.clone()).collect();
+            nodes.push(TestNode::start(&generator, peers)?);
+        }
+        let cluster = Self { nodes, generator };
+        cluster.wait_for_mesh().await?;
+        Ok(cluster)
+    }
+
+    /// Waits for every node to be connected to all the other ones, and gives
+    /// `gossipsub` time to exchange the subscriptions.
+    async fn wait_for_mesh(&self) -> anyhow::Result<()> {
+        let start = tokio::time::Instant::now();
+        for node in &self.nodes {
+            while node.peers().await?.len() + 1 < self.nodes.len() {
+                if start.elapsed() > EVENT_TIMEOUT {
+                    bail!("node {} did not connect to all its peers", node.peer_id);
+                }
+                tokio::time::sleep(Duration::from_millis(50)).await;
+            }
+        }
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        Ok(())
+    }
+
+    /// Mines `len` tipsets with a block of each of the `miners` on top of the
+    /// head of the given node, which imports them. Returns them from the
+    /// oldest one.
+    pub fn mine(
+        &mut self,
+        node: usize,
+        miners: &[u64],
+        len: usize,
+    ) -> anyhow::Result<Vec<Arc<Tipset>>> {
+        let node = &self.nodes[node];
+        let tipsets = self.generator.extend(&node.head(), miners, len);
+        node.import(&tipsets)?;
+        Ok(tipsets)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::key_management::{KeyStore, KeyStoreConfig, Wallet};
+    use crate::message_pool::tests::create_smsg;
+    use crate::shim::{
+        crypto::{Signature, SignatureType},
+        message::Message,
+    };
+
+    /// Wallet holding a sender and a recipient key
+    fn wallet() -> (Wallet, Address, Address) {
+        let mut wallet = Wallet::new(KeyStore::new(KeyStoreConfig::Memory).unwrap());
+        let from = wallet.generate_addr(SignatureType::Secp256k1).unwrap();
+        let to = wallet.generate_addr(SignatureType::Secp256k1).unwrap();
+        (wallet, from, to)
+    }
+
+    #[tokio::test]
+    async fn chain_exchange_between_nodes() {
+        let mut cluster = TestCluster::new(2).await.unwrap();
+        let tipsets = cluster.mine(0, &[1000], 10).unwrap();
+        let (server, client) = (&cluster.nodes[0], &cluster.nodes[1]);
+
+        let head = server.head();
+        assert_eq!(&head, tipsets.last().unwrap());
+        let fetched = client
+            .network
+            .chain_exchange_headers(Some(server.peer_id), head.key(), 10)
+            .await
+            .unwrap();
+        // From the head down
+        let expected: Vec<_> = tipsets.iter().rev().cloned().collect();
+        assert_eq!(fetched, expected);
+
+        client.import(&tipsets).unwrap();
+        assert_eq!(client.head(), head);
+    }
+
+    #[tokio::test]
+    async fn blocks_propagate_over_gossip() {
+        let mut cluster = TestCluster::new(3).await.unwrap();
+        let tipset = cluster.mine(0, &[1000], 1).unwrap().remove(0);
+        let miner = &cluster.nodes[0];
+        miner.publish_tipset(&tipset).await.unwrap();
+
+        for node in &cluster.nodes[1..] {
+            let (source, message) = node.wait_for_pubsub().await.unwrap();
+            let PubsubMessage::Block(block) = message else {
+                panic!("expected a block, got {message:?}");
+            };
+            assert_eq!(&block.header, tipset.min_ticket_block());
+            // Forwarded by the miner or by the other node
+            assert!(cluster.nodes.iter().any(|n| n.peer_id == source));
+        }
+    }
+
+    #[tokio::test]
+    async fn messages_propagate_over_gossip() {
+        let cluster = TestCluster::new(2).await.unwrap();
+        // Messages sent from ID addresses are checked by the message pool
+        // rather than by the gossip validation
+        let message = SignedMessage::new_unchecked(
+            Message {
+                from: Address::new_id(100),
+                to: Address::new_id(101),
+                ..Default::default()
+            },
+            Signature::new_bls(vec![]),
+        );
+        cluster.nodes[0].publish_message(&message).await.unwrap();
+
+        let (_, received) = cluster.nodes[1].wait_for_pubsub().await.unwrap();
+        let PubsubMessage::Message(received) = received else {
+            panic!("expected a message, got {received:?}");
+        };
+        assert_eq!(received, message);
+    }
+
+    #[tokio::test]
+    async fn pushed_messages_reach_the_other_pools() {
+        let cluster = TestCluster::new(3).await.unwrap();
+        let (mut wallet, from, to) = wallet();
+        let messages: Vec<_> = (0..3)
+            .map(|sequence| create_smsg(&to, &from, &mut wallet, sequence, 1_000_000, 100))
+            .collect();
+        for message in &messages {
+            cluster.nodes[0].mpool.push(message.clone()).await.unwrap();
+        }
+
+        for node in &cluster.nodes[1..] {
+            let pending = node
+                .wait_for_pending(&from, |pending| pending.len() == messages.len())
+                .await
+                .unwrap();
+            assert_eq!(pending, messages);
+        }
+    }
+
+    #[tokio::test]
+    async fn replacements_reach_the_other_pools() {
+        let cluster = TestCluster::new(2).await.unwrap();
+        let (mut wallet, from, to) = wallet();
+        let original = create_smsg(&to, &from, &mut wallet, 0, 1_000_000, 100);
+        let replacement = create_smsg(&to, &from, &mut wallet, 0, 1_000_000, 200);
+        let (sender, receiver) = (&cluster.nodes[0], &cluster.nodes[1]);
+
+        sender.mpool.push(original.clone()).await.unwrap();
+        receiver
+            .wait_for_pending(&from, |pending| pending == [original.clone()])
+            .await
+            .unwrap();
+        sender.mpool.push(replacement.clone()).await.unwrap();
+        receiver
+            .wait_for_pending(&from, |pending| pending == [replacement.clone()])
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn invalid_messages_stay_out_of_the_pools() {
+        let cluster = TestCluster::new(2).await.unwrap();
+        let (mut wallet, from, to) = wallet();
+        let valid = create_smsg(&to, &from, &mut wallet, 0, 1_000_000, 100);
+        // Signed by the recipient rather than by the sender
+        let forged = create_smsg(&to, &to, &mut wallet, 1, 1_000_000, 100);
+        let forged = SignedMessage::new_unchecked(
+            Message {
+                from,
+                ..forged.message().clone()
+            },
+            forged.signature().clone(),
+        );
+        let (sender, receiver) = (&cluster.nodes[0], &cluster.nodes[1]);
+
+        sender.publish_message(&forged).await.unwrap();
+        sender.mpool.push(valid.clone()).await.unwrap();
+        // Gossip keeps the order of the messages of a peer, so the forged one
+        // was handled once the valid one is in the pool
+        let pending = receiver
+            .wait_for_pending(&from, |pending| pending.contains(&valid))
+            .await
+            .unwrap();
+        assert_eq!(pending, [valid]);
+    }
+}
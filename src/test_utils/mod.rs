@@ -1,9 +1,14 @@
 // Copyright 2019-2023 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
+mod chain_generator;
 mod chain_structures;
+#[cfg(test)]
+mod cluster;
 
-pub use self::chain_structures::*;
+pub use self::{chain_generator::*, chain_structures::*};
+#[cfg(test)]
+pub use cluster::*;
 
 // Serialize macro used for testing
 #[macro_export]
@@ -0,0 +1,40 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use std::ffi::OsString;
+
+use crate::cli_shared::{
+    cli::{CliOpts, DaemonConfig, LogConfig},
+    logger,
+};
+use clap::Parser;
+
+use super::subcommands::{Cli, Subcommand};
+
+pub fn main<ArgT>(args: impl IntoIterator<Item = ArgT>) -> anyhow::Result<()>
+where
+    ArgT: Into<OsString> + Clone,
+{
+    // Capture Cli inputs
+    let Cli { cmd } = Cli::parse_from(args);
+
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .unwrap()
+        .block_on(async {
+            logger::setup_logger(
+                &LogConfig::default(),
+                &DaemonConfig::default(),
+                &CliOpts::default(),
+            );
+            match cmd {
+                Subcommand::Archive(cmd) => cmd.run().await,
+                Subcommand::Snapshot(cmd) => cmd.run().await,
+                Subcommand::State(cmd) => cmd.run(),
+                Subcommand::Shed(cmd) => cmd.run(),
+                Subcommand::TestVectors(cmd) => cmd.run().await,
+                Subcommand::DB(cmd) => cmd.run(),
+            }
+        })
+}
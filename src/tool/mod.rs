@@ -0,0 +1,4 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+pub mod main;
+pub mod subcommands;
@@ -0,0 +1,521 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Summary of a CAR archive, computed while streaming through it so that
+//! snapshots of any size can be inspected without importing them, and export
+//! of the messages of an address from indexed archives.
+
+use std::{
+    fmt,
+    fs::File,
+    io::{self, BufWriter, SeekFrom, Write},
+    num::NonZeroUsize,
+    ops::RangeInclusive,
+    path::PathBuf,
+    sync::Arc,
+    time::Duration,
+};
+
+use crate::blocks::{BlockHeader, Tipset, TipsetKeys};
+use crate::chain::{get_parent_reciept, messages_for_tipset, ChainIndex};
+use crate::interpreter::resolve_to_key_addr;
+use crate::message::{ChainMessage, Message as _};
+use crate::shim::{
+    address::{Address, StrictAddress},
+    clock::ChainEpoch,
+    executor::Receipt,
+    state_tree::StateTree,
+};
+use crate::utils::{
+    car::{car_v1_payload, forest::ForestCarFooter},
+    io::ProgressBar,
+    net::FetchProgress,
+};
+use ahash::{HashMap, HashSet};
+use anyhow::Context;
+use async_compression::futures::bufread::ZstdDecoder;
+use base64::{prelude::BASE64_STANDARD, Engine};
+use cid::Cid;
+use clap::Subcommand;
+use futures::{io::BufReader, AsyncRead, AsyncReadExt, AsyncSeekExt};
+use fvm_ipld_blockstore::Blockstore;
+use fvm_ipld_car::CarReader;
+use fvm_ipld_encoding::Cbor;
+use human_repr::HumanCount;
+use lru::LruCache;
+use nonzero_ext::nonzero;
+use parking_lot::Mutex;
+use serde::Serialize;
+
+use super::open_archive;
+
+// https://github.com/facebook/zstd/blob/dev/doc/zstd_compression_format.md#zstandard-frames
+const ZSTD_MAGIC_HEADER: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+const TIPSET_CACHE_SIZE: NonZeroUsize = nonzero!(1024usize);
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum ExportFormat {
+    #[default]
+    Json,
+    Csv,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum ArchiveCommands {
+    /// Show the roots, blocks, epochs and state roots found in a CAR archive,
+    /// compressed with zstd or not
+    Info {
+        /// Path to the archive
+        archive: PathBuf,
+    },
+    /// Export the messages sent from or to an address, with their receipts,
+    /// from a `.forest.car.zst` archive or an indexed CARv2 file
+    ExportMsgs {
+        /// Path to the archive
+        archive: PathBuf,
+        /// Address the messages are sent from or to, in any of its forms
+        address: StrictAddress,
+        /// Lowest epoch of the messages exported, the earliest one whose
+        /// messages are in the archive by default
+        #[arg(long)]
+        from: Option<ChainEpoch>,
+        /// Highest epoch of the messages exported, the head of the archive by
+        /// default
+        #[arg(long)]
+        to: Option<ChainEpoch>,
+        #[arg(long, value_enum, default_value_t = ExportFormat::default())]
+        format: ExportFormat,
+        /// File the messages are written to, the standard output by default
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+}
+
+impl ArchiveCommands {
+    pub async fn run(&self) -> anyhow::Result<()> {
+        match self {
+            Self::Info { archive } => {
+                let mut file = async_fs::File::open(archive).await?;
+                let file_size = file.metadata().await?.len();
+                let compressed = {
+                    let mut header = [0; ZSTD_MAGIC_HEADER.len()];
+                    file.read_exact(&mut header).await?;
+                    file.seek(SeekFrom::Start(0)).await?;
+                    header == ZSTD_MAGIC_HEADER
+                };
+
+                let progress_bar = ProgressBar::new(file_size);
+                progress_bar.message("Reading archive ");
+                progress_bar.set_units(crate::utils::io::progress_bar::Units::Bytes);
+                progress_bar.set_max_refresh_rate(Some(Duration::from_millis(500)));
+                let reader = BufReader::new(FetchProgress {
+                    inner: file,
+                    progress_bar,
+                });
+                let mut info = if compressed {
+                    let mut decoder = ZstdDecoder::new(reader);
+                    decoder.multiple_members(true);
+                    ArchiveInfo::read(decoder).await?
+                } else {
+                    ArchiveInfo::read(reader).await?
+                };
+                info.compressed = compressed;
+                info.file_size = file_size;
+                info.indexed_blocks = ForestCarFooter::read(&std::fs::File::open(archive)?)?
+                    .map(|footer| footer.entry_count);
+
+                println!("{info}");
+                Ok(())
+            }
+            Self::ExportMsgs {
+                archive,
+                address: StrictAddress(address),
+                from,
+                to,
+                format,
+                output,
+            } => {
+                let store = open_archive(archive)?;
+                let records = export_messages(&store, store.roots(), address, *from, *to)?;
+
+                let writer: Box<dyn Write> = match output {
+                    Some(path) => Box::new(File::create(path)?),
+                    None => Box::new(io::stdout()),
+                };
+                let mut writer = BufWriter::new(writer);
+                match format {
+                    ExportFormat::Json => {
+                        serde_json::to_writer_pretty(&mut writer, &records)?;
+                        writeln!(writer)?;
+                    }
+                    ExportFormat::Csv => {
+                        writeln!(writer, "{}", MessageRecord::CSV_HEADER)?;
+                        for record in records.iter() {
+                            writeln!(writer, "{}", record.csv_row())?;
+                        }
+                    }
+                }
+                writer.flush()?;
+                Ok(())
+            }
+        }
+    }
+}
+
+/// A message and its receipt, with the amounts in attoFIL
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct MessageRecord {
+    epoch: ChainEpoch,
+    cid: String,
+    from: String,
+    to: String,
+    nonce: u64,
+    value: String,
+    method: u64,
+    gas_limit: u64,
+    gas_fee_cap: String,
+    gas_premium: String,
+    /// Receipt fields, missing when the tipset executing the message isn't
+    /// in the archive
+    exit_code: Option<u32>,
+    gas_used: Option<u64>,
+    /// Base64 encoded
+    return_data: Option<String>,
+}
+
+impl MessageRecord {
+    const CSV_HEADER: &'static str = "epoch,cid,from,to,nonce,value,method,gas_limit,gas_fee_cap,gas_premium,exit_code,gas_used,return_data";
+
+    fn new(
+        epoch: ChainEpoch,
+        message: &ChainMessage,
+        receipt: Option<Receipt>,
+    ) -> anyhow::Result<Self> {
+        Ok(Self {
+            epoch,
+            cid: message.cid()?.to_string(),
+            from: message.from().to_string(),
+            to: message.to().to_string(),
+            nonce: message.sequence(),
+            value: message.value().atto().to_string(),
+            method: message.method_num(),
+            gas_limit: message.gas_limit(),
+            gas_fee_cap: message.gas_fee_cap().atto().to_string(),
+            gas_premium: message.gas_premium().atto().to_string(),
+            exit_code: receipt.as_ref().map(|r| r.exit_code().value()),
+            gas_used: receipt.as_ref().map(|r| r.gas_used()),
+            return_data: receipt.map(|r| BASE64_STANDARD.encode(r.return_data().bytes())),
+        })
+    }
+
+    /// None of the fields contain commas or quotes, they are written as is
+    fn csv_row(&self) -> String {
+        let optional = |field: Option<String>| field.unwrap_or_default();
+        [
+            self.epoch.to_string(),
+            self.cid.clone(),
+            self.from.clone(),
+            self.to.clone(),
+            self.nonce.to_string(),
+            self.value.clone(),
+            self.method.to_string(),
+            self.gas_limit.to_string(),
+            self.gas_fee_cap.clone(),
+            self.gas_premium.clone(),
+            optional(self.exit_code.map(|code| code.to_string())),
+            optional(self.gas_used.map(|gas| gas.to_string())),
+            optional(self.return_data.clone()),
+        ]
+        .join(",")
+    }
+}
+
+/// Returns the address with its ID and key forms, as found in the parent
+/// state of the head. Messages may refer to the actor with any of them.
+fn address_forms(store: &impl Blockstore, head: &Tipset, address: &Address) -> HashSet<Address> {
+    let mut addresses = HashSet::from_iter([*address]);
+    if let Ok(state_tree) = StateTree::new_from_root(store, head.parent_state()) {
+        if let Ok(Some(id)) = state_tree.lookup_id(address) {
+            addresses.insert(Address::new_id(id));
+        }
+        if let Ok(key) = resolve_to_key_addr(&state_tree, store, address) {
+            addresses.insert(key);
+        }
+    }
+    addresses
+}
+
+/// Returns the messages of the tipsets from epoch `from` to `to`, the head by
+/// default, that are sent from or to the address, from the most recent one.
+/// The chain index skips the tipsets above the range, rather than loading
+/// each of them. Without `from`, the export stops at the first tipset whose
+/// messages or parents aren't in the archive, as below the depth of a lite
+/// snapshot.
+fn export_messages<DB: Blockstore>(
+    db: &DB,
+    roots: &[Cid],
+    address: &Address,
+    from: Option<ChainEpoch>,
+    to: Option<ChainEpoch>,
+) -> anyhow::Result<Vec<MessageRecord>> {
+    let earliest = from.is_none();
+    let from = from.unwrap_or(0);
+    let index = ChainIndex::new(Arc::new(Mutex::new(LruCache::new(TIPSET_CACHE_SIZE))), db);
+    let head = index.load_tipset(&TipsetKeys::new(roots.to_vec()))?;
+    let addresses = address_forms(db, &head, address);
+    // The receipts of the messages of a tipset are in its child
+    let (mut tipset, mut child) = match to {
+        Some(to) if to < head.epoch() => {
+            let child = index.get_tipset_by_height(head, to + 1)?;
+            (index.load_tipset(child.parents())?, Some(child))
+        }
+        _ => (head, None),
+    };
+    let mut records = Vec::new();
+    while tipset.epoch() >= from {
+        if earliest && !all_in_store(db, tipset.blocks().iter().map(|block| block.messages()))? {
+            break;
+        }
+        let messages = messages_for_tipset(db, &tipset)?;
+        for (i, message) in messages.iter().enumerate() {
+            if !addresses.contains(&message.from()) && !addresses.contains(&message.to()) {
+                continue;
+            }
+            let receipt = match &child {
+                Some(child) => get_parent_reciept(db, child.min_ticket_block(), i)?,
+                None => None,
+            };
+            records.push(MessageRecord::new(tipset.epoch(), message, receipt)?);
+        }
+        if tipset.epoch() == 0 || (earliest && !all_in_store(db, tipset.parents().cids())?) {
+            break;
+        }
+        let parent = index.load_tipset(tipset.parents()).with_context(|| {
+            format!(
+                "the parent of epoch {} isn't in the archive",
+                tipset.epoch()
+            )
+        })?;
+        child = Some(std::mem::replace(&mut tipset, parent));
+    }
+    Ok(records)
+}
+
+fn all_in_store<'a>(
+    db: &impl Blockstore,
+    mut cids: impl Iterator<Item = &'a Cid>,
+) -> anyhow::Result<bool> {
+    cids.try_fold(true, |all, cid| Ok(all && db.has(cid)?))
+}
+
+#[derive(Debug, Default)]
+struct ArchiveInfo {
+    car_version: u64,
+    compressed: bool,
+    file_size: u64,
+    roots: Vec<Cid>,
+    block_count: u64,
+    /// Size of the keys and values stored in the database on import
+    import_size: u64,
+    /// Number of blocks in the index of `.forest.car.zst` archives
+    indexed_blocks: Option<u64>,
+    header_count: u64,
+    header_epochs: Option<RangeInclusive<ChainEpoch>>,
+    /// Epochs of the headers whose state root is in the archive
+    state_epochs: Option<RangeInclusive<ChainEpoch>>,
+}
+
+impl ArchiveInfo {
+    /// Reads the uncompressed archive to its end, keeping only the
+    /// headers' epochs and state roots in memory
+    async fn read(reader: impl AsyncRead + Send + Unpin) -> anyhow::Result<Self> {
+        let (car_version, reader) = car_v1_payload(reader).await?;
+        let mut car_reader = CarReader::new(reader).await?;
+        let mut info = ArchiveInfo {
+            car_version,
+            roots: car_reader.header.roots.clone(),
+            ..Default::default()
+        };
+
+        // The state roots are few, one per tipset. Exports write them after
+        // their header, the blocks written before that look like a state root
+        // are kept too.
+        let mut header_state_roots: HashMap<Cid, ChainEpoch> = HashMap::default();
+        let mut state_roots = HashSet::default();
+        while let Some(block) = car_reader.next_block().await? {
+            info.block_count += 1;
+            info.import_size += (block.cid.encoded_len() + block.data.len()) as u64;
+            if header_state_roots.contains_key(&block.cid) || may_be_state_root(&block.data) {
+                state_roots.insert(block.cid);
+            }
+            if !may_be_block_header(&block.data) {
+                continue;
+            }
+            if let Ok(header) = BlockHeader::unmarshal_cbor(&block.data) {
+                info.header_count += 1;
+                info.header_epochs = Some(extend(info.header_epochs.take(), header.epoch()));
+                header_state_roots.insert(*header.state_root(), header.epoch());
+            }
+        }
+        for (state_root, epoch) in header_state_roots {
+            if state_roots.contains(&state_root) {
+                info.state_epochs = Some(extend(info.state_epochs.take(), epoch));
+            }
+        }
+        Ok(info)
+    }
+}
+
+impl fmt::Display for ArchiveInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let format_epochs = |epochs: &Option<RangeInclusive<ChainEpoch>>| match epochs {
+            Some(epochs) => format!("{}..={}", epochs.start(), epochs.end()),
+            None => "none".to_string(),
+        };
+        writeln!(f, "CAR version:           {}", self.car_version)?;
+        writeln!(
+            f,
+            "Compression:           {}",
+            if self.compressed { "zstd" } else { "none" }
+        )?;
+        writeln!(
+            f,
+            "File size:             {}",
+            self.file_size.human_count_bytes()
+        )?;
+        writeln!(f, "Roots:                 {}", self.roots.len())?;
+        for root in self.roots.iter() {
+            writeln!(f, "  {root}")?;
+        }
+        writeln!(f, "Blocks:                {}", self.block_count)?;
+        writeln!(
+            f,
+            "Indexed blocks:        {}",
+            self.indexed_blocks
+                .map(|count| count.to_string())
+                .unwrap_or_else(|| "no index".to_string())
+        )?;
+        writeln!(f, "Block headers:         {}", self.header_count)?;
+        writeln!(
+            f,
+            "Header epochs:         {}",
+            format_epochs(&self.header_epochs)
+        )?;
+        writeln!(
+            f,
+            "State root epochs:     {}",
+            format_epochs(&self.state_epochs)
+        )?;
+        write!(
+            f,
+            "Estimated import size: {}",
+            self.import_size.human_count_bytes()
+        )
+    }
+}
+
+fn extend(
+    range: Option<RangeInclusive<ChainEpoch>>,
+    epoch: ChainEpoch,
+) -> RangeInclusive<ChainEpoch> {
+    match range {
+        Some(range) => *range.start().min(&epoch)..=*range.end().max(&epoch),
+        None => epoch..=epoch,
+    }
+}
+
+/// Block headers are CBOR arrays of 16 items
+fn may_be_block_header(data: &[u8]) -> bool {
+    data.first() == Some(&0x90)
+}
+
+/// State roots, since actors v2, are CBOR arrays of a version and two links
+fn may_be_state_root(data: &[u8]) -> bool {
+    matches!(data, [0x83, version, 0xd8, 0x2a, ..] if *version <= 0x17)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::genesis::EXPORT_SR_40;
+    use crate::utils::car::{CARV2_HEADER_LEN, CARV2_PRAGMA};
+    use futures::io::Cursor;
+
+    #[tokio::test]
+    async fn read_carv1() {
+        let info = ArchiveInfo::read(EXPORT_SR_40).await.unwrap();
+        assert_eq!(info.car_version, 1);
+        assert_eq!(info.roots.len(), 1);
+        assert_eq!(info.header_count, 1);
+        assert_eq!(info.header_epochs, Some(0..=0));
+    }
+
+    #[tokio::test]
+    async fn read_carv2() {
+        let padding = 7;
+        let mut carv2 = CARV2_PRAGMA.to_vec();
+        carv2.extend([0; 16]);
+        let data_offset = (CARV2_PRAGMA.len() + CARV2_HEADER_LEN + padding) as u64;
+        carv2.extend(data_offset.to_le_bytes());
+        carv2.extend((EXPORT_SR_40.len() as u64).to_le_bytes());
+        carv2.extend(0u64.to_le_bytes());
+        carv2.extend(vec![0; padding]);
+        carv2.extend(EXPORT_SR_40);
+        // Index, not read
+        carv2.extend([0xff; 8]);
+
+        let v1 = ArchiveInfo::read(EXPORT_SR_40).await.unwrap();
+        let v2 = ArchiveInfo::read(Cursor::new(carv2)).await.unwrap();
+        assert_eq!(v2.car_version, 2);
+        assert_eq!(v2.roots, v1.roots);
+        assert_eq!(v2.block_count, v1.block_count);
+        assert_eq!(v2.import_size, v1.import_size);
+    }
+
+    #[test]
+    fn export_starts_at_the_earliest_messages() {
+        let db = crate::db::MemoryDB::default();
+        let mut generator = crate::test_utils::ChainGenerator::new();
+        let genesis = generator.genesis().clone();
+        let chain = generator.extend(&genesis, &[1000], 3);
+        for tipset in &chain {
+            crate::chain::persist_objects(&db, tipset.blocks()).unwrap();
+        }
+        let head = chain.last().unwrap().key().cids().to_vec();
+        let address = Address::new_id(100);
+
+        // The generated headers link to messages that aren't in the store
+        assert!(export_messages(&db, &head, &address, None, None)
+            .unwrap()
+            .is_empty());
+        assert!(export_messages(&db, &head, &address, Some(1), None).is_err());
+    }
+
+    #[test]
+    fn csv_rows_match_the_header() {
+        let record = MessageRecord {
+            epoch: 10,
+            cid: Cid::default().to_string(),
+            from: Address::new_id(100).to_string(),
+            to: Address::new_id(101).to_string(),
+            nonce: 1,
+            value: "1000".into(),
+            method: 0,
+            gas_limit: 1000000,
+            gas_fee_cap: "100".into(),
+            gas_premium: "10".into(),
+            exit_code: None,
+            gas_used: None,
+            return_data: None,
+        };
+        let row = record.csv_row();
+        assert_eq!(
+            row.split(',').count(),
+            MessageRecord::CSV_HEADER.split(',').count()
+        );
+        assert!(row.starts_with("10,"));
+        assert!(row.ends_with(",,,"));
+    }
+}
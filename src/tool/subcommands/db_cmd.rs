@@ -0,0 +1,301 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Consistency checks of the database of a node, run while the node is
+//! stopped. After a crash or a partial import the chain may reference blocks
+//! that never made it to the store, which the node only finds out about when
+//! it needs them.
+
+use std::fmt;
+
+use crate::blocks::{BlockHeader, TipsetKeys, TxMeta};
+use crate::cli_shared::{chain_path, cli::CliOpts};
+use crate::db::db_engine::{db_root, open_proxy_db};
+use crate::shim::clock::ChainEpoch;
+use crate::utils::db::file_backed_obj::FileBackedObject;
+use anyhow::{bail, Context};
+use cid::Cid;
+use clap::Subcommand;
+use fvm_ipld_blockstore::Blockstore;
+use fvm_ipld_encoding::CborStore;
+
+#[derive(Debug, Subcommand)]
+pub enum DBCommands {
+    /// Walk from the head of the chain back to genesis, checking that every
+    /// block header, message root and, optionally, state root it references
+    /// is in the database. Reports the first missing one.
+    VerifyLinks {
+        /// A TOML file containing the configuration of the node
+        #[arg(short, long)]
+        config: Option<String>,
+        /// The chain of the database, that of the configuration by default
+        #[arg(long)]
+        chain: Option<String>,
+        /// Check the state roots as well. Only the roots are looked up, not
+        /// the whole state trees.
+        #[arg(long)]
+        state_roots: bool,
+        /// Check the message and state roots of the given number of epochs
+        /// below the head only, the headers being checked down to genesis
+        /// regardless. Snapshots only carry the messages and states of their
+        /// most recent epochs.
+        #[arg(long)]
+        depth: Option<ChainEpoch>,
+    },
+}
+
+impl DBCommands {
+    pub fn run(&self) -> anyhow::Result<()> {
+        match self {
+            Self::VerifyLinks {
+                config,
+                chain,
+                state_roots,
+                depth,
+            } => {
+                let opts = CliOpts {
+                    config: config.clone(),
+                    chain: chain.clone(),
+                    ..Default::default()
+                };
+                let (config, _) = opts.to_config()?;
+                let chain_data_path = chain_path(&config);
+                let head_path = chain_data_path.join("HEAD");
+                let head = TipsetKeys::deserialize(
+                    &std::fs::read(&head_path)
+                        .with_context(|| format!("failed to read {}", head_path.display()))?,
+                )?;
+                let db = open_proxy_db(db_root(&chain_data_path), config.db_config().clone())?;
+
+                let report = verify_links(
+                    &db,
+                    &head,
+                    &VerifyOptions {
+                        state_roots: *state_roots,
+                        depth: *depth,
+                    },
+                )?;
+                println!("{report}");
+                if report.missing.is_some() {
+                    bail!("the chain is missing blocks");
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct VerifyOptions {
+    pub state_roots: bool,
+    pub depth: Option<ChainEpoch>,
+}
+
+/// What a missing CID is in the chain
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkKind {
+    Header,
+    Messages,
+    StateRoot,
+}
+
+/// A CID referenced by the chain that isn't in the store
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MissingLink {
+    pub kind: LinkKind,
+    pub cid: Cid,
+    /// Epoch of the block that references the CID, the head having none
+    pub epoch: Option<ChainEpoch>,
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct VerifyReport {
+    /// Epoch of the head of the chain, if it could be loaded
+    pub head_epoch: Option<ChainEpoch>,
+    pub headers: usize,
+    pub message_roots: usize,
+    pub state_roots: usize,
+    /// First missing CID, walking down from the head
+    pub missing: Option<MissingLink>,
+}
+
+impl fmt::Display for VerifyReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Checked {} headers, {} message roots and {} state roots",
+            self.headers, self.message_roots, self.state_roots
+        )?;
+        match &self.missing {
+            None => write!(
+                f,
+                "All the links from epoch {} down to genesis resolve",
+                self.head_epoch.unwrap_or_default()
+            ),
+            Some(MissingLink { kind, cid, epoch }) => {
+                write!(f, "Missing {kind:?} {cid}")?;
+                match epoch {
+                    Some(epoch) => write!(f, ", referenced at epoch {epoch}"),
+                    None => write!(f, " of the head"),
+                }
+            }
+        }
+    }
+}
+
+/// Walks the chain from the tipset `head` down to genesis and checks that the
+/// CIDs it references resolve in the store, stopping at the first one that
+/// doesn't.
+pub fn verify_links(
+    db: &impl Blockstore,
+    head: &TipsetKeys,
+    options: &VerifyOptions,
+) -> anyhow::Result<VerifyReport> {
+    let mut report = VerifyReport::default();
+    let mut keys = head.clone();
+    // Epoch of the tipset whose parents are being checked
+    let mut child_epoch = None;
+    loop {
+        let mut headers = Vec::with_capacity(keys.cids().len());
+        for cid in keys.cids() {
+            match db.get_cbor::<BlockHeader>(cid)? {
+                Some(header) => headers.push(header),
+                None => {
+                    report.missing = Some(MissingLink {
+                        kind: LinkKind::Header,
+                        cid: *cid,
+                        epoch: child_epoch,
+                    });
+                    return Ok(report);
+                }
+            }
+        }
+        report.headers += headers.len();
+        let Some(first) = headers.first() else {
+            bail!("empty tipset key below epoch {child_epoch:?}");
+        };
+        let epoch = first.epoch();
+        let head_epoch = *report.head_epoch.get_or_insert(epoch);
+
+        if options
+            .depth
+            .map_or(true, |depth| head_epoch - epoch < depth)
+        {
+            for header in &headers {
+                if let Some(cid) = missing_messages(db, header.messages())? {
+                    report.missing = Some(MissingLink {
+                        kind: LinkKind::Messages,
+                        cid,
+                        epoch: Some(epoch),
+                    });
+                    return Ok(report);
+                }
+                report.message_roots += 1;
+            }
+            if options.state_roots {
+                // The blocks of a tipset share their parent state
+                let state_root = first.state_root();
+                if !db.has(state_root)? {
+                    report.missing = Some(MissingLink {
+                        kind: LinkKind::StateRoot,
+                        cid: *state_root,
+                        epoch: Some(epoch),
+                    });
+                    return Ok(report);
+                }
+                report.state_roots += 1;
+            }
+        }
+
+        // The parents of genesis aren't blocks
+        if epoch == 0 {
+            return Ok(report);
+        }
+        keys = first.parents().clone();
+        child_epoch = Some(epoch);
+    }
+}
+
+/// Returns the first CID of the messages of a block that isn't in the store:
+/// the root of the messages or one of the roots of the BLS and `secp256k1`
+/// messages.
+fn missing_messages(db: &impl Blockstore, messages: &Cid) -> anyhow::Result<Option<Cid>> {
+    let Some(meta) = db.get_cbor::<TxMeta>(messages)? else {
+        return Ok(Some(*messages));
+    };
+    for root in [meta.bls_message_root, meta.secp_message_root] {
+        if !db.has(&root)? {
+            return Ok(Some(root));
+        }
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chain::persist_objects;
+    use crate::db::MemoryDB;
+    use crate::test_utils::ChainGenerator;
+
+    #[test]
+    fn walks_down_to_genesis() {
+        let db = MemoryDB::default();
+        let mut generator = ChainGenerator::new();
+        let genesis = generator.genesis().clone();
+        let tipsets = generator.extend(&genesis, &[1000, 1001], 10);
+        persist_objects(&db, genesis.blocks()).unwrap();
+        for tipset in &tipsets {
+            persist_objects(&db, tipset.blocks()).unwrap();
+        }
+        let head = tipsets.last().unwrap().key();
+
+        // The generated blocks have no messages
+        let options = VerifyOptions {
+            depth: Some(0),
+            ..Default::default()
+        };
+        let report = verify_links(&db, head, &options).unwrap();
+        assert_eq!(report.missing, None);
+        assert_eq!(report.head_epoch, Some(10));
+        assert_eq!(report.headers, 21);
+        assert_eq!(report.message_roots, 0);
+
+        let report = verify_links(&db, head, &VerifyOptions::default()).unwrap();
+        assert_eq!(
+            report.missing,
+            Some(MissingLink {
+                kind: LinkKind::Messages,
+                cid: *tipsets[9].blocks()[0].messages(),
+                epoch: Some(10),
+            })
+        );
+    }
+
+    #[test]
+    fn reports_first_missing_header() {
+        let db = MemoryDB::default();
+        let mut generator = ChainGenerator::new();
+        let genesis = generator.genesis().clone();
+        let tipsets = generator.extend(&genesis, &[1000], 10);
+        persist_objects(&db, genesis.blocks()).unwrap();
+        for tipset in tipsets.iter().filter(|ts| ts.epoch() != 4) {
+            persist_objects(&db, tipset.blocks()).unwrap();
+        }
+
+        let options = VerifyOptions {
+            depth: Some(0),
+            ..Default::default()
+        };
+        let report = verify_links(&db, tipsets[9].key(), &options).unwrap();
+        assert_eq!(report.headers, 6);
+        assert_eq!(
+            report.missing,
+            Some(MissingLink {
+                kind: LinkKind::Header,
+                cid: *tipsets[3].blocks()[0].cid(),
+                epoch: Some(5),
+            })
+        );
+    }
+}
@@ -0,0 +1,111 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+mod archive_cmd;
+mod db_cmd;
+mod shed_cmd;
+mod snapshot_cmd;
+mod state_cmd;
+mod test_vectors_cmd;
+
+use std::path::Path;
+
+use crate::cli_shared::cli::HELP_MESSAGE;
+use crate::utils::car::{
+    forest::{ForestCarFooter, ForestCarReader},
+    IndexedCarReader,
+};
+use crate::utils::version::FOREST_VERSION_STRING;
+use cid::Cid;
+use clap::Parser;
+use fvm_ipld_blockstore::Blockstore;
+
+use self::archive_cmd::ArchiveCommands;
+use self::db_cmd::DBCommands;
+use self::shed_cmd::ShedCommands;
+use self::snapshot_cmd::SnapshotCommands;
+use self::state_cmd::StateCommands;
+use self::test_vectors_cmd::TestVectorsCommands;
+
+/// Command-line tools working on Forest files, without a running node
+#[derive(Parser)]
+#[command(name = "forest-tool", author = env!("CARGO_PKG_AUTHORS"), version = FOREST_VERSION_STRING.as_str(), about = "Tools for the files used by Forest, working without a running node")]
+#[command(help_template(HELP_MESSAGE))]
+pub struct Cli {
+    #[command(subcommand)]
+    pub cmd: Subcommand,
+}
+
+/// forest-tool sub-commands available.
+#[derive(clap::Subcommand)]
+pub enum Subcommand {
+    /// Inspect CAR archives
+    #[command(subcommand)]
+    Archive(ArchiveCommands),
+    /// Validate snapshots
+    #[command(subcommand)]
+    Snapshot(SnapshotCommands),
+
+    /// Inspect the state trees in CAR archives
+    #[command(subcommand)]
+    State(StateCommands),
+
+    /// Debugging tools for the actor states
+    #[command(subcommand)]
+    Shed(ShedCommands),
+
+    /// Run the Filecoin conformance test vectors
+    #[command(subcommand)]
+    TestVectors(TestVectorsCommands),
+
+    /// Check the database of a stopped node
+    #[command(subcommand)]
+    DB(DBCommands),
+}
+
+/// Read-only blockstore over an indexed archive
+enum Archive {
+    ForestCar(ForestCarReader),
+    IndexedCar(IndexedCarReader),
+}
+
+impl Archive {
+    fn roots(&self) -> &[Cid] {
+        match self {
+            Archive::ForestCar(car) => car.roots(),
+            Archive::IndexedCar(car) => car.roots(),
+        }
+    }
+}
+
+impl Blockstore for Archive {
+    fn get(&self, k: &Cid) -> anyhow::Result<Option<Vec<u8>>> {
+        match self {
+            Archive::ForestCar(car) => car.get(k),
+            Archive::IndexedCar(car) => car.get(k),
+        }
+    }
+
+    fn has(&self, k: &Cid) -> anyhow::Result<bool> {
+        match self {
+            Archive::ForestCar(car) => car.has(k),
+            Archive::IndexedCar(car) => car.has(k),
+        }
+    }
+
+    fn put_keyed(&self, k: &Cid, block: &[u8]) -> anyhow::Result<()> {
+        match self {
+            Archive::ForestCar(car) => car.put_keyed(k, block),
+            Archive::IndexedCar(car) => car.put_keyed(k, block),
+        }
+    }
+}
+
+/// Opens a `.forest.car.zst` archive or an indexed CARv2 file
+fn open_archive(path: &Path) -> anyhow::Result<Archive> {
+    if ForestCarFooter::read(&std::fs::File::open(path)?)?.is_some() {
+        Ok(Archive::ForestCar(ForestCarReader::open(path)?))
+    } else {
+        Ok(Archive::IndexedCar(IndexedCarReader::open(path)?))
+    }
+}
@@ -0,0 +1,169 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use std::{fmt::Display, path::PathBuf};
+
+use crate::blocks::{header::json::BlockHeaderJson, BlockHeader};
+use crate::ipld::{json::IpldJsonRef, Ipld};
+use crate::json::{
+    message::json::MessageJson, message_receipt::json::ReceiptJson,
+    signed_message::json::SignedMessageJson,
+};
+use crate::message::SignedMessage;
+use crate::shim::{executor::Receipt, message::Message};
+use crate::statediff::collections::{diff_amts, diff_bitfields, diff_hamts, Change};
+use base64::{prelude::BASE64_STANDARD, Engine};
+use cid::Cid;
+use clap::{Subcommand, ValueEnum};
+use fvm_ipld_bitfield::BitField;
+use fvm_ipld_encoding::{from_slice, to_vec, Cbor};
+
+use super::open_archive;
+
+/// Debugging tools for the actor states
+#[derive(Debug, Subcommand)]
+pub enum ShedCommands {
+    /// Print the entries added, removed and modified between two HAMTs of an
+    /// archive
+    DiffHamt {
+        /// Path to the `.forest.car.zst` archive or indexed CARv2 file
+        archive: PathBuf,
+        /// Root of the HAMT before the changes
+        pre: Cid,
+        /// Root of the HAMT after the changes
+        post: Cid,
+    },
+    /// Print the entries added, removed and modified between two AMTs of an
+    /// archive
+    DiffAmt {
+        /// Path to the `.forest.car.zst` archive or indexed CARv2 file
+        archive: PathBuf,
+        /// Root of the AMT before the changes
+        pre: Cid,
+        /// Root of the AMT after the changes
+        post: Cid,
+    },
+    /// Print the bits set and unset between two RLE+ bitfields
+    DiffBitfield {
+        /// Bitfield before the changes, RLE+ encoded in base64
+        pre: String,
+        /// Bitfield after the changes, RLE+ encoded in base64
+        post: String,
+    },
+    /// Print the JSON, as served by the API, of a hex encoded CBOR structure
+    Decode {
+        #[arg(value_enum)]
+        kind: CodecKind,
+        /// CBOR of the structure, hex encoded
+        cbor_hex: String,
+    },
+    /// Print the hex encoded CBOR, and the CID, of a structure given in the
+    /// JSON served by the API
+    Encode {
+        #[arg(value_enum)]
+        kind: CodecKind,
+        /// JSON of the structure
+        json: String,
+    },
+}
+
+/// Chain structures with a CBOR and a JSON encoding
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum CodecKind {
+    Header,
+    Message,
+    SignedMessage,
+    Receipt,
+}
+
+impl ShedCommands {
+    pub fn run(&self) -> anyhow::Result<()> {
+        match self {
+            Self::DiffHamt { archive, pre, post } => {
+                let store = open_archive(archive)?;
+                for change in diff_hamts::<Ipld, _>(&store, pre, post)? {
+                    print_change(change, hex::encode)?;
+                }
+                Ok(())
+            }
+            Self::DiffAmt { archive, pre, post } => {
+                let store = open_archive(archive)?;
+                for change in diff_amts::<Ipld, _>(&store, pre, post)? {
+                    print_change(change, |index| index)?;
+                }
+                Ok(())
+            }
+            Self::DiffBitfield { pre, post } => {
+                let decode = |bitfield: &str| -> anyhow::Result<BitField> {
+                    Ok(BitField::from_bytes(&BASE64_STANDARD.decode(bitfield)?)?)
+                };
+                let diff = diff_bitfields(&decode(pre)?, &decode(post)?);
+                println!("+ {:?}", diff.added.iter().collect::<Vec<_>>());
+                println!("- {:?}", diff.removed.iter().collect::<Vec<_>>());
+                Ok(())
+            }
+            Self::Decode { kind, cbor_hex } => {
+                let bytes = hex::decode(cbor_hex.trim())?;
+                let json = match kind {
+                    CodecKind::Header => {
+                        serde_json::to_string_pretty(&BlockHeaderJson(from_slice(&bytes)?))?
+                    }
+                    CodecKind::Message => {
+                        serde_json::to_string_pretty(&MessageJson(from_slice(&bytes)?))?
+                    }
+                    CodecKind::SignedMessage => {
+                        serde_json::to_string_pretty(&SignedMessageJson(from_slice(&bytes)?))?
+                    }
+                    CodecKind::Receipt => {
+                        serde_json::to_string_pretty(&ReceiptJson(from_slice(&bytes)?))?
+                    }
+                };
+                println!("{json}");
+                Ok(())
+            }
+            Self::Encode { kind, json } => {
+                // Receipts aren't blocks of their own, but entries of an AMT
+                let (bytes, cid) = match kind {
+                    CodecKind::Header => {
+                        let BlockHeaderJson(header) = serde_json::from_str(json)?;
+                        (to_vec::<BlockHeader>(&header)?, Some(*header.cid()))
+                    }
+                    CodecKind::Message => {
+                        let MessageJson(message) = serde_json::from_str(json)?;
+                        (to_vec::<Message>(&message)?, Some(message.cid()?))
+                    }
+                    CodecKind::SignedMessage => {
+                        // The CID of a BLS signed message is the one of its
+                        // unsigned message
+                        let SignedMessageJson(message) = serde_json::from_str(json)?;
+                        (to_vec::<SignedMessage>(&message)?, Some(message.cid()?))
+                    }
+                    CodecKind::Receipt => {
+                        let ReceiptJson(receipt) = serde_json::from_str(json)?;
+                        (to_vec::<Receipt>(&receipt)?, None)
+                    }
+                };
+                println!("{}", hex::encode(bytes));
+                if let Some(cid) = cid {
+                    println!("{cid}");
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+fn print_change<K, D: Display>(
+    change: Change<K, Ipld>,
+    format_key: impl Fn(K) -> D,
+) -> anyhow::Result<()> {
+    let json = |value: &Ipld| serde_json::to_string(&IpldJsonRef(value));
+    match change {
+        Change::Added(key, value) => println!("+ {}: {}", format_key(key), json(&value)?),
+        Change::Removed(key, value) => println!("- {}: {}", format_key(key), json(&value)?),
+        Change::Modified { key, pre, post } => {
+            println!("~ {}: {} -> {}", format_key(key), json(&pre)?, json(&post)?)
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,237 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Offline validation of snapshots: the completeness of their recent blocks,
+//! their genesis, and the states they hold, executed again.
+
+use std::{num::NonZeroUsize, path::PathBuf, sync::Arc};
+
+use crate::blocks::{BlockHeader, Tipset, TipsetKeys};
+use crate::chain::{ChainIndex, ChainStore};
+use crate::cli_shared::cli::Config;
+use crate::daemon::bundle::load_bundles;
+use crate::db::MemoryDB;
+use crate::interpreter::RewardActorMessageCalc;
+use crate::ipld::walk_snapshot;
+use crate::networks::{ChainConfig, NetworkChain};
+use crate::state_manager::StateManager;
+use crate::utils::proofs_api::paramfetch::ensure_params_downloaded;
+use anyhow::{ensure, Context};
+use cid::Cid;
+use clap::Subcommand;
+use fvm_ipld_blockstore::Blockstore;
+use lru::LruCache;
+use nonzero_ext::nonzero;
+use parking_lot::Mutex;
+use tempfile::TempDir;
+
+use super::{open_archive, Archive};
+
+const TIPSET_CACHE_SIZE: NonZeroUsize = nonzero!(1024usize);
+
+#[derive(Debug, Subcommand)]
+pub enum SnapshotCommands {
+    /// Validate a `.forest.car.zst` archive or an indexed CARv2 snapshot
+    Validate {
+        /// Path to the snapshot
+        snapshot: PathBuf,
+        /// Number of recent epochs whose messages and states must be complete
+        #[arg(long, default_value_t = 2000)]
+        check_links: u32,
+        /// Network the genesis of the snapshot must be the one of, e.g.
+        /// `mainnet` or `calibnet`
+        #[arg(long)]
+        check_network: Option<NetworkChain>,
+        /// Number of tipsets, spread over the `--check-links` epochs, executed
+        /// again to compare their resulting state roots to those of the
+        /// headers
+        #[arg(long, default_value_t = 0)]
+        check_stateroots: u32,
+    },
+}
+
+impl SnapshotCommands {
+    pub async fn run(&self) -> anyhow::Result<()> {
+        match self {
+            Self::Validate {
+                snapshot,
+                check_links,
+                check_network,
+                check_stateroots,
+            } => {
+                let store = ArchiveOverlay::new(open_archive(snapshot)?);
+                let index = ChainIndex::new(
+                    Arc::new(Mutex::new(LruCache::new(TIPSET_CACHE_SIZE))),
+                    store.clone(),
+                );
+                let head = index.load_tipset(&TipsetKeys::new(store.archive.roots().to_vec()))?;
+                println!("Head at epoch {}", head.epoch());
+
+                check_links_of(&store, &head, *check_links).await?;
+                println!("Blocks complete over the last {check_links} epochs");
+
+                let genesis = index.get_tipset_by_height(head.clone(), 0)?;
+                let genesis = genesis.min_ticket_block();
+                let network = match check_network {
+                    Some(network) => {
+                        check_genesis(genesis, network)?;
+                        println!("Genesis of {network}");
+                        Some(network.clone())
+                    }
+                    None => [NetworkChain::Mainnet, NetworkChain::Calibnet]
+                        .into_iter()
+                        .find(|network| check_genesis(genesis, network).is_ok()),
+                };
+
+                if *check_stateroots > 0 {
+                    let network = network.context(
+                        "the network of the snapshot is unknown, pass --check-network to execute its tipsets",
+                    )?;
+                    check_state_roots(
+                        store,
+                        head,
+                        genesis,
+                        &network,
+                        *check_links,
+                        *check_stateroots,
+                    )
+                    .await?;
+                    println!("{check_stateroots} state roots computed again");
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Blockstore reading from an archive, keeping the blocks written in memory,
+/// e.g. the actor bundles or the states computed on top of the archive.
+#[derive(Clone)]
+struct ArchiveOverlay {
+    archive: Arc<Archive>,
+    written: MemoryDB,
+}
+
+impl ArchiveOverlay {
+    fn new(archive: Archive) -> Self {
+        Self {
+            archive: Arc::new(archive),
+            written: MemoryDB::default(),
+        }
+    }
+}
+
+impl Blockstore for ArchiveOverlay {
+    fn get(&self, k: &Cid) -> anyhow::Result<Option<Vec<u8>>> {
+        match self.written.get(k)? {
+            Some(block) => Ok(Some(block)),
+            None => self.archive.get(k),
+        }
+    }
+
+    fn has(&self, k: &Cid) -> anyhow::Result<bool> {
+        Ok(self.written.has(k)? || self.archive.has(k)?)
+    }
+
+    fn put_keyed(&self, k: &Cid, block: &[u8]) -> anyhow::Result<()> {
+        self.written.put_keyed(k, block)
+    }
+}
+
+/// Checks that the headers down to the genesis are in the snapshot, and the
+/// messages and states of the last `epochs` epochs, as exported by Forest.
+async fn check_links_of(store: &ArchiveOverlay, head: &Tipset, epochs: u32) -> anyhow::Result<()> {
+    walk_snapshot(
+        head,
+        epochs.into(),
+        |cid| {
+            let store = store.clone();
+            async move {
+                store
+                    .get(&cid)?
+                    .with_context(|| format!("{cid} is missing from the snapshot"))
+            }
+        },
+        Some("Checking links | blocks "),
+        None,
+        None,
+    )
+    .await?;
+    Ok(())
+}
+
+fn check_genesis(genesis: &BlockHeader, network: &NetworkChain) -> anyhow::Result<()> {
+    let expected = ChainConfig::from_chain(network)
+        .genesis_cid
+        .with_context(|| format!("the genesis of {network} isn't known"))?;
+    let actual = genesis.cid().to_string();
+    ensure!(
+        actual == expected,
+        "the genesis {actual} of the snapshot isn't the one of {network}, {expected}"
+    );
+    Ok(())
+}
+
+/// Executes `samples` tipsets, evenly spread over the last `epochs` epochs,
+/// and checks that their state roots and receipts are those of the headers of
+/// their children.
+async fn check_state_roots(
+    store: ArchiveOverlay,
+    head: Arc<Tipset>,
+    genesis: &BlockHeader,
+    network: &NetworkChain,
+    epochs: u32,
+    samples: u32,
+) -> anyhow::Result<()> {
+    let chain_config = Arc::new(ChainConfig::from_chain(network));
+    let config = Config {
+        chain: chain_config.clone(),
+        ..Default::default()
+    };
+    load_bundles(head.epoch(), &config, store.clone()).await?;
+    ensure_params_downloaded().await?;
+
+    let chain_data_root = TempDir::new()?;
+    let chain_store = Arc::new(ChainStore::new(
+        store,
+        chain_config.clone(),
+        genesis,
+        chain_data_root.path(),
+    )?);
+    let state_manager = Arc::new(StateManager::new(
+        chain_store.clone(),
+        chain_config,
+        Arc::new(RewardActorMessageCalc),
+    )?);
+
+    // The parent states of these tipsets are in the snapshot
+    let lowest_epoch = head.epoch() - epochs as i64;
+    let mut tipsets = Vec::new();
+    let mut child = head;
+    while child.epoch() > 0 {
+        let tipset = chain_store.tipset_from_keys(child.parents())?;
+        if tipset.epoch() <= lowest_epoch {
+            break;
+        }
+        tipsets.push((tipset.clone(), child));
+        child = tipset;
+    }
+    let step = (tipsets.len() / samples as usize).max(1);
+    for (tipset, child) in tipsets.iter().step_by(step).take(samples as usize) {
+        let (state_root, receipt_root) = state_manager.tipset_state(tipset).await?;
+        ensure!(
+            &state_root == child.parent_state(),
+            "the state computed at epoch {} is {state_root}, the header has {}",
+            tipset.epoch(),
+            child.parent_state()
+        );
+        ensure!(
+            &receipt_root == child.min_ticket_block().message_receipts(),
+            "the receipts computed at epoch {} are {receipt_root}, the header has {}",
+            tipset.epoch(),
+            child.min_ticket_block().message_receipts()
+        );
+        println!("Epoch {}: state root {state_root}", tipset.epoch());
+    }
+    Ok(())
+}
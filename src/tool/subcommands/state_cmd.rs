@@ -0,0 +1,74 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use std::{path::PathBuf, str::FromStr};
+
+use crate::blocks::BlockHeader;
+use crate::shim::address::Address;
+use crate::statediff::read_actor_state;
+use anyhow::Context;
+use cid::Cid;
+use clap::Subcommand;
+use fvm_ipld_blockstore::Blockstore;
+use fvm_ipld_encoding::CborStore;
+
+use super::open_archive;
+
+#[derive(Debug, Subcommand)]
+pub enum StateCommands {
+    /// Print the state of an actor as JSON, read from a `.forest.car.zst`
+    /// archive or an indexed CARv2 file
+    ReadActor {
+        /// Path to the archive
+        archive: PathBuf,
+        /// Address of the actor
+        address: String,
+        /// State root to read the actor from, the one of the archive's head
+        /// by default
+        #[arg(long)]
+        state_root: Option<Cid>,
+        /// The depth at which IPLD links, e.g. those of the HAMTs and AMTs of
+        /// the state, are resolved
+        #[arg(short, long, default_value_t = 1)]
+        depth: u64,
+    },
+}
+
+impl StateCommands {
+    pub fn run(&self) -> anyhow::Result<()> {
+        match self {
+            Self::ReadActor {
+                archive,
+                address,
+                state_root,
+                depth,
+            } => {
+                let address = Address::from_str(address)?;
+                let store = open_archive(archive)?;
+                read_actor(&store, store.roots(), *state_root, &address, *depth)
+            }
+        }
+    }
+}
+
+fn read_actor(
+    bs: &impl Blockstore,
+    roots: &[Cid],
+    state_root: Option<Cid>,
+    address: &Address,
+    depth: u64,
+) -> anyhow::Result<()> {
+    let state_root = match state_root {
+        Some(state_root) => state_root,
+        None => {
+            let head = roots.first().context("the archive has no root")?;
+            let header: BlockHeader = bs
+                .get_cbor(head)?
+                .with_context(|| format!("the head {head} isn't in the archive"))?;
+            *header.state_root()
+        }
+    };
+    let state = read_actor_state(bs, &state_root, address, Some(depth))?;
+    println!("{}", serde_json::to_string_pretty(&state)?);
+    Ok(())
+}
@@ -0,0 +1,89 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use std::{
+    fs::File,
+    io::BufReader,
+    path::{Path, PathBuf},
+};
+
+use crate::conformance::{vector::TestVector, Runner, VariantResult};
+use clap::Subcommand;
+use walkdir::WalkDir;
+
+#[derive(Debug, Subcommand)]
+pub enum TestVectorsCommands {
+    /// Execute the `message` and `tipset` test vectors, and report those
+    /// whose state roots or receipts differ from the expected ones
+    Run {
+        /// Test vector, or directory searched for the `.json` test vectors
+        path: PathBuf,
+    },
+}
+
+impl TestVectorsCommands {
+    pub async fn run(&self) -> anyhow::Result<()> {
+        match self {
+            Self::Run { path } => {
+                let runner = Runner::new().await?;
+                let (mut passed, mut failed, mut skipped) = (0, 0, 0);
+                for file in vector_files(path)? {
+                    let name = file.display();
+                    let outcomes = match read_vector(&file) {
+                        Ok(vector) => runner.run(&vector).await,
+                        Err(e) => Err(e),
+                    };
+                    let outcomes = match outcomes {
+                        Ok(outcomes) => outcomes,
+                        Err(e) => {
+                            println!("FAIL {name}: {e}");
+                            failed += 1;
+                            continue;
+                        }
+                    };
+                    for outcome in outcomes {
+                        let variant = &outcome.variant;
+                        match outcome.result {
+                            VariantResult::Passed => {
+                                println!("PASS {name} {variant}");
+                                passed += 1;
+                            }
+                            VariantResult::Failed(mismatches) => {
+                                println!("FAIL {name} {variant}");
+                                for mismatch in mismatches {
+                                    println!("    {mismatch}");
+                                }
+                                failed += 1;
+                            }
+                            VariantResult::Skipped(reason) => {
+                                println!("SKIP {name} {variant}: {reason}");
+                                skipped += 1;
+                            }
+                        }
+                    }
+                }
+                println!("{passed} passed, {failed} failed, {skipped} skipped");
+                anyhow::ensure!(failed == 0, "{failed} test vectors failed");
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Returns the `.json` files under the path, in order
+fn vector_files(path: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for entry in WalkDir::new(path).sort_by_file_name() {
+        let entry = entry?;
+        if entry.file_type().is_file()
+            && entry.path().extension().map_or(false, |ext| ext == "json")
+        {
+            files.push(entry.into_path());
+        }
+    }
+    Ok(files)
+}
+
+fn read_vector(path: &Path) -> anyhow::Result<TestVector> {
+    Ok(serde_json::from_reader(BufReader::new(File::open(path)?))?)
+}
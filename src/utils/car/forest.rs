@@ -0,0 +1,374 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! The `.forest.car.zst` format: a CARv1 archive compressed as a sequence of
+//! independent zstd frames, each holding whole CAR sections, followed by an
+//! index of the blocks stored in zstd skippable frames. Any zstd decoder reads
+//! it as a plain `.car.zst` archive, while Forest can read a single block by
+//! decompressing only the frame holding it.
+//!
+//! Layout:
+//! - data frames, cut once they hold [`FRAME_SIZE`] uncompressed bytes. The
+//!   first one starts with the CAR header.
+//! - index frames, skippable, holding up to [`INDEX_FRAME_ENTRIES`] entries
+//!   sorted by key. An entry is the key of a CID, see [`index_key`], and the
+//!   offset of the data frame holding the block, both as little endian `u64`s.
+//! - the footer, a skippable frame holding the offset of the first index
+//!   frame, the number of entries and [`FOREST_CAR_MAGIC`].
+//!
+//! The output only depends on the CARv1 input, so that the same export always
+//! yields the same archive.
+
+use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+    os::unix::fs::FileExt,
+    path::Path,
+    sync::Arc,
+};
+
+use anyhow::Context;
+use cid::Cid;
+use fvm_ipld_blockstore::Blockstore;
+use fvm_ipld_car::CarHeader;
+use parking_lot::Mutex;
+
+use super::read_varint;
+
+/// Uncompressed size above which a data frame is closed
+pub const FRAME_SIZE: usize = 64 * 1024;
+
+/// Number of index entries per skippable frame, whose size must fit in a
+/// `u32`
+pub const INDEX_FRAME_ENTRIES: u64 = 1 << 24;
+
+/// Last bytes of `.forest.car.zst` archives
+pub const FOREST_CAR_MAGIC: [u8; 8] = *b"FRSTCAR1";
+
+/// Fixed compression level, for the output to be reproducible
+const COMPRESSION_LEVEL: i32 = 3;
+
+// https://github.com/facebook/zstd/blob/dev/doc/zstd_compression_format.md#skippable-frames
+const ZSTD_SKIPPABLE_FRAME_MAGIC: [u8; 4] = [0x50, 0x2a, 0x4d, 0x18];
+const ZSTD_SKIPPABLE_FRAME_HEADER_LEN: u64 = 8;
+
+const INDEX_ENTRY_LEN: u64 = 16;
+const FOOTER_DATA_LEN: usize = 24;
+const FOOTER_LEN: u64 = ZSTD_SKIPPABLE_FRAME_HEADER_LEN + FOOTER_DATA_LEN as u64;
+
+/// Key of a CID in the index. Distinct CIDs may share a key, the block is
+/// then found by reading the frames of all the matching entries.
+pub fn index_key(cid: &Cid) -> u64 {
+    let hash = blake2b_simd::Params::new()
+        .hash_length(8)
+        .hash(&cid.to_bytes());
+    let mut key = [0; 8];
+    key.copy_from_slice(hash.as_bytes());
+    u64::from_le_bytes(key)
+}
+
+/// Writes the CARv1 archive `car_v1` in the `.forest.car.zst` format.
+pub fn write_forest_car(car_v1: impl Read, out: impl Write) -> anyhow::Result<()> {
+    let mut car_v1 = BufReader::new(car_v1);
+    let mut out = BufWriter::new(out);
+    let mut offset = 0;
+    let mut entries = vec![];
+    let mut frame = vec![];
+    let mut frame_cids = vec![];
+    let mut is_header = true;
+    let mut section = vec![];
+    while let Some((len, varint)) = read_varint(&mut car_v1)? {
+        section.resize(len as usize, 0);
+        car_v1.read_exact(&mut section)?;
+        if !is_header {
+            let cid =
+                Cid::read_bytes(section.as_slice()).context("invalid CID in the CAR archive")?;
+            frame_cids.push(index_key(&cid));
+        }
+        is_header = false;
+        frame.extend_from_slice(&varint);
+        frame.extend_from_slice(&section);
+        if frame.len() >= FRAME_SIZE {
+            entries.extend(frame_cids.drain(..).map(|key| (key, offset)));
+            offset += write_frame(&mut out, &mut frame)?;
+        }
+    }
+    anyhow::ensure!(!is_header, "missing CAR header");
+    if !frame.is_empty() {
+        entries.extend(frame_cids.drain(..).map(|key| (key, offset)));
+        offset += write_frame(&mut out, &mut frame)?;
+    }
+
+    entries.sort_unstable();
+    let index_offset = offset;
+    for chunk in entries.chunks(INDEX_FRAME_ENTRIES as usize) {
+        out.write_all(&ZSTD_SKIPPABLE_FRAME_MAGIC)?;
+        out.write_all(&((chunk.len() as u64 * INDEX_ENTRY_LEN) as u32).to_le_bytes())?;
+        for (key, frame_offset) in chunk {
+            out.write_all(&key.to_le_bytes())?;
+            out.write_all(&frame_offset.to_le_bytes())?;
+        }
+    }
+    out.write_all(&ZSTD_SKIPPABLE_FRAME_MAGIC)?;
+    out.write_all(&(FOOTER_DATA_LEN as u32).to_le_bytes())?;
+    out.write_all(&index_offset.to_le_bytes())?;
+    out.write_all(&(entries.len() as u64).to_le_bytes())?;
+    out.write_all(&FOREST_CAR_MAGIC)?;
+    out.flush()?;
+    Ok(())
+}
+
+/// Compresses and writes the frame, returning its compressed size
+fn write_frame(out: &mut impl Write, frame: &mut Vec<u8>) -> io::Result<u64> {
+    let compressed = zstd::bulk::compress(frame, COMPRESSION_LEVEL)?;
+    out.write_all(&compressed)?;
+    frame.clear();
+    Ok(compressed.len() as u64)
+}
+
+/// Location of the index, read from the footer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ForestCarFooter {
+    pub index_offset: u64,
+    pub entry_count: u64,
+}
+
+impl ForestCarFooter {
+    /// Reads the footer of the file, returning `None` if it isn't a
+    /// `.forest.car.zst` archive
+    pub fn read(file: &File) -> io::Result<Option<Self>> {
+        let len = file.metadata()?.len();
+        if len < FOOTER_LEN {
+            return Ok(None);
+        }
+        let mut footer = [0; FOOTER_LEN as usize];
+        file.read_exact_at(&mut footer, len - FOOTER_LEN)?;
+        let (header, data) = footer.split_at(ZSTD_SKIPPABLE_FRAME_HEADER_LEN as usize);
+        if header[..4] != ZSTD_SKIPPABLE_FRAME_MAGIC
+            || header[4..] != (FOOTER_DATA_LEN as u32).to_le_bytes()
+            || data[16..] != FOREST_CAR_MAGIC
+        {
+            return Ok(None);
+        }
+        let read_u64 = |offset: usize| {
+            let mut value = [0; 8];
+            value.copy_from_slice(&data[offset..offset + 8]);
+            u64::from_le_bytes(value)
+        };
+        Ok(Some(Self {
+            index_offset: read_u64(0),
+            entry_count: read_u64(8),
+        }))
+    }
+}
+
+/// Read-only [`Blockstore`] over a `.forest.car.zst` archive. Only the frames
+/// holding the requested blocks are decompressed, the last one being cached.
+pub struct ForestCarReader {
+    file: File,
+    footer: ForestCarFooter,
+    roots: Vec<Cid>,
+    /// Offset and content of the last frame read
+    cache: Mutex<Option<(u64, Arc<Vec<u8>>)>>,
+}
+
+impl ForestCarReader {
+    pub fn open(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let file = File::open(path.as_ref())?;
+        let footer = ForestCarFooter::read(&file)?.context("not a .forest.car.zst archive")?;
+        let reader = Self {
+            file,
+            footer,
+            roots: vec![],
+            cache: Mutex::new(None),
+        };
+        let frame = reader.frame(0)?;
+        let mut first = frame.as_slice();
+        let (len, _) = read_varint(&mut first)?.context("missing CAR header")?;
+        let header: CarHeader = fvm_ipld_encoding::from_slice(
+            first.get(..len as usize).context("truncated CAR header")?,
+        )?;
+        Ok(Self {
+            roots: header.roots,
+            ..reader
+        })
+    }
+
+    pub fn roots(&self) -> &[Cid] {
+        &self.roots
+    }
+
+    /// Number of blocks in the archive
+    pub fn len(&self) -> u64 {
+        self.footer.entry_count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn entry(&self, index: u64) -> io::Result<(u64, u64)> {
+        let frame = index / INDEX_FRAME_ENTRIES;
+        let offset = self.footer.index_offset
+            + frame * (ZSTD_SKIPPABLE_FRAME_HEADER_LEN + INDEX_FRAME_ENTRIES * INDEX_ENTRY_LEN)
+            + ZSTD_SKIPPABLE_FRAME_HEADER_LEN
+            + (index % INDEX_FRAME_ENTRIES) * INDEX_ENTRY_LEN;
+        let mut entry = [0; INDEX_ENTRY_LEN as usize];
+        self.file.read_exact_at(&mut entry, offset)?;
+        let mut key = [0; 8];
+        let mut frame_offset = [0; 8];
+        key.copy_from_slice(&entry[..8]);
+        frame_offset.copy_from_slice(&entry[8..]);
+        Ok((u64::from_le_bytes(key), u64::from_le_bytes(frame_offset)))
+    }
+
+    /// Decompresses the frame starting at the given offset
+    fn frame(&self, offset: u64) -> io::Result<Arc<Vec<u8>>> {
+        if let Some((cached, frame)) = &*self.cache.lock() {
+            if *cached == offset {
+                return Ok(frame.clone());
+            }
+        }
+        let reader = ReadAt {
+            file: &self.file,
+            offset,
+        };
+        let mut frame = Vec::with_capacity(FRAME_SIZE * 2);
+        zstd::stream::read::Decoder::new(reader)?
+            .single_frame()
+            .read_to_end(&mut frame)?;
+        let frame = Arc::new(frame);
+        *self.cache.lock() = Some((offset, frame.clone()));
+        Ok(frame)
+    }
+
+    /// Offsets of the frames that may hold the block
+    fn frame_offsets(&self, cid: &Cid) -> io::Result<Vec<u64>> {
+        let key = index_key(cid);
+        // First entry whose key isn't lower than the one looked up
+        let (mut low, mut high) = (0, self.footer.entry_count);
+        while low < high {
+            let middle = low + (high - low) / 2;
+            match self.entry(middle)?.0 < key {
+                true => low = middle + 1,
+                false => high = middle,
+            }
+        }
+        let mut offsets = vec![];
+        for index in low..self.footer.entry_count {
+            let (entry_key, frame_offset) = self.entry(index)?;
+            if entry_key != key {
+                break;
+            }
+            offsets.push(frame_offset);
+        }
+        Ok(offsets)
+    }
+}
+
+impl Blockstore for ForestCarReader {
+    fn get(&self, k: &Cid) -> anyhow::Result<Option<Vec<u8>>> {
+        for frame_offset in self.frame_offsets(k)? {
+            let frame = self.frame(frame_offset)?;
+            let mut sections = frame.as_slice();
+            if frame_offset == 0 {
+                let (len, _) = read_varint(&mut sections)?.context("missing CAR header")?;
+                sections = sections
+                    .get(len as usize..)
+                    .context("truncated CAR header")?;
+            }
+            while let Some((len, _)) = read_varint(&mut sections)? {
+                anyhow::ensure!(sections.len() >= len as usize, "truncated CAR section");
+                let (mut section, rest) = sections.split_at(len as usize);
+                sections = rest;
+                if Cid::read_bytes(&mut section)? == *k {
+                    return Ok(Some(section.to_vec()));
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    fn has(&self, k: &Cid) -> anyhow::Result<bool> {
+        Ok(self.get(k)?.is_some())
+    }
+
+    fn put_keyed(&self, _k: &Cid, _block: &[u8]) -> anyhow::Result<()> {
+        anyhow::bail!("CAR files are read-only")
+    }
+}
+
+/// Reader of a file from an offset, not sharing the file cursor
+struct ReadAt<'a> {
+    file: &'a File,
+    offset: u64,
+}
+
+impl Read for ReadAt<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.file.read_at(buf, self.offset)?;
+        self.offset += read as u64;
+        Ok(read)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::genesis::EXPORT_SR_40;
+    use async_compression::futures::bufread::ZstdDecoder;
+    use futures::{io::BufReader as AsyncBufReader, AsyncReadExt};
+    use fvm_ipld_car::CarReader;
+
+    fn forest_car(car_v1: &[u8]) -> (tempfile::TempDir, std::path::PathBuf) {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("export40.forest.car.zst");
+        write_forest_car(car_v1, File::create(&path).unwrap()).unwrap();
+        (dir, path)
+    }
+
+    #[tokio::test]
+    async fn forest_car_roundtrip() {
+        let (_dir, path) = forest_car(EXPORT_SR_40);
+        let reader = ForestCarReader::open(&path).unwrap();
+        let mut original = CarReader::new(EXPORT_SR_40).await.unwrap();
+        assert_eq!(reader.roots(), original.header.roots.as_slice());
+        let mut count = 0;
+        while let Some(block) = original.next_block().await.unwrap() {
+            count += 1;
+            assert_eq!(reader.get(&block.cid).unwrap(), Some(block.data));
+        }
+        assert_eq!(reader.len(), count);
+        assert!(!reader.has(&Cid::default()).unwrap());
+    }
+
+    #[tokio::test]
+    async fn forest_car_is_car_zst() {
+        let (_dir, path) = forest_car(EXPORT_SR_40);
+        let compressed = std::fs::read(&path).unwrap();
+        let mut decoder = ZstdDecoder::new(AsyncBufReader::new(compressed.as_slice()));
+        decoder.multiple_members(true);
+        let mut decompressed = vec![];
+        decoder.read_to_end(&mut decompressed).await.unwrap();
+        assert_eq!(decompressed, EXPORT_SR_40);
+    }
+
+    #[test]
+    fn forest_car_is_deterministic() {
+        let (_dir, first) = forest_car(EXPORT_SR_40);
+        let (_dir, second) = forest_car(EXPORT_SR_40);
+        assert_eq!(
+            std::fs::read(first).unwrap(),
+            std::fs::read(second).unwrap()
+        );
+    }
+
+    #[test]
+    fn footer_of_other_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("export40.car");
+        std::fs::write(&path, EXPORT_SR_40).unwrap();
+        let file = File::open(&path).unwrap();
+        assert_eq!(ForestCarFooter::read(&file).unwrap(), None);
+    }
+}
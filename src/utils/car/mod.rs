@@ -0,0 +1,589 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! [CARv2](https://ipld.io/specs/transport/car/carv2/) support. A CARv2 file
+//! wraps a CARv1 payload, after a fixed size header, and optionally ends with
+//! an index of the blocks of the payload. The index is written in the
+//! `MultihashIndexSorted` format of `go-car`, so that the archives exported by
+//! Forest can be read randomly by other tools.
+
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, BinaryHeap},
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write},
+    os::unix::fs::FileExt,
+    path::Path,
+};
+
+use ahash::{HashMap, HashMapExt};
+use anyhow::Context;
+use cid::Cid;
+use futures::{
+    io::{Cursor, Take},
+    AsyncRead, AsyncReadExt,
+};
+use fvm_ipld_blockstore::Blockstore;
+use fvm_ipld_car::CarHeader;
+
+pub mod forest;
+
+/// Fixed bytes starting CARv2 files, see <https://ipld.io/specs/transport/car/carv2/#pragma>
+pub const CARV2_PRAGMA: [u8; 11] = [
+    0x0a, 0xa1, 0x67, 0x76, 0x65, 0x72, 0x73, 0x69, 0x6f, 0x6e, 0x02,
+];
+
+/// Characteristics, data offset, data size and index offset
+pub const CARV2_HEADER_LEN: usize = 40;
+
+/// Offset of the CARv1 payload in the CARv2 files written by Forest, right
+/// after the header
+pub const CARV2_DATA_OFFSET: u64 = (CARV2_PRAGMA.len() + CARV2_HEADER_LEN) as u64;
+
+/// Multicodec of the `MultihashIndexSorted` index format
+const MULTIHASH_INDEX_SORTED: u64 = 0x0401;
+
+/// Size of the offsets following the digests in the index
+const INDEX_OFFSET_LEN: usize = 8;
+
+/// Number of index records sorted in memory before being spilled to a run
+const INDEX_RUN_LEN: usize = 1 << 20;
+
+/// Header following the pragma of CARv2 files
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct CarV2Header {
+    pub characteristics: [u8; 16],
+    /// Offset of the CARv1 payload from the start of the file
+    pub data_offset: u64,
+    /// Size of the CARv1 payload
+    pub data_size: u64,
+    /// Offset of the index from the start of the file, 0 if there is none
+    pub index_offset: u64,
+}
+
+impl CarV2Header {
+    pub fn from_bytes(bytes: &[u8; CARV2_HEADER_LEN]) -> Self {
+        let read_u64 = |offset: usize| {
+            let mut value = [0; 8];
+            value.copy_from_slice(&bytes[offset..offset + 8]);
+            u64::from_le_bytes(value)
+        };
+        let mut characteristics = [0; 16];
+        characteristics.copy_from_slice(&bytes[..16]);
+        Self {
+            characteristics,
+            data_offset: read_u64(16),
+            data_size: read_u64(24),
+            index_offset: read_u64(32),
+        }
+    }
+
+    pub fn to_bytes(self) -> [u8; CARV2_HEADER_LEN] {
+        let mut bytes = [0; CARV2_HEADER_LEN];
+        bytes[..16].copy_from_slice(&self.characteristics);
+        bytes[16..24].copy_from_slice(&self.data_offset.to_le_bytes());
+        bytes[24..32].copy_from_slice(&self.data_size.to_le_bytes());
+        bytes[32..].copy_from_slice(&self.index_offset.to_le_bytes());
+        bytes
+    }
+}
+
+/// Reader of the CARv1 payload of a CARv1 or CARv2 stream
+pub type CarV1Payload<R> = Take<futures::io::Chain<Cursor<Vec<u8>>, R>>;
+
+/// Returns the version of the CAR stream and the reader of its CARv1 payload,
+/// so that both versions can be streamed with [`fvm_ipld_car::CarReader`].
+/// The CARv2 index, if any, is not read.
+pub async fn car_v1_payload<R>(mut reader: R) -> io::Result<(u64, CarV1Payload<R>)>
+where
+    R: AsyncRead + Unpin,
+{
+    let mut pragma = vec![0; CARV2_PRAGMA.len()];
+    reader.read_exact(&mut pragma).await?;
+    if pragma != CARV2_PRAGMA {
+        return Ok((1, Cursor::new(pragma).chain(reader).take(u64::MAX)));
+    }
+    let mut header = [0; CARV2_HEADER_LEN];
+    reader.read_exact(&mut header).await?;
+    let header = CarV2Header::from_bytes(&header);
+    let skipped = header
+        .data_offset
+        .checked_sub((CARV2_PRAGMA.len() + CARV2_HEADER_LEN) as u64)
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("invalid CARv2 data offset: {}", header.data_offset),
+            )
+        })?;
+    futures::io::copy(&mut (&mut reader).take(skipped), &mut futures::io::sink()).await?;
+    Ok((2, Cursor::new(vec![]).chain(reader).take(header.data_size)))
+}
+
+/// Turns `file`, whose CARv1 payload has been written from
+/// [`CARV2_DATA_OFFSET`] to its end, into a CARv2 file: the index of all the
+/// blocks of the payload is appended, and the pragma and header written before
+/// the payload.
+///
+/// The index records are sorted in runs of bounded size, spilled to temporary
+/// files in `tmp_dir` and merged as the index is written, so that the memory
+/// used doesn't depend on the size of the payload.
+pub fn finish_car_v2(file: &mut File, tmp_dir: &Path) -> anyhow::Result<()> {
+    let car_v1_len = file
+        .metadata()?
+        .len()
+        .checked_sub(CARV2_DATA_OFFSET)
+        .context("missing CARv1 payload")?;
+
+    file.seek(SeekFrom::Start(CARV2_DATA_OFFSET))?;
+    let mut car_v1 = BufReader::new(&*file).take(car_v1_len);
+    let mut index = IndexRuns::new(tmp_dir);
+    let mut offset = 0;
+    let mut section = vec![];
+    let mut is_header = true;
+    while let Some((len, varint)) = read_varint(&mut car_v1)? {
+        section.resize(len as usize, 0);
+        car_v1.read_exact(&mut section)?;
+        if !is_header {
+            let cid = Cid::read_bytes(section.as_slice())
+                .with_context(|| format!("invalid CID at offset {offset}"))?;
+            index.push(IndexRecord {
+                code: cid.hash().code(),
+                digest_len: cid.hash().digest().len(),
+                digest: cid.hash().digest().to_vec(),
+                offset,
+            })?;
+        }
+        is_header = false;
+        offset += (varint.len() + section.len()) as u64;
+    }
+    anyhow::ensure!(
+        offset == car_v1_len,
+        "CARv1 payload is {offset} bytes long, expected {car_v1_len}"
+    );
+
+    let index_offset = CARV2_DATA_OFFSET + car_v1_len;
+    file.seek(SeekFrom::Start(index_offset))?;
+    let mut out = BufWriter::new(&*file);
+    index.write(&mut out)?;
+    out.flush()?;
+    drop(out);
+
+    let header = CarV2Header {
+        data_offset: CARV2_DATA_OFFSET,
+        data_size: car_v1_len,
+        index_offset,
+        ..Default::default()
+    };
+    file.write_all_at(&CARV2_PRAGMA, 0)?;
+    file.write_all_at(&header.to_bytes(), CARV2_PRAGMA.len() as u64)?;
+    file.sync_all()?;
+    Ok(())
+}
+
+/// Record of the index, ordered like in the `MultihashIndexSorted` format: by
+/// multihash code, digest length and digest
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct IndexRecord {
+    code: u64,
+    digest_len: usize,
+    digest: Vec<u8>,
+    offset: u64,
+}
+
+impl IndexRecord {
+    fn write(&self, out: &mut impl Write) -> io::Result<()> {
+        out.write_all(&self.code.to_le_bytes())?;
+        out.write_all(&(self.digest_len as u32).to_le_bytes())?;
+        out.write_all(&self.digest)?;
+        out.write_all(&self.offset.to_le_bytes())
+    }
+
+    /// Reads the next record of a run, `None` at its end
+    fn read(reader: &mut impl Read) -> io::Result<Option<Self>> {
+        let mut code = [0; 8];
+        match reader.read_exact(&mut code) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e),
+        }
+        let mut digest_len = [0; 4];
+        reader.read_exact(&mut digest_len)?;
+        let digest_len = u32::from_le_bytes(digest_len) as usize;
+        let mut digest = vec![0; digest_len];
+        reader.read_exact(&mut digest)?;
+        let mut offset = [0; 8];
+        reader.read_exact(&mut offset)?;
+        Ok(Some(Self {
+            code: u64::from_le_bytes(code),
+            digest_len,
+            digest,
+            offset: u64::from_le_bytes(offset),
+        }))
+    }
+}
+
+/// Index records, sorted in runs spilled to temporary files
+struct IndexRuns<'a> {
+    tmp_dir: &'a Path,
+    records: Vec<IndexRecord>,
+    runs: Vec<File>,
+    /// Number of records by multihash code and digest length
+    counts: BTreeMap<u64, BTreeMap<usize, u64>>,
+}
+
+impl<'a> IndexRuns<'a> {
+    fn new(tmp_dir: &'a Path) -> Self {
+        Self {
+            tmp_dir,
+            records: vec![],
+            runs: vec![],
+            counts: BTreeMap::new(),
+        }
+    }
+
+    fn push(&mut self, record: IndexRecord) -> io::Result<()> {
+        *self
+            .counts
+            .entry(record.code)
+            .or_default()
+            .entry(record.digest_len)
+            .or_default() += 1;
+        self.records.push(record);
+        if self.records.len() >= INDEX_RUN_LEN {
+            self.spill()?;
+        }
+        Ok(())
+    }
+
+    /// Writes the records in memory, sorted, to a new run
+    fn spill(&mut self) -> io::Result<()> {
+        if self.records.is_empty() {
+            return Ok(());
+        }
+        self.records.sort_unstable();
+        let mut run = BufWriter::new(tempfile::tempfile_in(self.tmp_dir)?);
+        for record in self.records.drain(..) {
+            record.write(&mut run)?;
+        }
+        let mut run = run.into_inner().map_err(io::IntoInnerError::into_error)?;
+        run.rewind()?;
+        self.runs.push(run);
+        Ok(())
+    }
+
+    /// Writes the index, merging the runs
+    fn write(mut self, out: &mut impl Write) -> anyhow::Result<()> {
+        self.spill()?;
+        let mut runs: Vec<_> = self.runs.into_iter().map(BufReader::new).collect();
+        let mut heads = BinaryHeap::new();
+        for (i, run) in runs.iter_mut().enumerate() {
+            if let Some(record) = IndexRecord::read(run)? {
+                heads.push(Reverse((record, i)));
+            }
+        }
+        let mut next_record = || -> anyhow::Result<IndexRecord> {
+            let Reverse((record, i)) = heads.pop().context("missing index record")?;
+            if let Some(next) = IndexRecord::read(&mut runs[i])? {
+                heads.push(Reverse((next, i)));
+            }
+            Ok(record)
+        };
+
+        write_varint(out, MULTIHASH_INDEX_SORTED)?;
+        out.write_all(&(self.counts.len() as i32).to_le_bytes())?;
+        for (code, widths) in self.counts {
+            out.write_all(&code.to_le_bytes())?;
+            out.write_all(&(widths.len() as i32).to_le_bytes())?;
+            for (digest_len, count) in widths {
+                let width = digest_len + INDEX_OFFSET_LEN;
+                out.write_all(&(width as u32).to_le_bytes())?;
+                out.write_all(&(count * width as u64).to_le_bytes())?;
+                for _ in 0..count {
+                    let record = next_record()?;
+                    anyhow::ensure!(
+                        record.code == code && record.digest_len == digest_len,
+                        "index records out of order"
+                    );
+                    out.write_all(&record.digest)?;
+                    out.write_all(&record.offset.to_le_bytes())?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Read-only [`Blockstore`] over an indexed CARv2 file. The blocks are read
+/// on demand, using the index left on disk, so that archives of any size can
+/// be opened.
+pub struct IndexedCarReader {
+    file: File,
+    header: CarV2Header,
+    roots: Vec<Cid>,
+    /// Sorted records of the index, by multihash code and digest length
+    buckets: HashMap<(u64, usize), IndexBucket>,
+}
+
+struct IndexBucket {
+    /// Offset of the first record from the start of the file
+    offset: u64,
+    count: u64,
+}
+
+impl IndexedCarReader {
+    pub fn open(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let file = File::open(path.as_ref())?;
+        let mut pragma = [0; CARV2_PRAGMA.len()];
+        file.read_exact_at(&mut pragma, 0)?;
+        anyhow::ensure!(pragma == CARV2_PRAGMA, "not a CARv2 file");
+        let mut header = [0; CARV2_HEADER_LEN];
+        file.read_exact_at(&mut header, CARV2_PRAGMA.len() as u64)?;
+        let header = CarV2Header::from_bytes(&header);
+        anyhow::ensure!(header.index_offset != 0, "the CARv2 file has no index");
+
+        let (len, varint_len) =
+            read_varint_at(&file, header.data_offset)?.context("missing CARv1 header")?;
+        let mut car_header = vec![0; len as usize];
+        file.read_exact_at(&mut car_header, header.data_offset + varint_len as u64)?;
+        let car_header: CarHeader = fvm_ipld_encoding::from_slice(&car_header)?;
+
+        let mut reader = BufReader::new(&file);
+        reader.seek(SeekFrom::Start(header.index_offset))?;
+        let (codec, varint) = read_varint(&mut reader)?.context("missing index codec")?;
+        anyhow::ensure!(
+            codec == MULTIHASH_INDEX_SORTED,
+            "unsupported CARv2 index codec: {codec:#x}"
+        );
+        let mut position = header.index_offset + varint.len() as u64;
+        let mut buckets = HashMap::new();
+        let code_count = i32::from_le_bytes(read_array(&mut reader, &mut position)?);
+        for _ in 0..code_count {
+            let code = u64::from_le_bytes(read_array(&mut reader, &mut position)?);
+            let width_count = i32::from_le_bytes(read_array(&mut reader, &mut position)?);
+            for _ in 0..width_count {
+                let width = u32::from_le_bytes(read_array(&mut reader, &mut position)?) as usize;
+                let size = u64::from_le_bytes(read_array(&mut reader, &mut position)?);
+                anyhow::ensure!(width > INDEX_OFFSET_LEN, "invalid index width: {width}");
+                buckets.insert(
+                    (code, width - INDEX_OFFSET_LEN),
+                    IndexBucket {
+                        offset: position,
+                        count: size / width as u64,
+                    },
+                );
+                reader.seek_relative(size as i64)?;
+                position += size;
+            }
+        }
+
+        Ok(Self {
+            file,
+            header,
+            roots: car_header.roots,
+            buckets,
+        })
+    }
+
+    pub fn roots(&self) -> &[Cid] {
+        &self.roots
+    }
+
+    /// Offset of the block in the CARv1 payload, found by binary search in
+    /// the index
+    fn find(&self, cid: &Cid) -> io::Result<Option<u64>> {
+        let digest = cid.hash().digest();
+        let Some(bucket) = self.buckets.get(&(cid.hash().code(), digest.len())) else {
+            return Ok(None);
+        };
+        let width = (digest.len() + INDEX_OFFSET_LEN) as u64;
+        let mut record = vec![0; width as usize];
+        let (mut low, mut high) = (0, bucket.count);
+        while low < high {
+            let middle = low + (high - low) / 2;
+            self.file
+                .read_exact_at(&mut record, bucket.offset + middle * width)?;
+            match record[..digest.len()].cmp(digest) {
+                std::cmp::Ordering::Less => low = middle + 1,
+                std::cmp::Ordering::Greater => high = middle,
+                std::cmp::Ordering::Equal => {
+                    let mut offset = [0; INDEX_OFFSET_LEN];
+                    offset.copy_from_slice(&record[digest.len()..]);
+                    return Ok(Some(u64::from_le_bytes(offset)));
+                }
+            }
+        }
+        Ok(None)
+    }
+}
+
+impl Blockstore for IndexedCarReader {
+    fn get(&self, k: &Cid) -> anyhow::Result<Option<Vec<u8>>> {
+        let Some(offset) = self.find(k)? else {
+            return Ok(None);
+        };
+        let offset = self.header.data_offset + offset;
+        let (len, varint_len) =
+            read_varint_at(&self.file, offset)?.context("missing CARv2 section")?;
+        let mut section = vec![0; len as usize];
+        self.file
+            .read_exact_at(&mut section, offset + varint_len as u64)?;
+        let mut reader = section.as_slice();
+        let cid = Cid::read_bytes(&mut reader)?;
+        anyhow::ensure!(&cid == k, "CARv2 index points to {cid} instead of {k}");
+        Ok(Some(reader.to_vec()))
+    }
+
+    fn has(&self, k: &Cid) -> anyhow::Result<bool> {
+        Ok(self.find(k)?.is_some())
+    }
+
+    fn put_keyed(&self, _k: &Cid, _block: &[u8]) -> anyhow::Result<()> {
+        anyhow::bail!("CAR files are read-only")
+    }
+}
+
+/// Reads an unsigned varint, returning `None` at the end of the stream, and
+/// the bytes it's made of
+fn read_varint(reader: &mut impl Read) -> io::Result<Option<(u64, Vec<u8>)>> {
+    let mut bytes = vec![];
+    let mut byte = [0];
+    loop {
+        if reader.read(&mut byte)? == 0 {
+            return match bytes.is_empty() {
+                true => Ok(None),
+                false => Err(io::ErrorKind::UnexpectedEof.into()),
+            };
+        }
+        bytes.push(byte[0]);
+        if byte[0] & 0x80 == 0 {
+            let (value, _) = unsigned_varint::decode::u64(&bytes)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            return Ok(Some((value, bytes)));
+        }
+    }
+}
+
+/// Reads an unsigned varint at the given offset of the file, returning its
+/// value and length
+fn read_varint_at(file: &File, offset: u64) -> io::Result<Option<(u64, usize)>> {
+    let mut buffer = [0; 10];
+    let read = file.read_at(&mut buffer, offset)?;
+    if read == 0 {
+        return Ok(None);
+    }
+    let (value, rest) = unsigned_varint::decode::u64(&buffer[..read])
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    Ok(Some((value, read - rest.len())))
+}
+
+fn write_varint(out: &mut impl Write, value: u64) -> io::Result<()> {
+    let mut buffer = unsigned_varint::encode::u64_buffer();
+    out.write_all(unsigned_varint::encode::u64(value, &mut buffer))
+}
+
+fn read_array<const N: usize>(reader: &mut impl Read, position: &mut u64) -> io::Result<[u8; N]> {
+    let mut bytes = [0; N];
+    reader.read_exact(&mut bytes)?;
+    *position += N as u64;
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::genesis::EXPORT_SR_40;
+    use fvm_ipld_car::CarReader;
+
+    #[tokio::test]
+    async fn car_v2_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("export40.car");
+        let mut file = File::options()
+            .read(true)
+            .write(true)
+            .create(true)
+            .open(&path)
+            .unwrap();
+        file.write_all_at(EXPORT_SR_40, CARV2_DATA_OFFSET).unwrap();
+        finish_car_v2(&mut file, dir.path()).unwrap();
+
+        // Streamed as the CARv1 payload
+        let car_v2 = std::fs::read(&path).unwrap();
+        let (version, payload) = car_v1_payload(car_v2.as_slice()).await.unwrap();
+        assert_eq!(version, 2);
+        let mut reader = CarReader::new(payload).await.unwrap();
+        let mut blocks = vec![];
+        while let Some(block) = reader.next_block().await.unwrap() {
+            blocks.push(block);
+        }
+        let mut original = CarReader::new(EXPORT_SR_40).await.unwrap();
+        assert_eq!(reader.header.roots, original.header.roots);
+        let mut count = 0;
+        while let Some(block) = original.next_block().await.unwrap() {
+            count += 1;
+            assert!(blocks.iter().any(|b| b.cid == block.cid));
+        }
+        assert_eq!(blocks.len(), count);
+
+        // Read randomly with the index
+        let indexed = IndexedCarReader::open(&path).unwrap();
+        assert_eq!(indexed.roots(), reader.header.roots.as_slice());
+        for block in blocks {
+            assert_eq!(indexed.get(&block.cid).unwrap(), Some(block.data));
+        }
+        let missing = Cid::default();
+        assert!(!indexed.has(&missing).unwrap());
+    }
+
+    #[test]
+    fn index_runs_are_merged() {
+        let dir = tempfile::tempdir().unwrap();
+        let record = |code, digest: &[u8], offset| IndexRecord {
+            code,
+            digest_len: digest.len(),
+            digest: digest.to_vec(),
+            offset,
+        };
+        let mut index = IndexRuns::new(dir.path());
+        index.push(record(0x12, &[3, 0], 0)).unwrap();
+        index.push(record(0x12, &[1, 0], 1)).unwrap();
+        index.spill().unwrap();
+        index.push(record(0x12, &[2, 0], 2)).unwrap();
+        index.push(record(0x12, &[0], 3)).unwrap();
+        index.push(record(0x11, &[9, 9], 4)).unwrap();
+        let mut out = vec![];
+        index.write(&mut out).unwrap();
+
+        let mut expected = vec![];
+        write_varint(&mut expected, MULTIHASH_INDEX_SORTED).unwrap();
+        expected.extend(2i32.to_le_bytes());
+        expected.extend(0x11u64.to_le_bytes());
+        expected.extend(1i32.to_le_bytes());
+        expected.extend(10u32.to_le_bytes());
+        expected.extend(10u64.to_le_bytes());
+        expected.extend([9, 9]);
+        expected.extend(4u64.to_le_bytes());
+        expected.extend(0x12u64.to_le_bytes());
+        expected.extend(2i32.to_le_bytes());
+        expected.extend(9u32.to_le_bytes());
+        expected.extend(9u64.to_le_bytes());
+        expected.extend([0]);
+        expected.extend(3u64.to_le_bytes());
+        expected.extend(10u32.to_le_bytes());
+        expected.extend(30u64.to_le_bytes());
+        for (digest, offset) in [([1, 0], 1u64), ([2, 0], 2), ([3, 0], 0)] {
+            expected.extend(digest);
+            expected.extend(offset.to_le_bytes());
+        }
+        assert_eq!(out, expected);
+    }
+
+    #[tokio::test]
+    async fn car_v1_payload_of_car_v1() {
+        let (version, payload) = car_v1_payload(EXPORT_SR_40).await.unwrap();
+        assert_eq!(version, 1);
+        let reader = CarReader::new(payload).await.unwrap();
+        let original = CarReader::new(EXPORT_SR_40).await.unwrap();
+        assert_eq!(reader.header.roots, original.header.roots);
+    }
+}
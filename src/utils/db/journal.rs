@@ -0,0 +1,164 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::PathBuf,
+};
+
+use cid::Cid;
+
+/// Write-ahead journal of the records buffered in memory before they are
+/// written to the store. Each record is appended as the length of its key,
+/// the key, the length of its data and the data, the lengths being
+/// little-endian `u32`s. A record cut short by a crash is ignored.
+pub struct Journal {
+    path: PathBuf,
+    file: BufWriter<File>,
+}
+
+impl Journal {
+    /// Opens the journal at `path`, creating it if needed, and returns the
+    /// records left in it by a write that didn't complete.
+    pub fn open(path: impl Into<PathBuf>) -> io::Result<(Self, Vec<(Cid, Vec<u8>)>)> {
+        let path = path.into();
+        let pending = match File::open(&path) {
+            Ok(file) => read_records(BufReader::new(file))?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => vec![],
+            Err(e) => return Err(e),
+        };
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        Ok((
+            Self {
+                path,
+                file: BufWriter::new(file),
+            },
+            pending,
+        ))
+    }
+
+    pub fn append(&mut self, key: &Cid, value: &[u8]) -> io::Result<()> {
+        let key = key.to_bytes();
+        for bytes in [&key[..], value] {
+            let len = u32::try_from(bytes.len())
+                .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "record too large"))?;
+            self.file.write_all(&len.to_le_bytes())?;
+            self.file.write_all(bytes)?;
+        }
+        Ok(())
+    }
+
+    /// Hands the appended records to the operating system, so that they
+    /// survive the process being killed.
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+
+    /// Writes the appended records to the disk, so that they survive the
+    /// machine crashing.
+    pub fn sync(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        self.file.get_ref().sync_data()
+    }
+
+    /// Empties the journal once its records are in the store.
+    pub fn clear(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        self.file.get_ref().set_len(0)
+    }
+
+    /// Deletes the journal once the write completed.
+    pub fn remove(mut self) -> io::Result<()> {
+        self.file.flush()?;
+        std::fs::remove_file(&self.path)
+    }
+}
+
+fn read_records(mut reader: impl Read) -> io::Result<Vec<(Cid, Vec<u8>)>> {
+    let mut records = vec![];
+    loop {
+        let (Some(key), Some(value)) = (read_bytes(&mut reader)?, read_bytes(&mut reader)?) else {
+            return Ok(records);
+        };
+        let Ok(key) = Cid::try_from(key) else {
+            return Ok(records);
+        };
+        records.push((key, value));
+    }
+}
+
+/// Reads bytes prefixed by their length, returning `None` at the end of the
+/// journal or of its last complete record
+fn read_bytes(reader: &mut impl Read) -> io::Result<Option<Vec<u8>>> {
+    let mut len = [0; 4];
+    let mut bytes = vec![];
+    let read = reader.read_exact(&mut len).and_then(|_| {
+        bytes.resize(u32::from_le_bytes(len) as usize, 0);
+        reader.read_exact(&mut bytes)
+    });
+    match read {
+        Ok(()) => Ok(Some(bytes)),
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cid::multihash::{Code, MultihashDigest};
+    use fvm_ipld_encoding::DAG_CBOR;
+
+    fn record(data: &[u8]) -> (Cid, Vec<u8>) {
+        (
+            Cid::new_v1(DAG_CBOR, Code::Blake2b256.digest(data)),
+            data.to_vec(),
+        )
+    }
+
+    #[test]
+    fn pending_records_are_replayed() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("journal");
+        let records = vec![record(b"a"), record(b"bc"), record(b"")];
+
+        let (mut journal, pending) = Journal::open(&path).unwrap();
+        assert!(pending.is_empty());
+        for (key, value) in &records {
+            journal.append(key, value).unwrap();
+        }
+        journal.flush().unwrap();
+        drop(journal);
+
+        let (mut journal, pending) = Journal::open(&path).unwrap();
+        assert_eq!(pending, records);
+        journal.clear().unwrap();
+        let (journal, pending) = Journal::open(&path).unwrap();
+        assert!(pending.is_empty());
+        journal.remove().unwrap();
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn torn_record_is_ignored() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("journal");
+        let (mut journal, _) = Journal::open(&path).unwrap();
+        let (key, value) = record(b"complete");
+        journal.append(&key, &value).unwrap();
+        journal.append(&record(b"torn").0, b"torn").unwrap();
+        journal.flush().unwrap();
+        drop(journal);
+
+        let len = std::fs::metadata(&path).unwrap().len();
+        File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_len(len - 2)
+            .unwrap();
+        let (_, pending) = Journal::open(&path).unwrap();
+        assert_eq!(pending, vec![(key, value)]);
+    }
+}
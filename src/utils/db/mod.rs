@@ -2,7 +2,14 @@
 // SPDX-License-Identifier: Apache-2.0, MIT
 
 pub mod file_backed_obj;
+mod journal;
 
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use crate::metrics::BUFFERED_WRITE_BYTES;
 use async_trait::async_trait;
 use chrono::Utc;
 use cid::{
@@ -14,13 +21,27 @@ use fvm_ipld_encoding::{to_vec, DAG_CBOR};
 use fvm_ipld_encoding3::CborStore;
 use human_repr::HumanCount;
 use log::info;
+use once_cell::sync::Lazy;
 use serde::ser::Serialize;
+use tokio::{sync::RwLock, time::MissedTickBehavior};
+
+use journal::Journal;
 
 /// DB key size in bytes for estimating reachable data size. Use parity-db value
 /// for simplicity. The actual value for other underlying DB might be slightly
 /// different but that is negligible for calculating the total reachable data
 /// size
 pub const DB_KEY_BYTES: usize = 32;
+
+/// Held for reading by the buffered writes in progress
+static BUFFERED_WRITES: Lazy<RwLock<()>> = Lazy::new(Default::default);
+
+/// Waits for the buffered writes in progress to flush their buffers, which
+/// they do as soon as their channel is closed.
+pub async fn wait_for_buffered_writes() {
+    drop(BUFFERED_WRITES.write().await);
+}
+
 /// Extension methods for inserting and retrieving IPLD data with CIDs
 pub trait BlockstoreExt: Blockstore {
     /// Batch put CBOR objects into block store and returns vector of CIDs
@@ -72,31 +93,101 @@ pub trait CborStoreExt: CborStore {
 
 impl<T: CborStore> CborStoreExt for T {}
 
+/// Settings of [`BlockstoreBufferedWriteExt::buffered_write`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BufferedWriteConfig {
+    /// Bytes of records held in memory at most before they are written to
+    /// the store
+    pub capacity_bytes: usize,
+    /// Interval at which the buffered records are written regardless of their
+    /// size, so that a slow stream of records doesn't sit in memory
+    pub flush_interval: Option<Duration>,
+    /// File the records are appended to before they are buffered. The records
+    /// a crashed write left in it are written to the store when the next
+    /// write with the same journal starts.
+    pub journal: Option<PathBuf>,
+}
+
+impl Default for BufferedWriteConfig {
+    fn default() -> Self {
+        Self::with_capacity(1024 * 1024 * 1024)
+    }
+}
+
+impl BufferedWriteConfig {
+    pub fn with_capacity(capacity_bytes: usize) -> Self {
+        Self {
+            capacity_bytes,
+            flush_interval: None,
+            journal: None,
+        }
+    }
+}
+
+/// Writes the records a crashed buffered write left in the journal at `path`
+/// to the store and deletes the journal, if there is one. Returns the number
+/// of records written.
+pub fn replay_journal(db: &impl Blockstore, path: &Path) -> anyhow::Result<usize> {
+    if !path.exists() {
+        return Ok(0);
+    }
+    let (journal, pending) = Journal::open(path)?;
+    let n_records = pending.len();
+    if n_records > 0 {
+        info!(
+            "Writing {n_records} records left by an interrupted write in {}",
+            path.display()
+        );
+        db.put_many_keyed(pending)?;
+    }
+    journal.remove()?;
+    Ok(n_records)
+}
+
 /// Extension methods for buffered write with manageable limit of RAM usage
 #[async_trait]
 pub trait BlockstoreBufferedWriteExt: Blockstore + Sized {
     async fn buffered_write(
         &self,
         rx: flume::Receiver<(Cid, Vec<u8>)>,
-        buffer_capacity_bytes: usize,
+        config: &BufferedWriteConfig,
     ) -> anyhow::Result<()> {
+        let _in_progress = BUFFERED_WRITES.read().await;
         let start = Utc::now();
         let mut total_bytes = 0;
         let mut total_entries = 0;
-        let mut estimated_buffer_bytes = 0;
-        let mut buffer = vec![];
-        while let Ok((key, value)) = rx.recv_async().await {
-            // Key is stored in 32 bytes in paritydb
-            estimated_buffer_bytes += DB_KEY_BYTES + value.len();
-            total_bytes += DB_KEY_BYTES + value.len();
-            total_entries += 1;
-            buffer.push((key, value));
-            if estimated_buffer_bytes >= buffer_capacity_bytes {
-                self.put_many_keyed(std::mem::take(&mut buffer))?;
-                estimated_buffer_bytes = 0;
+        let mut buffer = WriteBuffer::new(self, config)?;
+        let mut ticks = config.flush_interval.map(|period| {
+            let mut ticks = tokio::time::interval_at(tokio::time::Instant::now() + period, period);
+            ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            ticks
+        });
+        loop {
+            let tick = async {
+                match &mut ticks {
+                    Some(ticks) => {
+                        ticks.tick().await;
+                    }
+                    None => std::future::pending().await,
+                }
+            };
+            tokio::select! {
+                received = rx.recv_async() => {
+                    let Ok((key, value)) = received else {
+                        break;
+                    };
+                    // Key is stored in 32 bytes in paritydb
+                    total_bytes += DB_KEY_BYTES + value.len();
+                    total_entries += 1;
+                    buffer.push(key, value)?;
+                    if rx.is_empty() {
+                        buffer.flush_journal()?;
+                    }
+                }
+                _ = tick => buffer.flush()?,
             }
         }
-        self.put_many_keyed(buffer)?;
+        buffer.finish()?;
         info!(
             "Buffered write completed: total entries: {total_entries}, total size: {}, took: {}s",
             total_bytes.human_count_bytes(),
@@ -108,3 +199,140 @@ pub trait BlockstoreBufferedWriteExt: Blockstore + Sized {
 }
 
 impl<T: fvm_ipld_blockstore::Blockstore> BlockstoreBufferedWriteExt for T {}
+
+/// Records waiting to be written to the store, accounted for in
+/// [`BUFFERED_WRITE_BYTES`]
+struct WriteBuffer<'a, DB> {
+    db: &'a DB,
+    capacity_bytes: usize,
+    records: Vec<(Cid, Vec<u8>)>,
+    /// Memory held by the records
+    bytes: usize,
+    journal: Option<Journal>,
+}
+
+impl<'a, DB: Blockstore> WriteBuffer<'a, DB> {
+    /// Opens the journal, if any, writing the records it has left to the
+    /// store.
+    fn new(db: &'a DB, config: &BufferedWriteConfig) -> anyhow::Result<Self> {
+        let journal = match &config.journal {
+            Some(path) => {
+                replay_journal(db, path)?;
+                Some(Journal::open(path)?.0)
+            }
+            None => None,
+        };
+        Ok(Self {
+            db,
+            capacity_bytes: config.capacity_bytes,
+            records: vec![],
+            bytes: 0,
+            journal,
+        })
+    }
+
+    fn push(&mut self, key: Cid, value: Vec<u8>) -> anyhow::Result<()> {
+        if let Some(journal) = &mut self.journal {
+            journal.append(&key, &value)?;
+        }
+        let bytes = std::mem::size_of::<(Cid, Vec<u8>)>() + value.capacity();
+        self.bytes += bytes;
+        BUFFERED_WRITE_BYTES.add(bytes as u64);
+        self.records.push((key, value));
+        if self.bytes >= self.capacity_bytes {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    fn flush_journal(&mut self) -> anyhow::Result<()> {
+        if let Some(journal) = &mut self.journal {
+            journal.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Writes the buffered records to the store, after the journal to the
+    /// disk.
+    fn flush(&mut self) -> anyhow::Result<()> {
+        if self.records.is_empty() {
+            return Ok(());
+        }
+        if let Some(journal) = &mut self.journal {
+            journal.sync()?;
+        }
+        self.db.put_many_keyed(std::mem::take(&mut self.records))?;
+        BUFFERED_WRITE_BYTES.sub(std::mem::take(&mut self.bytes) as u64);
+        if let Some(journal) = &mut self.journal {
+            journal.clear()?;
+        }
+        Ok(())
+    }
+
+    fn finish(mut self) -> anyhow::Result<()> {
+        self.flush()?;
+        if let Some(journal) = self.journal.take() {
+            journal.remove()?;
+        }
+        Ok(())
+    }
+}
+
+impl<DB> Drop for WriteBuffer<'_, DB> {
+    fn drop(&mut self) {
+        BUFFERED_WRITE_BYTES.sub(self.bytes as u64);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::MemoryDB;
+
+    #[tokio::test]
+    async fn buffered_write_flushes_on_interval() {
+        let db = MemoryDB::default();
+        let (tx, rx) = flume::unbounded();
+        let config = BufferedWriteConfig {
+            flush_interval: Some(Duration::from_millis(10)),
+            ..Default::default()
+        };
+        let write = {
+            let db = db.clone();
+            tokio::spawn(async move { db.buffered_write(rx, &config).await })
+        };
+
+        let data = b"record".to_vec();
+        let cid = Cid::new_v1(DAG_CBOR, Code::Blake2b256.digest(&data));
+        tx.send((cid, data)).unwrap();
+        // Far below the capacity of the buffer
+        while !db.has(&cid).unwrap() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        drop(tx);
+        write.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn buffered_write_replays_journal() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("journal");
+        let data = b"left over".to_vec();
+        let cid = Cid::new_v1(DAG_CBOR, Code::Blake2b256.digest(&data));
+        let (mut journal, _) = Journal::open(&path).unwrap();
+        journal.append(&cid, &data).unwrap();
+        journal.flush().unwrap();
+        drop(journal);
+
+        let db = MemoryDB::default();
+        let (tx, rx) = flume::unbounded();
+        drop(tx);
+        let config = BufferedWriteConfig {
+            journal: Some(path.clone()),
+            ..Default::default()
+        };
+        db.buffered_write(rx, &config).await.unwrap();
+        assert_eq!(db.get(&cid).unwrap(), Some(data));
+        assert!(!path.exists());
+    }
+}
@@ -3,6 +3,7 @@
 
 pub mod parser;
 pub mod progress_bar;
+mod rotating_file;
 mod tempfile;
 mod writer_checksum;
 
@@ -13,6 +14,7 @@ use std::{
 };
 
 pub use progress_bar::{ProgressBar, ProgressBarVisibility};
+pub use rotating_file::RotatingFile;
 pub use writer_checksum::*;
 
 pub use self::tempfile::*;
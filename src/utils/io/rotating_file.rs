@@ -0,0 +1,164 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use parking_lot::Mutex;
+use tracing_subscriber::fmt::MakeWriter;
+
+/// Append-only file rotated when it grows too large or too old. On rotation
+/// `forest.log` is renamed to `forest.log.1`, `forest.log.1` to
+/// `forest.log.2` and so on, up to `max_files` rotated files.
+pub struct RotatingFile {
+    path: PathBuf,
+    /// Size in bytes above which the file is rotated
+    max_size: Option<u64>,
+    /// Age above which the file is rotated
+    max_age: Option<Duration>,
+    max_files: usize,
+    current: Mutex<Current>,
+}
+
+struct Current {
+    file: File,
+    size: u64,
+    opened_at: Instant,
+}
+
+impl RotatingFile {
+    pub fn open(
+        path: impl Into<PathBuf>,
+        max_size: Option<u64>,
+        max_age: Option<Duration>,
+        max_files: usize,
+    ) -> io::Result<Self> {
+        let path = path.into();
+        let current = Current::open(&path)?;
+        Ok(Self {
+            path,
+            max_size,
+            max_age,
+            max_files,
+            current: Mutex::new(current),
+        })
+    }
+
+    fn rotated_path(&self, index: usize) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(format!(".{index}"));
+        path.into()
+    }
+
+    fn rotate(&self, current: &mut Current) -> io::Result<()> {
+        current.file.flush()?;
+        if self.max_files == 0 {
+            fs::remove_file(&self.path)?;
+        } else {
+            for index in (1..self.max_files).rev() {
+                let from = self.rotated_path(index);
+                if from.exists() {
+                    fs::rename(from, self.rotated_path(index + 1))?;
+                }
+            }
+            fs::rename(&self.path, self.rotated_path(1))?;
+        }
+        *current = Current::open(&self.path)?;
+        Ok(())
+    }
+
+    fn must_rotate(&self, current: &Current, len: usize) -> bool {
+        // A line larger than the limit is written to a file of its own
+        if current.size == 0 {
+            return false;
+        }
+        let too_large = self
+            .max_size
+            .map(|max_size| current.size + len as u64 > max_size)
+            .unwrap_or_default();
+        let too_old = self
+            .max_age
+            .map(|max_age| current.opened_at.elapsed() >= max_age)
+            .unwrap_or_default();
+        too_large || too_old
+    }
+}
+
+impl Current {
+    fn open(path: &Path) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let size = file.metadata()?.len();
+        Ok(Self {
+            file,
+            size,
+            opened_at: Instant::now(),
+        })
+    }
+}
+
+impl Write for &RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut current = self.current.lock();
+        if self.must_rotate(&current, buf.len()) {
+            self.rotate(&mut current)?;
+        }
+        let written = current.file.write(buf)?;
+        current.size += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.current.lock().file.flush()
+    }
+}
+
+impl<'a> MakeWriter<'a> for RotatingFile {
+    type Writer = &'a RotatingFile;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rotates_by_size() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("forest.log");
+        let file = RotatingFile::open(&path, Some(10), None, 2).unwrap();
+        for line in ["first\n", "second\n", "third\n", "fourth\n"] {
+            (&file).write_all(line.as_bytes()).unwrap();
+        }
+        assert_eq!(fs::read_to_string(&path).unwrap(), "fourth\n");
+        assert_eq!(
+            fs::read_to_string(dir.path().join("forest.log.1")).unwrap(),
+            "third\n"
+        );
+        assert_eq!(
+            fs::read_to_string(dir.path().join("forest.log.2")).unwrap(),
+            "second\n"
+        );
+        assert!(!dir.path().join("forest.log.3").exists());
+    }
+
+    #[test]
+    fn rotates_by_age() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("forest.log");
+        let file = RotatingFile::open(&path, None, Some(Duration::ZERO), 1).unwrap();
+        (&file).write_all(b"first\n").unwrap();
+        (&file).write_all(b"second\n").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "second\n");
+        assert_eq!(
+            fs::read_to_string(dir.path().join("forest.log.1")).unwrap(),
+            "first\n"
+        );
+    }
+}
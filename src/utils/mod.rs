@@ -1,6 +1,7 @@
 // Copyright 2019-2023 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
+pub mod car;
 pub mod cid;
 pub mod db;
 pub mod encoding;
@@ -9,6 +10,7 @@ pub mod json;
 pub mod misc;
 pub mod monitoring;
 pub mod net;
+pub mod operations;
 pub mod proofs_api;
 pub mod version;
 
@@ -19,7 +19,7 @@ use pin_project_lite::pin_project;
 use thiserror::Error;
 use url::Url;
 
-use super::https_client;
+use super::{fetch_object, https_client, ObjectStoreUrl};
 use crate::utils::{io::ProgressBar, misc::Either};
 
 // https://github.com/facebook/zstd/blob/dev/doc/zstd_compression_format.md#zstandard-frames
@@ -69,7 +69,9 @@ impl FetchProgress<DownloadStream> {
 impl FetchProgress<ZstdDecoder<DownloadStream>> {
     pub async fn fetch_zstd_compressed_from_url(url: &Url) -> anyhow::Result<Self> {
         let (inner, progress_bar) = fetch_stream_from_url(url).await?;
-        let inner = ZstdDecoder::new(inner);
+        let mut inner = ZstdDecoder::new(inner);
+        // `.forest.car.zst` archives are made of many frames
+        inner.multiple_members(true);
         Ok(FetchProgress {
             inner,
             progress_bar,
@@ -102,7 +104,8 @@ impl FetchProgress<ZstdDecoder<BufReader<async_fs::File>>> {
         pb.set_units(crate::utils::io::progress_bar::Units::Bytes);
         pb.set_max_refresh_rate(Some(Duration::from_millis(500)));
 
-        let inner = ZstdDecoder::new(BufReader::new(file));
+        let mut inner = ZstdDecoder::new(BufReader::new(file));
+        inner.multiple_members(true);
 
         Ok(FetchProgress {
             progress_bar: pb,
@@ -159,6 +162,42 @@ pub async fn get_fetch_progress_from_url(
     }
 }
 
+/// A reader of an object in a bucket, see [`fetch_object`]
+pub type ObjectStream = Pin<Box<dyn AsyncRead + Send>>;
+
+pub async fn get_fetch_progress_from_object_store(
+    url: &str,
+) -> anyhow::Result<
+    Either<FetchProgress<ObjectStream>, FetchProgress<ZstdDecoder<BufReader<ObjectStream>>>>,
+> {
+    let (mut stream, total_size) = fetch_object(&ObjectStoreUrl::parse(url)?).await?;
+    let mut header = [0; ZSTD_MAGIC_HEADER.len()];
+    stream.read_exact(&mut header).await?;
+    let is_zstd_compressed = header == ZSTD_MAGIC_HEADER;
+    log::info!("Loading {url}, is_zstd_compressed: {is_zstd_compressed}");
+    // The header is read once, and put back in front of the stream
+    let stream: ObjectStream = Box::pin(futures::io::Cursor::new(header).chain(stream));
+
+    let pb = ProgressBar::new(total_size);
+    pb.message("Downloading/Importing snapshot ");
+    pb.set_units(crate::utils::io::progress_bar::Units::Bytes);
+    pb.set_max_refresh_rate(Some(Duration::from_millis(500)));
+
+    if is_zstd_compressed {
+        let mut inner = ZstdDecoder::new(BufReader::new(stream));
+        inner.multiple_members(true);
+        Ok(Either::Right(FetchProgress {
+            inner,
+            progress_bar: pb,
+        }))
+    } else {
+        Ok(Either::Left(FetchProgress {
+            inner: stream,
+            progress_bar: pb,
+        }))
+    }
+}
+
 async fn fetch_stream_from_url(url: &Url) -> anyhow::Result<(DownloadStream, ProgressBar)> {
     let client = https_client();
     let url = {
@@ -3,9 +3,10 @@
 
 mod download;
 mod http;
+mod object_store;
 
 // re-exports hyper
 pub use hyper;
 pub use hyper_rustls;
 
-pub use self::{download::*, http::*};
+pub use self::{download::*, http::*, object_store::*};
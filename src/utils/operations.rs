@@ -0,0 +1,309 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Registry of the long-running operations of the node, such as snapshot
+//! exports and garbage collections, so that their progress can be monitored
+//! and they can be cancelled over RPC. Each operation gets an ID when it
+//! starts, and stays in the registry for a while after it ends to tell how it
+//! ended.
+
+use std::{
+    collections::BTreeMap,
+    future::Future,
+    sync::{atomic, Arc},
+};
+
+use crate::ipld::ProgressBarCurrentTotalPair;
+use chrono::Utc;
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use tokio_util::sync::CancellationToken;
+
+/// Number of ended operations kept in the registry
+const MAX_ENDED_OPERATIONS: usize = 32;
+
+static OPERATIONS: Lazy<Mutex<Registry>> = Lazy::new(Default::default);
+
+#[derive(Default)]
+struct Registry {
+    next_id: u64,
+    operations: BTreeMap<u64, Arc<Operation>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, strum::Display)]
+pub enum OperationKind {
+    SnapshotExport,
+    DatabaseGarbageCollection,
+    ReceiptBackfill,
+    StateMigration,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, strum::Display)]
+pub enum OperationState {
+    Running,
+    /// Cancelled, stopping at its next await point
+    Cancelling,
+    Completed,
+    Failed,
+    Cancelled,
+}
+
+/// Status of an operation, as reported over RPC
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct OperationStatus {
+    pub id: u64,
+    pub kind: OperationKind,
+    pub description: String,
+    pub state: OperationState,
+    /// Unix timestamps, in seconds, of the start and end of the operation
+    pub started: i64,
+    pub ended: Option<i64>,
+    /// Progress of the operation, in units of its own such as blocks or
+    /// epochs, of `total`, 0 if unknown
+    pub current: u64,
+    pub total: u64,
+    pub cancellable: bool,
+    pub error: Option<String>,
+}
+
+impl OperationStatus {
+    /// Progress of the operation in percent, if its total is known
+    pub fn percent(&self) -> Option<f64> {
+        (self.total > 0).then(|| 100. * self.current.min(self.total) as f64 / self.total as f64)
+    }
+}
+
+/// The error of an operation that was cancelled
+#[derive(Debug, thiserror::Error)]
+#[error("{0} was cancelled")]
+pub struct OperationCancelled(pub OperationKind);
+
+/// An operation in progress or ended
+pub struct Operation {
+    id: u64,
+    kind: OperationKind,
+    description: String,
+    started: i64,
+    progress: ProgressBarCurrentTotalPair,
+    cancellable: bool,
+    cancel: CancellationToken,
+    /// How the operation ended, and when
+    end: Mutex<Option<(OperationState, Option<String>, i64)>>,
+}
+
+impl Operation {
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Progress the operation reports, as its current and total
+    pub fn progress(&self) -> &ProgressBarCurrentTotalPair {
+        &self.progress
+    }
+
+    pub fn set_progress(&self, current: u64, total: u64) {
+        self.progress.0.store(current, atomic::Ordering::Relaxed);
+        self.progress.1.store(total, atomic::Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel.is_cancelled()
+    }
+
+    /// Records how the operation ended, with the result of its work.
+    pub fn end<T>(&self, result: &anyhow::Result<T>) {
+        let state = match result {
+            Ok(_) => OperationState::Completed,
+            Err(e) if e.is::<OperationCancelled>() => OperationState::Cancelled,
+            Err(_) => OperationState::Failed,
+        };
+        let error = result.as_ref().err().map(|e| format!("{e:#}"));
+        *self.end.lock() = Some((state, error, Utc::now().timestamp()));
+        prune_ended();
+    }
+
+    pub fn status(&self) -> OperationStatus {
+        let (state, error, ended) = match &*self.end.lock() {
+            Some((state, error, ended)) => (*state, error.clone(), Some(*ended)),
+            None if self.is_cancelled() => (OperationState::Cancelling, None, None),
+            None => (OperationState::Running, None, None),
+        };
+        OperationStatus {
+            id: self.id,
+            kind: self.kind,
+            description: self.description.clone(),
+            state,
+            started: self.started,
+            ended,
+            current: self.progress.0.load(atomic::Ordering::Relaxed),
+            total: self.progress.1.load(atomic::Ordering::Relaxed),
+            cancellable: self.cancellable,
+            error,
+        }
+    }
+}
+
+/// Registers an operation that can't be cancelled, which the caller reports
+/// the end of with [`Operation::end`].
+pub fn start(kind: OperationKind, description: impl Into<String>) -> Arc<Operation> {
+    register(kind, description.into(), None, false)
+}
+
+/// Runs the work of a cancellable operation, reporting its progress in
+/// `progress` if given. On cancellation, the work is dropped at its current
+/// await point and [`OperationCancelled`] is returned.
+pub async fn run<T, F, Fut>(
+    kind: OperationKind,
+    description: impl Into<String>,
+    progress: Option<ProgressBarCurrentTotalPair>,
+    work: F,
+) -> anyhow::Result<T>
+where
+    F: FnOnce(Arc<Operation>) -> Fut,
+    Fut: Future<Output = anyhow::Result<T>>,
+{
+    let operation = register(kind, description.into(), progress, true);
+    let cancel = operation.cancel.clone();
+    let result = tokio::select! {
+        result = work(operation.clone()) => result,
+        _ = cancel.cancelled() => Err(OperationCancelled(kind).into()),
+    };
+    operation.end(&result);
+    result
+}
+
+fn register(
+    kind: OperationKind,
+    description: String,
+    progress: Option<ProgressBarCurrentTotalPair>,
+    cancellable: bool,
+) -> Arc<Operation> {
+    let progress = progress.unwrap_or_default();
+    // The trackers shared with other operations of the same kind start over
+    progress.0.store(0, atomic::Ordering::Relaxed);
+    progress.1.store(0, atomic::Ordering::Relaxed);
+    let mut registry = OPERATIONS.lock();
+    registry.next_id += 1;
+    let operation = Arc::new(Operation {
+        id: registry.next_id,
+        kind,
+        description,
+        started: Utc::now().timestamp(),
+        progress,
+        cancellable,
+        cancel: CancellationToken::new(),
+        end: Mutex::new(None),
+    });
+    registry.operations.insert(operation.id, operation.clone());
+    operation
+}
+
+/// Drops the oldest ended operations beyond [`MAX_ENDED_OPERATIONS`].
+fn prune_ended() {
+    let mut registry = OPERATIONS.lock();
+    let ended: Vec<u64> = registry
+        .operations
+        .values()
+        .filter(|operation| operation.end.lock().is_some())
+        .map(|operation| operation.id)
+        .collect();
+    for id in ended
+        .iter()
+        .take(ended.len().saturating_sub(MAX_ENDED_OPERATIONS))
+    {
+        registry.operations.remove(id);
+    }
+}
+
+/// Returns the statuses of the operations in progress and of the recently
+/// ended ones, in the order they started.
+pub fn statuses() -> Vec<OperationStatus> {
+    OPERATIONS
+        .lock()
+        .operations
+        .values()
+        .map(|operation| operation.status())
+        .collect()
+}
+
+/// Cancels an operation in progress, failing if there is no such operation
+/// or if it can't be cancelled.
+pub fn cancel(id: u64) -> anyhow::Result<()> {
+    let operation = OPERATIONS
+        .lock()
+        .operations
+        .get(&id)
+        .cloned()
+        .ok_or_else(|| anyhow::anyhow!("no operation {id}"))?;
+    if !operation.cancellable {
+        anyhow::bail!("{} can't be cancelled", operation.kind);
+    }
+    if operation.end.lock().is_some() {
+        anyhow::bail!("operation {id} already ended");
+    }
+    operation.cancel.cancel();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn status(id: u64) -> OperationStatus {
+        statuses().into_iter().find(|s| s.id == id).unwrap()
+    }
+
+    #[tokio::test]
+    async fn cancel_running_operation() {
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        let task = tokio::spawn(run(
+            OperationKind::SnapshotExport,
+            "export",
+            None,
+            |operation| async move {
+                tx.send(operation.id()).unwrap();
+                operation.set_progress(1, 4);
+                std::future::pending::<()>().await;
+                Ok(())
+            },
+        ));
+        let id = rx.await.unwrap();
+        let running = status(id);
+        assert_eq!(running.state, OperationState::Running);
+        assert_eq!(running.percent(), Some(25.));
+
+        cancel(id).unwrap();
+        let error = task.await.unwrap().unwrap_err();
+        assert!(error.is::<OperationCancelled>());
+        assert_eq!(status(id).state, OperationState::Cancelled);
+        cancel(id).unwrap_err();
+    }
+
+    #[tokio::test]
+    async fn operations_report_how_they_ended() {
+        let id = run(
+            OperationKind::DatabaseGarbageCollection,
+            "gc",
+            None,
+            |operation| async move {
+                tokio::time::sleep(Duration::from_millis(1)).await;
+                Ok(operation.id())
+            },
+        )
+        .await
+        .unwrap();
+        let completed = status(id);
+        assert_eq!(completed.state, OperationState::Completed);
+        assert!(completed.ended.is_some());
+
+        let migration = start(OperationKind::StateMigration, "migration");
+        cancel(migration.id()).unwrap_err();
+        migration.end(&Err::<(), _>(anyhow::anyhow!("boom")));
+        let failed = status(migration.id());
+        assert_eq!(failed.state, OperationState::Failed);
+        assert_eq!(failed.error.as_deref(), Some("boom"));
+    }
+}
@@ -0,0 +1,176 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Dedicated thread pool for proof and signature verification. Verifying a
+//! winning PoSt or a BLS aggregate takes tens of milliseconds of CPU time,
+//! running them on the `tokio` blocking pool or the async executor starves
+//! the other tasks during epochs with many proving messages.
+//!
+//! The verification libraries may panic on crafted inputs. In the builds that
+//! unwind, a job that panics fails with a [`VerificationPanic`] instead of
+//! taking the node down. Release builds abort on panic, so the callers also
+//! check the inputs the libraries expect to be well-formed before submitting
+//! the jobs.
+
+use std::{
+    panic::{catch_unwind, AssertUnwindSafe},
+    time::Instant,
+};
+
+use lazy_static::lazy_static;
+use prometheus::{core::Opts, HistogramOpts, HistogramVec, IntGauge};
+use rayon::prelude::*;
+use thiserror::Error;
+use tokio::sync::oneshot;
+
+pub mod kinds {
+    pub const BLOCK_SIGNATURE: &str = "block_signature";
+    pub const BLS_AGGREGATE: &str = "bls_aggregate";
+    pub const SECP_SIGNATURES: &str = "secp_signatures";
+    pub const WINNING_POST: &str = "winning_post";
+}
+
+lazy_static! {
+    static ref POOL: rayon::ThreadPool = rayon::ThreadPoolBuilder::new()
+        .num_threads(num_cpus::get())
+        .thread_name(|i| format!("proof-verifier-{i}"))
+        .build()
+        .expect("Building the proof verification thread pool must succeed");
+    pub static ref PROOF_VERIFICATION_QUEUE_DEPTH: Box<IntGauge> = {
+        let queue_depth = Box::new(
+            IntGauge::new(
+                "proof_verification_queue_depth",
+                "Number of verification jobs waiting for a thread",
+            )
+            .expect("Defining the proof_verification_queue_depth metric must succeed"),
+        );
+        prometheus::default_registry().register(queue_depth.clone()).expect(
+            "Registering the proof_verification_queue_depth metric with the metrics registry must succeed",
+        );
+        queue_depth
+    };
+    pub static ref PROOF_VERIFICATION_TIME: Box<HistogramVec> = {
+        let verification_time = Box::new(
+            HistogramVec::new(
+                HistogramOpts {
+                    common_opts: Opts::new(
+                        "proof_verification_time",
+                        "Duration of verification jobs, including the time spent in the queue",
+                    ),
+                    buckets: vec![],
+                },
+                &["kind"],
+            )
+            .expect("Defining the proof_verification_time metric must succeed"),
+        );
+        prometheus::default_registry().register(verification_time.clone()).expect(
+            "Registering the proof_verification_time metric with the metrics registry must succeed",
+        );
+        verification_time
+    };
+}
+
+/// Error of a verification job that panicked
+#[derive(Debug, Error)]
+#[error("{kind} verification panicked: {message}")]
+pub struct VerificationPanic {
+    kind: &'static str,
+    message: String,
+}
+
+impl VerificationPanic {
+    fn new(kind: &'static str, payload: Box<dyn std::any::Any + Send>) -> Self {
+        let message = if let Some(message) = payload.downcast_ref::<&str>() {
+            message.to_string()
+        } else if let Some(message) = payload.downcast_ref::<String>() {
+            message.clone()
+        } else {
+            "unknown panic".into()
+        };
+        Self { kind, message }
+    }
+}
+
+/// Runs a verification job on the verification thread pool.
+pub async fn verify<T, F>(kind: &'static str, job: F) -> Result<T, VerificationPanic>
+where
+    T: Send + 'static,
+    F: FnOnce() -> T + Send + 'static,
+{
+    let mut results = verify_batch(kind, vec![job]).await;
+    results.pop().expect("one result per job")
+}
+
+/// Runs a batch of verification jobs of the same kind on the verification
+/// thread pool, in parallel, and returns their results in order.
+pub async fn verify_batch<T, F>(
+    kind: &'static str,
+    jobs: Vec<F>,
+) -> Vec<Result<T, VerificationPanic>>
+where
+    T: Send + 'static,
+    F: FnOnce() -> T + Send + 'static,
+{
+    let timer = PROOF_VERIFICATION_TIME
+        .with_label_values(&[kind])
+        .start_timer();
+    let (tx, rx) = oneshot::channel();
+    let job_count = jobs.len();
+    PROOF_VERIFICATION_QUEUE_DEPTH.inc();
+    let queued_at = Instant::now();
+    POOL.spawn(move || {
+        PROOF_VERIFICATION_QUEUE_DEPTH.dec();
+        log::trace!(
+            "{kind} verification waited {:?} in the queue",
+            queued_at.elapsed()
+        );
+        let results = jobs
+            .into_par_iter()
+            .map(|job| {
+                catch_unwind(AssertUnwindSafe(job))
+                    .map_err(|payload| VerificationPanic::new(kind, payload))
+            })
+            .collect();
+        // The receiver is gone if the validation was cancelled
+        let _ = tx.send(results);
+    });
+    let results = rx.await.unwrap_or_else(|_| {
+        (0..job_count)
+            .map(|_| {
+                Err(VerificationPanic {
+                    kind,
+                    message: "verification job dropped".into(),
+                })
+            })
+            .collect()
+    });
+    timer.observe_duration();
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn batch_results_are_ordered() {
+        let jobs = (0..100).map(|i| move || i * 2).collect();
+        let results: Vec<_> = verify_batch("test", jobs)
+            .await
+            .into_iter()
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(results, (0..100).map(|i| i * 2).collect::<Vec<_>>());
+        assert_eq!(verify("test", || 42).await.unwrap(), 42);
+    }
+
+    #[tokio::test]
+    async fn panics_are_errors() {
+        let jobs: Vec<Box<dyn FnOnce() -> u32 + Send>> =
+            vec![Box::new(|| 1), Box::new(|| panic!("crafted proof"))];
+        let results = verify_batch("test", jobs).await;
+        assert_eq!(results[0].as_ref().unwrap(), &1);
+        let err = results[1].as_ref().unwrap_err();
+        assert_eq!(err.to_string(), "test verification panicked: crafted proof");
+    }
+}
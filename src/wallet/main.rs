@@ -0,0 +1,53 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use std::ffi::OsString;
+
+use crate::cli_shared::{
+    cli::{CliOpts, DaemonConfig, LogConfig},
+    logger,
+};
+use crate::rpc_client::chain_get_name;
+use crate::shim::address::{CurrentNetwork, Network};
+use clap::Parser;
+
+use super::subcommands::{wallet_cmd::WalletBackend, Cli};
+
+pub fn main<ArgT>(args: impl IntoIterator<Item = ArgT>) -> anyhow::Result<()>
+where
+    ArgT: Into<OsString> + Clone,
+{
+    // Capture Cli inputs
+    let Cli {
+        token,
+        remote_wallet,
+        keystore_dir,
+        encrypt_keystore,
+        cmd,
+    } = Cli::parse_from(args);
+
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .unwrap()
+        .block_on(async {
+            logger::setup_logger(
+                &LogConfig::default(),
+                &DaemonConfig::default(),
+                &CliOpts::default(),
+            );
+            // The addresses are printed with the prefix of the network of the
+            // node
+            if let Ok(name) = chain_get_name((), &token).await {
+                if name != "mainnet" {
+                    CurrentNetwork::set_global(Network::Testnet);
+                }
+            }
+            let backend = if remote_wallet {
+                WalletBackend::Remote
+            } else {
+                WalletBackend::open_local(keystore_dir, encrypt_keystore).await?
+            };
+            cmd.run(backend, &token).await
+        })
+}
@@ -0,0 +1,52 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+pub mod wallet_cmd;
+
+use std::path::PathBuf;
+
+use crate::cli_shared::cli::HELP_MESSAGE;
+use crate::utils::version::FOREST_VERSION_STRING;
+use clap::{ArgAction, Parser};
+
+use self::wallet_cmd::WalletCommands;
+
+/// Wallet keeping the keys on this machine. The node set in
+/// `FULLNODE_API_INFO` is only queried for nonces, gas estimates and balances,
+/// and receives the messages once signed.
+#[derive(Parser)]
+#[command(name = "forest-wallet", author = env!("CARGO_PKG_AUTHORS"), version = FOREST_VERSION_STRING.as_str(), about = "Manage the keys of Filecoin accounts and send messages through a Forest node")]
+#[command(help_template(HELP_MESSAGE))]
+pub struct Cli {
+    /// Client JWT token to use for JSON-RPC authentication
+    #[arg(short, long)]
+    pub token: Option<String>,
+    /// Use the wallet of the node instead of the local keystore
+    #[arg(long)]
+    pub remote_wallet: bool,
+    /// Directory of the local keystore, defaults to the `forest-wallet` data
+    /// directory of the user
+    #[arg(long)]
+    pub keystore_dir: Option<PathBuf>,
+    /// Encrypt the local keystore, `--encrypt-keystore false` keeps it in
+    /// plain text. The passphrase is read from `FOREST_KEYSTORE_PHRASE` if
+    /// set, otherwise prompted for
+    #[arg(long, default_value_t = true, action = ArgAction::Set)]
+    pub encrypt_keystore: bool,
+    #[command(subcommand)]
+    pub cmd: WalletCommands,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keystore_encryption_can_be_disabled() {
+        let cli = Cli::try_parse_from(["forest-wallet", "list"]).unwrap();
+        assert!(cli.encrypt_keystore);
+        let cli =
+            Cli::try_parse_from(["forest-wallet", "--encrypt-keystore", "false", "list"]).unwrap();
+        assert!(!cli.encrypt_keystore);
+    }
+}
@@ -0,0 +1,490 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use std::{
+    path::PathBuf,
+    str::{self, FromStr},
+};
+
+use crate::blocks::{tipset_keys_json::TipsetKeysJson, TipsetKeys};
+use crate::cli::{humantoken, humantoken::TokenAmountPretty as _, subcommands::handle_rpc_err};
+use crate::json::{
+    address::json::AddressJson, message::json::MessageJson,
+    signature::json::signature_type::SignatureTypeJson, signed_message::json::SignedMessageJson,
+};
+use crate::key_management::{
+    json::KeyInfoJson, KeyInfo, KeyStore, KeyStoreConfig, Wallet, ENCRYPTED_KEYSTORE_NAME,
+    FOREST_KEYSTORE_PHRASE_ENV,
+};
+use crate::message::{Message as _, SignedMessage};
+use crate::rpc_client::{gas_ops::*, mpool_ops::*, wallet_ops::*};
+use crate::shim::{
+    address::{Address, Protocol, StrictAddress},
+    crypto::{Signature, SignatureType},
+    econ::TokenAmount,
+    message::Message,
+};
+use crate::utils::io::read_file_to_string;
+use anyhow::Context;
+use base64::{prelude::BASE64_STANDARD, Engine};
+use clap::{arg, Subcommand};
+use dialoguer::{theme::ColorfulTheme, Password};
+use directories::ProjectDirs;
+use fvm_ipld_encoding::Cbor;
+use fvm_shared3::{message::Message as Message_v3, METHOD_SEND};
+use num::{BigInt, Zero as _};
+
+/// Where the keys are kept
+pub enum WalletBackend {
+    /// Keystore on this machine, the keys never leave it
+    Local(Wallet),
+    /// Wallet of the node
+    Remote,
+}
+
+impl WalletBackend {
+    /// Opens, or creates, the local keystore in `keystore_dir`
+    pub async fn open_local(
+        keystore_dir: Option<PathBuf>,
+        encrypt_keystore: bool,
+    ) -> anyhow::Result<Self> {
+        let keystore_dir = match keystore_dir {
+            Some(keystore_dir) => keystore_dir,
+            None => ProjectDirs::from("com", "ChainSafe", "Forest-Wallet")
+                .context("failed to find the data directory, please set --keystore-dir")?
+                .data_dir()
+                .to_path_buf(),
+        };
+        let keystore = if encrypt_keystore {
+            let passphrase = match std::env::var(FOREST_KEYSTORE_PHRASE_ENV) {
+                Ok(passphrase) => passphrase,
+                Err(_) => {
+                    let exists = keystore_dir.join(ENCRYPTED_KEYSTORE_NAME).exists();
+                    tokio::task::spawn_blocking(move || {
+                        let theme = ColorfulTheme::default();
+                        let mut prompt = Password::with_theme(&theme);
+                        prompt.with_prompt("Enter the password for the wallet keystore");
+                        if !exists {
+                            prompt.with_confirmation(
+                                "Confirm password",
+                                "Error: the passwords do not match.",
+                            );
+                        }
+                        prompt.interact()
+                    })
+                    .await??
+                }
+            };
+            KeyStore::new(KeyStoreConfig::Encrypted(keystore_dir, passphrase))
+                .context("Couldn't open the wallet keystore")?
+        } else {
+            KeyStore::new(KeyStoreConfig::Persistent(keystore_dir))?
+        };
+        Ok(Self::Local(Wallet::new(keystore)))
+    }
+
+    async fn new_address(
+        &mut self,
+        signature_type: SignatureType,
+        token: &Option<String>,
+    ) -> anyhow::Result<String> {
+        match self {
+            Self::Local(wallet) => Ok(wallet.generate_addr(signature_type)?.to_string()),
+            Self::Remote => wallet_new((SignatureTypeJson(signature_type),), token)
+                .await
+                .map_err(handle_rpc_err),
+        }
+    }
+
+    async fn default_address(&self, token: &Option<String>) -> anyhow::Result<Option<String>> {
+        match self {
+            Self::Local(wallet) => Ok(wallet.get_default().ok().map(|addr| addr.to_string())),
+            Self::Remote => wallet_default_address((), token)
+                .await
+                .map_err(handle_rpc_err),
+        }
+    }
+
+    async fn export(&mut self, address: String, token: &Option<String>) -> anyhow::Result<KeyInfo> {
+        match self {
+            Self::Local(wallet) => Ok(wallet.export(&Address::from_str(&address)?)?),
+            Self::Remote => Ok(wallet_export((address,), token)
+                .await
+                .map_err(handle_rpc_err)?
+                .0),
+        }
+    }
+
+    async fn has(&mut self, address: String, token: &Option<String>) -> anyhow::Result<bool> {
+        match self {
+            Self::Local(wallet) => Ok(wallet.has_key(&Address::from_str(&address)?)),
+            Self::Remote => wallet_has((address,), token).await.map_err(handle_rpc_err),
+        }
+    }
+
+    async fn import(
+        &mut self,
+        key_info: KeyInfo,
+        token: &Option<String>,
+    ) -> anyhow::Result<String> {
+        match self {
+            Self::Local(wallet) => Ok(wallet.import(key_info)?.to_string()),
+            Self::Remote => wallet_import(vec![KeyInfoJson(key_info)], token)
+                .await
+                .map_err(handle_rpc_err),
+        }
+    }
+
+    async fn list(&self, token: &Option<String>) -> anyhow::Result<Vec<Address>> {
+        match self {
+            Self::Local(wallet) => Ok(wallet.list_addrs()?),
+            Self::Remote => Ok(wallet_list((), token)
+                .await
+                .map_err(handle_rpc_err)?
+                .into_iter()
+                .map(|address| address.0)
+                .collect()),
+        }
+    }
+
+    async fn set_default(
+        &mut self,
+        address: Address,
+        token: &Option<String>,
+    ) -> anyhow::Result<()> {
+        match self {
+            Self::Local(wallet) => wallet.set_default(address),
+            Self::Remote => wallet_set_default((AddressJson(address),), token)
+                .await
+                .map_err(handle_rpc_err),
+        }
+    }
+
+    async fn sign(
+        &mut self,
+        address: Address,
+        message: Vec<u8>,
+        token: &Option<String>,
+    ) -> anyhow::Result<Signature> {
+        match self {
+            Self::Local(wallet) => Ok(wallet.sign(&address, &message)?),
+            Self::Remote => Ok(wallet_sign(
+                (
+                    AddressJson(address),
+                    BASE64_STANDARD.encode(message).into_bytes(),
+                ),
+                token,
+            )
+            .await
+            .map_err(handle_rpc_err)?
+            .0),
+        }
+    }
+
+    /// Pushes the message to the node. With the local keystore the nonce and
+    /// gas are queried from the node and the message is signed locally.
+    async fn send(
+        &mut self,
+        message: Message,
+        token: &Option<String>,
+    ) -> anyhow::Result<SignedMessage> {
+        let wallet = match self {
+            Self::Local(wallet) => wallet,
+            Self::Remote => {
+                return Ok(mpool_push_message((MessageJson(message), None), token)
+                    .await
+                    .map_err(handle_rpc_err)?
+                    .0)
+            }
+        };
+        let mut message = message;
+        message.sequence = mpool_get_nonce((AddressJson(message.from()),), token)
+            .await
+            .map_err(handle_rpc_err)?;
+        let MessageJson(message) = gas_estimate_message_gas(
+            (
+                MessageJson(message),
+                None,
+                TipsetKeysJson(TipsetKeys::default()),
+            ),
+            token,
+        )
+        .await
+        .map_err(handle_rpc_err)?;
+        let signature = wallet.sign(&message.from(), message.cid()?.to_bytes().as_slice())?;
+        let signed_message = SignedMessage::new_from_parts(message, signature)?;
+        mpool_push((SignedMessageJson(signed_message.clone()),), token)
+            .await
+            .map_err(handle_rpc_err)?;
+        Ok(signed_message)
+    }
+}
+
+#[derive(Debug, Subcommand)]
+pub enum WalletCommands {
+    /// Create a new wallet
+    New {
+        /// The signature type to use. One of SECP256k1, or BLS
+        #[arg(default_value = "secp256k1")]
+        signature_type: String,
+    },
+    /// Get account balance
+    Balance {
+        /// The address of the account to check
+        address: String,
+    },
+    /// Get the default address of the wallet
+    Default,
+    /// Export the wallet's keys
+    Export {
+        /// The address that contains the keys to export
+        address: String,
+    },
+    /// Check if the wallet has a key
+    Has {
+        /// The key to check
+        key: String,
+    },
+    /// Import keys from existing wallet
+    Import {
+        /// The path to the private key
+        path: Option<String>,
+    },
+    /// List addresses of the wallet
+    List {
+        /// Output is rounded to 4 significant figures by default.
+        /// Do not round
+        #[arg(long, alias = "exact-balance", short_alias = 'e')]
+        no_round: bool,
+        /// Output may be given an SI prefix like `atto` by default.
+        /// Do not do this, showing whole FIL at all times.
+        #[arg(long, alias = "fixed-unit", short_alias = 'f')]
+        no_abbrev: bool,
+    },
+    /// Set the default wallet address
+    SetDefault {
+        /// The given key to set to the default address
+        key: String,
+    },
+    /// Sign a message
+    Sign {
+        /// The hex encoded message to sign
+        #[arg(short)]
+        message: String,
+        /// The address to be used to sign the message
+        #[arg(short)]
+        address: String,
+    },
+    /// Verify the signature of a message. Returns true if the signature matches
+    /// the message and address
+    Verify {
+        /// The address used to sign the message
+        #[arg(short)]
+        address: String,
+        /// The message to verify
+        #[arg(short)]
+        message: String,
+        /// The signature of the message to verify
+        #[arg(short)]
+        signature: String,
+    },
+    /// Send funds between accounts
+    Send {
+        /// optionally specify the account to send funds from (otherwise the
+        /// default one will be used)
+        #[arg(long)]
+        from: Option<String>,
+        target_address: String,
+        #[arg(value_parser = humantoken::parse)]
+        amount: TokenAmount,
+        #[arg(long, value_parser = humantoken::parse, default_value_t = TokenAmount::zero())]
+        gas_feecap: TokenAmount,
+        /// In milliGas
+        #[arg(long, default_value_t = 0)]
+        gas_limit: i64,
+        #[arg(long, value_parser = humantoken::parse, default_value_t = TokenAmount::zero())]
+        gas_premium: TokenAmount,
+    },
+}
+
+impl WalletCommands {
+    pub async fn run(
+        self,
+        mut backend: WalletBackend,
+        token: &Option<String>,
+    ) -> anyhow::Result<()> {
+        match self {
+            Self::New { signature_type } => {
+                let signature_type = match signature_type.to_lowercase().as_str() {
+                    "secp256k1" => SignatureType::Secp256k1,
+                    _ => SignatureType::BLS,
+                };
+                let response = backend.new_address(signature_type, token).await?;
+                println!("{response}");
+                Ok(())
+            }
+            Self::Balance { address } => {
+                let response = wallet_balance((address,), token)
+                    .await
+                    .map_err(handle_rpc_err)?;
+                println!("{response}");
+                Ok(())
+            }
+            Self::Default => {
+                let response = backend
+                    .default_address(token)
+                    .await?
+                    .unwrap_or_else(|| "No default wallet address set".to_string());
+                println!("{response}");
+                Ok(())
+            }
+            Self::Export { address } => {
+                let key_info = backend.export(address, token).await?;
+                let encoded_key = serde_json::to_string(&KeyInfoJson(key_info))?;
+                println!("{}", hex::encode(encoded_key));
+                Ok(())
+            }
+            Self::Has { key } => {
+                println!("{}", backend.has(key, token).await?);
+                Ok(())
+            }
+            Self::Import { path } => {
+                let key = match path {
+                    Some(path) => read_file_to_string(&PathBuf::from(path))?,
+                    _ => {
+                        tokio::task::spawn_blocking(|| {
+                            Password::with_theme(&ColorfulTheme::default())
+                                .allow_empty_password(true)
+                                .with_prompt("Enter the private key")
+                                .interact()
+                        })
+                        .await??
+                    }
+                };
+
+                let key = key.trim();
+
+                let decoded_key = hex::decode(key).context("Key must be hex encoded")?;
+
+                let key_str = str::from_utf8(&decoded_key)?;
+
+                let key: KeyInfoJson =
+                    serde_json::from_str(key_str).context("invalid key format")?;
+
+                let key = backend.import(key.0, token).await?;
+
+                println!("{key}");
+                Ok(())
+            }
+            Self::List {
+                no_round,
+                no_abbrev,
+            } => {
+                let addresses = backend.list(token).await?;
+                let default = backend.default_address(token).await?;
+
+                let (title_address, title_default_mark, title_balance) =
+                    ("Address", "Default", "Balance");
+                println!("{title_address:41} {title_default_mark:7} {title_balance}");
+
+                for address in addresses {
+                    let addr = address.to_string();
+                    let default_address_mark = if default.as_ref() == Some(&addr) {
+                        "X"
+                    } else {
+                        ""
+                    };
+
+                    let balance_string = wallet_balance((addr.clone(),), token)
+                        .await
+                        .map_err(handle_rpc_err)?;
+
+                    let balance_token_amount =
+                        TokenAmount::from_atto(balance_string.parse::<BigInt>()?);
+
+                    let balance_string = match (no_round, no_abbrev) {
+                        // no_round, absolute
+                        (true, true) => format!("{:#}", balance_token_amount.pretty()),
+                        // no_round, relative
+                        (true, false) => format!("{}", balance_token_amount.pretty()),
+                        // round, absolute
+                        (false, true) => format!("{:#.4}", balance_token_amount.pretty()),
+                        // round, relative
+                        (false, false) => format!("{:.4}", balance_token_amount.pretty()),
+                    };
+
+                    println!("{addr:41}  {default_address_mark:7}  {balance_string}");
+                }
+                Ok(())
+            }
+            Self::SetDefault { key } => {
+                let StrictAddress(key) = StrictAddress::from_str(&key)
+                    .with_context(|| format!("Invalid address: {key}"))?;
+                backend.set_default(key, token).await
+            }
+            Self::Sign { address, message } => {
+                let StrictAddress(address) = StrictAddress::from_str(&address)
+                    .with_context(|| format!("Invalid address: {address}"))?;
+
+                let message = hex::decode(message).context("Message has to be a hex string")?;
+
+                let signature = backend.sign(address, message, token).await?;
+                println!("{}", hex::encode(signature.bytes()));
+                Ok(())
+            }
+            Self::Verify {
+                message,
+                address,
+                signature,
+            } => {
+                let sig_bytes =
+                    hex::decode(signature).context("Signature has to be a hex string")?;
+                let StrictAddress(address) = StrictAddress::from_str(&address)
+                    .with_context(|| format!("Invalid address: {address}"))?;
+                let signature = match address.protocol() {
+                    Protocol::Secp256k1 => Signature::new_secp256k1(sig_bytes),
+                    Protocol::BLS => Signature::new_bls(sig_bytes),
+                    _ => anyhow::bail!("Invalid signature (must be bls or secp256k1)"),
+                };
+                let msg = hex::decode(message).context("Message has to be a hex string")?;
+
+                // Verifying doesn't need the keys, nor the node
+                println!("{}", signature.verify(&msg, &address).is_ok());
+                Ok(())
+            }
+            Self::Send {
+                from,
+                target_address,
+                amount,
+                gas_feecap,
+                gas_limit,
+                gas_premium,
+            } => {
+                let from: Address = if let Some(from) = from {
+                    StrictAddress::from_str(&from)?.into()
+                } else {
+                    Address::from_str(&backend.default_address(token).await?.ok_or_else(|| {
+                        anyhow::anyhow!(
+                            "No default wallet address selected. Please set a default address."
+                        )
+                    })?)?
+                };
+
+                let message = Message_v3 {
+                    from: from.into(),
+                    to: StrictAddress::from_str(&target_address)?.into(),
+                    value: amount.into(),
+                    method_num: METHOD_SEND,
+                    gas_limit: gas_limit as u64,
+                    gas_fee_cap: gas_feecap.into(),
+                    gas_premium: gas_premium.into(),
+                    ..Default::default()
+                };
+
+                let signed_message = backend.send(message.into(), token).await?;
+                println!("{}", signed_message.cid()?);
+                Ok(())
+            }
+        }
+    }
+}
@@ -0,0 +1,540 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Notifications of the messages sent from or to watched addresses, when they
+//! enter the message pool, when they land on chain and when the tipset
+//! including them is reverted. The events are kept in memory for the clients
+//! polling or subscribing to them over RPC, and posted as JSON to the webhook
+//! of the watch if it has one. The watches are persisted across restarts.
+
+use std::{collections::VecDeque, path::PathBuf, sync::Arc, time::Duration};
+
+use crate::blocks::{Tipset, TipsetKeys};
+use crate::chain::{messages_for_tipset, reorg_ops, ChainStore, HeadChange};
+use crate::events::{self, ChainEvent};
+use crate::message::{Message as MessageTrait, SignedMessage};
+use crate::shim::{address::Address, clock::ChainEpoch};
+use crate::utils::db::file_backed_obj::{FileBacked, FileBackedObject};
+use cid::Cid;
+use fvm_ipld_blockstore::Blockstore;
+use fvm_ipld_encoding::Cbor;
+use log::warn;
+use parking_lot::{Mutex, RwLock};
+use serde::{Deserialize, Serialize};
+use tokio::{
+    sync::broadcast::{self, error::RecvError},
+    task::JoinSet,
+};
+
+/// Name of the file in the chain data directory the watches are persisted to
+pub const WATCHES_FILE_NAME: &str = "watches.json";
+
+/// Number of the most recent events kept in memory
+const MAX_EVENTS: usize = 4096;
+
+/// Number of the events waiting to be posted to the webhooks, the events
+/// beyond it are not posted
+const MAX_QUEUED_POSTS: usize = 1024;
+
+/// Time after which a webhook post is abandoned
+const POST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Largest number of epochs of missed head changes the watcher catches up
+/// on, the events of the tipsets further away are not reported
+const MAX_CATCH_UP_EPOCHS: u64 = 100;
+
+/// A watched address, and where its events are posted
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Watch {
+    #[serde(with = "crate::json::address::json")]
+    pub address: Address,
+    /// ID of the actor at the address, if it existed when the watch was
+    /// registered, as messages may refer to it by either
+    #[serde(with = "crate::json::address::json::opt")]
+    pub id: Option<Address>,
+    pub webhook: Option<String>,
+}
+
+impl Watch {
+    fn matches(&self, address: &Address) -> bool {
+        self.address == *address || self.id.as_ref() == Some(address)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum WatchEventKind {
+    /// The message entered the message pool
+    Pending,
+    /// The message was included in a tipset
+    Included,
+    /// The tipset including the message was reverted
+    Reverted,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct WatchEvent {
+    /// Sequence number of the event, increasing from 1
+    pub seq: u64,
+    pub kind: WatchEventKind,
+    /// The watched address the message was sent from or to
+    #[serde(with = "crate::json::address::json")]
+    pub address: Address,
+    #[serde(with = "crate::json::cid")]
+    pub message: Cid,
+    #[serde(with = "crate::json::address::json")]
+    pub from: Address,
+    #[serde(with = "crate::json::address::json")]
+    pub to: Address,
+    /// Epoch of the tipset including the message, or of the reverted one
+    pub epoch: Option<ChainEpoch>,
+}
+
+#[derive(Default)]
+struct Events {
+    next_seq: u64,
+    events: VecDeque<WatchEvent>,
+}
+
+/// Registry of the watched addresses and of their recent events
+pub struct AddressWatcher {
+    watches: RwLock<Vec<Watch>>,
+    /// File the watches are persisted to, if any
+    path: Option<PathBuf>,
+    events: Mutex<Events>,
+    /// Events waiting to be posted, with the webhook to post them to
+    posts: (
+        flume::Sender<(String, WatchEvent)>,
+        flume::Receiver<(String, WatchEvent)>,
+    ),
+    /// Events sent to the subscribers as they are recorded
+    live: broadcast::Sender<WatchEvent>,
+}
+
+impl Default for AddressWatcher {
+    fn default() -> Self {
+        Self {
+            watches: Default::default(),
+            path: None,
+            events: Default::default(),
+            posts: flume::bounded(MAX_QUEUED_POSTS),
+            live: broadcast::channel(MAX_EVENTS).0,
+        }
+    }
+}
+
+impl AddressWatcher {
+    /// Loads the watches persisted to the given file, or creates an empty
+    /// registry. The watches are written to the file as they change.
+    pub fn load_or_create(path: PathBuf) -> anyhow::Result<Self> {
+        let watches = FileBacked::load_from_file_or_create(path.clone(), Watches::default, None)?;
+        Ok(Self {
+            watches: RwLock::new(watches.inner().0.clone()),
+            path: Some(path),
+            ..Default::default()
+        })
+    }
+
+    /// Watches an address, replacing its previous watch if any.
+    pub fn watch(&self, watch: Watch) -> anyhow::Result<()> {
+        let mut watches = self.watches.write();
+        watches.retain(|w| w.address != watch.address);
+        watches.push(watch);
+        self.persist(&watches)
+    }
+
+    /// Stops watching an address, returns `false` if it wasn't watched.
+    pub fn unwatch(&self, address: &Address) -> anyhow::Result<bool> {
+        let mut watches = self.watches.write();
+        let len = watches.len();
+        watches.retain(|w| w.address != *address);
+        if watches.len() == len {
+            return Ok(false);
+        }
+        self.persist(&watches)?;
+        Ok(true)
+    }
+
+    /// Writes the watches to the file of the registry, if it has one. The
+    /// caller holds the write lock, so that the writes happen in order.
+    fn persist(&self, watches: &[Watch]) -> anyhow::Result<()> {
+        match &self.path {
+            Some(path) => FileBacked::new(Watches(watches.to_vec()), path.clone()).sync(),
+            None => Ok(()),
+        }
+    }
+
+    pub fn watches(&self) -> Vec<Watch> {
+        self.watches.read().clone()
+    }
+
+    /// Returns up to `limit` of the events following the one numbered
+    /// `after`, oldest first.
+    pub fn events_after(&self, after: u64, limit: usize) -> Vec<WatchEvent> {
+        self.events
+            .lock()
+            .events
+            .iter()
+            .filter(|event| event.seq > after)
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+
+    /// Subscribes to the events recorded from now on.
+    pub fn subscribe(&self) -> broadcast::Receiver<WatchEvent> {
+        self.live.subscribe()
+    }
+
+    /// Records the events of a message for the watches it matches, and posts
+    /// them to their webhooks.
+    fn notify(
+        &self,
+        kind: WatchEventKind,
+        message: Cid,
+        from: Address,
+        to: Address,
+        epoch: Option<ChainEpoch>,
+    ) {
+        let matched: Vec<Watch> = self
+            .watches
+            .read()
+            .iter()
+            .filter(|watch| watch.matches(&from) || watch.matches(&to))
+            .cloned()
+            .collect();
+        if kind == WatchEventKind::Included && !matched.is_empty() {
+            events::publish(ChainEvent::MessageLanded {
+                message,
+                epoch: epoch.unwrap_or_default(),
+            });
+        }
+        for watch in matched {
+            let event = {
+                let mut events = self.events.lock();
+                events.next_seq += 1;
+                let event = WatchEvent {
+                    seq: events.next_seq,
+                    kind,
+                    address: watch.address,
+                    message,
+                    from,
+                    to,
+                    epoch,
+                };
+                events.events.push_back(event.clone());
+                if events.events.len() > MAX_EVENTS {
+                    events.events.pop_front();
+                }
+                event
+            };
+            // No subscribers is not an error
+            let _ = self.live.send(event.clone());
+            if let Some(webhook) = watch.webhook {
+                if self.posts.0.try_send((webhook.clone(), event)).is_err() {
+                    warn!("Too many events waiting to be posted, dropped one for {webhook}");
+                }
+            }
+        }
+    }
+
+    /// Notifies the messages of the watched addresses in a tipset, included or
+    /// reverted.
+    fn notify_tipset<DB: Blockstore>(
+        &self,
+        kind: WatchEventKind,
+        db: &DB,
+        tipset: &Tipset,
+    ) -> anyhow::Result<()> {
+        if self.watches.read().is_empty() {
+            return Ok(());
+        }
+        for message in messages_for_tipset(db, tipset)? {
+            match message.cid() {
+                Ok(cid) => self.notify(
+                    kind,
+                    cid,
+                    message.from(),
+                    message.to(),
+                    Some(tipset.epoch()),
+                ),
+                Err(e) => warn!("Failed to notify a message at {}: {e}", tipset.epoch()),
+            }
+        }
+        Ok(())
+    }
+
+    /// Notifies the messages of the watched addresses entering the message
+    /// pool, received from `pending`, and the ones of the tipsets applied and
+    /// reverted by the head changes.
+    pub async fn notify_loop<DB>(
+        self: Arc<Self>,
+        chain_store: Arc<ChainStore<DB>>,
+        mut pending: broadcast::Receiver<SignedMessage>,
+    ) -> anyhow::Result<()>
+    where
+        DB: Blockstore + Send + Sync + 'static,
+    {
+        let client = reqwest::Client::builder().timeout(POST_TIMEOUT).build()?;
+        let mut posts = JoinSet::new();
+        posts.spawn(post_loop(client, self.posts.1.clone()));
+
+        let mut head = chain_store.heaviest_tipset();
+        let mut head_changes = chain_store.subscribe_head_changes_from(head.epoch() + 1)?;
+        loop {
+            tokio::select! {
+                head_change = head_changes.recv() => match head_change {
+                    Ok(change) => {
+                        let load_tipset = |tsk: &TipsetKeys| chain_store.tipset_from_keys(tsk);
+                        match follow_head_change(&head, change, load_tipset) {
+                            Ok((new_head, steps)) => {
+                                head = new_head;
+                                for step in steps {
+                                    self.notify_step(chain_store.blockstore(), step);
+                                }
+                            }
+                            Err(e) => warn!("Failed to follow a head change: {e}"),
+                        }
+                    }
+                    Err(RecvError::Lagged(n)) => {
+                        // Replaying from the last head followed, the head
+                        // changes missed are caught up on
+                        warn!("Address watcher skipped {n} head changes");
+                        head_changes = chain_store
+                            .subscribe_head_changes_from(head.epoch() + 1)
+                            .or_else(|_| {
+                                let epoch = chain_store.heaviest_tipset().epoch();
+                                chain_store.subscribe_head_changes_from(epoch + 1)
+                            })?;
+                    }
+                    Err(RecvError::Closed) => return Ok(()),
+                },
+                message = pending.recv() => match message {
+                    Ok(message) => match message.cid() {
+                        Ok(cid) => {
+                            let (from, to) = (message.from(), message.to());
+                            self.notify(WatchEventKind::Pending, cid, from, to, None);
+                        }
+                        Err(e) => warn!("Failed to notify a pending message: {e}"),
+                    },
+                    Err(RecvError::Lagged(n)) => warn!("Address watcher skipped {n} messages"),
+                    Err(RecvError::Closed) => return Ok(()),
+                },
+            }
+        }
+    }
+
+    fn notify_step<DB: Blockstore>(&self, db: &DB, step: HeadChange) {
+        let (kind, tipset) = match step {
+            HeadChange::Revert(tipset) => (WatchEventKind::Reverted, tipset),
+            HeadChange::Apply(tipset) | HeadChange::Current(tipset) => {
+                (WatchEventKind::Included, tipset)
+            }
+        };
+        if let Err(e) = self.notify_tipset(kind, db, &tipset) {
+            warn!("Failed to notify the messages at {}: {e}", tipset.epoch());
+        }
+    }
+}
+
+/// Returns the new head after a head change from `head`, and the tipsets
+/// reverted and applied to reach it. The head changes that were missed in
+/// between, by lagging behind or before subscribing, are included, unless they
+/// span more than [`MAX_CATCH_UP_EPOCHS`].
+fn follow_head_change<F, E>(
+    head: &Arc<Tipset>,
+    change: HeadChange,
+    mut load_tipset: F,
+) -> Result<(Arc<Tipset>, Vec<HeadChange>), E>
+where
+    F: FnMut(&TipsetKeys) -> Result<Arc<Tipset>, E>,
+{
+    let new_head = match change {
+        HeadChange::Revert(tipset) => load_tipset(tipset.parents())?,
+        HeadChange::Apply(tipset) | HeadChange::Current(tipset) => tipset,
+    };
+    if new_head.epoch().abs_diff(head.epoch()) > MAX_CATCH_UP_EPOCHS {
+        warn!(
+            "Address watcher jumped from epoch {} to {}, the events in between are not reported",
+            head.epoch(),
+            new_head.epoch()
+        );
+        return Ok((new_head, vec![]));
+    }
+    let steps = reorg_ops(load_tipset, head.clone(), new_head.clone())?;
+    Ok((new_head, steps))
+}
+
+/// Posts the queued events to their webhooks, one at a time.
+async fn post_loop(
+    client: reqwest::Client,
+    posts: flume::Receiver<(String, WatchEvent)>,
+) -> anyhow::Result<()> {
+    while let Ok((webhook, event)) = posts.recv_async().await {
+        post(&client, &webhook, &event).await;
+    }
+    Ok(())
+}
+
+async fn post(client: &reqwest::Client, webhook: &str, event: &WatchEvent) {
+    let result = async {
+        client
+            .post(webhook)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(serde_json::to_vec(event)?)
+            .send()
+            .await?
+            .error_for_status()?;
+        anyhow::Ok(())
+    }
+    .await;
+    if let Err(e) = result {
+        warn!("Failed to post event {} to {webhook}: {e}", event.seq);
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+struct Watches(Vec<Watch>);
+
+impl FileBackedObject for Watches {
+    fn serialize(&self) -> anyhow::Result<Vec<u8>> {
+        Ok(serde_json::to_vec(self)?)
+    }
+
+    fn deserialize(bytes: &[u8]) -> anyhow::Result<Self> {
+        Ok(serde_json::from_slice(bytes)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::ChainGenerator;
+    use ahash::HashMap;
+    use cid::multihash::{Code, MultihashDigest};
+    use tempfile::TempDir;
+
+    fn cid(data: &[u8]) -> Cid {
+        Cid::new_v1(fvm_ipld_encoding::DAG_CBOR, Code::Blake2b256.digest(data))
+    }
+
+    #[test]
+    fn events_of_watched_addresses() {
+        let watcher = AddressWatcher::default();
+        let mut live = watcher.subscribe();
+        watcher
+            .watch(Watch {
+                address: Address::new_id(1000),
+                id: None,
+                webhook: None,
+            })
+            .unwrap();
+        watcher
+            .watch(Watch {
+                address: Address::new_bls(&[1; 48]).unwrap(),
+                id: Some(Address::new_id(1001)),
+                webhook: None,
+            })
+            .unwrap();
+
+        let (from, to) = (Address::new_id(1001), Address::new_id(1000));
+        watcher.notify(WatchEventKind::Pending, cid(b"a"), from, to, None);
+        watcher.notify(
+            WatchEventKind::Included,
+            cid(b"b"),
+            from,
+            Address::new_id(99),
+            Some(10),
+        );
+        watcher.notify(
+            WatchEventKind::Included,
+            cid(b"c"),
+            Address::new_id(98),
+            Address::new_id(99),
+            Some(10),
+        );
+
+        let events = watcher.events_after(0, 10);
+        assert_eq!(
+            events
+                .iter()
+                .map(|e| (e.seq, e.message))
+                .collect::<Vec<_>>(),
+            [(1, cid(b"a")), (2, cid(b"a")), (3, cid(b"b"))]
+        );
+        assert_eq!(events[2].address, Address::new_bls(&[1; 48]).unwrap());
+        assert_eq!(watcher.events_after(2, 10).len(), 1);
+        assert_eq!(live.try_recv().unwrap(), events[0]);
+
+        assert!(watcher.unwatch(&Address::new_id(1000)).unwrap());
+        assert!(!watcher.unwatch(&Address::new_id(1000)).unwrap());
+        assert_eq!(watcher.watches().len(), 1);
+    }
+
+    #[test]
+    fn watches_persisted_across_restarts() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join(WATCHES_FILE_NAME);
+        let watch = Watch {
+            address: Address::new_id(1000),
+            id: None,
+            webhook: Some("http://localhost:8080/events".into()),
+        };
+
+        let watcher = AddressWatcher::load_or_create(path.clone()).unwrap();
+        watcher.watch(watch.clone()).unwrap();
+        watcher
+            .watch(Watch {
+                address: Address::new_id(1001),
+                id: None,
+                webhook: None,
+            })
+            .unwrap();
+        assert!(watcher.unwatch(&Address::new_id(1001)).unwrap());
+
+        let watcher = AddressWatcher::load_or_create(path).unwrap();
+        assert_eq!(watcher.watches(), [watch]);
+    }
+
+    #[test]
+    fn missed_head_changes_are_followed() {
+        let mut generator = ChainGenerator::new();
+        let genesis = generator.genesis().clone();
+        let main = generator.extend(&genesis, &[1000], 4);
+        let fork = generator.extend(&main[1], &[1001], 3);
+        let tipsets: HashMap<_, _> = [&genesis]
+            .into_iter()
+            .chain(&main)
+            .chain(&fork)
+            .map(|ts| (ts.key().clone(), ts.clone()))
+            .collect();
+        let load = |tsk: &TipsetKeys| tipsets.get(tsk).cloned().ok_or(());
+        let epochs = |steps: Vec<HeadChange>| -> Vec<_> {
+            steps
+                .iter()
+                .map(|step| match step {
+                    HeadChange::Revert(ts) => -ts.epoch(),
+                    HeadChange::Apply(ts) | HeadChange::Current(ts) => ts.epoch(),
+                })
+                .collect()
+        };
+
+        // The tipsets of the fork applied before the last one were missed
+        let (head, steps) =
+            follow_head_change(&main[3], HeadChange::Apply(fork[2].clone()), load).unwrap();
+        assert_eq!(head, fork[2]);
+        assert_eq!(epochs(steps), [-4, -3, 3, 4, 5]);
+
+        let (head, steps) =
+            follow_head_change(&head, HeadChange::Revert(fork[2].clone()), load).unwrap();
+        assert_eq!(head, fork[1]);
+        assert_eq!(epochs(steps), [-5]);
+
+        let (head, steps) =
+            follow_head_change(&head, HeadChange::Apply(fork[2].clone()), load).unwrap();
+        assert_eq!(head, fork[2]);
+        assert_eq!(epochs(steps), [5]);
+    }
+}